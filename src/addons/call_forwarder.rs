@@ -6,7 +6,7 @@ use alloy_rpc_types_eth::{
     state::{EvmOverrides, StateOverride},
 };
 use jsonrpsee::{
-    http_client::{HttpClient, HttpClientBuilder},
+    http_client::HttpClient,
     proc_macros::rpc,
     rpc_params,
     types::{ErrorObject, error::INTERNAL_ERROR_CODE},
@@ -15,6 +15,22 @@ use jsonrpsee_core::{ClientError, RpcResult, async_trait, client::ClientT};
 use reth_rpc::eth::EthApiTypes;
 use reth_rpc_eth_api::{RpcTxReq, helpers::EthCall};
 
+use crate::node::rpc::errors::{HlErrorCode, HlRpcError, UpstreamUnavailableData};
+
+/// Maps a failed upstream request for `method`: errors answered by the upstream are passed on,
+/// failures to reach it are [`HlErrorCode::UpstreamUnavailable`] errors.
+pub(crate) fn upstream_error(err: ClientError, method: &str, prefix: &str) -> ErrorObject<'static> {
+    match err {
+        ClientError::Call(err) => err,
+        err => HlRpcError::new(
+            HlErrorCode::UpstreamUnavailable,
+            format!("{prefix}: {err:?}"),
+            UpstreamUnavailableData { method: method.to_owned() },
+        )
+        .into(),
+    }
+}
+
 #[rpc(server, namespace = "eth")]
 pub(crate) trait CallForwarderApi<TxReq: RpcObject> {
     /// Executes a new message call immediately without creating a transaction on the block chain.
@@ -44,10 +60,7 @@ pub struct CallForwarderExt<EthApi> {
 }
 
 impl<EthApi> CallForwarderExt<EthApi> {
-    pub fn new(upstream_rpc_url: String, eth_api: EthApi) -> Self {
-        let upstream_client =
-            HttpClientBuilder::default().build(upstream_rpc_url).expect("Failed to build client");
-
+    pub fn new(upstream_client: HttpClient, eth_api: EthApi) -> Self {
         Self { upstream_client, eth_api }
     }
 }
@@ -73,14 +86,7 @@ where
                     rpc_params![request, block_id, state_overrides, block_overrides],
                 )
                 .await
-                .map_err(|e| match e {
-                    ClientError::Call(e) => e,
-                    _ => ErrorObject::owned(
-                        INTERNAL_ERROR_CODE,
-                        format!("Failed to call: {e:?}"),
-                        Some(()),
-                    ),
-                })?
+                .map_err(|e| upstream_error(e, "eth_call", "Failed to call"))?
         } else {
             EthCall::call(
                 &self.eth_api,
@@ -108,14 +114,7 @@ where
             self.upstream_client
                 .request("eth_estimateGas", rpc_params![request, block_id, state_override])
                 .await
-                .map_err(|e| match e {
-                    ClientError::Call(e) => e,
-                    _ => ErrorObject::owned(
-                        INTERNAL_ERROR_CODE,
-                        format!("Failed to estimate gas: {e:?}"),
-                        Some(()),
-                    ),
-                })?
+                .map_err(|e| upstream_error(e, "eth_estimateGas", "Failed to estimate gas"))?
         } else {
             EthCall::estimate_gas_at(
                 &self.eth_api,
@@ -0,0 +1,134 @@
+//! Which methods are forwarded to the upstream RPC and which are executed by the node itself.
+//!
+//! Transactions can only reach the chain through the upstream RPC, so sends are forwarded by
+//! default, while calls run locally. Operators can move any of the methods the node knows how to
+//! forward to either side, e.g. to forward gas estimation that needs read precompiles the node
+//! can't execute.
+
+use jsonrpsee::RpcModule;
+use std::{collections::BTreeSet, fmt};
+
+/// A method the node can forward to the upstream RPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum)]
+pub enum ForwardedMethod {
+    #[value(name = "eth_sendRawTransaction")]
+    SendRawTransaction,
+    #[value(name = "eth_sendRawTransactionSync")]
+    SendRawTransactionSync,
+    /// Only calls against the latest block are forwarded, the upstream serving no history.
+    #[value(name = "eth_call")]
+    Call,
+    /// Only estimates against the latest block are forwarded, as for `eth_call`.
+    #[value(name = "eth_estimateGas")]
+    EstimateGas,
+}
+
+impl ForwardedMethod {
+    pub const ALL: [Self; 4] =
+        [Self::SendRawTransaction, Self::SendRawTransactionSync, Self::Call, Self::EstimateGas];
+
+    /// The JSON-RPC method name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::SendRawTransaction => "eth_sendRawTransaction",
+            Self::SendRawTransactionSync => "eth_sendRawTransactionSync",
+            Self::Call => "eth_call",
+            Self::EstimateGas => "eth_estimateGas",
+        }
+    }
+}
+
+impl fmt::Display for ForwardedMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The methods forwarded to the upstream RPC; all others are executed locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardingPolicy {
+    forwarded: BTreeSet<ForwardedMethod>,
+}
+
+impl Default for ForwardingPolicy {
+    /// Forwards transactions and executes calls locally.
+    fn default() -> Self {
+        Self {
+            forwarded: BTreeSet::from([
+                ForwardedMethod::SendRawTransaction,
+                ForwardedMethod::SendRawTransactionSync,
+            ]),
+        }
+    }
+}
+
+impl ForwardingPolicy {
+    /// The default policy, additionally forwarding `allow` and executing `deny` locally. A method
+    /// on both lists is rejected.
+    pub fn new(allow: &[ForwardedMethod], deny: &[ForwardedMethod]) -> eyre::Result<Self> {
+        if let Some(method) = allow.iter().find(|method| deny.contains(method)) {
+            eyre::bail!("{method} is both forwarded and executed locally");
+        }
+        let mut policy = Self::default();
+        policy.forwarded.extend(allow);
+        policy.forwarded.retain(|method| !deny.contains(method));
+        Ok(policy)
+    }
+
+    pub fn forwards(&self, method: ForwardedMethod) -> bool {
+        self.forwarded.contains(&method)
+    }
+
+    pub fn forwarded(&self) -> impl Iterator<Item = ForwardedMethod> + '_ {
+        self.forwarded.iter().copied()
+    }
+
+    /// Removes the methods executed locally from the forwarding `module`, so that merging it
+    /// leaves the node's own implementation of them in place.
+    pub fn retain_forwarded<Context>(&self, module: &mut RpcModule<Context>) {
+        for method in ForwardedMethod::ALL {
+            if !self.forwards(method) {
+                module.remove_method(method.name());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_and_deny_lists_adjust_the_default() {
+        let policy = ForwardingPolicy::default();
+        assert!(policy.forwards(ForwardedMethod::SendRawTransaction));
+        assert!(!policy.forwards(ForwardedMethod::Call));
+
+        let policy = ForwardingPolicy::new(
+            &[ForwardedMethod::Call],
+            &[ForwardedMethod::SendRawTransactionSync],
+        )
+        .unwrap();
+        assert_eq!(
+            policy.forwarded().collect::<Vec<_>>(),
+            [ForwardedMethod::SendRawTransaction, ForwardedMethod::Call]
+        );
+
+        let err = ForwardingPolicy::new(&[ForwardedMethod::Call], &[ForwardedMethod::Call]);
+        assert!(err.unwrap_err().to_string().contains("eth_call"));
+    }
+
+    #[test]
+    fn removes_local_methods_from_the_module() {
+        let mut module = RpcModule::new(());
+        for method in ForwardedMethod::ALL {
+            module.register_method(method.name(), |_, _, _| "upstream").unwrap();
+        }
+        let policy = ForwardingPolicy::new(&[], &[ForwardedMethod::SendRawTransaction]).unwrap();
+        policy.retain_forwarded(&mut module);
+        assert_eq!(
+            module.method_names().collect::<BTreeSet<_>>(),
+            BTreeSet::from(["eth_sendRawTransactionSync"])
+        );
+    }
+}
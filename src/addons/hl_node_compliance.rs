@@ -1,42 +1,77 @@
 //! Overrides for RPC methods to post-filter system transactions and logs.
 //!
+//! Whether system transactions are visible follows `--expose-system-txs`, which defaults to
+//! exposing them outside hl-node compliant mode. Where they are hidden, [`hide_system_txs`]
+//! leaves them out of receipts, logs and transactions by index, as [`HlBlockExt`] does for
+//! blocks, so that every method agrees on the transaction indices. Compliant mode adds hl-node's
+//! own system transaction methods, see [`install_hl_node_compliance`].
+//!
 //! System transactions are always at the beginning of the block,
 //! so we can use the transaction index to determine if the log is from a system transaction,
 //! and if it is, we can exclude it.
 //!
 //! For non-system transactions, we can just return the log as is, and the client will
 //! adjust the transaction index accordingly.
+//!
+//! `eth_getLogs` over a block range first checks the user-only bloom of each header, so that
+//! receipts are not fetched for blocks where only system transactions match the filter.
+//!
+//! `eth_getBlockBy*` are served by [`HlBlockExt`] in both modes. Whether blocks list their
+//! system transactions follows `--expose-system-txs`, which defaults to exposing them outside
+//! compliant mode; exposed system transactions are flagged with `systemTx: true`. The block
+//! counting methods are served by [`HlBlockCountExt`] from the header and the stored body
+//! indices, so that they always agree with the transactions returned by `eth_getBlockBy*`.
 
 use alloy_consensus::{
-    BlockHeader, TxReceipt,
+    BlockHeader, EMPTY_OMMER_ROOT_HASH, TxReceipt,
     transaction::{TransactionMeta, TxHashRef},
 };
 use alloy_eips::{BlockId, BlockNumberOrTag};
 use alloy_json_rpc::RpcObject;
-use alloy_primitives::{B256, U256};
+use alloy_primitives::{B256, Bloom, Sealable, U256};
 use alloy_rpc_types::{
-    BlockTransactions, Filter, FilterChanges, FilterId, Log, PendingTransactionFilterKind,
-    TransactionInfo,
+    Block, BlockTransactions, Filter, FilterBlockOption, FilterChanges, FilterId, Index, Log,
+    PendingTransactionFilterKind, Transaction, TransactionInfo,
     pubsub::{Params, SubscriptionKind},
 };
 use jsonrpsee::{PendingSubscriptionSink, proc_macros::rpc};
 use jsonrpsee_core::{RpcResult, async_trait};
-use jsonrpsee_types::{ErrorObject, error::INTERNAL_ERROR_CODE};
+use jsonrpsee_types::{
+    ErrorObject,
+    error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
+};
 use reth::{api::FullNodeComponents, builder::rpc::RpcContext, tasks::TaskSpawner};
 use reth_primitives_traits::SignedTransaction;
-use reth_provider::{BlockIdReader, BlockReader, BlockReaderIdExt, ReceiptProvider};
+use reth_provider::{
+    BlockBodyIndicesProvider, BlockIdReader, BlockReader, BlockReaderIdExt, HeaderProvider,
+    ProviderResult, ReceiptProvider,
+};
 use reth_rpc::{EthFilter, EthPubSub};
 use reth_rpc_eth_api::{
-    EthApiTypes, EthFilterApiServer, EthPubSubApiServer, RpcBlock, RpcConvert, RpcReceipt,
-    RpcTransaction, helpers::EthBlocks, transaction::ConvertReceiptInput,
+    EthApiTypes, EthFilterApiServer, EthPubSubApiServer, FromEthApiError, RpcBlock, RpcConvert,
+    RpcHeader, RpcReceipt, RpcTransaction,
+    helpers::{EthBlocks, EthTransactions},
+    transaction::ConvertReceiptInput,
 };
 use reth_rpc_eth_types::EthApiError;
 use serde::{Deserialize, Serialize};
-use std::{marker::PhantomData, sync::Arc};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    marker::PhantomData,
+    ops::RangeInclusive,
+    sync::Arc,
+};
 use tokio_stream::StreamExt;
-use tracing::{Instrument, trace};
+use tracing::{Instrument, trace, warn};
+
+use crate::{
+    HlHeader,
+    addons::utils::{EthWrapper, new_headers_stream, pipe_from_stream},
+};
 
-use crate::addons::utils::{EthWrapper, new_headers_stream, pipe_from_stream};
+/// Largest `eth_getLogs` block range whose headers are checked against the user-only bloom.
+/// Larger ranges go straight to reth, which enforces its own range limit.
+const MAX_LOGS_PRESCAN_BLOCKS: u64 = 100_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -68,6 +103,13 @@ pub trait EthSystemTransactionApi<T: RpcObject, R: RpcObject> {
         &self,
         block_id: Option<BlockId>,
     ) -> RpcResult<Option<Vec<R>>>;
+
+    /// Returns all transaction receipts for a given block, including system transactions.
+    #[method(name = "getBlockReceiptsWithSystemTx")]
+    async fn block_receipts_with_system_tx(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<BlockReceiptsWithSystemTx<R>>>;
 }
 
 pub struct HlSystemTransactionExt<Eth: EthWrapper> {
@@ -254,22 +296,115 @@ where
             )),
         }
     }
+
+    /// Handler for: `eth_getBlockReceiptsWithSystemTx`
+    async fn block_receipts_with_system_tx(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<BlockReceiptsWithSystemTx<RpcReceipt<Eth::NetworkTypes>>>> {
+        trace!(target: "rpc::eth", ?block_id, "Serving eth_getBlockReceiptsWithSystemTx");
+        if self.eth_api.provider().block_by_id(block_id).map_err(EthApiError::from)?.is_none() {
+            return Ok(None);
+        }
+        let result = block_receipts_with_system_txs(block_id, &self.eth_api)
+            .instrument(engine_span!())
+            .await?;
+        Ok(result)
+    }
 }
 
 pub struct HlNodeFilterHttp<Eth: EthWrapper> {
     filter: Arc<EthFilter<Eth>>,
     provider: Arc<Eth::Provider>,
+    /// Largest block range of a request, `None` if unlimited.
+    max_blocks: Option<u64>,
+    /// Most logs of a response, `None` if unlimited.
+    max_logs: Option<usize>,
 }
 
 impl<Eth: EthWrapper> HlNodeFilterHttp<Eth> {
     pub fn new(filter: Arc<EthFilter<Eth>>, provider: Arc<Eth::Provider>) -> Self {
-        Self { filter, provider }
+        Self { filter, provider, max_blocks: None, max_logs: None }
+    }
+
+    /// Enforces `--rpc.max-blocks-per-filter` and `--rpc.max-logs-per-response` on whole
+    /// `eth_getLogs` requests, which reth only sees split into the ranges of their candidate
+    /// blocks.
+    pub fn with_limits(mut self, max_blocks: Option<u64>, max_logs: Option<usize>) -> Self {
+        self.max_blocks = max_blocks;
+        self.max_logs = max_logs;
+        self
     }
 }
 
+/// Returns the first block of `filter`'s range and the blocks of the range whose user
+/// transactions may match it, or `None` for block hash filters and ranges that are not
+/// pre-scanned. Reads the headers of the range.
+fn user_log_ranges<P>(
+    provider: &P,
+    filter: &Filter,
+    max_blocks: Option<u64>,
+) -> RpcResult<Option<(u64, Vec<RangeInclusive<u64>>)>>
+where
+    P: BlockIdReader + HeaderProvider<Header = HlHeader>,
+{
+    let FilterBlockOption::Range { from_block, to_block } = filter.block_option else {
+        return Ok(None);
+    };
+    let resolve = |block: Option<BlockNumberOrTag>| {
+        provider.convert_block_number(block.unwrap_or_default()).map_err(EthApiError::from)
+    };
+    let (Some(from), Some(to)) = (resolve(from_block)?, resolve(to_block)?) else {
+        return Ok(None);
+    };
+    if from > to || to - from >= MAX_LOGS_PRESCAN_BLOCKS {
+        return Ok(None);
+    }
+    if let Some(max_blocks) = max_blocks.filter(|max_blocks| to - from > *max_blocks) {
+        let message = format!("query exceeds max block range {max_blocks}");
+        return Err(ErrorObject::owned(INVALID_PARAMS_CODE, message, None::<()>));
+    }
+    let headers = provider.headers_range(from..=to).map_err(EthApiError::from)?;
+    let blooms = headers.iter().map(|header| (header.number(), header.user_logs_bloom()));
+    Ok(Some((from, candidate_ranges(filter, blooms))))
+}
+
+/// Fails once the `logs` of a range starting at `from` exceed `max_logs`, naming the range that
+/// fits as reth does.
+fn check_max_logs(logs: &[Log], from: u64, max_logs: Option<usize>) -> RpcResult<()> {
+    let Some((max_logs, first_over)) =
+        max_logs.and_then(|max_logs| Some((max_logs, logs.get(max_logs)?)))
+    else {
+        return Ok(());
+    };
+    let to = first_over.block_number.unwrap_or(from).saturating_sub(1).max(from);
+    let message = format!("query exceeds max results {max_logs}, retry with the range {from}-{to}");
+    Err(ErrorObject::owned(INVALID_PARAMS_CODE, message, None::<()>))
+}
+
+/// Groups the blocks whose bloom may match `filter` into contiguous ranges.
+pub fn candidate_ranges(
+    filter: &Filter,
+    blooms: impl IntoIterator<Item = (u64, Bloom)>,
+) -> Vec<RangeInclusive<u64>> {
+    let mut ranges: Vec<RangeInclusive<u64>> = Vec::new();
+    for (number, bloom) in blooms {
+        if !filter.matches_bloom(bloom) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == number => *range = *range.start()..=number,
+            _ => ranges.push(number..=number),
+        }
+    }
+    ranges
+}
+
 #[async_trait]
 impl<Eth: EthWrapper> EthFilterApiServer<RpcTransaction<Eth::NetworkTypes>>
     for HlNodeFilterHttp<Eth>
+where
+    Eth::Provider: HeaderProvider<Header = HlHeader>,
 {
     async fn new_filter(&self, filter: Filter) -> RpcResult<FilterId> {
         trace!(target: "rpc::eth", "Serving eth_newFilter");
@@ -309,8 +444,46 @@ impl<Eth: EthWrapper> EthFilterApiServer<RpcTransaction<Eth::NetworkTypes>>
 
     async fn logs(&self, filter: Filter) -> RpcResult<Vec<Log>> {
         trace!(target: "rpc::eth", "Serving eth_getLogs");
-        let logs = EthFilterApiServer::logs(&*self.filter, filter).await?;
-        Ok(logs.into_iter().filter_map(|log| adjust_log::<Eth>(log, &self.provider)).collect())
+        let (provider, prescanned, max_blocks) =
+            (self.provider.clone(), filter.clone(), self.max_blocks);
+        let ranges = tokio::task::spawn_blocking(move || {
+            user_log_ranges(&*provider, &prescanned, max_blocks)
+        })
+        .await
+        .map_err(|err| ErrorObject::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>))??;
+        let logs = match ranges {
+            Some((from, ranges)) => {
+                let mut logs = Vec::new();
+                for range in ranges {
+                    let filter = filter.clone().from_block(*range.start()).to_block(*range.end());
+                    logs.extend(EthFilterApiServer::logs(&*self.filter, filter).await?);
+                    check_max_logs(&logs, from, self.max_logs)?;
+                }
+                logs
+            }
+            None => EthFilterApiServer::logs(&*self.filter, filter).await?,
+        };
+
+        let provider = self.provider.clone();
+        let logs = tokio::task::spawn_blocking(move || {
+            let mut system_counts = HashMap::new();
+            let mut shifted = Vec::with_capacity(logs.len());
+            for log in logs {
+                let Some(block_number) = log.block_number else { continue };
+                let counts = match system_counts.entry(block_number) {
+                    Entry::Occupied(entry) => *entry.get(),
+                    Entry::Vacant(entry) => {
+                        *entry.insert(system_tx_and_log_counts::<Eth>(&provider, block_number)?)
+                    }
+                };
+                shifted.extend(counts.and_then(|counts| shift_log(log, counts)));
+            }
+            ProviderResult::Ok(shifted)
+        })
+        .await
+        .map_err(|err| ErrorObject::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>))?
+        .map_err(EthApiError::from)?;
+        Ok(logs)
     }
 }
 
@@ -365,9 +538,25 @@ where
     }
 }
 
-fn adjust_log<Eth: EthWrapper>(mut log: Log, provider: &Eth::Provider) -> Option<Log> {
-    let (tx_idx, log_idx) = (log.transaction_index?, log.log_index?);
-    let receipts = provider.receipts_by_block(log.block_number?.into()).unwrap()?;
+fn adjust_log<Eth: EthWrapper>(log: Log, provider: &Eth::Provider) -> Option<Log> {
+    let block_number = log.block_number?;
+    match system_tx_and_log_counts::<Eth>(provider, block_number) {
+        Ok(counts) => shift_log(log, counts?),
+        Err(err) => {
+            warn!(target: "rpc::eth", block_number, %err, "Dropping log of unreadable receipts");
+            None
+        }
+    }
+}
+
+/// Returns how many system transactions a block has, and how many logs they emitted.
+fn system_tx_and_log_counts<Eth: EthWrapper>(
+    provider: &Eth::Provider,
+    block_number: u64,
+) -> ProviderResult<Option<(u64, u64)>> {
+    let Some(receipts) = provider.receipts_by_block(block_number.into())? else {
+        return Ok(None);
+    };
     let (mut sys_tx_count, mut sys_log_count) = (0u64, 0u64);
     for receipt in receipts {
         if receipt.cumulative_gas_used() == 0 {
@@ -375,6 +564,12 @@ fn adjust_log<Eth: EthWrapper>(mut log: Log, provider: &Eth::Provider) -> Option
             sys_log_count += receipt.logs().len() as u64;
         }
     }
+    Ok(Some((sys_tx_count, sys_log_count)))
+}
+
+/// Drops `log` if it belongs to a system transaction, or shifts its indices past them.
+fn shift_log(mut log: Log, (sys_tx_count, sys_log_count): (u64, u64)) -> Option<Log> {
+    let (tx_idx, log_idx) = (log.transaction_index?, log.log_index?);
     if sys_tx_count > tx_idx {
         return None;
     }
@@ -388,6 +583,12 @@ pub struct HlNodeBlockFilterHttp<Eth: EthWrapper> {
     _marker: PhantomData<Eth>,
 }
 
+impl<Eth: EthWrapper> Clone for HlNodeBlockFilterHttp<Eth> {
+    fn clone(&self) -> Self {
+        Self::new(self.eth_api.clone())
+    }
+}
+
 impl<Eth: EthWrapper> HlNodeBlockFilterHttp<Eth> {
     pub fn new(eth_api: Arc<Eth>) -> Self {
         Self { eth_api, _marker: PhantomData }
@@ -395,7 +596,52 @@ impl<Eth: EthWrapper> HlNodeBlockFilterHttp<Eth> {
 }
 
 #[rpc(server, namespace = "eth")]
-pub trait EthBlockApi<B: RpcObject, R: RpcObject> {
+pub trait EthBlockApi<R: RpcObject> {
+    /// Returns all transaction receipts for a given block.
+    #[method(name = "getBlockReceipts")]
+    async fn block_receipts(&self, block_id: BlockId) -> RpcResult<Option<Vec<R>>>;
+
+    #[method(name = "getTransactionReceipt")]
+    async fn transaction_receipt(&self, hash: B256) -> RpcResult<Option<R>>;
+}
+
+#[rpc(server, namespace = "eth")]
+pub trait EthTransactionByIndexApi<T: RpcObject> {
+    /// Returns the user transaction at `index` of a block by hash.
+    #[method(name = "getTransactionByBlockHashAndIndex")]
+    async fn transaction_by_block_hash_and_index(
+        &self,
+        hash: B256,
+        index: Index,
+    ) -> RpcResult<Option<T>>;
+
+    /// Returns the user transaction at `index` of a block by number.
+    #[method(name = "getTransactionByBlockNumberAndIndex")]
+    async fn transaction_by_block_number_and_index(
+        &self,
+        number: BlockNumberOrTag,
+        index: Index,
+    ) -> RpcResult<Option<T>>;
+}
+
+/// A block transaction, flagged when it is a system transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlBlockTransaction<T> {
+    #[serde(flatten)]
+    pub transaction: T,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub system_tx: bool,
+}
+
+/// A block as returned by `eth_getBlockBy*`.
+pub type HlRpcBlock<Eth> = Block<
+    HlBlockTransaction<RpcTransaction<<Eth as EthApiTypes>::NetworkTypes>>,
+    RpcHeader<<Eth as EthApiTypes>::NetworkTypes>,
+>;
+
+#[rpc(server, namespace = "eth")]
+pub trait EthBlockByApi<B: RpcObject> {
     /// Returns information about a block by hash.
     #[method(name = "getBlockByHash")]
     async fn block_by_hash(&self, hash: B256, full: bool) -> RpcResult<Option<B>>;
@@ -403,29 +649,183 @@ pub trait EthBlockApi<B: RpcObject, R: RpcObject> {
     /// Returns information about a block by number.
     #[method(name = "getBlockByNumber")]
     async fn block_by_number(&self, number: BlockNumberOrTag, full: bool) -> RpcResult<Option<B>>;
+}
 
-    /// Returns all transaction receipts for a given block.
-    #[method(name = "getBlockReceipts")]
-    async fn block_receipts(&self, block_id: BlockId) -> RpcResult<Option<Vec<R>>>;
+/// Block methods that list system transactions or leave them out, see `--expose-system-txs`.
+pub struct HlBlockExt<Eth: EthWrapper> {
+    eth_api: Arc<Eth>,
+    expose_system_txs: bool,
+}
 
-    /// Returns all transaction receipts for a given block, including system transactions.
-    #[method(name = "getBlockReceiptsWithSystemTx")]
-    async fn block_receipts_with_system_tx(
+impl<Eth: EthWrapper> HlBlockExt<Eth> {
+    pub fn new(eth_api: Arc<Eth>, expose_system_txs: bool) -> Self {
+        Self { eth_api, expose_system_txs }
+    }
+
+    fn adjust(
         &self,
-        block_id: BlockId,
-    ) -> RpcResult<Option<BlockReceiptsWithSystemTx<R>>>;
+        block: Option<RpcBlock<Eth::NetworkTypes>>,
+    ) -> Result<Option<HlRpcBlock<Eth>>, Eth::Error> {
+        let Some(block) = block else {
+            return Ok(None);
+        };
+        let system_tx_count = system_tx_count_for_block(&*self.eth_api, block.number().into())?;
+        Ok(Some(adjust_block_transactions(block, system_tx_count, self.expose_system_txs)))
+    }
+}
+
+/// Flags the leading system transactions of a block, or drops them if they are not exposed.
+fn adjust_block_transactions<T, H>(
+    mut block: Block<Transaction<T>, H>,
+    system_tx_count: usize,
+    expose_system_txs: bool,
+) -> Block<HlBlockTransaction<Transaction<T>>, H> {
+    if !expose_system_txs {
+        drop_system_txs(&mut block.transactions, system_tx_count);
+    }
+    block.map_transactions(|transaction| {
+        let system_tx = expose_system_txs &&
+            transaction.transaction_index.is_some_and(|index| index < system_tx_count as u64);
+        HlBlockTransaction { transaction, system_tx }
+    })
+}
+
+#[async_trait]
+impl<Eth: EthWrapper> EthBlockByApiServer<HlRpcBlock<Eth>> for HlBlockExt<Eth>
+where
+    ErrorObject<'static>: From<Eth::Error>,
+{
+    /// Handler for: `eth_getBlockByHash`
+    async fn block_by_hash(&self, hash: B256, full: bool) -> RpcResult<Option<HlRpcBlock<Eth>>> {
+        trace!(target: "rpc::eth", ?hash, ?full, "Serving eth_getBlockByHash");
+        let block = self.eth_api.block_by_hash(hash, full).instrument(engine_span!()).await?;
+        Ok(self.adjust(block)?)
+    }
+
+    /// Handler for: `eth_getBlockByNumber`
+    async fn block_by_number(
+        &self,
+        number: BlockNumberOrTag,
+        full: bool,
+    ) -> RpcResult<Option<HlRpcBlock<Eth>>> {
+        trace!(target: "rpc::eth", ?number, ?full, "Serving eth_getBlockByNumber");
+        let block = self.eth_api.block_by_number(number, full).instrument(engine_span!()).await?;
+        Ok(self.adjust(block)?)
+    }
+}
 
+#[rpc(server, namespace = "eth")]
+pub trait EthBlockCountApi {
+    /// Returns the number of transactions in a block by hash.
     #[method(name = "getBlockTransactionCountByHash")]
     async fn block_transaction_count_by_hash(&self, hash: B256) -> RpcResult<Option<U256>>;
 
+    /// Returns the number of transactions in a block by number.
     #[method(name = "getBlockTransactionCountByNumber")]
     async fn block_transaction_count_by_number(
         &self,
         number: BlockNumberOrTag,
     ) -> RpcResult<Option<U256>>;
 
-    #[method(name = "getTransactionReceipt")]
-    async fn transaction_receipt(&self, hash: B256) -> RpcResult<Option<R>>;
+    /// Returns the number of uncles in a block by hash.
+    #[method(name = "getUncleCountByBlockHash")]
+    async fn block_uncles_count_by_hash(&self, hash: B256) -> RpcResult<Option<U256>>;
+
+    /// Returns the number of uncles in a block by number.
+    #[method(name = "getUncleCountByBlockNumber")]
+    async fn block_uncles_count_by_number(
+        &self,
+        number: BlockNumberOrTag,
+    ) -> RpcResult<Option<U256>>;
+}
+
+/// Block counting methods, consistent with the block transactions of the mode.
+///
+/// Transactions are counted from the stored body indices and the header's system transaction
+/// count, without loading the block body.
+pub struct HlBlockCountExt<Eth: EthWrapper> {
+    eth_api: Arc<Eth>,
+    expose_system_txs: bool,
+}
+
+impl<Eth: EthWrapper> HlBlockCountExt<Eth> {
+    pub fn new(eth_api: Arc<Eth>, expose_system_txs: bool) -> Self {
+        Self { eth_api, expose_system_txs }
+    }
+
+    fn transaction_count(&self, block_id: BlockId) -> RpcResult<Option<U256>> {
+        let Some(header) = self.header(block_id)? else {
+            return Ok(None);
+        };
+        let indices = self
+            .eth_api
+            .provider()
+            .block_body_indices(header.number())
+            .map_err(EthApiError::from)?;
+        Ok(indices.map(|indices| {
+            let count = transaction_count(
+                indices.tx_count,
+                header.extras.system_tx_count,
+                self.expose_system_txs,
+            );
+            U256::from(count)
+        }))
+    }
+
+    fn header(&self, block_id: BlockId) -> RpcResult<Option<HlHeader>> {
+        Ok(self.eth_api.provider().header_by_id(block_id).map_err(EthApiError::from)?)
+    }
+}
+
+/// Number of transactions `eth_getBlockBy*` returns for a block, which only has user
+/// transactions unless system transactions are exposed.
+fn transaction_count(tx_count: u64, system_tx_count: u64, expose_system_txs: bool) -> u64 {
+    if expose_system_txs { tx_count } else { tx_count.saturating_sub(system_tx_count) }
+}
+
+#[async_trait]
+impl<Eth: EthWrapper> EthBlockCountApiServer for HlBlockCountExt<Eth>
+where
+    ErrorObject<'static>: From<Eth::Error>,
+{
+    /// Handler for: `eth_getBlockTransactionCountByHash`
+    async fn block_transaction_count_by_hash(&self, hash: B256) -> RpcResult<Option<U256>> {
+        trace!(target: "rpc::eth", ?hash, "Serving eth_getBlockTransactionCountByHash");
+        self.transaction_count(hash.into())
+    }
+
+    /// Handler for: `eth_getBlockTransactionCountByNumber`
+    async fn block_transaction_count_by_number(
+        &self,
+        number: BlockNumberOrTag,
+    ) -> RpcResult<Option<U256>> {
+        trace!(target: "rpc::eth", ?number, "Serving eth_getBlockTransactionCountByNumber");
+        self.transaction_count(number.into())
+    }
+
+    /// Handler for: `eth_getUncleCountByBlockHash`
+    async fn block_uncles_count_by_hash(&self, hash: B256) -> RpcResult<Option<U256>> {
+        trace!(target: "rpc::eth", ?hash, "Serving eth_getUncleCountByBlockHash");
+        match self.header(hash.into())? {
+            // HyperEVM blocks have no uncles
+            Some(header) if header.ommers_hash() == EMPTY_OMMER_ROOT_HASH => Ok(Some(U256::ZERO)),
+            Some(_) => self.eth_api.block_uncles_count_by_hash(hash).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Handler for: `eth_getUncleCountByBlockNumber`
+    async fn block_uncles_count_by_number(
+        &self,
+        number: BlockNumberOrTag,
+    ) -> RpcResult<Option<U256>> {
+        trace!(target: "rpc::eth", ?number, "Serving eth_getUncleCountByBlockNumber");
+        match self.header(number.into())? {
+            Some(header) if header.ommers_hash() == EMPTY_OMMER_ROOT_HASH => Ok(Some(U256::ZERO)),
+            Some(_) => self.eth_api.block_uncles_count_by_number(number).await,
+            None => Ok(None),
+        }
+    }
 }
 
 macro_rules! engine_span {
@@ -434,30 +834,25 @@ macro_rules! engine_span {
     };
 }
 
-fn adjust_block<Eth: EthWrapper>(
-    recovered_block: &RpcBlock<Eth::NetworkTypes>,
-    eth_api: &Eth,
-) -> RpcBlock<Eth::NetworkTypes> {
-    let system_tx_count = system_tx_count_for_block(eth_api, recovered_block.number().into());
-    let mut new_block = recovered_block.clone();
-
-    new_block.transactions = match new_block.transactions {
-        BlockTransactions::Full(mut transactions) => {
+/// Removes the leading system transactions of a block, renumbering the user transactions.
+fn drop_system_txs<T>(
+    transactions: &mut BlockTransactions<Transaction<T>>,
+    system_tx_count: usize,
+) {
+    match transactions {
+        BlockTransactions::Full(transactions) => {
             transactions.drain(..system_tx_count);
             transactions.iter_mut().for_each(|tx| {
                 if let Some(idx) = &mut tx.transaction_index {
                     *idx -= system_tx_count as u64;
                 }
             });
-            BlockTransactions::Full(transactions)
         }
-        BlockTransactions::Hashes(mut hashes) => {
+        BlockTransactions::Hashes(hashes) => {
             hashes.drain(..system_tx_count);
-            BlockTransactions::Hashes(hashes)
         }
-        BlockTransactions::Uncle => BlockTransactions::Uncle,
-    };
-    new_block
+        BlockTransactions::Uncle => {}
+    }
 }
 
 async fn adjust_block_receipts<Eth: EthWrapper>(
@@ -465,8 +860,8 @@ async fn adjust_block_receipts<Eth: EthWrapper>(
     eth_api: &Eth,
 ) -> Result<Option<(usize, Vec<RpcReceipt<Eth::NetworkTypes>>)>, Eth::Error> {
     // Modified from EthBlocks::block_receipt. See `NOTE` comment below.
-    let system_tx_count = system_tx_count_for_block(eth_api, block_id);
     if let Some((block, receipts)) = EthBlocks::load_block_and_receipts(eth_api, block_id).await? {
+        let system_tx_count = block.header().extras.system_tx_count as usize;
         let block_number = block.number;
         let base_fee = block.base_fee_per_gas;
         let block_hash = block.hash();
@@ -522,8 +917,8 @@ async fn block_receipts_with_system_txs<Eth: EthWrapper>(
     block_id: BlockId,
     eth_api: &Eth,
 ) -> Result<Option<BlockReceiptsWithSystemTx<RpcReceipt<Eth::NetworkTypes>>>, Eth::Error> {
-    let system_tx_count = system_tx_count_for_block(eth_api, block_id);
     if let Some((block, receipts)) = EthBlocks::load_block_and_receipts(eth_api, block_id).await? {
+        let system_tx_count = block.header().extras.system_tx_count as usize;
         let block_number = block.number;
         let base_fee = block.base_fee_per_gas;
         let block_hash = block.hash();
@@ -594,7 +989,9 @@ async fn block_receipts_with_system_txs<Eth: EthWrapper>(
     Ok(None)
 }
 
-async fn adjust_transaction_receipt<Eth: EthWrapper>(
+/// Receipt of the user transaction `tx_hash`, numbered without the system transactions of its
+/// block. System transactions have none.
+pub(crate) async fn adjust_transaction_receipt<Eth: EthWrapper>(
     tx_hash: B256,
     eth_api: &Eth,
 ) -> Result<Option<RpcReceipt<Eth::NetworkTypes>>, Eth::Error> {
@@ -602,81 +999,41 @@ async fn adjust_transaction_receipt<Eth: EthWrapper>(
         Some((_, meta, _)) => {
             // LoadReceipt::block_transaction_receipt loads the block again, so loading blocks again
             // doesn't hurt performance much
+            // The block is gone if it was reorged out meanwhile, and system transactions have no
+            // receipt among the user ones
             let Some((system_tx_count, block_receipts)) =
                 adjust_block_receipts(meta.block_hash.into(), eth_api).await?
             else {
-                unreachable!();
+                return Ok(None);
             };
-            Ok(Some(block_receipts.into_iter().nth(meta.index as usize - system_tx_count).unwrap()))
+            let index = (meta.index as usize).checked_sub(system_tx_count);
+            Ok(index.and_then(|index| block_receipts.into_iter().nth(index)))
         }
         None => Ok(None),
     }
 }
 
-// This function assumes that `block_id` is already validated by the caller.
-fn system_tx_count_for_block<Eth: EthWrapper>(eth_api: &Eth, block_id: BlockId) -> usize {
-    let provider = eth_api.provider();
-    let header = provider.header_by_id(block_id).unwrap().unwrap();
-
-    header.extras.system_tx_count.try_into().unwrap()
+/// Number of system transactions of the block `block_id`. Fails if the block is unknown, e.g.
+/// reorged out since the caller resolved it.
+pub(crate) fn system_tx_count_for_block<Eth: EthWrapper>(
+    eth_api: &Eth,
+    block_id: BlockId,
+) -> Result<usize, Eth::Error> {
+    let header = eth_api
+        .provider()
+        .header_by_id(block_id)
+        .map_err(Eth::Error::from_eth_err)?
+        .ok_or_else(|| Eth::Error::from_eth_err(EthApiError::HeaderNotFound(block_id)))?;
+    Ok(header.extras.system_tx_count as usize)
 }
 
 #[async_trait]
-impl<Eth: EthWrapper> EthBlockApiServer<RpcBlock<Eth::NetworkTypes>, RpcReceipt<Eth::NetworkTypes>>
+impl<Eth: EthWrapper> EthBlockApiServer<RpcReceipt<Eth::NetworkTypes>>
     for HlNodeBlockFilterHttp<Eth>
 where
     Eth: EthApiTypes + 'static,
     ErrorObject<'static>: From<Eth::Error>,
 {
-    /// Handler for: `eth_getBlockByHash`
-    async fn block_by_hash(
-        &self,
-        hash: B256,
-        full: bool,
-    ) -> RpcResult<Option<RpcBlock<Eth::NetworkTypes>>> {
-        let res = self.eth_api.block_by_hash(hash, full).instrument(engine_span!()).await?;
-        Ok(res.map(|block| adjust_block(&block, &*self.eth_api)))
-    }
-
-    /// Handler for: `eth_getBlockByNumber`
-    async fn block_by_number(
-        &self,
-        number: BlockNumberOrTag,
-        full: bool,
-    ) -> RpcResult<Option<RpcBlock<Eth::NetworkTypes>>> {
-        trace!(target: "rpc::eth", ?number, ?full, "Serving eth_getBlockByNumber");
-        let res = self.eth_api.block_by_number(number, full).instrument(engine_span!()).await?;
-        Ok(res.map(|block| adjust_block(&block, &*self.eth_api)))
-    }
-
-    /// Handler for: `eth_getBlockTransactionCountByHash`
-    async fn block_transaction_count_by_hash(&self, hash: B256) -> RpcResult<Option<U256>> {
-        trace!(target: "rpc::eth", ?hash, "Serving eth_getBlockTransactionCountByHash");
-        let res =
-            self.eth_api.block_transaction_count_by_hash(hash).instrument(engine_span!()).await?;
-        Ok(res.map(|count| {
-            let sys_tx_count =
-                system_tx_count_for_block(&*self.eth_api, BlockId::Hash(hash.into()));
-            count - U256::from(sys_tx_count)
-        }))
-    }
-
-    /// Handler for: `eth_getBlockTransactionCountByNumber`
-    async fn block_transaction_count_by_number(
-        &self,
-        number: BlockNumberOrTag,
-    ) -> RpcResult<Option<U256>> {
-        trace!(target: "rpc::eth", ?number, "Serving eth_getBlockTransactionCountByNumber");
-        let res = self
-            .eth_api
-            .block_transaction_count_by_number(number)
-            .instrument(engine_span!())
-            .await?;
-        Ok(res.map(|count| {
-            count - U256::from(system_tx_count_for_block(&*self.eth_api, number.into()))
-        }))
-    }
-
     async fn transaction_receipt(
         &self,
         hash: B256,
@@ -699,30 +1056,82 @@ where
             adjust_block_receipts(block_id, &*self.eth_api).instrument(engine_span!()).await?;
         Ok(result.map(|(_, receipts)| receipts))
     }
+}
 
-    /// Handler for: `eth_getBlockReceiptsWithSystemTx`
-    async fn block_receipts_with_system_tx(
+impl<Eth: EthWrapper> HlNodeBlockFilterHttp<Eth>
+where
+    ErrorObject<'static>: From<Eth::Error>,
+{
+    /// The user transaction at `index` of the block `block_id`, numbered among the user
+    /// transactions.
+    async fn user_transaction_by_index(
         &self,
         block_id: BlockId,
-    ) -> RpcResult<Option<BlockReceiptsWithSystemTx<RpcReceipt<Eth::NetworkTypes>>>> {
-        trace!(target: "rpc::eth", ?block_id, "Serving eth_getBlockReceiptsWithSystemTx");
-        if self.eth_api.provider().block_by_id(block_id).map_err(EthApiError::from)?.is_none() {
+        index: Index,
+    ) -> RpcResult<Option<RpcTransaction<Eth::NetworkTypes>>> {
+        let Some(header) =
+            self.eth_api.provider().header_by_id(block_id).map_err(EthApiError::from)?
+        else {
             return Ok(None);
-        }
-        let result = block_receipts_with_system_txs(block_id, &*self.eth_api)
+        };
+        // Pinned to the hash, so that the offset is the one of the block the transaction is from
+        let block_id = BlockId::from(header.hash_slow());
+        let system_tx_count = header.extras.system_tx_count;
+        let index = usize::from(index) + system_tx_count as usize;
+        let tx = self
+            .eth_api
+            .transaction_by_block_and_tx_index(block_id, index)
             .instrument(engine_span!())
             .await?;
-        Ok(result)
+        Ok(tx.map(|mut tx| {
+            if let Some(idx) = &mut tx.transaction_index {
+                *idx -= system_tx_count;
+            }
+            tx
+        }))
     }
 }
 
-pub fn install_hl_node_compliance<Node, EthApi>(
-    ctx: &mut RpcContext<Node, EthApi>,
-) -> Result<(), eyre::Error>
+#[async_trait]
+impl<Eth: EthWrapper> EthTransactionByIndexApiServer<RpcTransaction<Eth::NetworkTypes>>
+    for HlNodeBlockFilterHttp<Eth>
+where
+    Eth: EthApiTypes + 'static,
+    ErrorObject<'static>: From<Eth::Error>,
+{
+    /// Handler for: `eth_getTransactionByBlockHashAndIndex`
+    async fn transaction_by_block_hash_and_index(
+        &self,
+        hash: B256,
+        index: Index,
+    ) -> RpcResult<Option<RpcTransaction<Eth::NetworkTypes>>> {
+        trace!(target: "rpc::eth", ?hash, ?index, "Serving eth_getTransactionByBlockHashAndIndex");
+        self.user_transaction_by_index(hash.into(), index).await
+    }
+
+    /// Handler for: `eth_getTransactionByBlockNumberAndIndex`
+    async fn transaction_by_block_number_and_index(
+        &self,
+        number: BlockNumberOrTag,
+        index: Index,
+    ) -> RpcResult<Option<RpcTransaction<Eth::NetworkTypes>>> {
+        trace!(
+            target: "rpc::eth",
+            ?number,
+            ?index,
+            "Serving eth_getTransactionByBlockNumberAndIndex"
+        );
+        self.user_transaction_by_index(number.into(), index).await
+    }
+}
+
+/// Leaves system transactions out of receipts, logs and transactions by index, renumbering the
+/// user transactions, for nodes that don't expose them.
+pub fn hide_system_txs<Node, EthApi>(ctx: &mut RpcContext<Node, EthApi>) -> Result<(), eyre::Error>
 where
     Node: FullNodeComponents,
     Node::Provider: BlockIdReader + BlockReader<Block = crate::HlBlock>,
-    EthApi: EthWrapper,
+    EthApi: EthWrapper<Provider: HeaderProvider<Header = HlHeader>>,
     ErrorObject<'static>: From<EthApi::Error>,
 {
     ctx.modules.replace_configured(
@@ -730,6 +1139,11 @@ where
             Arc::new(ctx.registry.eth_handlers().filter.clone()),
             Arc::new(ctx.registry.eth_api().provider().clone()),
         )
+        .with_limits(
+            ctx.config().rpc.rpc_max_blocks_per_filter.into(),
+            Option::<u64>::from(ctx.config().rpc.rpc_max_logs_per_response)
+                .map(|max_logs| max_logs as usize),
+        )
         .into_rpc(),
     )?;
     ctx.modules.replace_configured(
@@ -741,12 +1155,196 @@ where
         .into_rpc(),
     )?;
 
-    ctx.modules.replace_configured(
-        HlNodeBlockFilterHttp::new(Arc::new(ctx.registry.eth_api().clone())).into_rpc(),
-    )?;
+    let block_filter = HlNodeBlockFilterHttp::new(Arc::new(ctx.registry.eth_api().clone()));
+    ctx.modules.replace_configured(EthBlockApiServer::into_rpc(block_filter.clone()))?;
+    ctx.modules.replace_configured(EthTransactionByIndexApiServer::into_rpc(block_filter))?;
+
+    Ok(())
+}
 
+/// Installs hl-node's system transaction methods, `eth_getEvmSystemTxs*` and
+/// `eth_getBlockReceiptsWithSystemTx`.
+pub fn install_hl_node_compliance<Node, EthApi>(
+    ctx: &mut RpcContext<Node, EthApi>,
+) -> Result<(), eyre::Error>
+where
+    Node: FullNodeComponents,
+    EthApi: EthWrapper,
+    ErrorObject<'static>: From<EthApi::Error>,
+{
     ctx.modules
         .merge_configured(HlSystemTransactionExt::new(ctx.registry.eth_api().clone()).into_rpc())?;
-
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::transaction::Recovered;
+    use alloy_primitives::{Address, Log as PrimitiveLog, LogData, logs_bloom};
+
+    /// A topic emitted by system transactions in every block, e.g. a token `Transfer`.
+    const POPULAR_TOPIC: B256 = B256::repeat_byte(0x11);
+    const USER_TOPIC: B256 = B256::repeat_byte(0x22);
+
+    fn bloom(topics: &[B256]) -> Bloom {
+        let logs: Vec<_> = topics
+            .iter()
+            .map(|topic| PrimitiveLog {
+                address: Address::repeat_byte(0x20),
+                data: LogData::new_unchecked(vec![*topic], Default::default()),
+            })
+            .collect();
+        logs_bloom(&logs)
+    }
+
+    /// Headers of a synthetic range where the popular topic only appears in system txs, and a
+    /// user transaction emits another topic every 100 blocks.
+    fn headers() -> Vec<HlHeader> {
+        (0..1_000u64)
+            .map(|number| {
+                let user_topics = if number % 100 == 0 { vec![USER_TOPIC] } else { vec![] };
+                let mut header = HlHeader::default();
+                header.inner.number = number;
+                header.inner.logs_bloom = bloom(&user_topics);
+                header.extras.logs_bloom_with_system_txs =
+                    bloom(&[user_topics, vec![POPULAR_TOPIC]].concat());
+                header
+            })
+            .collect()
+    }
+
+    fn blocks(ranges: &[RangeInclusive<u64>]) -> usize {
+        ranges.iter().map(|range| range.clone().count()).sum()
+    }
+
+    #[test]
+    fn user_bloom_skips_blocks_matched_by_system_txs_only() {
+        let headers = headers();
+        let filter = Filter::new().event_signature(POPULAR_TOPIC);
+
+        // Normal mode shows system txs: every block has to be scanned
+        let with_system_txs = headers
+            .iter()
+            .map(|header| (header.number(), header.logs_bloom_with_system_txs()));
+        assert_eq!(candidate_ranges(&filter, with_system_txs), vec![0..=999]);
+
+        // Compliant mode: no receipt is fetched at all
+        let user_only = headers.iter().map(|header| (header.number(), header.user_logs_bloom()));
+        assert_eq!(blocks(&candidate_ranges(&filter, user_only)), 0);
+    }
+
+    #[test]
+    fn user_bloom_keeps_blocks_with_matching_user_logs() {
+        let headers = headers();
+        let filter = Filter::new().event_signature(USER_TOPIC);
+        let user_only = headers.iter().map(|header| (header.number(), header.user_logs_bloom()));
+        let ranges = candidate_ranges(&filter, user_only);
+        assert_eq!(ranges, (0..10).map(|i| i * 100..=i * 100).collect::<Vec<_>>());
+
+        // Contiguous matching blocks are fetched in one range
+        let blooms = [(5, bloom(&[USER_TOPIC])), (6, bloom(&[USER_TOPIC])), (8, bloom(&[]))];
+        assert_eq!(candidate_ranges(&filter, blooms), vec![5..=6]);
+    }
+
+    #[test]
+    fn max_logs_apply_to_the_whole_range() {
+        // Two logs in each of blocks 10, 12 and 15, gathered from three candidate ranges
+        let logs: Vec<_> = [10, 10, 12, 12, 15, 15]
+            .into_iter()
+            .map(|block_number| Log { block_number: Some(block_number), ..Default::default() })
+            .collect();
+        assert!(check_max_logs(&logs, 10, None).is_ok());
+        assert!(check_max_logs(&logs, 10, Some(6)).is_ok());
+        let err = check_max_logs(&logs, 10, Some(5)).unwrap_err();
+        assert_eq!(err.code(), INVALID_PARAMS_CODE);
+        assert_eq!(err.message(), "query exceeds max results 5, retry with the range 10-14");
+        let err = check_max_logs(&logs, 10, Some(1)).unwrap_err();
+        assert_eq!(err.message(), "query exceeds max results 1, retry with the range 10-10");
+    }
+
+    #[test]
+    fn transaction_count_matches_block_transactions_in_both_modes() {
+        // Two system transactions followed by three user transactions
+        let (tx_count, system_tx_count) = (5, 2);
+        let hashes: Vec<_> = (0..tx_count).map(|i| B256::with_last_byte(i as u8)).collect();
+        let full: Vec<_> = (0..tx_count)
+            .map(|i| Transaction {
+                inner: Recovered::new_unchecked((), Address::ZERO),
+                block_hash: None,
+                block_number: None,
+                transaction_index: Some(i),
+                effective_gas_price: None,
+            })
+            .collect();
+
+        for expose_system_txs in [true, false] {
+            let count = transaction_count(tx_count, system_tx_count, expose_system_txs);
+            for mut transactions in
+                [BlockTransactions::Hashes(hashes.clone()), BlockTransactions::Full(full.clone())]
+            {
+                if !expose_system_txs {
+                    drop_system_txs(&mut transactions, system_tx_count as usize);
+                }
+                assert_eq!(transactions.len() as u64, count, "exposed: {expose_system_txs}");
+            }
+        }
+
+        let mut transactions = BlockTransactions::Full(full);
+        drop_system_txs(&mut transactions, system_tx_count as usize);
+        let indices: Vec<_> = transactions.txns().map(|tx| tx.transaction_index).collect();
+        assert_eq!(indices, [Some(0), Some(1), Some(2)]);
+        assert_eq!(transaction_count(1, 2, false), 0);
+    }
+
+    #[test]
+    fn system_txs_are_flagged_or_dropped() {
+        // Two system transactions followed by a user transaction
+        let transactions = (0..3u8)
+            .map(|i| Transaction {
+                inner: Recovered::new_unchecked(i, Address::ZERO),
+                block_hash: None,
+                block_number: None,
+                transaction_index: Some(i as u64),
+                effective_gas_price: None,
+            })
+            .collect();
+        let block = Block::<_, ()> {
+            header: (),
+            uncles: vec![],
+            transactions: BlockTransactions::Full(transactions),
+            withdrawals: None,
+        };
+
+        let exposed = adjust_block_transactions(block.clone(), 2, true);
+        let flags: Vec<_> = exposed.transactions.txns().map(|tx| tx.system_tx).collect();
+        assert_eq!(flags, [true, true, false]);
+
+        // The flag is added next to the transaction fields, and left out for user transactions
+        let transaction = serde_json::json!({ "hash": B256::ZERO });
+        let system_tx = HlBlockTransaction { transaction: transaction.clone(), system_tx: true };
+        let json = serde_json::to_value(&system_tx).unwrap();
+        assert_eq!(json, serde_json::json!({ "hash": B256::ZERO, "systemTx": true }));
+        let user_tx = HlBlockTransaction { transaction: transaction.clone(), system_tx: false };
+        assert_eq!(serde_json::to_value(&user_tx).unwrap(), transaction);
+
+        let hidden = adjust_block_transactions(block, 2, false);
+        let user_txs: Vec<_> = hidden.transactions.txns().collect();
+        assert_eq!(user_txs.len(), 1);
+        assert_eq!(*user_txs[0].transaction.inner.inner(), 2);
+        assert_eq!(user_txs[0].transaction.transaction_index, Some(0));
+        assert!(!user_txs[0].system_tx);
+    }
+
+    #[test]
+    fn shifts_user_logs_past_system_txs() {
+        let log = |tx, index| Log {
+            transaction_index: Some(tx),
+            log_index: Some(index),
+            ..Default::default()
+        };
+        assert_eq!(shift_log(log(0, 0), (2, 3)), None);
+        let shifted = shift_log(log(2, 3), (2, 3)).unwrap();
+        assert_eq!((shifted.transaction_index, shifted.log_index), (Some(0), Some(0)));
+    }
+}
@@ -1,6 +1,20 @@
 pub mod call_forwarder;
+pub mod forwarding;
 pub mod hl_node_compliance;
+pub mod raw_data;
+pub mod replay_check;
+pub mod rpc_rate_limit;
+pub mod state_diff;
 pub mod subscribe_fixup;
+pub mod sync_limits;
+pub mod sync_replica;
 pub mod sync_server;
+pub mod sync_static_files;
+pub mod system_trace;
+pub mod system_tx_lookup;
+pub mod trace;
 pub mod tx_forwarder;
+pub mod tx_policy;
+pub mod tx_tracker;
+pub mod upstream;
 mod utils;
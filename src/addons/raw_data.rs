@@ -0,0 +1,322 @@
+//! Raw encodings of stored blocks, complete with the HL extensions.
+//!
+//! reth's `debug_getRaw*` methods encode Ethereum blocks, which drop the fields [`HlBlock`] adds,
+//! such as the read precompile calls. These overrides encode the node's own types instead:
+//! `debug_getRawHeader` and `debug_getRawBlock` return the RLP of [`HlHeader`] and [`HlBlock`],
+//! and `debug_getRawReceipts` the EIP-2718 encoding of the stored receipts.
+//!
+//! `hl_getRawBlockAndReceipts` returns a block as msgpack+lz4 [`BlockAndReceipts`], byte for byte
+//! what `hl_syncGetBlock` serves, so archives can store it next to blocks from the S3 bucket.
+//!
+//! `hl_getUserTransactionsRoot` returns the root of a block's transactions without its system
+//! transactions, so that clients can check the user transactions they were served on their own.
+
+use alloy_consensus::TxReceipt;
+use alloy_eips::{BlockId, Encodable2718};
+use alloy_primitives::{B256, Bytes};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use reth::rpc::result::internal_rpc_err;
+use reth_ethereum_primitives::EthereumReceipt;
+use reth_primitives_traits::BlockBody as _;
+use reth_provider::{
+    BlockIdReader, BlockNumReader, BlockReader, HeaderProvider, ReceiptProvider,
+    StageCheckpointReader,
+};
+use reth_rpc_eth_types::EthApiError;
+use tracing::trace;
+
+use crate::{
+    HlBlock, HlBlockBody, HlHeader,
+    addons::sync_server::{ProviderSyncReader, SyncBlockReader, encode_block, encode_single_block},
+    node::types::BlockAndReceipts,
+};
+
+#[rpc(server, namespace = "debug")]
+#[async_trait]
+pub trait HlDebugRawApi {
+    /// Returns the RLP of the HL header of a block, or empty bytes if the block is unknown.
+    #[method(name = "getRawHeader")]
+    async fn raw_header(&self, block_id: BlockId) -> RpcResult<Bytes>;
+
+    /// Returns the RLP of a block, including its read precompile calls.
+    #[method(name = "getRawBlock")]
+    async fn raw_block(&self, block_id: BlockId) -> RpcResult<Bytes>;
+
+    /// Returns the EIP-2718 encoded receipts of a block, with their blooms.
+    #[method(name = "getRawReceipts")]
+    async fn raw_receipts(&self, block_id: BlockId) -> RpcResult<Vec<Bytes>>;
+}
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlRawBlockApi {
+    /// Returns a block with its receipts, serialized as msgpack+lz4 bytes the same way as
+    /// `hl_syncGetBlock`.
+    #[method(name = "getRawBlockAndReceipts")]
+    async fn raw_block_and_receipts(&self, block_id: BlockId) -> RpcResult<Option<Bytes>>;
+}
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlUserTransactionsRootApi {
+    /// Returns the transactions root of a block computed over its user transactions only, or
+    /// null if the block is unknown.
+    #[method(name = "getUserTransactionsRoot")]
+    async fn user_transactions_root(&self, block_id: BlockId) -> RpcResult<Option<B256>>;
+}
+
+/// Root of the transactions of `body` without its system transactions, the same way
+/// [`HlBlockBody`] computes its transactions root.
+pub fn user_transactions_root(body: &HlBlockBody) -> B256 {
+    body.calculate_tx_root()
+}
+
+pub fn encode_raw_header(header: &HlHeader) -> Bytes {
+    alloy_rlp::encode(header).into()
+}
+
+pub fn encode_raw_block(block: &HlBlock) -> Bytes {
+    alloy_rlp::encode(block).into()
+}
+
+pub fn encode_raw_receipts(receipts: &[EthereumReceipt]) -> Vec<Bytes> {
+    receipts.iter().map(|receipt| receipt.with_bloom_ref().encoded_2718().into()).collect()
+}
+
+pub fn encode_raw_block_and_receipts(block: &BlockAndReceipts) -> RpcResult<Bytes> {
+    let payload = encode_block(block)
+        .map_err(|e| internal_rpc_err(format!("Failed to serialize block: {e}")))?;
+    encode_single_block(&payload)
+}
+
+#[derive(Debug)]
+pub struct HlRawDataExt<P> {
+    provider: P,
+    reader: ProviderSyncReader<P>,
+}
+
+impl<P: Clone> HlRawDataExt<P> {
+    pub fn new(provider: P) -> Self {
+        Self { reader: ProviderSyncReader::new(provider.clone()), provider }
+    }
+}
+
+#[async_trait]
+impl<P> HlDebugRawApiServer for HlRawDataExt<P>
+where
+    P: BlockReader<Block = HlBlock>
+        + HeaderProvider<Header = HlHeader>
+        + ReceiptProvider<Receipt = EthereumReceipt>
+        + BlockIdReader
+        + 'static,
+{
+    async fn raw_header(&self, block_id: BlockId) -> RpcResult<Bytes> {
+        trace!(target: "rpc::debug", ?block_id, "Serving debug_getRawHeader");
+        let Some(hash) = self.provider.block_hash_for_id(block_id).map_err(EthApiError::from)?
+        else {
+            return Ok(Bytes::new());
+        };
+        let header =
+            self.provider.header_by_hash_or_number(hash.into()).map_err(EthApiError::from)?;
+        Ok(header.as_ref().map(encode_raw_header).unwrap_or_default())
+    }
+
+    async fn raw_block(&self, block_id: BlockId) -> RpcResult<Bytes> {
+        trace!(target: "rpc::debug", ?block_id, "Serving debug_getRawBlock");
+        let block = match self.provider.block_hash_for_id(block_id).map_err(EthApiError::from)? {
+            Some(hash) => self.provider.block(hash.into()).map_err(EthApiError::from)?,
+            None => None,
+        };
+        let block = block.ok_or(EthApiError::HeaderNotFound(block_id))?;
+        Ok(encode_raw_block(&block))
+    }
+
+    async fn raw_receipts(&self, block_id: BlockId) -> RpcResult<Vec<Bytes>> {
+        trace!(target: "rpc::debug", ?block_id, "Serving debug_getRawReceipts");
+        let receipts = match self.provider.block_hash_for_id(block_id).map_err(EthApiError::from)? {
+            Some(hash) => {
+                self.provider.receipts_by_block(hash.into()).map_err(EthApiError::from)?
+            }
+            None => None,
+        };
+        Ok(receipts.map(|receipts| encode_raw_receipts(&receipts)).unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl<P> HlRawBlockApiServer for HlRawDataExt<P>
+where
+    P: BlockReader<Block = HlBlock>
+        + ReceiptProvider<Receipt = EthereumReceipt>
+        + BlockIdReader
+        + BlockNumReader
+        + StageCheckpointReader
+        + std::fmt::Debug
+        + Send
+        + Sync
+        + 'static,
+{
+    async fn raw_block_and_receipts(&self, block_id: BlockId) -> RpcResult<Option<Bytes>> {
+        trace!(target: "rpc::hl", ?block_id, "Serving hl_getRawBlockAndReceipts");
+        let Some(hash) = self.provider.block_hash_for_id(block_id).map_err(EthApiError::from)?
+        else {
+            return Ok(None);
+        };
+        let Some(block) = self
+            .reader
+            .read_block_by_hash(hash)
+            .map_err(|e| internal_rpc_err(format!("Failed to read block {hash}: {e}")))?
+        else {
+            return Ok(None);
+        };
+        encode_raw_block_and_receipts(&block).map(Some)
+    }
+}
+
+#[async_trait]
+impl<P> HlUserTransactionsRootApiServer for HlRawDataExt<P>
+where
+    P: BlockReader<Block = HlBlock> + BlockIdReader + 'static,
+{
+    async fn user_transactions_root(&self, block_id: BlockId) -> RpcResult<Option<B256>> {
+        trace!(target: "rpc::hl", ?block_id, "Serving hl_getUserTransactionsRoot");
+        let block = match self.provider.block_hash_for_id(block_id).map_err(EthApiError::from)? {
+            Some(hash) => self.provider.block(hash.into()).map_err(EthApiError::from)?,
+            None => None,
+        };
+        Ok(block.map(|block| user_transactions_root(&block.body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HlPrimitives,
+        node::{
+            primitives::{TransactionSigned, header::HlHeaderExtras},
+            types::{ReadPrecompileCalls, ReadPrecompileInput, ReadPrecompileResult},
+        },
+        pseudo_peer::decode_rmp_lz4,
+    };
+    use alloy_consensus::{
+        BlockBody, EthereumTxEnvelope, Header, ReceiptWithBloom, Signed, TxLegacy, TxType,
+        proofs::calculate_transaction_root,
+    };
+    use alloy_eips::Decodable2718;
+    use alloy_primitives::{Address, Bloom, Log, Signature, TxKind, U256};
+    use alloy_rlp::Decodable;
+    use reth_provider::test_utils::MockEthProvider;
+
+    /// A block with a system transaction, a user transaction calling a read precompile, and
+    /// the HL extensions set.
+    fn fixture() -> (HlBlock, Vec<EthereumReceipt>) {
+        let transactions = (0..2)
+            .map(|nonce| {
+                let tx = TxLegacy {
+                    nonce,
+                    gas_price: nonce as u128,
+                    gas_limit: 21_000,
+                    to: TxKind::Call(Address::with_last_byte(0x20)),
+                    value: U256::from(100),
+                    ..Default::default()
+                };
+                let tx = Signed::new_unhashed(tx, Signature::test_signature());
+                TransactionSigned::Default(EthereumTxEnvelope::Legacy(tx))
+            })
+            .collect();
+        let receipts = vec![
+            EthereumReceipt {
+                tx_type: TxType::Legacy,
+                success: true,
+                cumulative_gas_used: 0,
+                logs: vec![],
+            },
+            EthereumReceipt {
+                tx_type: TxType::Legacy,
+                success: true,
+                cumulative_gas_used: 21_000,
+                logs: vec![Log::new_unchecked(
+                    Address::with_last_byte(0x20),
+                    vec![B256::with_last_byte(1)],
+                    Bytes::from_static(b"log"),
+                )],
+            },
+        ];
+        let precompile_call = (
+            Address::with_last_byte(0x01),
+            vec![(
+                ReadPrecompileInput { input: Bytes::from_static(&[0xaa]), gas_limit: 30_000 },
+                ReadPrecompileResult::Ok { gas_used: 2_000, bytes: Bytes::from_static(&[0xbb]) },
+            )],
+        );
+        let header = HlHeader {
+            inner: Header { number: 42, timestamp: 1_700_000_000, ..Default::default() },
+            extras: HlHeaderExtras { logs_bloom_with_system_txs: Bloom::ZERO, system_tx_count: 1 },
+        };
+        let block = HlBlock {
+            header,
+            body: HlBlockBody {
+                inner: BlockBody { transactions, ommers: vec![], withdrawals: None },
+                sidecars: None,
+                read_precompile_calls: Some(ReadPrecompileCalls(vec![precompile_call])),
+                highest_precompile_address: Some(Address::with_last_byte(0x10)),
+            },
+        };
+        (block, receipts)
+    }
+
+    #[test]
+    fn raw_encodings_round_trip() {
+        let (block, receipts) = fixture();
+
+        let header = encode_raw_header(&block.header);
+        assert_eq!(HlHeader::decode(&mut header.as_ref()).unwrap(), block.header);
+
+        // The read precompile calls survive the round trip
+        let raw = encode_raw_block(&block);
+        assert_eq!(HlBlock::decode(&mut raw.as_ref()).unwrap(), block);
+
+        let decoded: Vec<_> = encode_raw_receipts(&receipts)
+            .iter()
+            .map(|raw| ReceiptWithBloom::<EthereumReceipt>::decode_2718(&mut raw.as_ref()).unwrap())
+            .collect();
+        assert_eq!(decoded.len(), receipts.len());
+        for (decoded, receipt) in decoded.iter().zip(&receipts) {
+            assert_eq!(&decoded.receipt, receipt);
+            assert_eq!(decoded.logs_bloom, receipt.bloom());
+        }
+    }
+
+    #[test]
+    fn block_and_receipts_match_the_sync_format() {
+        let (block, receipts) = fixture();
+        let expected = BlockAndReceipts::from_db(block, receipts);
+
+        let raw = encode_raw_block_and_receipts(&expected).unwrap();
+        assert_eq!(decode_rmp_lz4(&raw).unwrap(), vec![expected.clone()]);
+        let payload = encode_block(&expected).unwrap();
+        assert_eq!(raw, encode_single_block(&payload).unwrap());
+    }
+
+    #[tokio::test]
+    async fn user_transactions_root_leaves_out_system_transactions() {
+        let (block, _) = fixture();
+        // The first transaction of the fixture is the system transaction
+        let user_transactions = &block.body.transactions[1..];
+        assert!(user_transactions.iter().all(|tx| !tx.is_system_transaction()));
+        let expected = calculate_transaction_root(user_transactions);
+        assert_ne!(expected, calculate_transaction_root(&block.body.transactions));
+
+        let provider = MockEthProvider::<HlPrimitives>::default();
+        provider.add_block(block.header.hash_slow(), block.clone());
+        let ext = HlRawDataExt::new(provider);
+        let root = HlUserTransactionsRootApiServer::user_transactions_root(&ext, 42.into()).await;
+        assert_eq!(root.unwrap(), Some(expected));
+        assert_eq!(user_transactions_root(&block.body), expected);
+
+        let unknown = HlUserTransactionsRootApiServer::user_transactions_root(&ext, 43.into());
+        assert_eq!(unknown.await.unwrap(), None);
+    }
+}
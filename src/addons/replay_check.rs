@@ -0,0 +1,321 @@
+//! Online replay check: re-executes a sample of imported blocks and compares the outcome with
+//! what was imported.
+//!
+//! Blocks arrive pre-executed from the block source, so an execution bug (e.g. in the precompile
+//! replay) does not stop the import; it only shows up as receipts that differ from the official
+//! node. Re-executing every Nth block from its parent state and validating the outcome the same
+//! way `re-execute` does for a range, surfaces such divergence while the node runs.
+//!
+//! With `halt_on_divergence`, the first divergent block stops the check with a
+//! [`ReplayDiverged`] error, which shuts the node down like a fatal error of the pseudo peer.
+
+use crate::{HlBlock, HlPrimitives, chainspec::HlChainSpec, node::consensus::HlConsensus};
+use alloy_consensus::BlockHeader;
+use alloy_primitives::B256;
+use futures::StreamExt;
+use reth::consensus::FullConsensus;
+use reth_ethereum_primitives::Receipt;
+use reth_evm::{ConfigureEvm, execute::Executor};
+use reth_metrics::{
+    Metrics,
+    metrics::{self, Counter},
+};
+use reth_primitives_traits::RecoveredBlock;
+use reth_provider::{BlockExecutionResult, CanonStateNotificationStream, StateProviderFactory};
+use reth_revm::database::StateProviderDatabase;
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+
+/// How often blocks are re-executed, and what happens on divergence.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayCheckConfig {
+    /// Re-execute every block whose number is a multiple of this; 0 disables the check.
+    pub interval: u64,
+    /// Shut the node down on the first divergence instead of only reporting it.
+    pub halt_on_divergence: bool,
+}
+
+impl ReplayCheckConfig {
+    /// Returns true if block `number` is sampled for re-execution.
+    pub fn should_check(&self, number: u64) -> bool {
+        self.interval > 0 && number > 0 && number % self.interval == 0
+    }
+}
+
+/// A difference between the imported and the re-executed outcome of a block.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum ReplayDivergence {
+    /// The re-executed block fails the post-execution validation of its header, e.g. its gas used,
+    /// receipts root or logs bloom.
+    #[display("{_0}")]
+    PostExecution(String),
+    #[display("receipt count: imported {imported}, replayed {replayed}")]
+    ReceiptCount { imported: usize, replayed: usize },
+    #[display("receipt {_0} differs")]
+    Receipt(usize),
+}
+
+/// The first block found diverging with `halt_on_divergence`.
+#[derive(Debug, thiserror::Error)]
+#[error("block {number} ({hash}) diverges on re-execution: {divergences:?}")]
+pub struct ReplayDiverged {
+    pub number: u64,
+    pub hash: B256,
+    pub divergences: Vec<ReplayDivergence>,
+}
+
+/// Compares the replayed receipts of a block with its imported receipts.
+pub fn compare_receipts(imported: &[Receipt], replayed: &[Receipt]) -> Vec<ReplayDivergence> {
+    let mut divergences = Vec::new();
+    if imported.len() != replayed.len() {
+        divergences.push(ReplayDivergence::ReceiptCount {
+            imported: imported.len(),
+            replayed: replayed.len(),
+        });
+    }
+    divergences.extend(
+        imported
+            .iter()
+            .zip(replayed)
+            .enumerate()
+            .filter(|(_, (imported, replayed))| imported != replayed)
+            .map(|(index, _)| ReplayDivergence::Receipt(index)),
+    );
+    divergences
+}
+
+/// Re-executes `block` on top of its parent state.
+fn replay<P, E>(
+    provider: &P,
+    evm_config: &E,
+    block: &RecoveredBlock<HlBlock>,
+) -> eyre::Result<BlockExecutionResult<Receipt>>
+where
+    P: StateProviderFactory,
+    E: ConfigureEvm<Primitives = HlPrimitives>,
+{
+    let state = provider.history_by_block_number(block.number() - 1)?;
+    let mut executor = evm_config.batch_executor(StateProviderDatabase::new(state));
+    Ok(executor.execute_one(block)?)
+}
+
+#[derive(Metrics, Clone)]
+#[metrics(scope = "replay_check")]
+struct ReplayCheckMetrics {
+    /// How many blocks were re-executed
+    checked: Counter,
+    /// How many re-executed blocks diverged from what was imported
+    execution_divergence: Counter,
+    /// How many sampled blocks could not be re-executed
+    failed: Counter,
+}
+
+/// Re-executes sampled blocks as they are committed to the canonical chain.
+#[derive(Debug)]
+pub struct ReplayChecker<P, E> {
+    provider: P,
+    evm_config: E,
+    consensus: HlConsensus<HlChainSpec>,
+    config: ReplayCheckConfig,
+    metrics: ReplayCheckMetrics,
+}
+
+impl<P, E> ReplayChecker<P, E> {
+    pub fn new(
+        provider: P,
+        evm_config: E,
+        chain_spec: Arc<HlChainSpec>,
+        config: ReplayCheckConfig,
+    ) -> Self {
+        let consensus = HlConsensus::new(chain_spec);
+        Self { provider, evm_config, consensus, config, metrics: ReplayCheckMetrics::default() }
+    }
+
+    /// Checks the outcome of re-executing `block` against its `imported` receipts. Fails with
+    /// `halt_on_divergence` if they diverge; blocks that couldn't be re-executed are only
+    /// reported.
+    fn check(
+        &self,
+        block: &RecoveredBlock<HlBlock>,
+        imported: &[Receipt],
+        replayed: eyre::Result<BlockExecutionResult<Receipt>>,
+    ) -> Result<(), ReplayDiverged> {
+        let (number, hash) = (block.number(), block.hash());
+        let replayed = match replayed {
+            Ok(replayed) => replayed,
+            Err(err) => {
+                self.metrics.failed.increment(1);
+                warn!(number, %hash, %err, "Failed to re-execute sampled block");
+                return Ok(());
+            }
+        };
+
+        self.metrics.checked.increment(1);
+        let mut divergences = Vec::new();
+        // Same validation as `re-execute`, then the receipts it leaves out
+        if let Err(err) = self.consensus.validate_block_post_execution(block, &replayed) {
+            divergences.push(ReplayDivergence::PostExecution(err.to_string()));
+        }
+        divergences.extend(compare_receipts(imported, &replayed.receipts));
+        if divergences.is_empty() {
+            debug!(number, %hash, "Re-executed block matches the imported block");
+            return Ok(());
+        }
+
+        self.metrics.execution_divergence.increment(1);
+        for divergence in &divergences {
+            error!(number, %hash, %divergence, "Re-executed block diverges");
+        }
+        if self.config.halt_on_divergence {
+            return Err(ReplayDiverged { number, hash, divergences });
+        }
+        Ok(())
+    }
+}
+
+impl<P, E> ReplayChecker<P, E>
+where
+    P: StateProviderFactory + Clone + Send + Sync + 'static,
+    E: ConfigureEvm<Primitives = HlPrimitives> + Clone + 'static,
+{
+    /// Checks the sampled blocks of each committed chain, one block at a time.
+    ///
+    /// Returns the first divergence with `halt_on_divergence`, and runs until the notifications
+    /// end otherwise.
+    pub async fn run(
+        self,
+        mut notifications: CanonStateNotificationStream<HlPrimitives>,
+    ) -> Result<(), ReplayDiverged> {
+        while let Some(notification) = notifications.next().await {
+            let sampled: Vec<_> = notification
+                .committed()
+                .blocks_and_receipts()
+                .filter(|(block, _)| self.config.should_check(block.number()))
+                .map(|(block, receipts)| (block.clone(), receipts.clone()))
+                .collect();
+
+            for (block, imported) in sampled {
+                let (number, hash) = (block.number(), block.hash());
+                let (provider, evm_config) = (self.provider.clone(), self.evm_config.clone());
+                let replayed = tokio::task::spawn_blocking(move || {
+                    let replayed = replay(&provider, &evm_config, &block);
+                    (block, replayed)
+                })
+                .await;
+
+                let (block, replayed) = match replayed {
+                    Ok(replayed) => replayed,
+                    Err(err) => {
+                        self.metrics.failed.increment(1);
+                        warn!(number, %hash, %err, "Re-execution task failed");
+                        continue;
+                    }
+                };
+                self.check(&block, &imported, replayed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HlBlockBody,
+        node::primitives::{BlockBody, HlHeader},
+    };
+    use alloy_consensus::{Header, TxType};
+    use alloy_primitives::{Address, Bytes, Log, logs_bloom};
+
+    fn receipt(cumulative_gas_used: u64, logs: Vec<Log>) -> Receipt {
+        Receipt { tx_type: TxType::Legacy, success: true, cumulative_gas_used, logs }
+    }
+
+    /// A block whose header matches `receipts`.
+    fn block(number: u64, receipts: &[Receipt]) -> RecoveredBlock<HlBlock> {
+        let header = Header {
+            number,
+            gas_used: receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used),
+            receipts_root: Receipt::calculate_receipt_root_no_memo(receipts),
+            logs_bloom: logs_bloom(receipts.iter().flat_map(|receipt| &receipt.logs)),
+            ..Default::default()
+        };
+        let block = HlBlock {
+            header: HlHeader { inner: header, extras: Default::default() },
+            body: HlBlockBody {
+                inner: BlockBody { transactions: vec![], ommers: vec![], withdrawals: None },
+                sidecars: None,
+                read_precompile_calls: None,
+                highest_precompile_address: None,
+            },
+        };
+        RecoveredBlock::new_unhashed(block, vec![])
+    }
+
+    fn outcome(receipts: Vec<Receipt>) -> eyre::Result<BlockExecutionResult<Receipt>> {
+        let gas_used = receipts.last().map_or(0, |receipt| receipt.cumulative_gas_used);
+        Ok(BlockExecutionResult { receipts, requests: Default::default(), gas_used })
+    }
+
+    fn checker(halt_on_divergence: bool) -> ReplayChecker<(), ()> {
+        let config = ReplayCheckConfig { interval: 1, halt_on_divergence };
+        ReplayChecker::new((), (), Arc::new(HlChainSpec::default()), config)
+    }
+
+    #[test]
+    fn samples_every_nth_block() {
+        let config = ReplayCheckConfig { interval: 100, halt_on_divergence: false };
+        assert!(config.should_check(200));
+        assert!(!config.should_check(201));
+        assert!(!config.should_check(0));
+        assert!(!ReplayCheckConfig::default().should_check(200));
+    }
+
+    #[test]
+    fn detects_divergent_receipts() {
+        let log = Log::new_unchecked(Address::repeat_byte(1), vec![], Bytes::new());
+        let imported = vec![receipt(21_000, vec![]), receipt(50_000, vec![log])];
+        assert!(compare_receipts(&imported, &imported).is_empty());
+
+        let replayed = vec![receipt(21_000, vec![]), receipt(48_000, vec![])];
+        assert_eq!(compare_receipts(&imported, &replayed), vec![ReplayDivergence::Receipt(1)]);
+        assert_eq!(
+            compare_receipts(&imported, &imported[..1]),
+            vec![ReplayDivergence::ReceiptCount { imported: 2, replayed: 1 }]
+        );
+    }
+
+    #[test]
+    fn halts_on_the_first_divergent_block_only_when_asked() {
+        let log = Log::new_unchecked(Address::repeat_byte(1), vec![], Bytes::new());
+        let imported = vec![receipt(21_000, vec![]), receipt(50_000, vec![log])];
+        let block = block(200, &imported);
+        // A replay bug that drops the log and charges different gas
+        let replayed = vec![receipt(21_000, vec![]), receipt(48_000, vec![])];
+
+        for halt_on_divergence in [false, true] {
+            let checker = checker(halt_on_divergence);
+            assert!(checker.check(&block, &imported, outcome(imported.clone())).is_ok());
+            // Blocks that can't be re-executed say nothing about divergence
+            let failed = Err(eyre::eyre!("parent state not found"));
+            assert!(checker.check(&block, &imported, failed).is_ok());
+
+            let result = checker.check(&block, &imported, outcome(replayed.clone()));
+            if !halt_on_divergence {
+                assert!(result.is_ok());
+                continue;
+            }
+            let err = result.unwrap_err();
+            assert_eq!((err.number, err.hash), (200, block.hash()));
+            assert!(
+                matches!(
+                    &err.divergences[..],
+                    [ReplayDivergence::PostExecution(_), ReplayDivergence::Receipt(1)]
+                ),
+                "{:?}",
+                err.divergences
+            );
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Per-method rate limits for public RPC endpoints.
+//!
+//! Expensive methods such as `eth_call`, `eth_getLogs` or `debug_trace*` are limited by a token
+//! bucket per method, shared by all clients or kept per client. The limited methods are wrapped
+//! after every other module is installed, so the limits apply to reth's methods and to the HL
+//! overrides alike, and the sync server's own listener gets the same wrappers. Requests over a
+//! limit fail with [`RPC_RATE_LIMITED_CODE`] and a `retryAfterMs` hint, as sync requests over the
+//! sync server's limits do.
+
+use crate::addons::sync_limits::{ClientKey, RateLimitedData, TokenBucket, forward_extensions};
+use clap::Args;
+use jsonrpsee::{
+    RpcModule,
+    core::{
+        server::{MethodCallback, Methods, MethodsError},
+        traits::ToRpcParams,
+    },
+};
+use jsonrpsee_types::{ErrorObject, error::INTERNAL_ERROR_CODE};
+use reth_metrics::{
+    Metrics,
+    metrics::{Counter, Gauge},
+};
+use reth_network::cache::LruMap;
+use serde_json::value::RawValue;
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// JSON-RPC error code returned when a request is over its method's limit, the EIP-1474
+/// "limit exceeded" code.
+pub const RPC_RATE_LIMITED_CODE: i32 = -32005;
+
+/// Requests per second allowed for the methods matching `method`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodRateLimit {
+    /// Method name, or a prefix followed by `*`, e.g. `debug_trace*`.
+    pub method: String,
+    pub per_second: u32,
+}
+
+impl MethodRateLimit {
+    fn matches(&self, method: &str) -> bool {
+        match self.method.strip_suffix('*') {
+            Some(prefix) => method.starts_with(prefix),
+            None => method == self.method,
+        }
+    }
+}
+
+impl FromStr for MethodRateLimit {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, per_second) = s
+            .split_once('=')
+            .ok_or_else(|| eyre::eyre!("invalid rate limit {s:?}, expected <method>=<rate>"))?;
+        let per_second = per_second
+            .parse()
+            .ok()
+            .filter(|rate| *rate > 0)
+            .ok_or_else(|| eyre::eyre!("invalid rate in {s:?}, expected a positive integer"))?;
+        Ok(Self { method: method.trim().to_owned(), per_second })
+    }
+}
+
+/// Rate limits of the RPC methods. No method is limited by default.
+#[derive(Debug, Clone, Default, Args)]
+pub struct RpcRateLimits {
+    /// Requests per second allowed for a method, as <method>=<rate>, comma-separated.
+    ///
+    /// A trailing `*` limits every method starting with the prefix, each with its own budget,
+    /// e.g. `eth_call=50,eth_getLogs=10,debug_trace*=2`. An exact name takes precedence over a
+    /// prefix.
+    #[arg(long = "rpc.rate-limit", env = "RPC_RATE_LIMIT", value_delimiter = ',')]
+    pub limits: Vec<MethodRateLimit>,
+
+    /// Apply the rate limits to each client separately rather than across all clients.
+    ///
+    /// Clients are identified by connection on the RPC servers, and by IP on the sync server's own
+    /// listener, see `--sync-server-addr`.
+    #[arg(long = "rpc.rate-limit-per-client", env = "RPC_RATE_LIMIT_PER_CLIENT")]
+    pub per_client: bool,
+}
+
+/// A request rejected by the [`RpcRateLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+#[display("Rate limit of {method} exceeded, retry after {retry_after:?}")]
+pub struct MethodRateLimited {
+    pub method: &'static str,
+    pub retry_after: Duration,
+}
+
+impl From<MethodRateLimited> for ErrorObject<'static> {
+    fn from(err: MethodRateLimited) -> Self {
+        let data = RateLimitedData { retry_after_ms: err.retry_after.as_millis() as u64 };
+        ErrorObject::owned(RPC_RATE_LIMITED_CODE, err.to_string(), Some(data))
+    }
+}
+
+#[derive(Metrics, Clone)]
+#[metrics(scope = "rpc_rate_limit")]
+struct RpcRateLimitMetrics {
+    /// Number of method and client pairs tracked by the limits
+    tracked_buckets: Gauge,
+    /// How many requests were rejected by the limits
+    rate_limited_requests: Counter,
+}
+
+/// Enforces [`RpcRateLimits`] on the methods they name.
+pub struct RpcRateLimiter {
+    limits: Vec<MethodRateLimit>,
+    per_client: bool,
+    buckets: Mutex<LruMap<(&'static str, ClientKey), TokenBucket>>,
+    metrics: RpcRateLimitMetrics,
+}
+
+impl std::fmt::Debug for RpcRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcRateLimiter").field("limits", &self.limits).finish_non_exhaustive()
+    }
+}
+
+/// Parameters of a wrapped request, passed on to the wrapped method as received.
+pub(crate) struct RawParams(pub(crate) Option<String>);
+
+impl ToRpcParams for RawParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, serde_json::Error> {
+        self.0.map(RawValue::from_string).transpose()
+    }
+}
+
+pub(crate) fn call_error(err: MethodsError) -> ErrorObject<'static> {
+    match err {
+        MethodsError::JsonRpc(err) => err,
+        err => ErrorObject::owned(INTERNAL_ERROR_CODE, err.to_string(), None::<()>),
+    }
+}
+
+impl RpcRateLimiter {
+    /// Number of method and client pairs whose token buckets are kept around.
+    const MAX_TRACKED_BUCKETS: u32 = 16384;
+
+    pub fn new(limits: &RpcRateLimits) -> Self {
+        Self {
+            limits: limits.limits.clone(),
+            per_client: limits.per_client,
+            buckets: Mutex::new(LruMap::new(Self::MAX_TRACKED_BUCKETS)),
+            metrics: RpcRateLimitMetrics::default(),
+        }
+    }
+
+    /// Whether no method is limited.
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Requests per second allowed for `method`, if it is limited.
+    pub fn limit(&self, method: &str) -> Option<u32> {
+        let exact = self.limits.iter().find(|limit| limit.method == method);
+        exact
+            .or_else(|| {
+                self.limits
+                    .iter()
+                    .filter(|limit| limit.matches(method))
+                    .max_by_key(|limit| limit.method.len())
+            })
+            .map(|limit| limit.per_second)
+    }
+
+    /// Admits a call to `method` from `client`.
+    pub fn admit(&self, method: &'static str, client: ClientKey) -> Result<(), MethodRateLimited> {
+        let result = self.try_admit(method, client, Instant::now());
+        if result.is_err() {
+            self.metrics.rate_limited_requests.increment(1);
+        }
+        result
+    }
+
+    fn try_admit(
+        &self,
+        method: &'static str,
+        client: ClientKey,
+        now: Instant,
+    ) -> Result<(), MethodRateLimited> {
+        let Some(rate) = self.limit(method) else {
+            return Ok(());
+        };
+        let key = (method, if self.per_client { client } else { ClientKey::Unknown });
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.get(&key).is_none() {
+            buckets.insert(key, TokenBucket::new(rate.into(), now));
+        }
+        self.metrics.tracked_buckets.set(buckets.len() as f64);
+        let bucket = buckets.get(&key).expect("bucket was just inserted");
+        bucket.try_take(1.0, now).map_err(|retry_after| MethodRateLimited { method, retry_after })
+    }
+
+    /// Wraps the limited methods of `methods` in a module enforcing the limits, to replace them.
+    ///
+    /// Subscriptions aren't limited. The extensions of the request are forwarded to the original
+    /// method, see [`forward_extensions`].
+    pub fn wrap(self: &Arc<Self>, methods: Methods) -> RpcModule<Methods> {
+        let names: Vec<_> = methods
+            .method_names()
+            .filter(|name| self.limit(name).is_some())
+            .filter(|name| {
+                matches!(
+                    methods.method(name),
+                    Some(MethodCallback::Sync(_) | MethodCallback::Async(_))
+                )
+            })
+            .collect();
+
+        let mut module = RpcModule::new(methods);
+        for name in names {
+            let limiter = self.clone();
+            module
+                .register_async_method(name, move |params, methods, ext| {
+                    let limiter = limiter.clone();
+                    async move {
+                        limiter.admit(name, ClientKey::from_extensions(&ext))?;
+                        let params = RawParams(params.as_str().map(str::to_owned));
+                        let call = methods.call::<_, Box<RawValue>>(name, params);
+                        forward_extensions(&ext, call).await.map_err(call_error)
+                    }
+                })
+                .expect("method names of a module are unique");
+        }
+        module
+    }
+
+    /// Replaces the limited methods of `module`, for modules served on their own listener rather
+    /// than the node's RPC servers.
+    pub fn limit_module(
+        self: &Arc<Self>,
+        mut module: RpcModule<()>,
+    ) -> eyre::Result<RpcModule<()>> {
+        if self.is_empty() {
+            return Ok(module);
+        }
+        let limited = self.wrap(module.clone().into());
+        for name in limited.method_names().collect::<Vec<_>>() {
+            module.remove_method(name);
+        }
+        module.merge(limited)?;
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::addons::sync_server::serve_over_tcp;
+    use jsonrpsee::{
+        core::{ClientError, client::ClientT},
+        http_client::HttpClientBuilder,
+        rpc_params,
+    };
+
+    fn limiter(limits: &str, per_client: bool) -> Arc<RpcRateLimiter> {
+        let limits = limits.split(',').map(|limit| limit.parse().unwrap()).collect();
+        Arc::new(RpcRateLimiter::new(&RpcRateLimits { limits, per_client }))
+    }
+
+    #[test]
+    fn limits_match_exact_names_before_prefixes() {
+        let limiter = limiter("debug_trace*=2,debug_traceCall=5,eth_call=50", false);
+        assert_eq!(limiter.limit("eth_call"), Some(50));
+        assert_eq!(limiter.limit("debug_traceCall"), Some(5));
+        assert_eq!(limiter.limit("debug_traceTransaction"), Some(2));
+        assert_eq!(limiter.limit("eth_blockNumber"), None);
+        assert!("eth_call".parse::<MethodRateLimit>().is_err());
+        assert!("eth_call=0".parse::<MethodRateLimit>().is_err());
+    }
+
+    #[tokio::test]
+    async fn exceeding_a_method_limit_leaves_other_methods_unaffected() {
+        let mut module = RpcModule::new(());
+        module.register_method("eth_call", |_, _, _| "0x").unwrap();
+        module.register_method("eth_blockNumber", |_, _, _| "0x1").unwrap();
+        let module = limiter("eth_call=1", false).wrap(module.into());
+
+        let result: String = module.call("eth_call", rpc_params![]).await.unwrap();
+        assert_eq!(result, "0x");
+        let err = module.call::<_, String>("eth_call", rpc_params![]).await.unwrap_err();
+        let MethodsError::JsonRpc(err) = err else { panic!("unexpected error {err:?}") };
+        assert_eq!(err.code(), RPC_RATE_LIMITED_CODE);
+        let data: RateLimitedData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert!(data.retry_after_ms > 0 && data.retry_after_ms <= 1000, "{data:?}");
+
+        for _ in 0..3 {
+            let result: String = module.call("eth_blockNumber", rpc_params![]).await.unwrap();
+            assert_eq!(result, "0x1");
+        }
+    }
+
+    #[test]
+    fn clients_share_limits_unless_per_client() {
+        let now = Instant::now();
+        let (a, b) = (ClientKey::Connection(1), ClientKey::Connection(2));
+
+        let shared = limiter("eth_getLogs=1", false);
+        assert!(shared.try_admit("eth_getLogs", a, now).is_ok());
+        assert!(shared.try_admit("eth_getLogs", b, now).is_err());
+
+        let per_client = limiter("eth_getLogs=1", true);
+        assert!(per_client.try_admit("eth_getLogs", a, now).is_ok());
+        let err = per_client.try_admit("eth_getLogs", a, now).unwrap_err();
+        assert_eq!(err.retry_after, Duration::from_secs(1));
+        assert!(per_client.try_admit("eth_getLogs", b, now).is_ok());
+    }
+
+    #[tokio::test]
+    async fn limited_methods_see_the_client_on_their_own_listener() {
+        let mut module = RpcModule::new(());
+        module
+            .register_method("hl_syncGetBlock", |_, _, ext| {
+                format!("{:?}", ClientKey::from_extensions(ext))
+            })
+            .unwrap();
+        let module = limiter("hl_sync*=1", true).limit_module(module).unwrap();
+        let (addr, handle) = serve_over_tcp(module, ([127, 0, 0, 1], 0).into()).await.unwrap();
+        let client = HttpClientBuilder::default().build(format!("http://{addr}")).unwrap();
+
+        let client_key: String = client.request("hl_syncGetBlock", rpc_params![]).await.unwrap();
+        let ip = ClientKey::Ip([127, 0, 0, 1].into());
+        assert_eq!(client_key, format!("{ip:?}"));
+        let err = client.request::<String, _>("hl_syncGetBlock", rpc_params![]).await.unwrap_err();
+        let ClientError::Call(err) = err else { panic!("unexpected error {err:?}") };
+        assert_eq!(err.code(), RPC_RATE_LIMITED_CODE);
+
+        handle.stop().unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! `hl_getBlockStateDiff`: the balance, nonce, code and storage changes of a block.
+//!
+//! The block is re-executed on top of its parent state with its recorded read precompile calls,
+//! the same way it was imported, and the state each transaction leaves behind is compared with
+//! the state before it. Changes made by system transactions (HyperCore transfers into HyperEVM)
+//! are reported apart from the changes of user transactions, so an account that is credited by a
+//! system transaction and spends in a user transaction shows up on both sides.
+//!
+//! Re-execution is as expensive as importing the block, so the method is only served with
+//! `--enable-state-diff-rpc`, and diffs are cached by block hash.
+
+use crate::{
+    HlBlock, HlPrimitives,
+    node::{
+        evm::patch_mainnet_after_tx,
+        rpc::errors::{HlErrorCode, HlRpcError, ReplayFailedData},
+    },
+};
+use alloy_consensus::BlockHeader;
+use alloy_eips::BlockId;
+use alloy_primitives::{Address, B256, Bytes, KECCAK_EMPTY, U64, U256};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use jsonrpsee_types::{ErrorObject, error::INTERNAL_ERROR_CODE};
+use reth_evm::{ConfigureEvm, Evm, execute::BlockExecutor};
+use reth_network::cache::LruMap;
+use reth_primitives_traits::RecoveredBlock;
+use reth_provider::{BlockIdReader, BlockReader, StateProviderFactory, TransactionVariant};
+use reth_revm::{State, database::StateProviderDatabase};
+use revm::{
+    Database,
+    state::{Account, AccountInfo, Bytecode, EvmState},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tracing::trace;
+
+/// Number of blocks whose state diffs are cached.
+pub const STATE_DIFF_CACHE_SIZE: u32 = 128;
+
+/// A value before and after a block's transactions changed it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueChange<T> {
+    pub from: T,
+    pub to: T,
+}
+
+impl<T: PartialEq> ValueChange<T> {
+    fn new(from: T, to: T) -> Option<Self> {
+        (from != to).then_some(Self { from, to })
+    }
+}
+
+/// The changes of one account. Fields that didn't change are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDiff {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance: Option<ValueChange<U256>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<ValueChange<U64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ValueChange<Bytes>>,
+    /// Changed storage slots. Slots wiped by a self-destruct are not listed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<B256, ValueChange<B256>>,
+    /// Whether the account self-destructed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub destroyed: bool,
+}
+
+impl AccountDiff {
+    fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.nonce.is_none()
+            && self.code.is_none()
+            && self.storage.is_empty()
+            && !self.destroyed
+    }
+
+    /// Adds the changes of a later transaction, keeping the values before the earlier one.
+    fn extend(&mut self, later: Self) {
+        fn chain<T>(earlier: &mut Option<ValueChange<T>>, later: Option<ValueChange<T>>) {
+            match (earlier.as_mut(), later) {
+                (Some(earlier), Some(later)) => earlier.to = later.to,
+                (None, later) => *earlier = later,
+                (Some(_), None) => {}
+            }
+        }
+        chain(&mut self.balance, later.balance);
+        chain(&mut self.nonce, later.nonce);
+        chain(&mut self.code, later.code);
+        for (slot, change) in later.storage {
+            self.storage.entry(slot).and_modify(|earlier| earlier.to = change.to).or_insert(change);
+        }
+        self.destroyed |= later.destroyed;
+    }
+
+    /// Drops the values that transactions changed back to what they were before the block.
+    fn prune(&mut self) {
+        fn unchanged<T: PartialEq>(change: &mut Option<ValueChange<T>>) {
+            if change.as_ref().is_some_and(|change| change.from == change.to) {
+                *change = None;
+            }
+        }
+        unchanged(&mut self.balance);
+        unchanged(&mut self.nonce);
+        unchanged(&mut self.code);
+        self.storage.retain(|_, change| change.from != change.to);
+    }
+}
+
+/// The state changes of a block, by whether system or user transactions made them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockStateDiff {
+    pub block_hash: B256,
+    pub block_number: U64,
+    pub system: BTreeMap<Address, AccountDiff>,
+    pub user: BTreeMap<Address, AccountDiff>,
+}
+
+/// Collects the state changes of a block's transactions in execution order.
+#[derive(Debug, Default)]
+pub struct StateDiffBuilder {
+    /// User and system transaction changes, in that order.
+    accounts: [BTreeMap<Address, AccountDiff>; 2],
+}
+
+impl StateDiffBuilder {
+    /// Records the state a transaction left behind. `db` is the state before the transaction.
+    pub fn record<DB: Database>(
+        &mut self,
+        system_tx: bool,
+        state: &EvmState,
+        db: &mut DB,
+    ) -> Result<(), DB::Error> {
+        let accounts = &mut self.accounts[system_tx as usize];
+        for (address, account) in state.iter().filter(|(_, account)| account.is_touched()) {
+            let before = db.basic(*address)?.unwrap_or_default();
+            let diff = account_diff(db, &before, account)?;
+            accounts.entry(*address).or_default().extend(diff);
+        }
+        Ok(())
+    }
+
+    pub fn finish(self, block_hash: B256, block_number: u64) -> BlockStateDiff {
+        let [user, system] = self.accounts.map(|mut accounts| {
+            accounts.values_mut().for_each(AccountDiff::prune);
+            accounts.retain(|_, diff| !diff.is_empty());
+            accounts
+        });
+        BlockStateDiff { block_hash, block_number: U64::from(block_number), system, user }
+    }
+}
+
+/// Compares an account after a transaction with the account `before` it.
+fn account_diff<DB: Database>(
+    db: &mut DB,
+    before: &AccountInfo,
+    account: &Account,
+) -> Result<AccountDiff, DB::Error> {
+    let destroyed = account.is_selfdestructed();
+    let after = if destroyed { AccountInfo::default() } else { account.info.clone() };
+
+    let code = if before.code_hash != after.code_hash {
+        let from = if before.code_hash == KECCAK_EMPTY {
+            Bytes::new()
+        } else {
+            db.code_by_hash(before.code_hash)?.original_bytes()
+        };
+        let to = after.code.as_ref().map(Bytecode::original_bytes).unwrap_or_default();
+        Some(ValueChange { from, to })
+    } else {
+        None
+    };
+    let storage = account
+        .storage
+        .iter()
+        .filter(|(_, slot)| slot.is_changed())
+        .map(|(key, slot)| {
+            let change = ValueChange {
+                from: B256::from(slot.original_value),
+                to: B256::from(slot.present_value),
+            };
+            (B256::from(*key), change)
+        })
+        .collect();
+
+    Ok(AccountDiff {
+        balance: ValueChange::new(before.balance, after.balance),
+        nonce: ValueChange::new(U64::from(before.nonce), U64::from(after.nonce)),
+        code,
+        storage,
+        destroyed,
+    })
+}
+
+/// Re-executes `block` on top of its parent state, collecting the changes of each transaction.
+///
+/// With `system_only`, execution stops after the block's system transactions.
+pub(crate) fn execute_state_diff<P, E>(
+    provider: &P,
+    evm_config: &E,
+    block: &RecoveredBlock<HlBlock>,
+    system_only: bool,
+) -> eyre::Result<BlockStateDiff>
+where
+    P: StateProviderFactory,
+    E: ConfigureEvm<Primitives = HlPrimitives>,
+{
+    let state = provider.history_by_block_hash(block.parent_hash())?;
+    let mut db = State::builder()
+        .with_database(StateProviderDatabase::new(state))
+        .with_bundle_update()
+        .build();
+    let mut executor = evm_config.executor_for_block(&mut db, block.sealed_block())?;
+    executor.apply_pre_execution_changes()?;
+
+    let tx_count =
+        if system_only { block.header().extras.system_tx_count as usize } else { usize::MAX };
+    let mut builder = StateDiffBuilder::default();
+    for (index, tx) in block.transactions_recovered().enumerate().take(tx_count) {
+        let system_tx = tx.is_system_transaction();
+        let output = executor.execute_transaction_without_commit(tx)?;
+        // The patched state is what the executor commits, see `commit_transaction`
+        let mut state = output.state.clone();
+        patch_mainnet_after_tx(block.number(), index as u64, system_tx, &mut state)?;
+        builder.record(system_tx, &state, executor.evm_mut().db_mut())?;
+        executor.commit_transaction(output, tx)?;
+    }
+    Ok(builder.finish(block.hash(), block.number()))
+}
+
+/// The error of a block that [`execute_state_diff`] failed to re-execute.
+pub(crate) fn replay_failed(block_hash: B256, err: eyre::Report) -> ErrorObject<'static> {
+    let message = format!("Failed to re-execute block: {err}");
+    HlRpcError::new(HlErrorCode::ReplayFailed, message, ReplayFailedData { block_hash }).into()
+}
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlStateDiffApi {
+    /// Returns the state changes of a block, found by re-executing it.
+    #[method(name = "getBlockStateDiff")]
+    async fn block_state_diff(&self, block: BlockId) -> RpcResult<Option<BlockStateDiff>>;
+}
+
+pub struct HlStateDiffExt<P, E> {
+    provider: P,
+    evm_config: E,
+    cache: Arc<Mutex<LruMap<B256, BlockStateDiff>>>,
+}
+
+impl<P, E> HlStateDiffExt<P, E> {
+    pub fn new(provider: P, evm_config: E) -> Self {
+        let cache = Arc::new(Mutex::new(LruMap::new(STATE_DIFF_CACHE_SIZE)));
+        Self { provider, evm_config, cache }
+    }
+}
+
+#[async_trait]
+impl<P, E> HlStateDiffApiServer for HlStateDiffExt<P, E>
+where
+    P: BlockReader<Block = HlBlock>
+        + BlockIdReader
+        + StateProviderFactory
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    E: ConfigureEvm<Primitives = HlPrimitives> + Clone + 'static,
+{
+    async fn block_state_diff(&self, block: BlockId) -> RpcResult<Option<BlockStateDiff>> {
+        trace!(target: "rpc::hl", ?block, "Serving hl_getBlockStateDiff");
+        let internal = |err: String| ErrorObject::owned(INTERNAL_ERROR_CODE, err, None::<()>);
+
+        let Some(hash) =
+            self.provider.block_hash_for_id(block).map_err(|err| internal(err.to_string()))?
+        else {
+            return Ok(None);
+        };
+        if let Some(diff) = self.cache.lock().unwrap().get(&hash) {
+            return Ok(Some(diff.clone()));
+        }
+
+        let (provider, evm_config) = (self.provider.clone(), self.evm_config.clone());
+        let diff = tokio::task::spawn_blocking(move || {
+            let Some(block) =
+                provider.recovered_block(hash.into(), TransactionVariant::WithHash)?
+            else {
+                return Ok(None);
+            };
+            execute_state_diff(&provider, &evm_config, &block, false).map(Some)
+        })
+        .await
+        .map_err(|err| internal(err.to_string()))?
+        .map_err(|err| replay_failed(hash, err))?;
+
+        if let Some(diff) = &diff {
+            self.cache.lock().unwrap().insert(hash, diff.clone());
+        }
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use revm::{database::InMemoryDB, state::EvmStorageSlot};
+    use serde_json::json;
+
+    const USER: Address = address!("0x00000000000000000000000000000000000000aa");
+    const TOKEN: Address = address!("0x00000000000000000000000000000000000000bb");
+    const ORACLE: Address = address!("0x00000000000000000000000000000000000000cc");
+
+    /// A touched account with the given `(slot, from, to)` storage writes.
+    fn account(balance: u64, nonce: u64, storage: &[(u64, u64, u64)]) -> Account {
+        let info = AccountInfo { balance: U256::from(balance), nonce, ..Default::default() };
+        let mut account = Account::from(info);
+        account.mark_touch();
+        for &(slot, from, to) in storage {
+            let value = EvmStorageSlot::new_changed(U256::from(from), U256::from(to), 0);
+            account.storage.insert(U256::from(slot), value);
+        }
+        account
+    }
+
+    fn balance(from: u64, to: u64) -> Option<ValueChange<U256>> {
+        ValueChange::new(U256::from(from), U256::from(to))
+    }
+
+    /// A block with a system transaction crediting the user with 100 wei from HyperCore, and a
+    /// user transaction paying 30 wei to a token contract that records the payment.
+    #[test]
+    fn attributes_changes_to_system_and_user_transactions() {
+        let mut db = InMemoryDB::default();
+        let token = AccountInfo { balance: U256::from(5), nonce: 1, ..Default::default() };
+        db.insert_account_info(TOKEN, token);
+
+        let mut builder = StateDiffBuilder::default();
+        let system_tx = EvmState::from_iter([(USER, account(100, 0, &[]))]);
+        builder.record(true, &system_tx, &mut db).unwrap();
+        db.insert_account_info(USER, system_tx[&USER].info.clone());
+
+        let mut token = account(35, 1, &[(1, 0, 30), (2, 9, 9)]);
+        token.storage.insert(U256::from(3), EvmStorageSlot::new(U256::from(4), 0));
+        let user_tx = EvmState::from_iter([
+            (USER, account(70, 1, &[])),
+            (TOKEN, token),
+            // Loaded but not written, e.g. by a static call
+            (ORACLE, Account::from(AccountInfo::default())),
+        ]);
+        builder.record(false, &user_tx, &mut db).unwrap();
+
+        let diff = builder.finish(B256::repeat_byte(0x11), 42);
+        assert_eq!(diff.block_number, U64::from(42));
+        assert_eq!(
+            diff.system,
+            BTreeMap::from([(
+                USER,
+                AccountDiff { balance: balance(0, 100), ..Default::default() }
+            )])
+        );
+        let payment = ValueChange { from: B256::ZERO, to: B256::with_last_byte(30) };
+        assert_eq!(
+            diff.user,
+            BTreeMap::from([
+                (
+                    USER,
+                    AccountDiff {
+                        balance: balance(100, 70),
+                        nonce: ValueChange::new(U64::ZERO, U64::from(1)),
+                        ..Default::default()
+                    }
+                ),
+                (
+                    TOKEN,
+                    AccountDiff {
+                        balance: balance(5, 35),
+                        storage: BTreeMap::from([(B256::with_last_byte(1), payment)]),
+                        ..Default::default()
+                    }
+                ),
+            ])
+        );
+        assert_eq!(
+            serde_json::to_value(&diff.user[&USER]).unwrap(),
+            json!({
+                "balance": { "from": "0x64", "to": "0x46" },
+                "nonce": { "from": "0x0", "to": "0x1" },
+            })
+        );
+    }
+
+    #[test]
+    fn changes_reverted_within_the_block_are_dropped() {
+        let mut db = InMemoryDB::default();
+        let mut builder = StateDiffBuilder::default();
+        let first = EvmState::from_iter([(USER, account(10, 0, &[(1, 0, 7)]))]);
+        builder.record(false, &first, &mut db).unwrap();
+        db.insert_account_info(USER, first[&USER].info.clone());
+
+        let second = EvmState::from_iter([(USER, account(0, 0, &[(1, 7, 0)]))]);
+        builder.record(false, &second, &mut db).unwrap();
+
+        let diff = builder.finish(B256::ZERO, 1);
+        assert!(diff.system.is_empty());
+        assert!(diff.user.is_empty());
+    }
+
+    #[test]
+    fn failed_replay_names_the_block() {
+        let err = replay_failed(B256::repeat_byte(0x11), eyre::eyre!("parent state not found"));
+        assert_eq!(err.code(), HlErrorCode::ReplayFailed.code());
+        assert_eq!(err.message(), "Failed to re-execute block: parent state not found");
+        let data: ReplayFailedData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data.block_hash, B256::repeat_byte(0x11));
+    }
+}
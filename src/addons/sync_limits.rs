@@ -0,0 +1,366 @@
+//! Admission control for the sync server.
+//!
+//! Requests are limited by the number of concurrent sync requests, a per-client token bucket
+//! on blocks served and a global token bucket on serialized bytes. Rejected requests fail with
+//! [`SYNC_RATE_LIMITED_CODE`] and a `retryAfterMs` hint, which [`RpcBlockSource`] backs off on.
+//!
+//! [`RpcBlockSource`]: crate::pseudo_peer::sources::RpcBlockSource
+
+use clap::Args;
+use jsonrpsee::{ConnectionId, Extensions};
+use jsonrpsee_types::ErrorObject;
+use reth_metrics::{
+    Metrics,
+    metrics::{Counter, Gauge},
+};
+use reth_network::cache::LruMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// JSON-RPC error code returned when a sync request is over the server's limits.
+///
+/// The request can be retried after the `retryAfterMs` carried in the error data.
+pub const SYNC_RATE_LIMITED_CODE: i32 = -32005;
+
+/// Retry hint when the server is at its concurrency limit.
+const CONCURRENCY_RETRY_AFTER: Duration = Duration::from_millis(100);
+
+/// Limits applied to the sync server. All limits are disabled by default.
+#[derive(Debug, Clone, Default, Args)]
+pub struct SyncServerLimits {
+    /// Maximum number of sync requests served concurrently.
+    #[arg(
+        long = "sync-server-max-concurrent-requests",
+        env = "SYNC_SERVER_MAX_CONCURRENT_REQUESTS"
+    )]
+    pub max_concurrent_requests: Option<usize>,
+
+    /// Maximum number of blocks served per second to a single client.
+    ///
+    /// Clients are identified by IP when the sync server has its own listener
+    /// (--sync-server-addr), and by connection on the RPC servers and Unix domain sockets.
+    /// Up to one second worth of blocks can be requested at once.
+    #[arg(long = "sync-server-max-blocks-per-second", env = "SYNC_SERVER_MAX_BLOCKS_PER_SECOND")]
+    pub max_blocks_per_second: Option<u32>,
+
+    /// Maximum number of serialized bytes served per second, across all clients.
+    #[arg(long = "sync-server-max-bytes-per-second", env = "SYNC_SERVER_MAX_BYTES_PER_SECOND")]
+    pub max_bytes_per_second: Option<u64>,
+}
+
+/// Data attached to a [`SYNC_RATE_LIMITED_CODE`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitedData {
+    pub retry_after_ms: u64,
+}
+
+/// A request rejected by the [`SyncRateLimiter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+#[display("Sync server limit exceeded ({reason}), retry after {retry_after:?}")]
+pub struct RateLimited {
+    pub reason: &'static str,
+    pub retry_after: Duration,
+}
+
+impl From<RateLimited> for ErrorObject<'static> {
+    fn from(err: RateLimited) -> Self {
+        let data = RateLimitedData { retry_after_ms: err.retry_after.as_millis() as u64 };
+        ErrorObject::owned(SYNC_RATE_LIMITED_CODE, err.to_string(), Some(data))
+    }
+}
+
+/// Identifies a sync client for per-client limits.
+///
+/// Only listeners that insert the [`SocketAddr`] of the client into the request extensions, such
+/// as [`serve_over_tcp`], identify clients by IP.
+///
+/// [`serve_over_tcp`]: crate::addons::sync_server::serve_over_tcp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    Ip(IpAddr),
+    Connection(usize),
+    Unknown,
+}
+
+tokio::task_local! {
+    /// Extensions of the request served by a wrapped method, see [`forward_extensions`].
+    static FORWARDED_EXTENSIONS: Extensions;
+}
+
+/// Runs `call`, a call of a wrapped method through [`Methods::call`], with the extensions `ext`
+/// of the request. [`Methods::call`] passes no extensions on, so the wrapped method identifies
+/// its client by the forwarded ones instead. A wrapper that was itself called without them
+/// forwards the ones of the outer wrapper.
+///
+/// [`Methods::call`]: jsonrpsee::core::server::Methods::call
+pub(crate) async fn forward_extensions<F: Future>(ext: &Extensions, call: F) -> F::Output {
+    let ext = match ClientKey::from_request(ext) {
+        Some(_) => ext.clone(),
+        None => FORWARDED_EXTENSIONS.try_with(Clone::clone).unwrap_or_else(|_| ext.clone()),
+    };
+    FORWARDED_EXTENSIONS.scope(ext, call).await
+}
+
+impl ClientKey {
+    /// Identifies the client of a request by its extensions, or by the ones forwarded to the
+    /// wrapped method serving it.
+    pub fn from_extensions(ext: &Extensions) -> Self {
+        Self::from_request(ext)
+            .or_else(|| FORWARDED_EXTENSIONS.try_with(Self::from_request).ok().flatten())
+            .unwrap_or(Self::Unknown)
+    }
+
+    fn from_request(ext: &Extensions) -> Option<Self> {
+        if let Some(addr) = ext.get::<SocketAddr>() {
+            Some(Self::Ip(addr.ip()))
+        } else {
+            ext.get::<ConnectionId>().map(|id| Self::Connection(id.0))
+        }
+    }
+}
+
+/// A token bucket refilled continuously at `rate` tokens per second, holding at most one
+/// second worth of tokens.
+///
+/// Costs above the capacity are admitted once the bucket is full and leave it in debt, so that
+/// large requests are slowed down rather than rejected forever.
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64, now: Instant) -> Self {
+        Self { rate, tokens: rate, updated_at: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated_at = now;
+    }
+
+    /// Takes `cost` tokens, or returns how long to wait until they are available.
+    pub(crate) fn try_take(&mut self, cost: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        let required = cost.min(self.rate);
+        if self.tokens < required {
+            return Err(Duration::from_secs_f64((required - self.tokens) / self.rate));
+        }
+        self.tokens -= cost;
+        Ok(())
+    }
+}
+
+#[derive(Metrics, Clone)]
+#[metrics(scope = "sync_server")]
+struct SyncServerMetrics {
+    /// Number of sync requests currently being served
+    in_flight_requests: Gauge,
+    /// Number of clients tracked by the per-client limit
+    tracked_clients: Gauge,
+    /// Bytes that can currently be served under the bandwidth limit
+    available_bandwidth_bytes: Gauge,
+    /// How many sync requests were rejected by the limits
+    rate_limited_requests: Counter,
+    /// How many blocks were admitted for serving
+    admitted_blocks: Counter,
+    /// How many serialized bytes were served
+    served_bytes: Counter,
+}
+
+/// Enforces [`SyncServerLimits`] on incoming sync requests.
+pub struct SyncRateLimiter {
+    concurrency: Option<Arc<Semaphore>>,
+    blocks_per_second: Option<f64>,
+    clients: Mutex<LruMap<ClientKey, TokenBucket>>,
+    bandwidth: Option<Mutex<TokenBucket>>,
+    metrics: SyncServerMetrics,
+}
+
+impl std::fmt::Debug for SyncRateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncRateLimiter").finish_non_exhaustive()
+    }
+}
+
+/// Admission granted by [`SyncRateLimiter::admit`], held while the request is served.
+pub struct SyncPermit<'a> {
+    limiter: &'a SyncRateLimiter,
+    _concurrency: Option<OwnedSemaphorePermit>,
+}
+
+impl SyncPermit<'_> {
+    /// Accounts the serialized size of the response against the bandwidth limit.
+    pub fn record_bytes(&self, bytes: usize) {
+        self.limiter.metrics.served_bytes.increment(bytes as u64);
+        if let Some(bandwidth) = &self.limiter.bandwidth {
+            let mut bandwidth = bandwidth.lock().unwrap();
+            bandwidth.tokens -= bytes as f64;
+            self.limiter.metrics.available_bandwidth_bytes.set(bandwidth.tokens.max(0.0));
+        }
+    }
+}
+
+impl Drop for SyncPermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.metrics.in_flight_requests.decrement(1);
+    }
+}
+
+impl SyncRateLimiter {
+    /// Number of clients whose token buckets are kept around.
+    const MAX_TRACKED_CLIENTS: u32 = 4096;
+
+    pub fn new(limits: &SyncServerLimits) -> Self {
+        let now = Instant::now();
+        Self {
+            concurrency: limits.max_concurrent_requests.map(|n| Arc::new(Semaphore::new(n))),
+            blocks_per_second: limits.max_blocks_per_second.map(f64::from),
+            clients: Mutex::new(LruMap::new(Self::MAX_TRACKED_CLIENTS)),
+            bandwidth: limits
+                .max_bytes_per_second
+                .map(|rate| Mutex::new(TokenBucket::new(rate as f64, now))),
+            metrics: SyncServerMetrics::default(),
+        }
+    }
+
+    /// Admits a request for `blocks` blocks from `client`.
+    ///
+    /// Block tokens are only taken once every other limit has admitted the request. The
+    /// bandwidth limit admits requests as long as it isn't in debt; the actual size is accounted
+    /// afterwards through [`SyncPermit::record_bytes`].
+    pub fn admit(&self, client: ClientKey, blocks: u64) -> Result<SyncPermit<'_>, RateLimited> {
+        let result = self.try_admit(client, blocks, Instant::now());
+        match &result {
+            Ok(_) => {
+                self.metrics.in_flight_requests.increment(1);
+                self.metrics.admitted_blocks.increment(blocks);
+            }
+            Err(_) => self.metrics.rate_limited_requests.increment(1),
+        }
+        result
+    }
+
+    fn try_admit(
+        &self,
+        client: ClientKey,
+        blocks: u64,
+        now: Instant,
+    ) -> Result<SyncPermit<'_>, RateLimited> {
+        let concurrency = match &self.concurrency {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().map_err(|_| {
+                RateLimited { reason: "concurrent requests", retry_after: CONCURRENCY_RETRY_AFTER }
+            })?),
+            None => None,
+        };
+
+        if let Some(bandwidth) = &self.bandwidth {
+            let mut bandwidth = bandwidth.lock().unwrap();
+            bandwidth
+                .try_take(0.0, now)
+                .map_err(|retry_after| RateLimited { reason: "bandwidth", retry_after })?;
+            self.metrics.available_bandwidth_bytes.set(bandwidth.tokens);
+        }
+
+        if let Some(rate) = self.blocks_per_second {
+            let mut clients = self.clients.lock().unwrap();
+            if clients.get(&client).is_none() {
+                clients.insert(client, TokenBucket::new(rate, now));
+            }
+            self.metrics.tracked_clients.set(clients.len() as f64);
+            let bucket = clients.get(&client).expect("bucket was just inserted");
+            bucket
+                .try_take(blocks as f64, now)
+                .map_err(|retry_after| RateLimited { reason: "blocks per second", retry_after })?;
+        }
+
+        Ok(SyncPermit { limiter: self, _concurrency: concurrency })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(10.0, start);
+        assert!(bucket.try_take(10.0, start).is_ok());
+        assert_eq!(bucket.try_take(5.0, start), Err(Duration::from_millis(500)));
+        assert!(bucket.try_take(5.0, start + Duration::from_millis(500)).is_ok());
+
+        // Oversized costs wait for a full bucket and leave it in debt
+        let later = start + Duration::from_secs(10);
+        assert!(bucket.try_take(20.0, later).is_ok());
+        assert_eq!(bucket.try_take(0.0, later), Err(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn clients_are_limited_independently() {
+        let limits = SyncServerLimits { max_blocks_per_second: Some(2), ..Default::default() };
+        let limiter = SyncRateLimiter::new(&limits);
+        let (a, b) = (ClientKey::Connection(1), ClientKey::Connection(2));
+        let now = Instant::now();
+
+        assert!(limiter.try_admit(a, 2, now).is_ok());
+        let err = limiter.try_admit(a, 1, now).err().unwrap();
+        assert_eq!(err.reason, "blocks per second");
+        assert_eq!(err.retry_after, Duration::from_millis(500));
+        assert!(limiter.try_admit(b, 2, now).is_ok());
+    }
+
+    #[test]
+    fn connections_of_one_ip_share_a_budget() {
+        let connection = |ip: [u8; 4], port: u16| {
+            let mut ext = Extensions::new();
+            ext.insert(SocketAddr::from((ip, port)));
+            ClientKey::from_extensions(&ext)
+        };
+        let (a, b) = (connection([10, 0, 0, 1], 40000), connection([10, 0, 0, 1], 40001));
+        assert_eq!(a, b);
+        assert_eq!(a, ClientKey::Ip([10, 0, 0, 1].into()));
+
+        let limits = SyncServerLimits { max_blocks_per_second: Some(2), ..Default::default() };
+        let limiter = SyncRateLimiter::new(&limits);
+        let now = Instant::now();
+        assert!(limiter.try_admit(a, 2, now).is_ok());
+        assert!(limiter.try_admit(b, 1, now).is_err());
+        assert!(limiter.try_admit(connection([10, 0, 0, 2], 40000), 2, now).is_ok());
+    }
+
+    #[test]
+    fn concurrency_and_bandwidth_are_limited_globally() {
+        let limits = SyncServerLimits {
+            max_concurrent_requests: Some(1),
+            max_bytes_per_second: Some(1000),
+            ..Default::default()
+        };
+        let limiter = SyncRateLimiter::new(&limits);
+        let now = Instant::now();
+
+        let permit = limiter.try_admit(ClientKey::Unknown, 1, now).unwrap();
+        let err = limiter.try_admit(ClientKey::Unknown, 1, now).err().unwrap();
+        assert_eq!(err.reason, "concurrent requests");
+        permit.record_bytes(1500);
+        drop(permit);
+
+        let err = limiter.try_admit(ClientKey::Unknown, 1, now).err().unwrap();
+        assert_eq!(err.reason, "bandwidth");
+        assert_eq!(err.retry_after, Duration::from_millis(500));
+
+        let error = ErrorObject::from(err);
+        assert_eq!(error.code(), SYNC_RATE_LIMITED_CODE);
+        let data: RateLimitedData = serde_json::from_str(error.data().unwrap().get()).unwrap();
+        assert_eq!(data.retry_after_ms, 500);
+    }
+}
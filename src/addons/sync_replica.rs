@@ -0,0 +1,179 @@
+//! Read-only replica of the sync server's database, for spreading block reads of a seed node.
+//!
+//! A node serving many sync clients can saturate the disk of its own datadir. With
+//! `--sync-replica-datadir`, block reads alternate between the node's database and a read-only
+//! copy of it, e.g. on another disk kept up to date by a second node. The replica only serves
+//! blocks it has finished, and none at all while it lags the node by more than the allowed
+//! number of blocks, so clients never see a difference.
+
+use crate::{
+    addons::{sync_server::SyncBlockReader, sync_static_files::StaticFileSyncReader},
+    chainspec::HlChainSpec,
+    node::{HlNode, types::BlockAndReceipts},
+};
+use alloy_primitives::B256;
+use reth::api::NodeTypesWithDBAdapter;
+use reth_db::{ClientVersion, DatabaseEnv, mdbx::DatabaseArguments, open_db_read_only};
+use reth_provider::{ProviderFactory, providers::StaticFileProvider};
+use std::{
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// Default number of blocks the replica may trail the node by and still serve blocks.
+pub const DEFAULT_REPLICA_MAX_LAG: u64 = 64;
+
+/// How often the heights of the node and the replica are compared.
+const REPLICA_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Opens the database and static files of the datadir at `path` read-only, as a sync reader.
+pub fn open_replica_reader(
+    path: &Path,
+    chain_spec: Arc<HlChainSpec>,
+) -> eyre::Result<Arc<dyn SyncBlockReader>> {
+    let db = open_db_read_only(&path.join("db"), DatabaseArguments::new(ClientVersion::default()))?;
+    // Watched, so that static files the replica's writer adds later are picked up
+    let static_files = StaticFileProvider::read_only(path.join("static_files"), true)?;
+    let factory = ProviderFactory::<NodeTypesWithDBAdapter<HlNode, Arc<DatabaseEnv>>>::new(
+        Arc::new(db),
+        chain_spec,
+        static_files,
+    );
+    info!(path = %path.display(), "Opened sync replica");
+    Ok(Arc::new(StaticFileSyncReader::new(factory)))
+}
+
+/// Heights last read from the node and the replica.
+#[derive(Debug)]
+struct ReplicaCheck {
+    checked_at: Option<Instant>,
+    /// Highest block the replica finished.
+    replica_height: u64,
+    /// Whether the replica trails the node by more than the allowed lag.
+    lagging: bool,
+}
+
+/// Sync reader that alternates block reads between the node's database and a read-only replica.
+///
+/// Everything but block bodies, i.e. the heights and hashes the sync server checks requests
+/// against, is read from the node's database.
+#[derive(Debug)]
+pub struct ReplicaSyncReader {
+    primary: Arc<dyn SyncBlockReader>,
+    replica: Arc<dyn SyncBlockReader>,
+    max_lag: u64,
+    reads: AtomicU64,
+    check: Mutex<ReplicaCheck>,
+}
+
+impl ReplicaSyncReader {
+    pub fn new(
+        primary: Arc<dyn SyncBlockReader>,
+        replica: Arc<dyn SyncBlockReader>,
+        max_lag: u64,
+    ) -> Self {
+        Self {
+            primary,
+            replica,
+            max_lag,
+            reads: AtomicU64::new(0),
+            check: Mutex::new(ReplicaCheck { checked_at: None, replica_height: 0, lagging: true }),
+        }
+    }
+
+    /// Whether the replica may serve block `number`, comparing heights at most every
+    /// [`REPLICA_CHECK_INTERVAL`].
+    fn replica_serves(&self, number: u64) -> bool {
+        let mut check = self.check.lock().unwrap();
+        if check.checked_at.is_none_or(|at| at.elapsed() >= REPLICA_CHECK_INTERVAL) {
+            check.checked_at = Some(Instant::now());
+            match (self.primary.finished_block_number(), self.replica.finished_block_number()) {
+                (Ok(primary), Ok(replica)) => {
+                    let lagging = primary.saturating_sub(replica) > self.max_lag;
+                    match (check.lagging, lagging) {
+                        (true, false) => info!(primary, replica, "Sync replica serves blocks"),
+                        (false, true) => {
+                            warn!(primary, replica, "Sync replica lags; serving blocks without it")
+                        }
+                        _ => {}
+                    }
+                    check.replica_height = replica;
+                    check.lagging = lagging;
+                }
+                (_, Err(err)) | (Err(err), _) => {
+                    debug!(target: "rpc::hl", %err, "Failed to compare sync replica height");
+                    check.lagging = true;
+                }
+            }
+        }
+        !check.lagging && number <= check.replica_height
+    }
+
+    /// Reader for the next block read: every other read goes to the replica if it can serve it.
+    fn reader_for(&self, number: u64) -> &dyn SyncBlockReader {
+        let use_replica = self.reads.fetch_add(1, Ordering::Relaxed) % 2 == 1;
+        if use_replica && self.replica_serves(number) { &*self.replica } else { &*self.primary }
+    }
+}
+
+impl SyncBlockReader for ReplicaSyncReader {
+    fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+        self.reader_for(number).read_block_and_receipts(number)
+    }
+
+    fn best_block_number(&self) -> eyre::Result<u64> {
+        self.primary.best_block_number()
+    }
+
+    fn block_hash(&self, number: u64) -> eyre::Result<Option<B256>> {
+        self.primary.block_hash(number)
+    }
+
+    fn read_block_by_hash(&self, hash: B256) -> eyre::Result<Option<BlockAndReceipts>> {
+        self.primary.read_block_by_hash(hash)
+    }
+
+    fn finished_block_number(&self) -> eyre::Result<u64> {
+        self.primary.finished_block_number()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::CountingReader;
+
+    #[test]
+    fn spreads_reads_across_primary_and_replica() {
+        let (primary, replica) = (CountingReader::new(100), CountingReader::new(100));
+        let reader = ReplicaSyncReader::new(primary.clone(), replica.clone(), 10);
+        for number in 1..=10 {
+            assert_eq!(reader.read_block_and_receipts(number).unwrap().number(), number);
+        }
+        assert_eq!((primary.reads(), replica.reads()), (5, 5));
+    }
+
+    #[test]
+    fn lagging_replica_is_not_served() {
+        let (primary, replica) = (CountingReader::new(100), CountingReader::new(95));
+        let reader = ReplicaSyncReader::new(primary.clone(), replica.clone(), 10);
+        // Blocks past the replica's height are read from the primary
+        for number in 91..=100 {
+            reader.read_block_and_receipts(number).unwrap();
+        }
+        assert_eq!((primary.reads(), replica.reads()), (8, 2));
+
+        // Beyond the allowed lag the replica serves nothing, even the blocks it has
+        let (primary, replica) = (CountingReader::new(100), CountingReader::new(80));
+        let reader = ReplicaSyncReader::new(primary.clone(), replica.clone(), 10);
+        for number in 1..=10 {
+            reader.read_block_and_receipts(number).unwrap();
+        }
+        assert_eq!((primary.reads(), replica.reads()), (10, 0));
+    }
+}
@@ -1,18 +1,89 @@
-use crate::node::types::BlockAndReceipts;
-use alloy_primitives::Bytes;
-use jsonrpsee::proc_macros::rpc;
-use jsonrpsee_core::{RpcResult, async_trait};
+use crate::{
+    addons::{
+        rpc_rate_limit::RpcRateLimiter,
+        sync_limits::{ClientKey, SyncRateLimiter, SyncServerLimits},
+    },
+    node::{
+        rpc::errors::{BlockNotServedData, HlErrorCode, HlRpcError},
+        types::{BLOCK_FORMAT_VERSION, BlockAndReceipts},
+    },
+    pseudo_peer::sources::ActiveSource,
+};
+use alloy_primitives::{B256, Bytes};
+use hyper::body::Incoming;
+use jsonrpsee::{
+    Extensions, Methods, PendingSubscriptionSink, SubscriptionMessage,
+    proc_macros::rpc,
+    server::{HttpRequest, Server, ServerHandle, serve_with_graceful_shutdown, stop_channel},
+};
+use jsonrpsee_core::{RpcResult, SubscriptionResult, async_trait};
+use lz4_flex::frame::{FrameDecoder, FrameEncoder, FrameInfo};
 use reth::rpc::result::internal_rpc_err;
-use std::sync::OnceLock;
-use tracing::trace;
+use reth_network::cache::LruMap;
+use reth_stages_types::StageId;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU8, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::net::TcpListener;
+use tower::Service;
+use tracing::{debug, trace};
+
+/// Default budget for the compressed size of a `hl_syncGetBlocks` response.
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Default number of serialized blocks kept in memory by the sync server.
+pub const DEFAULT_PAYLOAD_CACHE_SIZE: u32 = 1024;
+
+/// Default number of blocks a serving node may trail its own source by and still be ready.
+pub const DEFAULT_MAX_READY_LAG: u64 = 64;
+
+/// How often a `hl_subscribeBlocks` subscription checks for newly synced blocks.
+const BLOCK_PUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Most blocks pushed at once to a subscriber that fell behind. Older blocks are left for it to
+/// request.
+const MAX_PUSHED_BACKLOG: u64 = 64;
+
+/// Version of the sync protocol served by this node.
+///
+/// - 1: `hl_syncGetBlocks(heights)`. Servers without `hl_syncProtocolVersion` speak version 1.
+/// - 2: `hl_syncGetBlocks(heights, known)`, where blocks the client already has are confirmed by
+///   hash instead of being sent again.
+pub const SYNC_PROTOCOL_VERSION: u64 = 2;
 
 /// Trait for reading blocks from the database for the sync server.
-pub trait SyncBlockReader: Send + Sync + 'static {
+pub trait SyncBlockReader: Send + Sync + std::fmt::Debug + 'static {
     fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts>;
     fn best_block_number(&self) -> eyre::Result<u64>;
+
+    /// Returns the hash of the block at `number`, if it is stored.
+    fn block_hash(&self, number: u64) -> eyre::Result<Option<B256>> {
+        Ok(Some(self.read_block_and_receipts(number)?.hash()))
+    }
+
+    /// Returns the block with the given hash, if it is stored.
+    fn read_block_by_hash(&self, _hash: B256) -> eyre::Result<Option<BlockAndReceipts>> {
+        Ok(None)
+    }
+
+    /// Returns the highest block that went through every stage, i.e. the highest block that is
+    /// safe to serve.
+    fn finished_block_number(&self) -> eyre::Result<u64> {
+        self.best_block_number()
+    }
 }
 
 /// Wraps any reth provider that implements the needed traits.
+#[derive(Debug)]
 pub struct ProviderSyncReader<P> {
     provider: P,
 }
@@ -28,6 +99,8 @@ where
     P: reth_provider::BlockReader<Block = crate::HlBlock>
         + reth_provider::ReceiptProvider<Receipt = reth_ethereum_primitives::EthereumReceipt>
         + reth_provider::BlockNumReader
+        + reth_provider::StageCheckpointReader
+        + std::fmt::Debug
         + Send
         + Sync
         + 'static,
@@ -47,21 +120,310 @@ where
     fn best_block_number(&self) -> eyre::Result<u64> {
         Ok(self.provider.last_block_number()?)
     }
+
+    fn block_hash(&self, number: u64) -> eyre::Result<Option<B256>> {
+        Ok(self.provider.block_hash(number)?)
+    }
+
+    fn read_block_by_hash(&self, hash: B256) -> eyre::Result<Option<BlockAndReceipts>> {
+        let Some(block) = self.provider.block_by_hash(hash)? else {
+            return Ok(None);
+        };
+        let receipts = self
+            .provider
+            .receipts_by_block(hash.into())?
+            .ok_or_else(|| eyre::eyre!("Receipts for block {hash} not found in database"))?;
+        Ok(Some(BlockAndReceipts::from_db_sealed(hash, block, receipts)))
+    }
+
+    fn finished_block_number(&self) -> eyre::Result<u64> {
+        Ok(self
+            .provider
+            .get_stage_checkpoint(StageId::Finish)?
+            .unwrap_or_default()
+            .block_number)
+    }
+}
+
+/// Latest block seen from the serving node's own block source, shared between the block source
+/// that records it and the sync server that reports it.
+///
+/// Also counts the block cache lookups and tracks the hl-node source in use, for the periodic
+/// status log. Everything is atomic, so recording doesn't contend with the fetches.
+#[derive(Debug, Clone, Default)]
+pub struct SyncSourceStatus {
+    /// 0 until the source reported a block.
+    latest: Arc<AtomicU64>,
+    cache_hits: Arc<AtomicU64>,
+    cache_misses: Arc<AtomicU64>,
+    /// 0 without an hl-node source, otherwise 1 + the [`ActiveSource`] gauge value.
+    active_source: Arc<AtomicU8>,
+}
+
+impl SyncSourceStatus {
+    /// Records that the source has a block at `height`.
+    pub fn record(&self, height: u64) {
+        self.latest.fetch_max(height, Ordering::Relaxed);
+    }
+
+    /// Returns the latest block known to be available from the source.
+    pub fn latest(&self) -> Option<u64> {
+        Some(self.latest.load(Ordering::Relaxed)).filter(|latest| *latest > 0)
+    }
+
+    /// Records lookups of the block cache in front of the source.
+    pub fn record_cache_lookups(&self, hits: u64, misses: u64) {
+        self.cache_hits.fetch_add(hits, Ordering::Relaxed);
+        self.cache_misses.fetch_add(misses, Ordering::Relaxed);
+    }
+
+    /// Returns the running `(hits, misses)` counts of the block cache.
+    pub fn cache_lookups(&self) -> (u64, u64) {
+        (self.cache_hits.load(Ordering::Relaxed), self.cache_misses.load(Ordering::Relaxed))
+    }
+
+    /// Records the source serving blocks of the hl-node block source.
+    pub fn record_active_source(&self, active: ActiveSource) {
+        self.active_source.store(active.gauge_value() as u8 + 1, Ordering::Relaxed);
+    }
+
+    /// Returns the source serving blocks of the hl-node block source, if the node has one.
+    pub fn active_source(&self) -> Option<ActiveSource> {
+        match self.active_source.load(Ordering::Relaxed) {
+            1 => Some(ActiveSource::Local),
+            2 => Some(ActiveSource::Fallback),
+            _ => None,
+        }
+    }
+}
+
+/// Sync status of a serving node, as returned by `hl_syncLatestBlockNumber`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncLatestBlock {
+    /// Highest block the node serves.
+    pub latest: u64,
+    /// Latest block of the node's own block source, if it has one.
+    pub source_latest: Option<u64>,
+    /// How far `latest` trails `source_latest`.
+    pub lag: Option<u64>,
+    /// Whether the node is close enough to its source for followers to sync from it.
+    pub ready: bool,
+    /// Chain the node serves, for followers to check before fetching blocks. Missing from older
+    /// servers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+}
+
+/// Response of `hl_syncLatestBlockNumber`: a plain height for servers running with
+/// `--sync-server-legacy-latest-block-number` (and older servers), a [`SyncLatestBlock`]
+/// otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SyncLatestBlockResponse {
+    Status(SyncLatestBlock),
+    Number(Option<u64>),
+}
+
+impl SyncLatestBlockResponse {
+    pub fn latest(&self) -> Option<u64> {
+        match self {
+            Self::Status(status) => Some(status.latest),
+            Self::Number(latest) => *latest,
+        }
+    }
+
+    /// Chain of the server, if it reports one. Plain heights never do.
+    pub fn chain_id(&self) -> Option<u64> {
+        match self {
+            Self::Status(status) => status.chain_id,
+            Self::Number(_) => None,
+        }
+    }
+
+    /// Whether the server is ready to be synced from. Plain heights are always ready.
+    pub fn ready(&self) -> bool {
+        match self {
+            Self::Status(status) => status.ready,
+            Self::Number(_) => true,
+        }
+    }
+}
+
+/// LRU of recently served blocks, msgpack-encoded and keyed by block hash.
+///
+/// Followers backfilling in parallel tend to request the same ranges, so caching the encoded
+/// payload skips both the database read and the serialization for hot blocks.
+#[derive(Debug)]
+pub struct SerializedBlockCache {
+    cache: Mutex<LruMap<B256, Bytes>>,
+}
+
+impl SerializedBlockCache {
+    pub fn new(limit: u32) -> Self {
+        Self { cache: Mutex::new(LruMap::new(limit)) }
+    }
+
+    /// Returns the msgpack encoding of the block at `height`, reading and caching it on a miss.
+    fn get_or_encode(&self, reader: &dyn SyncBlockReader, height: u64) -> eyre::Result<Bytes> {
+        if let Some(hash) = reader.block_hash(height)? &&
+            let Some(payload) = self.cache.lock().unwrap().get(&hash)
+        {
+            return Ok(payload.clone());
+        }
+
+        let block = reader.read_block_and_receipts(height)?;
+        let payload = encode_block(&block)?;
+        self.cache.lock().unwrap().insert(block.hash(), payload.clone());
+        Ok(payload)
+    }
+}
+
+/// Encodes a single block as msgpack.
+pub(crate) fn encode_block(block: &BlockAndReceipts) -> eyre::Result<Bytes> {
+    let mut payload = Vec::new();
+    // Use write_named (map format) to match the S3/Go msgpack format.
+    rmp_serde::encode::write_named(&mut payload, block)?;
+    Ok(payload.into())
+}
+
+/// Frames one msgpack-encoded block as a single-element array, compressed the same way as the S3
+/// and local block sources.
+pub(crate) fn encode_single_block(block: &[u8]) -> RpcResult<Bytes> {
+    let mut encoder = checksummed_encoder();
+    rmp::encode::write_array_len(&mut encoder, 1)
+        .map_err(|e| internal_rpc_err(format!("Failed to serialize block: {e}")))?;
+    encoder
+        .write_all(block)
+        .map_err(|e| internal_rpc_err(format!("Failed to serialize block: {e}")))?;
+    let compressed = encoder
+        .finish()
+        .map_err(|e| internal_rpc_err(format!("Failed to compress block: {e}")))?;
+    Ok(Bytes::from(compressed))
+}
+
+/// Starts an lz4 frame that ends with an xxhash32 checksum of the uncompressed msgpack.
+///
+/// Decoders verify the checksum once the frame is read, so clients catch a payload corrupted
+/// anywhere between serialization and their decode even when the msgpack still parses.
+fn checksummed_encoder() -> FrameEncoder<Vec<u8>> {
+    FrameEncoder::with_frame_info(FrameInfo::new().content_checksum(true), Vec::new())
+}
+
+/// Returns the msgpack encoding of the block at `height`, going through `cache` if enabled.
+fn read_encoded_block(
+    reader: &dyn SyncBlockReader,
+    cache: Option<&SerializedBlockCache>,
+    height: u64,
+) -> eyre::Result<Bytes> {
+    match cache {
+        Some(cache) => cache.get_or_encode(reader, height),
+        None => encode_block(&reader.read_block_and_receipts(height)?),
+    }
+}
+
+/// Response of `hl_syncGetBlocks`.
+///
+/// Complete responses are plain msgpack+lz4 bytes, with the lz4 content checksum set. Once the
+/// response budget is exhausted, the remaining slots of the msgpack array are `nil` and
+/// `truncatedAt` is the first height that was not served, so that the client can request the
+/// rest.
+///
+/// Requests carrying known blocks (protocol version 2) are always answered with `Confirmed`:
+/// blocks whose hash matches are `nil` and listed in `confirmed` instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SyncBlocksResponse {
+    Complete(Bytes),
+    // Must come before `Truncated`, which would otherwise match and drop `confirmed`
+    #[serde(rename_all = "camelCase")]
+    Confirmed {
+        blocks: Bytes,
+        confirmed: Vec<(u64, B256)>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        truncated_at: Option<u64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Truncated { blocks: Bytes, truncated_at: u64 },
 }
 
-static DB_READER: OnceLock<Box<dyn SyncBlockReader>> = OnceLock::new();
+impl SyncBlocksResponse {
+    /// Size of the compressed blocks.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::Complete(blocks) |
+            Self::Confirmed { blocks, .. } |
+            Self::Truncated { blocks, .. } => blocks.len(),
+        }
+    }
+
+    /// Blocks the client already has, as `(height, hash)` pairs.
+    pub fn confirmed(&self) -> &[(u64, B256)] {
+        match self {
+            Self::Confirmed { confirmed, .. } => confirmed,
+            Self::Complete(_) | Self::Truncated { .. } => &[],
+        }
+    }
 
-/// Set the database reader for the sync server.
-/// Called during node startup when `--enable-sync-server` is set.
-pub fn set_sync_db_reader(reader: Box<dyn SyncBlockReader>) {
-    DB_READER.set(reader).ok();
+    /// Decodes the served blocks, along with the height the response was truncated at.
+    pub fn decode(&self) -> eyre::Result<(Vec<BlockAndReceipts>, Option<u64>)> {
+        let (bytes, truncated_at) = match self {
+            Self::Complete(bytes) => (bytes, None),
+            Self::Confirmed { blocks, truncated_at, .. } => (blocks, *truncated_at),
+            Self::Truncated { blocks, truncated_at } => (blocks, Some(*truncated_at)),
+        };
+        let mut decoder = FrameDecoder::new(&bytes[..]);
+        let blocks: Vec<Option<BlockAndReceipts>> = rmp_serde::from_read(&mut decoder)?;
+        Ok((blocks.into_iter().flatten().collect(), truncated_at))
+    }
 }
 
-fn get_sync_db_reader() -> RpcResult<&'static dyn SyncBlockReader> {
-    DB_READER
-        .get()
-        .map(|b| b.as_ref())
-        .ok_or_else(|| internal_rpc_err("Sync server not yet initialized"))
+/// Serializes blocks one at a time straight into the lz4 encoder.
+///
+/// Peak memory is roughly one block plus the compressed output. Once the compressed output
+/// reaches `max_response_bytes`, no more blocks are read; at least one block is always served.
+///
+/// Blocks listed in `known` with a matching hash are confirmed rather than read.
+fn encode_blocks(
+    reader: &dyn SyncBlockReader,
+    cache: Option<&SerializedBlockCache>,
+    heights: &[u64],
+    known: Option<&HashMap<u64, B256>>,
+    max_response_bytes: usize,
+) -> eyre::Result<SyncBlocksResponse> {
+    let mut encoder = checksummed_encoder();
+    rmp::encode::write_array_len(&mut encoder, heights.len() as u32)?;
+
+    let mut truncated_at = None;
+    let mut confirmed = Vec::new();
+    for (index, &height) in heights.iter().enumerate() {
+        if truncated_at.is_none() && index > 0 && encoder.get_ref().len() >= max_response_bytes {
+            truncated_at = Some(height);
+        }
+        if truncated_at.is_some() {
+            rmp::encode::write_nil(&mut encoder)?;
+            continue;
+        }
+
+        if let Some(known_hash) = known.and_then(|known| known.get(&height)) &&
+            reader.block_hash(height)?.as_ref() == Some(known_hash)
+        {
+            confirmed.push((height, *known_hash));
+            rmp::encode::write_nil(&mut encoder)?;
+            continue;
+        }
+
+        encoder.write_all(&read_encoded_block(reader, cache, height)?)?;
+    }
+
+    let blocks = Bytes::from(encoder.finish()?);
+    Ok(match (known, truncated_at) {
+        (Some(_), truncated_at) => {
+            SyncBlocksResponse::Confirmed { blocks, confirmed, truncated_at }
+        }
+        (None, Some(truncated_at)) => SyncBlocksResponse::Truncated { blocks, truncated_at },
+        (None, None) => SyncBlocksResponse::Complete(blocks),
+    })
 }
 
 /// RPC trait for node-to-node block syncing.
@@ -71,70 +433,669 @@ fn get_sync_db_reader() -> RpcResult<&'static dyn SyncBlockReader> {
 #[rpc(server, namespace = "hl")]
 #[async_trait]
 pub trait HlSyncApi {
-    /// Returns a block at the given height, serialized as msgpack+lz4 bytes.
-    #[method(name = "syncGetBlock")]
+    /// Returns a block at the given height, serialized as msgpack+lz4 bytes with the lz4 content
+    /// checksum set.
+    #[method(name = "syncGetBlock", with_extensions)]
     async fn sync_get_block(&self, height: u64) -> RpcResult<Bytes>;
 
+    /// Returns the block with the given hash in the same format as `hl_syncGetBlock`, or `null`
+    /// if this node doesn't have it. Lets a client that detected a divergence fetch the block it
+    /// expects rather than whatever is stored at that height.
+    #[method(name = "syncGetBlockByHash", with_extensions)]
+    async fn sync_get_block_by_hash(&self, hash: B256) -> RpcResult<Option<Bytes>>;
+
     /// Returns multiple blocks by height, serialized as msgpack+lz4 bytes.
-    /// Heights are capped at 500 per request.
-    #[method(name = "syncGetBlocks")]
-    async fn sync_get_blocks(&self, heights: Vec<u64>) -> RpcResult<Bytes>;
+    /// Heights are capped at 500 per request, and the response is truncated once it exceeds the
+    /// server's size budget.
+    ///
+    /// `known` lists `(height, hash)` pairs the client already has; matching blocks are
+    /// confirmed instead of sent.
+    #[method(name = "syncGetBlocks", with_extensions)]
+    async fn sync_get_blocks(
+        &self,
+        heights: Vec<u64>,
+        known: Option<Vec<(u64, B256)>>,
+    ) -> RpcResult<SyncBlocksResponse>;
 
-    /// Returns the latest block number available from this node's database.
+    /// Returns the sync protocol version served by this node.
+    #[method(name = "syncProtocolVersion")]
+    async fn sync_protocol_version(&self) -> RpcResult<u64>;
+
+    /// Returns the version of the block encoding served by this node, see
+    /// [`BLOCK_FORMAT_VERSION`].
+    #[method(name = "syncBlockFormatVersion")]
+    async fn sync_block_format_version(&self) -> RpcResult<u64>;
+
+    /// Returns the latest block available from this node's database, along with how far the
+    /// node trails its own block source.
     #[method(name = "syncLatestBlockNumber")]
-    async fn sync_latest_block_number(&self) -> RpcResult<Option<u64>>;
+    async fn sync_latest_block_number(&self) -> RpcResult<SyncLatestBlockResponse>;
+
+    /// Streams the blocks this node serves as they are synced, each in the same format as
+    /// `hl_syncGetBlock`. Blocks synced before the subscription are not pushed.
+    #[subscription(
+        name = "subscribeBlocks" => "block",
+        unsubscribe = "unsubscribeBlocks",
+        item = Bytes
+    )]
+    async fn subscribe_blocks(&self) -> SubscriptionResult;
 }
 
-pub struct HlSyncServer;
+pub struct HlSyncServer {
+    reader: Arc<dyn SyncBlockReader>,
+    /// Budget for the compressed size of a `hl_syncGetBlocks` response.
+    max_response_bytes: usize,
+    /// Recently served blocks, disabled when the configured size is 0.
+    payload_cache: Option<Arc<SerializedBlockCache>>,
+    limiter: SyncRateLimiter,
+    /// The node's own block source, when it syncs from one.
+    source_status: Option<SyncSourceStatus>,
+    /// Lag to the block source above which the node reports itself as not ready.
+    max_ready_lag: u64,
+    /// Report the latest block as a plain height, for clients predating [`SyncLatestBlock`].
+    legacy_latest_block_number: bool,
+    /// Blocks below the synced height that are not served yet.
+    serve_lag: u64,
+    /// Chain reported to followers.
+    chain_id: Option<u64>,
+}
+
+impl HlSyncServer {
+    pub fn new(
+        reader: Arc<dyn SyncBlockReader>,
+        max_response_bytes: usize,
+        payload_cache_size: u32,
+        limits: &SyncServerLimits,
+    ) -> Self {
+        Self {
+            reader,
+            max_response_bytes,
+            payload_cache: (payload_cache_size > 0)
+                .then(|| Arc::new(SerializedBlockCache::new(payload_cache_size))),
+            limiter: SyncRateLimiter::new(limits),
+            source_status: None,
+            max_ready_lag: DEFAULT_MAX_READY_LAG,
+            legacy_latest_block_number: false,
+            serve_lag: 0,
+            chain_id: None,
+        }
+    }
+
+    /// Reports readiness relative to the block source tracked by `source_status`.
+    pub fn with_source_status(
+        mut self,
+        source_status: SyncSourceStatus,
+        max_ready_lag: u64,
+    ) -> Self {
+        self.source_status = Some(source_status);
+        self.max_ready_lag = max_ready_lag;
+        self
+    }
+
+    /// Reports the latest block as a plain height.
+    pub fn with_legacy_latest_block_number(mut self, legacy: bool) -> Self {
+        self.legacy_latest_block_number = legacy;
+        self
+    }
+
+    /// Withholds the `serve_lag` blocks below the synced height, which may still reorg.
+    pub fn with_serve_lag(mut self, serve_lag: u64) -> Self {
+        self.serve_lag = serve_lag;
+        self
+    }
+
+    /// Reports `chain_id` as the chain of the served blocks.
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Returns the highest block that may be served, `serve_lag` blocks below the synced height.
+    fn served_tip(&self, finished: u64) -> u64 {
+        served_tip(finished, self.serve_lag)
+    }
+
+    /// Fails if `height` is above the highest block that may be served.
+    fn ensure_servable(&self, height: u64) -> RpcResult<()> {
+        let finished = self
+            .reader
+            .finished_block_number()
+            .map_err(|e| internal_rpc_err(format!("Failed to get synced height: {e}")))?;
+        let tip = self.served_tip(finished);
+        if height > tip {
+            let message = format!(
+                "Block {height} is above this node's served tip {tip} \
+                 (synced height {finished}, serve lag {})",
+                self.serve_lag
+            );
+            let data = BlockNotServedData {
+                block_number: height,
+                served_tip: tip,
+                synced_height: finished,
+                serve_lag: self.serve_lag,
+            };
+            return Err(HlRpcError::new(HlErrorCode::BlockNotServed, message, data).into());
+        }
+        Ok(())
+    }
+
+    fn latest_block(&self) -> eyre::Result<SyncLatestBlock> {
+        let finished = self.reader.finished_block_number()?;
+        let latest = self.served_tip(finished);
+        let Some(source_status) = &self.source_status else {
+            return Ok(SyncLatestBlock {
+                latest,
+                source_latest: None,
+                lag: None,
+                ready: true,
+                chain_id: self.chain_id,
+            });
+        };
+
+        // The lag to the source is about sync progress, regardless of what is withheld
+        let source_latest = source_status.latest();
+        let lag = source_latest.map(|source_latest| source_latest.saturating_sub(finished));
+        // Not ready until the source reported anything
+        let ready = lag.is_some_and(|lag| lag <= self.max_ready_lag);
+        Ok(SyncLatestBlock { latest, source_latest, lag, ready, chain_id: self.chain_id })
+    }
+}
 
 #[async_trait]
 impl HlSyncApiServer for HlSyncServer {
-    async fn sync_get_block(&self, height: u64) -> RpcResult<Bytes> {
+    async fn sync_get_block(&self, ext: &Extensions, height: u64) -> RpcResult<Bytes> {
         trace!(target: "rpc::hl", height, "Serving hl_syncGetBlock");
-        let reader = get_sync_db_reader()?;
-        let block = reader
-            .read_block_and_receipts(height)
+        self.ensure_servable(height)?;
+        let permit = self.limiter.admit(ClientKey::from_extensions(ext), 1)?;
+        let block = read_encoded_block(&*self.reader, self.payload_cache.as_deref(), height)
             .map_err(|e| internal_rpc_err(format!("Failed to read block {height}: {e}")))?;
+        let compressed = encode_single_block(&block)?;
+        permit.record_bytes(compressed.len());
+        Ok(compressed)
+    }
 
-        // Encode as msgpack + lz4 (same format as S3/local block sources).
-        // Use write_named (map format) to match the S3/Go msgpack format.
-        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
-        rmp_serde::encode::write_named(&mut encoder, &vec![block])
+    async fn sync_get_block_by_hash(
+        &self,
+        ext: &Extensions,
+        hash: B256,
+    ) -> RpcResult<Option<Bytes>> {
+        trace!(target: "rpc::hl", %hash, "Serving hl_syncGetBlockByHash");
+        let permit = self.limiter.admit(ClientKey::from_extensions(ext), 1)?;
+        let Some(block) = self
+            .reader
+            .read_block_by_hash(hash)
+            .map_err(|e| internal_rpc_err(format!("Failed to read block {hash}: {e}")))?
+        else {
+            return Ok(None);
+        };
+        self.ensure_servable(block.number())?;
+        let payload = encode_block(&block)
             .map_err(|e| internal_rpc_err(format!("Failed to serialize block: {e}")))?;
-        let compressed = encoder
-            .finish()
-            .map_err(|e| internal_rpc_err(format!("Failed to compress block: {e}")))?;
-        Ok(Bytes::from(compressed))
+        let compressed = encode_single_block(&payload)?;
+        permit.record_bytes(compressed.len());
+        Ok(Some(compressed))
     }
 
-    async fn sync_get_blocks(&self, heights: Vec<u64>) -> RpcResult<Bytes> {
+    async fn sync_get_blocks(
+        &self,
+        ext: &Extensions,
+        heights: Vec<u64>,
+        known: Option<Vec<(u64, B256)>>,
+    ) -> RpcResult<SyncBlocksResponse> {
         const MAX_BATCH: usize = 500;
         let heights = if heights.len() > MAX_BATCH { &heights[..MAX_BATCH] } else { &heights };
         trace!(target: "rpc::hl", count = heights.len(), "Serving hl_syncGetBlocks");
-        let reader = get_sync_db_reader()?;
+        if let Some(&highest) = heights.iter().max() {
+            self.ensure_servable(highest)?;
+        }
+        let permit = self.limiter.admit(ClientKey::from_extensions(ext), heights.len() as u64)?;
 
-        let blocks: Vec<BlockAndReceipts> = heights
-            .iter()
-            .map(|&h| reader.read_block_and_receipts(h))
-            .collect::<Result<_, _>>()
-            .map_err(|e| internal_rpc_err(format!("Failed to read blocks: {e}")))?;
+        let known = known.map(|known| known.into_iter().collect::<HashMap<_, _>>());
+        let response = encode_blocks(
+            &*self.reader,
+            self.payload_cache.as_deref(),
+            heights,
+            known.as_ref(),
+            self.max_response_bytes,
+        )
+        .map_err(|e| internal_rpc_err(format!("Failed to serve blocks: {e}")))?;
+        permit.record_bytes(response.encoded_len());
+        Ok(response)
+    }
+
+    async fn sync_protocol_version(&self) -> RpcResult<u64> {
+        Ok(SYNC_PROTOCOL_VERSION)
+    }
 
-        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
-        rmp_serde::encode::write_named(&mut encoder, &blocks)
-            .map_err(|e| internal_rpc_err(format!("Failed to serialize blocks: {e}")))?;
-        let compressed = encoder
-            .finish()
-            .map_err(|e| internal_rpc_err(format!("Failed to compress blocks: {e}")))?;
-        Ok(Bytes::from(compressed))
+    async fn sync_block_format_version(&self) -> RpcResult<u64> {
+        Ok(BLOCK_FORMAT_VERSION)
     }
 
-    async fn sync_latest_block_number(&self) -> RpcResult<Option<u64>> {
+    async fn sync_latest_block_number(&self) -> RpcResult<SyncLatestBlockResponse> {
         trace!(target: "rpc::hl", "Serving hl_syncLatestBlockNumber");
-        let reader = get_sync_db_reader()?;
-        Ok(Some(
-            reader
-                .best_block_number()
-                .map_err(|e| internal_rpc_err(format!("Failed to get latest block: {e}")))?,
-        ))
+        let latest = self
+            .latest_block()
+            .map_err(|e| internal_rpc_err(format!("Failed to get latest block: {e}")))?;
+        Ok(if self.legacy_latest_block_number {
+            SyncLatestBlockResponse::Number(Some(latest.latest))
+        } else {
+            SyncLatestBlockResponse::Status(latest)
+        })
+    }
+
+    async fn subscribe_blocks(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        trace!(target: "rpc::hl", "Serving hl_subscribeBlocks");
+        let (reader, cache, serve_lag) =
+            (self.reader.clone(), self.payload_cache.clone(), self.serve_lag);
+        let mut pushed = served_tip(reader.finished_block_number()?, serve_lag);
+        let sink = pending.accept().await?;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(BLOCK_PUSH_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                tokio::select! {
+                    _ = sink.closed() => return,
+                    _ = interval.tick() => {}
+                }
+                let tip = match reader.finished_block_number() {
+                    Ok(finished) => served_tip(finished, serve_lag),
+                    Err(err) => {
+                        debug!(target: "rpc::hl", %err, "Failed to get synced height");
+                        continue;
+                    }
+                };
+                // Blocks replaced by an unwind are pushed again
+                pushed = pushed.clamp(tip.saturating_sub(MAX_PUSHED_BACKLOG), tip);
+                while pushed < tip {
+                    let height = pushed + 1;
+                    let block = match read_pushed_block(&*reader, cache.as_deref(), height) {
+                        Ok(block) => block,
+                        Err(err) => {
+                            debug!(target: "rpc::hl", height, %err, "Failed to push block");
+                            break;
+                        }
+                    };
+                    let Ok(message) = SubscriptionMessage::new(
+                        sink.method_name(),
+                        sink.subscription_id(),
+                        &block,
+                    ) else {
+                        return;
+                    };
+                    if sink.send(message).await.is_err() {
+                        return;
+                    }
+                    pushed = height;
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Returns the highest block that may be served, `serve_lag` blocks below the synced height.
+fn served_tip(finished: u64, serve_lag: u64) -> u64 {
+    finished.saturating_sub(serve_lag)
+}
+
+/// Reads the block at `height` in the format of `hl_syncGetBlock`, to push it to subscribers.
+fn read_pushed_block(
+    reader: &dyn SyncBlockReader,
+    cache: Option<&SerializedBlockCache>,
+    height: u64,
+) -> eyre::Result<Bytes> {
+    let block = read_encoded_block(reader, cache, height)?;
+    Ok(encode_single_block(&block)?)
+}
+
+/// Where the sync server is served instead of the node's RPC servers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncServerListener {
+    /// A Unix domain socket, for nodes syncing from this one on the same host.
+    Uds(PathBuf),
+    /// A dedicated HTTP and WebSocket listener, see [`serve_over_tcp`].
+    Tcp(SocketAddr),
+}
+
+impl SyncServerListener {
+    /// Serves `sync_server`, with the methods limited by `rate_limiter` replaced as on the RPC
+    /// servers.
+    pub async fn serve(
+        &self,
+        sync_server: HlSyncServer,
+        rate_limiter: &Arc<RpcRateLimiter>,
+    ) -> eyre::Result<ServerHandle> {
+        let module = rate_limiter.limit_module(sync_server.into_rpc().remove_context())?;
+        match self {
+            Self::Uds(path) => serve_over_uds(module, path).await,
+            Self::Tcp(addr) => Ok(serve_over_tcp(module, *addr).await?.1),
+        }
+    }
+}
+
+impl std::fmt::Display for SyncServerListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uds(path) => write!(f, "unix://{}", path.display()),
+            Self::Tcp(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+/// Serves the sync server `methods` over HTTP and WebSocket at `addr` instead of the node's RPC
+/// servers, returning the address it listens on.
+///
+/// Unlike the RPC servers, the listener hands the address of each client to the sync server, so
+/// that the per-client limits are kept per IP rather than per connection.
+pub async fn serve_over_tcp(
+    methods: impl Into<Methods>,
+    addr: SocketAddr,
+) -> eyre::Result<(SocketAddr, ServerHandle)> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|err| eyre::eyre!("failed to serve the sync server at {addr}: {err}"))?;
+    let local_addr = listener.local_addr()?;
+    let methods = methods.into();
+    let (stop_handle, server_handle) = stop_channel();
+    let service_builder = Server::builder().to_service_builder();
+    tokio::spawn(async move {
+        loop {
+            let (socket, remote_addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        debug!(target: "rpc::hl", %err, "Failed to accept a sync client");
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => return,
+            };
+            let (methods, stop, service_builder) =
+                (methods.clone(), stop_handle.clone(), service_builder.clone());
+            let service = tower::service_fn(move |mut request: HttpRequest<Incoming>| {
+                request.extensions_mut().insert(remote_addr);
+                let mut service = service_builder.clone().build(methods.clone(), stop.clone());
+                async move { service.call(request).await }
+            });
+            tokio::spawn(serve_with_graceful_shutdown(
+                socket,
+                service,
+                stop_handle.clone().shutdown(),
+            ));
+        }
+    });
+    Ok((local_addr, server_handle))
+}
+
+/// Serves the sync server `methods` on the Unix domain socket at `path` instead of the node's RPC
+/// servers, for nodes syncing from it on the same host. A socket left at `path` by a previous run
+/// is replaced.
+pub async fn serve_over_uds(
+    methods: impl Into<Methods>,
+    path: &Path,
+) -> eyre::Result<ServerHandle> {
+    let endpoint = path.to_string_lossy().into_owned();
+    reth_ipc::server::Builder::default()
+        .build(endpoint)
+        .start(methods)
+        .await
+        .map_err(|err| eyre::eyre!("failed to serve the sync server at {}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sealed_block;
+    use alloy_consensus::Header;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const BLOCK_SIZE: usize = 1024 * 1024;
+
+    /// Serves blocks with 1 MiB of incompressible extra data and counts the reads.
+    #[derive(Debug, Default)]
+    struct LargeBlockReader {
+        reads: AtomicUsize,
+    }
+
+    impl SyncBlockReader for LargeBlockReader {
+        fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            // xorshift, so that lz4 can't shrink the payload
+            let mut state = number.wrapping_add(0x9e3779b97f4a7c15);
+            let extra_data = (0..BLOCK_SIZE)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as u8
+                })
+                .collect::<Vec<_>>();
+            let header = Header { number, extra_data: extra_data.into(), ..Default::default() };
+            Ok(sealed_block(header, hash(number)))
+        }
+
+        fn best_block_number(&self) -> eyre::Result<u64> {
+            Ok(u64::MAX)
+        }
+
+        fn block_hash(&self, number: u64) -> eyre::Result<Option<B256>> {
+            Ok(Some(hash(number)))
+        }
+    }
+
+    fn hash(number: u64) -> B256 {
+        B256::with_last_byte(number as u8)
+    }
+
+    fn numbers(blocks: &[BlockAndReceipts]) -> Vec<u64> {
+        blocks.iter().map(|b| b.number()).collect()
+    }
+
+    #[test]
+    fn complete_response_round_trips() {
+        let reader = LargeBlockReader::default();
+        let response =
+            encode_blocks(&reader, None, &[1, 2, 3], None, DEFAULT_MAX_RESPONSE_BYTES).unwrap();
+        assert!(matches!(response, SyncBlocksResponse::Complete(_)));
+
+        let (blocks, truncated_at) = response.decode().unwrap();
+        assert_eq!(numbers(&blocks), vec![1, 2, 3]);
+        assert_eq!(truncated_at, None);
+    }
+
+    #[test]
+    fn flipped_byte_fails_checksum() {
+        let reader = LargeBlockReader::default();
+        let response =
+            encode_blocks(&reader, None, &[1, 2, 3], None, DEFAULT_MAX_RESPONSE_BYTES).unwrap();
+        let SyncBlocksResponse::Complete(blocks) = response else { panic!("expected Complete") };
+
+        // Lands in the incompressible extra data, so the msgpack itself still parses
+        let mut corrupted = blocks.to_vec();
+        corrupted[blocks.len() / 2] ^= 0x01;
+        assert!(SyncBlocksResponse::Complete(corrupted.into()).decode().is_err());
+        assert!(SyncBlocksResponse::Complete(blocks).decode().is_ok());
+    }
+
+    #[test]
+    fn large_blocks_are_truncated_within_budget() {
+        let reader = LargeBlockReader::default();
+        let heights = (100..110).collect::<Vec<u64>>();
+        let budget = 3 * BLOCK_SIZE;
+        let response = encode_blocks(&reader, None, &heights, None, budget).unwrap();
+
+        // Blocks past the budget are never read from the database
+        let reads = reader.reads.load(Ordering::SeqCst);
+        assert!(reads < heights.len());
+        let SyncBlocksResponse::Truncated { blocks, truncated_at } = &response else {
+            panic!("expected a truncated response");
+        };
+        assert!(blocks.len() < budget + 2 * BLOCK_SIZE);
+        assert_eq!(*truncated_at, heights[reads]);
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["truncatedAt"], *truncated_at);
+
+        let (blocks, _) = response.decode().unwrap();
+        assert_eq!(blocks.len(), reads);
+        assert_eq!(blocks.last().unwrap().number(), heights[reads - 1]);
+    }
+
+    #[test]
+    fn at_least_one_block_is_served() {
+        let reader = LargeBlockReader::default();
+        let (blocks, truncated_at) =
+            encode_blocks(&reader, None, &[1, 2], None, 0).unwrap().decode().unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(truncated_at, Some(2));
+    }
+
+    #[test]
+    fn known_blocks_full_miss() {
+        let reader = LargeBlockReader::default();
+        // Unknown height and a stale hash: both blocks are sent
+        let known = HashMap::from([(2, B256::repeat_byte(0xff))]);
+        let response =
+            encode_blocks(&reader, None, &[1, 2], Some(&known), DEFAULT_MAX_RESPONSE_BYTES)
+                .unwrap();
+
+        assert!(response.confirmed().is_empty());
+        let (blocks, truncated_at) = response.decode().unwrap();
+        assert_eq!(numbers(&blocks), vec![1, 2]);
+        assert_eq!(truncated_at, None);
+        assert_eq!(reader.reads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn known_blocks_full_hit() {
+        let reader = LargeBlockReader::default();
+        let known = HashMap::from([(1, hash(1)), (2, hash(2))]);
+        let response =
+            encode_blocks(&reader, None, &[1, 2], Some(&known), DEFAULT_MAX_RESPONSE_BYTES)
+                .unwrap();
+
+        assert_eq!(response.confirmed(), &[(1, hash(1)), (2, hash(2))]);
+        assert!(response.decode().unwrap().0.is_empty());
+        assert_eq!(reader.reads.load(Ordering::SeqCst), 0);
+        assert!(response.encoded_len() < 64);
+    }
+
+    #[test]
+    fn known_blocks_partial_hit() {
+        let reader = LargeBlockReader::default();
+        let known = HashMap::from([(1, hash(1)), (3, hash(3))]);
+        let response =
+            encode_blocks(&reader, None, &[1, 2, 3], Some(&known), DEFAULT_MAX_RESPONSE_BYTES)
+                .unwrap();
+
+        assert_eq!(response.confirmed(), &[(1, hash(1)), (3, hash(3))]);
+        assert_eq!(numbers(&response.decode().unwrap().0), vec![2]);
+        assert_eq!(reader.reads.load(Ordering::SeqCst), 1);
+
+        // Round-trips through JSON without being mistaken for a plain truncated response
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(serde_json::from_str::<SyncBlocksResponse>(&json).unwrap(), response);
+    }
+
+    #[test]
+    fn payload_cache_serves_identical_bytes() {
+        let reader = LargeBlockReader::default();
+        let cache = SerializedBlockCache::new(DEFAULT_PAYLOAD_CACHE_SIZE);
+        let heights = [1, 2, 3];
+        let encode = |cache| {
+            encode_blocks(&reader, cache, &heights, None, DEFAULT_MAX_RESPONSE_BYTES).unwrap()
+        };
+
+        let uncached = encode(None);
+        let cold = encode(Some(&cache));
+        assert_eq!(reader.reads.load(Ordering::SeqCst), 6);
+
+        // Every block is served from the cache
+        let warm = encode(Some(&cache));
+        assert_eq!(reader.reads.load(Ordering::SeqCst), 6);
+        assert_eq!(uncached, cold);
+        assert_eq!(cold, warm);
+    }
+
+    /// Checks that cached blocks are served faster than cold ones. Run with
+    /// `cargo test --release bench_payload_cache -- --ignored`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_payload_cache() {
+        let reader = LargeBlockReader::default();
+        let cache = SerializedBlockCache::new(DEFAULT_PAYLOAD_CACHE_SIZE);
+        let heights = (0..100).collect::<Vec<u64>>();
+        let encode = |cache| {
+            let started = std::time::Instant::now();
+            encode_blocks(&reader, cache, &heights, None, usize::MAX).unwrap();
+            started.elapsed()
+        };
+
+        let uncached = encode(None);
+        encode(Some(&cache));
+        let cached = encode(Some(&cache));
+        assert!(cached < uncached, "uncached: {uncached:?}, cached: {cached:?}");
+    }
+
+    #[test]
+    fn readiness_follows_source_lag() {
+        let server = || {
+            HlSyncServer::new(
+                Arc::new(LargeBlockReader::default()),
+                DEFAULT_MAX_RESPONSE_BYTES,
+                0,
+                &SyncServerLimits::default(),
+            )
+        };
+        // `LargeBlockReader` has every block up to u64::MAX
+        assert!(server().latest_block().unwrap().ready);
+
+        let status = SyncSourceStatus::default();
+        let tracked = server().with_source_status(status.clone(), DEFAULT_MAX_READY_LAG);
+        let latest = tracked.latest_block().unwrap();
+        assert_eq!((latest.source_latest, latest.ready), (None, false));
+
+        status.record(u64::MAX);
+        let latest = tracked.latest_block().unwrap();
+        assert_eq!((latest.lag, latest.ready), (Some(0), true));
+        let json = serde_json::to_value(SyncLatestBlockResponse::Status(latest)).unwrap();
+        assert_eq!(json["sourceLatest"], u64::MAX);
+    }
+
+    /// [`LargeBlockReader`] synced up to block 100.
+    #[derive(Debug, Default)]
+    struct SyncedReader(LargeBlockReader);
+
+    impl SyncBlockReader for SyncedReader {
+        fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+            self.0.read_block_and_receipts(number)
+        }
+
+        fn best_block_number(&self) -> eyre::Result<u64> {
+            Ok(100)
+        }
+    }
+
+    #[tokio::test]
+    async fn blocks_within_serve_lag_are_refused() {
+        let server = HlSyncServer::new(
+            Arc::new(SyncedReader::default()),
+            DEFAULT_MAX_RESPONSE_BYTES,
+            0,
+            &SyncServerLimits::default(),
+        )
+        .with_serve_lag(10);
+        let ext = Extensions::new();
+
+        let latest = server.sync_latest_block_number().await.unwrap();
+        assert_eq!(latest.latest(), Some(90));
+        assert!(server.sync_get_block(&ext, 90).await.is_ok());
+        assert!(server.sync_get_blocks(&ext, vec![89, 90], None).await.is_ok());
+
+        let err = server.sync_get_block(&ext, 91).await.unwrap_err();
+        assert!(err.message().contains("above this node's served tip 90"), "{err:?}");
+        assert_eq!(err.code(), HlErrorCode::BlockNotServed.code());
+        let data: BlockNotServedData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!((data.block_number, data.served_tip, data.synced_height), (91, 90, 100));
+        assert!(server.sync_get_blocks(&ext, vec![90, 91], None).await.is_err());
     }
 }
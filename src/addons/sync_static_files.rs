@@ -0,0 +1,328 @@
+use crate::{
+    HlBlock, HlBlockBody, HlHeader, HlPrimitives,
+    addons::sync_server::{ProviderSyncReader, SyncBlockReader},
+    node::{
+        primitives::TransactionSigned,
+        storage::read_block_extras,
+        types::{BlockAndReceipts, HlExtras},
+    },
+};
+use alloy_consensus::BlockBody;
+use alloy_eips::eip4895::Withdrawals;
+use alloy_primitives::B256;
+use reth_chainspec::EthereumHardforks;
+use reth_db::{tables, transaction::DbTx};
+use reth_ethereum_primitives::EthereumReceipt;
+use reth_provider::{
+    BlockBodyIndicesProvider, BlockNumReader, BlockReader, ChainSpecProvider, DBProvider,
+    DatabaseProviderFactory, HeaderProvider, ReceiptProvider, StageCheckpointReader,
+    StaticFileProviderFactory, TransactionsProvider,
+};
+use tracing::trace;
+
+/// Sync server reader that assembles blocks straight from static files.
+///
+/// Headers, transactions and receipts are read by tx number range from the static-file provider,
+/// skipping the in-memory canonical state lookups and the header rehashing of
+/// [`ProviderSyncReader`]. Blocks that are not (yet) in static files are read through the
+/// regular provider.
+#[derive(Debug)]
+pub struct StaticFileSyncReader<P> {
+    provider: P,
+    fallback: ProviderSyncReader<P>,
+}
+
+impl<P: Clone> StaticFileSyncReader<P> {
+    pub fn new(provider: P) -> Self {
+        Self { fallback: ProviderSyncReader::new(provider.clone()), provider }
+    }
+}
+
+impl<P> StaticFileSyncReader<P>
+where
+    P: StaticFileProviderFactory<Primitives = HlPrimitives> +
+        DatabaseProviderFactory<Provider: BlockBodyIndicesProvider> +
+        ChainSpecProvider<ChainSpec: EthereumHardforks>,
+{
+    /// Reads the block at `number` from static files, or `None` if it is not fully there.
+    fn read_static_files(&self, number: u64) -> eyre::Result<Option<BlockAndReceipts>> {
+        let static_files = self.provider.static_file_provider();
+        let Some(header) = static_files.sealed_header(number)? else {
+            return Ok(None);
+        };
+        let db = self.provider.database_provider_ro()?;
+        let Some(indices) = db.block_body_indices(number)? else {
+            return Ok(None);
+        };
+
+        let tx_range = indices.tx_num_range();
+        let transactions = static_files.transactions_by_tx_range(tx_range.clone())?;
+        let receipts = static_files.receipts_by_tx_range(tx_range)?;
+        if transactions.len() != indices.tx_count as usize || receipts.len() != transactions.len()
+        {
+            return Ok(None);
+        }
+
+        let (header, hash) = header.split();
+        // Same rule as the ethereum body reader, so that both paths serialize identically
+        let shanghai = self.provider.chain_spec().is_shanghai_active_at_timestamp(header.timestamp);
+        let withdrawals = if shanghai {
+            Some(
+                db.tx_ref()
+                    .get::<tables::BlockWithdrawals>(number)?
+                    .map(|stored| stored.withdrawals)
+                    .unwrap_or_default(),
+            )
+        } else {
+            None
+        };
+        let extras = read_block_extras(db.tx_ref(), number)?;
+
+        Ok(Some(assemble_block(hash, header, transactions, withdrawals, receipts, extras)))
+    }
+}
+
+/// Builds the served block from its stored parts, the same way [`BlockAndReceipts::from_db`]
+/// does for blocks read through the provider.
+fn assemble_block(
+    hash: B256,
+    header: HlHeader,
+    transactions: Vec<TransactionSigned>,
+    withdrawals: Option<Withdrawals>,
+    receipts: Vec<EthereumReceipt>,
+    extras: HlExtras,
+) -> BlockAndReceipts {
+    let block = HlBlock {
+        header,
+        body: HlBlockBody {
+            inner: BlockBody { transactions, ommers: vec![], withdrawals },
+            sidecars: None,
+            read_precompile_calls: extras.read_precompile_calls,
+            highest_precompile_address: extras.highest_precompile_address,
+        },
+    };
+    BlockAndReceipts::from_db_sealed(hash, block, receipts)
+}
+
+impl<P> SyncBlockReader for StaticFileSyncReader<P>
+where
+    P: StaticFileProviderFactory<Primitives = HlPrimitives> +
+        DatabaseProviderFactory<Provider: BlockBodyIndicesProvider> +
+        ChainSpecProvider<ChainSpec: EthereumHardforks> +
+        BlockReader<Block = HlBlock> +
+        ReceiptProvider<Receipt = EthereumReceipt> +
+        BlockNumReader +
+        StageCheckpointReader +
+        std::fmt::Debug +
+        Send +
+        Sync +
+        'static,
+{
+    fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+        match self.read_static_files(number) {
+            Ok(Some(block)) => return Ok(block),
+            Ok(None) => {}
+            Err(err) => trace!(target: "rpc::hl", number, %err, "Static file read failed"),
+        }
+        self.fallback.read_block_and_receipts(number)
+    }
+
+    fn best_block_number(&self) -> eyre::Result<u64> {
+        self.fallback.best_block_number()
+    }
+
+    fn block_hash(&self, number: u64) -> eyre::Result<Option<B256>> {
+        self.fallback.block_hash(number)
+    }
+
+    fn read_block_by_hash(&self, hash: B256) -> eyre::Result<Option<BlockAndReceipts>> {
+        self.fallback.read_block_by_hash(hash)
+    }
+
+    fn finished_block_number(&self) -> eyre::Result<u64> {
+        self.fallback.finished_block_number()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chainspec::parser::chain_value_parser,
+        node::{HlNode, primitives::header::HlHeaderExtras, storage::tables as hl_tables},
+    };
+    use alloy_consensus::{EthereumTxEnvelope, Header, Signed, TxLegacy, TxType};
+    use alloy_primitives::{Address, Bloom, Bytes, Log, Sealable, Signature, TxKind, U256};
+    use reth::api::NodeTypesWithDBAdapter;
+    use reth_db::{
+        ClientVersion, Database, DatabaseEnv, mdbx::DatabaseArguments,
+        models::StoredBlockBodyIndices, transaction::DbTxMut,
+    };
+    use reth_provider::{
+        ProviderFactory, StaticFileSegment, StaticFileWriter, providers::StaticFileProvider,
+    };
+    use std::{ops::Range, path::Path, sync::Arc, time::Instant};
+
+    type Factory = ProviderFactory<NodeTypesWithDBAdapter<HlNode, Arc<DatabaseEnv>>>;
+
+    /// Blocks mixing system and regular transactions, in their stored form.
+    fn fixture_blocks(numbers: Range<u64>) -> Vec<(HlBlock, Vec<EthereumReceipt>)> {
+        numbers
+            .map(|number| {
+                let system_tx_count = number % 3;
+                let tx_count = system_tx_count + 2;
+                let transactions = (0..tx_count)
+                    .map(|nonce| {
+                        let tx = TxLegacy {
+                            nonce,
+                            gas_limit: 21_000,
+                            to: TxKind::Call(Address::with_last_byte(number as u8)),
+                            value: U256::from(number),
+                            ..Default::default()
+                        };
+                        TransactionSigned::Default(EthereumTxEnvelope::Legacy(
+                            Signed::new_unhashed(tx, Signature::test_signature()),
+                        ))
+                    })
+                    .collect();
+                let receipts = (0..tx_count)
+                    .map(|index| EthereumReceipt {
+                        tx_type: TxType::Legacy,
+                        success: true,
+                        cumulative_gas_used: 21_000 * (index + 1),
+                        logs: vec![Log::new_unchecked(
+                            Address::with_last_byte(index as u8),
+                            vec![B256::with_last_byte(number as u8)],
+                            Bytes::from(number.to_be_bytes().to_vec()),
+                        )],
+                    })
+                    .collect();
+                let header = HlHeader {
+                    inner: Header { number, timestamp: number, ..Default::default() },
+                    extras: HlHeaderExtras {
+                        logs_bloom_with_system_txs: Bloom::ZERO,
+                        system_tx_count,
+                    },
+                };
+                // Shanghai is active from genesis, so stored blocks read back with no withdrawals
+                let withdrawals = Some(Default::default());
+                let block = HlBlock {
+                    header,
+                    body: HlBlockBody {
+                        inner: BlockBody { transactions, ommers: vec![], withdrawals },
+                        sidecars: None,
+                        read_precompile_calls: None,
+                        highest_precompile_address: Some(Address::with_last_byte(0x10)),
+                    },
+                };
+                (block, receipts)
+            })
+            .collect()
+    }
+
+    /// A datadir at `dir` holding `blocks` from genesis, with their headers, transactions and
+    /// receipts in static files as the node stores them.
+    fn fixture_factory(dir: &Path, blocks: &[(HlBlock, Vec<EthereumReceipt>)]) -> Factory {
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::init_db(dir.join("db"), args).unwrap();
+        db.create_tables_for::<hl_tables::Tables>().unwrap();
+        let static_files =
+            StaticFileProvider::<HlPrimitives>::read_write(dir.join("static_files")).unwrap();
+
+        let mut headers = static_files.get_writer(0, StaticFileSegment::Headers).unwrap();
+        for (block, _) in blocks {
+            let hash = block.header.hash_slow();
+            headers.append_header(&block.header, U256::ZERO, &hash).unwrap();
+        }
+        headers.commit().unwrap();
+        drop(headers);
+
+        let tx = db.tx_mut().unwrap();
+        let mut transactions = static_files.get_writer(0, StaticFileSegment::Transactions).unwrap();
+        let mut first_tx_num = 0;
+        for (block, _) in blocks {
+            let number = block.header.inner.number;
+            transactions.increment_block(number).unwrap();
+            for (tx_num, transaction) in (first_tx_num..).zip(&block.body.inner.transactions) {
+                transactions.append_transaction(tx_num, transaction).unwrap();
+            }
+            let tx_count = block.body.inner.transactions.len() as u64;
+            tx.put::<tables::BlockBodyIndices>(
+                number,
+                StoredBlockBodyIndices { first_tx_num, tx_count },
+            )
+            .unwrap();
+            let extras = HlExtras {
+                read_precompile_calls: block.body.read_precompile_calls.clone(),
+                highest_precompile_address: block.body.highest_precompile_address,
+            };
+            let extras = Bytes::from(rmp_serde::to_vec(&extras).unwrap());
+            tx.put::<hl_tables::BlockReadPrecompileCalls>(number, extras).unwrap();
+            first_tx_num += tx_count;
+        }
+        transactions.commit().unwrap();
+        drop(transactions);
+        tx.commit().unwrap();
+
+        let mut receipts = static_files.get_writer(0, StaticFileSegment::Receipts).unwrap();
+        let mut tx_num = 0;
+        for (block, block_receipts) in blocks {
+            receipts.increment_block(block.header.inner.number).unwrap();
+            for receipt in block_receipts {
+                receipts.append_receipt(tx_num, receipt).unwrap();
+                tx_num += 1;
+            }
+        }
+        receipts.commit().unwrap();
+        drop(receipts);
+
+        let chain_spec = chain_value_parser("mainnet").unwrap();
+        ProviderFactory::new(Arc::new(db), chain_spec, static_files)
+    }
+
+    fn encode(block: &BlockAndReceipts) -> Vec<u8> {
+        rmp_serde::encode::to_vec_named(block).unwrap()
+    }
+
+    #[test]
+    fn static_file_reads_match_provider_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let blocks = fixture_blocks(0..10);
+        let reader = StaticFileSyncReader::new(fixture_factory(dir.path(), &blocks));
+
+        for (block, receipts) in blocks {
+            let number = block.header.inner.number;
+            let expected = BlockAndReceipts::from_db(block, receipts);
+            let from_static_files = reader.read_static_files(number).unwrap().unwrap();
+            let from_provider = reader.fallback.read_block_and_receipts(number).unwrap();
+            assert_eq!(encode(&from_static_files), encode(&expected), "block {number}");
+            assert_eq!(encode(&from_provider), encode(&expected), "block {number}");
+        }
+
+        // Past the static files, blocks are left to the provider
+        assert!(reader.read_static_files(10).unwrap().is_none());
+    }
+
+    /// Checks that reading blocks from static files is faster than reading them through the
+    /// provider, both uncached. Run with
+    /// `cargo test --release bench_static_file_reads -- --ignored`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_static_file_reads() {
+        const BLOCKS: u64 = 10_000;
+        let dir = tempfile::tempdir().unwrap();
+        let factory = fixture_factory(dir.path(), &fixture_blocks(0..BLOCKS));
+        let reader = StaticFileSyncReader::new(factory);
+        let time = |read: &dyn Fn(u64) -> BlockAndReceipts| {
+            let started = Instant::now();
+            for number in 0..BLOCKS {
+                std::hint::black_box(read(number));
+            }
+            started.elapsed()
+        };
+
+        let static_files = time(&|number| reader.read_static_files(number).unwrap().unwrap());
+        let provider = time(&|number| reader.fallback.read_block_and_receipts(number).unwrap());
+        assert!(static_files < provider, "static files: {static_files:?}, provider: {provider:?}");
+    }
+}
@@ -0,0 +1,194 @@
+//! `hl_traceSystemBlockExecution`: the traces and state changes of a block's system
+//! transactions alone.
+//!
+//! System transactions (HyperCore transfers into HyperEVM) are at the beginning of a block, so
+//! replaying the first `system_tx_count` transactions on top of the parent state is enough to
+//! audit what HyperCore did to a block, without tracing the user transactions after them.
+//!
+//! Tracing is done by reth's [`DebugApi`] on top of [`HlEthApi`], whose [`Trace::inspect`]
+//! installs the block's read precompile calls, on a copy of the block without its user
+//! transactions, traced on the state of its parent. The state changes come from the
+//! re-execution behind `hl_getBlockStateDiff`. As it replays blocks like `debug_traceBlock`, the
+//! method is only served where the `debug` namespace is enabled.
+//!
+//! [`HlEthApi`]: crate::node::rpc::HlEthApi
+//! [`Trace::inspect`]: reth_rpc_eth_api::helpers::Trace::inspect
+
+use crate::{
+    HlBlock, HlPrimitives,
+    addons::{
+        state_diff::{AccountDiff, execute_state_diff, replay_failed},
+        utils::EthWrapper,
+    },
+};
+use alloy_consensus::{BlockHeader, EMPTY_ROOT_HASH, TxReceipt};
+use alloy_eips::BlockId;
+use alloy_primitives::{Address, B256, U64};
+use alloy_rpc_types_trace::geth::{GethDebugTracingOptions, TraceResult};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use jsonrpsee_types::{ErrorObject, error::INTERNAL_ERROR_CODE};
+use reth_primitives::logs_bloom;
+use reth_primitives_traits::{BlockBody, RecoveredBlock};
+use reth_provider::ReceiptProvider;
+use reth_rpc::DebugApi;
+use reth_rpc_eth_api::{EthApiTypes, RpcNodeCore, helpers::TraceExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::trace;
+
+/// The replay of a block's system transactions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemBlockTrace {
+    pub block_hash: B256,
+    pub block_number: U64,
+    /// Traces of the system transactions, in block order.
+    pub traces: Vec<TraceResult>,
+    /// State changes made by the system transactions.
+    pub state_diff: BTreeMap<Address, AccountDiff>,
+}
+
+/// `block` without its user transactions, under a header matching what is left of it: no user
+/// transactions, receipts or gas used, and the logs of the system transactions in `receipts`.
+fn system_block<R: TxReceipt<Log = alloy_primitives::Log>>(
+    block: &RecoveredBlock<HlBlock>,
+    receipts: &[R],
+) -> HlBlock {
+    let mut system_block = block.clone_block();
+    let system_tx_count = block.header().extras.system_tx_count as usize;
+    system_block.body.inner.transactions.truncate(system_tx_count);
+
+    let bloom = logs_bloom(receipts.iter().take(system_tx_count).flat_map(|r| r.logs()));
+    let header = &mut system_block.header;
+    header.inner.transactions_root = system_block.body.calculate_tx_root();
+    header.inner.receipts_root = EMPTY_ROOT_HASH;
+    header.inner.gas_used = 0;
+    header.inner.logs_bloom = bloom;
+    header.extras.logs_bloom_with_system_txs = bloom;
+    system_block
+}
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlSystemTraceApi {
+    /// Replays the system transactions of a block, returning their traces and state changes.
+    #[method(name = "traceSystemBlockExecution")]
+    async fn trace_system_block_execution(
+        &self,
+        block: BlockId,
+        opts: Option<GethDebugTracingOptions>,
+    ) -> RpcResult<Option<SystemBlockTrace>>;
+}
+
+pub struct HlSystemTraceExt<Eth: EthWrapper> {
+    debug: DebugApi<Eth>,
+    eth_api: Eth,
+}
+
+impl<Eth: EthWrapper> HlSystemTraceExt<Eth> {
+    pub fn new(debug: DebugApi<Eth>, eth_api: Eth) -> Self {
+        Self { debug, eth_api }
+    }
+}
+
+#[async_trait]
+impl<Eth> HlSystemTraceApiServer for HlSystemTraceExt<Eth>
+where
+    Eth: EthWrapper + TraceExt + RpcNodeCore<Primitives = HlPrimitives>,
+    ErrorObject<'static>: From<<Eth as EthApiTypes>::Error>,
+{
+    async fn trace_system_block_execution(
+        &self,
+        block: BlockId,
+        opts: Option<GethDebugTracingOptions>,
+    ) -> RpcResult<Option<SystemBlockTrace>> {
+        trace!(target: "rpc::hl", ?block, "Serving hl_traceSystemBlockExecution");
+        let internal = |err: String| ErrorObject::owned(INTERNAL_ERROR_CODE, err, None::<()>);
+
+        let Some(block) = self.eth_api.recovered_block(block).await? else {
+            return Ok(None);
+        };
+        let (block_hash, block_number) = (block.hash(), block.number());
+        let provider = self.eth_api.provider().clone();
+        let evm_config = self.eth_api.evm_config().clone();
+        let (rlp_block, diff) = tokio::task::spawn_blocking(move || {
+            let receipts = provider.receipts_by_block(block_hash.into())?.unwrap_or_default();
+            let rlp_block = alloy_rlp::encode(system_block(&block, &receipts)).into();
+            let diff = execute_state_diff(&provider, &evm_config, &block, true)?;
+            eyre::Ok((rlp_block, diff))
+        })
+        .await
+        .map_err(|err| internal(err.to_string()))?
+        .map_err(|err| replay_failed(block_hash, err))?;
+        let traces = self.debug.debug_trace_raw_block(rlp_block, opts.unwrap_or_default()).await?;
+
+        Ok(Some(SystemBlockTrace {
+            block_hash,
+            block_number: U64::from(block_number),
+            traces,
+            state_diff: diff.system,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chainspec::parser::chain_value_parser,
+        node::{evm::config::HlEvmConfig, primitives::TransactionSigned},
+    };
+    use alloy_consensus::{Signed, TxLegacy};
+    use alloy_primitives::{Signature, TxKind, U256, address};
+    use reth_primitives::TransactionSigned as RethTxSigned;
+    use reth_provider::test_utils::{ExtendedAccount, MockEthProvider};
+
+    const SYSTEM: Address = address!("0x2222222222222222222222222222222222222222");
+    const RECIPIENT: Address = address!("0x00000000000000000000000000000000000000aa");
+
+    fn transfer(gas_price: u128, nonce: u64, value: u64) -> TransactionSigned {
+        let tx = TxLegacy {
+            gas_price,
+            nonce,
+            gas_limit: 21_000,
+            to: TxKind::Call(RECIPIENT),
+            value: U256::from(value),
+            ..Default::default()
+        };
+        let signature = Signature::new(U256::from(1), U256::from(1), true);
+        TransactionSigned::Default(RethTxSigned::Legacy(Signed::new_unhashed(tx, signature)))
+    }
+
+    /// A block with two system transactions crediting the recipient 100 and 150 wei from
+    /// HyperCore, followed by a user transaction paying it another 1000 wei. The traces of the
+    /// replay are checked end to end, in `traces_system_txs_alone_where_debug_is_enabled`.
+    #[test]
+    fn system_block_and_state_diff_leave_out_user_transactions() {
+        let provider = MockEthProvider::<HlPrimitives>::default();
+        provider.add_account(SYSTEM, ExtendedAccount::new(0, U256::from(1_000)));
+        let user = Address::repeat_byte(0x11);
+        provider.add_account(user, ExtendedAccount::new(0, U256::from(1_000_000_000)));
+
+        let mut block = HlBlock::default();
+        block.header.inner.number = 1;
+        block.header.inner.gas_limit = 30_000_000;
+        block.header.inner.base_fee_per_gas = Some(0);
+        block.header.extras.system_tx_count = 2;
+        block.body.inner.transactions =
+            vec![transfer(0, 0, 100), transfer(0, 1, 150), transfer(1, 0, 1000)];
+        let block = RecoveredBlock::new_unhashed(block, vec![SYSTEM, SYSTEM, user]);
+
+        let system_block = system_block(&block, &[] as &[reth_primitives::Receipt]);
+        assert_eq!(system_block.body.inner.transactions.len(), 2);
+        // System transactions are left out of the roots and gas of the header
+        assert_eq!(system_block.header.inner.transactions_root, EMPTY_ROOT_HASH);
+        assert_eq!(system_block.header.inner.gas_used, 0);
+
+        let evm_config = HlEvmConfig::new(chain_value_parser("testnet").unwrap());
+        let diff = execute_state_diff(&provider, &evm_config, &block, true).unwrap();
+        assert_eq!(diff.system[&RECIPIENT].balance.unwrap().to, U256::from(250));
+        assert_eq!(diff.system[&SYSTEM].balance.unwrap().to, U256::from(750));
+        assert!(diff.user.is_empty());
+    }
+}
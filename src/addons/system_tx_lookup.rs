@@ -0,0 +1,273 @@
+//! Lookup of system transactions by hash.
+//!
+//! System transactions are indexed by hash in the dedicated `SystemTxHashNumbers` table at
+//! import. `hl_getSystemTransactionByHash` serves them from that index, and
+//! `eth_getTransactionByHash` handles them explicitly: they are returned where system
+//! transactions are exposed (`--expose-system-txs`), and `null` where they are hidden everywhere
+//! else too.
+//!
+//! The `hl_` methods serve system transactions either way, together with the HyperCore
+//! action they carry, see [`SystemTxKind`].
+
+use alloy_consensus::{BlockHeader, transaction::TxHashRef};
+use alloy_eips::BlockId;
+use alloy_json_rpc::RpcObject;
+use alloy_primitives::{Address, B256};
+use alloy_rpc_types::TransactionInfo;
+use futures::StreamExt;
+use jsonrpsee::{PendingSubscriptionSink, proc_macros::rpc};
+use jsonrpsee_core::{RpcResult, SubscriptionResult, async_trait};
+use jsonrpsee_types::ErrorObject;
+use reth::tasks::TaskSpawner;
+use reth_primitives_traits::{RecoveredBlock, SignedTransaction};
+use reth_provider::{CanonStateSubscriptions, DBProvider, DatabaseProviderFactory};
+use reth_rpc_eth_api::{EthApiServer, EthApiTypes, RpcNodeCore, RpcTransaction};
+use reth_rpc_eth_types::EthApiError;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::trace;
+
+use crate::{
+    HlBlock,
+    addons::utils::{EthWrapper, pipe_from_stream},
+    node::{
+        primitives::TransactionSigned,
+        storage::{SystemTxLocation, read_system_tx_location},
+        types::SystemTxKind,
+    },
+};
+
+/// A system transaction with the HyperCore action it carries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlSystemTransaction<T> {
+    #[serde(flatten)]
+    pub transaction: T,
+    pub kind: SystemTxKind,
+}
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlSystemTxApi<T: RpcObject> {
+    /// Returns the system transaction with the given hash, in either mode.
+    #[method(name = "getSystemTransactionByHash")]
+    async fn system_transaction_by_hash(&self, hash: B256) -> RpcResult<Option<T>>;
+
+    /// Returns the system transactions of a block with their decoded actions.
+    #[method(name = "getSystemTransactionsByBlock")]
+    async fn system_transactions_by_block(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<Vec<HlSystemTransaction<T>>>>;
+
+    /// Streams the system transactions of new canonical blocks with their decoded actions.
+    #[subscription(
+        name = "subscribeSystemTransactions" => "systemTransaction",
+        unsubscribe = "unsubscribeSystemTransactions",
+        item = HlSystemTransaction<T>
+    )]
+    async fn subscribe_system_transactions(&self) -> SubscriptionResult;
+}
+
+#[rpc(server, namespace = "eth")]
+#[async_trait]
+pub trait EthTransactionByHashApi<T: RpcObject> {
+    /// Returns the transaction with the given hash, or `null` for a system transaction unless
+    /// system transactions are exposed.
+    #[method(name = "getTransactionByHash")]
+    async fn transaction_by_hash(&self, hash: B256) -> RpcResult<Option<T>>;
+}
+
+/// How `eth_getTransactionByHash` resolves a hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionByHashRoute {
+    /// A hidden system transaction: `null`.
+    Hidden,
+    /// An exposed system transaction, served from the index.
+    SystemTx(SystemTxLocation),
+    /// Not a system transaction: served by the regular eth API.
+    Eth,
+}
+
+impl TransactionByHashRoute {
+    pub fn new(expose_system_txs: bool, location: Option<SystemTxLocation>) -> Self {
+        match location {
+            Some(_) if !expose_system_txs => Self::Hidden,
+            Some(location) => Self::SystemTx(location),
+            None => Self::Eth,
+        }
+    }
+}
+
+/// Converts the transaction at `index` of `block` into its RPC representation.
+fn fill_transaction<Eth: EthWrapper>(
+    eth_api: &Eth,
+    block: &RecoveredBlock<HlBlock>,
+    index: usize,
+    signer: Address,
+    tx: &TransactionSigned,
+) -> Option<RpcTransaction<Eth::NetworkTypes>> {
+    let tx_info = TransactionInfo {
+        hash: Some(*tx.tx_hash()),
+        block_hash: Some(block.hash()),
+        block_number: Some(block.number()),
+        base_fee: block.base_fee_per_gas(),
+        index: Some(index as u64),
+    };
+    eth_api.tx_resp_builder().fill(tx.clone().with_signer(signer), tx_info).ok()
+}
+
+/// Returns the system transactions of `block` with their decoded actions.
+fn system_transactions<Eth: EthWrapper>(
+    eth_api: &Eth,
+    block: &RecoveredBlock<HlBlock>,
+) -> Vec<HlSystemTransaction<RpcTransaction<Eth::NetworkTypes>>> {
+    block
+        .transactions_with_sender()
+        .enumerate()
+        .filter_map(|(index, (signer, tx))| {
+            let kind = SystemTxKind::from_transaction(tx)?;
+            let transaction = fill_transaction(eth_api, block, index, *signer, tx)?;
+            Some(HlSystemTransaction { transaction, kind })
+        })
+        .collect()
+}
+
+pub struct HlSystemTxLookupExt<Eth: EthWrapper> {
+    eth_api: Arc<Eth>,
+    expose_system_txs: bool,
+    subscription_task_spawner: Box<dyn TaskSpawner + 'static>,
+}
+
+impl<Eth: EthWrapper> HlSystemTxLookupExt<Eth>
+where
+    Eth::Provider: DatabaseProviderFactory,
+    ErrorObject<'static>: From<<Eth as EthApiTypes>::Error>,
+{
+    pub fn new(
+        eth_api: Arc<Eth>,
+        expose_system_txs: bool,
+        subscription_task_spawner: Box<dyn TaskSpawner + 'static>,
+    ) -> Self {
+        Self { eth_api, expose_system_txs, subscription_task_spawner }
+    }
+
+    fn location(&self, hash: B256) -> RpcResult<Option<SystemTxLocation>> {
+        let provider = self.eth_api.provider().database_provider_ro().map_err(EthApiError::from)?;
+        Ok(read_system_tx_location(provider.tx_ref(), hash).map_err(EthApiError::from)?)
+    }
+
+    async fn system_transaction(
+        &self,
+        hash: B256,
+        location: SystemTxLocation,
+    ) -> RpcResult<Option<RpcTransaction<Eth::NetworkTypes>>> {
+        let Some(block) = self.eth_api.recovered_block(location.block_number.into()).await? else {
+            return Ok(None);
+        };
+        let Some((signer, tx)) = block.transactions_with_sender().nth(location.index as usize)
+        else {
+            return Ok(None);
+        };
+        // Entries of unwound blocks stay in the index until the transaction is imported again
+        if *tx.tx_hash() != hash || !tx.is_system_transaction() {
+            return Ok(None);
+        }
+        Ok(fill_transaction(&*self.eth_api, &block, location.index as usize, *signer, tx))
+    }
+}
+
+#[async_trait]
+impl<Eth: EthWrapper> HlSystemTxApiServer<RpcTransaction<Eth::NetworkTypes>>
+    for HlSystemTxLookupExt<Eth>
+where
+    Eth::Provider: DatabaseProviderFactory,
+    ErrorObject<'static>: From<<Eth as EthApiTypes>::Error>,
+{
+    async fn system_transaction_by_hash(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<RpcTransaction<Eth::NetworkTypes>>> {
+        trace!(target: "rpc::hl", ?hash, "Serving hl_getSystemTransactionByHash");
+        match self.location(hash)? {
+            Some(location) => self.system_transaction(hash, location).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn system_transactions_by_block(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<Vec<HlSystemTransaction<RpcTransaction<Eth::NetworkTypes>>>>> {
+        trace!(target: "rpc::hl", ?block_id, "Serving hl_getSystemTransactionsByBlock");
+        let block = self.eth_api.recovered_block(block_id).await?;
+        Ok(block.map(|block| system_transactions(&*self.eth_api, &block)))
+    }
+
+    async fn subscribe_system_transactions(
+        &self,
+        pending: PendingSubscriptionSink,
+    ) -> SubscriptionResult {
+        let sink = pending.accept().await?;
+        let eth_api = self.eth_api.clone();
+        self.subscription_task_spawner.spawn(Box::pin(async move {
+            let notifications = eth_api.provider().canonical_state_stream();
+            let stream = notifications.flat_map(move |new_chain| {
+                let txs = new_chain
+                    .committed()
+                    .blocks_iter()
+                    .flat_map(|block| system_transactions(&*eth_api, block))
+                    .collect::<Vec<_>>();
+                futures::stream::iter(txs)
+            });
+            let _ = pipe_from_stream(sink, stream).await;
+        }));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<Eth: EthWrapper> EthTransactionByHashApiServer<RpcTransaction<Eth::NetworkTypes>>
+    for HlSystemTxLookupExt<Eth>
+where
+    Eth::Provider: DatabaseProviderFactory,
+    ErrorObject<'static>: From<<Eth as EthApiTypes>::Error>,
+{
+    async fn transaction_by_hash(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<RpcTransaction<Eth::NetworkTypes>>> {
+        trace!(target: "rpc::eth", ?hash, "Serving eth_getTransactionByHash");
+        match TransactionByHashRoute::new(self.expose_system_txs, self.location(hash)?) {
+            TransactionByHashRoute::Hidden => Ok(None),
+            TransactionByHashRoute::SystemTx(location) => {
+                self.system_transaction(hash, location).await
+            }
+            TransactionByHashRoute::Eth => {
+                EthApiServer::transaction_by_hash(&*self.eth_api, hash).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_txs_are_served_only_when_exposed() {
+        let location = SystemTxLocation { block_number: 7, index: 1 };
+        assert_eq!(
+            TransactionByHashRoute::new(true, Some(location)),
+            TransactionByHashRoute::SystemTx(location)
+        );
+        assert_eq!(
+            TransactionByHashRoute::new(false, Some(location)),
+            TransactionByHashRoute::Hidden
+        );
+
+        // User transactions are not indexed and go to the regular eth API either way
+        assert_eq!(TransactionByHashRoute::new(false, None), TransactionByHashRoute::Eth);
+        assert_eq!(TransactionByHashRoute::new(true, None), TransactionByHashRoute::Eth);
+    }
+}
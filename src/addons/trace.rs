@@ -0,0 +1,244 @@
+//! Overrides for the parity-style `trace_` namespace.
+//!
+//! Tracing itself is done by reth's [`TraceApi`] on top of [`HlEthApi`], whose
+//! [`Trace::inspect`] installs the block's read precompile calls via `apply_precompiles`, so
+//! precompile-touching transactions replay against the recorded results.
+//!
+//! This module only post-processes the output: system transactions are always at the beginning
+//! of the block, so traces with a `transaction_position` below the block's system tx count are
+//! either dropped or flagged with `systemTx: true`, per `--expose-system-txs`.
+//!
+//! [`HlEthApi`]: crate::node::rpc::HlEthApi
+//! [`Trace::inspect`]: reth_rpc_eth_api::helpers::Trace::inspect
+
+use alloy_eips::BlockId;
+use alloy_primitives::B256;
+use alloy_rpc_types_trace::{filter::TraceFilter, parity::LocalizedTransactionTrace};
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use jsonrpsee_types::ErrorObject;
+use reth::rpc::api::TraceApiServer;
+use reth_rpc::TraceApi;
+use reth_rpc_eth_api::EthApiTypes;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::Arc,
+};
+use tracing::trace;
+
+use crate::addons::{hl_node_compliance::system_tx_count_for_block, utils::EthWrapper};
+
+/// A parity-style transaction trace, flagged when it belongs to a system transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlLocalizedTransactionTrace {
+    #[serde(flatten)]
+    pub trace: LocalizedTransactionTrace,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub system_tx: bool,
+}
+
+#[rpc(server, namespace = "trace")]
+#[async_trait]
+pub trait HlTraceApi {
+    /// Returns traces created at given block.
+    #[method(name = "block")]
+    async fn trace_block(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<Vec<HlLocalizedTransactionTrace>>>;
+
+    /// Returns all traces of given transaction.
+    #[method(name = "transaction")]
+    async fn trace_transaction(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<Vec<HlLocalizedTransactionTrace>>>;
+
+    /// Returns traces matching given filter.
+    #[method(name = "filter")]
+    async fn trace_filter(&self, filter: TraceFilter)
+    -> RpcResult<Vec<HlLocalizedTransactionTrace>>;
+}
+
+pub struct HlTraceExt<Eth: EthWrapper> {
+    trace: Arc<TraceApi<Eth>>,
+    eth_api: Arc<Eth>,
+    expose_system_txs: bool,
+}
+
+impl<Eth: EthWrapper> HlTraceExt<Eth> {
+    pub fn new(trace: Arc<TraceApi<Eth>>, eth_api: Arc<Eth>, expose_system_txs: bool) -> Self {
+        Self { trace, eth_api, expose_system_txs }
+    }
+
+    fn adjust(
+        &self,
+        traces: Vec<LocalizedTransactionTrace>,
+    ) -> Result<Vec<HlLocalizedTransactionTrace>, Eth::Error> {
+        let mut system_tx_counts = HashMap::new();
+        for block_number in traces.iter().filter_map(|trace| trace.block_number) {
+            if let Entry::Vacant(entry) = system_tx_counts.entry(block_number) {
+                let count = system_tx_count_for_block(&*self.eth_api, block_number.into())?;
+                entry.insert(count as u64);
+            }
+        }
+        Ok(adjust_traces(traces, self.expose_system_txs, |block_number| {
+            system_tx_counts[&block_number]
+        }))
+    }
+}
+
+/// Flags or drops system transaction traces.
+///
+/// Unless system transactions are exposed, their traces are dropped and the
+/// `transaction_position` of the remaining traces is shifted so that it matches the user-only
+/// transaction index.
+fn adjust_traces(
+    traces: Vec<LocalizedTransactionTrace>,
+    expose_system_txs: bool,
+    mut system_tx_count: impl FnMut(u64) -> u64,
+) -> Vec<HlLocalizedTransactionTrace> {
+    traces
+        .into_iter()
+        .filter_map(|mut trace| {
+            let (Some(block_number), Some(position)) =
+                (trace.block_number, trace.transaction_position)
+            else {
+                // Block rewards and other traces that aren't tied to a transaction
+                return Some(HlLocalizedTransactionTrace { trace, system_tx: false });
+            };
+            let sys_tx_count = system_tx_count(block_number);
+            let system_tx = position < sys_tx_count;
+            if !expose_system_txs {
+                if system_tx {
+                    return None;
+                }
+                trace.transaction_position = Some(position - sys_tx_count);
+            }
+            Some(HlLocalizedTransactionTrace { trace, system_tx })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl<Eth: EthWrapper> HlTraceApiServer for HlTraceExt<Eth>
+where
+    Eth: EthApiTypes + 'static,
+    TraceApi<Eth>: TraceApiServer,
+    ErrorObject<'static>: From<Eth::Error>,
+{
+    async fn trace_block(
+        &self,
+        block_id: BlockId,
+    ) -> RpcResult<Option<Vec<HlLocalizedTransactionTrace>>> {
+        trace!(target: "rpc::trace", ?block_id, "Serving trace_block");
+        let traces = TraceApiServer::trace_block(&*self.trace, block_id).await?;
+        Ok(traces.map(|traces| self.adjust(traces)).transpose()?)
+    }
+
+    async fn trace_transaction(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<Vec<HlLocalizedTransactionTrace>>> {
+        trace!(target: "rpc::trace", ?hash, "Serving trace_transaction");
+        let traces = TraceApiServer::trace_transaction(&*self.trace, hash).await?;
+        let traces = traces.map(|traces| self.adjust(traces)).transpose()?;
+        Ok(traces.filter(|traces| !traces.is_empty()))
+    }
+
+    async fn trace_filter(
+        &self,
+        filter: TraceFilter,
+    ) -> RpcResult<Vec<HlLocalizedTransactionTrace>> {
+        trace!(target: "rpc::trace", ?filter, "Serving trace_filter");
+        // Pagination has to be applied after system transactions are dropped, otherwise `after`
+        // and `count` would refer to traces the caller never sees.
+        let (after, count) = (filter.after, filter.count);
+        let filter = TraceFilter { after: None, count: None, ..filter };
+        let traces = TraceApiServer::trace_filter(&*self.trace, filter).await?;
+        let traces = self
+            .adjust(traces)?
+            .into_iter()
+            .skip(after.unwrap_or_default() as usize)
+            .take(count.map_or(usize::MAX, |count| count as usize))
+            .collect();
+        Ok(traces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{Address, Bytes, address};
+    use alloy_rpc_types_trace::parity::{
+        Action, CallAction, CallOutput, CallType, TraceOutput, TransactionTrace,
+    };
+
+    /// A call into the `0x...0801` read precompile, as recorded for HL blocks.
+    const PRECOMPILE: Address = address!("0x0000000000000000000000000000000000000801");
+
+    fn call_trace(block_number: u64, position: u64, to: Address) -> LocalizedTransactionTrace {
+        LocalizedTransactionTrace {
+            trace: TransactionTrace {
+                action: Action::Call(CallAction {
+                    from: Address::repeat_byte(0xaa),
+                    call_type: CallType::StaticCall,
+                    gas: 30_000,
+                    input: Bytes::from_static(&[0x01, 0x02]),
+                    to,
+                    value: Default::default(),
+                }),
+                error: None,
+                result: Some(TraceOutput::Call(CallOutput {
+                    gas_used: 2_100,
+                    output: Bytes::from_static(&[0xde, 0xad]),
+                })),
+                subtraces: 0,
+                trace_address: vec![],
+            },
+            transaction_hash: Some(B256::with_last_byte(position as u8)),
+            transaction_position: Some(position),
+            block_number: Some(block_number),
+            block_hash: Some(B256::with_last_byte(block_number as u8)),
+        }
+    }
+
+    /// Block 10 has one system tx (position 0) and a user tx calling the read precompile.
+    fn fixture() -> Vec<LocalizedTransactionTrace> {
+        vec![
+            call_trace(10, 0, Address::repeat_byte(0x22)),
+            call_trace(10, 1, Address::repeat_byte(0x33)),
+            call_trace(10, 1, PRECOMPILE),
+        ]
+    }
+
+    #[test]
+    fn system_tx_traces_are_flagged() {
+        let traces = adjust_traces(fixture(), true, |_| 1);
+        assert_eq!(traces.len(), 3);
+        assert!(traces[0].system_tx);
+        assert!(!traces[1].system_tx);
+        assert_eq!(traces[2].trace.trace.result, fixture()[2].trace.result);
+        assert_eq!(traces[2].trace.transaction_position, Some(1));
+
+        let json = serde_json::to_value(&traces[0]).unwrap();
+        assert_eq!(json["systemTx"], true);
+        assert_eq!(json["type"], "call");
+        let json = serde_json::to_value(&traces[1]).unwrap();
+        assert!(json.get("systemTx").is_none());
+    }
+
+    #[test]
+    fn system_tx_traces_are_dropped_when_hidden() {
+        let traces = adjust_traces(fixture(), false, |_| 1);
+        assert_eq!(traces.len(), 2);
+        assert!(traces.iter().all(|trace| !trace.system_tx));
+        assert!(traces.iter().all(|trace| trace.trace.transaction_position == Some(0)));
+        assert!(matches!(
+            &traces[1].trace.trace.action,
+            Action::Call(CallAction { to, .. }) if *to == PRECOMPILE
+        ));
+    }
+}
@@ -1,64 +1,266 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
+use alloy_consensus::{Transaction, transaction::TxHashRef};
+use alloy_eips::Decodable2718;
 use alloy_json_rpc::RpcObject;
 use alloy_network::Ethereum;
-use alloy_primitives::{B256, Bytes};
+use alloy_primitives::{Address, B256, Bytes};
 use alloy_rpc_types::TransactionRequest;
+use futures::future::BoxFuture;
 use jsonrpsee::{
-    http_client::{HttpClient, HttpClientBuilder},
+    http_client::HttpClient,
     proc_macros::rpc,
-    types::{ErrorObject, error::INTERNAL_ERROR_CODE},
+    types::{ErrorObject, error::INVALID_PARAMS_CODE},
 };
-use jsonrpsee_core::{ClientError, RpcResult, async_trait, client::ClientT};
+use jsonrpsee_core::{RpcResult, async_trait, client::ClientT};
 use reth::rpc::{result::internal_rpc_err, server_types::eth::EthApiError};
+use reth_primitives_traits::SignerRecoverable;
 use reth_rpc_eth_api::RpcReceipt;
+use tracing::warn;
+
+use crate::{
+    addons::{
+        call_forwarder::upstream_error,
+        tx_policy::{ForwardAll, TxForwardDecision, TxForwardPolicy},
+        tx_tracker::ForwardedTxTracker,
+    },
+    chainspec::chain_name,
+    node::primitives::TransactionSigned,
+};
+
+/// Error code of transactions refused by the [`TxForwardPolicy`], as in EIP-1474.
+pub const TRANSACTION_REJECTED_CODE: i32 = -32003;
+
+/// A pool that transactions the [`TxForwardPolicy`] keeps local are submitted to.
+pub trait LocalTxPool: Send + Sync + 'static {
+    fn send_raw_transaction(&self, tx: Bytes) -> BoxFuture<'_, RpcResult<B256>>;
+
+    fn transaction_receipt(
+        &self,
+        hash: B256,
+    ) -> BoxFuture<'_, RpcResult<Option<RpcReceipt<Ethereum>>>>;
+}
+
+/// What the forwarder does with a transaction signed for another chain than the node's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChainIdMismatchPolicy {
+    /// Reject the transaction without forwarding it.
+    #[default]
+    Reject,
+    /// Log a warning and forward the transaction anyway, e.g. to debug the upstream's answer.
+    Warn,
+}
 
 #[rpc(server, namespace = "eth")]
 pub trait EthForwarderApi<R: RpcObject> {
     #[method(name = "sendRawTransaction")]
     async fn send_raw_transaction(&self, tx: Bytes) -> RpcResult<B256>;
 
-    #[method(name = "eth_sendTransaction")]
+    #[method(name = "sendTransaction")]
     async fn send_transaction(&self, _tx: TransactionRequest) -> RpcResult<B256>;
 
-    #[method(name = "eth_sendRawTransactionSync")]
+    #[method(name = "sendRawTransactionSync")]
     async fn send_raw_transaction_sync(&self, tx: Bytes) -> RpcResult<R>;
 }
 
-pub struct EthForwarderExt {
+pub struct EthForwarderExt<P = ForwardAll> {
     client: HttpClient,
+    chain_id: u64,
+    chain_id_mismatch: ChainIdMismatchPolicy,
+    /// Whether legacy transactions that aren't bound to a chain are refused.
+    reject_pre_eip155: bool,
+    /// Highest gas limit of a block of the chain, see [`HlChainSpec::BIG_BLOCK_GAS_LIMIT`].
+    ///
+    /// [`HlChainSpec::BIG_BLOCK_GAS_LIMIT`]: crate::chainspec::HlChainSpec::BIG_BLOCK_GAS_LIMIT
+    block_gas_limit: Option<u64>,
+    policy: P,
+    local_pool: Option<Arc<dyn LocalTxPool>>,
+    tracker: Option<ForwardedTxTracker>,
 }
 
 impl EthForwarderExt {
-    pub fn new(upstream_rpc_url: String) -> Self {
-        let client =
-            HttpClientBuilder::default().build(upstream_rpc_url).expect("Failed to build client");
+    pub fn new(client: HttpClient, chain_id: u64) -> Self {
+        Self {
+            client,
+            chain_id,
+            chain_id_mismatch: ChainIdMismatchPolicy::default(),
+            reject_pre_eip155: false,
+            block_gas_limit: None,
+            policy: ForwardAll,
+            local_pool: None,
+            tracker: None,
+        }
+    }
+}
 
-        Self { client }
+impl<P: TxForwardPolicy> EthForwarderExt<P> {
+    pub fn with_chain_id_mismatch(mut self, policy: ChainIdMismatchPolicy) -> Self {
+        self.chain_id_mismatch = policy;
+        self
     }
 
-    fn from_client_error(e: ClientError, internal_error_prefix: &str) -> ErrorObject<'static> {
-        match e {
-            ClientError::Call(e) => e,
-            _ => ErrorObject::owned(
-                INTERNAL_ERROR_CODE,
-                format!("{internal_error_prefix}: {e:?}"),
-                Some(()),
-            ),
+    /// Refuses legacy transactions predating EIP-155, which can be replayed on any chain.
+    pub fn with_reject_pre_eip155(mut self, reject: bool) -> Self {
+        self.reject_pre_eip155 = reject;
+        self
+    }
+
+    /// Refuses transactions whose gas limit exceeds `block_gas_limit`, which no block can fit.
+    pub fn with_block_gas_limit(mut self, block_gas_limit: u64) -> Self {
+        self.block_gas_limit = Some(block_gas_limit);
+        self
+    }
+
+    /// Decides what happens to each transaction with `policy` instead of forwarding all of them.
+    pub fn with_policy<Q: TxForwardPolicy>(self, policy: Q) -> EthForwarderExt<Q> {
+        let Self {
+            client,
+            chain_id,
+            chain_id_mismatch,
+            reject_pre_eip155,
+            block_gas_limit,
+            local_pool,
+            tracker,
+            ..
+        } = self;
+        EthForwarderExt {
+            client,
+            chain_id,
+            chain_id_mismatch,
+            reject_pre_eip155,
+            block_gas_limit,
+            policy,
+            local_pool,
+            tracker,
+        }
+    }
+
+    /// Submits the transactions the policy keeps local to `pool`. Without one, they are rejected.
+    pub fn with_local_pool(mut self, pool: Arc<dyn LocalTxPool>) -> Self {
+        self.local_pool = Some(pool);
+        self
+    }
+
+    /// Records the forwarded transactions and the upstream's answers in `tracker`.
+    pub fn with_tracker(mut self, tracker: ForwardedTxTracker) -> Self {
+        self.tracker = Some(tracker);
+        self
+    }
+
+    /// Validates a transaction and sends it, unchanged, where the policy routes it.
+    async fn route(&self, tx: Bytes) -> RpcResult<(B256, Route)> {
+        let (decoded, sender) = self.validate(&tx)?;
+        match self.policy.decide(&decoded) {
+            TxForwardDecision::Forward => {
+                let tracked = *decoded.tx_hash();
+                if let Some(tracker) = &self.tracker {
+                    tracker.submitted(tracked, sender, decoded.nonce());
+                }
+                let sent =
+                    self.client.request("eth_sendRawTransaction", vec![tx]).await.map_err(|e| {
+                        upstream_error(e, "eth_sendRawTransaction", "Failed to send transaction")
+                    });
+                if let Some(tracker) = &self.tracker {
+                    match &sent {
+                        Ok(_) => tracker.accepted(tracked),
+                        Err(err) => tracker.refused(tracked, err.message()),
+                    }
+                }
+                Ok((sent?, Route::Upstream))
+            }
+            TxForwardDecision::Reject(reason) => Err(ErrorObject::owned(
+                TRANSACTION_REJECTED_CODE,
+                format!("transaction rejected: {reason}"),
+                None::<()>,
+            )),
+            TxForwardDecision::Local => {
+                let Some(pool) = &self.local_pool else {
+                    return Err(ErrorObject::owned(
+                        TRANSACTION_REJECTED_CODE,
+                        "transaction rejected: this node has no local transaction pool",
+                        None::<()>,
+                    ));
+                };
+                Ok((pool.send_raw_transaction(tx).await?, Route::Local))
+            }
+        }
+    }
+
+    async fn transaction_receipt(
+        &self,
+        hash: B256,
+        route: Route,
+    ) -> RpcResult<Option<RpcReceipt<Ethereum>>> {
+        match (route, &self.local_pool) {
+            (Route::Local, Some(pool)) => pool.transaction_receipt(hash).await,
+            _ => self.client.request("eth_getTransactionReceipt", vec![hash]).await.map_err(|e| {
+                upstream_error(e, "eth_getTransactionReceipt", "Failed to get transaction receipt")
+            }),
+        }
+    }
+
+    /// Checks what the upstream would refuse anyway, so that senders learn about it right away:
+    /// the encoding, the chain id, the signature and the gas limit. Returns the transaction and
+    /// its signer.
+    fn validate(&self, tx: &Bytes) -> RpcResult<(TransactionSigned, Address)> {
+        let invalid =
+            |message: String| ErrorObject::owned(INVALID_PARAMS_CODE, message, None::<()>);
+        let decoded = TransactionSigned::decode_2718(&mut tx.as_ref())
+            .map_err(|err| invalid(format!("invalid transaction: failed to decode: {err}")))?;
+        self.check_chain_id(&decoded)?;
+        if self.reject_pre_eip155 && decoded.chain_id().is_none() {
+            return Err(invalid(
+                "invalid transaction: legacy transactions without a chain id (pre-EIP-155) are \
+                 not accepted by this node"
+                    .to_string(),
+            ));
+        }
+        let Ok(sender) = decoded.recover_signer() else {
+            return Err(invalid("invalid transaction: the signature does not recover".to_string()));
+        };
+        if let Some(limit) = self.block_gas_limit.filter(|limit| decoded.gas_limit() > *limit) {
+            return Err(invalid(format!(
+                "invalid transaction: gas limit {} exceeds the block gas limit of {limit}",
+                decoded.gas_limit()
+            )));
+        }
+        Ok((decoded, sender))
+    }
+
+    /// Checks the chain id of a transaction before it's forwarded. Transactions that aren't
+    /// bound to a chain (pre-EIP-155) pass, unless refused by `reject_pre_eip155`.
+    fn check_chain_id(&self, decoded: &TransactionSigned) -> RpcResult<()> {
+        let Some(found) = decoded.chain_id().filter(|found| *found != self.chain_id) else {
+            return Ok(());
+        };
+        let message = format!(
+            "invalid chain id: transaction is signed for {}, but the node runs {}",
+            chain_name(found),
+            chain_name(self.chain_id)
+        );
+        match self.chain_id_mismatch {
+            ChainIdMismatchPolicy::Reject => {
+                Err(ErrorObject::owned(INVALID_PARAMS_CODE, message, None::<()>))
+            }
+            ChainIdMismatchPolicy::Warn => {
+                warn!(tx_hash = %decoded.tx_hash(), "Forwarding anyway: {message}");
+                Ok(())
+            }
         }
     }
 }
 
+/// Where a transaction was sent to.
+#[derive(Debug, Clone, Copy)]
+enum Route {
+    Upstream,
+    Local,
+}
+
 #[async_trait]
-impl EthForwarderApiServer<RpcReceipt<Ethereum>> for EthForwarderExt {
+impl<P: TxForwardPolicy> EthForwarderApiServer<RpcReceipt<Ethereum>> for EthForwarderExt<P> {
     async fn send_raw_transaction(&self, tx: Bytes) -> RpcResult<B256> {
-        let txhash = self
-            .client
-            .clone()
-            .request("eth_sendRawTransaction", vec![tx])
-            .await
-            .map_err(|e| Self::from_client_error(e, "Failed to send transaction"))?;
-        Ok(txhash)
+        Ok(self.route(tx).await?.0)
     }
 
     async fn send_transaction(&self, _tx: TransactionRequest) -> RpcResult<B256> {
@@ -66,17 +268,13 @@ impl EthForwarderApiServer<RpcReceipt<Ethereum>> for EthForwarderExt {
     }
 
     async fn send_raw_transaction_sync(&self, tx: Bytes) -> RpcResult<RpcReceipt<Ethereum>> {
-        let hash = self.send_raw_transaction(tx).await?;
+        let (hash, route) = self.route(tx).await?;
         const TIMEOUT_DURATION: Duration = Duration::from_secs(30);
         const INTERVAL: Duration = Duration::from_secs(1);
 
         tokio::time::timeout(TIMEOUT_DURATION, async {
             loop {
-                let receipt =
-                    self.client.request("eth_getTransactionReceipt", vec![hash]).await.map_err(
-                        |e| Self::from_client_error(e, "Failed to get transaction receipt"),
-                    )?;
-                if let Some(receipt) = receipt {
+                if let Some(receipt) = self.transaction_receipt(hash, route).await? {
                     return Ok(receipt);
                 }
                 tokio::time::sleep(INTERVAL).await;
@@ -89,3 +287,208 @@ impl EthForwarderApiServer<RpcReceipt<Ethereum>> for EthForwarderExt {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HlBlock, HlBlockBody, HlHeader,
+        addons::tx_tracker::ForwardedTxState,
+        node::{
+            primitives::BlockBody,
+            rpc::errors::{HlErrorCode, UpstreamUnavailableData},
+        },
+    };
+    use alloy_consensus::{EthereumTxEnvelope, Header, Signed, TxEip1559, TxLegacy};
+    use alloy_eips::Encodable2718;
+    use alloy_primitives::Signature;
+    use jsonrpsee::{
+        RpcModule,
+        http_client::HttpClientBuilder,
+        server::{Server, ServerHandle},
+    };
+    use reth_primitives_traits::RecoveredBlock;
+    use std::sync::Mutex;
+
+    const UPSTREAM_HASH: B256 = B256::repeat_byte(0x01);
+    const LOCAL_HASH: B256 = B256::repeat_byte(0x02);
+
+    #[derive(Debug)]
+    struct Fixed(TxForwardDecision);
+
+    impl TxForwardPolicy for Fixed {
+        fn decide(&self, _tx: &TransactionSigned) -> TxForwardDecision {
+            self.0.clone()
+        }
+    }
+
+    struct Pool;
+
+    impl LocalTxPool for Pool {
+        fn send_raw_transaction(&self, _tx: Bytes) -> BoxFuture<'_, RpcResult<B256>> {
+            Box::pin(async { Ok(LOCAL_HASH) })
+        }
+
+        fn transaction_receipt(
+            &self,
+            _hash: B256,
+        ) -> BoxFuture<'_, RpcResult<Option<RpcReceipt<Ethereum>>>> {
+            Box::pin(async { Ok(None) })
+        }
+    }
+
+    type Received = Arc<Mutex<Vec<Bytes>>>;
+
+    /// An upstream recording the transactions it receives.
+    async fn upstream() -> (HttpClient, Received, ServerHandle) {
+        let received = Received::default();
+        let mut module = RpcModule::new(received.clone());
+        module
+            .register_method("eth_sendRawTransaction", |params, received, _| {
+                let (tx,): (Bytes,) = params.parse().unwrap();
+                received.lock().unwrap().push(tx);
+                UPSTREAM_HASH
+            })
+            .unwrap();
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let client = HttpClientBuilder::default().build(url).unwrap();
+        (client, received, server.start(module))
+    }
+
+    fn encode(tx: impl Into<EthereumTxEnvelope<alloy_consensus::TxEip4844>>) -> Bytes {
+        TransactionSigned::Default(tx.into()).encoded_2718().into()
+    }
+
+    fn raw_tx_with(chain_id: u64, gas_limit: u64) -> Bytes {
+        let tx = TxEip1559 { chain_id, gas_limit, ..Default::default() };
+        encode(Signed::new_unhashed(tx, Signature::test_signature()))
+    }
+
+    fn raw_tx() -> Bytes {
+        raw_tx_with(999, 21_000)
+    }
+
+    #[test]
+    fn methods_are_registered_in_the_eth_namespace() {
+        let client = HttpClientBuilder::default().build("http://127.0.0.1:1").unwrap();
+        let module = EthForwarderExt::new(client, 999).into_rpc();
+        let mut names: Vec<_> = module.method_names().collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            ["eth_sendRawTransaction", "eth_sendRawTransactionSync", "eth_sendTransaction"]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_transactions_are_rejected_before_forwarding() {
+        let (client, received, _handle) = upstream().await;
+        let forwarder = EthForwarderExt::new(client, 999)
+            .with_block_gas_limit(2_000_000)
+            .with_reject_pre_eip155(true);
+        let rejection = |tx| async {
+            let err = forwarder.send_raw_transaction(tx).await.unwrap_err();
+            assert_eq!(err.code(), INVALID_PARAMS_CODE);
+            err.message().to_string()
+        };
+
+        let message = rejection(raw_tx_with(1, 21_000)).await;
+        assert_eq!(
+            message,
+            format!(
+                "invalid chain id: transaction is signed for {}, but the node runs {}",
+                chain_name(1),
+                chain_name(999)
+            )
+        );
+        let message = rejection(Bytes::from_static(&[0x02, 0xde, 0xad])).await;
+        assert!(message.starts_with("invalid transaction: failed to decode"), "{message}");
+        let message = rejection(raw_tx_with(999, 2_000_001)).await;
+        assert_eq!(
+            message,
+            "invalid transaction: gas limit 2000001 exceeds the block gas limit of 2000000"
+        );
+        let pre_eip155 = TxLegacy { chain_id: None, gas_limit: 21_000, ..Default::default() };
+        let message =
+            rejection(encode(Signed::new_unhashed(pre_eip155, Signature::test_signature()))).await;
+        assert!(message.contains("pre-EIP-155"), "{message}");
+        assert!(received.lock().unwrap().is_empty());
+
+        // Valid transactions are forwarded unchanged
+        let tx = raw_tx_with(999, 2_000_000);
+        assert_eq!(forwarder.send_raw_transaction(tx.clone()).await.unwrap(), UPSTREAM_HASH);
+        assert_eq!(*received.lock().unwrap(), vec![tx]);
+    }
+
+    #[tokio::test]
+    async fn forwarded_transactions_are_tracked_until_included() {
+        let (client, _received, _handle) = upstream().await;
+        let tracker = ForwardedTxTracker::default();
+        let forwarder = EthForwarderExt::new(client, 999).with_tracker(tracker.clone());
+        let tx = raw_tx();
+        let decoded = TransactionSigned::decode_2718(&mut tx.as_ref()).unwrap();
+        let (hash, sender) = (*decoded.tx_hash(), decoded.recover_signer().unwrap());
+        assert_eq!(tracker.status(hash), None);
+
+        forwarder.send_raw_transaction(tx).await.unwrap();
+        assert_eq!(tracker.status(hash).unwrap().state, ForwardedTxState::Accepted);
+
+        let block = HlBlock {
+            header: HlHeader {
+                inner: Header { number: 5, ..Default::default() },
+                extras: Default::default(),
+            },
+            body: HlBlockBody {
+                inner: BlockBody { transactions: vec![decoded], ommers: vec![], withdrawals: None },
+                sidecars: None,
+                read_precompile_calls: None,
+                highest_precompile_address: None,
+            },
+        };
+        tracker.on_imported(&RecoveredBlock::new_unhashed(block, vec![sender]));
+        let status = tracker.status(hash).unwrap();
+        assert_eq!(status.state, ForwardedTxState::Included);
+        assert_eq!(status.included_block, Some(5));
+    }
+
+    #[tokio::test]
+    async fn transactions_go_where_the_policy_decides() {
+        let (client, received, _handle) = upstream().await;
+        let forwarder =
+            |decision| EthForwarderExt::new(client.clone(), 999).with_policy(Fixed(decision));
+
+        let forward = forwarder(TxForwardDecision::Forward);
+        assert_eq!(forward.send_raw_transaction(raw_tx()).await.unwrap(), UPSTREAM_HASH);
+        assert_eq!(received.lock().unwrap().len(), 1);
+
+        let reject = forwarder(TxForwardDecision::Reject("gas limit too high".into()));
+        let err = reject.send_raw_transaction(raw_tx()).await.unwrap_err();
+        assert_eq!(err.code(), TRANSACTION_REJECTED_CODE);
+        assert_eq!(err.message(), "transaction rejected: gas limit too high");
+
+        let local = forwarder(TxForwardDecision::Local);
+        let err = local.send_raw_transaction(raw_tx()).await.unwrap_err();
+        assert_eq!(err.message(), "transaction rejected: this node has no local transaction pool");
+        let local = local.with_local_pool(Arc::new(Pool));
+        assert_eq!(local.send_raw_transaction(raw_tx()).await.unwrap(), LOCAL_HASH);
+
+        // Only the forwarded transaction reached the upstream
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_upstream_is_reported_as_unavailable() {
+        let (client, _received, handle) = upstream().await;
+        handle.stop().unwrap();
+        handle.stopped().await;
+        let forwarder = EthForwarderExt::new(client, 999);
+
+        let err = forwarder.send_raw_transaction(raw_tx()).await.unwrap_err();
+        assert_eq!(err.code(), HlErrorCode::UpstreamUnavailable.code());
+        assert!(err.message().starts_with("Failed to send transaction: "), "{}", err.message());
+        let data: UpstreamUnavailableData =
+            serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data.method, "eth_sendRawTransaction");
+    }
+}
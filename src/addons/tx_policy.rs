@@ -0,0 +1,162 @@
+//! Which transactions `eth_sendRawTransaction` forwards to the upstream RPC.
+//!
+//! The forwarder asks a [`TxForwardPolicy`] about every transaction it decodes. By default all of
+//! them are forwarded; `--forward.rules` loads a [`RuleBasedPolicy`] that rejects transactions
+//! the operator doesn't want to relay. Nodes embedding the forwarder can plug in their own
+//! policy, including one that keeps transactions in a local pool.
+
+use alloy_consensus::Transaction;
+use alloy_primitives::Address;
+use serde::Deserialize;
+use std::{collections::BTreeSet, fmt, path::Path, sync::Arc};
+
+use crate::node::primitives::TransactionSigned;
+
+/// What to do with a transaction sent to the node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxForwardDecision {
+    /// Forward the transaction to the upstream RPC.
+    Forward,
+    /// Refuse the transaction, telling the sender why.
+    Reject(String),
+    /// Submit the transaction to the local pool instead of forwarding it.
+    Local,
+}
+
+/// Decides what happens to transactions before they are forwarded.
+pub trait TxForwardPolicy: fmt::Debug + Send + Sync + 'static {
+    fn decide(&self, tx: &TransactionSigned) -> TxForwardDecision;
+}
+
+impl<P: TxForwardPolicy + ?Sized> TxForwardPolicy for Arc<P> {
+    fn decide(&self, tx: &TransactionSigned) -> TxForwardDecision {
+        (**self).decide(tx)
+    }
+}
+
+/// Forwards every transaction, leaving validation to the upstream.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ForwardAll;
+
+impl TxForwardPolicy for ForwardAll {
+    fn decide(&self, _tx: &TransactionSigned) -> TxForwardDecision {
+        TxForwardDecision::Forward
+    }
+}
+
+/// Rejects transactions over a gas limit, under a fee floor or calling blocked contracts, and
+/// forwards the rest.
+///
+/// Loaded from a JSON file, e.g.
+/// `{"maxGasLimit": 2000000, "minMaxFeePerGas": 100000000, "blockedAddresses": ["0x…"]}`.
+/// Every rule is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RuleBasedPolicy {
+    /// Highest gas limit accepted.
+    pub max_gas_limit: Option<u64>,
+    /// Lowest max fee per gas accepted, in wei. Legacy transactions are checked by gas price.
+    pub min_max_fee_per_gas: Option<u128>,
+    /// Contracts transactions must not call.
+    #[serde(default)]
+    pub blocked_addresses: BTreeSet<Address>,
+}
+
+impl RuleBasedPolicy {
+    /// Reads the rules from the JSON file at `path`.
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let file = std::fs::read(path)
+            .map_err(|err| eyre::eyre!("failed to read {}: {err}", path.display()))?;
+        serde_json::from_slice(&file)
+            .map_err(|err| eyre::eyre!("invalid forwarding rules in {}: {err}", path.display()))
+    }
+}
+
+impl TxForwardPolicy for RuleBasedPolicy {
+    fn decide(&self, tx: &TransactionSigned) -> TxForwardDecision {
+        if let Some(max) = self.max_gas_limit.filter(|max| tx.gas_limit() > *max) {
+            return TxForwardDecision::Reject(format!(
+                "gas limit {} exceeds the maximum of {max} accepted by this node",
+                tx.gas_limit()
+            ));
+        }
+        if let Some(min) = self.min_max_fee_per_gas.filter(|min| tx.max_fee_per_gas() < *min) {
+            return TxForwardDecision::Reject(format!(
+                "max fee per gas {} is below the minimum of {min} accepted by this node",
+                tx.max_fee_per_gas()
+            ));
+        }
+        if let Some(to) = tx.to().filter(|to| self.blocked_addresses.contains(to)) {
+            return TxForwardDecision::Reject(format!(
+                "transactions to {to} are not accepted by this node"
+            ));
+        }
+        TxForwardDecision::Forward
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::{EthereumTxEnvelope, Signed, TxEip1559};
+    use alloy_primitives::{Signature, TxKind, address};
+
+    const BLOCKED: Address = address!("0x00000000000000000000000000000000000000bb");
+
+    fn tx(gas_limit: u64, max_fee_per_gas: u128, to: Address) -> TransactionSigned {
+        let tx = TxEip1559 {
+            chain_id: 999,
+            gas_limit,
+            max_fee_per_gas,
+            to: TxKind::Call(to),
+            ..Default::default()
+        };
+        let signed = Signed::new_unhashed(tx, Signature::test_signature());
+        TransactionSigned::Default(EthereumTxEnvelope::Eip1559(signed))
+    }
+
+    fn rules() -> RuleBasedPolicy {
+        serde_json::from_value(serde_json::json!({
+            "maxGasLimit": 100_000,
+            "minMaxFeePerGas": 1_000,
+            "blockedAddresses": [BLOCKED],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn forwards_everything_by_default() {
+        assert_eq!(ForwardAll.decide(&tx(u64::MAX, 0, BLOCKED)), TxForwardDecision::Forward);
+    }
+
+    #[test]
+    fn rules_reject_with_the_broken_rule() {
+        let rules = rules();
+        assert_eq!(rules.decide(&tx(100_000, 1_000, Address::ZERO)), TxForwardDecision::Forward);
+
+        let reason = |tx| match rules.decide(&tx) {
+            TxForwardDecision::Reject(reason) => reason,
+            decision => panic!("expected a rejection, got {decision:?}"),
+        };
+        assert_eq!(
+            reason(tx(100_001, 1_000, Address::ZERO)),
+            "gas limit 100001 exceeds the maximum of 100000 accepted by this node"
+        );
+        assert_eq!(
+            reason(tx(21_000, 999, Address::ZERO)),
+            "max fee per gas 999 is below the minimum of 1000 accepted by this node"
+        );
+        assert_eq!(
+            reason(tx(21_000, 1_000, BLOCKED)),
+            format!("transactions to {BLOCKED} are not accepted by this node")
+        );
+    }
+
+    #[test]
+    fn rules_are_optional_but_must_be_known() {
+        let rules: RuleBasedPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(rules, RuleBasedPolicy::default());
+        assert_eq!(rules.decide(&tx(u64::MAX, 0, BLOCKED)), TxForwardDecision::Forward);
+        assert!(serde_json::from_str::<RuleBasedPolicy>(r#"{"maxGas": 1}"#).is_err());
+    }
+}
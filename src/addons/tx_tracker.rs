@@ -0,0 +1,303 @@
+//! Tracks what became of the transactions forwarded to the upstream RPC, served by
+//! `hl_getForwardedTransactionStatus`.
+//!
+//! The forwarder records each transaction as it is sent and the upstream's answer; locally
+//! imported blocks then show whether it was included, or replaced by another transaction with the
+//! same sender and nonce. Transactions neither included nor dropped within the TTL are reported
+//! as expired. The tracker holds a bounded number of transactions, forgetting the oldest first.
+
+use crate::{HlBlock, HlPrimitives};
+use alloy_consensus::{BlockHeader, Transaction, transaction::TxHashRef};
+use alloy_primitives::{Address, B256};
+use futures::StreamExt;
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use reth_primitives_traits::RecoveredBlock;
+use reth_provider::CanonStateNotificationStream;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::trace;
+
+/// Default number of forwarded transactions tracked at once.
+pub const DEFAULT_TRACKED_TXS: usize = 10_000;
+
+/// Default time a forwarded transaction has to be included before it is reported as expired.
+pub const DEFAULT_TRACKING_TTL: Duration = Duration::from_secs(600);
+
+/// Where a forwarded transaction is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardedTxState {
+    /// Sent to the upstream, which hasn't answered yet.
+    Submitted,
+    /// Accepted by the upstream, not included yet.
+    Accepted,
+    /// Included in a block imported by this node.
+    Included,
+    /// Refused by the upstream, or replaced by another transaction with the same nonce.
+    Dropped,
+    /// Not included within the tracking TTL.
+    Expired,
+}
+
+/// Response of `hl_getForwardedTransactionStatus`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardedTxStatus {
+    pub state: ForwardedTxState,
+    /// Error the upstream refused the transaction with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_error: Option<String>,
+    /// Number of the block that included the transaction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub included_block: Option<u64>,
+    /// Hash of the transaction included with the same sender and nonce instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaced_by: Option<B256>,
+}
+
+impl ForwardedTxStatus {
+    fn new(state: ForwardedTxState) -> Self {
+        Self { state, upstream_error: None, included_block: None, replaced_by: None }
+    }
+
+    /// Whether the transaction may still be included.
+    fn is_pending(&self) -> bool {
+        matches!(self.state, ForwardedTxState::Submitted | ForwardedTxState::Accepted)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    status: ForwardedTxStatus,
+    sender: Address,
+    nonce: u64,
+    submitted_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Tracked {
+    entries: HashMap<B256, Entry>,
+    /// Hashes by submission, oldest first.
+    order: VecDeque<B256>,
+    by_nonce: HashMap<(Address, u64), B256>,
+}
+
+impl Tracked {
+    fn remove_oldest(&mut self) {
+        let Some(hash) = self.order.pop_front() else { return };
+        if let Some(entry) = self.entries.remove(&hash) {
+            let key = (entry.sender, entry.nonce);
+            if self.by_nonce.get(&key) == Some(&hash) {
+                self.by_nonce.remove(&key);
+            }
+        }
+    }
+}
+
+/// Lifecycle of the transactions forwarded to the upstream, shared by the forwarder, the import
+/// watcher and the RPC.
+#[derive(Debug, Clone)]
+pub struct ForwardedTxTracker {
+    tracked: Arc<Mutex<Tracked>>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl Default for ForwardedTxTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRACKED_TXS, DEFAULT_TRACKING_TTL)
+    }
+}
+
+impl ForwardedTxTracker {
+    /// Tracks up to `capacity` transactions, reported as expired when not included within `ttl`
+    /// and forgotten twice as long after they were sent.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self { tracked: Default::default(), capacity: capacity.max(1), ttl }
+    }
+
+    /// Records a transaction being sent to the upstream.
+    pub fn submitted(&self, hash: B256, sender: Address, nonce: u64) {
+        let mut tracked = self.tracked.lock().unwrap();
+        let now = Instant::now();
+        while tracked.order.front().is_some_and(|oldest| {
+            tracked
+                .entries
+                .get(oldest)
+                .is_none_or(|entry| now.duration_since(entry.submitted_at) > self.ttl * 2)
+        }) {
+            tracked.remove_oldest();
+        }
+        // A resent transaction starts over
+        if tracked.entries.contains_key(&hash) {
+            tracked.order.retain(|tracked| *tracked != hash);
+        }
+        while tracked.order.len() >= self.capacity {
+            tracked.remove_oldest();
+        }
+        let status = ForwardedTxStatus::new(ForwardedTxState::Submitted);
+        tracked.entries.insert(hash, Entry { status, sender, nonce, submitted_at: now });
+        tracked.order.push_back(hash);
+        tracked.by_nonce.insert((sender, nonce), hash);
+    }
+
+    /// Records the upstream accepting a transaction.
+    pub fn accepted(&self, hash: B256) {
+        self.update(hash, |status| status.state = ForwardedTxState::Accepted);
+    }
+
+    /// Records the upstream refusing a transaction with `error`.
+    pub fn refused(&self, hash: B256, error: impl Into<String>) {
+        self.update(hash, |status| {
+            status.state = ForwardedTxState::Dropped;
+            status.upstream_error = Some(error.into());
+        });
+    }
+
+    fn update(&self, hash: B256, update: impl FnOnce(&mut ForwardedTxStatus)) {
+        let mut tracked = self.tracked.lock().unwrap();
+        if let Some(entry) =
+            tracked.entries.get_mut(&hash).filter(|entry| entry.status.is_pending())
+        {
+            update(&mut entry.status);
+        }
+    }
+
+    /// Records the transactions of a block imported as `number`, given by hash, sender and nonce.
+    pub fn on_block(&self, number: u64, txs: impl IntoIterator<Item = (B256, Address, u64)>) {
+        let mut tracked = self.tracked.lock().unwrap();
+        if tracked.entries.is_empty() {
+            return;
+        }
+        let Tracked { entries, by_nonce, .. } = &mut *tracked;
+        for (hash, sender, nonce) in txs {
+            let Some(&tracked_hash) = by_nonce.get(&(sender, nonce)) else { continue };
+            let Some(entry) = entries.get_mut(&tracked_hash) else { continue };
+            if tracked_hash == hash {
+                entry.status.state = ForwardedTxState::Included;
+                entry.status.included_block = Some(number);
+            } else if entry.status.is_pending() {
+                entry.status.state = ForwardedTxState::Dropped;
+                entry.status.replaced_by = Some(hash);
+            }
+        }
+    }
+
+    /// Returns the status of a forwarded transaction, if it is tracked.
+    pub fn status(&self, hash: B256) -> Option<ForwardedTxStatus> {
+        let tracked = self.tracked.lock().unwrap();
+        let entry = tracked.entries.get(&hash)?;
+        let mut status = entry.status.clone();
+        if status.is_pending() && entry.submitted_at.elapsed() > self.ttl {
+            status.state = ForwardedTxState::Expired;
+        }
+        Some(status)
+    }
+
+    /// Records the transactions of a block imported by the node.
+    pub fn on_imported(&self, block: &RecoveredBlock<HlBlock>) {
+        let txs = block
+            .transactions_with_sender()
+            .map(|(sender, tx)| (*tx.tx_hash(), *sender, tx.nonce()));
+        self.on_block(block.number(), txs);
+    }
+
+    /// Records the transactions of the blocks the node imports, until the stream ends.
+    pub async fn run(self, mut notifications: CanonStateNotificationStream<HlPrimitives>) {
+        while let Some(notification) = notifications.next().await {
+            for block in notification.committed().blocks_iter() {
+                self.on_imported(block);
+            }
+        }
+    }
+}
+
+/// RPC reporting what became of forwarded transactions.
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlForwardedTxApi {
+    /// Returns the status of a transaction this node forwarded, or null if it isn't tracked.
+    #[method(name = "getForwardedTransactionStatus")]
+    async fn forwarded_transaction_status(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<ForwardedTxStatus>>;
+}
+
+#[async_trait]
+impl HlForwardedTxApiServer for ForwardedTxTracker {
+    async fn forwarded_transaction_status(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<ForwardedTxStatus>> {
+        trace!(target: "rpc::hl", ?hash, "Serving hl_getForwardedTransactionStatus");
+        Ok(self.status(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: Address = Address::repeat_byte(0xaa);
+
+    fn hash(byte: u8) -> B256 {
+        B256::repeat_byte(byte)
+    }
+
+    fn state(tracker: &ForwardedTxTracker, hash: B256) -> Option<ForwardedTxState> {
+        tracker.status(hash).map(|status| status.state)
+    }
+
+    #[test]
+    fn replaced_refused_and_expired_transactions_are_dropped() {
+        let tracker = ForwardedTxTracker::new(10, Duration::from_secs(60));
+        tracker.submitted(hash(1), SENDER, 0);
+        tracker.accepted(hash(1));
+        tracker.on_block(7, [(hash(9), SENDER, 0)]);
+        let status = tracker.status(hash(1)).unwrap();
+        assert_eq!(status.state, ForwardedTxState::Dropped);
+        assert_eq!(status.replaced_by, Some(hash(9)));
+
+        tracker.submitted(hash(2), SENDER, 1);
+        tracker.refused(hash(2), "nonce too low");
+        let status = tracker.status(hash(2)).unwrap();
+        assert_eq!(status.state, ForwardedTxState::Dropped);
+        assert_eq!(status.upstream_error.as_deref(), Some("nonce too low"));
+
+        let tracker = ForwardedTxTracker::new(10, Duration::ZERO);
+        tracker.submitted(hash(3), SENDER, 2);
+        tracker.accepted(hash(3));
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(state(&tracker, hash(3)), Some(ForwardedTxState::Expired));
+    }
+
+    #[test]
+    fn oldest_transactions_are_forgotten_beyond_capacity() {
+        let tracker = ForwardedTxTracker::new(2, Duration::from_secs(60));
+        for nonce in 0..3 {
+            tracker.submitted(hash(nonce as u8 + 1), SENDER, nonce);
+        }
+        assert_eq!(state(&tracker, hash(1)), None);
+        assert_eq!(state(&tracker, hash(3)), Some(ForwardedTxState::Submitted));
+
+        // The forgotten transaction's nonce no longer maps to it
+        tracker.on_block(1, [(hash(1), SENDER, 0)]);
+        assert_eq!(state(&tracker, hash(1)), None);
+    }
+
+    #[test]
+    fn status_serializes_like_the_rpc_documents() {
+        let mut status = ForwardedTxStatus::new(ForwardedTxState::Included);
+        status.included_block = Some(5);
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({ "state": "included", "includedBlock": 5 })
+        );
+    }
+}
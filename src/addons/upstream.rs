@@ -0,0 +1,167 @@
+//! Client of the upstream RPC that transactions and calls are forwarded to, shared by the
+//! forwarders.
+//!
+//! Connections are set up on the first request, so the first forwarded transaction pays for the
+//! TCP and TLS handshakes. With `--forward-preconnect`, a background task connects at startup and
+//! pings the upstream periodically, which keeps the connection alive and notices a dead upstream
+//! before a user request does.
+
+use alloy_primitives::U64;
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use reth_metrics::{
+    Metrics,
+    metrics::{Counter, Gauge},
+};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+/// Interval of the keep-alive pings of `--forward-preconnect`, well below the idle timeout of
+/// common load balancers.
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Metrics, Clone)]
+#[metrics(scope = "forwarder.upstream")]
+struct UpstreamMetrics {
+    /// Whether the last ping of the upstream RPC succeeded
+    up: Gauge,
+    /// How many pings of the upstream RPC failed
+    ping_failures: Counter,
+}
+
+/// Health of the upstream, as last seen by a ping.
+const UNKNOWN: u8 = 0;
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+
+/// The upstream RPC, whose clones share connections.
+#[derive(Clone)]
+pub struct UpstreamClient {
+    url: Arc<str>,
+    client: HttpClient,
+    health: Arc<AtomicU8>,
+    metrics: UpstreamMetrics,
+}
+
+impl std::fmt::Debug for UpstreamClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpstreamClient").field("url", &self.url).finish_non_exhaustive()
+    }
+}
+
+impl UpstreamClient {
+    pub fn new(url: &str) -> eyre::Result<Self> {
+        Ok(Self {
+            url: url.into(),
+            client: HttpClientBuilder::default().build(url)?,
+            health: Arc::new(AtomicU8::new(UNKNOWN)),
+            metrics: UpstreamMetrics::default(),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The client requests are forwarded with.
+    pub fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    /// Whether the last ping succeeded, `None` before the first one.
+    pub fn is_up(&self) -> Option<bool> {
+        match self.health.load(Ordering::Relaxed) {
+            UP => Some(true),
+            DOWN => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Sends a cheap request to the upstream, connecting if needed, and records whether it
+    /// answered.
+    pub async fn ping(&self) -> bool {
+        let result = self.client.request::<U64, _>("eth_chainId", rpc_params![]).await;
+        let up = result.is_ok();
+        let previous = self.health.swap(if up { UP } else { DOWN }, Ordering::Relaxed);
+        self.metrics.up.set(up as u8 as f64);
+        match result {
+            Ok(_) if previous == DOWN => info!(url = %self.url, "Upstream RPC is reachable again"),
+            Ok(_) if previous == UNKNOWN => debug!(url = %self.url, "Connected to upstream RPC"),
+            Ok(_) => {}
+            Err(err) => {
+                self.metrics.ping_failures.increment(1);
+                if previous != DOWN {
+                    warn!(url = %self.url, %err, "Upstream RPC is unreachable");
+                }
+            }
+        }
+        up
+    }
+
+    /// Connects right away and pings the upstream every `interval`.
+    pub async fn keep_alive(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            // The first tick completes immediately
+            ticker.tick().await;
+            self.ping().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::{RpcModule, server::Server};
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn preconnects_before_the_first_request() {
+        let pings = Arc::new(AtomicUsize::new(0));
+        let mut module = RpcModule::new(pings.clone());
+        module
+            .register_method("eth_chainId", |_, pings, _| {
+                pings.fetch_add(1, Ordering::SeqCst);
+                U64::from(999)
+            })
+            .unwrap();
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        let handle = server.start(module);
+
+        let upstream = UpstreamClient::new(&url).unwrap();
+        assert_eq!(upstream.is_up(), None);
+        tokio::spawn(upstream.clone().keep_alive(Duration::from_millis(50)));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while upstream.is_up().is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(upstream.is_up(), Some(true));
+        assert!(pings.load(Ordering::SeqCst) >= 1);
+
+        // A dead upstream is noticed without any forwarded request
+        handle.stop().unwrap();
+        handle.stopped().await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while upstream.is_up() != Some(false) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::{HlBlock, HlPrimitives};
+use crate::{HlBlock, HlPrimitives, chainspec::TOTAL_DIFFICULTY};
 use alloy_primitives::U256;
 use alloy_rpc_types::Header;
 use futures::StreamExt;
@@ -80,7 +80,7 @@ pub(super) fn new_headers_stream<Eth: EthWrapper>(
             .map(|block| {
                 Header::from_consensus(
                     SealedHeader::new(block.header().inner.clone(), block.hash()).into(),
-                    None,
+                    Some(TOTAL_DIFFICULTY),
                     Some(U256::from(block.rlp_length())),
                 )
             })
@@ -7,7 +7,7 @@ use crate::{
 };
 use alloy_eips::eip7840::BlobParams;
 use alloy_genesis::Genesis;
-use alloy_primitives::{Address, B256, U256};
+use alloy_primitives::{Address, B256, U256, address};
 use reth_chainspec::{
     BaseFeeParams, ChainSpec, DepositContract, EthChainSpec, EthereumHardfork, EthereumHardforks,
     ForkCondition, ForkFilter, ForkId, Hardforks, Head,
@@ -16,9 +16,29 @@ use reth_discv4::NodeRecord;
 use reth_evm::eth::spec::EthExecutorSpec;
 use std::fmt::Display;
 
+/// Total difficulty of every HyperEVM block. The chain is post-merge from genesis, with a
+/// terminal total difficulty of 0, and blocks have no difficulty. `NewBlock` messages carry it,
+/// and the RPC reports it as `totalDifficulty`.
+pub const TOTAL_DIFFICULTY: U256 = U256::ZERO;
+
 pub const MAINNET_CHAIN_ID: u64 = 999;
 pub const TESTNET_CHAIN_ID: u64 = 998;
 
+/// Human-readable name of the chain `chain_id`, for error messages.
+pub fn chain_name(chain_id: u64) -> String {
+    match chain_id {
+        MAINNET_CHAIN_ID => format!("HyperEVM mainnet ({chain_id})"),
+        TESTNET_CHAIN_ID => format!("HyperEVM testnet ({chain_id})"),
+        _ => format!("chain {chain_id}"),
+    }
+}
+
+/// Lowest read precompile address.
+pub const BASE_PRECOMPILE_ADDRESS: Address = address!("0x0000000000000000000000000000000000000800");
+/// Highest read precompile address of blocks that don't record `highest_precompile_address`.
+pub const DEFAULT_HIGHEST_PRECOMPILE_ADDRESS: Address =
+    address!("0x000000000000000000000000000000000000080d");
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct HlChainSpec {
     pub inner: ChainSpec,
@@ -114,6 +134,9 @@ impl EthExecutorSpec for HlChainSpec {
 impl HlChainSpec {
     pub const MAINNET_RPC_URL: &str = "https://rpc.hyperliquid.xyz/evm";
     pub const TESTNET_RPC_URL: &str = "https://rpc.hyperliquid-testnet.xyz/evm";
+    /// Gas limit of the big blocks, the highest gas limit of a block on either chain. Small
+    /// blocks are produced far more often, with a lower gas limit.
+    pub const BIG_BLOCK_GAS_LIMIT: u64 = 30_000_000;
 
     pub fn official_rpc_url(&self) -> &'static str {
         match self.inner.chain().id() {
@@ -5,6 +5,8 @@ mod evm;
 mod hardforks;
 pub mod node;
 pub mod pseudo_peer;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod version;
 
 pub use node::primitives::{HlBlock, HlBlockBody, HlHeader, HlPrimitives};
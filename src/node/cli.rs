@@ -1,12 +1,55 @@
+#[cfg(feature = "otel")]
+use crate::node::otel::{OtlpArgs, OtlpExport};
 use crate::{
+    addons::{
+        forwarding::{ForwardedMethod, ForwardingPolicy},
+        rpc_rate_limit::RpcRateLimits,
+        sync_limits::SyncServerLimits,
+        sync_replica::DEFAULT_REPLICA_MAX_LAG,
+        sync_server::{
+            DEFAULT_MAX_READY_LAG, DEFAULT_MAX_RESPONSE_BYTES, DEFAULT_PAYLOAD_CACHE_SIZE,
+        },
+        tx_forwarder::ChainIdMismatchPolicy,
+        tx_policy::{ForwardAll, RuleBasedPolicy, TxForwardPolicy},
+        tx_tracker::{DEFAULT_TRACKED_TXS, DEFAULT_TRACKING_TTL, ForwardedTxTracker},
+    },
     chainspec::{HlChainSpec, parser::HlChainSpecParser},
     node::{
-        HlNode, consensus::HlConsensus, evm::config::HlEvmConfig, migrate::Migrator,
-        spot_meta::init as spot_meta_init, storage::tables::Tables,
+        HlNode,
+        commands::{
+            audit::AuditCommand,
+            audit_state_root::AuditStateRootCommand,
+            backfill::BackfillCommand,
+            decode_block::DecodeBlockCommand,
+            export_precompile_calls::ExportPrecompileCallsCommand,
+            import_stdin::{ImportStdinArgs, import_stdin},
+            stream_blocks::StreamBlocksCommand,
+            verify_precompile_addresses::VerifyPrecompileAddressesCommand,
+        },
+        consensus::{HlConsensus, precompile_calls::DEFAULT_HISTORICAL_BLOCK_AGE},
+        evm::config::HlEvmConfig,
+        migrate::{Migrator, SystemTxCountSource, TxRootCheck},
+        network::{
+            NewBlockLimits,
+            block_import::forkchoice::{
+                DEFAULT_FINALIZED_DEPTH, DEFAULT_FORKCHOICE_INTERVAL, DEFAULT_FORKCHOICE_TIMEOUT,
+                DEFAULT_SAFE_DEPTH, ForkchoicePolicy,
+            },
+        },
+        rpc::{proof::DEFAULT_ETH_GET_PROOF_WINDOW, trace_timeout::parse_timeout},
+        spot_meta::init as spot_meta_init,
+        status_log::DEFAULT_STATUS_LOG_INTERVAL,
+        storage::{
+            prune::{ArchiveWindow, MINIMUM_ARCHIVE_WINDOW},
+            tables::Tables,
+        },
+        types::{SPOT_META_PERSIST_ATTEMPTS, SPOT_META_PERSIST_BACKOFF},
+    },
+    pseudo_peer::{
+        BLOCK_CACHE_SNAPSHOT_FILE, BlockCacheSnapshot, BlockSourceArgs, DEFAULT_SNAPSHOT_MAX_BYTES,
     },
-    pseudo_peer::BlockSourceArgs,
 };
-use clap::{Args, Parser};
+use clap::{Args, Parser, Subcommand};
 use reth::{
     CliRunner,
     args::{DatabaseArgs, DatadirArgs, LogArgs},
@@ -17,12 +60,19 @@ use reth::{
 };
 use reth_chainspec::EthChainSpec;
 use reth_cli::chainspec::ChainSpecParser;
-use reth_cli_commands::{common::EnvironmentArgs, launcher::FnLauncher};
+use reth_cli_commands::{common::EnvironmentArgs, launcher::FnLauncher, node::NodeCommand};
 use reth_db::{DatabaseEnv, init_db, mdbx::init_db_for};
-use reth_tracing::FileWorkerGuard;
+use reth_tracing::{
+    FileWorkerGuard, Layers,
+    tracing_subscriber::{self, EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt},
+};
 use std::{
     fmt::{self},
+    io::IsTerminal,
+    net::SocketAddr,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 use tracing::info;
 
@@ -53,19 +103,80 @@ pub struct HlNodeArgs {
     /// Enable hl-node compliant mode.
     ///
     /// This option
-    /// 1. filters out system transactions from block transaction list.
-    /// 2. filters out logs that are not from the block's transactions.
-    /// 3. filters out logs and transactions from subscription.
+    /// 1. hides system transactions unless --expose-system-txs is set.
+    /// 2. adds hl-node's system transaction methods, e.g. eth_getEvmSystemTxsByBlockNumber.
     #[arg(long, env = "HL_NODE_COMPLIANT")]
     pub hl_node_compliant: bool,
 
+    /// Expose system transactions in every RPC method, flagged with `systemTx: true` in blocks.
+    ///
+    /// Hidden system transactions are left out of blocks, receipts, logs, subscriptions, traces
+    /// and transaction lookups, which number the user transactions from 0. Defaults to exposing
+    /// them unless --hl-node-compliant is set.
+    #[arg(long, env = "EXPOSE_SYSTEM_TXS", num_args = 0..=1, default_missing_value = "true")]
+    pub expose_system_txs: Option<bool>,
+
     /// Forward eth_call and eth_estimateGas to the upstream RPC.
     ///
-    /// This is useful when read precompile is needed for gas estimation.
+    /// This is useful when read precompile is needed for gas estimation. Same as
+    /// --forward.allow=eth_call,eth_estimateGas.
     #[arg(long, env = "FORWARD_CALL")]
     pub forward_call: bool,
 
-    /// Experimental: enables the eth_getProof RPC method.
+    /// Methods to forward to the upstream RPC in addition to eth_sendRawTransaction and
+    /// eth_sendRawTransactionSync, comma-separated.
+    #[arg(long = "forward.allow", env = "FORWARD_ALLOW", value_enum, value_delimiter = ',')]
+    pub forward_allow: Vec<ForwardedMethod>,
+
+    /// Methods to execute locally instead of forwarding them, comma-separated. Transactions sent
+    /// to a method executed locally only enter the node's own pool.
+    #[arg(long = "forward.deny", env = "FORWARD_DENY", value_enum, value_delimiter = ',')]
+    pub forward_deny: Vec<ForwardedMethod>,
+
+    /// Connect to the upstream RPC at startup and ping it every 30 seconds, so that the first
+    /// forwarded request doesn't wait for the connection and a dead upstream is logged early.
+    #[arg(long, env = "FORWARD_PRECONNECT")]
+    pub forward_preconnect: bool,
+
+    /// What to do with a transaction signed for another chain: reject it before forwarding, or
+    /// only log a warning and forward it anyway, e.g. for debugging.
+    #[arg(
+        long = "forward.chain-id-mismatch",
+        env = "FORWARD_CHAIN_ID_MISMATCH",
+        value_enum,
+        default_value_t = ChainIdMismatchPolicy::Reject
+    )]
+    pub forward_chain_id_mismatch: ChainIdMismatchPolicy,
+
+    /// Reject legacy transactions without a chain id (pre-EIP-155) before forwarding them. They
+    /// are forwarded by default.
+    #[arg(long = "forward.reject-pre-eip155", env = "FORWARD_REJECT_PRE_EIP155")]
+    pub forward_reject_pre_eip155: bool,
+
+    /// JSON file of rules transactions must pass to be forwarded: `maxGasLimit`,
+    /// `minMaxFeePerGas` and `blockedAddresses`. Transactions breaking a rule are rejected.
+    #[arg(long = "forward.rules", env = "FORWARD_RULES", value_name = "FILE")]
+    pub forward_rules: Option<PathBuf>,
+
+    /// Seconds a forwarded transaction has to be included before
+    /// `hl_getForwardedTransactionStatus` reports it as expired.
+    #[arg(
+        long = "forward.status-ttl",
+        env = "FORWARD_STATUS_TTL",
+        default_value_t = DEFAULT_TRACKING_TTL.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub forward_status_ttl: u64,
+
+    /// Number of forwarded transactions whose status is tracked; the oldest are forgotten first.
+    #[arg(
+        long = "forward.status-capacity",
+        env = "FORWARD_STATUS_CAPACITY",
+        default_value_t = DEFAULT_TRACKED_TXS
+    )]
+    pub forward_status_capacity: usize,
+
+    /// Experimental: enables the eth_getProof RPC method for all blocks.
     ///
     /// Note: Due to the state root difference, trie updates* may not function correctly in all
     /// scenarios. For example, incremental root updates are not possible, which can cause
@@ -73,16 +184,24 @@ pub struct HlNodeArgs {
     ///
     /// This limitation does not impact normal node functionality, except for state root (which is
     /// unused) and eth_getProof. The archival state is maintained by block order, not by trie
-    /// updates. As a precaution, nanoreth disables eth_getProof by default to prevent
-    /// potential issues.
+    /// updates. As a precaution, nanoreth only serves eth_getProof for the latest blocks (see
+    /// --eth-get-proof-window).
     ///
-    /// Use --experimental-eth-get-proof to forcibly enable eth_getProof, assuming trie updates are
-    /// working as intended. Enabling this by default will be tracked in #15.
+    /// Use --experimental-eth-get-proof to serve eth_getProof for any block within
+    /// --rpc.eth-proof-window, assuming trie updates are working as intended. Enabling this by
+    /// default will be tracked in #15.
     ///
     /// * Refers to the Merkle trie used for eth_getProof and state root, not actual state values.
     #[arg(long, env = "EXPERIMENTAL_ETH_GET_PROOF")]
     pub experimental_eth_get_proof: bool,
 
+    /// Number of blocks behind the tip for which eth_getProof is served.
+    ///
+    /// Requests for older blocks are rejected. With --experimental-eth-get-proof, the window of
+    /// --rpc.eth-proof-window applies instead.
+    #[arg(long, env = "ETH_GET_PROOF_WINDOW", default_value_t = DEFAULT_ETH_GET_PROOF_WINDOW)]
+    pub eth_get_proof_window: u64,
+
     /// Allow network configuration overrides from CLI.
     ///
     /// When enabled, network settings (discovery_addr, listener_addr, dns_discovery, nat)
@@ -90,12 +209,349 @@ pub struct HlNodeArgs {
     #[arg(long, env = "ALLOW_NETWORK_OVERRIDES")]
     pub allow_network_overrides: bool,
 
+    /// Secret key of the pseudo peer, generated on first use; defaults to `pseudo-peer-secret`
+    /// under the datadir. Keeps the pseudo peer's enode stable across restarts, so that it can
+    /// be pinned as a trusted peer.
+    #[arg(long, value_name = "PATH", env = "PSEUDO_PEER_KEY")]
+    pub pseudo_peer_key: Option<PathBuf>,
+
+    /// Saves the in-memory block cache to `block-cache.rmp.lz4` under the datadir on shutdown,
+    /// and warms the cache from it on startup, so that a restarted node serves the blocks it
+    /// served before without fetching them again.
+    #[arg(long, env = "PERSIST_BLOCK_CACHE")]
+    pub persist_block_cache: bool,
+
+    /// Largest size of the blocks saved by --persist-block-cache, in MiB. The most recently used
+    /// blocks are kept.
+    #[arg(
+        long,
+        default_value_t = DEFAULT_SNAPSHOT_MAX_BYTES >> 20,
+        requires = "persist_block_cache",
+        value_parser = clap::value_parser!(u64).range(1..).map(|mb| mb as usize)
+    )]
+    pub block_cache_snapshot_max_mb: usize,
+
+    /// Most transactions in a block announced by a peer; messages with larger blocks are dropped
+    /// before they are decoded.
+    #[arg(
+        long = "network.max-block-transactions",
+        env = "NETWORK_MAX_BLOCK_TRANSACTIONS",
+        default_value_t = NewBlockLimits::DEFAULT.max_transactions
+    )]
+    pub network_max_block_transactions: usize,
+
+    /// Largest encoding of the read precompile calls of a block announced by a peer, in bytes.
+    #[arg(
+        long = "network.max-precompile-calls-bytes",
+        env = "NETWORK_MAX_PRECOMPILE_CALLS_BYTES",
+        default_value_t = NewBlockLimits::DEFAULT.max_precompile_calls_bytes
+    )]
+    pub network_max_precompile_calls_bytes: usize,
+
+    /// Most blob sidecars in a block announced by a peer.
+    #[arg(
+        long = "network.max-blob-sidecars",
+        env = "NETWORK_MAX_BLOB_SIDECARS",
+        default_value_t = NewBlockLimits::DEFAULT.max_sidecars
+    )]
+    pub network_max_blob_sidecars: usize,
+
     /// Enable the sync server RPC endpoints (hl_syncGetBlock, hl_syncLatestBlockNumber).
     ///
     /// When enabled, this node can serve blocks to other nanoreth nodes
     /// that use --block-source=rpc://... to sync from this node.
     #[arg(long, env = "ENABLE_SYNC_SERVER")]
     pub enable_sync_server: bool,
+
+    /// Maximum compressed size of a single hl_syncGetBlocks response, in bytes.
+    ///
+    /// Larger responses are truncated and the client requests the remaining blocks separately.
+    #[arg(
+        long,
+        env = "SYNC_SERVER_MAX_RESPONSE_BYTES",
+        default_value_t = DEFAULT_MAX_RESPONSE_BYTES
+    )]
+    pub sync_server_max_response_bytes: usize,
+
+    /// Number of recently served blocks the sync server keeps serialized in memory.
+    ///
+    /// Set to 0 to disable the cache.
+    #[arg(
+        long,
+        env = "SYNC_SERVER_PAYLOAD_CACHE_SIZE",
+        default_value_t = DEFAULT_PAYLOAD_CACHE_SIZE
+    )]
+    pub sync_server_payload_cache_size: u32,
+
+    /// Number of blocks this node may trail its own block source by while still reporting
+    /// itself as ready to the nodes syncing from it.
+    #[arg(long, env = "SYNC_SERVER_MAX_READY_LAG", default_value_t = DEFAULT_MAX_READY_LAG)]
+    pub sync_server_max_ready_lag: u64,
+
+    /// Report hl_syncLatestBlockNumber as a plain block number, for nodes that predate the
+    /// structured sync status.
+    #[arg(long, env = "SYNC_SERVER_LEGACY_LATEST_BLOCK_NUMBER")]
+    pub sync_server_legacy_latest_block_number: bool,
+
+    /// Number of blocks below this node's synced height that are withheld from the nodes syncing
+    /// from it, so that blocks near the tip which may still reorg are not served.
+    #[arg(long, alias = "sync-serve-lag", env = "SYNC_SERVER_SERVE_LAG", default_value_t = 0)]
+    pub sync_server_serve_lag: u64,
+
+    /// Serve the sync server on this Unix domain socket instead of the RPC servers, for nodes
+    /// syncing from this one on the same host with --block-source=unix://PATH.
+    #[arg(long, value_name = "PATH", env = "SYNC_SERVER_UDS", requires = "enable_sync_server")]
+    pub sync_server_uds: Option<PathBuf>,
+
+    /// Serve the sync server on its own HTTP and WebSocket listener at this address instead of
+    /// the RPC servers.
+    ///
+    /// The RPC servers don't tell the sync server who is calling, so its per-client limits only
+    /// apply per connection there. On its own listener, they apply per client IP.
+    #[arg(
+        long,
+        value_name = "ADDR",
+        env = "SYNC_SERVER_ADDR",
+        requires = "enable_sync_server",
+        conflicts_with = "sync_server_uds"
+    )]
+    pub sync_server_addr: Option<SocketAddr>,
+
+    /// Datadir of a read-only replica of this node's database, e.g. on another disk, that serves
+    /// every other block read of the sync server to spread the load.
+    #[arg(long, env = "SYNC_REPLICA_DATADIR", requires = "enable_sync_server")]
+    pub sync_replica_datadir: Option<PathBuf>,
+
+    /// Number of blocks the replica may trail this node by before blocks are only served from
+    /// this node's database.
+    #[arg(
+        long,
+        env = "SYNC_REPLICA_MAX_LAG",
+        default_value_t = DEFAULT_REPLICA_MAX_LAG,
+        requires = "sync_replica_datadir"
+    )]
+    pub sync_replica_max_lag: u64,
+
+    /// Re-execute every Nth imported block in the background and compare receipts, gas used
+    /// and logs bloom with the imported block. 0 disables the check.
+    #[arg(long, env = "REPLAY_CHECK_INTERVAL", default_value_t = 0)]
+    pub replay_check_interval: u64,
+
+    /// Shut the node down when a re-executed block diverges, instead of only logging an error.
+    #[arg(long, env = "HALT_ON_DIVERGENCE", requires = "replay_check_interval")]
+    pub halt_on_divergence: bool,
+
+    /// Serve hl_getBlockStateDiff, which re-executes a block to return the state changes of its
+    /// system and user transactions. Each uncached request costs as much as importing the block.
+    #[arg(long, env = "ENABLE_STATE_DIFF_RPC")]
+    pub enable_state_diff_rpc: bool,
+
+    /// Log a status line of the import pipeline and its block source every N seconds: imported
+    /// height, source height and lag, import rate, cache hit rate and active source. 0 disables
+    /// the status line.
+    #[arg(long, env = "STATUS_LOG_INTERVAL", default_value_t = DEFAULT_STATUS_LOG_INTERVAL)]
+    pub status_log_interval: u64,
+
+    /// Start without checking the block source, upstream RPC and datadir first. By default the
+    /// launch is aborted with a report of every failed check.
+    #[arg(long, env = "SKIP_PREFLIGHT")]
+    pub skip_preflight: bool,
+
+    /// Also check before launching that spot metadata can be fetched from the Hyperliquid API.
+    #[arg(long = "preflight.spot-meta", env = "PREFLIGHT_SPOT_META")]
+    pub preflight_spot_meta: bool,
+
+    /// Number of blocks the safe block trails the imported head by, as reported by the `safe`
+    /// block tag.
+    #[arg(
+        long = "forkchoice.safe-depth",
+        env = "FORKCHOICE_SAFE_DEPTH",
+        default_value_t = DEFAULT_SAFE_DEPTH
+    )]
+    pub forkchoice_safe_depth: u64,
+
+    /// Number of blocks the finalized block trails the imported head by, as reported by the
+    /// `finalized` block tag. Must be at least --forkchoice.safe-depth.
+    ///
+    /// Defaults to the head itself: blocks are final once HyperBFT commits them.
+    #[arg(
+        long = "forkchoice.finalized-depth",
+        env = "FORKCHOICE_FINALIZED_DEPTH",
+        default_value_t = DEFAULT_FINALIZED_DEPTH
+    )]
+    pub forkchoice_finalized_depth: u64,
+
+    /// Re-send the forkchoice update of the current head every N seconds, so that the safe and
+    /// finalized blocks are set after a restart and while no new block arrives. 0 disables it.
+    #[arg(
+        long = "forkchoice.interval",
+        env = "FORKCHOICE_INTERVAL",
+        default_value_t = DEFAULT_FORKCHOICE_INTERVAL
+    )]
+    pub forkchoice_interval: u64,
+
+    /// Seconds the engine has to answer a forkchoice update. An update that isn't answered in
+    /// time is logged and reported as failed by hl_engineStatus instead of holding up imports.
+    #[arg(
+        long = "forkchoice.timeout",
+        env = "FORKCHOICE_TIMEOUT",
+        default_value_t = DEFAULT_FORKCHOICE_TIMEOUT,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub forkchoice_timeout: u64,
+
+    /// Import blocks whose read precompile calls are inconsistent (e.g. gas used above the gas
+    /// limit) with a warning, instead of rejecting them. For replaying historical data as
+    /// recorded.
+    #[arg(long, env = "TOLERATE_INVALID_PRECOMPILE_CALLS")]
+    pub tolerate_invalid_precompile_calls: bool,
+
+    /// Age in seconds past which a block is historical: its inconsistent read precompile calls
+    /// are imported with a warning even without --tolerate-invalid-precompile-calls, so that a
+    /// corrupted archive entry doesn't halt a sync. 0 rejects them at any age.
+    #[arg(
+        long,
+        env = "HISTORICAL_BLOCK_AGE",
+        default_value_t = DEFAULT_HISTORICAL_BLOCK_AGE.as_secs()
+    )]
+    pub historical_block_age: u64,
+
+    /// Import blocks whose transaction hashes collide, within the block or with transactions
+    /// already indexed elsewhere, with a warning, instead of rejecting them.
+    #[arg(long, env = "TOLERATE_DUPLICATE_TX_HASHES")]
+    pub tolerate_duplicate_tx_hashes: bool,
+
+    /// Keep full data for the latest N blocks only: read precompile calls and receipts of older
+    /// blocks are pruned as the chain grows, and requests for them are rejected.
+    ///
+    /// Replaces configuring reth's --prune.* options for each segment.
+    #[arg(
+        long,
+        env = "ARCHIVE_WINDOW",
+        value_parser = clap::value_parser!(u64).range(MINIMUM_ARCHIVE_WINDOW..)
+    )]
+    pub archive_window: Option<u64>,
+
+    /// Prune state history outside of --archive-window too, so that historical state is only
+    /// available within the window.
+    #[arg(
+        long = "archive-window.state",
+        env = "ARCHIVE_WINDOW_STATE",
+        requires = "archive_window"
+    )]
+    pub archive_window_state: bool,
+
+    /// Attempts at persisting fetched spot metadata to the database before giving up until
+    /// shutdown, which writes it again.
+    #[arg(
+        long = "spot-meta.persist-attempts",
+        env = "SPOT_META_PERSIST_ATTEMPTS",
+        default_value_t = SPOT_META_PERSIST_ATTEMPTS,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub spot_meta_persist_attempts: u32,
+
+    /// Milliseconds to wait before retrying a failed spot metadata persist, doubled for each
+    /// later attempt.
+    #[arg(
+        long = "spot-meta.persist-backoff-ms",
+        env = "SPOT_META_PERSIST_BACKOFF_MS",
+        default_value_t = SPOT_META_PERSIST_BACKOFF.as_millis() as u64
+    )]
+    pub spot_meta_persist_backoff_ms: u64,
+
+    #[command(flatten)]
+    pub sync_server_limits: SyncServerLimits,
+
+    #[command(flatten)]
+    pub rpc_rate_limits: RpcRateLimits,
+
+    /// Timeout of the debug_trace* calls replaying blocks that don't set the `timeout` tracer
+    /// option, as a duration such as 30s or 2m. Traces are unlimited by default.
+    ///
+    /// A trace past its timeout fails and releases its tracing permit for other requests.
+    #[arg(long = "rpc.trace-timeout", env = "RPC_TRACE_TIMEOUT", value_parser = parse_timeout)]
+    pub rpc_trace_timeout: Option<Duration>,
+}
+
+impl HlNodeArgs {
+    /// The safe and finalized blocks of forkchoice updates configured by --forkchoice.*.
+    pub fn forkchoice_policy(&self) -> eyre::Result<ForkchoicePolicy> {
+        ForkchoicePolicy::new(
+            self.forkchoice_safe_depth,
+            self.forkchoice_finalized_depth,
+            (self.forkchoice_interval > 0).then(|| Duration::from_secs(self.forkchoice_interval)),
+        )
+        .map(|policy| policy.with_timeout(Duration::from_secs(self.forkchoice_timeout)))
+    }
+
+    /// The methods forwarded to the upstream RPC, configured by --forward-call and --forward.*.
+    pub fn forwarding_policy(&self) -> eyre::Result<ForwardingPolicy> {
+        let mut allow = self.forward_allow.clone();
+        if self.forward_call {
+            allow.extend([ForwardedMethod::Call, ForwardedMethod::EstimateGas]);
+        }
+        ForwardingPolicy::new(&allow, &self.forward_deny)
+    }
+
+    /// The policy deciding which transactions are forwarded, configured by --forward.rules.
+    pub fn tx_forward_policy(&self) -> eyre::Result<Arc<dyn TxForwardPolicy>> {
+        Ok(match &self.forward_rules {
+            Some(path) => Arc::new(RuleBasedPolicy::load(path)?),
+            None => Arc::new(ForwardAll),
+        })
+    }
+
+    /// The tracker of forwarded transactions configured by --forward.status-*.
+    pub fn forwarded_tx_tracker(&self) -> ForwardedTxTracker {
+        ForwardedTxTracker::new(
+            self.forward_status_capacity,
+            Duration::from_secs(self.forward_status_ttl),
+        )
+    }
+
+    /// The bounds on blocks announced by peers configured by --network.max-*.
+    pub fn new_block_limits(&self) -> NewBlockLimits {
+        NewBlockLimits {
+            max_transactions: self.network_max_block_transactions,
+            max_precompile_calls_bytes: self.network_max_precompile_calls_bytes,
+            max_sidecars: self.network_max_blob_sidecars,
+        }
+    }
+
+    /// Attempts at persisting spot metadata and the backoff after the first failure, configured
+    /// by --spot-meta.*.
+    pub fn spot_meta_persist_retries(&self) -> (u32, Duration) {
+        (self.spot_meta_persist_attempts, Duration::from_millis(self.spot_meta_persist_backoff_ms))
+    }
+
+    /// The block cache snapshot under `data_dir` enabled by --persist-block-cache, if any.
+    pub fn block_cache_snapshot(&self, data_dir: &Path) -> Option<BlockCacheSnapshot> {
+        self.persist_block_cache.then(|| {
+            BlockCacheSnapshot::new(
+                data_dir.join(BLOCK_CACHE_SNAPSHOT_FILE),
+                self.block_cache_snapshot_max_mb << 20,
+            )
+        })
+    }
+
+    /// The archive window configured by --archive-window, if any.
+    pub fn archive_window(&self) -> Option<ArchiveWindow> {
+        self.archive_window
+            .map(|blocks| ArchiveWindow { blocks, prune_state: self.archive_window_state })
+    }
+
+    /// Age past which inconsistent read precompile calls are tolerated, configured by
+    /// --historical-block-age.
+    pub fn historical_block_age(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.historical_block_age)).filter(|age| !age.is_zero())
+    }
+
+    /// Whether blocks list their system transactions, configured by --expose-system-txs and
+    /// --hl-node-compliant.
+    pub fn exposes_system_txs(&self) -> bool {
+        self.expose_system_txs.unwrap_or(!self.hl_node_compliant)
+    }
 }
 
 /// The main reth_hl cli interface.
@@ -107,10 +563,87 @@ pub struct Cli<Spec: ChainSpecParser = HlChainSpecParser, Ext: clap::Args + fmt:
 {
     /// The command to run
     #[command(subcommand)]
-    pub command: Commands<Spec, Ext>,
+    pub command: HlCommands<Spec, Ext>,
 
     #[command(flatten)]
     logs: LogArgs,
+
+    #[cfg(feature = "otel")]
+    #[command(flatten)]
+    otlp: OtlpArgs,
+
+    /// When migrating the database (`CHECK_DB_MIGRATION`), leave headers in mdbx that can't be
+    /// decoded as they are instead of aborting, and list them in `corrupt-headers.txt` in the data
+    /// directory. A corrupt header in static files always aborts the migration.
+    #[arg(long, global = true)]
+    skip_corrupt_headers: bool,
+
+    /// When migrating the database, recompute the transactions root of each migrated header from
+    /// its block body and list mismatches in `tx-root-mismatches.txt` in the data directory.
+    #[arg(long, global = true)]
+    verify_tx_roots: bool,
+
+    /// With `--verify-tx-roots`, write the recomputed root into mismatching headers, which are
+    /// rehashed. The children of repaired headers are reported, as their parent hash still names
+    /// the old hash.
+    #[arg(long = "verify-tx-roots.repair", global = true, requires = "verify_tx_roots")]
+    repair_tx_roots: bool,
+
+    /// When migrating the database, count the system transactions of old headers from their
+    /// receipts or their transactions. Without it, a block for which the two counts disagree
+    /// aborts the migration.
+    #[arg(long, env = "MIGRATE_SYSTEM_TX_COUNT_SOURCE", global = true, value_enum)]
+    system_tx_count_source: Option<SystemTxCountSource>,
+}
+
+/// All commands of the reth_hl cli: reth's [`Commands`] plus HL-specific ones.
+#[derive(Debug, Subcommand)]
+pub enum HlCommands<C: ChainSpecParser, Ext: clap::Args + fmt::Debug> {
+    #[command(flatten)]
+    Reth(Commands<C, Ext>),
+    /// Compare stored blocks against the configured block source.
+    #[command(name = "audit")]
+    Audit(AuditCommand<C>),
+    /// Recompute the state root at a block and verify account proofs against the header.
+    #[command(name = "audit-state-root")]
+    AuditStateRoot(AuditStateRootCommand<C>),
+    /// Backfill HL indexes, such as the system transaction hash index, for existing blocks.
+    #[command(name = "backfill")]
+    Backfill(BackfillCommand<C>),
+    /// Export the stored read precompile calls of a block range as CSV.
+    #[command(name = "export-precompile-calls")]
+    ExportPrecompileCalls(ExportPrecompileCallsCommand<C>),
+    /// Verify the stored highest precompile address of blocks against their read precompile
+    /// calls.
+    #[command(name = "verify-precompile-addresses")]
+    VerifyPrecompileAddresses(VerifyPrecompileAddressesCommand<C>),
+    /// Stream stored blocks to stdout as newline-delimited JSON, in the hl-node file format.
+    #[command(name = "stream-blocks")]
+    StreamBlocks(StreamBlocksCommand<C>),
+    /// Decode a single `.rmp.lz4` or `.rmp` block file and print its blocks.
+    #[command(name = "decode-block")]
+    DecodeBlock(DecodeBlockCommand),
+    /// Run the node importing the hl-node block lines piped to stdin, and exit once they are
+    /// imported.
+    #[command(name = "import-stdin")]
+    ImportStdin(Box<NodeCommand<C, ImportStdinArgs>>),
+}
+
+impl<C: ChainSpecParser, Ext: clap::Args + fmt::Debug> HlCommands<C, Ext> {
+    /// Returns the underlying chain being used for commands
+    pub fn chain_spec(&self) -> Option<&Arc<C::ChainSpec>> {
+        match self {
+            Self::Reth(command) => command.chain_spec(),
+            Self::Audit(command) => Some(&command.env.chain),
+            Self::AuditStateRoot(command) => Some(&command.env.chain),
+            Self::Backfill(command) => Some(&command.env.chain),
+            Self::ExportPrecompileCalls(command) => Some(&command.env.chain),
+            Self::VerifyPrecompileAddresses(command) => Some(&command.env.chain),
+            Self::StreamBlocks(command) => Some(&command.env.chain),
+            Self::DecodeBlock(_) => None,
+            Self::ImportStdin(command) => Some(&command.chain),
+        }
+    }
 }
 
 impl<C, Ext> Cli<C, Ext>
@@ -147,26 +680,93 @@ where
                 self.logs.log_file_directory.join(chain_spec.chain().to_string());
         }
 
-        let _guard = self.init_tracing()?;
+        #[cfg(feature = "otel")]
+        let mut otlp =
+            OtlpExport::new(&self.otlp, self.command.chain_spec().map(|spec| spec.chain().id()))?;
+        #[cfg_attr(not(feature = "otel"), allow(unused_mut))]
+        let mut layers = Layers::new();
+        #[cfg(feature = "otel")]
+        if let Some(otlp) = &otlp {
+            layers.add_layer(otlp.tracing_layer()?);
+        }
+        let _guard = if matches!(self.command, HlCommands::StreamBlocks(_)) {
+            // stdout carries the streamed blocks
+            self.init_stderr_tracing()?;
+            None
+        } else {
+            self.logs.init_tracing_with_layers(layers)?
+        };
         info!(target: "reth::cli", "Initialized tracing, debug log directory: {}", self.logs.log_file_directory);
 
         // Install the prometheus recorder to be sure to record all metrics
         let _ = install_prometheus_recorder();
+        #[cfg(feature = "otel")]
+        if let Some(otlp) = &mut otlp {
+            let handle = install_prometheus_recorder().handle().clone();
+            otlp.bridge_metrics(move || handle.render());
+            info!(target: "reth::cli", "Exporting traces and HL metrics over OTLP");
+        }
 
         let components = |spec: Arc<C::ChainSpec>| {
             (HlEvmConfig::new(spec.clone()), Arc::new(HlConsensus::new(spec)))
         };
 
-        match self.command {
-            Commands::Node(command) => runner.run_command_until_exit(|ctx| {
+        let skip_corrupt_headers = self.skip_corrupt_headers;
+        let tx_root_check = match (self.verify_tx_roots, self.repair_tx_roots) {
+            (_, true) => TxRootCheck::Repair,
+            (true, false) => TxRootCheck::Verify,
+            (false, false) => TxRootCheck::Off,
+        };
+        let system_tx_count_source = self.system_tx_count_source;
+        let command = match self.command {
+            HlCommands::Reth(command) => command,
+            HlCommands::Audit(command) => {
+                return runner.run_until_ctrl_c(command.execute::<HlNode>());
+            }
+            HlCommands::AuditStateRoot(command) => {
+                return runner.run_blocking_until_ctrl_c(command.execute::<HlNode>());
+            }
+            HlCommands::Backfill(command) => {
+                // Creates the HL tables missing from databases of older versions
+                Self::init_db(&command.env)?;
+                return runner.run_blocking_until_ctrl_c(command.execute::<HlNode>());
+            }
+            HlCommands::ExportPrecompileCalls(command) => {
+                return runner.run_blocking_until_ctrl_c(command.execute::<HlNode>());
+            }
+            HlCommands::VerifyPrecompileAddresses(command) => {
+                return runner.run_blocking_until_ctrl_c(command.execute::<HlNode>());
+            }
+            HlCommands::StreamBlocks(command) => {
+                return runner.run_blocking_until_ctrl_c(command.execute::<HlNode>());
+            }
+            HlCommands::DecodeBlock(command) => return command.execute(),
+            HlCommands::ImportStdin(mut command) => {
+                command.ext.apply_batch_size(&mut command.engine);
+                return runner.run_command_until_exit(|ctx| {
+                    command.execute(ctx, FnLauncher::new::<C, ImportStdinArgs>(import_stdin))
+                });
+            }
+        };
+
+        match command {
+            Commands::Node(command) => {
                 // NOTE: This is for one time migration around Oct 10 upgrade:
                 // It's not necessary anymore, an environment variable gate is added here.
                 if std::env::var("CHECK_DB_MIGRATION").is_ok() {
-                    Self::migrate_db(&command.chain, &command.datadir, &command.db)
-                        .expect("Failed to migrate database");
+                    Self::migrate_db(
+                        &command.chain,
+                        &command.datadir,
+                        &command.db,
+                        skip_corrupt_headers,
+                        tx_root_check,
+                        system_tx_count_source,
+                    )?;
                 }
-                command.execute(ctx, FnLauncher::new::<C, Ext>(launcher))
-            }),
+                runner.run_command_until_exit(|ctx| {
+                    command.execute(ctx, FnLauncher::new::<C, Ext>(launcher))
+                })
+            }
             Commands::Init(command) => {
                 runner.run_blocking_until_ctrl_c(command.execute::<HlNode>())
             }
@@ -204,6 +804,28 @@ where
         Ok(guard)
     }
 
+    /// Initializes tracing to stderr only, with the verbosity and filter of the stdout logs, for
+    /// commands writing their output to stdout.
+    fn init_stderr_tracing(&self) -> eyre::Result<()> {
+        let filter = EnvFilter::builder()
+            .with_default_directive(self.logs.verbosity.directive())
+            .from_env_lossy();
+        let filter = self
+            .logs
+            .log_stdout_filter
+            .split(',')
+            .filter(|directive| !directive.is_empty())
+            .try_fold(filter, |filter, directive| {
+                eyre::Ok(filter.add_directive(directive.parse()?))
+            })?;
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_ansi(std::io::stderr().is_terminal())
+            .with_filter(filter);
+        tracing_subscriber::registry().with(layer).try_init()?;
+        Ok(())
+    }
+
     fn init_db(env: &EnvironmentArgs<C>) -> eyre::Result<()> {
         let data_dir = env.datadir.clone().resolve_datadir(env.chain.chain());
         let db_path = data_dir.db();
@@ -221,8 +843,19 @@ where
         chain: &HlChainSpec,
         datadir: &DatadirArgs,
         db: &DatabaseArgs,
+        skip_corrupt_headers: bool,
+        tx_root_check: TxRootCheck,
+        system_tx_count_source: Option<SystemTxCountSource>,
     ) -> eyre::Result<()> {
-        Migrator::<HlNode>::new(chain.clone(), datadir.clone(), *db)?.migrate_db()?;
+        Migrator::<HlNode>::new(
+            chain.clone(),
+            datadir.clone(),
+            *db,
+            skip_corrupt_headers,
+            tx_root_check,
+            system_tx_count_source,
+        )?
+        .migrate_db()?;
         Ok(())
     }
 }
@@ -0,0 +1,278 @@
+//! `audit` command: replays a block range from the block source and compares it with the DB.
+//!
+//! The block source is the authority here; every block is fetched as [`BlockAndReceipts`] and
+//! compared field by field with the stored block converted back via
+//! [`BlockAndReceipts::from_db`]. This catches silent storage corruption without re-executing.
+
+use crate::{
+    addons::sync_server::{ProviderSyncReader, SyncBlockReader},
+    chainspec::HlChainSpec,
+    node::types::{BlockAndReceipts, EvmBlock},
+    pseudo_peer::{BlockSource, BlockSourceArgs},
+};
+use alloy_primitives::{Address, B256};
+use clap::Parser;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, Environment, EnvironmentArgs};
+use std::{collections::HashSet, ops::RangeInclusive};
+use tracing::{info, warn};
+
+/// Compares stored blocks against the configured block source.
+#[derive(Debug, Parser)]
+pub struct AuditCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    pub env: EnvironmentArgs<C>,
+
+    #[command(flatten)]
+    pub block_source_args: BlockSourceArgs,
+
+    /// First block to audit (inclusive).
+    #[arg(long)]
+    pub from: u64,
+
+    /// Last block to audit (inclusive).
+    #[arg(long)]
+    pub to: u64,
+}
+
+/// A single field that differs between the source and the stored block.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum Divergence {
+    #[display("block hash: source {source}, stored {stored}")]
+    BlockHash { source: B256, stored: B256 },
+    #[display("header fields differ")]
+    Header,
+    #[display("transaction count: source {source}, stored {stored}")]
+    TransactionCount { source: usize, stored: usize },
+    #[display("transaction {_0} differs")]
+    Transaction(usize),
+    #[display("receipt count: source {source}, stored {stored}")]
+    ReceiptCount { source: usize, stored: usize },
+    #[display("receipt {_0} differs")]
+    Receipt(usize),
+    #[display("system transaction count: source {source}, stored {stored}")]
+    SystemTxCount { source: usize, stored: usize },
+    #[display("system transaction {_0} differs")]
+    SystemTx(usize),
+    #[display("system transaction receipt {_0} differs")]
+    SystemTxReceipt(usize),
+    #[display("withdrawals differ")]
+    Withdrawals,
+    #[display("read precompile calls differ")]
+    ReadPrecompileCalls,
+    #[display("highest precompile address: source {source:?}, stored {stored:?}")]
+    HighestPrecompileAddress { source: Option<Address>, stored: Option<Address> },
+}
+
+/// Compares a block fetched from the source with the stored one.
+///
+/// System transaction receipts are only compared when the source carries them, since older
+/// source files don't include them.
+pub fn diff_block_and_receipts(
+    source: &BlockAndReceipts,
+    stored: &BlockAndReceipts,
+) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    let (EvmBlock::Reth115(source_block), EvmBlock::Reth115(stored_block)) =
+        (&source.block, &stored.block);
+
+    if source.hash() != stored.hash() {
+        divergences.push(Divergence::BlockHash { source: source.hash(), stored: stored.hash() });
+    }
+    if source_block.header.header != stored_block.header.header {
+        divergences.push(Divergence::Header);
+    }
+
+    let (source_txs, stored_txs) =
+        (&source_block.body.transactions, &stored_block.body.transactions);
+    if source_txs.len() != stored_txs.len() {
+        divergences.push(Divergence::TransactionCount {
+            source: source_txs.len(),
+            stored: stored_txs.len(),
+        });
+    }
+    divergences.extend(
+        source_txs
+            .iter()
+            .zip(stored_txs)
+            .enumerate()
+            .filter(|(_, (source, stored))| source != stored)
+            .map(|(index, _)| Divergence::Transaction(index)),
+    );
+
+    if source.receipts.len() != stored.receipts.len() {
+        divergences.push(Divergence::ReceiptCount {
+            source: source.receipts.len(),
+            stored: stored.receipts.len(),
+        });
+    }
+    divergences.extend(
+        source
+            .receipts
+            .iter()
+            .zip(&stored.receipts)
+            .enumerate()
+            .filter(|(_, (source, stored))| source != stored)
+            .map(|(index, _)| Divergence::Receipt(index)),
+    );
+
+    if source.system_txs.len() != stored.system_txs.len() {
+        divergences.push(Divergence::SystemTxCount {
+            source: source.system_txs.len(),
+            stored: stored.system_txs.len(),
+        });
+    }
+    for (index, (source, stored)) in source.system_txs.iter().zip(&stored.system_txs).enumerate() {
+        if source.tx != stored.tx {
+            divergences.push(Divergence::SystemTx(index));
+        }
+        if source.receipt.is_some() && source.receipt != stored.receipt {
+            divergences.push(Divergence::SystemTxReceipt(index));
+        }
+    }
+
+    if source_block.body.withdrawals != stored_block.body.withdrawals {
+        divergences.push(Divergence::Withdrawals);
+    }
+    if source.read_precompile_calls != stored.read_precompile_calls {
+        divergences.push(Divergence::ReadPrecompileCalls);
+    }
+    if source.highest_precompile_address != stored.highest_precompile_address {
+        divergences.push(Divergence::HighestPrecompileAddress {
+            source: source.highest_precompile_address,
+            stored: stored.highest_precompile_address,
+        });
+    }
+
+    divergences
+}
+
+/// Heights of `requested` that aren't among `returned`.
+fn missing_heights(requested: RangeInclusive<u64>, returned: &[BlockAndReceipts]) -> Vec<u64> {
+    let returned = returned.iter().map(BlockAndReceipts::number).collect::<HashSet<_>>();
+    requested.filter(|number| !returned.contains(number)).collect()
+}
+
+impl<C: ChainSpecParser<ChainSpec = HlChainSpec>> AuditCommand<C> {
+    pub async fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec, Primitives = crate::HlPrimitives>,
+    {
+        eyre::ensure!(self.from <= self.to, "--from must not be greater than --to");
+
+        let Environment { provider_factory, .. } = self.env.init::<N>(AccessRights::RO)?;
+        let config = self
+            .block_source_args
+            .parse()
+            .await?
+            .ok_or_else(|| eyre::eyre!("audit requires a block source (e.g. --s3)"))?;
+        let block_source = config.create_block_source((*self.env.chain).clone()).await?;
+        let reader = ProviderSyncReader::new(provider_factory);
+
+        let chunk_size = block_source.recommended_chunk_size().max(1);
+        let (mut audited, mut diverged, mut missing) = (0u64, 0u64, 0u64);
+        let mut start = self.from;
+        while start <= self.to {
+            let end = start.saturating_add(chunk_size - 1).min(self.to);
+            let source_blocks = block_source.collect_blocks((start..=end).collect()).await?;
+            for number in missing_heights(start..=end, &source_blocks) {
+                warn!(number, "Block missing from source");
+                missing += 1;
+            }
+            for source_block in source_blocks {
+                let number = source_block.number();
+                audited += 1;
+                let stored_block = match reader.read_block_and_receipts(number) {
+                    Ok(block) => block,
+                    Err(err) => {
+                        warn!(number, %err, "Failed to read stored block");
+                        diverged += 1;
+                        continue;
+                    }
+                };
+
+                let divergences = diff_block_and_receipts(&source_block, &stored_block);
+                if !divergences.is_empty() {
+                    diverged += 1;
+                    for divergence in divergences {
+                        warn!(number, "Divergence: {divergence}");
+                    }
+                }
+            }
+            info!(audited, diverged, missing, "Audited blocks {start}..={end}");
+            start = end + 1;
+        }
+
+        eyre::ensure!(diverged == 0, "{diverged} of {audited} audited blocks diverge from source");
+        eyre::ensure!(missing == 0, "{missing} blocks of the range are missing from source");
+        info!(audited, "All audited blocks match the block source");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        node::types::{ReadPrecompileCalls, ReadPrecompileInput, ReadPrecompileResult},
+        test_utils::sealed_block,
+    };
+    use alloy_consensus::{Header, TxType};
+    use alloy_primitives::{Bytes, address};
+    use reth_ethereum_primitives::EthereumReceipt;
+
+    fn block(number: u64) -> BlockAndReceipts {
+        let header = Header { number, gas_used: 21_000, ..Default::default() };
+        BlockAndReceipts {
+            receipts: vec![
+                EthereumReceipt {
+                    tx_type: TxType::Legacy,
+                    success: true,
+                    cumulative_gas_used: 21_000,
+                    logs: vec![],
+                }
+                .into(),
+            ],
+            read_precompile_calls: ReadPrecompileCalls(vec![(
+                address!("0x0000000000000000000000000000000000000801"),
+                vec![(
+                    ReadPrecompileInput { input: Bytes::from_static(&[1]), gas_limit: 100 },
+                    ReadPrecompileResult::Ok { gas_used: 10, bytes: Bytes::from_static(&[2]) },
+                )],
+            )]),
+            ..sealed_block(header, B256::with_last_byte(number as u8))
+        }
+    }
+
+    #[test]
+    fn identical_blocks_do_not_diverge() {
+        assert!(diff_block_and_receipts(&block(1), &block(1)).is_empty());
+    }
+
+    #[test]
+    fn altered_stored_block_is_flagged() {
+        let source = block(1);
+        let mut stored = block(1);
+        stored.receipts[0] = EthereumReceipt {
+            tx_type: TxType::Legacy,
+            success: false,
+            cumulative_gas_used: 21_000,
+            logs: vec![],
+        }
+        .into();
+        stored.read_precompile_calls = ReadPrecompileCalls::default();
+        let EvmBlock::Reth115(block) = &mut stored.block;
+        block.header.header.gas_used = 0;
+
+        assert_eq!(
+            diff_block_and_receipts(&source, &stored),
+            vec![Divergence::Header, Divergence::Receipt(0), Divergence::ReadPrecompileCalls]
+        );
+    }
+
+    #[test]
+    fn heights_the_source_skipped_are_missing() {
+        assert_eq!(missing_heights(1..=4, &[block(1), block(2), block(3), block(4)]), vec![]);
+        assert_eq!(missing_heights(1..=5, &[block(4), block(2)]), vec![1, 3, 5]);
+    }
+}
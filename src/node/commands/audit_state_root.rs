@@ -0,0 +1,264 @@
+//! `audit-state-root` command: measures how far the trie has diverged from the flat state.
+//!
+//! The archival state is maintained by block order, not by trie updates, so the trie used for
+//! `eth_getProof` may drift. This command recomputes the state root at a block and compares it
+//! with the header, and optionally verifies account proofs end-to-end against the header root.
+
+use crate::chainspec::HlChainSpec;
+use alloy_consensus::BlockHeader;
+use alloy_primitives::{Address, B256};
+use clap::Parser;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, Environment, EnvironmentArgs};
+use reth_db::transaction::DbTx;
+use reth_provider::{
+    BlockNumReader, DBProvider, HeaderProvider, StateProofProvider, StateProviderFactory,
+    StateRootProvider,
+};
+use reth_trie::{
+    AccountProof, HashedPostState, StateRoot, proof::Proof,
+    trie_cursor::noop::NoopTrieCursorFactory,
+};
+use reth_trie_db::{DatabaseHashedCursorFactory, DatabaseProof};
+use std::{path::PathBuf, str::FromStr, time::Instant};
+use tracing::{info, warn};
+
+/// Recomputes the state root at a block and compares it with the header's state root.
+#[derive(Debug, Parser)]
+pub struct AuditStateRootCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    pub env: EnvironmentArgs<C>,
+
+    /// Block to audit. Defaults to the latest executed block.
+    ///
+    /// For the latest block the root is recomputed from scratch from the hashed state. For
+    /// historical blocks, the trie is reverted to the block instead.
+    #[arg(long)]
+    pub block: Option<u64>,
+
+    /// File with accounts whose proofs are verified against the header's state root.
+    ///
+    /// One account per line, optionally followed by whitespace-separated storage slots. Empty
+    /// lines and lines starting with `#` are ignored.
+    #[arg(long)]
+    pub accounts_file: Option<PathBuf>,
+}
+
+/// An account and the storage slots to prove, as read from `--accounts-file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountToProve {
+    pub address: Address,
+    pub slots: Vec<B256>,
+}
+
+/// Parses the contents of an `--accounts-file`.
+pub fn parse_accounts_file(contents: &str) -> eyre::Result<Vec<AccountToProve>> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let mut parts = line.split_whitespace();
+            let address = parts.next().expect("line is not empty");
+            let address = Address::from_str(address)
+                .map_err(|e| eyre::eyre!("line {line_number}: invalid address {address}: {e}"))?;
+            let slots = parts
+                .map(|slot| {
+                    B256::from_str(slot)
+                        .map_err(|e| eyre::eyre!("line {line_number}: invalid slot {slot}: {e}"))
+                })
+                .collect::<eyre::Result<_>>()?;
+            Ok(AccountToProve { address, slots })
+        })
+        .collect()
+}
+
+/// Outcome of the audit of the trie at a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateRootAudit {
+    /// State root of the block's header.
+    pub expected: B256,
+    /// State root recomputed from the flat state.
+    pub computed: B256,
+    /// Accounts whose proof doesn't verify against the header's state root.
+    pub failed_proofs: Vec<Address>,
+}
+
+impl StateRootAudit {
+    pub fn diverged(&self) -> bool {
+        self.computed != self.expected || !self.failed_proofs.is_empty()
+    }
+}
+
+/// Audits the latest state of `tx` against the header state root `expected`.
+///
+/// The root is recomputed from the hashed state alone, ignoring the stored trie nodes, so that a
+/// trie that no longer matches the flat state shows. The proofs of `accounts` are built from the
+/// stored trie, as `eth_getProof` builds them.
+pub fn audit_latest_state<Tx: DbTx>(
+    tx: &Tx,
+    expected: B256,
+    accounts: &[AccountToProve],
+) -> eyre::Result<StateRootAudit> {
+    let computed =
+        StateRoot::new(NoopTrieCursorFactory, DatabaseHashedCursorFactory::new(tx)).root()?;
+    let failed_proofs = verify_proofs(expected, accounts, |address, slots| {
+        Ok(Proof::from_tx(tx).account_proof(address, slots)?)
+    })?;
+    Ok(StateRootAudit { expected, computed, failed_proofs })
+}
+
+/// Verifies the proofs of `accounts` made by `prove` against `expected`, returning the accounts
+/// whose proof doesn't verify.
+fn verify_proofs(
+    expected: B256,
+    accounts: &[AccountToProve],
+    mut prove: impl FnMut(Address, &[B256]) -> eyre::Result<AccountProof>,
+) -> eyre::Result<Vec<Address>> {
+    let mut failed = vec![];
+    for AccountToProve { address, slots } in accounts {
+        match prove(*address, slots)?.verify(expected) {
+            Ok(()) => info!(%address, slots = slots.len(), "Proof verified"),
+            Err(err) => {
+                warn!(%address, %err, "Proof does not verify against header state root");
+                failed.push(*address);
+            }
+        }
+    }
+    if !accounts.is_empty() {
+        let verified = accounts.len() - failed.len();
+        info!(verified, failed = failed.len(), "Verified account proofs");
+    }
+    Ok(failed)
+}
+
+impl<C: ChainSpecParser<ChainSpec = HlChainSpec>> AuditStateRootCommand<C> {
+    pub async fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec, Primitives = crate::HlPrimitives>,
+    {
+        let accounts = match &self.accounts_file {
+            Some(path) => parse_accounts_file(&std::fs::read_to_string(path)?)?,
+            None => vec![],
+        };
+
+        let Environment { provider_factory, .. } = self.env.init::<N>(AccessRights::RO)?;
+        let provider = provider_factory.provider()?;
+        let latest = provider.best_block_number()?;
+        let block = self.block.unwrap_or(latest);
+        eyre::ensure!(block <= latest, "block {block} is ahead of the latest block {latest}");
+
+        let header = provider
+            .header_by_number(block)?
+            .ok_or_else(|| eyre::eyre!("header of block {block} not found"))?;
+        let expected = header.state_root();
+
+        let started = Instant::now();
+        let audit = if block == latest {
+            audit_latest_state(provider.tx_ref(), expected, &accounts)?
+        } else {
+            let state = provider_factory.history_by_block_number(block)?;
+            let computed = state.state_root(HashedPostState::default())?;
+            let failed_proofs = verify_proofs(expected, &accounts, |address, slots| {
+                Ok(state.proof(Default::default(), address, slots)?)
+            })?;
+            StateRootAudit { expected, computed, failed_proofs }
+        };
+        let (computed, elapsed) = (audit.computed, started.elapsed());
+        if computed != expected {
+            warn!(block, %expected, %computed, ?elapsed, "State root diverges");
+        } else {
+            info!(block, state_root = %computed, ?elapsed, "State root matches");
+        }
+
+        eyre::ensure!(
+            !audit.diverged(),
+            "trie at block {block} diverges from the header state root"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{U256, address, keccak256};
+    use reth_db::{ClientVersion, Database, mdbx::DatabaseArguments, tables, transaction::DbTxMut};
+    use reth_primitives_traits::Account;
+    use reth_trie::StoredNibbles;
+    use reth_trie_db::DatabaseStateRoot;
+
+    #[test]
+    fn audit_finds_flat_state_diverging_from_the_trie() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = reth_db::init_db(dir.path(), DatabaseArguments::new(ClientVersion::default()))?;
+        // Enough accounts for the trie to store branch nodes below the root
+        let accounts: Vec<_> = (0..1_000u16)
+            .map(|index| {
+                let account = Account { nonce: 1, balance: U256::from(index), bytecode_hash: None };
+                (Address::left_padding_from(&index.to_be_bytes()), account)
+            })
+            .collect();
+        let tx = db.tx_mut()?;
+        for (address, account) in &accounts {
+            tx.put::<tables::HashedAccounts>(keccak256(address), *account)?;
+        }
+        let (root, updates) = StateRoot::from_tx(&tx).root_with_updates()?;
+        assert!(!updates.account_nodes_ref().is_empty());
+        for (nibbles, node) in updates.account_nodes_ref() {
+            tx.put::<tables::AccountsTrie>(StoredNibbles(*nibbles), node.clone())?;
+        }
+        tx.commit()?;
+
+        let (address, account) = accounts[1];
+        let proved = [AccountToProve { address, slots: vec![] }];
+        let audit = audit_latest_state(&db.tx()?, root, &proved)?;
+        assert_eq!(audit, StateRootAudit { expected: root, computed: root, failed_proofs: vec![] });
+        assert!(!audit.diverged());
+
+        // The flat state moves on without the trie
+        let tx = db.tx_mut()?;
+        tx.put::<tables::HashedAccounts>(keccak256(address), Account { nonce: 2, ..account })?;
+        tx.commit()?;
+        // The stored trie still hashes to the header root, the flat state doesn't
+        assert_eq!(StateRoot::from_tx(&db.tx()?).root()?, root);
+        let audit = audit_latest_state(&db.tx()?, root, &proved)?;
+        assert_ne!(audit.computed, root);
+        assert_eq!(audit.failed_proofs, vec![address]);
+        assert!(audit.diverged());
+        Ok(())
+    }
+
+    #[test]
+    fn parses_accounts_file() {
+        let contents = "
+            # comment
+            0x3333333333333333333333333333333333333333
+
+            0x2222222222222222222222222222222222222222 0x0000000000000000000000000000000000000000000000000000000000000001
+        ";
+        assert_eq!(
+            parse_accounts_file(contents).unwrap(),
+            vec![
+                AccountToProve {
+                    address: address!("0x3333333333333333333333333333333333333333"),
+                    slots: vec![],
+                },
+                AccountToProve {
+                    address: address!("0x2222222222222222222222222222222222222222"),
+                    slots: vec![B256::with_last_byte(1)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let err = parse_accounts_file("0x33\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 1: invalid address"));
+        let err =
+            parse_accounts_file("0x3333333333333333333333333333333333333333 0x01\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 1: invalid slot"));
+    }
+}
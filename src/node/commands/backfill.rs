@@ -0,0 +1,59 @@
+//! `backfill` command: populates HL indexes for blocks imported before the index existed.
+//!
+//! Indexes are written at import, so databases synced by an older version lack the entries of
+//! their existing blocks. Backfilling is idempotent and can be run over any range.
+
+use crate::{chainspec::HlChainSpec, node::storage::write_system_tx_hashes};
+use clap::Parser;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, Environment, EnvironmentArgs};
+use reth_provider::{BlockNumReader, DBProvider, TransactionsProvider};
+use tracing::info;
+
+/// How many blocks are backfilled per database transaction.
+const BLOCKS_PER_COMMIT: u64 = 10_000;
+
+/// Backfills the system transaction hash index (`SystemTxHashNumbers`) of existing blocks.
+#[derive(Debug, Parser)]
+pub struct BackfillCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    pub env: EnvironmentArgs<C>,
+
+    /// First block to backfill.
+    #[arg(long, default_value_t = 0)]
+    pub from: u64,
+
+    /// Last block to backfill. Defaults to the latest block.
+    #[arg(long)]
+    pub to: Option<u64>,
+}
+
+impl<C: ChainSpecParser<ChainSpec = HlChainSpec>> BackfillCommand<C> {
+    pub async fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec, Primitives = crate::HlPrimitives>,
+    {
+        let Environment { provider_factory, .. } = self.env.init::<N>(AccessRights::RW)?;
+        let latest = provider_factory.provider()?.best_block_number()?;
+        let to = self.to.unwrap_or(latest).min(latest);
+        eyre::ensure!(self.from <= to, "nothing to backfill: --from {} is past {to}", self.from);
+
+        let mut indexed = 0;
+        let mut start = self.from;
+        while start <= to {
+            let end = to.min(start + BLOCKS_PER_COMMIT - 1);
+            let provider = provider_factory.provider_rw()?;
+            for number in start..=end {
+                let transactions =
+                    provider.transactions_by_block(number.into())?.unwrap_or_default();
+                indexed += write_system_tx_hashes(provider.tx_ref(), number, &transactions)?;
+            }
+            provider.commit()?;
+            info!(from = start, to = end, indexed, "Backfilled system transaction hashes");
+            start = end + 1;
+        }
+
+        info!(from = self.from, to, indexed, "Backfill complete");
+        Ok(())
+    }
+}
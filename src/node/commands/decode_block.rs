@@ -0,0 +1,132 @@
+//! `decode-block` command: decodes a single block file from S3 or a local ingest directory.
+//!
+//! Useful to inspect a file the node fails on, without a database or a running node.
+
+use crate::{
+    node::types::{BlockAndReceipts, EvmBlock},
+    pseudo_peer::decode_rmp_lz4,
+};
+use alloy_primitives::B256;
+use clap::Parser;
+use eyre::Context;
+use std::path::{Path, PathBuf};
+
+/// Decodes a block file and prints its blocks.
+#[derive(Debug, Parser)]
+pub struct DecodeBlockCommand {
+    /// Block file to decode: `.rmp.lz4` as stored on S3, or uncompressed `.rmp`.
+    #[arg(long)]
+    pub file: PathBuf,
+
+    /// Print the fully decoded blocks as JSON instead of a summary.
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl DecodeBlockCommand {
+    pub fn execute(self) -> eyre::Result<()> {
+        let bytes = std::fs::read(&self.file)
+            .wrap_err_with(|| format!("Failed to read {}", self.file.display()))?;
+        let blocks = decode_block_file(&self.file, &bytes)
+            .wrap_err_with(|| format!("Failed to decode {}", self.file.display()))?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&blocks)?);
+        } else {
+            for block in &blocks {
+                println!("{}", BlockSummary::new(block));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the blocks of a file, lz4-compressed if its extension is `.lz4`.
+pub fn decode_block_file(path: &Path, bytes: &[u8]) -> eyre::Result<Vec<BlockAndReceipts>> {
+    if path.extension().is_some_and(|extension| extension == "lz4") {
+        Ok(decode_rmp_lz4(bytes)?)
+    } else {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+/// What `decode-block` prints for a block.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+#[display(
+    "block {number} {hash}: {transactions} txs, {system_transactions} system txs, \
+     {receipts} receipts, {precompile_calls} read precompile calls"
+)]
+pub struct BlockSummary {
+    pub number: u64,
+    pub hash: B256,
+    pub transactions: usize,
+    pub system_transactions: usize,
+    pub receipts: usize,
+    pub precompile_calls: usize,
+}
+
+impl BlockSummary {
+    pub fn new(block: &BlockAndReceipts) -> Self {
+        let EvmBlock::Reth115(sealed) = &block.block;
+        Self {
+            number: block.number(),
+            hash: block.hash(),
+            transactions: sealed.body.transactions.len(),
+            system_transactions: block.system_txs.len(),
+            receipts: block.receipts.len(),
+            precompile_calls: block.read_precompile_calls.0.iter().map(|(_, c)| c.len()).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        node::types::{ReadPrecompileCalls, ReadPrecompileInput, ReadPrecompileResult},
+        test_utils::sealed_block,
+    };
+    use alloy_consensus::Header;
+    use alloy_primitives::{Address, Bytes};
+    use std::io::Write;
+
+    fn block(number: u64) -> BlockAndReceipts {
+        let input = ReadPrecompileInput { input: Bytes::from_static(&[1]), gas_limit: 100 };
+        let calls = vec![
+            (input.clone(), ReadPrecompileResult::OutOfGas),
+            (input, ReadPrecompileResult::Error),
+        ];
+        let header = Header { number, ..Default::default() };
+        BlockAndReceipts {
+            read_precompile_calls: ReadPrecompileCalls(vec![(Address::ZERO, calls)]),
+            ..sealed_block(header, B256::with_last_byte(number as u8))
+        }
+    }
+
+    #[test]
+    fn decodes_compressed_and_plain_files() {
+        let blocks = vec![block(7)];
+        let rmp = rmp_serde::to_vec(&blocks).unwrap();
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        encoder.write_all(&rmp).unwrap();
+        let lz4 = encoder.finish().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in [("7.rmp.lz4", &lz4), ("7.rmp", &rmp)] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            let decoded = decode_block_file(&path, &std::fs::read(&path).unwrap()).unwrap();
+            assert_eq!(decoded, blocks);
+        }
+
+        let summary = BlockSummary::new(&blocks[0]);
+        assert_eq!(summary.precompile_calls, 2);
+        assert_eq!(
+            summary.to_string(),
+            format!(
+                "block 7 {}: 0 txs, 0 system txs, 0 receipts, 2 read precompile calls",
+                B256::with_last_byte(7)
+            )
+        );
+    }
+}
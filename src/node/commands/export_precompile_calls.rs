@@ -0,0 +1,161 @@
+//! `export-precompile-calls` command: dumps the stored read precompile calls as CSV.
+//!
+//! One row per call, for bulk analysis of read precompile usage. Calls are read straight from the
+//! `BlockReadPrecompileCalls` table, so blocks without calls cost nothing.
+
+use crate::{
+    chainspec::HlChainSpec,
+    node::{
+        storage::tables,
+        types::{HlExtras, ReadPrecompileCalls, ReadPrecompileResult},
+    },
+};
+use clap::Parser;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, Environment, EnvironmentArgs};
+use reth_db::{cursor::DbCursorRO, transaction::DbTx};
+use reth_provider::{BlockNumReader, DBProvider};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use tracing::info;
+
+/// Header of the exported CSV.
+pub const CSV_HEADER: &str = "block,address,input_len,gas_limit,result_kind,gas_used,output_len";
+
+/// Exports the read precompile calls of a block range as CSV.
+#[derive(Debug, Parser)]
+pub struct ExportPrecompileCallsCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    pub env: EnvironmentArgs<C>,
+
+    /// First block to export (inclusive).
+    #[arg(long, default_value_t = 0)]
+    pub from: u64,
+
+    /// Last block to export (inclusive). Defaults to the latest block.
+    #[arg(long)]
+    pub to: Option<u64>,
+
+    /// CSV file to write.
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+impl<C: ChainSpecParser<ChainSpec = HlChainSpec>> ExportPrecompileCallsCommand<C> {
+    pub async fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec, Primitives = crate::HlPrimitives>,
+    {
+        let Environment { provider_factory, .. } = self.env.init::<N>(AccessRights::RO)?;
+        let provider = provider_factory.provider()?;
+        let to = self.to.unwrap_or(provider.best_block_number()?);
+        eyre::ensure!(self.from <= to, "nothing to export: --from {} is past {to}", self.from);
+
+        let mut out = BufWriter::new(File::create(&self.out)?);
+        let rows = export_precompile_calls(provider.tx_ref(), self.from, to, &mut out)?;
+        out.flush()?;
+
+        let out = self.out.display();
+        info!(from = self.from, to, rows, %out, "Exported read precompile calls");
+        Ok(())
+    }
+}
+
+/// Writes the read precompile calls of blocks `from..=to` as CSV, returning the number of rows.
+pub fn export_precompile_calls<Tx: DbTx>(
+    tx: &Tx,
+    from: u64,
+    to: u64,
+    out: &mut impl Write,
+) -> eyre::Result<usize> {
+    writeln!(out, "{CSV_HEADER}")?;
+    let mut rows = 0;
+    let mut cursor = tx.cursor_read::<tables::BlockReadPrecompileCalls>()?;
+    for entry in cursor.walk_range(from..=to)? {
+        let (number, extras) = entry?;
+        let extras: HlExtras = rmp_serde::from_slice(&extras)?;
+        if let Some(calls) = &extras.read_precompile_calls {
+            rows += write_rows(out, number, calls)?;
+        }
+    }
+    Ok(rows)
+}
+
+/// Writes one row per call of block `number`.
+fn write_rows(
+    out: &mut impl Write,
+    number: u64,
+    calls: &ReadPrecompileCalls,
+) -> std::io::Result<usize> {
+    let mut rows = 0;
+    for (address, calls) in &calls.0 {
+        for (input, result) in calls {
+            let (kind, gas_used, output_len) = match result {
+                ReadPrecompileResult::Ok { gas_used, bytes } => {
+                    ("ok", gas_used.to_string(), bytes.len().to_string())
+                }
+                ReadPrecompileResult::OutOfGas => ("out_of_gas", String::new(), String::new()),
+                ReadPrecompileResult::Error => ("error", String::new(), String::new()),
+                ReadPrecompileResult::UnexpectedError => {
+                    ("unexpected_error", String::new(), String::new())
+                }
+            };
+            writeln!(
+                out,
+                "{number},{address},{},{},{kind},{gas_used},{output_len}",
+                input.input.len(),
+                input.gas_limit
+            )?;
+            rows += 1;
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{storage::tables::Tables, types::ReadPrecompileInput};
+    use alloy_primitives::{Address, Bytes};
+    use reth_db::{ClientVersion, Database, mdbx::DatabaseArguments, transaction::DbTxMut};
+
+    fn extras(calls: usize) -> HlExtras {
+        let input = ReadPrecompileInput { input: Bytes::from_static(&[1, 2, 3]), gas_limit: 100 };
+        let results = [
+            ReadPrecompileResult::Ok { gas_used: 40, bytes: Bytes::from_static(&[0; 32]) },
+            ReadPrecompileResult::OutOfGas,
+        ];
+        let calls = (0..calls).map(|i| (input.clone(), results[i % 2].clone())).collect();
+        HlExtras {
+            read_precompile_calls: Some(ReadPrecompileCalls(vec![(Address::ZERO, calls)])),
+            highest_precompile_address: None,
+        }
+    }
+
+    #[test]
+    fn exports_one_row_per_call() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let tx = db.tx_mut().unwrap();
+        for (number, calls) in [(1, 2), (2, 0), (3, 3), (4, 5)] {
+            let value = Bytes::from(rmp_serde::to_vec(&extras(calls)).unwrap());
+            tx.put::<tables::BlockReadPrecompileCalls>(number, value).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let mut out = Vec::new();
+        let rows = export_precompile_calls(&db.tx().unwrap(), 1, 3, &mut out).unwrap();
+        assert_eq!(rows, 5);
+
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), rows + 1);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], format!("1,{},3,100,ok,40,32", Address::ZERO));
+        assert_eq!(lines[2], format!("1,{},3,100,out_of_gas,,", Address::ZERO));
+    }
+}
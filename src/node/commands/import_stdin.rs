@@ -0,0 +1,173 @@
+//! `import-stdin` command: runs the node with the blocks piped to stdin as its block source, and
+//! exits once they are imported.
+//!
+//! Blocks are read as newline-delimited JSON in the hl-node file format, as written by
+//! `stream-blocks`, e.g. `cat blocks.ndjson | reth-hl import-stdin --datadir /tmp/repro`. This
+//! makes import bugs reproducible from a handful of lines, without laying out block files.
+//!
+//! With `--import.batch-size`, the engine keeps imported blocks in memory and writes them to the
+//! database and static files a batch at a time, in one commit, instead of one commit per block.
+//! The last, partial batch is written once the input is imported. A commit is atomic: blocks of a
+//! batch written to static files past the last database commit are rolled back at the next start
+//! and imported again.
+
+use crate::{
+    chainspec::HlChainSpec,
+    node::{
+        cli::HlNodeArgs,
+        launch::{HlNodeHandle, launch_hl_node_with},
+    },
+    pseudo_peer::{BlockSourceConfig, StdinBlockSource},
+};
+use clap::Args;
+use reth::{
+    args::EngineArgs,
+    builder::{NodeBuilder, WithLaunchContext},
+};
+use reth_db::DatabaseEnv;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+/// How often the import progress is checked.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often the import throughput is logged.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Arguments of `import-stdin`: the node's, and how imported blocks are committed.
+#[derive(Debug, Clone, Args)]
+pub struct ImportStdinArgs {
+    #[command(flatten)]
+    pub node: HlNodeArgs,
+
+    /// Number of imported blocks written to the database and static files in one commit.
+    /// Defaults to the engine's persistence threshold.
+    #[arg(long = "import.batch-size", value_parser = clap::value_parser!(u64).range(1..))]
+    pub batch_size: Option<u64>,
+}
+
+impl ImportStdinArgs {
+    /// Configures the engine to commit [`Self::batch_size`] blocks at a time.
+    pub fn apply_batch_size(&self, engine: &mut EngineArgs) {
+        if let Some(batch_size) = self.batch_size {
+            commit_in_batches(engine, batch_size);
+        }
+    }
+}
+
+/// Configures the engine to commit imported blocks `batch_size` at a time. The engine persists
+/// once more than `persistence_threshold` blocks are in memory, keeping
+/// `memory_block_buffer_target` of them.
+pub fn commit_in_batches(engine: &mut EngineArgs, batch_size: u64) {
+    engine.persistence_threshold = batch_size.saturating_sub(1);
+    engine.memory_block_buffer_target = 0;
+}
+
+/// Launches the node importing the blocks read from stdin, returning once the last of them is
+/// the canonical head and written to the datadir. Block source arguments are ignored.
+pub async fn import_stdin(
+    builder: WithLaunchContext<NodeBuilder<Arc<DatabaseEnv>, HlChainSpec>>,
+    args: ImportStdinArgs,
+) -> eyre::Result<()> {
+    let source = StdinBlockSource::new();
+    let config = BlockSourceConfig::custom(Arc::new(Box::new(source.clone())));
+    let handle = launch_hl_node_with(builder, args.node, Some(config)).await?;
+    wait_for_import(&source, handle).await
+}
+
+/// Waits until the node imported every block of `source` and wrote them to the datadir, failing
+/// if the input was cut short, the import stalls or the node exits first. Progress and
+/// throughput are logged meanwhile.
+pub async fn wait_for_import(source: &StdinBlockSource, handle: HlNodeHandle) -> eyre::Result<()> {
+    let HlNodeHandle { engine_status, mut exit, persist, .. } = handle;
+    let imported = async {
+        let mut finished = std::pin::pin!(source.finished());
+        // Highest height of the input once it ended
+        let mut last = None;
+        let mut throughput = Throughput::new(engine_status.import().head);
+        let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                ended = &mut finished, if last.is_none() => {
+                    let Some(height) = ended? else {
+                        info!("No blocks on stdin, nothing to import");
+                        return Ok(());
+                    };
+                    info!(last = height, "Read blocks from stdin, waiting for their import");
+                    last = Some(height);
+                }
+                _ = interval.tick() => {}
+            }
+            let import = engine_status.import();
+            throughput.report(import.head);
+            if let Some(last) = last.filter(|last| import.head.is_some_and(|head| head >= *last)) {
+                // The last batch is still in memory
+                persist.await?;
+                let (blocks, elapsed) = throughput.total(last);
+                info!(
+                    last,
+                    blocks,
+                    ?elapsed,
+                    blocks_per_second = rate(blocks, elapsed),
+                    "Imported and committed the blocks from stdin"
+                );
+                return Ok(());
+            }
+            if import.stalled {
+                eyre::bail!(
+                    "import stalled at {:?}, last error: {}",
+                    import.head,
+                    import.last_error.as_deref().unwrap_or("none")
+                );
+            }
+        }
+    };
+    tokio::select! {
+        result = imported => result,
+        result = &mut exit => {
+            result?;
+            eyre::bail!("the node exited before importing the blocks from stdin")
+        }
+    }
+}
+
+/// Import throughput since the start of the command.
+struct Throughput {
+    started: Instant,
+    start_head: Option<u64>,
+    last_report: Instant,
+}
+
+impl Throughput {
+    fn new(start_head: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self { started: now, start_head, last_report: now }
+    }
+
+    /// Blocks imported since the start.
+    fn blocks(&self, head: u64) -> u64 {
+        self.start_head.map_or(head, |start| head.saturating_sub(start))
+    }
+
+    /// Logs the progress every [`REPORT_INTERVAL`].
+    fn report(&mut self, head: Option<u64>) {
+        let Some(head) = head.filter(|_| self.last_report.elapsed() >= REPORT_INTERVAL) else {
+            return;
+        };
+        self.last_report = Instant::now();
+        let elapsed = self.started.elapsed();
+        info!(head, blocks_per_second = rate(self.blocks(head), elapsed), "Importing from stdin");
+    }
+
+    /// Blocks imported up to `head`, and the time it took.
+    fn total(&self, head: u64) -> (u64, Duration) {
+        (self.blocks(head), self.started.elapsed())
+    }
+}
+
+fn rate(blocks: u64, elapsed: Duration) -> f64 {
+    blocks as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
@@ -0,0 +1,10 @@
+//! HL-specific CLI commands that live next to reth's [`Commands`](reth::cli::Commands).
+
+pub mod audit;
+pub mod audit_state_root;
+pub mod backfill;
+pub mod decode_block;
+pub mod export_precompile_calls;
+pub mod import_stdin;
+pub mod stream_blocks;
+pub mod verify_precompile_addresses;
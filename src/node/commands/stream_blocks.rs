@@ -0,0 +1,108 @@
+//! `stream-blocks` command: writes stored blocks to stdout as newline-delimited JSON.
+//!
+//! Each line is a `[block_time, block]` pair in the line format of hl-node's block files, so the
+//! output can be fed to external pipelines as well as to anything that reads hl-node files,
+//! including the hl-node block source.
+
+use crate::{
+    chainspec::HlChainSpec,
+    node::types::{BlockAndReceipts, EvmBlock},
+    pseudo_peer::LocalBlockAndReceipts,
+};
+use clap::Parser;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, Environment, EnvironmentArgs};
+use reth_provider::{BlockNumReader, BlockReader, ReceiptProvider};
+use std::io::{self, BufWriter, Write};
+use time::{OffsetDateTime, macros::format_description};
+
+/// Streams the stored blocks of a range to stdout, one JSON block per line.
+///
+/// Logs are written to stderr, so that stdout only carries the blocks.
+#[derive(Debug, Parser)]
+pub struct StreamBlocksCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    pub env: EnvironmentArgs<C>,
+
+    /// First block to stream (inclusive).
+    #[arg(long, default_value_t = 1)]
+    pub from: u64,
+
+    /// Last block to stream (inclusive). Defaults to the latest block.
+    #[arg(long)]
+    pub to: Option<u64>,
+}
+
+impl<C: ChainSpecParser<ChainSpec = HlChainSpec>> StreamBlocksCommand<C> {
+    pub async fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec, Primitives = crate::HlPrimitives>,
+    {
+        let Environment { provider_factory, .. } = self.env.init::<N>(AccessRights::RO)?;
+        let provider = provider_factory.provider()?;
+        let to = self.to.unwrap_or(provider.best_block_number()?);
+        eyre::ensure!(self.from <= to, "nothing to stream: --from {} is past {to}", self.from);
+
+        let mut out = BufWriter::new(io::stdout().lock());
+        for number in self.from..=to {
+            let block = provider
+                .block_by_number(number)?
+                .ok_or_else(|| eyre::eyre!("Block {number} not found in database"))?;
+            let receipts = provider
+                .receipts_by_block(number.into())?
+                .ok_or_else(|| eyre::eyre!("Receipts for block {number} not found in database"))?;
+            match write_block_line(&mut out, BlockAndReceipts::from_db(block, receipts)) {
+                // The reader stopped early, e.g. `| head`
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                result => result?,
+            }
+        }
+        match out.flush() {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => Ok(result?),
+        }
+    }
+}
+
+/// Writes `block` as one hl-node file line.
+pub fn write_block_line(out: &mut impl Write, block: BlockAndReceipts) -> io::Result<()> {
+    let EvmBlock::Reth115(sealed) = &block.block;
+    let block_time = block_time(sealed.header.header.timestamp);
+    serde_json::to_writer(&mut *out, &LocalBlockAndReceipts(block_time, block))?;
+    out.write_all(b"\n")
+}
+
+/// Formats a block timestamp the way hl-node does, e.g. `2025-06-30T12:00:00.000000000`.
+fn block_time(timestamp: u64) -> String {
+    let format =
+        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:9]");
+    OffsetDateTime::from_unix_timestamp(timestamp as i64)
+        .ok()
+        .and_then(|time| time.format(&format).ok())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pseudo_peer::Scanner, test_utils::empty_block};
+
+    #[test]
+    fn lines_reparse_as_hl_node_blocks() {
+        let blocks: Vec<_> = (1..=3).map(empty_block).collect();
+        let mut out = Vec::new();
+        for block in blocks.clone() {
+            write_block_line(&mut out, block).unwrap();
+        }
+
+        let output = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), blocks.len());
+        for (line, expected) in lines.into_iter().zip(&blocks) {
+            let (parsed, height) = Scanner::line_to_evm_block(line).unwrap();
+            assert_eq!(height, expected.number());
+            assert_eq!(parsed.hash(), expected.hash());
+        }
+        assert!(output.starts_with(r#"["2025-06-30T12:00:01.000000000","#), "{output}");
+    }
+}
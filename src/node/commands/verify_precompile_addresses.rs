@@ -0,0 +1,216 @@
+//! `verify-precompile-addresses` command: checks the stored `highest_precompile_address` of
+//! blocks against their read precompile calls.
+//!
+//! [`apply_precompiles`] installs the precompiles from the base address up to the stored address,
+//! so a corrupted address installs the wrong number of precompiles without any error. The stored
+//! address is the highest deployed precompile, which a block doesn't necessarily call, so only an
+//! address below the highest called one is wrong, as [`validate_precompile_addresses`] checks on
+//! import. Blocks without calls, or that don't record the address, are skipped: there is nothing
+//! to compare, or the chain default applies.
+//!
+//! [`apply_precompiles`]: crate::node::evm::apply_precompiles
+//! [`validate_precompile_addresses`]:
+//!     crate::node::consensus::precompile_calls::validate_precompile_addresses
+
+use crate::{
+    chainspec::HlChainSpec,
+    node::{storage::tables, types::HlExtras},
+};
+use alloy_primitives::{Address, Bytes};
+use clap::Parser;
+use reth_cli::chainspec::ChainSpecParser;
+use reth_cli_commands::common::{AccessRights, CliNodeTypes, Environment, EnvironmentArgs};
+use reth_db::{
+    cursor::DbCursorRO,
+    transaction::{DbTx, DbTxMut},
+};
+use reth_provider::{BlockNumReader, DBProvider};
+use tracing::{info, warn};
+
+/// Verifies the stored highest precompile address of a block range.
+#[derive(Debug, Parser)]
+pub struct VerifyPrecompileAddressesCommand<C: ChainSpecParser> {
+    #[command(flatten)]
+    pub env: EnvironmentArgs<C>,
+
+    /// First block to verify (inclusive).
+    #[arg(long, default_value_t = 0)]
+    pub from: u64,
+
+    /// Last block to verify (inclusive). Defaults to the latest block.
+    #[arg(long)]
+    pub to: Option<u64>,
+
+    /// Raise the stored address of mismatching blocks to their highest called address.
+    #[arg(long)]
+    pub repair: bool,
+}
+
+/// A block whose stored highest precompile address is below one of its calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+#[display("block {number}: stored highest precompile address {stored}, calls up to {recomputed}")]
+pub struct AddressMismatch {
+    pub number: u64,
+    pub stored: Address,
+    pub recomputed: Address,
+}
+
+impl<C: ChainSpecParser<ChainSpec = HlChainSpec>> VerifyPrecompileAddressesCommand<C> {
+    pub async fn execute<N>(self) -> eyre::Result<()>
+    where
+        N: CliNodeTypes<ChainSpec = C::ChainSpec, Primitives = crate::HlPrimitives>,
+    {
+        let access = if self.repair { AccessRights::RW } else { AccessRights::RO };
+        let Environment { provider_factory, .. } = self.env.init::<N>(access)?;
+        let provider = provider_factory.provider()?;
+        let to = self.to.unwrap_or(provider.best_block_number()?);
+        eyre::ensure!(self.from <= to, "nothing to verify: --from {} is past {to}", self.from);
+
+        let mismatches = verify_precompile_addresses(provider.tx_ref(), self.from, to)?;
+        drop(provider);
+        for mismatch in &mismatches {
+            warn!(%mismatch, "Highest precompile address mismatch");
+        }
+
+        if self.repair && !mismatches.is_empty() {
+            let provider = provider_factory.provider_rw()?;
+            repair_precompile_addresses(provider.tx_ref(), &mismatches)?;
+            provider.commit()?;
+            info!(repaired = mismatches.len(), "Repaired highest precompile addresses");
+            return Ok(());
+        }
+
+        eyre::ensure!(
+            mismatches.is_empty(),
+            "{} blocks between {} and {to} have a wrong highest precompile address",
+            mismatches.len(),
+            self.from
+        );
+        info!(from = self.from, to, "Highest precompile addresses match the calls");
+        Ok(())
+    }
+}
+
+/// Returns the blocks of `from..=to` whose stored highest precompile address is below the highest
+/// address among their read precompile calls.
+pub fn verify_precompile_addresses<Tx: DbTx>(
+    tx: &Tx,
+    from: u64,
+    to: u64,
+) -> eyre::Result<Vec<AddressMismatch>> {
+    let mut mismatches = Vec::new();
+    let mut cursor = tx.cursor_read::<tables::BlockReadPrecompileCalls>()?;
+    for entry in cursor.walk_range(from..=to)? {
+        let (number, extras) = entry?;
+        let extras: HlExtras = rmp_serde::from_slice(&extras)?;
+        let Some(stored) = extras.highest_precompile_address else { continue };
+        let called = extras.read_precompile_calls.iter().flat_map(|calls| &calls.0);
+        let Some(recomputed) = called.map(|(address, _)| *address).max() else { continue };
+        if stored < recomputed {
+            mismatches.push(AddressMismatch { number, stored, recomputed });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Raises the stored address of the blocks of `mismatches` to their recomputed address. A stored
+/// address is never lowered.
+pub fn repair_precompile_addresses<Tx: DbTxMut + DbTx>(
+    tx: &Tx,
+    mismatches: &[AddressMismatch],
+) -> eyre::Result<()> {
+    for mismatch in mismatches {
+        let Some(extras) = tx.get::<tables::BlockReadPrecompileCalls>(mismatch.number)? else {
+            continue;
+        };
+        let mut extras: HlExtras = rmp_serde::from_slice(&extras)?;
+        let stored = extras.highest_precompile_address.unwrap_or(Address::ZERO);
+        if stored >= mismatch.recomputed {
+            continue;
+        }
+        extras.highest_precompile_address = Some(mismatch.recomputed);
+        let extras = Bytes::from(rmp_serde::to_vec(&extras)?);
+        tx.put::<tables::BlockReadPrecompileCalls>(mismatch.number, extras)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{
+        storage::tables::Tables,
+        types::{ReadPrecompileCalls, ReadPrecompileInput, ReadPrecompileResult},
+    };
+    use reth_db::{ClientVersion, Database, mdbx::DatabaseArguments};
+
+    fn extras(called: &[u8], highest: Option<u8>) -> Bytes {
+        let input = ReadPrecompileInput { input: Bytes::from_static(&[1]), gas_limit: 100 };
+        let calls = called
+            .iter()
+            .map(|last| {
+                let address = Address::with_last_byte(*last);
+                (address, vec![(input.clone(), ReadPrecompileResult::OutOfGas)])
+            })
+            .collect();
+        let extras = HlExtras {
+            read_precompile_calls: Some(ReadPrecompileCalls(calls)),
+            highest_precompile_address: highest.map(Address::with_last_byte),
+        };
+        Bytes::from(rmp_serde::to_vec(&extras).unwrap())
+    }
+
+    #[test]
+    fn stored_address_above_the_calls_is_not_a_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let tx = db.tx_mut().unwrap();
+        tx.put::<tables::BlockReadPrecompileCalls>(1, extras(&[0x01, 0x02], Some(0x0f))).unwrap();
+        tx.commit().unwrap();
+
+        assert!(verify_precompile_addresses(&db.tx().unwrap(), 1, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_and_repairs_a_wrong_stored_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let tx = db.tx_mut().unwrap();
+        // Block 2 stores 0x..01 although it calls 0x..05, block 5 deploys precompiles up to
+        // 0x..09 without calling them, blocks 3 and 4 have nothing to verify
+        for (number, called, highest) in [
+            (1, &[0x01, 0x05][..], Some(0x05)),
+            (2, &[0x05, 0x01][..], Some(0x01)),
+            (3, &[][..], Some(0x07)),
+            (4, &[0x05][..], None),
+            (5, &[0x01, 0x05][..], Some(0x09)),
+        ] {
+            tx.put::<tables::BlockReadPrecompileCalls>(number, extras(called, highest)).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let mismatches = verify_precompile_addresses(&db.tx().unwrap(), 1, 5).unwrap();
+        let expected = AddressMismatch {
+            number: 2,
+            stored: Address::with_last_byte(0x01),
+            recomputed: Address::with_last_byte(0x05),
+        };
+        assert_eq!(mismatches, vec![expected]);
+
+        let tx = db.tx_mut().unwrap();
+        repair_precompile_addresses(&tx, &mismatches).unwrap();
+        tx.commit().unwrap();
+
+        let tx = db.tx().unwrap();
+        assert!(verify_precompile_addresses(&tx, 1, 5).unwrap().is_empty());
+        let repaired = tx.get::<tables::BlockReadPrecompileCalls>(2).unwrap().unwrap();
+        let repaired: HlExtras = rmp_serde::from_slice(&repaired).unwrap();
+        assert_eq!(repaired.highest_precompile_address, Some(Address::with_last_byte(0x05)));
+        assert_eq!(repaired.read_precompile_calls.unwrap().0.len(), 2);
+        let untouched = tx.get::<tables::BlockReadPrecompileCalls>(5).unwrap().unwrap();
+        let untouched: HlExtras = rmp_serde::from_slice(&untouched).unwrap();
+        assert_eq!(untouched.highest_precompile_address, Some(Address::with_last_byte(0x09)));
+    }
+}
@@ -12,16 +12,38 @@ use reth::{
         validate_against_parent_4844, validate_against_parent_hash_number,
     },
 };
-use reth_chainspec::EthChainSpec;
+use reth_chainspec::{EthChainSpec, EthereumHardforks};
 use reth_primitives::{Receipt, RecoveredBlock, SealedBlock, SealedHeader};
 use reth_primitives_traits::BlockHeader;
 use reth_provider::BlockExecutionResult;
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+pub mod precompile_calls;
+pub mod tx_hashes;
+pub mod withdrawals;
+
+use precompile_calls::{
+    DEFAULT_HISTORICAL_BLOCK_AGE, is_historical, validate_precompile_addresses,
+    validate_read_precompile_calls,
+};
+use tx_hashes::{TxHashIndex, validate_unique_tx_hashes};
+use withdrawals::validate_withdrawals;
 
 /// A basic Hl consensus builder.
 #[derive(Debug, Default, Clone, Copy)]
 #[non_exhaustive]
-pub struct HlConsensusBuilder;
+pub struct HlConsensusBuilder {
+    /// Only log inconsistent read precompile calls instead of rejecting the block.
+    pub(crate) tolerate_invalid_precompile_calls: bool,
+    /// Age past which the inconsistent read precompile calls of a block are only logged.
+    pub(crate) historical_block_age: Option<Duration>,
+    /// Only log colliding transaction hashes instead of rejecting the block.
+    pub(crate) tolerate_duplicate_tx_hashes: bool,
+}
 
 impl<Node> ConsensusBuilder<Node> for HlConsensusBuilder
 where
@@ -30,7 +52,13 @@ where
     type Consensus = Arc<HlConsensus<<Node::Types as NodeTypes>::ChainSpec>>;
 
     async fn build_consensus(self, ctx: &BuilderContext<Node>) -> eyre::Result<Self::Consensus> {
-        Ok(Arc::new(HlConsensus::new(ctx.chain_spec())))
+        Ok(Arc::new(
+            HlConsensus::new(ctx.chain_spec())
+                .with_tolerate_invalid_precompile_calls(self.tolerate_invalid_precompile_calls)
+                .with_historical_block_age(self.historical_block_age)
+                .with_tolerate_duplicate_tx_hashes(self.tolerate_duplicate_tx_hashes)
+                .with_tx_hash_index(Arc::new(ctx.provider().clone())),
+        ))
     }
 }
 
@@ -41,6 +69,11 @@ where
 pub struct HlConsensus<ChainSpec> {
     inner: EthBeaconConsensus<ChainSpec>,
     chain_spec: Arc<ChainSpec>,
+    tolerate_invalid_precompile_calls: bool,
+    historical_block_age: Option<Duration>,
+    tolerate_duplicate_tx_hashes: bool,
+    /// Index checked for transactions already imported elsewhere, see [`tx_hashes`].
+    tx_hash_index: Option<Arc<dyn TxHashIndex>>,
 }
 
 impl<ChainSpec> HlConsensus<ChainSpec>
@@ -49,7 +82,41 @@ where
 {
     /// Create a new instance of [`HlConsensus`]
     pub fn new(chain_spec: Arc<ChainSpec>) -> Self {
-        Self { inner: EthBeaconConsensus::new(chain_spec.clone()), chain_spec }
+        Self {
+            inner: EthBeaconConsensus::new(chain_spec.clone()),
+            chain_spec,
+            tolerate_invalid_precompile_calls: false,
+            historical_block_age: Some(DEFAULT_HISTORICAL_BLOCK_AGE),
+            tolerate_duplicate_tx_hashes: false,
+            tx_hash_index: None,
+        }
+    }
+
+    /// Only logs blocks with inconsistent read precompile calls instead of rejecting them, to
+    /// replay historical data as recorded.
+    pub fn with_tolerate_invalid_precompile_calls(mut self, tolerate: bool) -> Self {
+        self.tolerate_invalid_precompile_calls = tolerate;
+        self
+    }
+
+    /// Only logs the inconsistent read precompile calls of blocks older than `age`, see
+    /// [`precompile_calls`]. `None` rejects them at any age.
+    pub fn with_historical_block_age(mut self, age: Option<Duration>) -> Self {
+        self.historical_block_age = age;
+        self
+    }
+
+    /// Only logs blocks with colliding transaction hashes instead of rejecting them, to import a
+    /// chain whose hash index is known to be ambiguous.
+    pub fn with_tolerate_duplicate_tx_hashes(mut self, tolerate: bool) -> Self {
+        self.tolerate_duplicate_tx_hashes = tolerate;
+        self
+    }
+
+    /// Rejects blocks holding transactions that `index` has at another position.
+    pub fn with_tx_hash_index(mut self, index: Arc<dyn TxHashIndex>) -> Self {
+        self.tx_hash_index = Some(index);
+        self
     }
 }
 
@@ -118,8 +185,44 @@ where
 
     fn validate_block_pre_execution(
         &self,
-        _block: &SealedBlock<HlBlock>,
+        block: &SealedBlock<HlBlock>,
     ) -> Result<(), ConsensusError> {
+        let body = block.body();
+        validate_withdrawals(
+            block.header(),
+            body.inner.withdrawals.as_ref(),
+            self.chain_spec.is_shanghai_active_at_timestamp(block.timestamp()),
+        )?;
+
+        if let Some(calls) = &body.read_precompile_calls &&
+            let Err(violation) = validate_read_precompile_calls(calls).and_then(|()| {
+                validate_precompile_addresses(calls, body.highest_precompile_address)
+            })
+        {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let historical = is_historical(block.timestamp(), now, self.historical_block_age);
+            if !self.tolerate_invalid_precompile_calls && !historical {
+                return Err(ConsensusError::Other(format!("block {}: {violation}", block.number())));
+            }
+            warn!(
+                number = block.number(),
+                %violation,
+                historical,
+                "Inconsistent read precompile calls"
+            );
+        }
+
+        if let Err(err) = validate_unique_tx_hashes(
+            block.number(),
+            &body.inner.transactions,
+            self.tx_hash_index.as_deref(),
+        ) {
+            if !self.tolerate_duplicate_tx_hashes {
+                return Err(err);
+            }
+            warn!(number = block.number(), %err, "Colliding transaction hashes");
+        }
+
         // Check ommers hash
         // let ommers_hash = block.body().calculate_ommers_root();
         // if Some(block.ommers_hash()) != ommers_hash {
@@ -0,0 +1,257 @@
+//! Consistency checks of the read precompile calls attached to a block.
+//!
+//! The recorded results are replayed as-is by the EVM, so a corrupted archive entry (e.g. a gas
+//! usage above the call's gas limit) makes execution diverge from the canonical one without any
+//! error. These checks reject such blocks before execution.
+//!
+//! Historical blocks, older than [`DEFAULT_HISTORICAL_BLOCK_AGE`] unless configured otherwise, are
+//! replayed as recorded: their inconsistent calls are only logged, so that a corrupted archive
+//! entry doesn't halt a sync from genesis.
+
+use crate::{
+    chainspec::BASE_PRECOMPILE_ADDRESS,
+    node::types::{ReadPrecompileCalls, ReadPrecompileResult},
+};
+use alloy_primitives::{Address, Bytes};
+use std::{collections::HashMap, time::Duration};
+
+/// Age past which a block is historical, and its inconsistent read precompile calls tolerated.
+pub const DEFAULT_HISTORICAL_BLOCK_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A recorded read precompile call that can't be replayed as recorded.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum PrecompileCallViolation {
+    #[display(
+        "read precompile {address} input {index}: gas used {gas_used} exceeds gas limit {gas_limit}"
+    )]
+    GasUsedExceedsLimit { address: Address, index: usize, gas_used: u64, gas_limit: u64 },
+    #[display(
+        "read precompile {address} input {index}: result conflicts with an earlier result for the \
+         same input and gas limit"
+    )]
+    ConflictingResult { address: Address, index: usize },
+    #[display(
+        "read precompile {address} input {index}: ran out of gas with gas limit {gas_limit}, but \
+         the same input succeeded using {gas_used}"
+    )]
+    OutOfGasWithinLimit { address: Address, index: usize, gas_limit: u64, gas_used: u64 },
+    #[display("read precompile {address} is outside the address range {lowest}..={highest}")]
+    AddressOutOfRange { address: Address, lowest: Address, highest: Address },
+}
+
+/// Returns the first inconsistent call of `calls`, if any.
+///
+/// A successful call can't use more gas than its limit, and the same input with the same gas
+/// limit can't both succeed and run out of gas, since the replay looks results up by input. A
+/// precompile's gas doesn't depend on the gas limit, so an input that succeeded using some gas
+/// can't run out of gas with a limit covering it either.
+pub fn validate_read_precompile_calls(
+    calls: &ReadPrecompileCalls,
+) -> Result<(), PrecompileCallViolation> {
+    for (address, calls) in &calls.0 {
+        let address = *address;
+        let mut results = HashMap::with_capacity(calls.len());
+        // Per input data, the least gas used by a success and the highest limit run out of
+        let mut least_gas_used = HashMap::<&Bytes, u64>::new();
+        let mut highest_out_of_gas = HashMap::<&Bytes, u64>::new();
+        for (index, (input, result)) in calls.iter().enumerate() {
+            if results.insert(input, result).is_some_and(|previous| previous != result) {
+                return Err(PrecompileCallViolation::ConflictingResult { address, index });
+            }
+            let data = &input.input;
+            match result {
+                ReadPrecompileResult::Ok { gas_used, .. } => {
+                    let gas_used = *gas_used;
+                    if gas_used > input.gas_limit {
+                        return Err(PrecompileCallViolation::GasUsedExceedsLimit {
+                            address,
+                            index,
+                            gas_used,
+                            gas_limit: input.gas_limit,
+                        });
+                    }
+                    if let Some(&gas_limit) = highest_out_of_gas.get(data) &&
+                        gas_limit >= gas_used
+                    {
+                        return Err(PrecompileCallViolation::OutOfGasWithinLimit {
+                            address,
+                            index,
+                            gas_limit,
+                            gas_used,
+                        });
+                    }
+                    let least = least_gas_used.entry(data).or_insert(gas_used);
+                    *least = (*least).min(gas_used);
+                }
+                ReadPrecompileResult::OutOfGas => {
+                    let gas_limit = input.gas_limit;
+                    if let Some(&gas_used) = least_gas_used.get(data) &&
+                        gas_limit >= gas_used
+                    {
+                        return Err(PrecompileCallViolation::OutOfGasWithinLimit {
+                            address,
+                            index,
+                            gas_limit,
+                            gas_used,
+                        });
+                    }
+                    let highest = highest_out_of_gas.entry(data).or_insert(gas_limit);
+                    *highest = (*highest).max(gas_limit);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a block with `timestamp` is older than `age` at `now`, both in seconds since the
+/// epoch. A block is never historical without an `age`.
+pub fn is_historical(timestamp: u64, now: u64, age: Option<Duration>) -> bool {
+    age.is_some_and(|age| now.saturating_sub(timestamp) > age.as_secs())
+}
+
+/// Returns the first recorded precompile outside the block's precompile address range, if any.
+///
+/// Calls below the base address are never read precompiles. The upper bound is only checked for
+/// blocks that record their `highest_precompile_address`.
+pub fn validate_precompile_addresses(
+    calls: &ReadPrecompileCalls,
+    highest_precompile_address: Option<Address>,
+) -> Result<(), PrecompileCallViolation> {
+    let highest = highest_precompile_address.unwrap_or(Address::repeat_byte(0xff));
+    for (address, _) in &calls.0 {
+        if *address < BASE_PRECOMPILE_ADDRESS || *address > highest {
+            return Err(PrecompileCallViolation::AddressOutOfRange {
+                address: *address,
+                lowest: BASE_PRECOMPILE_ADDRESS,
+                highest,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::types::ReadPrecompileInput;
+    use alloy_primitives::{Bytes, address};
+
+    const PRECOMPILE: Address = address!("0x0000000000000000000000000000000000000801");
+
+    fn input(data: &'static [u8], gas_limit: u64) -> ReadPrecompileInput {
+        ReadPrecompileInput { input: Bytes::from_static(data), gas_limit }
+    }
+
+    fn ok(gas_used: u64) -> ReadPrecompileResult {
+        ReadPrecompileResult::Ok { gas_used, bytes: Bytes::from_static(&[0; 32]) }
+    }
+
+    #[test]
+    fn accepts_consistent_calls() {
+        let calls = ReadPrecompileCalls(vec![(
+            PRECOMPILE,
+            vec![
+                (input(&[1], 100), ok(100)),
+                (input(&[1], 50), ReadPrecompileResult::OutOfGas),
+                (input(&[2], 100), ReadPrecompileResult::Error),
+                (input(&[1], 100), ok(100)),
+            ],
+        )]);
+        assert_eq!(validate_read_precompile_calls(&calls), Ok(()));
+    }
+
+    #[test]
+    fn rejects_gas_used_above_limit() {
+        let calls = ReadPrecompileCalls(vec![(
+            PRECOMPILE,
+            vec![(input(&[1], 100), ok(40)), (input(&[2], 100), ok(u64::MAX))],
+        )]);
+        assert_eq!(
+            validate_read_precompile_calls(&calls),
+            Err(PrecompileCallViolation::GasUsedExceedsLimit {
+                address: PRECOMPILE,
+                index: 1,
+                gas_used: u64::MAX,
+                gas_limit: 100,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_running_out_of_gas_within_the_gas_used_of_a_success() {
+        // Running out of gas below the gas a success used is consistent, in either order
+        let calls = ReadPrecompileCalls(vec![(
+            PRECOMPILE,
+            vec![
+                (input(&[1], 30), ReadPrecompileResult::OutOfGas),
+                (input(&[1], 100), ok(40)),
+                (input(&[1], 39), ReadPrecompileResult::OutOfGas),
+                (input(&[2], 60), ReadPrecompileResult::OutOfGas),
+            ],
+        )]);
+        assert_eq!(validate_read_precompile_calls(&calls), Ok(()));
+
+        for calls in [
+            vec![(input(&[1], 100), ok(40)), (input(&[1], 50), ReadPrecompileResult::OutOfGas)],
+            vec![(input(&[1], 50), ReadPrecompileResult::OutOfGas), (input(&[1], 100), ok(40))],
+        ] {
+            let calls = ReadPrecompileCalls(vec![(PRECOMPILE, calls)]);
+            assert_eq!(
+                validate_read_precompile_calls(&calls),
+                Err(PrecompileCallViolation::OutOfGasWithinLimit {
+                    address: PRECOMPILE,
+                    index: 1,
+                    gas_limit: 50,
+                    gas_used: 40,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn only_blocks_older_than_the_age_are_historical() {
+        let now = 1_700_000_000;
+        let age = Some(DEFAULT_HISTORICAL_BLOCK_AGE);
+        assert!(is_historical(now - 2 * 24 * 60 * 60, now, age));
+        assert!(!is_historical(now - 60, now, age));
+        // Blocks ahead of the local clock
+        assert!(!is_historical(now + 60, now, age));
+        assert!(!is_historical(0, now, None));
+    }
+
+    #[test]
+    fn rejects_conflicting_results() {
+        let calls = ReadPrecompileCalls(vec![(
+            PRECOMPILE,
+            vec![(input(&[1], 100), ok(40)), (input(&[1], 100), ReadPrecompileResult::OutOfGas)],
+        )]);
+        assert_eq!(
+            validate_read_precompile_calls(&calls),
+            Err(PrecompileCallViolation::ConflictingResult { address: PRECOMPILE, index: 1 })
+        );
+    }
+
+    #[test]
+    fn rejects_precompiles_outside_the_address_range() {
+        let calls = |address| ReadPrecompileCalls(vec![(address, vec![(input(&[1], 100), ok(1))])]);
+        let highest = address!("0x0000000000000000000000000000000000000805");
+        assert_eq!(validate_precompile_addresses(&calls(PRECOMPILE), Some(highest)), Ok(()));
+
+        let above = address!("0x0000000000000000000000000000000000000806");
+        assert_eq!(
+            validate_precompile_addresses(&calls(above), Some(highest)),
+            Err(PrecompileCallViolation::AddressOutOfRange {
+                address: above,
+                lowest: BASE_PRECOMPILE_ADDRESS,
+                highest,
+            })
+        );
+        // Without a recorded highest address only the base address is checked
+        assert_eq!(validate_precompile_addresses(&calls(above), None), Ok(()));
+
+        let below = Address::with_last_byte(0x01);
+        assert!(validate_precompile_addresses(&calls(below), None).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Transaction hash checks of HL blocks.
+//!
+//! System transactions carry fabricated signatures, so nothing keeps their hashes apart from the
+//! hashes of user transactions, and the same system action shows up twice when hour files
+//! overlap. Either would point the transaction hash index at the wrong transaction, so a block
+//! repeating a hash, or holding one already indexed at another position, is rejected, unless
+//! `--tolerate-duplicate-tx-hashes` only logs it.
+
+use alloy_consensus::transaction::TxHashRef;
+use alloy_primitives::TxHash;
+use reth::consensus::ConsensusError;
+use reth_provider::{BlockBodyIndicesProvider, ProviderResult, TransactionsProvider};
+use tracing::warn;
+
+use crate::node::primitives::TransactionSigned;
+
+/// Position of a transaction in the chain: its block number and its index in the block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxLocation {
+    pub block: u64,
+    pub index: u64,
+}
+
+/// The node's transaction hash index, as far as the checks need it.
+pub trait TxHashIndex: Send + Sync + std::fmt::Debug {
+    /// Returns where the transaction with `hash` is indexed, if anywhere.
+    fn location(&self, hash: TxHash) -> ProviderResult<Option<TxLocation>>;
+}
+
+/// Looks hashes up in the `TransactionHashNumbers` table, which is one read for the hashes that
+/// aren't indexed. The transaction itself is never read.
+impl<P> TxHashIndex for P
+where
+    P: TransactionsProvider + BlockBodyIndicesProvider + std::fmt::Debug,
+{
+    fn location(&self, hash: TxHash) -> ProviderResult<Option<TxLocation>> {
+        let Some(number) = self.transaction_id(hash)? else { return Ok(None) };
+        let Some(block) = self.transaction_block(number)? else { return Ok(None) };
+        let Some(indices) = self.block_body_indices(block)? else { return Ok(None) };
+        Ok(Some(TxLocation { block, index: number - indices.first_tx_num }))
+    }
+}
+
+fn kind(tx: &TransactionSigned) -> &'static str {
+    if tx.is_system_transaction() { "system" } else { "user" }
+}
+
+/// Validates that the transactions of block `number`, system transactions included, have unique
+/// hashes that `index` doesn't already hold at another position. The hashes the transactions
+/// cache are used, so nothing is hashed again.
+///
+/// A failed lookup in the index is logged rather than rejecting the block.
+pub fn validate_unique_tx_hashes(
+    number: u64,
+    transactions: &[TransactionSigned],
+    index: Option<&dyn TxHashIndex>,
+) -> Result<(), ConsensusError> {
+    let mut seen = std::collections::HashMap::with_capacity(transactions.len());
+    let mut collisions = Vec::new();
+    for (position, tx) in transactions.iter().enumerate() {
+        let hash = *tx.tx_hash();
+        if let Some(first) = seen.insert(hash, position) {
+            collisions.push(format!(
+                "{} transaction {hash} at index {position} repeats the {} transaction at index \
+                 {first}",
+                kind(tx),
+                kind(&transactions[first])
+            ));
+            continue;
+        }
+
+        let Some(index) = index else { continue };
+        let here = TxLocation { block: number, index: position as u64 };
+        match index.location(hash) {
+            Ok(Some(indexed)) if indexed != here => collisions.push(format!(
+                "{} transaction {hash} at index {position} is already indexed at index {} of \
+                 block {}",
+                kind(tx),
+                indexed.index,
+                indexed.block
+            )),
+            Ok(_) => {}
+            Err(err) => warn!(number, %hash, %err, "Failed to look up transaction hash"),
+        }
+    }
+
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    Err(ConsensusError::Other(format!(
+        "block {number} has colliding transaction hashes: {}",
+        collisions.join("; ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::{Signed, TxLegacy};
+    use alloy_primitives::{Signature, U256};
+    use reth_primitives::TransactionSigned as RethTxSigned;
+    use std::collections::HashMap;
+
+    fn transaction(gas_price: u128, nonce: u64) -> TransactionSigned {
+        let tx = TxLegacy { gas_price, nonce, ..Default::default() };
+        let signature = Signature::new(U256::ZERO, U256::from(nonce + 1), false);
+        TransactionSigned::Default(RethTxSigned::Legacy(Signed::new_unhashed(tx, signature)))
+    }
+
+    #[derive(Debug, Default)]
+    struct MockIndex(HashMap<TxHash, TxLocation>);
+
+    impl TxHashIndex for MockIndex {
+        fn location(&self, hash: TxHash) -> ProviderResult<Option<TxLocation>> {
+            Ok(self.0.get(&hash).copied())
+        }
+    }
+
+    #[test]
+    fn repeated_hash_within_a_block_is_rejected() {
+        // The same system action twice, e.g. from overlapping hour files
+        let system = transaction(0, 0);
+        let hash = *system.tx_hash();
+        let transactions = [system.clone(), transaction(1, 1), system];
+        assert!(validate_unique_tx_hashes(5, &transactions[..2], None).is_ok());
+
+        let err = validate_unique_tx_hashes(5, &transactions, None).unwrap_err().to_string();
+        assert!(err.contains("block 5"), "{err}");
+        let expected = format!(
+            "system transaction {hash} at index 2 repeats the system transaction at index 0"
+        );
+        assert!(err.contains(&expected), "{err}");
+    }
+
+    #[test]
+    fn hash_indexed_elsewhere_is_rejected() {
+        let (system, user) = (transaction(0, 0), transaction(1, 1));
+        let transactions = [system.clone(), user.clone()];
+        let mut index = MockIndex::default();
+        // Re-importing the block finds its own transactions where they are
+        index.0.insert(*system.tx_hash(), TxLocation { block: 5, index: 0 });
+        assert!(validate_unique_tx_hashes(5, &transactions, Some(&index)).is_ok());
+
+        // A fabricated system transaction hashing like a user transaction of an earlier block
+        index.0.insert(*user.tx_hash(), TxLocation { block: 3, index: 4 });
+        let err =
+            validate_unique_tx_hashes(5, &transactions, Some(&index)).unwrap_err().to_string();
+        assert!(err.contains(&user.tx_hash().to_string()), "{err}");
+        assert!(err.contains("at index 1 is already indexed at index 4 of block 3"), "{err}");
+    }
+}
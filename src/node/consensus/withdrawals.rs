@@ -0,0 +1,100 @@
+//! Withdrawals checks of HL blocks.
+//!
+//! HyperEVM has no beacon chain, so no block ever withdraws anything. After Shanghai, blocks carry
+//! an empty withdrawals list and the matching root, as the block assembler builds them; before
+//! Shanghai, they carry neither. Anything else comes from a malformed block.
+
+use alloy_consensus::{BlockHeader, EMPTY_ROOT_HASH};
+use alloy_eips::eip4895::Withdrawals;
+use reth::consensus::ConsensusError;
+use reth_primitives::GotExpected;
+
+/// Validates the withdrawals of a block and its header's withdrawals root, given whether
+/// Shanghai is active at the block's timestamp.
+pub fn validate_withdrawals(
+    header: &impl BlockHeader,
+    withdrawals: Option<&Withdrawals>,
+    shanghai_active: bool,
+) -> Result<(), ConsensusError> {
+    if !shanghai_active {
+        if header.withdrawals_root().is_some() {
+            return Err(ConsensusError::WithdrawalsRootUnexpected);
+        }
+        if withdrawals.is_some() {
+            return Err(ConsensusError::Other("withdrawals before Shanghai".to_string()));
+        }
+        return Ok(());
+    }
+
+    let root = header.withdrawals_root().ok_or(ConsensusError::WithdrawalsRootMissing)?;
+    let withdrawals = withdrawals.ok_or(ConsensusError::BodyWithdrawalsMissing)?;
+    if !withdrawals.is_empty() {
+        return Err(ConsensusError::Other(format!(
+            "block has {} withdrawals, HL blocks have none",
+            withdrawals.len()
+        )));
+    }
+    if root != EMPTY_ROOT_HASH {
+        return Err(ConsensusError::BodyWithdrawalsRootDiff(
+            GotExpected { got: EMPTY_ROOT_HASH, expected: root }.into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::Header;
+    use alloy_eips::eip4895::Withdrawal;
+    use alloy_primitives::{Address, B256};
+
+    fn header(withdrawals_root: Option<B256>) -> Header {
+        Header { withdrawals_root, ..Default::default() }
+    }
+
+    #[test]
+    fn shanghai_requires_empty_withdrawals() {
+        let empty = Withdrawals::default();
+        let header_with_root = header(Some(EMPTY_ROOT_HASH));
+        assert!(validate_withdrawals(&header_with_root, Some(&empty), true).is_ok());
+
+        assert!(matches!(
+            validate_withdrawals(&header(None), Some(&empty), true),
+            Err(ConsensusError::WithdrawalsRootMissing)
+        ));
+        assert!(matches!(
+            validate_withdrawals(&header_with_root, None, true),
+            Err(ConsensusError::BodyWithdrawalsMissing)
+        ));
+
+        let spurious = Withdrawals::new(vec![Withdrawal {
+            index: 0,
+            validator_index: 1,
+            address: Address::repeat_byte(0xaa),
+            amount: 1_000_000_000,
+        }]);
+        let spurious_root = alloy_consensus::proofs::calculate_withdrawals_root(&spurious);
+        assert!(matches!(
+            validate_withdrawals(&header(Some(spurious_root)), Some(&spurious), true),
+            Err(ConsensusError::Other(_))
+        ));
+        assert!(matches!(
+            validate_withdrawals(&header(Some(B256::repeat_byte(1))), Some(&empty), true),
+            Err(ConsensusError::BodyWithdrawalsRootDiff(_))
+        ));
+    }
+
+    #[test]
+    fn pre_shanghai_forbids_withdrawals() {
+        assert!(validate_withdrawals(&header(None), None, false).is_ok());
+        assert!(matches!(
+            validate_withdrawals(&header(Some(EMPTY_ROOT_HASH)), None, false),
+            Err(ConsensusError::WithdrawalsRootUnexpected)
+        ));
+        assert!(matches!(
+            validate_withdrawals(&header(None), Some(&Withdrawals::default()), false),
+            Err(ConsensusError::Other(_))
+        ));
+    }
+}
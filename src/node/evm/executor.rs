@@ -279,9 +279,8 @@ fn address_to_u64(address: Address) -> u64 {
 }
 
 fn fill_all_precompiles(extras: &HlExtras, precompiles_mut: &mut PrecompilesMap) {
-    let lowest_address = 0x800;
-    let highest_address = extras.highest_precompile_address.map_or(0x80D, address_to_u64);
-    for address in lowest_address..=highest_address {
+    let range = extras.precompile_address_range();
+    for address in address_to_u64(*range.start())..=address_to_u64(*range.end()) {
         let address = Address::from(U160::from(address));
         precompiles_mut.apply_precompile(&address, |f| {
             if let Some(precompile) = f {
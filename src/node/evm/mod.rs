@@ -33,6 +33,7 @@ mod patch;
 pub mod receipt_builder;
 
 pub use executor::apply_precompiles;
+pub(crate) use patch::patch_mainnet_after_tx;
 
 /// HL EVM implementation.
 ///
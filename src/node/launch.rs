@@ -0,0 +1,504 @@
+//! Launching an [`HlNode`] with the HL RPC extensions, as done by the `reth-hl` binary.
+//!
+//! Everything a launched node needs is owned by the node, so several nodes can be launched in
+//! one process, e.g. by integration tests.
+
+use crate::{
+    addons::{
+        call_forwarder::{self, CallForwarderApiServer},
+        forwarding::ForwardedMethod,
+        hl_node_compliance::{
+            EthBlockByApiServer, EthBlockCountApiServer, HlBlockCountExt, HlBlockExt,
+            hide_system_txs, install_hl_node_compliance,
+        },
+        raw_data::{
+            HlDebugRawApiServer, HlRawBlockApiServer, HlRawDataExt, HlUserTransactionsRootApiServer,
+        },
+        replay_check::{ReplayCheckConfig, ReplayChecker},
+        rpc_rate_limit::RpcRateLimiter,
+        state_diff::{HlStateDiffApiServer, HlStateDiffExt},
+        subscribe_fixup::SubscribeFixup,
+        sync_replica::{ReplicaSyncReader, open_replica_reader},
+        sync_server::{
+            HlSyncApiServer, HlSyncServer, SyncBlockReader, SyncServerListener, SyncSourceStatus,
+        },
+        sync_static_files::StaticFileSyncReader,
+        system_trace::{HlSystemTraceApiServer, HlSystemTraceExt},
+        system_tx_lookup::{
+            EthTransactionByHashApiServer, HlSystemTxApiServer, HlSystemTxLookupExt,
+        },
+        trace::{HlTraceApiServer, HlTraceExt},
+        tx_forwarder::{self, EthForwarderApiServer},
+        tx_tracker::HlForwardedTxApiServer,
+        upstream::{KEEPALIVE_INTERVAL, UpstreamClient},
+    },
+    chainspec::HlChainSpec,
+    node::{
+        HlNode,
+        cli::HlNodeArgs,
+        network::block_import::status::EngineStatus,
+        preflight::Preflight,
+        rpc::{
+            call_many::{HlCallManyApiServer, HlCallManyExt},
+            engine_status::{HlEngineStatusApiServer, HlEngineStatusExt},
+            node_info::{HlNodeInfoApiServer, HlNodeInfoExt},
+            precompile::{
+                HlBlockPrecompileApiServer, HlBlockPrecompileExt,
+                HlPrecompileAddressRangeApiServer, HlPrecompileReceiptApiServer,
+            },
+            spot_meta::{HlSpotMetaApiServer, HlSpotMetaExt},
+            trace_timeout::{TIMED_TRACE_METHODS, TraceTimeouts},
+        },
+        spot_meta::init as spot_meta_init,
+        status_log::StatusLogger,
+        storage::{prune::PrecompileCallsPruner, tables::Tables},
+    },
+    pseudo_peer::BlockSourceConfig,
+};
+use futures::{FutureExt, future::BoxFuture};
+use reth::{
+    builder::{NodeBuilder, NodeHandle, WithLaunchContext},
+    rpc::{api::EthPubSubApiServer, builder::RpcServerHandle, eth::RpcNodeCore},
+};
+use reth_db::DatabaseEnv;
+use reth_provider::CanonStateSubscriptions;
+use reth_rpc_server_types::RethRpcModule;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::oneshot;
+use tracing::{error, info, warn};
+
+/// A launched node.
+pub struct HlNodeHandle {
+    /// Handle to the node's RPC servers, e.g. for their addresses.
+    pub rpc: RpcServerHandle,
+    /// Resolves when the node exits, or with the [`PseudoPeerError`] that stopped its block
+    /// source.
+    ///
+    /// [`PseudoPeerError`]: crate::pseudo_peer::PseudoPeerError
+    pub exit: BoxFuture<'static, eyre::Result<()>>,
+    /// Outcome of the block imports, e.g. to wait for a block to become the canonical head.
+    pub engine_status: EngineStatus,
+    /// Stops the engine once it wrote the blocks it holds in memory to the database and static
+    /// files, e.g. the last batch of an import.
+    pub persist: BoxFuture<'static, eyre::Result<()>>,
+}
+
+impl std::fmt::Debug for HlNodeHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HlNodeHandle").field("rpc", &self.rpc).finish_non_exhaustive()
+    }
+}
+
+/// Launches an [`HlNode`] configured by `ext`, with the block source given on the command line.
+pub async fn launch_hl_node(
+    builder: WithLaunchContext<NodeBuilder<Arc<DatabaseEnv>, HlChainSpec>>,
+    ext: HlNodeArgs,
+) -> eyre::Result<HlNodeHandle> {
+    let block_source_config = ext.block_source_args.parse().await?;
+    launch_hl_node_with(builder, ext, block_source_config).await
+}
+
+/// Like [`launch_hl_node`], importing blocks from `block_source_config` instead of the block
+/// source given on the command line.
+pub async fn launch_hl_node_with(
+    mut builder: WithLaunchContext<NodeBuilder<Arc<DatabaseEnv>, HlChainSpec>>,
+    ext: HlNodeArgs,
+    block_source_config: Option<BlockSourceConfig>,
+) -> eyre::Result<HlNodeHandle> {
+    let default_upstream_rpc_url = builder.config().chain.official_rpc_url();
+    let chain_id = builder.config().chain.inner.chain().id();
+    let chain_spec = builder.config().chain.clone();
+    let upstream_rpc_url =
+        ext.upstream_rpc_url.clone().unwrap_or_else(|| default_upstream_rpc_url.to_owned());
+
+    if !ext.skip_preflight {
+        let datadir = builder.config().datadir();
+        let report = Preflight::new(chain_spec.clone(), datadir.data_dir())
+            .with_block_source(block_source_config.clone())
+            .with_upstream_rpc_url(upstream_rpc_url.clone())
+            .with_spot_meta(ext.preflight_spot_meta)
+            .run()
+            .await;
+        report.log();
+        report.ensure_passed()?;
+    }
+
+    let enable_sync_server = ext.enable_sync_server;
+    let sync_server_max_response_bytes = ext.sync_server_max_response_bytes;
+    let sync_server_payload_cache_size = ext.sync_server_payload_cache_size;
+    let sync_server_limits = ext.sync_server_limits;
+    let rpc_rate_limiter = Arc::new(RpcRateLimiter::new(&ext.rpc_rate_limits));
+    let own_listener_rate_limiter = rpc_rate_limiter.clone();
+    let trace_timeouts = TraceTimeouts::new(ext.rpc_trace_timeout);
+    let sync_server_max_ready_lag = ext.sync_server_max_ready_lag;
+    let sync_server_legacy_latest_block_number = ext.sync_server_legacy_latest_block_number;
+    let sync_server_serve_lag = ext.sync_server_serve_lag;
+    let sync_server_listener = match (ext.sync_server_uds.clone(), ext.sync_server_addr) {
+        (Some(path), _) => Some(SyncServerListener::Uds(path)),
+        (None, Some(addr)) => Some(SyncServerListener::Tcp(addr)),
+        (None, None) => None,
+    };
+    let sync_replica =
+        ext.sync_replica_datadir.clone().map(|path| (path, ext.sync_replica_max_lag));
+    let replay_check = ReplayCheckConfig {
+        interval: ext.replay_check_interval,
+        halt_on_divergence: ext.halt_on_divergence,
+    };
+    // Shared by the block source and the sync server, which reports the lag between them
+    let sync_source_status = SyncSourceStatus::default();
+    let has_block_source = block_source_config.is_some();
+    let block_cache_snapshot = ext
+        .block_cache_snapshot(&builder.config().datadir().data_dir())
+        .filter(|_| has_block_source);
+    let eth_get_proof_window =
+        (!ext.experimental_eth_get_proof).then_some(ext.eth_get_proof_window);
+    let forkchoice_policy = ext.forkchoice_policy()?;
+    let forwarding_policy = ext.forwarding_policy()?;
+    let tx_forward_policy = ext.tx_forward_policy()?;
+    let tx_tracker = ext.forwarded_tx_tracker();
+    let rpc_tx_tracker = tx_tracker.clone();
+    let archive_window = ext.archive_window();
+    if let Some(window) = archive_window {
+        window.apply_to(&mut builder.config_mut().pruning);
+    }
+    let (node, engine_handle_tx) = HlNode::new(
+        block_source_config.map(|config| {
+            config
+                .with_source_status(sync_source_status.clone())
+                .with_cache_snapshot(block_cache_snapshot.clone())
+        }),
+        ext.debug_cutoff_height,
+        ext.allow_network_overrides,
+        eth_get_proof_window,
+        ext.tolerate_invalid_precompile_calls,
+        archive_window,
+        forkchoice_policy,
+    );
+    let (attempts, backoff) = ext.spot_meta_persist_retries();
+    let node = node
+        .with_new_block_limits(ext.new_block_limits())
+        .with_tolerate_duplicate_tx_hashes(ext.tolerate_duplicate_tx_hashes)
+        .with_historical_block_age(ext.historical_block_age())
+        .with_spot_meta_persist_retries(attempts, backoff)
+        .with_pseudo_peer_key(ext.pseudo_peer_key.clone());
+    let engine_status = node.engine_status().clone();
+    let handle_engine_status = engine_status.clone();
+    let status_logger = (ext.status_log_interval > 0).then(|| {
+        let interval = Duration::from_secs(ext.status_log_interval);
+        StatusLogger::new(engine_status.clone(), sync_source_status.clone(), interval)
+    });
+    let fatal_rx = node.fatal_errors().take_receiver();
+    let spot_meta = node.spot_meta().clone();
+    let rpc_spot_meta = spot_meta.clone();
+    let db_spot_meta = spot_meta.clone();
+    let (own_sync_server_tx, own_sync_server_rx) = oneshot::channel();
+    let NodeHandle { node, node_exit_future: exit } = builder
+        .node(node)
+        .extend_rpc_modules(move |mut ctx| {
+            let upstream = UpstreamClient::new(&upstream_rpc_url)?;
+            if ext.forward_preconnect {
+                // Before the RPC servers start, so that the first forwarded request finds the
+                // connection
+                ctx.node().task_executor.spawn_critical(
+                    "upstream keep-alive",
+                    upstream.clone().keep_alive(KEEPALIVE_INTERVAL),
+                );
+            }
+
+            // Methods executed locally are left out, keeping reth's implementation
+            let mut tx_forwarder =
+                tx_forwarder::EthForwarderExt::new(upstream.client().clone(), chain_id)
+                    .with_chain_id_mismatch(ext.forward_chain_id_mismatch)
+                    .with_reject_pre_eip155(ext.forward_reject_pre_eip155)
+                    .with_block_gas_limit(HlChainSpec::BIG_BLOCK_GAS_LIMIT)
+                    .with_tracker(rpc_tx_tracker.clone())
+                    .with_policy(tx_forward_policy)
+                    .into_rpc();
+            forwarding_policy.retain_forwarded(&mut tx_forwarder);
+            ctx.modules.replace_configured(tx_forwarder)?;
+            ctx.modules.merge_configured(HlForwardedTxApiServer::into_rpc(rpc_tx_tracker))?;
+
+            let mut call_forwarder = call_forwarder::CallForwarderExt::new(
+                upstream.client().clone(),
+                ctx.registry.eth_api().clone(),
+            )
+            .into_rpc();
+            forwarding_policy.retain_forwarded(&mut call_forwarder);
+            ctx.modules.replace_configured(call_forwarder)?;
+
+            let forwarded: Vec<_> =
+                forwarding_policy.forwarded().map(ForwardedMethod::name).collect();
+            info!(methods = ?forwarded, "Forwarding to {}", upstream.url());
+
+            // This is a temporary workaround to fix the issue with custom headers
+            // affects `eth_subscribe[type=newHeads]`
+            ctx.modules.replace_configured(
+                SubscribeFixup::new(
+                    Arc::new(ctx.registry.eth_handlers().pubsub.clone()),
+                    Arc::new(ctx.registry.eth_api().provider().clone()),
+                    Box::new(ctx.node().task_executor.clone()),
+                )
+                .into_rpc(),
+            )?;
+
+            // Every method lists system transactions or leaves them out per
+            // `--expose-system-txs`, so that they agree on the transaction indices
+            let expose_system_txs = ext.exposes_system_txs();
+            if !expose_system_txs {
+                hide_system_txs(&mut ctx)?;
+            }
+            info!(expose_system_txs, "System transactions");
+            if ext.hl_node_compliant {
+                install_hl_node_compliance(&mut ctx)?;
+                info!("hl-node compliant mode enabled");
+            }
+
+            // System transactions by hash: explicit per mode in `eth_`, always in `hl_`
+            let eth_api = Arc::new(ctx.registry.eth_api().clone());
+            let task_executor = ctx.node().task_executor.clone();
+            let system_tx_lookup = || {
+                let spawner = Box::new(task_executor.clone());
+                HlSystemTxLookupExt::new(eth_api.clone(), expose_system_txs, spawner)
+            };
+            ctx.modules
+                .replace_configured(EthTransactionByHashApiServer::into_rpc(system_tx_lookup()))?;
+            ctx.modules.merge_configured(HlSystemTxApiServer::into_rpc(system_tx_lookup()))?;
+
+            ctx.modules.replace_configured(
+                HlBlockExt::new(eth_api.clone(), expose_system_txs).into_rpc(),
+            )?;
+            ctx.modules.replace_configured(
+                HlBlockCountExt::new(eth_api.clone(), expose_system_txs).into_rpc(),
+            )?;
+
+            // Only replaces the trace_ methods if the `trace` namespace is enabled
+            ctx.modules.replace_configured(
+                HlTraceExt::new(
+                    Arc::new(ctx.registry.trace_api()),
+                    Arc::new(ctx.registry.eth_api().clone()),
+                    expose_system_txs,
+                )
+                .into_rpc(),
+            )?;
+
+            match eth_get_proof_window {
+                Some(window) => info!("eth_getProof is limited to the latest {window} blocks"),
+                None => info!("eth_getProof is enabled for all blocks"),
+            }
+
+            if enable_sync_server {
+                let provider = ctx.registry.eth_api().provider().clone();
+                let mut reader: Arc<dyn SyncBlockReader> =
+                    Arc::new(StaticFileSyncReader::new(provider));
+                if let Some((path, max_lag)) = sync_replica {
+                    let replica = open_replica_reader(&path, chain_spec)?;
+                    reader = Arc::new(ReplicaSyncReader::new(reader, replica, max_lag));
+                }
+                let mut sync_server = HlSyncServer::new(
+                    reader,
+                    sync_server_max_response_bytes,
+                    sync_server_payload_cache_size,
+                    &sync_server_limits,
+                )
+                .with_legacy_latest_block_number(sync_server_legacy_latest_block_number)
+                .with_serve_lag(sync_server_serve_lag)
+                .with_chain_id(chain_id);
+                if has_block_source {
+                    sync_server = sync_server
+                        .with_source_status(sync_source_status, sync_server_max_ready_lag);
+                }
+                if let Some(listener) = sync_server_listener {
+                    // Served once the node is launched, so that failing to bind fails the launch
+                    let _ = own_sync_server_tx.send((sync_server, listener));
+                } else {
+                    ctx.modules.merge_configured(sync_server.into_rpc())?;
+                    info!("Sync server RPC enabled (serving blocks from static files)");
+                }
+            }
+
+            ctx.modules.merge_configured(HlBlockPrecompileApiServer::into_rpc(
+                HlBlockPrecompileExt::new(ctx.registry.eth_api().clone()),
+            ))?;
+            ctx.modules.merge_configured(HlPrecompileAddressRangeApiServer::into_rpc(
+                HlBlockPrecompileExt::new(ctx.registry.eth_api().clone()),
+            ))?;
+            ctx.modules.merge_configured(HlPrecompileReceiptApiServer::into_rpc(
+                HlBlockPrecompileExt::new(ctx.registry.eth_api().clone())
+                    .with_expose_system_txs(expose_system_txs),
+            ))?;
+            ctx.modules.merge_configured(HlCallManyApiServer::into_rpc(HlCallManyExt::new(
+                ctx.registry.eth_api().clone(),
+            )))?;
+            // Replays blocks like `debug_traceBlock`: only where the `debug` namespace is enabled
+            ctx.modules.merge_if_module_configured(
+                RethRpcModule::Debug,
+                HlSystemTraceExt::new(ctx.registry.debug_api(), ctx.registry.eth_api().clone())
+                    .into_rpc(),
+            )?;
+
+            // Raw encodings with the HL extensions; the debug_ ones only where the `debug`
+            // namespace is enabled
+            let provider = ctx.registry.eth_api().provider().clone();
+            let raw_data = || HlRawDataExt::new(provider.clone());
+            ctx.modules.add_or_replace_if_module_configured(
+                RethRpcModule::Debug,
+                HlDebugRawApiServer::into_rpc(raw_data()),
+            )?;
+            ctx.modules.merge_configured(HlRawBlockApiServer::into_rpc(raw_data()))?;
+            ctx.modules.merge_configured(HlUserTransactionsRootApiServer::into_rpc(raw_data()))?;
+
+            if ext.enable_state_diff_rpc {
+                let provider = ctx.registry.eth_api().provider().clone();
+                let evm_config = ctx.registry.eth_api().evm_config().clone();
+                ctx.modules
+                    .merge_configured(HlStateDiffExt::new(provider, evm_config).into_rpc())?;
+                info!("hl_getBlockStateDiff enabled");
+            }
+
+            ctx.modules.merge_configured(
+                HlEngineStatusExt::new(engine_status, ctx.registry.eth_api().provider().clone())
+                    .into_rpc(),
+            )?;
+
+            ctx.modules.merge_configured(
+                HlNodeInfoExt::new(
+                    ctx.registry.eth_api().network().clone(),
+                    ext.allow_network_overrides,
+                )
+                .into_rpc(),
+            )?;
+
+            // Only served where the `admin` namespace is enabled
+            ctx.modules.merge_if_module_configured(
+                RethRpcModule::Admin,
+                HlSpotMetaExt::new(rpc_spot_meta, chain_id).into_rpc(),
+            )?;
+
+            // Traces given a timeout by their tracer options are timed out without a default too
+            let timed = ctx.modules.methods_by(|name: &str| TIMED_TRACE_METHODS.contains(&name));
+            ctx.modules.replace_configured(trace_timeouts.wrap(timed))?;
+
+            // Last, so that the limits apply to whichever implementation serves each method
+            if !rpc_rate_limiter.is_empty() {
+                let limited =
+                    ctx.modules.methods_by(|name: &str| rpc_rate_limiter.limit(name).is_some());
+                let names: Vec<_> = limited.method_names().collect();
+                ctx.modules.replace_configured(rpc_rate_limiter.wrap(limited))?;
+                info!(methods = ?names, "RPC rate limits enabled");
+            }
+
+            Ok(())
+        })
+        .apply(move |mut builder| {
+            builder.db_mut().create_tables_for::<Tables>().expect("create tables");
+
+            let chain_id = builder.config().chain.inner.chain().id();
+            let db = builder.db_mut().clone();
+
+            // Set database handle for on-demand persistence
+            db_spot_meta.set_db(db.clone());
+
+            // Load spot metadata from database and initialize cache
+            spot_meta_init::load_spot_metadata_cache(&db_spot_meta, &db, chain_id);
+
+            builder
+        })
+        .launch()
+        .await?;
+
+    engine_handle_tx.send(node.beacon_engine_handle.clone()).unwrap();
+
+    if let Ok((sync_server, listener)) = own_sync_server_rx.try_recv() {
+        let handle = listener.serve(sync_server, &own_listener_rate_limiter).await?;
+        info!(%listener, "Sync server enabled on its own listener");
+        node.task_executor.spawn_critical("sync server listener", handle.stopped());
+    }
+
+    // A divergent block with `--halt-on-divergence` stops the node like a fatal error
+    let (diverged_tx, diverged_rx) = oneshot::channel();
+    if replay_check.interval > 0 {
+        let (provider, evm_config) = (node.provider.clone(), node.evm_config.clone());
+        let checker = ReplayChecker::new(provider, evm_config, node.chain_spec(), replay_check);
+        let notifications = node.provider.canonical_state_stream();
+        node.task_executor.spawn_critical("replay check", async move {
+            if let Err(err) = checker.run(notifications).await {
+                error!(target: "reth::cli", %err, "Replay check diverged, shutting down the node");
+                let _ = diverged_tx.send(err);
+            }
+        });
+        info!("Replay check re-executes every {} blocks", replay_check.interval);
+    }
+
+    node.task_executor.spawn_critical(
+        "forwarded transaction tracker",
+        tx_tracker.run(node.provider.canonical_state_stream()),
+    );
+
+    if let Some(status_logger) = status_logger {
+        node.task_executor.spawn_critical("status log", status_logger.run());
+    }
+
+    if let Some(window) = archive_window {
+        let pruner = PrecompileCallsPruner::new(node.provider.clone(), window);
+        node.task_executor.spawn_critical(
+            "archive window pruner",
+            pruner.run(node.provider.canonical_state_stream()),
+        );
+        info!("Archive window keeps the data of the latest {} blocks", window.blocks);
+    }
+
+    // Flush pending spot metadata writes before the database is closed
+    node.task_executor.spawn_critical_with_graceful_shutdown_signal(
+        "spot metadata shutdown",
+        move |shutdown| async move {
+            let _guard = shutdown.await;
+            spot_meta.shutdown();
+        },
+    );
+
+    // Save the block cache for the next start; blocks are still served until the signal
+    if let Some(snapshot) = block_cache_snapshot {
+        node.task_executor.spawn_critical_with_graceful_shutdown_signal(
+            "block cache snapshot",
+            move |shutdown| async move {
+                let _guard = shutdown.await;
+                let path = snapshot.path().display();
+                match snapshot.save() {
+                    Ok(saved) => info!(saved, %path, "Saved the block cache snapshot"),
+                    Err(err) => warn!(%err, %path, "Failed to save the block cache snapshot"),
+                }
+            },
+        );
+    }
+
+    // A pseudo peer that stopped for good leaves nothing to import: exit with its error
+    let exit = async move {
+        let fatal = async move {
+            match fatal_rx {
+                Some(mut fatal_rx) => fatal_rx.recv().await,
+                None => None,
+            }
+        };
+        tokio::select! {
+            result = exit => result,
+            Some(err) = fatal => Err(err.into()),
+            Ok(err) = diverged_rx => Err(err.into()),
+        }
+    }
+    .boxed();
+
+    let engine_shutdown = node.add_ons_handle.engine_shutdown.clone();
+    let persist = async move {
+        // Already requested by an earlier call otherwise
+        let Some(persisted) = engine_shutdown.shutdown() else { return Ok(()) };
+        persisted.await.map_err(|_| eyre::eyre!("the engine stopped before persisting its blocks"))
+    }
+    .boxed();
+
+    Ok(HlNodeHandle {
+        rpc: node.rpc_server_handle().clone(),
+        exit,
+        engine_status: handle_engine_status,
+        persist,
+    })
+}
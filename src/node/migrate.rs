@@ -1,5 +1,5 @@
 use alloy_consensus::Header;
-use alloy_primitives::{B256, BlockHash, Bytes, U256, b256, hex::ToHexExt};
+use alloy_primitives::{B256, BlockHash, Bytes, Sealable, U256, b256, hex::ToHexExt};
 use reth::{
     api::NodeTypesWithDBAdapter,
     args::{DatabaseArgs, DatadirArgs},
@@ -20,16 +20,26 @@ use reth_db_api::{
 };
 use reth_errors::ProviderResult;
 use reth_ethereum_primitives::EthereumReceipt;
+use reth_primitives_traits::BlockBody as _;
 use reth_provider::{
     DatabaseProvider, ProviderFactory, ReceiptProvider, StaticFileProviderFactory,
-    StaticFileSegment, StaticFileWriter,
+    StaticFileSegment, StaticFileWriter, TransactionsProvider,
     providers::{NodeTypesForProvider, StaticFileProvider},
     static_file::SegmentRangeInclusive,
 };
-use std::{fs::File, io::Write, path::PathBuf, sync::Arc};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 use tracing::{info, warn};
 
-use crate::{HlHeader, HlPrimitives, chainspec::HlChainSpec};
+use crate::{
+    HlBlockBody, HlHeader, HlPrimitives,
+    chainspec::HlChainSpec,
+    node::primitives::{BlockBody, TransactionSigned},
+};
 
 pub(crate) trait HlNodeType:
     NodeTypesForProvider<ChainSpec = HlChainSpec, Primitives = HlPrimitives>
@@ -40,6 +50,199 @@ impl<N: NodeTypesForProvider<ChainSpec = HlChainSpec, Primitives = HlPrimitives>
 pub(super) struct Migrator<N: HlNodeType> {
     data_dir: ChainPath<DataDirPath>,
     provider_factory: ProviderFactory<NodeTypesWithDBAdapter<N, Arc<DatabaseEnv>>>,
+    system_tx_count_source: Option<SystemTxCountSource>,
+    corrupt_headers: Mutex<CorruptHeaders>,
+    tx_root_mismatches: Mutex<TxRootMismatches>,
+}
+
+/// Headers in mdbx that failed to decode during migration.
+///
+/// Without `--skip-corrupt-headers`, the first one aborts the migration. Otherwise they are
+/// logged, left as they are in mdbx and reported in [`CorruptHeaders::REPORT_FILE`] in the data
+/// directory, so that the rest of the database migrates and they can be repaired by hand.
+///
+/// Headers in static files are stored contiguously, so one that can't be decoded can't be left
+/// out: it always aborts the migration with its block number.
+#[derive(Debug, Default)]
+struct CorruptHeaders {
+    skip: bool,
+    found: Vec<CorruptHeader>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CorruptHeader {
+    number: u64,
+    /// Where the header is stored, `mdbx` or `static_files`.
+    location: &'static str,
+    raw: Vec<u8>,
+}
+
+impl CorruptHeaders {
+    const REPORT_FILE: &'static str = "corrupt-headers.txt";
+
+    fn new(skip: bool) -> Self {
+        Self { skip, found: vec![] }
+    }
+
+    /// Fails unless corrupt headers of `location` are skipped.
+    fn check(&self, number: u64, location: &'static str, raw: &[u8]) -> eyre::Result<()> {
+        if location == STATIC_FILES {
+            return Err(corrupt_static_file_header(number, raw));
+        }
+        eyre::ensure!(
+            self.skip,
+            "Header {number} in {location} is corrupt: {}. Pass --skip-corrupt-headers to migrate \
+             the other headers and record it for repair",
+            raw.encode_hex()
+        );
+        Ok(())
+    }
+
+    /// Records a header of mdbx that can't be decoded, or fails unless corrupt headers are
+    /// skipped. The header is left as it is.
+    fn record(&mut self, number: u64, raw: &[u8]) -> eyre::Result<()> {
+        self.check(number, MDBX, raw)?;
+        warn!(number, location = MDBX, "Skipping corrupt header, it needs to be repaired by hand");
+        self.found.push(CorruptHeader { number, location: MDBX, raw: raw.to_vec() });
+        Ok(())
+    }
+
+    /// Writes one line per corrupt header: location, block number and raw header in hex.
+    fn write_report(&self, path: &Path) -> eyre::Result<()> {
+        let mut report = File::create(path)?;
+        for header in &self.found {
+            writeln!(report, "{} {} {}", header.location, header.number, header.raw.encode_hex())?;
+        }
+        Ok(())
+    }
+}
+
+/// Error of a header in static files that can't be decoded, which can't be skipped.
+fn corrupt_static_file_header(number: u64, raw: &[u8]) -> eyre::Report {
+    eyre::eyre!(
+        "Header {number} in {STATIC_FILES} is corrupt: {}. Headers in static files can't be \
+         skipped, repair it before migrating",
+        raw.encode_hex()
+    )
+}
+
+/// Whether the transactions roots of migrated headers are checked against their block bodies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum TxRootCheck {
+    #[default]
+    Off,
+    /// Mismatches are logged and reported.
+    Verify,
+    /// Mismatches are also corrected in the migrated headers.
+    Repair,
+}
+
+/// Migrated headers whose transactions root doesn't match the root recomputed from their block
+/// body, reported in [`TxRootMismatches::REPORT_FILE`] in the data directory.
+///
+/// The root of a correctly migrated header still commits to the block's user transactions, so
+/// a mismatch points at a migration bug rather than at the chain.
+///
+/// Repairing a root changes the header's hash: the repaired header is stored under its recomputed
+/// hash, and the parent hash of its child, which still names the old hash, is reported as a broken
+/// link to be repaired by hand.
+#[derive(Debug, Default)]
+struct TxRootMismatches {
+    check: TxRootCheck,
+    found: Vec<TxRootMismatch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TxRootMismatch {
+    number: u64,
+    stored: B256,
+    computed: B256,
+    /// Stored hash of the header, named by its child's parent hash.
+    hash: BlockHash,
+    /// Hash of the repaired header, when repairing.
+    repaired_hash: Option<BlockHash>,
+}
+
+impl TxRootMismatches {
+    const REPORT_FILE: &'static str = "tx-root-mismatches.txt";
+
+    fn new(check: TxRootCheck) -> Self {
+        Self { check, found: vec![] }
+    }
+
+    /// Recomputes the transactions root of the converted `headers` from the transactions of
+    /// their blocks, recording mismatches and correcting them, along with the header hash, when
+    /// repairing.
+    fn verify(
+        &mut self,
+        headers: &mut [(HlHeader, U256, BlockHash)],
+        transactions: Vec<Vec<TransactionSigned>>,
+    ) {
+        if self.check == TxRootCheck::Off {
+            return;
+        }
+        for ((header, _, hash), transactions) in headers.iter_mut().zip(transactions) {
+            let number = header.inner.number;
+            let body = HlBlockBody {
+                inner: BlockBody { transactions, ommers: vec![], withdrawals: None },
+                sidecars: None,
+                read_precompile_calls: None,
+                highest_precompile_address: None,
+            };
+            let computed = body.calculate_tx_root();
+            let stored = header.inner.transactions_root;
+            if stored == computed {
+                continue;
+            }
+
+            let repair = self.check == TxRootCheck::Repair;
+            warn!(number, %stored, %computed, repair, "Migrated transactions root mismatch");
+            let mut mismatch =
+                TxRootMismatch { number, stored, computed, hash: *hash, repaired_hash: None };
+            if repair {
+                header.inner.transactions_root = computed;
+                *hash = header.hash_slow();
+                mismatch.repaired_hash = Some(*hash);
+                warn!(
+                    number,
+                    old_hash = %mismatch.hash,
+                    new_hash = %hash,
+                    "Repaired header was rehashed, the parent hash of block {} still names the \
+                     old hash",
+                    number + 1
+                );
+            }
+            self.found.push(mismatch);
+        }
+    }
+
+    /// Writes one line per mismatch: block number, stored root and recomputed root, followed for
+    /// repaired headers by the old and new hash and the child whose parent hash is now broken.
+    fn write_report(&self, path: &Path) -> eyre::Result<()> {
+        let mut report = File::create(path)?;
+        for mismatch in &self.found {
+            write!(report, "{} {} {}", mismatch.number, mismatch.stored, mismatch.computed)?;
+            if let Some(repaired_hash) = mismatch.repaired_hash {
+                let child = mismatch.number + 1;
+                write!(report, " {} {repaired_hash} broken-parent-hash:{child}", mismatch.hash)?;
+            }
+            writeln!(report)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where migrated headers take their system transaction count from.
+///
+/// Old headers don't record it, so it is derived from the block. Both ways of deriving it are
+/// computed, and a block for which they disagree fails the migration unless a source is picked
+/// with `--system-tx-count-source`, in which case the mismatch is only logged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum SystemTxCountSource {
+    /// Receipts without cumulative gas used, which miscounts user transactions using no gas.
+    Receipts,
+    /// Transactions with a zero gas price.
+    Transactions,
 }
 
 impl<N: HlNodeType> Migrator<N> {
@@ -49,10 +252,19 @@ impl<N: HlNodeType> Migrator<N> {
         chain_spec: HlChainSpec,
         datadir: DatadirArgs,
         database_args: DatabaseArgs,
+        skip_corrupt_headers: bool,
+        tx_root_check: TxRootCheck,
+        system_tx_count_source: Option<SystemTxCountSource>,
     ) -> eyre::Result<Self> {
         let data_dir = datadir.clone().resolve_datadir(chain_spec.chain());
         let provider_factory = Self::provider_factory(chain_spec, datadir, database_args)?;
-        Ok(Self { data_dir, provider_factory })
+        Ok(Self {
+            data_dir,
+            provider_factory,
+            system_tx_count_source,
+            corrupt_headers: Mutex::new(CorruptHeaders::new(skip_corrupt_headers)),
+            tx_root_mismatches: Mutex::new(TxRootMismatches::new(tx_root_check)),
+        })
     }
 
     pub fn sf_provider(&self) -> StaticFileProvider<HlPrimitives> {
@@ -80,9 +292,54 @@ impl<N: HlNodeType> Migrator<N> {
         if migrated_mdbx || migrated_static_files {
             info!("Database migrated successfully");
         }
+
+        let corrupt_headers = self.corrupt_headers.lock().unwrap();
+        if !corrupt_headers.found.is_empty() {
+            let path = self.data_dir.data_dir().join(CorruptHeaders::REPORT_FILE);
+            corrupt_headers.write_report(&path)?;
+            warn!(
+                count = corrupt_headers.found.len(),
+                report = %path.display(),
+                "Corrupt headers were skipped and need to be repaired by hand"
+            );
+        }
+
+        let tx_root_mismatches = self.tx_root_mismatches.lock().unwrap();
+        if !tx_root_mismatches.found.is_empty() {
+            let path = self.data_dir.data_dir().join(TxRootMismatches::REPORT_FILE);
+            tx_root_mismatches.write_report(&path)?;
+            warn!(
+                count = tx_root_mismatches.found.len(),
+                repaired = tx_root_mismatches.check == TxRootCheck::Repair,
+                report = %path.display(),
+                "Migrated headers have transactions roots that don't match their block bodies"
+            );
+        } else if migrated_static_files && tx_root_mismatches.check != TxRootCheck::Off {
+            info!("Transactions roots of migrated headers match their block bodies");
+        }
         Ok(())
     }
 
+    /// Whether the header at `number` needs migrating.
+    ///
+    /// A corrupt header fails the migration unless corrupt headers are skipped, in which case its
+    /// neighbours are migrated.
+    fn needs_migration(
+        &self,
+        number: u64,
+        location: &'static str,
+        header: &[u8],
+    ) -> eyre::Result<bool> {
+        match header_format(header) {
+            HeaderFormat::Old => Ok(true),
+            HeaderFormat::New => Ok(false),
+            HeaderFormat::Corrupt => {
+                self.corrupt_headers.lock().unwrap().check(number, location, header)?;
+                Ok(true)
+            }
+        }
+    }
+
     fn conversion_tmp_dir(&self) -> PathBuf {
         self.data_dir.data_dir().join(Self::MIGRATION_PATH_SUFFIX)
     }
@@ -110,11 +367,11 @@ impl<'a, N: HlNodeType> MigratorMdbx<'a, N> {
 
         let migration_needed = {
             let first_is_old = match cursor.first()? {
-                Some((number, header)) => using_old_header(number, &header),
+                Some((number, header)) => self.0.needs_migration(number, MDBX, &header)?,
                 None => false,
             };
             let last_is_old = match cursor.last()? {
-                Some((number, header)) => using_old_header(number, &header),
+                Some((number, header)) => self.0.needs_migration(number, MDBX, &header)?,
                 None => false,
             };
             first_is_old || last_is_old
@@ -151,18 +408,30 @@ impl<'a, N: HlNodeType> MigratorMdbx<'a, N> {
         let mut cursor_read = db_env.tx_ref().cursor_read::<tables::Headers<Bytes>>()?;
         let mut tmp_writer = File::create(tmp_path)?;
         let mut count = 0;
-        let old_headers = cursor_read.walk(None)?.filter_map(|row| {
-            let (block_number, header) = row.ok()?;
-            if !using_old_header(block_number, &header) {
-                None
-            } else {
-                Some((block_number, Header::decompress(&header).ok()?))
-            }
-        });
-        for (block_number, header) in old_headers {
-            let receipt =
-                db_env.receipts_by_block(block_number.into())?.expect("Receipt not found");
-            let new_header = to_hl_header(receipt, header);
+        for row in cursor_read.walk(None)? {
+            let (block_number, raw) = row?;
+            let header = match header_format(&raw) {
+                HeaderFormat::New => continue,
+                HeaderFormat::Old => decode_old_header(block_number, MDBX, &raw),
+                HeaderFormat::Corrupt => Err(eyre::eyre!("neither in the old nor the new format")),
+            };
+            let header = match header {
+                Ok(header) => header,
+                Err(err) => {
+                    warn!(block_number, %err, "Failed to decode header");
+                    // Left as is in mdbx
+                    self.0.corrupt_headers.lock().unwrap().record(block_number, &raw)?;
+                    continue;
+                }
+            };
+            let receipt = db_env
+                .receipts_by_block(block_number.into())?
+                .ok_or_else(|| eyre::eyre!("Receipts of block {block_number} not found"))?;
+            let transactions = db_env
+                .transactions_by_block(block_number.into())?
+                .ok_or_else(|| eyre::eyre!("Transactions of block {block_number} not found"))?;
+            let new_header =
+                to_hl_header(receipt, &transactions, header, self.0.system_tx_count_source)?;
             tmp_writer.write_all(&rmp_serde::to_vec(&(block_number, new_header))?)?;
             count += 1;
         }
@@ -289,7 +558,14 @@ impl<'a, N: HlNodeType> MigrateStaticFiles<'a, N> {
             let sf_tmp_provider = StaticFileProvider::<HlPrimitives>::read_write(&conversion_tmp)?;
             let provider = self.0.provider_factory.provider()?;
             let block_range_for_filename = sf_provider.find_fixed_range(block_range.start());
-            migrate_single_static_file(&sf_tmp_provider, &sf_provider, &provider, block_range)?;
+            migrate_single_static_file(
+                &sf_tmp_provider,
+                &sf_provider,
+                &provider,
+                block_range,
+                self.0.system_tx_count_source,
+                &mut self.0.tx_root_mismatches.lock().unwrap(),
+            )?;
 
             self.move_static_files_for_segment(block_range_for_filename)?;
         }
@@ -306,7 +582,7 @@ impl<'a, N: HlNodeType> MigrateStaticFiles<'a, N> {
             return Ok(false);
         };
 
-        Ok(using_old_header(number, &row[0]))
+        self.0.needs_migration(number, STATIC_FILES, &row[0])
     }
 }
 
@@ -336,11 +612,38 @@ fn is_new_header(header: &[u8]) -> bool {
     rmp_serde::from_slice::<HlHeader>(header).is_ok()
 }
 
+const MDBX: &str = "mdbx";
+const STATIC_FILES: &str = "static_files";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeaderFormat {
+    Old,
+    New,
+    /// Neither or both formats, as the format is only guessed.
+    Corrupt,
+}
+
+fn header_format(header: &[u8]) -> HeaderFormat {
+    match (is_old_header(header), is_new_header(header)) {
+        (true, false) => HeaderFormat::Old,
+        (false, true) => HeaderFormat::New,
+        _ => HeaderFormat::Corrupt,
+    }
+}
+
+/// Decodes the header of block `number` in the old format.
+fn decode_old_header(number: u64, location: &'static str, header: &[u8]) -> eyre::Result<Header> {
+    Header::decompress(header)
+        .map_err(|err| eyre::eyre!("Failed to decode old header {number} in {location}: {err}"))
+}
+
 fn migrate_single_static_file<N: HlNodeType>(
     sf_out: &StaticFileProvider<HlPrimitives>,
     sf_in: &StaticFileProvider<HlPrimitives>,
     provider: &DatabaseProvider<Tx<RO>, NodeTypesWithDBAdapter<N, Arc<DatabaseEnv>>>,
     block_range: SegmentRangeInclusive,
+    system_tx_count_source: Option<SystemTxCountSource>,
+    tx_root_mismatches: &mut TxRootMismatches,
 ) -> Result<(), eyre::Error> {
     info!("Migrating block range {}...", block_range);
 
@@ -351,30 +654,107 @@ fn migrate_single_static_file<N: HlNodeType>(
         let block_range = chunk..=end;
         let headers = old_headers_range(sf_in, block_range.clone())?;
         let receipts = provider.receipts_by_block_range(block_range.clone())?;
-        assert_eq!(headers.len(), receipts.len());
+        let transactions = provider.transactions_by_block_range(block_range.clone())?;
+        eyre::ensure!(
+            headers.len() == receipts.len() && headers.len() == transactions.len(),
+            "Block range {block_range:?} has {} headers, {} receipt lists and {} transaction lists",
+            headers.len(),
+            receipts.len(),
+            transactions.len()
+        );
         let mut writer = sf_out.get_writer(*block_range.start(), StaticFileSegment::Headers)?;
-        let new_headers = std::iter::zip(headers, receipts)
-            .map(|(header, receipts)| {
-                let eth_header = Header::decompress(&header[0]).unwrap();
-                let hl_header = to_hl_header(receipts, eth_header);
-
-                let difficulty: U256 = CompactU256::decompress(&header[1]).unwrap().into();
-                let hash = BlockHash::decompress(&header[2]).unwrap();
-                (hl_header, difficulty, hash)
-            })
-            .collect::<Vec<_>>();
+        let mut new_headers = convert_headers(
+            *block_range.start(),
+            headers,
+            receipts,
+            &transactions,
+            system_tx_count_source,
+        )?;
+        tx_root_mismatches.verify(&mut new_headers, transactions);
         for header in new_headers {
             writer.append_header(&header.0, header.1, &header.2)?;
         }
-        writer.commit().unwrap();
+        writer.commit()?;
         info!("Migrated block range {:?}...", block_range);
     }
     Ok(())
 }
 
-fn to_hl_header(receipts: Vec<EthereumReceipt>, eth_header: Header) -> HlHeader {
-    let system_tx_count = receipts.iter().filter(|r| r.cumulative_gas_used == 0).count();
-    HlHeader::from_ethereum_header(eth_header, &receipts, system_tx_count as u64)
+/// Converts the static file header rows of consecutive blocks from `start`.
+///
+/// A header that can't be decoded fails the conversion with its block number, as the blocks after
+/// it can't be migrated without it.
+fn convert_headers(
+    start: u64,
+    headers: Vec<Vec<Vec<u8>>>,
+    receipts: Vec<Vec<EthereumReceipt>>,
+    transactions: &[Vec<TransactionSigned>],
+    system_tx_count_source: Option<SystemTxCountSource>,
+) -> eyre::Result<Vec<(HlHeader, U256, BlockHash)>> {
+    let rows = std::iter::zip(headers, receipts).zip(transactions);
+    let mut converted = Vec::with_capacity(rows.len());
+    for (number, ((columns, receipts), transactions)) in (start..).zip(rows) {
+        let [header, difficulty, hash] = columns.as_slice() else {
+            eyre::bail!("Header {number} in {STATIC_FILES} has {} columns", columns.len());
+        };
+        let hl_header = match header_format(header) {
+            HeaderFormat::New => rmp_serde::from_slice(header)?,
+            HeaderFormat::Old => {
+                let eth_header = decode_old_header(number, STATIC_FILES, header)?;
+                to_hl_header(receipts, transactions, eth_header, system_tx_count_source)?
+            }
+            HeaderFormat::Corrupt => return Err(corrupt_static_file_header(number, header)),
+        };
+
+        let difficulty: U256 = CompactU256::decompress(difficulty)
+            .map_err(|err| eyre::eyre!("Failed to decode difficulty of header {number}: {err}"))?
+            .into();
+        let hash = BlockHash::decompress(hash)
+            .map_err(|err| eyre::eyre!("Failed to decode hash of header {number}: {err}"))?;
+        converted.push((hl_header, difficulty, hash));
+    }
+    Ok(converted)
+}
+
+/// Converts an old header, counting its system transactions from `source`. Without a source, the
+/// counts from receipts and transactions must agree.
+fn to_hl_header(
+    receipts: Vec<EthereumReceipt>,
+    transactions: &[TransactionSigned],
+    eth_header: Header,
+    source: Option<SystemTxCountSource>,
+) -> eyre::Result<HlHeader> {
+    let from_receipts = receipts.iter().filter(|r| r.cumulative_gas_used == 0).count() as u64;
+    let from_transactions =
+        transactions.iter().filter(|tx| tx.is_system_transaction()).count() as u64;
+    let system_tx_count = match source {
+        None => {
+            eyre::ensure!(
+                from_receipts == from_transactions,
+                "Block {} has {from_receipts} system transactions by its receipts but \
+                 {from_transactions} by its transactions. Pass --system-tx-count-source to pick \
+                 the count to migrate",
+                eth_header.number
+            );
+            from_receipts
+        }
+        Some(source) => {
+            if from_receipts != from_transactions {
+                warn!(
+                    block = eth_header.number,
+                    from_receipts,
+                    from_transactions,
+                    ?source,
+                    "System transaction counts from receipts and transactions disagree"
+                );
+            }
+            match source {
+                SystemTxCountSource::Receipts => from_receipts,
+                SystemTxCountSource::Transactions => from_transactions,
+            }
+        }
+    };
+    Ok(HlHeader::from_ethereum_header(eth_header, &receipts, system_tx_count))
 }
 
 fn old_headers_range(
@@ -413,17 +793,168 @@ fn to_range<R: std::ops::RangeBounds<u64>>(bounds: R) -> std::ops::Range<u64> {
     start..end
 }
 
-fn using_old_header(number: u64, header: &[u8]) -> bool {
-    let deserialized_old = is_old_header(header);
-    let deserialized_new = is_new_header(header);
-
-    assert!(
-        deserialized_old ^ deserialized_new,
-        "Header is not valid: {} {}\ndeserialized_old: {}\ndeserialized_new: {}",
-        number,
-        header.encode_hex(),
-        deserialized_old,
-        deserialized_new
-    );
-    deserialized_old && !deserialized_new
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::{EMPTY_OMMER_ROOT_HASH, Signed, TxLegacy};
+    use alloy_primitives::{Signature, TxKind, address};
+    use reth_db::table::Compress;
+    use reth_primitives::TransactionSigned as RethTxSigned;
+
+    fn transaction(gas_price: u128) -> TransactionSigned {
+        let tx = TxLegacy {
+            gas_price,
+            to: TxKind::Call(address!("0x2222222222222222222222222222222222222222")),
+            ..Default::default()
+        };
+        let signature = Signature::new(U256::from(1), U256::from(1), false);
+        TransactionSigned::Default(RethTxSigned::Legacy(Signed::new_unhashed(tx, signature)))
+    }
+
+    fn receipt(cumulative_gas_used: u64) -> EthereumReceipt {
+        EthereumReceipt { cumulative_gas_used, success: true, ..Default::default() }
+    }
+
+    /// A static file header row: header, difficulty and hash.
+    fn row(header: Vec<u8>, number: u64) -> Vec<Vec<u8>> {
+        let difficulty = CompactU256::from(U256::from(number)).compress();
+        let hash = B256::with_last_byte(number as u8).compress();
+        vec![header, difficulty.as_ref().to_vec(), hash.as_ref().to_vec()]
+    }
+
+    fn old_header_bytes(header: Header) -> Vec<u8> {
+        Header { ommers_hash: EMPTY_OMMER_ROOT_HASH, ..header }.compress().as_ref().to_vec()
+    }
+
+    #[test]
+    fn system_tx_count_sources_disagree() {
+        // One system transaction, then a user transaction that used no gas
+        let transactions = [transaction(0), transaction(1)];
+        let receipts = vec![receipt(0), receipt(0)];
+
+        let convert =
+            |source| to_hl_header(receipts.clone(), &transactions, Header::default(), source);
+
+        let err = convert(None).unwrap_err();
+        assert!(err.to_string().contains("--system-tx-count-source"), "{err}");
+        let header = convert(Some(SystemTxCountSource::Receipts)).unwrap();
+        assert_eq!(header.extras.system_tx_count, 2);
+        let header = convert(Some(SystemTxCountSource::Transactions)).unwrap();
+        assert_eq!(header.extras.system_tx_count, 1);
+
+        // Counts that agree need no source
+        let header =
+            to_hl_header(vec![receipt(0), receipt(21_000)], &transactions, Header::default(), None)
+                .unwrap();
+        assert_eq!(header.extras.system_tx_count, 1);
+    }
+
+    #[test]
+    fn corrupt_static_file_header_fails_with_its_block_number() {
+        let old_header = |number| old_header_bytes(Header { number, ..Default::default() });
+        let corrupt = vec![0xde, 0xad, 0xbe, 0xef];
+        let convert = |headers: Vec<Vec<Vec<u8>>>| {
+            convert_headers(10, headers, vec![vec![]; 3], &[vec![], vec![], vec![]], None)
+        };
+
+        let converted = convert(vec![
+            row(old_header(10), 10),
+            row(old_header(11), 11),
+            row(old_header(12), 12),
+        ])
+        .unwrap();
+        let numbers: Vec<_> = converted.iter().map(|(header, ..)| header.inner.number).collect();
+        assert_eq!(numbers, [10, 11, 12]);
+        assert_eq!(converted[1].2, B256::with_last_byte(11));
+
+        // Nothing is written in place of the corrupt header
+        let err = convert(vec![
+            row(old_header(10), 10),
+            row(corrupt.clone(), 11),
+            row(old_header(12), 12),
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("Header 11 in static_files is corrupt"), "{err}");
+        // Even when corrupt headers are skipped
+        let err = CorruptHeaders::new(true).check(11, STATIC_FILES, &corrupt).unwrap_err();
+        assert!(err.to_string().contains("can't be skipped"), "{err}");
+    }
+
+    #[test]
+    fn skips_corrupt_mdbx_header_only_when_asked() {
+        let corrupt = vec![0xde, 0xad, 0xbe, 0xef];
+        let err = CorruptHeaders::new(false).record(11, &corrupt).unwrap_err();
+        assert!(err.to_string().contains("Header 11 in mdbx is corrupt"), "{err}");
+
+        let mut corrupt_headers = CorruptHeaders::new(true);
+        corrupt_headers.record(11, &corrupt).unwrap();
+        assert_eq!(
+            corrupt_headers.found,
+            [CorruptHeader { number: 11, location: MDBX, raw: corrupt }]
+        );
+    }
+
+    #[test]
+    fn verifies_and_repairs_tx_roots_of_migrated_range() {
+        // The root commits to user transactions only, leaving the system transaction out
+        let transactions = vec![vec![transaction(1), transaction(0)]; 3];
+        let expected = alloy_consensus::proofs::calculate_transaction_root(&transactions[0][..1]);
+        let broken = B256::repeat_byte(0xab);
+        let headers = [(10, expected), (11, broken), (12, expected)].map(|(number, root)| {
+            let header = Header { number, transactions_root: root, ..Default::default() };
+            row(old_header_bytes(header), number)
+        });
+
+        let migrate = |check| {
+            let mut converted = convert_headers(
+                10,
+                headers.to_vec(),
+                vec![vec![]; 3],
+                &transactions,
+                Some(SystemTxCountSource::Transactions),
+            )
+            .unwrap();
+            let mut mismatches = TxRootMismatches::new(check);
+            mismatches.verify(&mut converted, transactions.clone());
+            let roots: Vec<_> =
+                converted.iter().map(|(header, ..)| header.inner.transactions_root).collect();
+            (roots, converted, mismatches)
+        };
+
+        let old_hash = B256::with_last_byte(11);
+        let mismatch = TxRootMismatch {
+            number: 11,
+            stored: broken,
+            computed: expected,
+            hash: old_hash,
+            repaired_hash: None,
+        };
+        let (roots, converted, mismatches) = migrate(TxRootCheck::Verify);
+        assert_eq!(roots, [expected, broken, expected]);
+        assert_eq!(converted[1].2, old_hash);
+        assert_eq!(mismatches.found, [mismatch.clone()]);
+
+        // The repaired header is stored under its new hash, and its child's link is reported
+        let (roots, converted, mismatches) = migrate(TxRootCheck::Repair);
+        assert_eq!(roots, [expected; 3]);
+        let new_hash = converted[1].0.hash_slow();
+        assert_eq!(converted[1].2, new_hash);
+        assert_ne!(new_hash, old_hash);
+        assert_eq!(
+            mismatches.found,
+            [TxRootMismatch { repaired_hash: Some(new_hash), ..mismatch }]
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TxRootMismatches::REPORT_FILE);
+        mismatches.write_report(&path).unwrap();
+        let report = std::fs::read_to_string(path).unwrap();
+        assert_eq!(
+            report,
+            format!("11 {broken} {expected} {old_hash} {new_hash} broken-parent-hash:12\n")
+        );
+
+        let (roots, _, mismatches) = migrate(TxRootCheck::Off);
+        assert_eq!(roots, [expected, broken, expected]);
+        assert!(mismatches.found.is_empty());
+    }
 }
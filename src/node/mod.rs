@@ -10,13 +10,18 @@ use crate::{
                 validator::HlPayloadValidatorBuilder,
             },
         },
-        storage::HlStorage,
+        storage::{HlStorage, prune::ArchiveWindow},
+        types::SpotMetaContext,
     },
-    pseudo_peer::BlockSourceConfig,
+    pseudo_peer::{BlockSourceConfig, FatalErrors},
 };
-use consensus::HlConsensusBuilder;
+use consensus::{HlConsensusBuilder, precompile_calls::DEFAULT_HISTORICAL_BLOCK_AGE};
 use evm::HlExecutorBuilder;
-use network::HlNetworkBuilder;
+use network::{
+    HlNetworkBuilder, NewBlockLimits,
+    block_import::{forkchoice::ForkchoicePolicy, status::EngineStatus},
+    block_trace::BlockSpans,
+};
 use reth::{
     api::{FullNodeTypes, NodeTypes},
     builder::{
@@ -26,18 +31,24 @@ use reth::{
     },
 };
 use reth_engine_primitives::ConsensusEngineHandle;
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, path::PathBuf, sync::Arc, time::Duration};
 use tokio::sync::{Mutex, oneshot};
 
 pub mod cli;
+pub mod commands;
 pub mod consensus;
 pub mod engine;
 pub mod evm;
+pub mod launch;
 pub mod migrate;
 pub mod network;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod preflight;
 pub mod primitives;
 pub mod rpc;
 pub mod spot_meta;
+pub mod status_log;
 pub mod storage;
 pub mod types;
 
@@ -52,6 +63,18 @@ pub struct HlNode {
     block_source_config: Option<BlockSourceConfig>,
     debug_cutoff_height: Option<u64>,
     allow_network_overrides: bool,
+    eth_get_proof_window: Option<u64>,
+    tolerate_invalid_precompile_calls: bool,
+    historical_block_age: Option<Duration>,
+    tolerate_duplicate_tx_hashes: bool,
+    archive_window: Option<ArchiveWindow>,
+    forkchoice_policy: ForkchoicePolicy,
+    engine_status: EngineStatus,
+    spot_meta: SpotMetaContext,
+    fatal_errors: FatalErrors,
+    new_block_limits: NewBlockLimits,
+    block_spans: BlockSpans,
+    pseudo_peer_key: Option<PathBuf>,
 }
 
 impl HlNode {
@@ -59,6 +82,10 @@ impl HlNode {
         block_source_config: Option<BlockSourceConfig>,
         debug_cutoff_height: Option<u64>,
         allow_network_overrides: bool,
+        eth_get_proof_window: Option<u64>,
+        tolerate_invalid_precompile_calls: bool,
+        archive_window: Option<ArchiveWindow>,
+        forkchoice_policy: ForkchoicePolicy,
     ) -> (Self, oneshot::Sender<ConsensusEngineHandle<HlPayloadTypes>>) {
         let (tx, rx) = oneshot::channel();
         (
@@ -67,10 +94,70 @@ impl HlNode {
                 block_source_config,
                 debug_cutoff_height,
                 allow_network_overrides,
+                eth_get_proof_window,
+                tolerate_invalid_precompile_calls,
+                historical_block_age: Some(DEFAULT_HISTORICAL_BLOCK_AGE),
+                tolerate_duplicate_tx_hashes: false,
+                archive_window,
+                forkchoice_policy,
+                engine_status: EngineStatus::default(),
+                spot_meta: SpotMetaContext::default(),
+                fatal_errors: FatalErrors::default(),
+                new_block_limits: NewBlockLimits::default(),
+                block_spans: BlockSpans::default(),
+                pseudo_peer_key: None,
             },
             tx,
         )
     }
+
+    /// Bounds the `NewBlock` messages accepted from peers, see [`NewBlockLimits`].
+    pub fn with_new_block_limits(mut self, new_block_limits: NewBlockLimits) -> Self {
+        self.new_block_limits = new_block_limits;
+        self
+    }
+
+    /// Imports historical blocks with inconsistent read precompile calls with a warning, see
+    /// [`precompile_calls`](crate::node::consensus::precompile_calls).
+    pub fn with_historical_block_age(mut self, age: Option<Duration>) -> Self {
+        self.historical_block_age = age;
+        self
+    }
+
+    /// Imports blocks with colliding transaction hashes with a warning, see
+    /// [`tx_hashes`](crate::node::consensus::tx_hashes).
+    pub fn with_tolerate_duplicate_tx_hashes(mut self, tolerate: bool) -> Self {
+        self.tolerate_duplicate_tx_hashes = tolerate;
+        self
+    }
+
+    /// Retries failed spot metadata persists, see [`SpotMetaContext::with_persist_retries`].
+    pub fn with_spot_meta_persist_retries(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.spot_meta = self.spot_meta.with_persist_retries(attempts, backoff);
+        self
+    }
+
+    /// Reads the pseudo peer's secret key from `path` instead of the datadir, see
+    /// [`load_pseudo_peer_key`](crate::pseudo_peer::load_pseudo_peer_key).
+    pub fn with_pseudo_peer_key(mut self, pseudo_peer_key: Option<PathBuf>) -> Self {
+        self.pseudo_peer_key = pseudo_peer_key;
+        self
+    }
+
+    /// Forkchoice updates of the block import service, as served by `hl_engineStatus`.
+    pub fn engine_status(&self) -> &EngineStatus {
+        &self.engine_status
+    }
+
+    /// Spot metadata of this node, from which the senders of system transactions are derived.
+    pub fn spot_meta(&self) -> &SpotMetaContext {
+        &self.spot_meta
+    }
+
+    /// Failures of the pseudo peer that stop this node, see [`FatalErrors`].
+    pub fn fatal_errors(&self) -> &FatalErrors {
+        &self.fatal_errors
+    }
 }
 
 mod pool;
@@ -99,8 +186,19 @@ impl HlNode {
                 block_source_config: self.block_source_config.clone(),
                 debug_cutoff_height: self.debug_cutoff_height,
                 allow_network_overrides: self.allow_network_overrides,
+                engine_status: self.engine_status.clone(),
+                forkchoice_policy: self.forkchoice_policy,
+                spot_meta: self.spot_meta.clone(),
+                fatal_errors: self.fatal_errors.clone(),
+                new_block_limits: self.new_block_limits,
+                block_spans: self.block_spans.clone(),
+                pseudo_peer_key: self.pseudo_peer_key.clone(),
+            })
+            .consensus(HlConsensusBuilder {
+                tolerate_invalid_precompile_calls: self.tolerate_invalid_precompile_calls,
+                historical_block_age: self.historical_block_age,
+                tolerate_duplicate_tx_hashes: self.tolerate_duplicate_tx_hashes,
             })
-            .consensus(HlConsensusBuilder::default())
     }
 }
 
@@ -132,7 +230,11 @@ where
 
     fn add_ons(&self) -> Self::AddOns {
         HlNodeAddOns::new(
-            HlEthApiBuilder { _nt: PhantomData },
+            HlEthApiBuilder {
+                eth_get_proof_window: self.eth_get_proof_window,
+                archive_window: self.archive_window,
+                _nt: PhantomData,
+            },
             Default::default(),
             Default::default(),
             Default::default(),
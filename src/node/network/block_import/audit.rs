@@ -0,0 +1,122 @@
+//! Audit trail of the block import service: one event per block, logged at `info` under the
+//! `block_import` target, telling what the import pipeline did with the block.
+//!
+//! The new payload and the forkchoice update of a block run as separate futures. Both hold the
+//! block's [`BlockImportAudit`], which logs the block once both are done and it is dropped.
+
+use alloy_primitives::B256;
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+/// Target of the per-block import events, e.g. `RUST_LOG=block_import=info`.
+pub const TARGET: &str = "block_import";
+
+/// What the import pipeline did with a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum ImportCategory {
+    /// The block became the canonical head.
+    Applied,
+    /// The canonical chain was left as it is: the block was already imported, or it is valid but
+    /// the chain kept a preferred block as its head.
+    Skipped,
+    /// The block became the canonical head in place of another block at its height.
+    Reorg,
+    /// The block was rejected, the engine failed, or the engine gave no verdict on it.
+    Failed,
+}
+
+#[derive(Debug, Default)]
+struct AuditResult {
+    /// Whether the forkchoice update made the block the head, once it was accepted.
+    head: Option<bool>,
+    error: Option<String>,
+}
+
+/// The import of one block, logged when the last future holding it is done.
+#[derive(Debug)]
+pub(crate) struct BlockImportAudit {
+    number: u64,
+    hash: B256,
+    tx_count: usize,
+    /// Whether another block was canonical at the block's height when it arrived.
+    replaces_canonical: bool,
+    started: Instant,
+    result: Mutex<AuditResult>,
+}
+
+impl BlockImportAudit {
+    pub(crate) fn new(
+        number: u64,
+        hash: B256,
+        tx_count: usize,
+        replaces_canonical: bool,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            number,
+            hash,
+            tx_count,
+            replaces_canonical,
+            started: Instant::now(),
+            result: Default::default(),
+        })
+    }
+
+    /// Records that the engine accepted the forkchoice update, making the block the head or not.
+    pub(crate) fn accepted(&self, head: bool) {
+        self.result.lock().unwrap().head = Some(head);
+    }
+
+    /// Records why the import failed. The first error is kept.
+    pub(crate) fn failed(&self, error: impl Display) {
+        self.result.lock().unwrap().error.get_or_insert_with(|| error.to_string());
+    }
+
+    fn category(&self, result: &AuditResult) -> ImportCategory {
+        match (&result.error, result.head) {
+            (Some(_), _) | (None, None) => ImportCategory::Failed,
+            (None, Some(false)) => ImportCategory::Skipped,
+            (None, Some(true)) if self.replaces_canonical => ImportCategory::Reorg,
+            (None, Some(true)) => ImportCategory::Applied,
+        }
+    }
+}
+
+impl Drop for BlockImportAudit {
+    fn drop(&mut self) {
+        let result = std::mem::take(self.result.get_mut().unwrap());
+        let category = self.category(&result);
+        let error = match (&result.error, category) {
+            (Some(error), _) => Some(error.as_str()),
+            (None, ImportCategory::Failed) => Some("no forkchoice verdict from the engine"),
+            _ => None,
+        };
+        log(category, self.number, self.hash, self.tx_count, self.started.elapsed(), error);
+    }
+}
+
+/// Logs a block that was not sent to the engine.
+pub(crate) fn log_unsent(
+    category: ImportCategory,
+    number: u64,
+    hash: B256,
+    tx_count: usize,
+    error: Option<&str>,
+) {
+    log(category, number, hash, tx_count, Duration::ZERO, error);
+}
+
+fn log(
+    category: ImportCategory,
+    number: u64,
+    hash: B256,
+    tx_count: usize,
+    duration: Duration,
+    error: Option<&str>,
+) {
+    let duration_ms = duration.as_millis() as u64;
+    info!(target: TARGET, %category, number, %hash, tx_count, duration_ms, error, "Block import");
+}
@@ -0,0 +1,138 @@
+//! Safe and finalized blocks of the forkchoice updates sent by the block import service.
+//!
+//! HyperBFT commits are final, and the node only imports committed blocks, so by default the
+//! head is safe and finalized as soon as it is imported. Operators whose consumers expect the
+//! `safe` and `finalized` tags to trail the head, as on Ethereum, can configure the depths.
+
+use alloy_primitives::B256;
+use alloy_rpc_types::engine::ForkchoiceState;
+use reth_provider::{BlockHashReader, ProviderResult};
+use std::time::Duration;
+
+/// Default number of blocks the safe block trails the head by.
+pub const DEFAULT_SAFE_DEPTH: u64 = 0;
+
+/// Default number of blocks the finalized block trails the head by.
+pub const DEFAULT_FINALIZED_DEPTH: u64 = 0;
+
+/// Default interval of the forkchoice updates re-sent for the current head, in seconds.
+pub const DEFAULT_FORKCHOICE_INTERVAL: u64 = 5;
+
+/// Default time the engine has to answer a forkchoice update, in seconds.
+pub const DEFAULT_FORKCHOICE_TIMEOUT: u64 = 30;
+
+/// How the block import service fills in its forkchoice updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForkchoicePolicy {
+    /// Number of blocks the safe block trails the head by.
+    pub safe_depth: u64,
+    /// Number of blocks the finalized block trails the head by, at least `safe_depth`.
+    pub finalized_depth: u64,
+    /// Interval of the forkchoice updates re-sent for the current head, so that the safe and
+    /// finalized blocks are set after a restart and follow the head without new blocks.
+    pub refresh_interval: Option<Duration>,
+    /// Time the engine has to answer a forkchoice update before it is given up on as failed.
+    pub timeout: Duration,
+}
+
+impl Default for ForkchoicePolicy {
+    fn default() -> Self {
+        Self {
+            safe_depth: DEFAULT_SAFE_DEPTH,
+            finalized_depth: DEFAULT_FINALIZED_DEPTH,
+            refresh_interval: None,
+            timeout: Duration::from_secs(DEFAULT_FORKCHOICE_TIMEOUT),
+        }
+    }
+}
+
+impl ForkchoicePolicy {
+    /// Policy with the given depths, rejecting a finalized block above the safe block.
+    pub fn new(
+        safe_depth: u64,
+        finalized_depth: u64,
+        refresh_interval: Option<Duration>,
+    ) -> eyre::Result<Self> {
+        eyre::ensure!(
+            finalized_depth >= safe_depth,
+            "finalized depth {finalized_depth} is below safe depth {safe_depth}: the finalized \
+             block must not be above the safe block"
+        );
+        Ok(Self { safe_depth, finalized_depth, refresh_interval, ..Default::default() })
+    }
+
+    /// Sets the time the engine has to answer a forkchoice update.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Forkchoice state for the head `head_hash` at `head_number`, the safe and finalized blocks
+    /// read from the canonical chain below it. Depths reaching below genesis leave the block
+    /// unset.
+    pub fn state(
+        &self,
+        provider: &impl BlockHashReader,
+        head_hash: B256,
+        head_number: u64,
+    ) -> ProviderResult<ForkchoiceState> {
+        let block_at_depth = |depth: u64| -> ProviderResult<B256> {
+            if depth == 0 {
+                return Ok(head_hash);
+            }
+            let Some(number) = head_number.checked_sub(depth) else {
+                return Ok(B256::ZERO);
+            };
+            Ok(provider.block_hash(number)?.unwrap_or_default())
+        };
+        Ok(ForkchoiceState {
+            head_block_hash: head_hash,
+            safe_block_hash: block_at_depth(self.safe_depth)?,
+            finalized_block_hash: block_at_depth(self.finalized_depth)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::BlockNumber;
+    use reth_provider::ProviderError;
+
+    /// Canonical chain whose block hashes end in their number.
+    struct Chain;
+
+    impl BlockHashReader for Chain {
+        fn block_hash(&self, number: BlockNumber) -> Result<Option<B256>, ProviderError> {
+            Ok(Some(B256::with_last_byte(number as u8)))
+        }
+
+        fn canonical_hashes_range(
+            &self,
+            _start: BlockNumber,
+            _end: BlockNumber,
+        ) -> Result<Vec<B256>, ProviderError> {
+            Ok(vec![])
+        }
+    }
+
+    #[test]
+    fn safe_and_finalized_trail_the_head() {
+        let head = B256::with_last_byte(10);
+        let state = ForkchoicePolicy::default().state(&Chain, head, 10).unwrap();
+        assert_eq!((state.safe_block_hash, state.finalized_block_hash), (head, head));
+
+        let policy = ForkchoicePolicy::new(1, 4, None).unwrap();
+        let state = policy.state(&Chain, head, 10).unwrap();
+        assert_eq!(state.head_block_hash, head);
+        assert_eq!(state.safe_block_hash, B256::with_last_byte(9));
+        assert_eq!(state.finalized_block_hash, B256::with_last_byte(6));
+
+        // Near genesis, only the blocks that exist are set
+        let state = policy.state(&Chain, B256::with_last_byte(2), 2).unwrap();
+        assert_eq!(state.safe_block_hash, B256::with_last_byte(1));
+        assert_eq!(state.finalized_block_hash, B256::ZERO);
+
+        assert!(ForkchoicePolicy::new(4, 1, None).is_err());
+    }
+}
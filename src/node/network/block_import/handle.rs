@@ -1,18 +1,22 @@
 use std::task::{Context, Poll};
 
 use reth_engine_primitives::EngineTypes;
-use reth_network::import::BlockImportError;
+use reth_eth_wire::NewBlockHashes;
+use reth_network::import::{BlockImportError, NewBlockEvent};
 use reth_network_api::PeerId;
 use reth_payload_primitives::PayloadTypes;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use super::service::{BlockMsg, ImportEvent, IncomingBlock, Outcome};
+use crate::node::network::HlNewBlock;
 
 /// A handle for interacting with the block import service.
 ///
 /// This handle provides a bidirectional communication channel with the
 /// [`super::service::ImportService`]:
 /// - Blocks can be sent to the service for import via [`send_block`](ImportHandle::send_block)
+/// - Blocks announced by hash can be sent via [`send_hashes`](ImportHandle::send_hashes), the
+///   service then fetches them from the network
 /// - Import outcomes can be received via [`poll_outcome`](ImportHandle::poll_outcome)`
 #[derive(Debug)]
 pub struct ImportHandle {
@@ -34,8 +38,26 @@ impl ImportHandle {
     /// Sends the block to import to the service.
     /// Returns a [`BlockImportError`] if the channel to the import service is closed.
     pub fn send_block(&self, block: BlockMsg, peer_id: PeerId) -> Result<(), BlockImportError> {
+        self.send(NewBlockEvent::Block(block), peer_id)
+    }
+
+    /// Sends the hashes of announced blocks to the service, to be fetched and imported.
+    /// Returns a [`BlockImportError`] if the channel to the import service is closed.
+    pub fn send_hashes(
+        &self,
+        hashes: NewBlockHashes,
+        peer_id: PeerId,
+    ) -> Result<(), BlockImportError> {
+        self.send(NewBlockEvent::Hashes(hashes), peer_id)
+    }
+
+    fn send(
+        &self,
+        event: NewBlockEvent<HlNewBlock>,
+        peer_id: PeerId,
+    ) -> Result<(), BlockImportError> {
         self.to_import
-            .send((block, peer_id))
+            .send((event, peer_id))
             .map_err(|_| BlockImportError::Other("block import service channel closed".into()))
     }
 
@@ -13,8 +13,11 @@ use std::{
 
 use crate::node::network::HlNewBlock;
 
+pub mod audit;
+pub mod forkchoice;
 pub mod handle;
 pub mod service;
+pub mod status;
 
 #[derive(Debug)]
 pub struct HlBlockImport {
@@ -29,9 +32,10 @@ impl HlBlockImport {
 
 impl BlockImport<HlNewBlock> for HlBlockImport {
     fn on_new_block(&mut self, peer_id: PeerId, incoming_block: NewBlockEvent<HlNewBlock>) {
-        if let NewBlockEvent::Block(block) = incoming_block {
-            let _ = self.handle.send_block(block, peer_id);
-        }
+        let _ = match incoming_block {
+            NewBlockEvent::Block(block) => self.handle.send_block(block, peer_id),
+            NewBlockEvent::Hashes(hashes) => self.handle.send_hashes(hashes, peer_id),
+        };
     }
 
     fn poll(&mut self, cx: &mut Context<'_>) -> Poll<ImportEvent> {
@@ -1,36 +1,62 @@
-use super::handle::ImportHandle;
+use super::{
+    audit::{self, BlockImportAudit, ImportCategory},
+    forkchoice::ForkchoicePolicy,
+    handle::ImportHandle,
+    status::EngineStatus,
+};
 use crate::{
     HlBlock, HlBlockBody,
+    chainspec::TOTAL_DIFFICULTY,
     consensus::HlConsensus,
     node::{
-        network::HlNewBlock,
+        network::{
+            HlNetworkPrimitives, HlNewBlock,
+            block_trace::{BlockSpans, TARGET as BLOCK_TRACE},
+        },
         rpc::engine_api::payload::HlPayloadTypes,
         types::{BlockAndReceipts, EvmBlock},
     },
 };
 use alloy_consensus::{BlockBody, Header};
-use alloy_primitives::U128;
-use alloy_rpc_types::engine::{ForkchoiceState, PayloadStatusEnum};
-use futures::{StreamExt, future::Either, stream::FuturesUnordered};
-use reth_engine_primitives::{ConsensusEngineHandle, EngineTypes};
-use reth_eth_wire::NewBlock;
+use alloy_primitives::B256;
+use alloy_rpc_types::engine::{ForkchoiceState, ForkchoiceUpdated, PayloadStatusEnum};
+use futures::{
+    FutureExt, StreamExt,
+    future::{BoxFuture, Either},
+    stream::FuturesUnordered,
+};
+use reth_engine_primitives::{BeaconForkChoiceUpdateError, ConsensusEngineHandle, EngineTypes};
+use reth_eth_wire::{BlockHashNumber, HeadersDirection, NewBlockHashes};
 use reth_network::{
-    import::{BlockImportError, BlockImportEvent, BlockImportOutcome, BlockValidation},
+    FetchClient,
+    import::{
+        BlockImportError, BlockImportEvent, BlockImportOutcome, BlockValidation, NewBlockEvent,
+    },
     message::NewBlockMessage,
 };
 use reth_network_api::PeerId;
+use reth_network_p2p::{
+    bodies::client::BodiesClient,
+    headers::client::{HeadersClient, HeadersRequest},
+};
 use reth_node_ethereum::EthEngineTypes;
 use reth_payload_primitives::{BuiltPayload, EngineApiMessageVersion, PayloadTypes};
 use reth_primitives::NodePrimitives;
 use reth_primitives_traits::{AlloyBlockHeader, Block};
 use reth_provider::{BlockHashReader, BlockNumReader};
 use std::{
+    collections::HashSet,
     future::Future,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::{Instant, Interval, MissedTickBehavior},
 };
-use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{Instrument, debug, error, trace_span, warn};
 
 /// Network message containing a new block
 pub(crate) type BlockMsg = NewBlockMessage<HlNewBlock>;
@@ -44,8 +70,68 @@ pub(crate) type ImportEvent = BlockImportEvent<HlNewBlock>;
 /// Future that processes a block import and returns its outcome
 type ImportFut = Pin<Box<dyn Future<Output = Option<Outcome>> + Send + Sync>>;
 
+/// Future that fetches a block announced by hash, resolving to the hash and the fetched block
+type FetchFut = Pin<Box<dyn Future<Output = (B256, Option<IncomingBlockMsg>)> + Send + Sync>>;
+
+/// A block paired with the peer that announced it
+type IncomingBlockMsg = (BlockMsg, PeerId);
+
 /// Channel message type for incoming blocks
-pub(crate) type IncomingBlock = (BlockMsg, PeerId);
+pub(crate) type IncomingBlock = (NewBlockEvent<HlNewBlock>, PeerId);
+
+/// A forkchoice update the engine failed or didn't answer in time.
+#[derive(Debug, thiserror::Error)]
+pub enum ForkchoiceUpdateError {
+    #[error(transparent)]
+    Engine(#[from] BeaconForkChoiceUpdateError),
+    #[error("engine did not answer the forkchoice update within {0:?}")]
+    Timeout(Duration),
+}
+
+/// Sends a forkchoice update for `state`, giving up on it after `timeout`.
+async fn send_fork_choice(
+    engine: &ConsensusEngineHandle<HlPayloadTypes>,
+    state: ForkchoiceState,
+    timeout: Duration,
+) -> Result<ForkchoiceUpdated, ForkchoiceUpdateError> {
+    let update = engine.fork_choice_updated(state, None, EngineApiMessageVersion::default());
+    match tokio::time::timeout(timeout, update).await {
+        Ok(response) => Ok(response?),
+        Err(_) => Err(ForkchoiceUpdateError::Timeout(timeout)),
+    }
+}
+
+/// Fetches the full block for a hash that was announced without its body.
+pub trait BlockFetcher: Send + Sync + 'static {
+    fn fetch_block(&self, hash: B256) -> BoxFuture<'static, eyre::Result<HlBlock>>;
+}
+
+impl BlockFetcher for FetchClient<HlNetworkPrimitives> {
+    fn fetch_block(&self, hash: B256) -> BoxFuture<'static, eyre::Result<HlBlock>> {
+        let client = self.clone();
+        async move {
+            let request = HeadersRequest {
+                start: hash.into(),
+                limit: 1,
+                direction: HeadersDirection::Rising,
+            };
+            let header = client
+                .get_headers(request)
+                .await?
+                .into_data()
+                .pop()
+                .ok_or_else(|| eyre::eyre!("No header returned for {hash}"))?;
+            let body = client
+                .get_block_bodies(vec![hash])
+                .await?
+                .into_data()
+                .pop()
+                .ok_or_else(|| eyre::eyre!("No body returned for {hash}"))?;
+            Ok(HlBlock { header, body })
+        }
+        .boxed()
+    }
+}
 
 /// A service that handles bidirectional block import communication with the network.
 /// It receives new blocks from the network via `from_network` channel and sends back
@@ -64,6 +150,20 @@ where
     to_network: UnboundedSender<ImportEvent>,
     /// Pending block imports.
     pending_imports: FuturesUnordered<ImportFut>,
+    /// Fetches blocks that were announced by hash only
+    fetcher: Option<Arc<dyn BlockFetcher>>,
+    /// Pending fetches of announced blocks.
+    pending_fetches: FuturesUnordered<FetchFut>,
+    /// Hashes of the announced blocks being fetched
+    fetching: HashSet<B256>,
+    /// Where the outcome of forkchoice updates is recorded
+    status: EngineStatus,
+    /// Safe and finalized blocks of the forkchoice updates
+    forkchoice: ForkchoicePolicy,
+    /// Ticks when the forkchoice update for the current head is re-sent
+    refresh: Option<Interval>,
+    /// Spans handed over by the pseudo peer for the blocks it announces
+    spans: BlockSpans,
 }
 
 impl<Provider> ImportService<Provider>
@@ -83,14 +183,59 @@ where
             from_network,
             to_network,
             pending_imports: FuturesUnordered::new(),
+            fetcher: None,
+            pending_fetches: FuturesUnordered::new(),
+            fetching: HashSet::new(),
+            status: EngineStatus::default(),
+            forkchoice: ForkchoicePolicy::default(),
+            refresh: None,
+            spans: BlockSpans::default(),
         }
     }
 
+    /// Sets the spans the pseudo peer of the node hands over, to trace the imports of its blocks
+    /// under them.
+    pub fn with_block_spans(mut self, spans: BlockSpans) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    /// Sets the cell the outcome of forkchoice updates is recorded in.
+    pub fn with_engine_status(mut self, status: EngineStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Sets how the safe and finalized blocks of forkchoice updates are chosen, and how often the
+    /// update for the current head is re-sent.
+    pub fn with_forkchoice_policy(mut self, forkchoice: ForkchoicePolicy) -> Self {
+        self.refresh = forkchoice.refresh_interval.map(|period| {
+            let mut refresh = tokio::time::interval_at(Instant::now() + period, period);
+            refresh.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            refresh
+        });
+        self.forkchoice = forkchoice;
+        self
+    }
+
+    /// Sets the client used to fetch blocks that peers announce by hash only.
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn BlockFetcher>) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
     /// Process a new payload and return the outcome
-    fn new_payload(&self, block: BlockMsg, peer_id: PeerId) -> ImportFut {
+    fn new_payload(
+        &self,
+        block: BlockMsg,
+        peer_id: PeerId,
+        audit: Arc<BlockImportAudit>,
+    ) -> ImportFut {
         let engine = self.engine.clone();
+        let status = self.status.clone();
         Box::pin(async move {
             let sealed_block = block.block.0.block.clone().seal();
+            let number = sealed_block.number();
             let payload = HlPayloadTypes::block_to_payload(sealed_block);
 
             match engine.new_payload(payload).await {
@@ -99,56 +244,233 @@ where
                         Outcome { peer: peer_id, result: Ok(BlockValidation::ValidBlock { block }) }
                             .into()
                     }
-                    PayloadStatusEnum::Invalid { validation_error } => Outcome {
-                        peer: peer_id,
-                        result: Err(BlockImportError::Other(validation_error.into())),
+                    PayloadStatusEnum::Invalid { validation_error } => {
+                        status.record_import_error(number, &validation_error);
+                        audit.failed(&validation_error);
+                        Outcome {
+                            peer: peer_id,
+                            result: Err(BlockImportError::Other(validation_error.into())),
+                        }
+                        .into()
                     }
-                    .into(),
                     _ => None,
                 },
-                Err(_) => None,
+                Err(err) => {
+                    warn!(number, hash = %block.hash, %err, "New payload failed");
+                    status.record_import_error(number, &err);
+                    audit.failed(&err);
+                    None
+                }
             }
         })
     }
 
     /// Process a forkchoice update and return the outcome
-    fn update_fork_choice(&self, block: BlockMsg, peer_id: PeerId) -> ImportFut {
+    fn update_fork_choice(
+        &self,
+        block: BlockMsg,
+        peer_id: PeerId,
+        audit: Arc<BlockImportAudit>,
+    ) -> ImportFut {
         let engine = self.engine.clone();
         let consensus = self.consensus.clone();
+        let status = self.status.clone();
+        let forkchoice = self.forkchoice;
         let sealed_block = block.block.0.block.clone().seal();
         let (hash, number) = (sealed_block.hash(), sealed_block.number());
 
         Box::pin(async move {
-            let (head_block_hash, _) = consensus.canonical_head(hash, number).ok()?;
-            let state = ForkchoiceState {
-                head_block_hash,
-                safe_block_hash: head_block_hash,
-                finalized_block_hash: head_block_hash,
+            let state = consensus.canonical_head(hash, number).and_then(|(head_block_hash, _)| {
+                // Otherwise the head stays at the current best block
+                let head_number = if head_block_hash == hash {
+                    number
+                } else {
+                    consensus.provider.best_block_number()?
+                };
+                Ok(forkchoice.state(&consensus.provider, head_block_hash, head_number)?)
+            });
+            let state = match state {
+                Ok(state) => state,
+                Err(err) => {
+                    warn!(number, %hash, %err, "Failed to determine the canonical head");
+                    status.record_import_error(number, &err);
+                    audit.failed(&err);
+                    return None;
+                }
             };
+            let head_block_hash = state.head_block_hash;
 
-            match engine.fork_choice_updated(state, None, EngineApiMessageVersion::default()).await
-            {
-                Ok(response) => match response.payload_status.status {
-                    PayloadStatusEnum::Valid => {
-                        Outcome { peer: peer_id, result: Ok(BlockValidation::ValidBlock { block }) }
+            match send_fork_choice(&engine, state, forkchoice.timeout).await {
+                Ok(response) => {
+                    status.record_response(state, &response.payload_status.status);
+                    match response.payload_status.status {
+                        PayloadStatusEnum::Valid => {
+                            // Blocks below the head leave the head where it is
+                            if head_block_hash == hash {
+                                status.record_import(number);
+                            }
+                            audit.accepted(head_block_hash == hash);
+                            Outcome {
+                                peer: peer_id,
+                                result: Ok(BlockValidation::ValidBlock { block }),
+                            }
                             .into()
+                        }
+                        PayloadStatusEnum::Invalid { validation_error } => {
+                            warn!(number, %hash, %validation_error, "Forkchoice update invalid");
+                            status.record_import_error(number, &validation_error);
+                            audit.failed(&validation_error);
+                            Outcome {
+                                peer: peer_id,
+                                result: Err(BlockImportError::Other(validation_error.into())),
+                            }
+                            .into()
+                        }
+                        _ => None,
                     }
-                    PayloadStatusEnum::Invalid { validation_error } => Outcome {
-                        peer: peer_id,
-                        result: Err(BlockImportError::Other(validation_error.into())),
-                    }
-                    .into(),
-                    _ => None,
-                },
-                Err(_) => None,
+                }
+                Err(err @ ForkchoiceUpdateError::Timeout(_)) => {
+                    error!(number, %hash, %err, "Forkchoice update timed out");
+                    status.record_error(state, &err);
+                    status.record_import_error(number, &err);
+                    audit.failed(&err);
+                    None
+                }
+                Err(err) => {
+                    warn!(number, %hash, %err, "Forkchoice update failed");
+                    status.record_error(state, &err);
+                    status.record_import_error(number, &err);
+                    audit.failed(&err);
+                    None
+                }
+            }
+        })
+    }
+
+    /// Re-sends the forkchoice update for the current head, without an outcome for the network
+    fn refresh_fork_choice(&self) -> ImportFut {
+        let engine = self.engine.clone();
+        let consensus = self.consensus.clone();
+        let status = self.status.clone();
+        let forkchoice = self.forkchoice;
+
+        Box::pin(async move {
+            let provider = &consensus.provider;
+            let state = provider.best_block_number().and_then(|number| {
+                let hash = provider.block_hash(number)?.unwrap_or_default();
+                forkchoice.state(provider, hash, number)
+            });
+            let state = match state {
+                Ok(state) if !state.head_block_hash.is_zero() => state,
+                Ok(_) => return None,
+                Err(err) => {
+                    debug!(%err, "Failed to read the head for a forkchoice refresh");
+                    return None;
+                }
+            };
+            match send_fork_choice(&engine, state, forkchoice.timeout).await {
+                Ok(response) => status.record_response(state, &response.payload_status.status),
+                Err(err @ ForkchoiceUpdateError::Timeout(_)) => {
+                    error!(%err, "Forkchoice refresh timed out");
+                    status.record_error(state, &err);
+                }
+                Err(err) => {
+                    debug!(%err, "Forkchoice refresh failed");
+                    status.record_error(state, &err);
+                }
             }
+            None
         })
     }
 
+    /// Returns the hash of the canonical block at `number`, if it is at or below the head.
+    fn canonical_hash(&self, number: u64) -> Option<B256> {
+        let best_number = self.consensus.provider.best_block_number().ok()?;
+        if number > best_number {
+            return None;
+        }
+        self.consensus.provider.block_hash(number).ok().flatten()
+    }
+
+    /// Returns true if a block with the same number and hash is already part of the canonical
+    /// chain. Blocks at or below the head with a different hash are logged and still imported.
+    fn is_already_imported(&self, number: u64, hash: B256) -> bool {
+        match self.canonical_hash(number) {
+            Some(stored_hash) if stored_hash == hash => true,
+            Some(stored_hash) => {
+                warn!(
+                    number,
+                    incoming_hash = %hash,
+                    %stored_hash,
+                    "Received block at or below head with mismatching hash"
+                );
+                false
+            }
+            None => false,
+        }
+    }
+
     /// Add a new block import task to the pending imports
     fn on_new_block(&mut self, block: BlockMsg, peer_id: PeerId) {
-        self.pending_imports.push(self.new_payload(block.clone(), peer_id));
-        self.pending_imports.push(self.update_fork_choice(block, peer_id));
+        let number = block.block.0.block.header.number;
+        let tx_count = block.block.0.block.body.inner.transactions.len();
+        let span = self.spans.import_span(number, block.hash);
+        let _span = span.enter();
+        if !block.block.has_valid_td() {
+            let td = block.block.0.td;
+            let error = format!("unexpected total difficulty {td}, expected {TOTAL_DIFFICULTY}");
+            warn!(number, hash = %block.hash, %error, "Rejecting block");
+            audit::log_unsent(ImportCategory::Failed, number, block.hash, tx_count, Some(&error));
+            let outcome =
+                Outcome { peer: peer_id, result: Err(BlockImportError::Other(error.into())) };
+            self.pending_imports.push(Box::pin(std::future::ready(Some(outcome))));
+            return;
+        }
+        if self.is_already_imported(number, block.hash) {
+            debug!(number, hash = %block.hash, "Skipping already imported block");
+            audit::log_unsent(ImportCategory::Skipped, number, block.hash, tx_count, None);
+            return;
+        }
+        // Not imported yet, so a canonical block at its height is a different one
+        let replaces_canonical = self.canonical_hash(number).is_some();
+        let audit = BlockImportAudit::new(number, block.hash, tx_count, replaces_canonical);
+        let new_payload = self.new_payload(block.clone(), peer_id, audit.clone());
+        let fork_choice = self.update_fork_choice(block, peer_id, audit);
+        self.pending_imports.push(Box::pin(
+            new_payload.instrument(trace_span!(target: BLOCK_TRACE, "new_payload")),
+        ));
+        self.pending_imports.push(Box::pin(
+            fork_choice.instrument(trace_span!(target: BLOCK_TRACE, "fork_choice_updated")),
+        ));
+    }
+
+    /// Fetch the blocks announced by hash that are neither imported nor already being fetched
+    fn on_new_block_hashes(&mut self, hashes: NewBlockHashes, peer_id: PeerId) {
+        let Some(fetcher) = self.fetcher.clone() else {
+            debug!(count = hashes.0.len(), "No block fetcher, ignoring announced hashes");
+            return;
+        };
+        for BlockHashNumber { hash, number } in hashes.0 {
+            if self.is_already_imported(number, hash) || !self.fetching.insert(hash) {
+                continue;
+            }
+            let block = fetcher.fetch_block(hash);
+            self.pending_fetches.push(Box::pin(async move {
+                let block = match block.await {
+                    Ok(block) if block.header.hash_slow() == hash => block,
+                    Ok(_) => {
+                        warn!(number, %hash, "Fetched block does not match the announced hash");
+                        return (hash, None);
+                    }
+                    Err(err) => {
+                        warn!(number, %hash, %err, "Failed to fetch announced block");
+                        return (hash, None);
+                    }
+                };
+                let block = HlNewBlock::new(block);
+                (hash, Some((NewBlockMessage { hash, block: Arc::new(block) }, peer_id)))
+            }));
+        }
     }
 }
 
@@ -161,9 +483,26 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
 
-        // Receive new blocks from network
-        while let Poll::Ready(Some((block, peer_id))) = this.from_network.poll_recv(cx) {
-            this.on_new_block(block, peer_id);
+        // Receive new blocks and block announcements from network
+        while let Poll::Ready(Some((event, peer_id))) = this.from_network.poll_recv(cx) {
+            match event {
+                NewBlockEvent::Block(block) => this.on_new_block(block, peer_id),
+                NewBlockEvent::Hashes(hashes) => this.on_new_block_hashes(hashes, peer_id),
+            }
+        }
+
+        // Import the announced blocks that were fetched
+        while let Poll::Ready(Some((hash, block))) = this.pending_fetches.poll_next_unpin(cx) {
+            this.fetching.remove(&hash);
+            if let Some((block, peer_id)) = block {
+                this.on_new_block(block, peer_id);
+            }
+        }
+
+        // Re-send the forkchoice update for the current head
+        while this.refresh.as_mut().is_some_and(|refresh| refresh.poll_tick(cx).is_ready()) {
+            let refresh = this.refresh_fork_choice();
+            this.pending_imports.push(refresh);
         }
 
         // Process completed imports and send events to network
@@ -179,21 +518,27 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::{HlHeader, chainspec::hl::hl_mainnet};
+    use crate::{
+        HlHeader, HlPrimitives,
+        chainspec::{HlChainSpec, hl::hl_mainnet},
+        node::rpc::engine_status::{HlEngineStatusApiServer, HlEngineStatusExt},
+    };
 
     use super::*;
     use alloy_primitives::{B256, U128};
     use alloy_rpc_types::engine::PayloadStatus;
     use reth_chainspec::ChainInfo;
     use reth_engine_primitives::{BeaconEngineMessage, OnForkChoiceUpdated};
-    use reth_eth_wire::NewBlock;
     use reth_node_ethereum::EthEngineTypes;
     use reth_primitives::Block;
-    use reth_provider::ProviderError;
+    use reth_errors::RethError;
+    use reth_provider::{ProviderError, test_utils::NoopProvider};
     use std::{
-        sync::Arc,
+        sync::{Arc, Mutex},
         task::{Context, Poll},
+        time::Duration,
     };
+    use tracing_subscriber::{Layer, layer::SubscriberExt};
 
     #[tokio::test]
     async fn can_handle_valid_block() {
@@ -243,8 +588,249 @@ mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn rejects_block_with_unexpected_td() {
+        let mut fixture = TestFixture::new(EngineResponses::both_valid()).await;
+        let mut block_msg = create_test_block();
+        Arc::make_mut(&mut block_msg.block).0.td = U128::from(1);
+        fixture.handle.send_block(block_msg, PeerId::random()).unwrap();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let outcome = loop {
+            match fixture.handle.poll_outcome(&mut cx) {
+                Poll::Ready(outcome) => break outcome.unwrap(),
+                Poll::Pending => tokio::task::yield_now().await,
+            }
+        };
+        assert!(matches!(
+            outcome,
+            BlockImportEvent::Outcome(BlockImportOutcome {
+                peer: _,
+                result: Err(BlockImportError::Other(_))
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn fetches_block_announced_by_hash() {
+        let block_msg = create_test_block();
+        let fetcher = MockFetcher { block: block_msg.block.0.block.clone() };
+        let mut fixture =
+            TestFixture::with_fetcher(EngineResponses::both_valid(), Some(Arc::new(fetcher))).await;
+
+        let hashes = NewBlockHashes(vec![BlockHashNumber {
+            hash: block_msg.hash,
+            number: block_msg.block.0.block.header.number,
+        }]);
+        fixture.handle.send_hashes(hashes, PeerId::random()).unwrap();
+        fixture
+            .assert_outcomes(|outcome| {
+                matches!(
+                    outcome,
+                    BlockImportEvent::Outcome(BlockImportOutcome {
+                        peer: _,
+                        result: Ok(BlockValidation::ValidBlock { block })
+                    }) if block.hash == block_msg.hash
+                )
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn engine_error_surfaces_in_engine_status() {
+        let fixture = TestFixture::new(EngineResponses::fcu_error()).await;
+        fixture.handle.send_block(create_test_block(), PeerId::random()).unwrap();
+
+        let provider = NoopProvider::<HlChainSpec, HlPrimitives>::new(Arc::default());
+        let api = HlEngineStatusExt::new(fixture.status.clone(), provider);
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let status = api.engine_status().await.unwrap();
+                if status.forkchoice.last_status.is_some() {
+                    break status;
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(status.forkchoice.last_status.as_deref(), Some("ERROR"));
+        assert!(status.forkchoice.last_error.is_some());
+        assert!(status.forkchoice.last_forkchoice.is_some());
+        assert_eq!(status.forkchoice.last_valid_at, None);
+        assert_eq!(status.finish_checkpoint, None);
+    }
+
+    #[tokio::test]
+    async fn stalled_engine_times_out_forkchoice_update() {
+        let fixture = TestFixture::new(EngineResponses::fcu_stalled()).await;
+        fixture.handle.send_block(create_test_block(), PeerId::random()).unwrap();
+
+        let provider = NoopProvider::<HlChainSpec, HlPrimitives>::new(Arc::default());
+        let api = HlEngineStatusExt::new(fixture.status.clone(), provider);
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let status = api.engine_status().await.unwrap();
+                if status.forkchoice.last_status.is_some() {
+                    break status;
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(status.forkchoice.last_status.as_deref(), Some("ERROR"));
+        let timeout = ForkchoiceUpdateError::Timeout(FCU_TIMEOUT).to_string();
+        assert_eq!(status.forkchoice.last_error, Some(timeout));
+    }
+
+    #[tokio::test]
+    async fn import_error_surfaces_in_import_status() {
+        let fixture = TestFixture::new(EngineResponses::invalid_new_payload()).await;
+        fixture.handle.send_block(create_test_block(), PeerId::random()).unwrap();
+
+        let provider = NoopProvider::<HlChainSpec, HlPrimitives>::new(Arc::default());
+        let api = HlEngineStatusExt::new(fixture.status.clone(), provider);
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let status = api.import_status().await.unwrap();
+                if status.last_error.is_some() {
+                    break status;
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(status.last_error.as_deref(), Some("block 0: test error"));
+        assert!(!status.stalled);
+    }
+
+    /// Collects the categories of the block import audit events.
+    #[derive(Clone, Default)]
+    struct AuditEvents(Arc<Mutex<Vec<String>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for AuditEvents {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct Category<'a>(&'a mut Option<String>);
+
+            impl tracing::field::Visit for Category<'_> {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "category" {
+                        *self.0 = Some(format!("{value:?}"));
+                    }
+                }
+            }
+
+            if event.metadata().target() == audit::TARGET {
+                let mut category = None;
+                event.record(&mut Category(&mut category));
+                self.0.lock().unwrap().extend(category);
+            }
+        }
+    }
+
+    /// Imports `block` into a chain whose head is block 0 with `head_hash`, returning the
+    /// category the import is audited with.
+    async fn audited_category(
+        responses: EngineResponses,
+        head_hash: B256,
+        block: NewBlockMessage<HlNewBlock>,
+    ) -> String {
+        let events = AuditEvents::default();
+        // The test runtime is single-threaded, so the service logs to this subscriber too
+        let _subscriber =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(events.clone()));
+
+        let fixture = TestFixture::with_head_hash(responses, head_hash).await;
+        fixture.handle.send_block(block, PeerId::random()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(category) = events.0.lock().unwrap().first() {
+                    break category.clone();
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn audits_import_outcome_categories() {
+        let block = create_test_block();
+
+        let applied = audited_category(EngineResponses::both_valid(), B256::ZERO, test_block(1));
+        assert_eq!(applied.await, ImportCategory::Applied.to_string());
+
+        // The chain keeps the block with the lowest hash at the head's height
+        let kept = audited_category(EngineResponses::both_valid(), B256::ZERO, block.clone());
+        assert_eq!(kept.await, ImportCategory::Skipped.to_string());
+        let imported = audited_category(EngineResponses::both_valid(), block.hash, block.clone());
+        assert_eq!(imported.await, ImportCategory::Skipped.to_string());
+
+        let reorg = audited_category(EngineResponses::both_valid(), B256::repeat_byte(0xff), block);
+        assert_eq!(reorg.await, ImportCategory::Reorg.to_string());
+
+        let failed = audited_category(EngineResponses::invalid_fcu(), B256::ZERO, test_block(1));
+        assert_eq!(failed.await, ImportCategory::Failed.to_string());
+    }
+
+    /// Serves a single block, whatever the requested hash.
+    struct MockFetcher {
+        block: HlBlock,
+    }
+
+    impl BlockFetcher for MockFetcher {
+        fn fetch_block(&self, _hash: B256) -> BoxFuture<'static, eyre::Result<HlBlock>> {
+            let block = self.block.clone();
+            async move { Ok(block) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_already_imported_block() {
+        let block_msg = create_test_block();
+        let provider = MockProvider { head_hash: block_msg.hash };
+        let consensus = Arc::new(HlConsensus { provider });
+        let (to_engine, mut from_engine) = mpsc::unbounded_channel();
+        let engine_handle = ConsensusEngineHandle::new(to_engine);
+
+        let (to_import, from_network) = mpsc::unbounded_channel();
+        let (to_network, import_outcome) = mpsc::unbounded_channel();
+        let mut handle = ImportHandle::new(to_import, import_outcome);
+        let service = ImportService::new(consensus, engine_handle, from_network, to_network);
+        tokio::spawn(Box::pin(async move {
+            service.await.unwrap();
+        }));
+
+        handle.send_block(block_msg, PeerId::random()).unwrap();
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(from_engine.try_recv().is_err(), "already imported block reached the engine");
+        assert!(handle.poll_outcome(&mut cx).is_pending());
+    }
+
     #[derive(Clone)]
-    struct MockProvider;
+    struct MockProvider {
+        head_hash: B256,
+    }
 
     impl BlockNumReader for MockProvider {
         fn chain_info(&self) -> Result<ChainInfo, ProviderError> {
@@ -263,7 +849,7 @@ mod tests {
 
     impl BlockHashReader for MockProvider {
         fn block_hash(&self, _number: u64) -> Result<Option<B256>, ProviderError> {
-            Ok(Some(B256::ZERO))
+            Ok(Some(self.head_hash))
         }
         fn canonical_hashes_range(
             &self,
@@ -274,39 +860,84 @@ mod tests {
         }
     }
 
+    /// Time the test engine has to answer forkchoice updates
+    const FCU_TIMEOUT: Duration = Duration::from_millis(500);
+
     /// Response configuration for engine messages
     struct EngineResponses {
         new_payload: PayloadStatusEnum,
         fcu: PayloadStatusEnum,
+        /// Fail forkchoice updates with this error instead of answering with `fcu`
+        fcu_error: Option<&'static str>,
+        /// Never answer forkchoice updates
+        fcu_stalled: bool,
     }
 
     impl EngineResponses {
         fn both_valid() -> Self {
-            Self { new_payload: PayloadStatusEnum::Valid, fcu: PayloadStatusEnum::Valid }
+            Self {
+                new_payload: PayloadStatusEnum::Valid,
+                fcu: PayloadStatusEnum::Valid,
+                fcu_error: None,
+                fcu_stalled: false,
+            }
         }
         fn invalid_new_payload() -> Self {
             Self {
                 new_payload: PayloadStatusEnum::Invalid { validation_error: "test error".into() },
                 fcu: PayloadStatusEnum::Valid,
+                fcu_error: None,
+                fcu_stalled: false,
             }
         }
         fn invalid_fcu() -> Self {
             Self {
                 new_payload: PayloadStatusEnum::Valid,
                 fcu: PayloadStatusEnum::Invalid { validation_error: "fcu error".into() },
+                fcu_error: None,
+                fcu_stalled: false,
             }
         }
+        fn fcu_error() -> Self {
+            Self { fcu_error: Some("engine unavailable"), ..Self::both_valid() }
+        }
+        fn fcu_stalled() -> Self {
+            Self { fcu_stalled: true, ..Self::both_valid() }
+        }
     }
 
     /// Test fixture for block import tests
     struct TestFixture {
         handle: ImportHandle,
+        status: EngineStatus,
     }
 
     impl TestFixture {
         /// Create a new test fixture with the given engine responses
         async fn new(responses: EngineResponses) -> Self {
-            let consensus = Arc::new(HlConsensus { provider: MockProvider });
+            Self::with_fetcher(responses, None).await
+        }
+
+        /// Create a new test fixture that fetches announced blocks with `fetcher`
+        async fn with_fetcher(
+            responses: EngineResponses,
+            fetcher: Option<Arc<dyn BlockFetcher>>,
+        ) -> Self {
+            Self::spawn(responses, B256::ZERO, fetcher).await
+        }
+
+        /// Create a new test fixture whose canonical head is block 0 with `head_hash`
+        async fn with_head_hash(responses: EngineResponses, head_hash: B256) -> Self {
+            Self::spawn(responses, head_hash, None).await
+        }
+
+        async fn spawn(
+            responses: EngineResponses,
+            head_hash: B256,
+            fetcher: Option<Arc<dyn BlockFetcher>>,
+        ) -> Self {
+            let provider = MockProvider { head_hash };
+            let consensus = Arc::new(HlConsensus { provider });
             let (to_engine, from_engine) = mpsc::unbounded_channel();
             let engine_handle = ConsensusEngineHandle::new(to_engine);
             handle_engine_msg(from_engine, responses).await;
@@ -314,11 +945,18 @@ mod tests {
             let (to_import, from_network) = mpsc::unbounded_channel();
             let (to_network, import_outcome) = mpsc::unbounded_channel();
             let handle = ImportHandle::new(to_import, import_outcome);
-            let service = ImportService::new(consensus, engine_handle, from_network, to_network);
+            let status = EngineStatus::default();
+            let mut service =
+                ImportService::new(consensus, engine_handle, from_network, to_network)
+                    .with_engine_status(status.clone())
+                    .with_forkchoice_policy(ForkchoicePolicy::default().with_timeout(FCU_TIMEOUT));
+            if let Some(fetcher) = fetcher {
+                service = service.with_fetcher(fetcher);
+            }
             tokio::spawn(Box::pin(async move {
                 service.await.unwrap();
             }));
-            Self { handle }
+            Self { handle, status }
         }
 
         /// Run a block import test with the given event assertion
@@ -328,7 +966,14 @@ mod tests {
         {
             let block_msg = create_test_block();
             self.handle.send_block(block_msg, PeerId::random()).unwrap();
+            self.assert_outcomes(assert_fn).await;
+        }
 
+        /// Wait for the NewPayload and FCU outcomes and assert that one of them matches
+        async fn assert_outcomes<F>(&mut self, assert_fn: F)
+        where
+            F: Fn(&BlockImportEvent<HlNewBlock>) -> bool,
+        {
             let waker = futures::task::noop_waker();
             let mut cx = Context::from_waker(&waker);
             let mut outcomes = Vec::new();
@@ -354,8 +999,15 @@ mod tests {
 
     /// Creates a test block message
     fn create_test_block() -> NewBlockMessage<HlNewBlock> {
+        test_block(0)
+    }
+
+    /// Creates a test block message for an empty block at `number`
+    fn test_block(number: u64) -> NewBlockMessage<HlNewBlock> {
+        let mut header = HlHeader::default();
+        header.inner.number = number;
         let block = HlBlock {
-            header: HlHeader::default(),
+            header,
             body: HlBlockBody {
                 inner: BlockBody {
                     transactions: Vec::new(),
@@ -367,7 +1019,7 @@ mod tests {
                 highest_precompile_address: None,
             },
         };
-        let new_block = HlNewBlock(NewBlock { block, td: U128::from(1) });
+        let new_block = HlNewBlock::new(block);
         let hash = new_block.0.block.header.hash_slow();
         NewBlockMessage { hash, block: Arc::new(new_block) }
     }
@@ -378,23 +1030,34 @@ mod tests {
         responses: EngineResponses,
     ) {
         tokio::spawn(Box::pin(async move {
+            // Unanswered forkchoice updates, kept so that they stay pending
+            let mut stalled = Vec::new();
             while let Some(message) = from_engine.recv().await {
                 match message {
                     BeaconEngineMessage::NewPayload { payload: _, tx } => {
                         tx.send(Ok(PayloadStatus::new(responses.new_payload.clone(), None)))
                             .unwrap();
                     }
+                    BeaconEngineMessage::ForkchoiceUpdated {
+                        state: _,
+                        payload_attrs: _,
+                        version: _,
+                        tx,
+                    } if responses.fcu_stalled => stalled.push(tx),
                     BeaconEngineMessage::ForkchoiceUpdated {
                         state: _,
                         payload_attrs: _,
                         version: _,
                         tx,
                     } => {
-                        tx.send(Ok(OnForkChoiceUpdated::valid(PayloadStatus::new(
-                            responses.fcu.clone(),
-                            None,
-                        ))))
-                        .unwrap();
+                        let response = match responses.fcu_error {
+                            Some(error) => Err(RethError::msg(error)),
+                            None => Ok(OnForkChoiceUpdated::valid(PayloadStatus::new(
+                                responses.fcu.clone(),
+                                None,
+                            ))),
+                        };
+                        tx.send(response).unwrap();
                     }
                     _ => {}
                 }
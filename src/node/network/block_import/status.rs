@@ -0,0 +1,120 @@
+use alloy_rpc_types::engine::{ForkchoiceState, PayloadStatusEnum};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How long the import pipeline may go without importing a block before it is reported as
+/// stalled.
+pub const IMPORT_STALL_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Outcome of the last forkchoice update sent by the block import service.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkchoiceStatus {
+    /// Forkchoice state of the last update.
+    pub last_forkchoice: Option<ForkchoiceState>,
+    /// Engine response to the last update: `VALID`, `INVALID`, `SYNCING`, `ACCEPTED`, or `ERROR`
+    /// when the engine could not be reached.
+    pub last_status: Option<String>,
+    /// Validation error or engine error of the last update.
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds) of the last update the engine accepted as valid.
+    pub last_valid_at: Option<u64>,
+}
+
+/// State of the import pipeline, as reported by `hl_importStatus`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportStatus {
+    /// Number of the last block the engine accepted as the canonical head.
+    pub head: Option<u64>,
+    /// Last error of a new payload or forkchoice update, kept after later imports succeed.
+    pub last_error: Option<String>,
+    /// Whether no block was imported for [`IMPORT_STALL_THRESHOLD`].
+    pub stalled: bool,
+    /// Unix timestamp (seconds) of the last imported block.
+    pub last_import_ts: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ImportProgress {
+    head: Option<u64>,
+    last_error: Option<String>,
+    last_import_ts: Option<u64>,
+    /// Last import, or startup until the first block is imported.
+    last_progress: Instant,
+}
+
+impl Default for ImportProgress {
+    fn default() -> Self {
+        Self { head: None, last_error: None, last_import_ts: None, last_progress: Instant::now() }
+    }
+}
+
+/// Shared cell the block import service records its forkchoice updates and imports in, read by
+/// `hl_engineStatus` and `hl_importStatus`.
+#[derive(Debug, Clone, Default)]
+pub struct EngineStatus {
+    forkchoice: Arc<Mutex<ForkchoiceStatus>>,
+    import: Arc<Mutex<ImportProgress>>,
+}
+
+fn unix_now() -> Option<u64> {
+    SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|now| now.as_secs())
+}
+
+impl EngineStatus {
+    /// Records the engine's response to a forkchoice update.
+    pub fn record_response(&self, state: ForkchoiceState, status: &PayloadStatusEnum) {
+        let mut forkchoice = self.forkchoice.lock().unwrap();
+        forkchoice.last_forkchoice = Some(state);
+        forkchoice.last_status = Some(status.as_str().to_string());
+        forkchoice.last_error = match status {
+            PayloadStatusEnum::Invalid { validation_error } => Some(validation_error.clone()),
+            _ => None,
+        };
+        if status.is_valid() {
+            forkchoice.last_valid_at = unix_now();
+        }
+    }
+
+    /// Records a forkchoice update the engine failed to process.
+    pub fn record_error(&self, state: ForkchoiceState, error: impl Display) {
+        let mut forkchoice = self.forkchoice.lock().unwrap();
+        forkchoice.last_forkchoice = Some(state);
+        forkchoice.last_status = Some("ERROR".to_string());
+        forkchoice.last_error = Some(error.to_string());
+    }
+
+    /// Returns the outcome of the last forkchoice update.
+    pub fn forkchoice(&self) -> ForkchoiceStatus {
+        self.forkchoice.lock().unwrap().clone()
+    }
+
+    /// Records that block `number` became the canonical head.
+    pub fn record_import(&self, number: u64) {
+        let mut import = self.import.lock().unwrap();
+        import.head = Some(number);
+        import.last_import_ts = unix_now();
+        import.last_progress = Instant::now();
+    }
+
+    /// Records a block the import pipeline failed to import.
+    pub fn record_import_error(&self, number: u64, error: impl Display) {
+        self.import.lock().unwrap().last_error = Some(format!("block {number}: {error}"));
+    }
+
+    /// Returns the state of the import pipeline.
+    pub fn import(&self) -> ImportStatus {
+        let import = self.import.lock().unwrap();
+        ImportStatus {
+            head: import.head,
+            last_error: import.last_error.clone(),
+            stalled: import.last_progress.elapsed() > IMPORT_STALL_THRESHOLD,
+            last_import_ts: import.last_import_ts,
+        }
+    }
+}
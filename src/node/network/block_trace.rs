@@ -0,0 +1,59 @@
+//! Tracing spans following a block from the block source to its import, enabled with
+//! `RUST_LOG=block_trace=trace`.
+//!
+//! The pseudo peer opens a `block` span per height, under which the block is fetched from the
+//! source (`fetch`), converted (`convert`), handed to the network (`send`) and announced
+//! (`announce`). The span travels with the block through the poller's channel. The devp2p hop to
+//! the import service can't carry it, so the pseudo peer hands the announced block's span to the
+//! [`BlockSpans`] of its node, and the import service of the node handles the block (`import`)
+//! under the span handed over for its hash.
+
+use alloy_primitives::B256;
+use reth_network::cache::LruMap;
+use std::sync::{Arc, Mutex};
+use tracing::{Span, field};
+
+/// Target of the block spans.
+pub const TARGET: &str = "block_trace";
+
+/// Number of announced blocks whose span is kept for the import service to pick up.
+const HANDED_OVER_SPANS: u32 = 1024;
+
+/// Opens the span of the block at `height`. Its hash is recorded once known, see
+/// [`BlockSpans::hand_over`].
+pub fn block_span(height: u64) -> Span {
+    tracing::trace_span!(target: TARGET, "block", height, hash = field::Empty)
+}
+
+/// Spans of the blocks announced by the pseudo peer of a node, for the import service of the same
+/// node. Clones share the same spans.
+#[derive(Debug, Clone)]
+pub struct BlockSpans(Arc<Mutex<LruMap<B256, Span>>>);
+
+impl Default for BlockSpans {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(LruMap::new(HANDED_OVER_SPANS))))
+    }
+}
+
+impl BlockSpans {
+    /// Records the hash of the block of `span`, and hands the span over to the import service.
+    pub fn hand_over(&self, hash: B256, span: &Span) {
+        if span.is_disabled() {
+            return;
+        }
+        span.record("hash", field::display(hash));
+        self.0.lock().unwrap().insert(hash, span.clone());
+    }
+
+    /// Opens the span of the import of the block `number` with `hash`, under the span handed over
+    /// for it, or under a new `block` span for blocks from other peers.
+    pub fn import_span(&self, number: u64, hash: B256) -> Span {
+        let parent = self.0.lock().unwrap().remove(&hash).unwrap_or_else(|| {
+            let span = block_span(number);
+            span.record("hash", field::display(hash));
+            span
+        });
+        tracing::trace_span!(target: TARGET, parent: &parent, "import")
+    }
+}
@@ -1,43 +1,165 @@
 #![allow(clippy::owned_cow)]
 use crate::{
     HlBlock,
+    addons::sync_server::ProviderSyncReader,
+    chainspec::TOTAL_DIFFICULTY,
     consensus::HlConsensus,
     node::{
         HlNode,
-        network::block_import::{HlBlockImport, handle::ImportHandle, service::ImportService},
+        network::{
+            block_import::{
+                HlBlockImport, forkchoice::ForkchoicePolicy, handle::ImportHandle,
+                service::ImportService, status::EngineStatus,
+            },
+            block_trace::BlockSpans,
+        },
         primitives::HlPrimitives,
         rpc::engine_api::payload::HlPayloadTypes,
-        types::ReadPrecompileCalls,
+        storage::checkpoint::{BlockSourceCheckpointer, reconcile_start_height},
+        types::{ReadPrecompileCalls, SpotMetaContext},
+    },
+    pseudo_peer::{
+        BlockSourceConfig, FatalErrors, PSEUDO_PEER_KEY_FILE, PseudoPeerContext, PseudoPeerError,
+        load_pseudo_peer_key, start_pseudo_peer,
     },
-    pseudo_peer::{BlockSourceConfig, start_pseudo_peer},
 };
+use alloy_primitives::{U128, U256};
 use alloy_rlp::{Decodable, Encodable};
+use futures::future::BoxFuture;
 use reth::{
     api::{FullNodeTypes, TxTy},
     builder::{BuilderContext, components::NetworkBuilder},
+    tasks::TaskSpawner,
     transaction_pool::{PoolTransaction, TransactionPool},
 };
 use reth_discv4::NodeRecord;
 use reth_engine_primitives::ConsensusEngineHandle;
-use reth_eth_wire::{BasicNetworkPrimitives, NewBlock, NewBlockPayload};
+use reth_eth_wire::{BasicNetworkPrimitives, NetworkPrimitives, NewBlock, NewBlockPayload};
 use reth_ethereum_primitives::PooledTransactionVariant;
-use reth_network::{NetworkConfig, NetworkHandle, NetworkManager};
+use reth_network::{
+    FetchClient, NetworkConfig, NetworkConfigBuilder, NetworkHandle, NetworkManager,
+};
 use reth_network_api::PeersInfo;
-use reth_provider::StageCheckpointReader;
+use reth_provider::{CanonStateSubscriptions, StageCheckpointReader};
 use reth_stages_types::StageId;
 use std::{
     net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
     sync::Arc,
 };
-use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio::{
+    sync::{Mutex, mpsc, oneshot},
+    task::JoinHandle,
+};
 use tracing::info;
 
 pub mod block_import;
+pub mod block_trace;
 
 /// HL `NewBlock` message value.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HlNewBlock(pub NewBlock<HlBlock>);
 
+impl HlNewBlock {
+    /// `NewBlock` message announcing `block`, with the chain's [`TOTAL_DIFFICULTY`].
+    pub fn new(block: HlBlock) -> Self {
+        Self(NewBlock { block, td: U128::from(TOTAL_DIFFICULTY) })
+    }
+
+    /// Whether the message carries the chain's [`TOTAL_DIFFICULTY`]. Anything else is a peer
+    /// on another chain or a broken one.
+    pub fn has_valid_td(&self) -> bool {
+        U256::from(self.0.td) == TOTAL_DIFFICULTY
+    }
+}
+
+/// Bounds on the `NewBlock` messages peers send, checked before anything of the message is
+/// allocated, so that a peer can't make the node allocate arbitrary amounts of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewBlockLimits {
+    /// Most transactions in a block.
+    pub max_transactions: usize,
+    /// Largest msgpack encoding of the block's read precompile calls, in bytes.
+    pub max_precompile_calls_bytes: usize,
+    /// Most blob sidecars in a block. HyperEVM has no blob transactions.
+    pub max_sidecars: usize,
+}
+
+impl NewBlockLimits {
+    pub const DEFAULT: Self =
+        Self { max_transactions: 10_000, max_precompile_calls_bytes: 8 << 20, max_sidecars: 0 };
+
+    /// Has the network built from `builder` decode the messages of peers under these limits.
+    ///
+    /// The network decodes messages in the tasks of its peer sessions without handing over any
+    /// state of the node, so the limits are carried into every task the network spawns on
+    /// `executor` instead, see [`NewBlockLimitsSpawner`].
+    pub fn configure_network<N, S>(
+        self,
+        builder: NetworkConfigBuilder<N>,
+        executor: S,
+    ) -> NetworkConfigBuilder<N>
+    where
+        N: NetworkPrimitives,
+        S: TaskSpawner + Clone + 'static,
+    {
+        builder
+            .with_task_executor(Box::new(NewBlockLimitsSpawner { inner: executor, limits: self }))
+    }
+
+    /// Limits of the network whose task is decoding a message, or the defaults outside of one.
+    fn current() -> Self {
+        NEW_BLOCK_LIMITS.try_with(|limits| *limits).unwrap_or(Self::DEFAULT)
+    }
+}
+
+impl Default for NewBlockLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+tokio::task_local! {
+    /// Limits of the network that spawned the running task.
+    static NEW_BLOCK_LIMITS: NewBlockLimits;
+}
+
+/// Spawns the tasks of a network, its peer sessions among them, under the network's
+/// [`NewBlockLimits`].
+#[derive(Debug, Clone)]
+pub struct NewBlockLimitsSpawner<S> {
+    inner: S,
+    limits: NewBlockLimits,
+}
+
+impl<S> NewBlockLimitsSpawner<S> {
+    fn scoped(&self, fut: BoxFuture<'static, ()>) -> BoxFuture<'static, ()> {
+        Box::pin(NEW_BLOCK_LIMITS.scope(self.limits, fut))
+    }
+}
+
+impl<S: TaskSpawner + Clone + 'static> TaskSpawner for NewBlockLimitsSpawner<S> {
+    fn spawn(&self, fut: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        self.inner.spawn(self.scoped(fut))
+    }
+
+    fn spawn_critical(&self, name: &'static str, fut: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        self.inner.spawn_critical(name, self.scoped(fut))
+    }
+
+    fn spawn_blocking(&self, fut: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        self.inner.spawn_blocking(self.scoped(fut))
+    }
+
+    fn spawn_critical_blocking(
+        &self,
+        name: &'static str,
+        fut: BoxFuture<'static, ()>,
+    ) -> JoinHandle<()> {
+        self.inner.spawn_critical_blocking(name, self.scoped(fut))
+    }
+}
+
 mod rlp {
     use super::*;
     use crate::{
@@ -46,11 +168,11 @@ mod rlp {
     };
     use alloy_consensus::BlobTransactionSidecar;
     use alloy_primitives::{Address, U128};
-    use alloy_rlp::{RlpDecodable, RlpEncodable};
+    use alloy_rlp::{EMPTY_STRING_CODE, Header, RlpDecodable, RlpEncodable};
     use alloy_rpc_types::Withdrawals;
     use std::borrow::Cow;
 
-    #[derive(RlpEncodable, RlpDecodable)]
+    #[derive(RlpEncodable)]
     #[rlp(trailing)]
     struct BlockHelper<'a> {
         header: Cow<'a, HlHeader>,
@@ -59,7 +181,7 @@ mod rlp {
         withdrawals: Option<Cow<'a, Withdrawals>>,
     }
 
-    #[derive(RlpEncodable, RlpDecodable)]
+    #[derive(RlpEncodable)]
     #[rlp(trailing)]
     struct HlNewBlockHelper<'a> {
         block: BlockHelper<'a>,
@@ -69,6 +191,27 @@ mod rlp {
         highest_precompile_address: Option<Cow<'a, Address>>,
     }
 
+    /// Owned counterpart of [`BlockHelper`], decoded straight into the fields of the block.
+    #[derive(RlpDecodable)]
+    #[rlp(trailing)]
+    struct DecodedBlock {
+        header: HlHeader,
+        transactions: Vec<TransactionSigned>,
+        ommers: Vec<HlHeader>,
+        withdrawals: Option<Withdrawals>,
+    }
+
+    /// Owned counterpart of [`HlNewBlockHelper`].
+    #[derive(RlpDecodable)]
+    #[rlp(trailing)]
+    struct DecodedNewBlock {
+        block: DecodedBlock,
+        td: U128,
+        sidecars: Option<Vec<BlobTransactionSidecar>>,
+        read_precompile_calls: Option<ReadPrecompileCalls>,
+        highest_precompile_address: Option<Address>,
+    }
+
     impl<'a> From<&'a HlNewBlock> for HlNewBlockHelper<'a> {
         fn from(value: &'a HlNewBlock) -> Self {
             let b = &value.0.block;
@@ -101,29 +244,104 @@ mod rlp {
         }
     }
 
-    impl Decodable for HlNewBlock {
-        fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
-            let h = HlNewBlockHelper::decode(buf)?;
+    /// Returns the payload of the list at the start of `buf`, advancing past it.
+    fn list_payload<'b>(buf: &mut &'b [u8]) -> alloy_rlp::Result<&'b [u8]> {
+        Header::decode_bytes(buf, true)
+    }
+
+    /// Skips the item at the start of `buf`.
+    fn skip_item(buf: &mut &[u8]) -> alloy_rlp::Result<()> {
+        let header = Header::decode(buf)?;
+        if buf.len() < header.payload_length {
+            return Err(alloy_rlp::Error::InputTooShort);
+        }
+        *buf = &buf[header.payload_length..];
+        Ok(())
+    }
+
+    /// Checks that the list payload `items` holds at most `max` items.
+    fn check_item_count(
+        mut items: &[u8],
+        max: usize,
+        error: &'static str,
+    ) -> alloy_rlp::Result<()> {
+        let mut count = 0;
+        while !items.is_empty() {
+            count += 1;
+            if count > max {
+                return Err(alloy_rlp::Error::Custom(error));
+            }
+            skip_item(&mut items)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the sizes in an encoded [`HlNewBlock`] against `limits`, walking the RLP headers
+    /// without decoding or allocating anything.
+    fn check_limits(mut buf: &[u8], limits: &NewBlockLimits) -> alloy_rlp::Result<()> {
+        let mut message = list_payload(&mut buf)?;
+        let mut block = list_payload(&mut message)?;
+        skip_item(&mut block)?;
+        check_item_count(
+            list_payload(&mut block)?,
+            limits.max_transactions,
+            "NewBlock has more transactions than allowed",
+        )?;
+        skip_item(&mut message)?;
+
+        // Trailing fields, each either absent, empty (0x80) or present
+        match message.first() {
+            Some(&EMPTY_STRING_CODE) => message = &message[1..],
+            Some(_) => check_item_count(
+                list_payload(&mut message)?,
+                limits.max_sidecars,
+                "NewBlock has more blob sidecars than allowed",
+            )?,
+            None => return Ok(()),
+        }
+        if !message.is_empty() {
+            let header = Header::decode(&mut message)?;
+            if header.payload_length > limits.max_precompile_calls_bytes {
+                return Err(alloy_rlp::Error::Custom(
+                    "NewBlock read precompile calls are larger than allowed",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    impl HlNewBlock {
+        /// Decodes a message, rejecting it if it exceeds `limits`.
+        pub fn decode_with_limits(
+            buf: &mut &[u8],
+            limits: &NewBlockLimits,
+        ) -> alloy_rlp::Result<Self> {
+            check_limits(buf, limits)?;
+            let h = DecodedNewBlock::decode(buf)?;
             Ok(HlNewBlock(NewBlock {
                 block: HlBlock {
-                    header: h.block.header.into_owned(),
+                    header: h.block.header,
                     body: HlBlockBody {
                         inner: BlockBody {
-                            transactions: h.block.transactions.into_owned(),
-                            ommers: h.block.ommers.into_owned(),
-                            withdrawals: h.block.withdrawals.map(|w| w.into_owned()),
+                            transactions: h.block.transactions,
+                            ommers: h.block.ommers,
+                            withdrawals: h.block.withdrawals,
                         },
-                        sidecars: h.sidecars.map(|s| s.into_owned()),
-                        read_precompile_calls: h.read_precompile_calls.map(|s| s.into_owned()),
-                        highest_precompile_address: h
-                            .highest_precompile_address
-                            .map(|s| s.into_owned()),
+                        sidecars: h.sidecars,
+                        read_precompile_calls: h.read_precompile_calls,
+                        highest_precompile_address: h.highest_precompile_address,
                     },
                 },
                 td: h.td,
             }))
         }
     }
+
+    impl Decodable for HlNewBlock {
+        fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+            Self::decode_with_limits(buf, &NewBlockLimits::current())
+        }
+    }
 }
 
 impl NewBlockPayload for HlNewBlock {
@@ -150,15 +368,32 @@ pub struct HlNetworkBuilder {
     pub(crate) debug_cutoff_height: Option<u64>,
 
     pub(crate) allow_network_overrides: bool,
+
+    pub(crate) engine_status: EngineStatus,
+
+    pub(crate) forkchoice_policy: ForkchoicePolicy,
+
+    pub(crate) spot_meta: SpotMetaContext,
+
+    pub(crate) fatal_errors: FatalErrors,
+
+    pub(crate) new_block_limits: NewBlockLimits,
+
+    pub(crate) block_spans: BlockSpans,
+
+    // defaults to `PSEUDO_PEER_KEY_FILE` under the datadir
+    pub(crate) pseudo_peer_key: Option<PathBuf>,
 }
 
 impl HlNetworkBuilder {
     /// Returns the [`NetworkConfig`] that contains the settings to launch the p2p network.
     ///
-    /// This applies the configured [`HlNetworkBuilder`] settings.
+    /// This applies the configured [`HlNetworkBuilder`] settings. The block import service uses
+    /// the fetch client received on `fetch_client_rx` for blocks announced by hash only.
     pub fn network_config<Node>(
         self,
         ctx: &BuilderContext<Node>,
+        fetch_client_rx: oneshot::Receiver<FetchClient<HlNetworkPrimitives>>,
     ) -> eyre::Result<NetworkConfig<Node::Provider, HlNetworkPrimitives>>
     where
         Node: FullNodeTypes<Types = HlNode>,
@@ -167,6 +402,10 @@ impl HlNetworkBuilder {
         let (to_network, import_outcome) = mpsc::unbounded_channel();
         let handle = ImportHandle::new(to_import, import_outcome);
         let consensus = Arc::new(HlConsensus { provider: ctx.provider().clone() });
+        let engine_status = self.engine_status.clone();
+        let forkchoice_policy = self.forkchoice_policy;
+        let new_block_limits = self.new_block_limits;
+        let block_spans = self.block_spans.clone();
 
         ctx.task_executor().spawn_critical("block import", async move {
             let handle = self
@@ -177,10 +416,18 @@ impl HlNetworkBuilder {
                 .expect("node should only be launched once")
                 .await
                 .unwrap();
-            ImportService::new(consensus, handle, from_network, to_network).await.unwrap();
+            let mut service = ImportService::new(consensus, handle, from_network, to_network)
+                .with_engine_status(engine_status)
+                .with_forkchoice_policy(forkchoice_policy)
+                .with_block_spans(block_spans);
+            if let Ok(fetch_client) = fetch_client_rx.await {
+                service = service.with_fetcher(Arc::new(fetch_client));
+            }
+            service.await.unwrap();
         });
 
-        let mut config_builder = ctx.network_config_builder()?;
+        let mut config_builder = new_block_limits
+            .configure_network(ctx.network_config_builder()?, ctx.task_executor().clone());
 
         // Only apply localhost-only network settings if network overrides are NOT allowed
         if !self.allow_network_overrides {
@@ -221,32 +468,66 @@ where
     ) -> eyre::Result<Self::Network> {
         let block_source_config = self.block_source_config.clone();
         let debug_cutoff_height = self.debug_cutoff_height;
-        let handle =
-            ctx.start_network(NetworkManager::builder(self.network_config(ctx)?).await?, pool);
+        let spot_meta = self.spot_meta.clone();
+        let (fetch_client_tx, fetch_client_rx) = oneshot::channel();
+        let network_config = self.network_config(ctx, fetch_client_rx)?;
+        let handle = ctx.start_network(NetworkManager::builder(network_config).await?, pool);
+        let _ = fetch_client_tx.send(handle.fetch_client().await?);
         let local_node_record = handle.local_node_record();
         info!(target: "reth::cli", enode=%local_node_record, "P2P networking initialized");
 
         if let Some(block_source_config) = block_source_config {
-            let next_block_number = ctx
-                .provider()
-                .get_stage_checkpoint(StageId::Finish)?
-                .unwrap_or_default()
-                .block_number
-                + 1;
+            let block_source_config = block_source_config
+                .with_local_blocks(Arc::new(ProviderSyncReader::new(ctx.provider().clone())));
+            let stage =
+                ctx.provider().get_stage_checkpoint(StageId::Finish)?.unwrap_or_default();
+            let (checkpointer, checkpoint) = BlockSourceCheckpointer::open(
+                ctx.provider().clone(),
+                block_source_config.checkpoint_key(),
+                stage.block_number,
+            )?;
+            let next_block_number = reconcile_start_height(stage.block_number, checkpoint) + 1;
 
+            let key_path = self.pseudo_peer_key.clone().unwrap_or_else(|| {
+                ctx.config().datadir().data_dir().join(PSEUDO_PEER_KEY_FILE)
+            });
+            let fatal_errors = self.fatal_errors.clone();
+            let context = PseudoPeerContext {
+                spot_meta,
+                progress: checkpointer.progress(),
+                fatal_errors: fatal_errors.clone(),
+                polling: block_source_config.polling,
+                block_spans: self.block_spans.clone(),
+                secret_key: Some(load_pseudo_peer_key(&key_path)?),
+                missing_block_timeout: block_source_config.missing_block_timeout,
+            };
+            ctx.task_executor().spawn_critical(
+                "block source checkpoint",
+                checkpointer.run(ctx.provider().canonical_state_stream()),
+            );
             let chain_spec = ctx.chain_spec();
             ctx.task_executor().spawn_critical("pseudo peer", async move {
-                let block_source = block_source_config
+                let block_source = match block_source_config
                     .create_cached_block_source((*chain_spec).clone(), next_block_number)
-                    .await;
-                start_pseudo_peer(
+                    .await
+                {
+                    Ok(block_source) => block_source,
+                    Err(err) => {
+                        fatal_errors.report(PseudoPeerError::Stopped(err));
+                        return;
+                    }
+                };
+                if let Err(err) = start_pseudo_peer(
                     chain_spec.clone(),
                     local_node_record.to_string(),
                     block_source,
                     debug_cutoff_height,
+                    context,
                 )
                 .await
-                .unwrap();
+                {
+                    fatal_errors.report(PseudoPeerError::Stopped(err));
+                }
             });
         } else {
             info!(target: "reth::cli", "No block source configured - syncing from P2P peers only");
@@ -262,3 +543,123 @@ static BOOTNODES: [&str; 0] = [];
 pub fn boot_nodes() -> Vec<NodeRecord> {
     BOOTNODES[..].iter().map(|s| s.parse().unwrap()).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HlBlockBody, HlHeader,
+        node::{
+            primitives::{BlockBody, TransactionSigned},
+            types::{ReadPrecompileInput, ReadPrecompileResult},
+        },
+    };
+    use alloy_consensus::{Header, Signed, TxLegacy};
+    use alloy_primitives::{Address, Bytes, Signature, U128};
+    use reth::tasks::TokioTaskExecutor;
+    use reth_chainspec::MAINNET;
+
+    fn new_block(transactions: usize, calls: Option<ReadPrecompileCalls>) -> HlNewBlock {
+        let tx = Signed::new_unhashed(TxLegacy::default(), Signature::test_signature());
+        let block = HlBlock {
+            header: HlHeader { inner: Header::default(), extras: Default::default() },
+            body: HlBlockBody {
+                inner: BlockBody {
+                    transactions: vec![TransactionSigned::Default(tx.into()); transactions],
+                    ommers: vec![],
+                    withdrawals: None,
+                },
+                sidecars: None,
+                read_precompile_calls: calls,
+                highest_precompile_address: None,
+            },
+        };
+        HlNewBlock(NewBlock { block, td: U128::from(1) })
+    }
+
+    #[test]
+    fn new_block_carries_zero_total_difficulty() {
+        let message = HlNewBlock::new(new_block(1, None).0.block);
+        assert_eq!(message.0.td, U128::ZERO);
+        assert!(message.has_valid_td());
+
+        // On the wire, the td is the empty string after the block
+        let encoded = alloy_rlp::encode(&message);
+        let mut payload = &encoded[..];
+        let mut fields = alloy_rlp::Header::decode_bytes(&mut payload, true).unwrap();
+        alloy_rlp::Header::decode_bytes(&mut fields, true).unwrap();
+        assert_eq!(fields.first(), Some(&alloy_rlp::EMPTY_STRING_CODE));
+        let decoded = HlNewBlock::decode(&mut encoded.as_slice()).unwrap();
+        assert!(decoded.has_valid_td());
+
+        // Any other td is rejected on import
+        assert!(!new_block(1, None).has_valid_td());
+    }
+
+    fn calls(output_len: usize) -> ReadPrecompileCalls {
+        let input = ReadPrecompileInput { input: Bytes::from_static(&[1, 2, 3, 4]), gas_limit: 0 };
+        let result =
+            ReadPrecompileResult::Ok { gas_used: 0, bytes: Bytes::from(vec![0; output_len]) };
+        ReadPrecompileCalls(vec![(Address::repeat_byte(8), vec![(input, result)])])
+    }
+
+    #[test]
+    fn new_block_within_limits_round_trips() {
+        let block = new_block(3, Some(calls(32)));
+        let encoded = alloy_rlp::encode(&block);
+        let limits = NewBlockLimits { max_transactions: 3, ..NewBlockLimits::DEFAULT };
+        let decoded = HlNewBlock::decode_with_limits(&mut encoded.as_slice(), &limits).unwrap();
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn new_block_beyond_limits_is_rejected() {
+        let encoded = alloy_rlp::encode(new_block(3, None));
+        let limits = NewBlockLimits { max_transactions: 2, ..NewBlockLimits::DEFAULT };
+        assert_eq!(
+            HlNewBlock::decode_with_limits(&mut encoded.as_slice(), &limits),
+            Err(alloy_rlp::Error::Custom("NewBlock has more transactions than allowed"))
+        );
+
+        let encoded = alloy_rlp::encode(new_block(1, Some(calls(4096))));
+        let limits = NewBlockLimits { max_precompile_calls_bytes: 1024, ..NewBlockLimits::DEFAULT };
+        assert_eq!(
+            HlNewBlock::decode_with_limits(&mut encoded.as_slice(), &limits),
+            Err(alloy_rlp::Error::Custom("NewBlock read precompile calls are larger than allowed"))
+        );
+    }
+
+    /// Decodes `encoded` in a task spawned by the network configured with `limits`, the way its
+    /// peer sessions do.
+    async fn decode_on_network(
+        limits: NewBlockLimits,
+        encoded: Vec<u8>,
+    ) -> alloy_rlp::Result<HlNewBlock> {
+        let builder = NetworkConfigBuilder::<HlNetworkPrimitives>::with_rng_secret_key();
+        let config = limits
+            .configure_network(builder, TokioTaskExecutor::default())
+            .build_with_noop_provider(MAINNET.clone());
+        let (tx, rx) = oneshot::channel();
+        config.executor.spawn(Box::pin(async move {
+            let _ = tx.send(HlNewBlock::decode(&mut encoded.as_slice()));
+        }));
+        rx.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn messages_are_decoded_under_the_limits_of_their_network() {
+        let strict = NewBlockLimits { max_transactions: 2, ..NewBlockLimits::DEFAULT };
+        let encoded = alloy_rlp::encode(new_block(3, None));
+
+        let (on_strict, on_default) = tokio::join!(
+            decode_on_network(strict, encoded.clone()),
+            decode_on_network(NewBlockLimits::DEFAULT, encoded.clone()),
+        );
+        assert_eq!(
+            on_strict,
+            Err(alloy_rlp::Error::Custom("NewBlock has more transactions than allowed"))
+        );
+        assert_eq!(on_default, Ok(new_block(3, None)));
+        assert!(decode_on_network(strict, alloy_rlp::encode(new_block(2, None))).await.is_ok());
+    }
+}
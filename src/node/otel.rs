@@ -0,0 +1,423 @@
+//! Export of traces and HL metrics to an OpenTelemetry collector over OTLP/HTTP, built with the
+//! `otel` feature and enabled by `--otlp-endpoint`.
+//!
+//! Spans are exported by a tracing layer installed next to reth's log layers. Metrics keep being
+//! recorded by the Prometheus recorder, the only global `metrics` recorder; the HL metrics (block
+//! sources, polling, forwarding, sync server, ...) are bridged by periodically reading the
+//! recorder's rendering. Counters are exported as OTLP counters, incremented by the growth of
+//! their total since the last reading, and the other series as OTLP gauges.
+
+use clap::Args;
+use opentelemetry::{
+    KeyValue,
+    metrics::{Counter, Gauge, Meter, MeterProvider as _},
+    trace::TracerProvider as _,
+};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    metrics::{PeriodicReader, SdkMeterProvider},
+    trace::SdkTracerProvider,
+};
+use reth::version::version_metadata;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::JoinHandle,
+    time::Duration,
+};
+use tracing::warn;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// Name of the exporting service and of its tracer and meter.
+const SERVICE_NAME: &str = "reth-hl";
+
+/// Prefix the Prometheus recorder installed by reth gives every metric name.
+const RECORDER_PREFIX: &str = "reth_";
+
+/// Prefixes of the Prometheus names of the metrics bridged to OTLP, after [`RECORDER_PREFIX`].
+const HL_METRIC_PREFIXES: &[&str] = &[
+    "block_source_",
+    "block_poller_",
+    "forwarder_",
+    "sync_server_",
+    "spot_meta_",
+    "replay_check_",
+    "rpc_rate_limit_",
+];
+
+/// OTLP export of traces and HL metrics.
+#[derive(Debug, Clone, Args)]
+pub struct OtlpArgs {
+    /// Base URL of the OTLP/HTTP collector to export traces and HL metrics to, e.g.
+    /// `http://localhost:4318`. Nothing is exported without it.
+    #[arg(long = "otlp-endpoint", env = "OTLP_ENDPOINT", global = true)]
+    pub endpoint: Option<String>,
+
+    /// Spans exported to the collector, as a `RUST_LOG`-style filter.
+    #[arg(long = "otlp-filter", env = "OTLP_FILTER", default_value = "info", global = true)]
+    pub filter: String,
+
+    /// Seconds between two exports of the HL metrics.
+    #[arg(
+        long = "otlp-metrics-interval",
+        env = "OTLP_METRICS_INTERVAL",
+        default_value_t = 30,
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    pub metrics_interval: u64,
+}
+
+/// Exporter of the node's traces and HL metrics. Dropping it flushes what wasn't exported yet.
+#[derive(Debug)]
+pub struct OtlpExport {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+    filter: String,
+    interval: Duration,
+    bridge: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+}
+
+impl OtlpExport {
+    /// Exports to `args.endpoint`, with `chain_id` and the node version as resource attributes.
+    /// Returns `None` without an endpoint.
+    pub fn new(args: &OtlpArgs, chain_id: Option<u64>) -> eyre::Result<Option<Self>> {
+        let Some(endpoint) = &args.endpoint else {
+            return Ok(None);
+        };
+        let endpoint = endpoint.trim_end_matches('/');
+        let interval = Duration::from_secs(args.metrics_interval);
+
+        let version = version_metadata();
+        let mut attributes = vec![
+            KeyValue::new("service.version", version.cargo_pkg_version.to_string()),
+            KeyValue::new("vcs.revision", version.vergen_git_sha_long.to_string()),
+        ];
+        if let Some(chain_id) = chain_id {
+            attributes.push(KeyValue::new("chain.id", chain_id as i64));
+        }
+        let resource =
+            Resource::builder().with_service_name(SERVICE_NAME).with_attributes(attributes).build();
+
+        let spans =
+            SpanExporter::builder().with_http().with_endpoint(format!("{endpoint}/v1/traces"));
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(spans.build()?)
+            .with_resource(resource.clone())
+            .build();
+
+        let metrics =
+            MetricExporter::builder().with_http().with_endpoint(format!("{endpoint}/v1/metrics"));
+        let reader = PeriodicReader::builder(metrics.build()?).with_interval(interval).build();
+        let meter_provider =
+            SdkMeterProvider::builder().with_reader(reader).with_resource(resource).build();
+
+        Ok(Some(Self {
+            tracer_provider,
+            meter_provider,
+            filter: args.filter.clone(),
+            interval,
+            bridge: None,
+        }))
+    }
+
+    /// The tracing layer exporting the spans selected by `--otlp-filter`.
+    pub fn tracing_layer(&self) -> eyre::Result<impl Layer<Registry> + Send + Sync + 'static> {
+        let tracer = self.tracer_provider.tracer(SERVICE_NAME);
+        let filter = EnvFilter::try_new(&self.filter)?;
+        Ok(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(filter))
+    }
+
+    /// Bridges the HL metrics out of the Prometheus rendering returned by `render`, read once
+    /// per metrics export.
+    pub fn bridge_metrics(&mut self, render: impl Fn() -> String + Send + 'static) {
+        let mut bridge = MetricsBridge::new(self.meter_provider.meter(SERVICE_NAME));
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let interval = self.interval;
+        let handle = std::thread::spawn(move || {
+            loop {
+                bridge.record(&render());
+                match stop_rx.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // Stopped, after a last reading for the final export
+                    _ => return bridge.record(&render()),
+                }
+            }
+        });
+        self.bridge = Some((stop_tx, handle));
+    }
+}
+
+impl Drop for OtlpExport {
+    fn drop(&mut self) {
+        if let Some((stop, handle)) = self.bridge.take() {
+            let _ = stop.send(());
+            let _ = handle.join();
+        }
+        if let Err(err) = self.tracer_provider.shutdown() {
+            warn!(%err, "Failed to flush the OTLP trace export");
+        }
+        if let Err(err) = self.meter_provider.shutdown() {
+            warn!(%err, "Failed to flush the OTLP metrics export");
+        }
+    }
+}
+
+/// Records the HL series of a Prometheus rendering as OTLP counters and gauges.
+struct MetricsBridge {
+    meter: Meter,
+    counters: HashMap<String, Counter<f64>>,
+    gauges: HashMap<String, Gauge<f64>>,
+    /// Last total of each counter series, by series.
+    totals: HashMap<String, f64>,
+}
+
+impl MetricsBridge {
+    fn new(meter: Meter) -> Self {
+        Self { meter, counters: HashMap::new(), gauges: HashMap::new(), totals: HashMap::new() }
+    }
+
+    fn record(&mut self, rendered: &str) {
+        let counters: HashSet<_> = rendered
+            .lines()
+            .filter_map(|line| line.strip_prefix("# TYPE ")?.strip_suffix(" counter"))
+            .collect();
+        for sample in rendered.lines().filter_map(parse_sample) {
+            let Some(hl_name) = sample.name.strip_prefix(RECORDER_PREFIX) else { continue };
+            if !HL_METRIC_PREFIXES.iter().any(|prefix| hl_name.starts_with(prefix)) {
+                continue;
+            }
+            let name = sample.name;
+            if !counters.contains(name) {
+                let gauge = self
+                    .gauges
+                    .entry(name.to_owned())
+                    .or_insert_with(|| self.meter.f64_gauge(name.to_owned()).build());
+                gauge.record(sample.value, &sample.labels);
+                continue;
+            }
+
+            let total = self.totals.entry(sample.series.to_owned()).or_default();
+            // A total below the last one was reset, e.g. by a restart of the recorder
+            let increment =
+                if sample.value >= *total { sample.value - *total } else { sample.value };
+            *total = sample.value;
+            let counter = self
+                .counters
+                .entry(name.to_owned())
+                .or_insert_with(|| self.meter.f64_counter(name.to_owned()).build());
+            counter.add(increment, &sample.labels);
+        }
+    }
+}
+
+/// A sample line of the Prometheus text format, e.g. `name{label="value"} 1.5`.
+#[derive(Debug)]
+struct Sample<'a> {
+    /// The name and labels, as rendered.
+    series: &'a str,
+    name: &'a str,
+    labels: Vec<KeyValue>,
+    value: f64,
+}
+
+/// Parses a sample line of the Prometheus text format.
+fn parse_sample(line: &str) -> Option<Sample<'_>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (series, value) = line.rsplit_once(' ')?;
+    let value = value.parse().ok()?;
+    let Some((name, labels)) = series.split_once('{') else {
+        return Some(Sample { series, name: series, labels: Vec::new(), value });
+    };
+
+    let mut attributes = Vec::new();
+    let mut rest = labels.strip_suffix('}')?;
+    while let Some((key, tail)) = rest.split_once("=\"") {
+        // Values escape quotes, so the value ends at the first unescaped one
+        let mut end = None;
+        let mut escaped = false;
+        for (i, c) in tail.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    end = Some(i);
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+        let end = end?;
+        let value = tail[..end].replace("\\\"", "\"").replace("\\\\", "\\");
+        attributes.push(KeyValue::new(key.trim_start_matches(',').to_owned(), value));
+        rest = &tail[end + 1..];
+    }
+    Some(Sample { series, name, labels: attributes, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pseudo_peer::BlockSourceMetrics;
+    use opentelemetry_proto::tonic::{
+        collector::metrics::v1::ExportMetricsServiceRequest,
+        common::v1::any_value,
+        metrics::v1::{Metric, metric::Data, number_data_point::Value},
+    };
+    use prost::Message;
+    use reth::prometheus_exporter::install_prometheus_recorder;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Requests received by the collector stub, as (path, body).
+    type Received = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    /// An OTLP/HTTP collector accepting every export.
+    fn collector() -> (String, Received) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let received = Received::default();
+        let requests = received.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (mut stream, requests) = (stream.unwrap(), requests.clone());
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    loop {
+                        let mut request_line = String::new();
+                        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let path = request_line.split(' ').nth(1).unwrap_or_default().to_owned();
+                        let mut length = 0;
+                        loop {
+                            let mut header = String::new();
+                            reader.read_line(&mut header).unwrap();
+                            if header.trim().is_empty() {
+                                break;
+                            }
+                            if let Some((name, value)) = header.split_once(':') &&
+                                name.eq_ignore_ascii_case("content-length")
+                            {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                        let mut body = vec![0; length];
+                        reader.read_exact(&mut body).unwrap();
+                        requests.lock().unwrap().push((path, body));
+                        stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").unwrap();
+                    }
+                });
+            }
+        });
+        (endpoint, received)
+    }
+
+    fn contains(body: &[u8], needle: &str) -> bool {
+        body.windows(needle.len()).any(|window| window == needle.as_bytes())
+    }
+
+    /// The metrics of the last export received by the collector, by name.
+    fn exported_metrics(received: &Received) -> HashMap<String, Metric> {
+        let received = received.lock().unwrap();
+        let (_, body) = received.iter().rfind(|(path, _)| path == "/v1/metrics").unwrap();
+        let request = ExportMetricsServiceRequest::decode(body.as_slice()).unwrap();
+        request
+            .resource_metrics
+            .into_iter()
+            .flat_map(|resource| resource.scope_metrics)
+            .flat_map(|scope| scope.metrics)
+            .map(|metric| (metric.name.clone(), metric))
+            .collect()
+    }
+
+    /// Value of the data point of `metric` labeled `kind`.
+    fn value_of_kind(metric: &Metric, kind: &str) -> Option<f64> {
+        let points = match metric.data.as_ref()? {
+            Data::Sum(sum) => &sum.data_points,
+            Data::Gauge(gauge) => &gauge.data_points,
+            _ => return None,
+        };
+        let point = points.iter().find(|point| {
+            point.attributes.iter().any(|attribute| {
+                let value = attribute.value.as_ref().and_then(|value| value.value.as_ref());
+                attribute.key == "kind" &&
+                    matches!(value, Some(any_value::Value::StringValue(value)) if value == kind)
+            })
+        })?;
+        match point.value? {
+            Value::AsDouble(value) => Some(value),
+            Value::AsInt(value) => Some(value as f64),
+        }
+    }
+
+    #[test]
+    fn parses_prometheus_samples() {
+        let sample = parse_sample(r#"block_source_rpc_errors{kind="a \"b\"",peer="x"} 3"#).unwrap();
+        assert_eq!((sample.name, sample.value), ("block_source_rpc_errors", 3.0));
+        assert_eq!(sample.series, r#"block_source_rpc_errors{kind="a \"b\"",peer="x"}"#);
+        let expected = [KeyValue::new("kind", "a \"b\""), KeyValue::new("peer", "x")];
+        assert_eq!(sample.labels, expected);
+        assert_eq!(parse_sample("block_poller_polling_interval 0.25").unwrap().value, 0.25);
+        assert!(parse_sample("# TYPE block_poller_polling_interval gauge").is_none());
+    }
+
+    #[test]
+    fn exports_traces_and_hl_metrics_to_the_collector() {
+        let (endpoint, received) = collector();
+        let args = OtlpArgs {
+            endpoint: Some(endpoint),
+            filter: "info".to_owned(),
+            metrics_interval: 3600,
+        };
+        let mut export = OtlpExport::new(&args, Some(999)).unwrap().unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(export.tracing_layer().unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("import", height = 7).in_scope(|| {});
+            // Filtered out by the default filter
+            tracing::trace_span!("fetch").in_scope(|| {});
+        });
+
+        // Recorded through reth's recorder, as the node does
+        let recorder = install_prometheus_recorder();
+        let source_metrics = BlockSourceMetrics::for_kind("otel_test");
+        source_metrics.fetched.increment(40);
+        source_metrics.chunk_size.set(7.0);
+        reth_metrics::metrics::counter!("sync.checkpoint").increment(1);
+        let handle = recorder.handle().clone();
+        assert!(handle.render().contains("reth_block_source_fetched{"));
+        export.bridge_metrics(move || handle.render());
+        source_metrics.fetched.increment(2);
+        // Flushes both exports
+        drop(export);
+
+        let requests = received.lock().unwrap();
+        let traces: Vec<_> =
+            requests.iter().filter(|(path, _)| path == "/v1/traces").map(|(_, b)| b).collect();
+        let metrics: Vec<_> =
+            requests.iter().filter(|(path, _)| path == "/v1/metrics").map(|(_, b)| b).collect();
+        assert!(traces.iter().any(|body| contains(body, "import")), "{requests:?}");
+        assert!(traces.iter().all(|body| !contains(body, "fetch")));
+        assert!(traces.iter().any(|body| contains(body, "chain.id")));
+        assert!(metrics.iter().any(|body| contains(body, SERVICE_NAME)));
+        drop(requests);
+
+        let exported = exported_metrics(&received);
+        assert!(!exported.contains_key("reth_sync_checkpoint"), "{:?}", exported.keys());
+        // Counters are exported as monotonic sums of the increments
+        let fetched = &exported["reth_block_source_fetched"];
+        assert!(matches!(&fetched.data, Some(Data::Sum(sum)) if sum.is_monotonic));
+        assert_eq!(value_of_kind(fetched, "otel_test"), Some(42.0));
+        let chunk_size = &exported["reth_block_source_chunk_size"];
+        assert!(matches!(chunk_size.data, Some(Data::Gauge(_))));
+        assert_eq!(value_of_kind(chunk_size, "otel_test"), Some(7.0));
+    }
+}
@@ -0,0 +1,356 @@
+//! Checks run before the node launches, so that a misconfiguration fails startup with one report
+//! instead of surfacing minutes later in the task that hits it.
+//!
+//! Every configured dependency is probed with a cheap call under a timeout, without building the
+//! block sources: each block source with one listing or request, the upstream RPC for its chain
+//! id, the datadir for writability and free space, and with `--preflight.spot-meta` the
+//! Hyperliquid API for spot metadata. Failed checks abort the launch unless `--skip-preflight` is
+//! given; warnings are only logged.
+
+use crate::{
+    chainspec::HlChainSpec,
+    node::spot_meta::erc20_contract_to_spot_token,
+    pseudo_peer::{BlockSourceConfig, BlockSourceType},
+};
+use alloy_primitives::U64;
+use futures::future::join_all;
+use jsonrpsee::{core::client::ClientT, http_client::HttpClientBuilder, rpc_params};
+use reth_chainspec::EthChainSpec;
+use std::{
+    fmt,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+/// How long each check may take.
+pub const DEFAULT_PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Free space of the datadir below which the launch is aborted.
+const MIN_FREE_SPACE: u64 = 1 << 30;
+
+/// Result of a check, with what was found or why it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Pass(String),
+    /// Something the node can start without, but likely needs.
+    Warn(String),
+    Fail(String),
+}
+
+/// A named check and its outcome.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: String,
+    pub outcome: CheckOutcome,
+}
+
+impl CheckResult {
+    fn new(name: impl Into<String>, outcome: CheckOutcome) -> Self {
+        Self { name: name.into(), outcome }
+    }
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (status, detail) = match &self.outcome {
+            CheckOutcome::Pass(detail) => ("pass", detail),
+            CheckOutcome::Warn(detail) => ("warn", detail),
+            CheckOutcome::Fail(detail) => ("FAIL", detail),
+        };
+        write!(f, "[{status}] {}: {detail}", self.name)
+    }
+}
+
+/// Outcomes of all checks.
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl PreflightReport {
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|check| matches!(check.outcome, CheckOutcome::Fail(_)))
+    }
+
+    /// Logs every check, at the level of its outcome.
+    pub fn log(&self) {
+        for check in &self.checks {
+            match check.outcome {
+                CheckOutcome::Pass(_) => info!(target: "reth::cli", "Preflight {check}"),
+                CheckOutcome::Warn(_) => warn!(target: "reth::cli", "Preflight {check}"),
+                CheckOutcome::Fail(_) => error!(target: "reth::cli", "Preflight {check}"),
+            }
+        }
+    }
+
+    /// Fails with the failed checks, if any.
+    pub fn ensure_passed(&self) -> eyre::Result<()> {
+        let failures: Vec<_> = self.failures().map(ToString::to_string).collect();
+        eyre::ensure!(
+            failures.is_empty(),
+            "preflight checks failed (pass --skip-preflight to start anyway):\n  {}",
+            failures.join("\n  ")
+        );
+        Ok(())
+    }
+}
+
+/// The dependencies of a node to check before it launches.
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    chain_spec: Arc<HlChainSpec>,
+    datadir: PathBuf,
+    block_source: Option<BlockSourceConfig>,
+    upstream_rpc_url: Option<String>,
+    spot_meta: bool,
+    timeout: Duration,
+}
+
+impl Preflight {
+    pub fn new(chain_spec: Arc<HlChainSpec>, datadir: impl Into<PathBuf>) -> Self {
+        Self {
+            chain_spec,
+            datadir: datadir.into(),
+            block_source: None,
+            upstream_rpc_url: None,
+            spot_meta: false,
+            timeout: DEFAULT_PREFLIGHT_TIMEOUT,
+        }
+    }
+
+    pub fn with_block_source(mut self, block_source: Option<BlockSourceConfig>) -> Self {
+        self.block_source = block_source;
+        self
+    }
+
+    pub fn with_upstream_rpc_url(mut self, upstream_rpc_url: impl Into<String>) -> Self {
+        self.upstream_rpc_url = Some(upstream_rpc_url.into());
+        self
+    }
+
+    /// Also checks that spot metadata can be fetched from the Hyperliquid API.
+    pub fn with_spot_meta(mut self, spot_meta: bool) -> Self {
+        self.spot_meta = spot_meta;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs all checks concurrently.
+    pub async fn run(&self) -> PreflightReport {
+        let chain_id = self.chain_spec.chain().id();
+        let block_sources = async {
+            let Some(config) = &self.block_source else { return vec![] };
+            // Every route of a routed source is checked on its own
+            let sources = match &config.source_type {
+                BlockSourceType::Routed { routes } => routes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (source_type, _))| {
+                        (format!("block source route {}", i + 1), source_type)
+                    })
+                    .collect(),
+                source_type => vec![("block source".to_string(), source_type)],
+            };
+            let mut checks = join_all(sources.into_iter().map(|(name, source_type)| async move {
+                CheckResult::new(
+                    name,
+                    check_block_source(source_type, &self.chain_spec, self.timeout).await,
+                )
+            }))
+            .await;
+            if let Some(args) = &config.block_source_from_node {
+                checks
+                    .push(CheckResult::new("hl-node files", check_readable_dir(&args.root).await));
+            }
+            checks
+        };
+        let upstream = async {
+            let url = self.upstream_rpc_url.as_deref()?;
+            Some(check_upstream(url, chain_id, self.timeout).await)
+        };
+        let spot_meta = async {
+            if !self.spot_meta {
+                return None;
+            }
+            Some(check_spot_meta(chain_id, self.timeout).await)
+        };
+        let (mut checks, upstream, datadir, spot_meta) =
+            tokio::join!(block_sources, upstream, check_datadir(&self.datadir), spot_meta);
+
+        if let Some(outcome) = upstream {
+            checks.push(CheckResult::new("upstream RPC", outcome));
+        }
+        checks.push(CheckResult::new("datadir", datadir));
+        if let Some(outcome) = spot_meta {
+            checks.push(CheckResult::new("spot metadata", outcome));
+        }
+        PreflightReport { checks }
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    fut: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    tokio::time::timeout(timeout, fut)
+        .await
+        .unwrap_or_else(|_| Err(format!("no response within {timeout:?}")))
+}
+
+/// Probes the block source with one cheap request, see [`BlockSourceType::probe`].
+pub async fn check_block_source(
+    source_type: &BlockSourceType,
+    chain_spec: &HlChainSpec,
+    timeout: Duration,
+) -> CheckOutcome {
+    match with_timeout(timeout, source_type.probe(chain_spec)).await {
+        Ok(found) => CheckOutcome::Pass(found),
+        Err(err) => CheckOutcome::Fail(err),
+    }
+}
+
+/// Checks that `eth_chainId` of the upstream RPC at `url` is `chain_id`.
+///
+/// Only an upstream of another chain fails, as transactions would be forwarded to the wrong
+/// chain; one that can't be reached is only warned about, as the node imports blocks without it.
+pub async fn check_upstream(url: &str, chain_id: u64, timeout: Duration) -> CheckOutcome {
+    let upstream_chain_id = with_timeout(timeout, async {
+        let client = HttpClientBuilder::default()
+            .request_timeout(timeout)
+            .build(url)
+            .map_err(|err| format!("invalid URL {url}: {err}"))?;
+        let chain_id: U64 = client
+            .request("eth_chainId", rpc_params![])
+            .await
+            .map_err(|err| format!("{url} is unreachable: {err}"))?;
+        Ok(chain_id.to::<u64>())
+    })
+    .await;
+    match upstream_chain_id {
+        Ok(id) if id == chain_id => CheckOutcome::Pass(format!("{url}, chain id {id}")),
+        Ok(id) => CheckOutcome::Fail(format!(
+            "{url} serves chain id {id}, but the node runs chain {chain_id}; transactions \
+             would be forwarded to the wrong chain"
+        )),
+        Err(err) => {
+            CheckOutcome::Warn(format!("{err}; transactions can't be forwarded until it is"))
+        }
+    }
+}
+
+async fn check_readable_dir(path: &Path) -> CheckOutcome {
+    match tokio::fs::read_dir(path).await {
+        Ok(_) => CheckOutcome::Pass(format!("{} is readable", path.display())),
+        Err(err) => CheckOutcome::Fail(format!("{} is not readable: {err}", path.display())),
+    }
+}
+
+/// Checks that the datadir can be written to and has space left.
+async fn check_datadir(path: &Path) -> CheckOutcome {
+    let probe = path.join(".preflight");
+    let written = tokio::fs::create_dir_all(path).await.and(tokio::fs::write(&probe, b"").await);
+    if let Err(err) = written {
+        return CheckOutcome::Fail(format!("{} is not writable: {err}", path.display()));
+    }
+    let _ = tokio::fs::remove_file(&probe).await;
+
+    match free_space(path) {
+        Ok(free) if free < MIN_FREE_SPACE => {
+            CheckOutcome::Fail(format!("{} has only {} MiB free", path.display(), free >> 20))
+        }
+        Ok(free) => CheckOutcome::Pass(format!("{}, {} GiB free", path.display(), free >> 30)),
+        Err(err) => CheckOutcome::Warn(format!("free space of {} unknown: {err}", path.display())),
+    }
+}
+
+/// Checks that the spot metadata system transaction senders are derived from can be fetched.
+///
+/// Spot metadata is also persisted in the database once fetched, so a node that fetched it
+/// before can still import blocks without it: the check only warns.
+async fn check_spot_meta(chain_id: u64, timeout: Duration) -> CheckOutcome {
+    let fetched = with_timeout(timeout, async {
+        tokio::task::spawn_blocking(move || erc20_contract_to_spot_token(chain_id))
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())
+    })
+    .await;
+    match fetched {
+        Ok(tokens) => CheckOutcome::Pass(format!("{} spot tokens", tokens.len())),
+        Err(err) => CheckOutcome::Warn(format!(
+            "failed to fetch: {err}; blocks with system transactions of tokens missing from the \
+             database can't be imported until it is reachable"
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: an all-zero `statvfs` is valid, and it is only read after `statvfs` filled it
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` outlives the call
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chainspec::parser::chain_value_parser;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn probes_unreachable_dependencies() {
+        let chain_spec = chain_value_parser("mainnet").unwrap();
+        let missing = BlockSourceConfig::local("/nonexistent/evm-blocks".into());
+        let outcome = check_block_source(&missing.source_type, &chain_spec, TIMEOUT).await;
+        assert!(matches!(&outcome, CheckOutcome::Fail(err) if err.contains("not readable")));
+        // Nothing listens on port 1
+        let rpc = BlockSourceType::Rpc {
+            url: "http://127.0.0.1:1".into(),
+            polling_interval: TIMEOUT,
+            batching: Default::default(),
+        };
+        let outcome = check_block_source(&rpc, &chain_spec, TIMEOUT).await;
+        assert!(matches!(&outcome, CheckOutcome::Fail(err) if err.contains("unreachable")));
+
+        // An unreachable upstream is only a warning
+        let outcome = check_upstream("http://127.0.0.1:1", 999, TIMEOUT).await;
+        assert!(matches!(&outcome, CheckOutcome::Warn(err) if err.contains("unreachable")));
+
+        let datadir = tempfile::tempdir().unwrap();
+        assert!(!matches!(check_datadir(datadir.path()).await, CheckOutcome::Fail(_)));
+    }
+
+    #[test]
+    fn report_lists_failures() {
+        let report = PreflightReport {
+            checks: vec![
+                CheckResult::new("datadir", CheckOutcome::Pass("ok".into())),
+                CheckResult::new("upstream RPC", CheckOutcome::Fail("serves chain id 1".into())),
+            ],
+        };
+        let err = report.ensure_passed().unwrap_err().to_string();
+        assert!(err.contains("[FAIL] upstream RPC: serves chain id 1"), "{err}");
+        assert!(!err.contains("datadir"), "{err}");
+        assert!(PreflightReport::default().ensure_passed().is_ok());
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
     HlHeader,
     node::{
         primitives::TransactionSigned,
-        types::{ReadPrecompileCall, ReadPrecompileCalls},
+        types::ReadPrecompileCalls,
     },
 };
 
@@ -42,9 +42,8 @@ impl InMemorySize for HlBlockBody {
             self.sidecars
                 .as_ref()
                 .map_or(0, |s| s.capacity() * core::mem::size_of::<BlobTransactionSidecar>()) +
-            self.read_precompile_calls
-                .as_ref()
-                .map_or(0, |s| s.0.capacity() * core::mem::size_of::<ReadPrecompileCall>())
+            self.read_precompile_calls.as_ref().map_or(0, |calls| calls.size()) +
+            self.highest_precompile_address.as_ref().map_or(0, |_| size_of::<Address>())
     }
 }
 
@@ -1,3 +1,4 @@
+use crate::chainspec::TOTAL_DIFFICULTY;
 use alloy_consensus::Header;
 use alloy_primitives::{Address, B64, B256, BlockNumber, Bloom, Bytes, Sealable, U256};
 use alloy_rlp::{RlpDecodable, RlpEncodable};
@@ -56,6 +57,17 @@ impl HlHeader {
             extras: HlHeaderExtras { logs_bloom_with_system_txs: logs_bloom, system_tx_count },
         }
     }
+
+    /// Bloom of the logs of user transactions only, as in the official block header.
+    pub fn user_logs_bloom(&self) -> Bloom {
+        self.inner.logs_bloom
+    }
+
+    /// Bloom of the logs of all transactions, system transactions included. This is the bloom
+    /// returned by [`BlockHeader::logs_bloom`](alloy_consensus::BlockHeader::logs_bloom).
+    pub fn logs_bloom_with_system_txs(&self) -> Bloom {
+        self.extras.logs_bloom_with_system_txs
+    }
 }
 
 impl From<Header> for HlHeader {
@@ -236,11 +248,26 @@ pub fn to_ethereum_ommers(ommers: &[HlHeader]) -> Vec<Header> {
     ommers.iter().map(|ommer| ommer.clone().into()).collect()
 }
 
+/// Reports [`TOTAL_DIFFICULTY`] as `totalDifficulty`, the same in every mode and version.
 impl FromConsensusHeader<HlHeader> for alloy_rpc_types::Header {
     fn from_consensus_header(header: SealedHeader<HlHeader>, block_size: usize) -> Self {
-        FromConsensusHeader::<Header>::from_consensus_header(
-            SealedHeader::<Header>::new(header.inner.clone(), header.hash()),
-            block_size,
-        )
+        let header = SealedHeader::<Header>::new(header.inner.clone(), header.hash());
+        Self::from_consensus(header.into(), Some(TOTAL_DIFFICULTY), Some(U256::from(block_size)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn rpc_header_reports_zero_total_difficulty() {
+        let header = SealedHeader::seal_slow(HlHeader::default());
+        let rpc_header: alloy_rpc_types::Header =
+            FromConsensusHeader::from_consensus_header(header, 600);
+        let rpc_header = serde_json::to_value(rpc_header).unwrap();
+        assert_eq!(rpc_header["totalDifficulty"], json!("0x0"));
+        assert_eq!(rpc_header["size"], json!("0x258"));
     }
 }
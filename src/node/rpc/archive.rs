@@ -0,0 +1,137 @@
+//! Rejection of requests for data pruned by `--archive-window`.
+//!
+//! Without a check, pruned read precompile calls would read as empty and silently change replays,
+//! and pruned receipts and state would fail differently depending on the method. Requests below
+//! the window all fail with the same error instead, [`HlErrorCode::ArchivePruned`], whose data
+//! names the lowest available height so that clients can send the request to an archive node.
+//!
+//! State reads, e.g. `eth_getCode`, `eth_getStorageAt` and `eth_getBalance`, are checked when
+//! their state is loaded; state is never fetched from another node.
+
+use crate::node::{rpc::errors::HlErrorCode, storage::prune::ArchiveWindow};
+use jsonrpsee_types::ErrorObject;
+use reth_rpc_eth_types::{EthApiError, error::ToRpcError};
+use serde::{Deserialize, Serialize};
+
+/// Data pruned by the archive window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ArchiveData {
+    /// Read precompile calls and receipts, always pruned.
+    Blocks,
+    /// State history, only pruned with `--archive-window.state`.
+    State,
+}
+
+impl ArchiveData {
+    fn noun(&self) -> &'static str {
+        match self {
+            Self::Blocks => "data",
+            Self::State => "state",
+        }
+    }
+}
+
+/// Data attached to an [`HlErrorCode::ArchivePruned`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ArchivePrunedData {
+    /// What was pruned.
+    pub pruned: ArchiveData,
+    pub block_number: u64,
+    /// Lowest block whose data is available.
+    pub lowest_available: u64,
+}
+
+/// A request for data that fell out of the archive window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "{} of block {} is pruned, data is available from height {} (--archive-window {window})",
+    .data.pruned.noun(),
+    .data.block_number,
+    .data.lowest_available
+)]
+pub(crate) struct ArchivePruned {
+    data: ArchivePrunedData,
+    window: u64,
+}
+
+impl ToRpcError for ArchivePruned {
+    fn to_rpc_error(&self) -> ErrorObject<'static> {
+        ErrorObject::owned(HlErrorCode::ArchivePruned.code(), self.to_string(), Some(self.data))
+    }
+}
+
+/// Rejects requests for the `data` of `block_number` if it fell out of `window`.
+pub(crate) fn check_archive_window(
+    window: Option<ArchiveWindow>,
+    data: ArchiveData,
+    best_number: u64,
+    block_number: u64,
+) -> Result<(), EthApiError> {
+    let Some(window) = window else {
+        return Ok(());
+    };
+    if data == ArchiveData::State && !window.prune_state {
+        return Ok(());
+    }
+
+    let lowest_available = window.lowest_available(best_number);
+    if block_number < lowest_available {
+        let data = ArchivePrunedData { pruned: data, block_number, lowest_available };
+        return Err(EthApiError::other(ArchivePruned { data, window: window.blocks }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: ArchiveWindow = ArchiveWindow { blocks: 100, prune_state: false };
+
+    #[test]
+    fn in_window_is_allowed() {
+        assert!(check_archive_window(Some(WINDOW), ArchiveData::Blocks, 1000, 901).is_ok());
+        assert!(check_archive_window(Some(WINDOW), ArchiveData::Blocks, 1000, 1000).is_ok());
+        assert!(check_archive_window(None, ArchiveData::Blocks, 1000, 0).is_ok());
+    }
+
+    fn rpc_error(err: EthApiError) -> ErrorObject<'static> {
+        err.into()
+    }
+
+    #[test]
+    fn pruned_is_rejected() {
+        let err = check_archive_window(Some(WINDOW), ArchiveData::Blocks, 1000, 900).unwrap_err();
+        let err = rpc_error(err);
+        assert_eq!(err.code(), HlErrorCode::ArchivePruned.code());
+        assert!(err.message().contains("from height 901"), "{}", err.message());
+    }
+
+    #[test]
+    fn state_is_only_rejected_when_pruned() {
+        assert!(check_archive_window(Some(WINDOW), ArchiveData::State, 1000, 0).is_ok());
+        let window = ArchiveWindow { prune_state: true, ..WINDOW };
+        assert!(check_archive_window(Some(window), ArchiveData::State, 1000, 0).is_err());
+    }
+
+    #[test]
+    fn pruned_state_names_the_floor() {
+        let window = ArchiveWindow { prune_state: true, ..WINDOW };
+        // State reads such as `eth_getCode` and `eth_getStorageAt` at the floor are served
+        assert!(check_archive_window(Some(window), ArchiveData::State, 1000, 901).is_ok());
+
+        let err = rpc_error(
+            check_archive_window(Some(window), ArchiveData::State, 1000, 900).unwrap_err(),
+        );
+        assert_eq!(err.code(), HlErrorCode::ArchivePruned.code());
+        assert!(err.message().starts_with("state of block 900 is pruned"), "{}", err.message());
+        let data: ArchivePrunedData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data.pruned, ArchiveData::State);
+        assert_eq!((data.block_number, data.lowest_available), (900, 901));
+        let json = serde_json::to_value(data).unwrap();
+        assert_eq!(json["lowestAvailable"], 901);
+        assert_eq!(json["pruned"], "state");
+    }
+}
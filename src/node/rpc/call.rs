@@ -1,19 +1,28 @@
 use core::fmt;
 
-use super::{HlEthApi, HlRpcNodeCore};
+use super::{HlEthApi, HlRpcNodeCore, trace_timeout::check_trace_deadline};
 use crate::{HlBlock, node::evm::apply_precompiles};
 use alloy_consensus::transaction::TxHashRef;
-use alloy_evm::Evm;
+use alloy_evm::{
+    Evm,
+    overrides::{OverrideBlockHashes, apply_block_overrides, apply_state_overrides},
+};
+use alloy_network::TransactionBuilder;
 use alloy_primitives::B256;
+use alloy_rpc_types_eth::state::EvmOverrides;
 use reth::rpc::server_types::eth::EthApiError;
-use reth_evm::{ConfigureEvm, Database, EvmEnvFor, HaltReasonFor, InspectorFor, SpecFor, TxEnvFor};
+use reth_evm::{
+    ConfigureEvm, Database, EvmEnvFor, HaltReasonFor, InspectorFor, SpecFor, TransactionEnv,
+    TxEnvFor,
+};
 use reth_primitives::{NodePrimitives, Recovered};
 use reth_provider::{ProviderError, ProviderTx};
+use reth_rpc_convert::RpcTxReq;
 use reth_rpc_eth_api::{
     FromEvmError, RpcConvert, RpcNodeCore,
     helpers::{Call, EthCall},
 };
-use revm::{DatabaseCommit, context::result::ResultAndState};
+use revm::{DatabaseCommit, context::result::ResultAndState, context_interface::Transaction};
 
 impl<N> HlRpcNodeCore for N where N: RpcNodeCore<Primitives: NodePrimitives<Block = HlBlock>> {}
 
@@ -60,8 +69,7 @@ where
     where
         DB: Database<Error = ProviderError> + fmt::Debug,
     {
-        let block_number = evm_env.block_env().number;
-        let hl_extras = self.get_hl_extras(block_number.to::<u64>().into())?;
+        let hl_extras = self.hl_extras_for_env(evm_env.block_env())?;
 
         let mut evm = self.evm_config().evm_with_env(db, evm_env);
         apply_precompiles(&mut evm, &hl_extras);
@@ -81,8 +89,7 @@ where
         DB: Database<Error = ProviderError> + fmt::Debug,
         I: InspectorFor<Self::Evm, DB>,
     {
-        let block_number = evm_env.block_env().number;
-        let hl_extras = self.get_hl_extras(block_number.to::<u64>().into())?;
+        let hl_extras = self.hl_extras_for_env(evm_env.block_env())?;
 
         let mut evm = self.evm_config().evm_with_env_and_inspector(db, evm_env, inspector);
         apply_precompiles(&mut evm, &hl_extras);
@@ -102,8 +109,7 @@ where
         DB: Database<Error = ProviderError> + DatabaseCommit + core::fmt::Debug,
         I: IntoIterator<Item = Recovered<&'a ProviderTx<Self::Provider>>>,
     {
-        let block_number = evm_env.block_env().number;
-        let hl_extras = self.get_hl_extras(block_number.to::<u64>().into())?;
+        let hl_extras = self.hl_extras_for_env(evm_env.block_env())?;
 
         let mut evm = self.evm_config().evm_with_env(db, evm_env);
         apply_precompiles(&mut evm, &hl_extras);
@@ -115,10 +121,62 @@ where
                 break;
             }
 
+            check_trace_deadline()?;
             let tx_env = self.evm_config().tx_env(tx);
             evm.transact_commit(tx_env).map_err(Self::Error::from_evm_err)?;
             index += 1;
         }
         Ok(index)
     }
+
+    // Modified version that keeps the base fee of a `baseFee` block override, which reth lowers
+    // to 0 for calls without a gas price; comments are stripped out.
+    fn prepare_call_env<DB>(
+        &self,
+        mut evm_env: EvmEnvFor<Self::Evm>,
+        mut request: RpcTxReq<<Self::RpcConvert as RpcConvert>::Network>,
+        db: &mut DB,
+        overrides: EvmOverrides,
+    ) -> Result<(EvmEnvFor<Self::Evm>, TxEnvFor<Self::Evm>), Self::Error>
+    where
+        DB: Database + DatabaseCommit + OverrideBlockHashes,
+        EthApiError: From<<DB as Database>::Error>,
+    {
+        if let Some(requested_gas) = request.as_ref().gas_limit() {
+            let global_gas_cap = self.call_gas_limit();
+            if global_gas_cap != 0 && global_gas_cap < requested_gas {
+                request.as_mut().set_gas_limit(global_gas_cap);
+            }
+        }
+
+        evm_env.cfg_env.disable_eip3607 = true;
+        evm_env.cfg_env.disable_base_fee = true;
+        evm_env.cfg_env.disable_nonce_check = true;
+
+        request.as_mut().take_nonce();
+
+        let base_fee_override = overrides.block.as_ref().and_then(|block| block.base_fee);
+        if let Some(block_overrides) = overrides.block {
+            apply_block_overrides(*block_overrides, db, &mut evm_env.block_env);
+        }
+        if let Some(state_overrides) = overrides.state {
+            apply_state_overrides(state_overrides, db)
+                .map_err(EthApiError::from_state_overrides_err)?;
+        }
+
+        let request_gas = request.as_ref().gas_limit();
+        let mut tx_env = self.create_txn_env(&evm_env, request, &mut *db)?;
+
+        // Without an override, the base fee can't exceed the gas price (geth's behavior)
+        if tx_env.gas_price() == 0 && base_fee_override.is_none() {
+            evm_env.block_env.basefee = 0;
+        }
+
+        if request_gas.is_none() && tx_env.gas_price() > 0 {
+            let cap = self.caller_gas_allowance(db, &evm_env, &tx_env)?;
+            tx_env.set_gas_limit(cap.min(evm_env.block_env.gas_limit));
+        }
+
+        Ok((evm_env, tx_env))
+    }
 }
@@ -0,0 +1,175 @@
+//! `hl_callMany`, executing calls against the same block in a single EVM.
+//!
+//! Each `eth_call` builds an EVM for its block and installs the block's read precompile replays
+//! anew. The calls of `hl_callMany` share one EVM, with the replays installed once, which
+//! amortizes the setup over bundles of calls. Calls don't see each other's state changes: each
+//! result is the one `eth_call` would return at the block.
+//!
+//! A request holds a blocking thread while its calls run, so it is bounded: it has at most
+//! [`MAX_CALLS`] calls, which share the gas of a single `eth_call` (`--rpc.gascap`). Each call is
+//! given at most the gas the previous ones left, and fails out of gas past it.
+
+use super::{HlEthApi, HlRpcNodeCore, apply_precompiles};
+use alloy_eips::BlockId;
+use alloy_json_rpc::RpcObject;
+use alloy_network::TransactionBuilder;
+use alloy_primitives::Bytes;
+use alloy_rpc_types_eth::EthCallResponse;
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use reth_evm::{ConfigureEvm, Evm, HaltReasonFor, SpecFor, TransactionEnv, TxEnvFor};
+use reth_revm::{database::StateProviderDatabase, db::CacheDB};
+use reth_rpc_convert::{RpcConvert, RpcTxReq};
+use reth_rpc_eth_api::{
+    IntoEthApiError, RpcNodeCore,
+    helpers::{Call, LoadState, SpawnBlocking},
+};
+use reth_rpc_eth_types::{
+    EthApiError, RevertError, RpcInvalidTransactionError,
+    error::{FromEvmError, api::FromEvmHalt},
+};
+use revm::context_interface::{Transaction, result::ExecutionResult};
+use tracing::trace;
+
+/// Most calls of a single `hl_callMany` request.
+pub const MAX_CALLS: usize = 100;
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlCallManyApi<R: RpcObject> {
+    /// Executes `requests` as `eth_call`s at `block` (the latest block by default), returning
+    /// the output or error of each.
+    #[method(name = "callMany")]
+    async fn call_many(
+        &self,
+        requests: Vec<R>,
+        block: Option<BlockId>,
+    ) -> RpcResult<Vec<EthCallResponse>>;
+}
+
+pub struct HlCallManyExt<N: HlRpcNodeCore, Rpc: RpcConvert> {
+    eth_api: HlEthApi<N, Rpc>,
+}
+
+impl<N: HlRpcNodeCore, Rpc: RpcConvert> HlCallManyExt<N, Rpc> {
+    /// Creates a new instance of the [`HlCallManyExt`].
+    pub fn new(eth_api: HlEthApi<N, Rpc>) -> Self {
+        Self { eth_api }
+    }
+}
+
+/// The output of a call, or the error `eth_call` fails with.
+fn call_output<H>(result: ExecutionResult<H>, gas_limit: u64) -> Result<Bytes, EthApiError>
+where
+    EthApiError: FromEvmHalt<H>,
+{
+    match result {
+        ExecutionResult::Success { output, .. } => Ok(output.into_data()),
+        ExecutionResult::Revert { output, .. } => {
+            Err(RpcInvalidTransactionError::Revert(RevertError::new(output)).into_eth_err())
+        }
+        ExecutionResult::Halt { reason, .. } => Err(EthApiError::from_evm_halt(reason, gas_limit)),
+    }
+}
+
+impl<N, Rpc> HlEthApi<N, Rpc>
+where
+    N: HlRpcNodeCore,
+    EthApiError: FromEvmError<N::Evm> + FromEvmHalt<HaltReasonFor<N::Evm>>,
+    Rpc: RpcConvert<
+            Primitives = N::Primitives,
+            Error = EthApiError,
+            TxEnv = TxEnvFor<N::Evm>,
+            Spec = SpecFor<N::Evm>,
+        >,
+{
+    /// Executes `requests` at `block` in one EVM, see [`HlCallManyApiServer::call_many`].
+    pub(crate) async fn call_many(
+        &self,
+        requests: Vec<RpcTxReq<Rpc::Network>>,
+        block: BlockId,
+    ) -> Result<Vec<EthCallResponse>, EthApiError> {
+        if requests.len() > MAX_CALLS {
+            return Err(EthApiError::InvalidParams(format!(
+                "too many calls: {} exceeds the maximum of {MAX_CALLS}",
+                requests.len()
+            )));
+        }
+        let (mut evm_env, at) = self.evm_env_at(block).await?;
+        // The environment of `eth_call`
+        evm_env.cfg_env.disable_eip3607 = true;
+        evm_env.cfg_env.disable_base_fee = true;
+        evm_env.cfg_env.disable_nonce_check = true;
+
+        self.spawn_blocking_io_fut(move |this| async move {
+            let state = this.state_at_block_id(at).await?;
+            let mut db = CacheDB::new(StateProviderDatabase::new(state));
+
+            // Built before the EVM, which holds the database
+            // A gas cap of 0 leaves calls unlimited
+            let gas_cap = Some(this.call_gas_limit()).filter(|cap| *cap != 0).unwrap_or(u64::MAX);
+            let tx_envs: Vec<_> = requests
+                .into_iter()
+                .map(|mut request| {
+                    request.as_mut().take_nonce();
+                    let request_gas = request.as_ref().gas_limit();
+                    if request_gas.is_some_and(|gas| gas > gas_cap) {
+                        request.as_mut().set_gas_limit(gas_cap);
+                    }
+                    let mut tx_env = this.create_txn_env(&evm_env, request, &mut db)?;
+                    if request_gas.is_none() && tx_env.gas_price() > 0 {
+                        let allowance = this.caller_gas_allowance(&mut db, &evm_env, &tx_env)?;
+                        tx_env.set_gas_limit(tx_env.gas_limit().min(allowance));
+                    }
+                    Ok(tx_env)
+                })
+                .collect();
+
+            let hl_extras = this.hl_extras_for_env(evm_env.block_env())?;
+            let mut evm = this.evm_config().evm_with_env(&mut db, evm_env);
+            apply_precompiles(&mut evm, &hl_extras);
+
+            // Shared by the calls of the request
+            let mut gas_left = gas_cap;
+            Ok(tx_envs
+                .into_iter()
+                .map(|tx_env: Result<_, EthApiError>| {
+                    let output = tx_env.and_then(|mut tx_env| {
+                        let gas_limit = tx_env.gas_limit().min(gas_left);
+                        tx_env.set_gas_limit(gas_limit);
+                        let res = evm.transact(tx_env).map_err(EthApiError::from_evm_err)?;
+                        gas_left -= res.result.gas_used();
+                        call_output(res.result, gas_limit)
+                    });
+                    match output {
+                        Ok(value) => EthCallResponse { value: Some(value), error: None },
+                        Err(err) => EthCallResponse { value: None, error: Some(err.to_string()) },
+                    }
+                })
+                .collect())
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl<N, Rpc> HlCallManyApiServer<RpcTxReq<Rpc::Network>> for HlCallManyExt<N, Rpc>
+where
+    N: HlRpcNodeCore,
+    EthApiError: FromEvmError<N::Evm> + FromEvmHalt<HaltReasonFor<N::Evm>>,
+    Rpc: RpcConvert<
+            Primitives = N::Primitives,
+            Error = EthApiError,
+            TxEnv = TxEnvFor<N::Evm>,
+            Spec = SpecFor<N::Evm>,
+        >,
+{
+    async fn call_many(
+        &self,
+        requests: Vec<RpcTxReq<Rpc::Network>>,
+        block: Option<BlockId>,
+    ) -> RpcResult<Vec<EthCallResponse>> {
+        trace!(target: "rpc::hl", calls = requests.len(), ?block, "Serving hl_callMany");
+        Ok(self.eth_api.call_many(requests, block.unwrap_or_default()).await?)
+    }
+}
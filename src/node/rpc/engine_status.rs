@@ -0,0 +1,67 @@
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use reth::rpc::result::internal_rpc_err;
+use reth_provider::StageCheckpointReader;
+use reth_stages_types::StageId;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use crate::node::network::block_import::status::{EngineStatus, ForkchoiceStatus, ImportStatus};
+
+/// Response of `hl_engineStatus`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineStatusResponse {
+    #[serde(flatten)]
+    pub forkchoice: ForkchoiceStatus,
+    /// Block number of the `Finish` stage checkpoint.
+    pub finish_checkpoint: Option<u64>,
+}
+
+/// RPC for diagnosing a stalled import pipeline.
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlEngineStatusApi {
+    /// Returns the last forkchoice update sent to the engine, its outcome, and the `Finish`
+    /// stage checkpoint.
+    #[method(name = "engineStatus")]
+    async fn engine_status(&self) -> RpcResult<EngineStatusResponse>;
+
+    /// Returns the last imported block, the last import error, and whether the import pipeline
+    /// has stalled.
+    #[method(name = "importStatus")]
+    async fn import_status(&self) -> RpcResult<ImportStatus>;
+}
+
+pub struct HlEngineStatusExt<P> {
+    status: EngineStatus,
+    provider: P,
+}
+
+impl<P> HlEngineStatusExt<P> {
+    /// Creates a new instance of the [`HlEngineStatusExt`].
+    pub fn new(status: EngineStatus, provider: P) -> Self {
+        Self { status, provider }
+    }
+}
+
+#[async_trait]
+impl<P> HlEngineStatusApiServer for HlEngineStatusExt<P>
+where
+    P: StageCheckpointReader + Send + Sync + 'static,
+{
+    async fn engine_status(&self) -> RpcResult<EngineStatusResponse> {
+        trace!(target: "rpc::hl", "Serving hl_engineStatus");
+        let finish_checkpoint = self
+            .provider
+            .get_stage_checkpoint(StageId::Finish)
+            .map_err(|e| internal_rpc_err(e.to_string()))?
+            .map(|checkpoint| checkpoint.block_number);
+        Ok(EngineStatusResponse { forkchoice: self.status.forkchoice(), finish_checkpoint })
+    }
+
+    async fn import_status(&self) -> RpcResult<ImportStatus> {
+        trace!(target: "rpc::hl", "Serving hl_importStatus");
+        Ok(self.status.import())
+    }
+}
@@ -0,0 +1,136 @@
+//! JSON-RPC error codes of HL-specific failures.
+//!
+//! Failures that only exist on HyperEVM, e.g. data pruned by the archive window or a block above
+//! what the sync server serves, have their own code in `-39000..=-39099` rather than failing as
+//! `-32603` internal errors that clients could only tell apart by their message. The message is
+//! unchanged, and the values it names are attached as `data`.
+
+use alloy_primitives::B256;
+use jsonrpsee_types::ErrorObject;
+use reth_rpc_eth_types::{EthApiError, error::ToRpcError};
+use serde::{Deserialize, Serialize};
+
+/// Codes of the HL-specific JSON-RPC errors, and the data they carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum HlErrorCode {
+    /// Data of a block pruned by `--archive-window`, with data
+    /// `{ pruned, blockNumber, lowestAvailable }`.
+    ArchivePruned = -39000,
+    /// A block that was replaced while a call executed against it, with data `{ blockNumber }`.
+    /// Sending the call again runs it against the new block.
+    BlockReplaced = -39001,
+    /// A block above the tip served by the sync server, with data
+    /// `{ blockNumber, servedTip, syncedHeight, serveLag }`.
+    BlockNotServed = -39002,
+    /// A forwarded request whose upstream RPC couldn't be reached, with data `{ method }`.
+    UpstreamUnavailable = -39003,
+    /// A block that failed to re-execute for a state diff or a system trace, with data
+    /// `{ blockHash }`.
+    ReplayFailed = -39004,
+    /// A trace that ran past its timeout, with data `{ timeoutMs }`.
+    TraceTimeout = -39005,
+}
+
+impl HlErrorCode {
+    /// The JSON-RPC error code.
+    pub const fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Data of a [`HlErrorCode::BlockReplaced`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockReplacedData {
+    pub block_number: u64,
+}
+
+/// Data of a [`HlErrorCode::BlockNotServed`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockNotServedData {
+    pub block_number: u64,
+    /// Highest block served, `serve_lag` blocks below the synced height.
+    pub served_tip: u64,
+    pub synced_height: u64,
+    pub serve_lag: u64,
+}
+
+/// Data of a [`HlErrorCode::UpstreamUnavailable`] error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamUnavailableData {
+    /// The forwarded method.
+    pub method: String,
+}
+
+/// Data of a [`HlErrorCode::ReplayFailed`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayFailedData {
+    pub block_hash: B256,
+}
+
+/// Data of a [`HlErrorCode::TraceTimeout`] error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceTimeoutData {
+    pub timeout_ms: u64,
+}
+
+/// An HL-specific failure, returned with its [`HlErrorCode`] and data.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{message}")]
+pub struct HlRpcError {
+    code: HlErrorCode,
+    message: String,
+    data: serde_json::Value,
+}
+
+impl HlRpcError {
+    pub fn new(code: HlErrorCode, message: impl Into<String>, data: impl Serialize) -> Self {
+        let data = serde_json::to_value(data).expect("error data is serializable");
+        Self { code, message: message.into(), data }
+    }
+
+    pub fn code(&self) -> HlErrorCode {
+        self.code
+    }
+}
+
+impl ToRpcError for HlRpcError {
+    fn to_rpc_error(&self) -> ErrorObject<'static> {
+        ErrorObject::owned(self.code.code(), self.message.clone(), Some(&self.data))
+    }
+}
+
+impl From<HlRpcError> for ErrorObject<'static> {
+    fn from(err: HlRpcError) -> Self {
+        err.to_rpc_error()
+    }
+}
+
+impl From<HlRpcError> for EthApiError {
+    fn from(err: HlRpcError) -> Self {
+        Self::other(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_keep_their_message_and_carry_data() {
+        let data = BlockReplacedData { block_number: 7 };
+        let message = "block 7 was replaced during the call, retry";
+        let err: ErrorObject<'static> =
+            EthApiError::from(HlRpcError::new(HlErrorCode::BlockReplaced, message, data)).into();
+
+        assert_eq!(err.code(), -39001);
+        assert_eq!(err.message(), message);
+        let json: serde_json::Value = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(json, serde_json::json!({ "blockNumber": 7 }));
+    }
+}
@@ -0,0 +1,223 @@
+//! Lookup of [`HlExtras`] for RPC execution.
+//!
+//! Extras are cached by block hash rather than number: during an unwind, an RPC call racing the
+//! reorg could otherwise pair the state of a block with the extras of the block that replaced it
+//! at the same height. The state of a call is acquired by hash, and the hash is recorded for the
+//! thread executing against it (see [`enter_state_block`]), so that the extras are those of the
+//! same block. A call executes on the state of its own block, and a replay on the state of the
+//! parent of the replayed block, which is then the canonical child of that state or has been
+//! replaced since the caller loaded it.
+
+use crate::{
+    HlBlock,
+    node::{
+        rpc::errors::{BlockReplacedData, HlErrorCode, HlRpcError},
+        types::HlExtras,
+    },
+};
+use alloy_consensus::BlockHeader;
+use alloy_eips::{BlockHashOrNumber, BlockNumHash};
+use alloy_primitives::B256;
+use reth::rpc::server_types::eth::EthApiError;
+use reth_network::cache::LruMap;
+use reth_primitives::SealedHeader;
+use reth_provider::{BlockReader, ProviderResult};
+use std::{
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
+/// Number of blocks whose extras are cached.
+pub(crate) const EXTRAS_CACHE_SIZE: u32 = 1024;
+
+thread_local! {
+    /// Block whose state the call running on this thread executes on.
+    static STATE_BLOCK: Cell<Option<BlockNumHash>> = const { Cell::new(None) };
+}
+
+/// Records `block` as the block whose state the call running on this thread executes on, `None`
+/// for the state of a pending block. Called where the state is acquired, on the thread that then
+/// executes against it.
+pub(crate) fn enter_state_block(block: Option<BlockNumHash>) {
+    STATE_BLOCK.set(block);
+}
+
+/// Extras of recently used blocks by block hash.
+#[derive(Debug, Clone)]
+pub(crate) struct HlExtrasCache(Arc<Mutex<LruMap<B256, HlExtras>>>);
+
+impl HlExtrasCache {
+    pub(crate) fn new(size: u32) -> Self {
+        Self(Arc::new(Mutex::new(LruMap::new(size))))
+    }
+
+    pub(crate) fn get(&self, hash: B256) -> Option<HlExtras> {
+        self.0.lock().unwrap().get(&hash).cloned()
+    }
+
+    pub(crate) fn insert(&self, hash: B256, extras: HlExtras) {
+        self.0.lock().unwrap().insert(hash, extras);
+    }
+}
+
+/// The error of a call executing block `number` after it was replaced.
+pub(crate) fn block_replaced(number: u64) -> EthApiError {
+    let message = format!("block {number} was replaced during the call, retry");
+    HlRpcError::new(HlErrorCode::BlockReplaced, message, BlockReplacedData { block_number: number })
+        .into()
+}
+
+/// Returns the hash of the block to take the extras of for an env of block `number`, executing
+/// on the state recorded for this thread, or `None` if there is no such block (e.g. a pending
+/// block env). `canonical` reads the canonical header at `number`.
+pub(crate) fn block_hash_for_env<H: BlockHeader>(
+    number: u64,
+    canonical: impl FnOnce() -> ProviderResult<Option<SealedHeader<H>>>,
+) -> Result<Option<B256>, EthApiError> {
+    block_hash_for_state(STATE_BLOCK.get(), number, canonical)
+}
+
+fn block_hash_for_state<H: BlockHeader>(
+    state: Option<BlockNumHash>,
+    number: u64,
+    canonical: impl FnOnce() -> ProviderResult<Option<SealedHeader<H>>>,
+) -> Result<Option<B256>, EthApiError> {
+    match state {
+        // A call on the state of its own block
+        Some(state) if state.number == number => Ok(Some(state.hash)),
+        // A replay on the state of the parent of the block
+        Some(state) if state.number + 1 == number => match canonical()? {
+            Some(header) if header.parent_hash() != state.hash => Err(block_replaced(number)),
+            header => Ok(header.map(|header| header.hash())),
+        },
+        // An env whose number was overridden takes the extras of the canonical block
+        _ => Ok(canonical()?.map(|header| header.hash())),
+    }
+}
+
+/// Reads the extras of `block`, from the cache when possible. Unknown blocks are an error rather
+/// than empty extras, which would silently disable the precompile replay.
+pub(crate) fn read_hl_extras<P: BlockReader<Block = HlBlock>>(
+    provider: &P,
+    cache: &HlExtrasCache,
+    block: BlockHashOrNumber,
+) -> Result<HlExtras, EthApiError> {
+    let hash = match block {
+        BlockHashOrNumber::Hash(hash) => hash,
+        BlockHashOrNumber::Number(number) => {
+            provider.block_hash(number)?.ok_or(EthApiError::HeaderNotFound(number.into()))?
+        }
+    };
+    if let Some(extras) = cache.get(hash) {
+        return Ok(extras);
+    }
+    let block = provider.block_by_hash(hash)?.ok_or(EthApiError::HeaderNotFound(hash.into()))?;
+    let extras = HlExtras {
+        read_precompile_calls: block.body.read_precompile_calls,
+        highest_precompile_address: block.body.highest_precompile_address,
+    };
+    cache.insert(hash, extras.clone());
+    Ok(extras)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HlPrimitives;
+    use alloy_consensus::Header;
+    use alloy_eips::BlockId;
+    use alloy_primitives::Address;
+    use jsonrpsee_types::ErrorObject;
+    use reth_provider::test_utils::MockEthProvider;
+
+    fn header(number: u64, parent_hash: B256) -> SealedHeader<Header> {
+        SealedHeader::seal_slow(Header { number, parent_hash, ..Default::default() })
+    }
+
+    #[test]
+    fn calls_and_replays_take_the_extras_of_their_block() {
+        let parent = header(4, B256::ZERO);
+        let block = header(5, parent.hash());
+        let canonical = || Ok(Some(block.clone()));
+
+        // `eth_call` on the state of block 5, and the replay of block 5 on the state of its parent
+        let resolved = block_hash_for_state(Some(block.num_hash()), 5, canonical).unwrap();
+        assert_eq!(resolved, Some(block.hash()));
+        let resolved = block_hash_for_state(Some(parent.num_hash()), 5, canonical).unwrap();
+        assert_eq!(resolved, Some(block.hash()));
+
+        // e.g. `eth_call` with a block number override
+        let overridden = header(7, B256::repeat_byte(7));
+        let resolved =
+            block_hash_for_state(Some(block.num_hash()), 7, || Ok(Some(overridden.clone())))
+                .unwrap();
+        assert_eq!(resolved, Some(overridden.hash()));
+
+        // Pending block env
+        let resolved = block_hash_for_state::<Header>(None, 6, || Ok(None)).unwrap();
+        assert_eq!(resolved, None);
+    }
+
+    #[test]
+    fn replay_on_a_replaced_parent_is_detected() {
+        let (original, replacement) = (header(4, B256::ZERO), header(4, B256::repeat_byte(1)));
+        let block = header(5, replacement.hash());
+
+        // State acquired for the original parent, which is unwound and replaced before the
+        // extras of its child are looked up
+        let err = block_hash_for_state(Some(original.num_hash()), 5, || Ok(Some(block.clone())))
+            .unwrap_err();
+        let err: ErrorObject<'static> = err.into();
+        assert_eq!(err.code(), HlErrorCode::BlockReplaced.code());
+        let data: BlockReplacedData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data.block_number, 5);
+
+        // Replays against the replacement are served with the extras of its child
+        let resolved =
+            block_hash_for_state(Some(replacement.num_hash()), 5, || Ok(Some(block.clone())))
+                .unwrap();
+        assert_eq!(resolved, Some(block.hash()));
+    }
+
+    #[test]
+    fn reads_extras_by_number_and_hash() {
+        let provider = MockEthProvider::<HlPrimitives>::default();
+        let mut block = HlBlock::default();
+        block.header.inner.number = 7;
+        block.body.highest_precompile_address = Some(Address::repeat_byte(0x08));
+        let hash = block.header.hash_slow();
+        provider.add_block(hash, block);
+        let cache = HlExtrasCache::new(EXTRAS_CACHE_SIZE);
+
+        let by_number = read_hl_extras(&provider, &cache, 7.into()).unwrap();
+        assert_eq!(by_number.highest_precompile_address, Some(Address::repeat_byte(0x08)));
+        let by_hash = read_hl_extras(&provider, &cache, hash.into()).unwrap();
+        assert_eq!(by_hash.highest_precompile_address, Some(Address::repeat_byte(0x08)));
+
+        let unknown = B256::repeat_byte(0xff);
+        assert!(matches!(
+            read_hl_extras(&provider, &cache, unknown.into()),
+            Err(EthApiError::HeaderNotFound(BlockId::Hash(id))) if id.block_hash == unknown
+        ));
+    }
+
+    #[test]
+    fn unknown_blocks_are_not_empty_extras() {
+        let provider = MockEthProvider::<HlPrimitives>::default();
+        let mut block = HlBlock::default();
+        block.header.inner.number = 7;
+        provider.add_block(block.header.hash_slow(), block);
+        let cache = HlExtrasCache::new(EXTRAS_CACHE_SIZE);
+
+        // A known block without read precompile calls
+        let extras = read_hl_extras(&provider, &cache, 7.into()).unwrap();
+        assert!(extras.read_precompile_calls.is_none());
+        assert!(extras.highest_precompile_address.is_none());
+
+        // A block that is not synced yet
+        assert!(matches!(
+            read_hl_extras(&provider, &cache, 8.into()),
+            Err(EthApiError::HeaderNotFound(BlockId::Number(_)))
+        ));
+    }
+}
@@ -1,12 +1,14 @@
 use crate::{
     HlBlock, HlPrimitives,
     chainspec::HlChainSpec,
-    node::{evm::apply_precompiles, types::HlExtras},
+    node::{evm::apply_precompiles, storage::prune::ArchiveWindow, types::HlExtras},
 };
-use alloy_eips::BlockId;
+use alloy_eips::{BlockHashOrNumber, BlockId, BlockNumHash};
 use alloy_evm::Evm;
 use alloy_network::Ethereum;
-use alloy_primitives::U256;
+use alloy_primitives::{Address, U256};
+use alloy_rpc_types::{EIP1186AccountProofResponse, serde_helpers::JsonStorageKey};
+use archive::ArchiveData;
 use reth::{
     api::{FullNodeTypes, HeaderTy, NodeTypes, PrimitivesTy},
     builder::{
@@ -25,10 +27,13 @@ use reth::{
         pool::{BlockingTaskGuard, BlockingTaskPool},
     },
 };
+use reth_errors::RethError;
 use reth_evm::{ConfigureEvm, Database, EvmEnvFor, HaltReasonFor, InspectorFor, TxEnvFor};
 use reth_primitives::NodePrimitives;
 use reth_provider::{
-    BlockReaderIdExt, ChainSpecProvider, ProviderError, ProviderHeader, ProviderTx,
+    BlockHashReader, BlockIdReader, BlockNumReader, ChainSpecProvider, HeaderProvider,
+    ProviderError, ProviderHeader, ProviderTx, StateProofProvider, StateProviderBox,
+    StateProviderFactory,
 };
 use reth_rpc::RpcTypes;
 use reth_rpc_eth_api::{
@@ -39,14 +44,24 @@ use reth_rpc_eth_api::{
         SpawnBlocking, Trace, pending_block::BuildPendingEnv, spec::SignersForApi,
     },
 };
-use revm::context::result::ResultAndState;
-use std::{fmt, marker::PhantomData, sync::Arc};
+use revm::context::{BlockEnv, result::ResultAndState};
+use std::{fmt, future::Future, marker::PhantomData, sync::Arc};
+use trace_timeout::{TraceDeadline, check_trace_deadline};
 
+mod archive;
 mod block;
 mod call;
+pub mod call_many;
 pub mod engine_api;
+pub mod engine_status;
+pub mod errors;
 mod estimate;
+mod extras;
+pub mod node_info;
 pub mod precompile;
+pub mod proof;
+pub mod spot_meta;
+pub mod trace_timeout;
 mod transaction;
 
 pub trait HlRpcNodeCore: RpcNodeCore<Primitives: NodePrimitives<Block = HlBlock>> {}
@@ -55,6 +70,12 @@ pub trait HlRpcNodeCore: RpcNodeCore<Primitives: NodePrimitives<Block = HlBlock>
 pub(crate) struct HlEthApiInner<N: HlRpcNodeCore, Rpc: RpcConvert> {
     /// Gateway to node's core components.
     pub(crate) eth_api: EthApiInner<N, Rpc>,
+    /// Number of blocks behind the tip for which `eth_getProof` is served, `None` if unlimited.
+    pub(crate) eth_get_proof_window: Option<u64>,
+    /// Latest blocks whose data is kept, `None` for a full archive.
+    pub(crate) archive_window: Option<ArchiveWindow>,
+    /// Extras of recently used blocks by hash.
+    pub(crate) extras_cache: extras::HlExtrasCache,
 }
 
 type HlRpcConvert<N, NetworkT> =
@@ -176,6 +197,19 @@ where
     fn tracing_task_guard(&self) -> &BlockingTaskGuard {
         self.inner.eth_api.blocking_task_guard()
     }
+
+    /// Same as the default implementation, but the replay runs under the deadline of the trace
+    /// request, see [`trace_timeout`].
+    fn spawn_tracing<F, R>(&self, f: F) -> impl Future<Output = Result<R, Self::Error>> + Send
+    where
+        F: FnOnce(Self) -> Result<R, Self::Error> + Send + 'static,
+        R: Send + 'static,
+    {
+        let this = self.clone();
+        let deadline = TraceDeadline::current();
+        let fut = self.tracing_task_pool().spawn(move || TraceDeadline::enter(deadline, || f(this)));
+        async move { fut.await.map_err(|_| EthApiError::InternalBlockingTaskError)? }
+    }
 }
 
 impl<N, Rpc> LoadFee for HlEthApi<N, Rpc>
@@ -201,6 +235,30 @@ where
     Rpc: RpcConvert<Primitives = N::Primitives, Error = EthApiError>,
     Self: LoadPendingBlock,
 {
+    /// Reads state from the provider like the default implementation, rejecting blocks whose
+    /// state history was pruned by `--archive-window.state` first. The state of a stored block is
+    /// read by hash, which is recorded for the extras of the call, see [`extras`].
+    async fn state_at_block_id(&self, at: BlockId) -> Result<StateProviderBox, Self::Error> {
+        let block = match at {
+            BlockId::Hash(hash) => self
+                .provider()
+                .block_number(hash.block_hash)?
+                .map(|number| BlockNumHash::new(number, hash.block_hash)),
+            BlockId::Number(number) if number.is_pending() => None,
+            BlockId::Number(_) => match self.provider().block_number_for_id(at)? {
+                Some(number) => {
+                    self.provider().block_hash(number)?.map(|hash| BlockNumHash::new(number, hash))
+                }
+                None => None,
+            },
+        };
+        extras::enter_state_block(block);
+        let Some(block) = block else {
+            return Ok(self.provider().state_by_block_id(at)?);
+        };
+        self.check_archive_window(ArchiveData::State, block.number)?;
+        Ok(self.provider().state_by_block_hash(block.hash)?)
+    }
 }
 
 impl<N, Rpc> EthState for HlEthApi<N, Rpc>
@@ -209,9 +267,57 @@ where
     Rpc: RpcConvert<Primitives = N::Primitives, Error = EthApiError>,
     Self: LoadPendingBlock,
 {
+    /// The HL proof window, or the configured `--rpc.eth-proof-window` once it is lifted with
+    /// `--experimental-eth-get-proof`.
     #[inline]
     fn max_proof_window(&self) -> u64 {
-        self.inner.eth_api.eth_proof_window()
+        self.inner.eth_get_proof_window.unwrap_or_else(|| self.inner.eth_api.eth_proof_window())
+    }
+
+    /// Same as the default implementation, but rejects blocks outside of the HL proof window
+    /// with a descriptive error first. See [`proof`] for why proofs are restricted.
+    fn get_proof(
+        &self,
+        address: Address,
+        keys: Vec<JsonStorageKey>,
+        block_id: Option<BlockId>,
+    ) -> Result<
+        impl Future<Output = Result<EIP1186AccountProofResponse, Self::Error>> + Send,
+        Self::Error,
+    >
+    where
+        Self: EthApiSpec,
+    {
+        Ok(async move {
+            let _permit = self
+                .acquire_owned()
+                .await
+                .map_err(RethError::other)
+                .map_err(EthApiError::Internal)?;
+
+            let chain_info = self.provider().chain_info()?;
+            let block_id = block_id.unwrap_or_default();
+            let block_number = self
+                .provider()
+                .block_number_for_id(block_id)?
+                .ok_or(EthApiError::HeaderNotFound(block_id))?;
+            proof::check_proof_window(
+                self.inner.eth_get_proof_window,
+                chain_info.best_number,
+                block_number,
+            )?;
+            if chain_info.best_number.saturating_sub(block_number) > self.max_proof_window() {
+                return Err(EthApiError::ExceedsMaxProofWindow);
+            }
+
+            self.spawn_blocking_io_fut(move |this| async move {
+                let state = this.state_at_block_id(block_id).await?;
+                let storage_keys = keys.iter().map(|key| key.as_b256()).collect::<Vec<_>>();
+                let proof = state.proof(Default::default(), address, &storage_keys)?;
+                Ok(proof.into_eip1186_response(keys))
+            })
+            .await
+        })
     }
 }
 
@@ -229,6 +335,7 @@ where
     EthApiError: FromEvmError<N::Evm>,
     Rpc: RpcConvert<Primitives = N::Primitives, Error = EthApiError>,
 {
+    /// Entry point for `trace_*` and `debug_trace*`, so read precompiles must be applied here.
     fn inspect<DB, I>(
         &self,
         db: DB,
@@ -240,8 +347,8 @@ where
         DB: Database<Error = ProviderError>,
         I: InspectorFor<Self::Evm, DB>,
     {
-        let block_number = evm_env.block_env().number;
-        let hl_extras = self.get_hl_extras(block_number.to::<u64>().into())?;
+        check_trace_deadline()?;
+        let hl_extras = self.hl_extras_for_env(evm_env.block_env())?;
 
         let mut evm = self.evm_config().evm_with_env_and_inspector(db, evm_env, inspector);
         apply_precompiles(&mut evm, &hl_extras);
@@ -254,15 +361,52 @@ where
     N: HlRpcNodeCore,
     Rpc: RpcConvert<Primitives = N::Primitives, Error = EthApiError>,
 {
-    fn get_hl_extras(&self, block: BlockId) -> Result<HlExtras, ProviderError> {
-        Ok(self
-            .provider()
-            .block_by_id(block)?
-            .map(|block| HlExtras {
-                read_precompile_calls: block.body.read_precompile_calls.clone(),
-                highest_precompile_address: block.body.highest_precompile_address,
-            })
-            .unwrap_or_default())
+    /// Returns the extras of `block`, failing if there is no such block or its extras were
+    /// pruned.
+    pub(crate) fn get_hl_extras(&self, block: BlockHashOrNumber) -> Result<HlExtras, EthApiError> {
+        if self.inner.archive_window.is_some() {
+            let number = match block {
+                BlockHashOrNumber::Number(number) => Some(number),
+                BlockHashOrNumber::Hash(hash) => self.provider().block_number(hash)?,
+            };
+            if let Some(number) = number {
+                self.check_archive_window(ArchiveData::Blocks, number)?;
+            }
+        }
+        extras::read_hl_extras(self.provider(), &self.inner.extras_cache, block)
+    }
+
+    /// Rejects requests for the `data` of block `number` if `--archive-window` pruned it.
+    pub(crate) fn check_archive_window(
+        &self,
+        data: ArchiveData,
+        number: u64,
+    ) -> Result<(), EthApiError> {
+        if self.inner.archive_window.is_none() {
+            return Ok(());
+        }
+        let best_number = self.provider().best_block_number()?;
+        archive::check_archive_window(self.inner.archive_window, data, best_number, number)
+    }
+
+    /// Returns the extras of the block `block_env` was built for, by the hash of the block whose
+    /// state the call acquired, failing if the block was replaced since.
+    ///
+    /// Blocks past the tip (pending and simulated blocks) have no recorded calls and get empty
+    /// extras; a missing block at or below the tip is an error.
+    fn hl_extras_for_env(&self, block_env: &BlockEnv) -> Result<HlExtras, EthApiError> {
+        let number = block_env.number.saturating_to();
+        let canonical = || self.provider().sealed_header(number);
+        match extras::block_hash_for_env(number, canonical)? {
+            Some(hash) => self.get_hl_extras(hash.into()).map_err(|err| match err {
+                EthApiError::HeaderNotFound(_) => extras::block_replaced(number),
+                err => err,
+            }),
+            None if number <= self.provider().best_block_number()? => {
+                Err(EthApiError::HeaderNotFound(number.into()))
+            }
+            None => Ok(HlExtras::default()),
+        }
     }
 }
 
@@ -282,13 +426,21 @@ where
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct HlEthApiBuilder<NetworkT = Ethereum> {
+    /// Number of blocks behind the tip for which `eth_getProof` is served, `None` if unlimited.
+    pub(crate) eth_get_proof_window: Option<u64>,
+    /// Latest blocks whose data is kept, `None` for a full archive.
+    pub(crate) archive_window: Option<ArchiveWindow>,
     /// Marker for network types.
     pub(crate) _nt: PhantomData<NetworkT>,
 }
 
 impl<NetworkT> Default for HlEthApiBuilder<NetworkT> {
     fn default() -> Self {
-        Self { _nt: PhantomData }
+        Self {
+            eth_get_proof_window: Some(proof::DEFAULT_ETH_GET_PROOF_WINDOW),
+            archive_window: None,
+            _nt: PhantomData,
+        }
     }
 }
 
@@ -314,6 +466,15 @@ where
             RpcConverter::new(EthReceiptConverter::<HlChainSpec>::new(provider.chain_spec()));
         let eth_api = ctx.eth_api_builder().with_rpc_converter(rpc_converter).build_inner();
 
-        Ok(HlEthApi { inner: Arc::new(HlEthApiInner { eth_api }) })
+        let extras_cache = extras::HlExtrasCache::new(extras::EXTRAS_CACHE_SIZE);
+
+        Ok(HlEthApi {
+            inner: Arc::new(HlEthApiInner {
+                eth_api,
+                eth_get_proof_window: self.eth_get_proof_window,
+                archive_window: self.archive_window,
+                extras_cache,
+            }),
+        })
     }
 }
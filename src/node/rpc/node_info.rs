@@ -0,0 +1,93 @@
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use reth_network_api::{NetworkInfo, PeersInfo};
+use reth_network_peers::{NodeRecord, PeerId};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tracing::trace;
+
+/// Response of `hl_nodeInfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfoResponse {
+    /// Enode URL of the local node, e.g. for `--destination-peer`.
+    pub enode: String,
+    pub id: PeerId,
+    /// Address the P2P listener is bound to.
+    pub listen_addr: SocketAddr,
+    /// UDP port used for discovery.
+    pub discovery_port: u16,
+    /// Whether `--allow-network-overrides` is set; otherwise the node only listens on localhost.
+    pub network_overrides: bool,
+}
+
+impl NodeInfoResponse {
+    pub fn new(record: NodeRecord, listen_addr: SocketAddr, network_overrides: bool) -> Self {
+        Self {
+            enode: record.to_string(),
+            id: record.id,
+            listen_addr,
+            discovery_port: record.udp_port,
+            network_overrides,
+        }
+    }
+}
+
+/// RPC exposing the local network identity, so peers can be wired up without reading logs.
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlNodeInfoApi {
+    /// Returns the local enode URL, listening addresses and network override setting.
+    #[method(name = "nodeInfo")]
+    async fn node_info(&self) -> RpcResult<NodeInfoResponse>;
+}
+
+pub struct HlNodeInfoExt<Net> {
+    network: Net,
+    network_overrides: bool,
+}
+
+impl<Net> HlNodeInfoExt<Net> {
+    /// Creates a new instance of the [`HlNodeInfoExt`].
+    pub fn new(network: Net, network_overrides: bool) -> Self {
+        Self { network, network_overrides }
+    }
+}
+
+#[async_trait]
+impl<Net> HlNodeInfoApiServer for HlNodeInfoExt<Net>
+where
+    Net: NetworkInfo + PeersInfo + 'static,
+{
+    async fn node_info(&self) -> RpcResult<NodeInfoResponse> {
+        trace!(target: "rpc::hl", "Serving hl_nodeInfo");
+        Ok(NodeInfoResponse::new(
+            self.network.local_node_record(),
+            self.network.local_addr(),
+            self.network_overrides,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn enode_parses_as_node_record() {
+        let record = NodeRecord::new_with_ports(
+            Ipv4Addr::LOCALHOST.into(),
+            30303,
+            Some(30304),
+            PeerId::repeat_byte(0xab),
+        );
+        let response = NodeInfoResponse::new(record, "127.0.0.1:30303".parse().unwrap(), false);
+
+        let json = serde_json::to_value(&response).unwrap();
+        let enode: NodeRecord = json["enode"].as_str().unwrap().parse().unwrap();
+        assert_eq!(enode, record);
+        assert_eq!(json["discoveryPort"], 30304);
+        assert_eq!(json["networkOverrides"], false);
+    }
+}
@@ -1,15 +1,45 @@
 use alloy_eips::BlockId;
+use alloy_json_rpc::RpcObject;
+use alloy_network::ReceiptResponse;
+use alloy_primitives::{Address, B256, U64};
 use jsonrpsee::proc_macros::rpc;
 use jsonrpsee_core::{RpcResult, async_trait};
+use reth_evm::{SpecFor, TxEnvFor};
+use reth_provider::BlockIdReader;
 use reth_rpc_convert::RpcConvert;
+use reth_rpc_eth_api::{
+    FromEvmError, RpcNodeCore, RpcReceipt,
+    helpers::{EthTransactions, Trace},
+};
 use reth_rpc_eth_types::EthApiError;
+use revm::{
+    Inspector,
+    context_interface::ContextTr,
+    interpreter::{CallInputs, CallOutcome},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::trace;
 
-use crate::node::{
-    rpc::{HlEthApi, HlRpcNodeCore},
-    types::HlExtras,
+use crate::{
+    addons::{hl_node_compliance::adjust_transaction_receipt, utils::EthWrapper},
+    node::{
+        rpc::{HlEthApi, HlRpcNodeCore},
+        types::{HlExtras, ReadPrecompileInput, ReadPrecompileResult},
+    },
 };
 
+/// Read precompile addresses of a block, see [`HlExtras::precompile_address_range`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrecompileAddressRange {
+    pub lowest: Address,
+    pub highest: Address,
+    /// Whether the block records its highest precompile address, otherwise the chain default
+    /// applies.
+    pub recorded: bool,
+}
+
 /// A custom RPC trait for fetching block precompile data.
 #[rpc(server, namespace = "eth")]
 #[async_trait]
@@ -19,14 +49,116 @@ pub trait HlBlockPrecompileApi {
     async fn block_precompile_data(&self, block: BlockId) -> RpcResult<HlExtras>;
 }
 
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlPrecompileAddressRangeApi {
+    /// Returns the read precompile address range of a block.
+    #[method(name = "getPrecompileAddressRange")]
+    async fn precompile_address_range(&self, block: BlockId) -> RpcResult<PrecompileAddressRange>;
+}
+
+/// A transaction receipt with the gas used by the transaction's read precompile calls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HlTransactionReceipt<R> {
+    #[serde(flatten)]
+    pub receipt: R,
+    pub precompile_gas_used: U64,
+}
+
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlPrecompileReceiptApi<R: RpcObject> {
+    /// Returns the receipt of a transaction together with the gas its read precompile calls
+    /// used, found by replaying the transaction.
+    #[method(name = "getTransactionReceipt")]
+    async fn transaction_receipt(&self, hash: B256) -> RpcResult<Option<HlTransactionReceipt<R>>>;
+}
+
+/// Sums the recorded gas of the read precompile calls made by the inspected transaction.
+///
+/// Calls are matched against the block's recorded calls the same way they are replayed, by
+/// precompile address, input and gas limit. Only successful calls use gas from the record.
+#[derive(Debug, Default)]
+pub struct PrecompileGasInspector {
+    calls: HashMap<Address, HashMap<ReadPrecompileInput, ReadPrecompileResult>>,
+    gas_used: u64,
+}
+
+impl PrecompileGasInspector {
+    /// Creates an inspector for a transaction of the block with the given extras.
+    pub fn new(extras: &HlExtras) -> Self {
+        let calls = extras
+            .read_precompile_calls
+            .iter()
+            .flat_map(|calls| &calls.0)
+            .map(|(address, calls)| (*address, calls.iter().cloned().collect()))
+            .collect();
+        Self { calls, gas_used: 0 }
+    }
+
+    /// Gas used by the read precompile calls seen so far.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    fn record(&mut self, address: Address, input: ReadPrecompileInput) {
+        if let Some(ReadPrecompileResult::Ok { gas_used, .. }) =
+            self.calls.get(&address).and_then(|calls| calls.get(&input))
+        {
+            self.gas_used += gas_used;
+        }
+    }
+}
+
+impl<CTX: ContextTr> Inspector<CTX> for PrecompileGasInspector {
+    fn call(&mut self, context: &mut CTX, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        if self.calls.contains_key(&inputs.bytecode_address) {
+            let input = ReadPrecompileInput {
+                input: inputs.input.bytes(context),
+                gas_limit: inputs.gas_limit,
+            };
+            self.record(inputs.bytecode_address, input);
+        }
+        None
+    }
+}
+
 pub struct HlBlockPrecompileExt<N: HlRpcNodeCore, Rpc: RpcConvert> {
     eth_api: HlEthApi<N, Rpc>,
+    expose_system_txs: bool,
 }
 
 impl<N: HlRpcNodeCore, Rpc: RpcConvert> HlBlockPrecompileExt<N, Rpc> {
     /// Creates a new instance of the [`HlBlockPrecompileExt`].
     pub fn new(eth_api: HlEthApi<N, Rpc>) -> Self {
-        Self { eth_api }
+        Self { eth_api, expose_system_txs: true }
+    }
+
+    /// Whether receipts count system transactions, as `eth_getTransactionReceipt` does per
+    /// `--expose-system-txs`. Hidden system transactions have no receipt.
+    pub fn with_expose_system_txs(mut self, expose_system_txs: bool) -> Self {
+        self.expose_system_txs = expose_system_txs;
+        self
+    }
+}
+
+impl<N, Rpc> HlBlockPrecompileExt<N, Rpc>
+where
+    N: HlRpcNodeCore,
+    Rpc: RpcConvert<Primitives = N::Primitives, Error = EthApiError>,
+{
+    fn hl_extras(&self, block: BlockId) -> Result<HlExtras, EthApiError> {
+        let block = match block {
+            BlockId::Hash(hash) => hash.block_hash.into(),
+            BlockId::Number(tag) => self
+                .eth_api
+                .provider()
+                .convert_block_number(tag)?
+                .ok_or(EthApiError::HeaderNotFound(block))?
+                .into(),
+        };
+        self.eth_api.get_hl_extras(block)
     }
 }
 
@@ -38,7 +170,106 @@ where
 {
     async fn block_precompile_data(&self, block: BlockId) -> RpcResult<HlExtras> {
         trace!(target: "rpc::eth", ?block, "Serving eth_blockPrecompileData");
-        let hl_extras = self.eth_api.get_hl_extras(block).map_err(EthApiError::from)?;
-        Ok(hl_extras)
+        Ok(self.hl_extras(block)?)
+    }
+}
+
+#[async_trait]
+impl<N, Rpc> HlPrecompileAddressRangeApiServer for HlBlockPrecompileExt<N, Rpc>
+where
+    N: HlRpcNodeCore,
+    Rpc: RpcConvert<Primitives = N::Primitives, Error = EthApiError>,
+{
+    async fn precompile_address_range(&self, block: BlockId) -> RpcResult<PrecompileAddressRange> {
+        trace!(target: "rpc::hl", ?block, "Serving hl_getPrecompileAddressRange");
+        let extras = self.hl_extras(block)?;
+        let range = extras.precompile_address_range();
+        Ok(PrecompileAddressRange {
+            lowest: *range.start(),
+            highest: *range.end(),
+            recorded: extras.highest_precompile_address.is_some(),
+        })
+    }
+}
+
+#[async_trait]
+impl<N, Rpc> HlPrecompileReceiptApiServer<RpcReceipt<Rpc::Network>> for HlBlockPrecompileExt<N, Rpc>
+where
+    N: HlRpcNodeCore,
+    HlEthApi<N, Rpc>: EthWrapper,
+    EthApiError: FromEvmError<N::Evm>,
+    Rpc: RpcConvert<
+            Primitives = N::Primitives,
+            Error = EthApiError,
+            TxEnv = TxEnvFor<N::Evm>,
+            Spec = SpecFor<N::Evm>,
+        >,
+{
+    async fn transaction_receipt(
+        &self,
+        hash: B256,
+    ) -> RpcResult<Option<HlTransactionReceipt<RpcReceipt<Rpc::Network>>>> {
+        trace!(target: "rpc::hl", ?hash, "Serving hl_getTransactionReceipt");
+        let receipt = if self.expose_system_txs {
+            EthTransactions::transaction_receipt(&self.eth_api, hash).await?
+        } else {
+            adjust_transaction_receipt(hash, &self.eth_api).await?
+        };
+        let Some(receipt) = receipt else {
+            return Ok(None);
+        };
+        let Some(block_hash) = receipt.block_hash() else {
+            return Ok(None);
+        };
+
+        let extras = self.eth_api.get_hl_extras(block_hash.into())?;
+        let precompile_gas_used = self
+            .eth_api
+            .spawn_trace_transaction_in_block_with_inspector(
+                hash,
+                PrecompileGasInspector::new(&extras),
+                |_, inspector, _, _| Ok(inspector.gas_used()),
+            )
+            .await?
+            .ok_or(EthApiError::TransactionNotFound)?;
+        Ok(Some(HlTransactionReceipt {
+            receipt,
+            precompile_gas_used: U64::from(precompile_gas_used),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::types::ReadPrecompileCalls;
+    use alloy_primitives::{Bytes, address};
+
+    #[test]
+    fn sums_gas_of_successful_precompile_reads() {
+        let precompile = address!("0x0000000000000000000000000000000000000801");
+        let input =
+            |byte| ReadPrecompileInput { input: Bytes::from(vec![byte; 32]), gas_limit: 50_000 };
+        let extras = HlExtras {
+            read_precompile_calls: Some(ReadPrecompileCalls(vec![(
+                precompile,
+                vec![
+                    (input(1), ReadPrecompileResult::Ok { gas_used: 2_100, bytes: Bytes::new() }),
+                    (input(2), ReadPrecompileResult::OutOfGas),
+                ],
+            )])),
+            highest_precompile_address: None,
+        };
+
+        let mut inspector = PrecompileGasInspector::new(&extras);
+        // The transaction reads the precompile twice and fails a third read
+        inspector.record(precompile, input(1));
+        inspector.record(precompile, input(1));
+        inspector.record(precompile, input(2));
+        // Calls to other addresses or with unrecorded inputs use no recorded gas
+        inspector.record(Address::ZERO, input(1));
+        inspector.record(precompile, ReadPrecompileInput { gas_limit: 1, ..input(1) });
+        assert_eq!(inspector.gas_used(), 4_200);
+        assert_eq!(PrecompileGasInspector::new(&HlExtras::default()).gas_used(), 0);
     }
 }
@@ -0,0 +1,58 @@
+//! Gating for `eth_getProof`.
+//!
+//! The archival state is maintained by block order rather than trie updates, so proofs for
+//! historical blocks may be wrong. Near the tip they are much less likely to be affected, so
+//! `eth_getProof` is served for the latest blocks only unless `--experimental-eth-get-proof` is
+//! set, which leaves reth's `--rpc.eth-proof-window` as the only limit.
+
+use reth_rpc_eth_types::EthApiError;
+
+/// Default number of blocks behind the tip for which `eth_getProof` is served.
+pub const DEFAULT_ETH_GET_PROOF_WINDOW: u64 = 256;
+
+/// Rejects proof requests for blocks further than `window` blocks behind `best_number`.
+///
+/// `None` means the window is disabled, which is the case with `--experimental-eth-get-proof`.
+pub(crate) fn check_proof_window(
+    window: Option<u64>,
+    best_number: u64,
+    block_number: u64,
+) -> Result<(), EthApiError> {
+    let Some(window) = window else {
+        return Ok(());
+    };
+
+    let distance = best_number.saturating_sub(block_number);
+    if distance > window {
+        return Err(EthApiError::InvalidParams(format!(
+            "eth_getProof is only available for the latest {window} blocks, block {block_number} \
+             is {distance} blocks behind the tip (use --experimental-eth-get-proof to lift this \
+             limit)"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_window_is_allowed() {
+        assert!(check_proof_window(Some(256), 1000, 1000).is_ok());
+        assert!(check_proof_window(Some(256), 1000, 744).is_ok());
+        // Blocks ahead of the tip are rejected later as unknown blocks
+        assert!(check_proof_window(Some(256), 1000, 1001).is_ok());
+    }
+
+    #[test]
+    fn out_of_window_is_rejected() {
+        let err = check_proof_window(Some(256), 1000, 743).unwrap_err();
+        assert!(matches!(err, EthApiError::InvalidParams(msg) if msg.contains("latest 256 blocks")));
+    }
+
+    #[test]
+    fn experimental_lifts_window() {
+        assert!(check_proof_window(None, 1000, 0).is_ok());
+    }
+}
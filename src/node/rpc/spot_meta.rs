@@ -0,0 +1,40 @@
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee_core::{RpcResult, async_trait};
+use reth::rpc::result::internal_rpc_err;
+use tracing::trace;
+
+use crate::node::{spot_meta::init::reload_spot_metadata, types::SpotMetaContext};
+
+/// Admin RPC for managing the spot metadata used to derive system transaction senders.
+#[rpc(server, namespace = "hl")]
+#[async_trait]
+pub trait HlSpotMetaApi {
+    /// Reloads spot metadata from the database and the API, returning the new entry count.
+    #[method(name = "reloadSpotMetadata")]
+    async fn reload_spot_metadata(&self) -> RpcResult<usize>;
+}
+
+pub struct HlSpotMetaExt {
+    spot_meta: SpotMetaContext,
+    chain_id: u64,
+}
+
+impl HlSpotMetaExt {
+    /// Creates a new instance of the [`HlSpotMetaExt`].
+    pub fn new(spot_meta: SpotMetaContext, chain_id: u64) -> Self {
+        Self { spot_meta, chain_id }
+    }
+}
+
+#[async_trait]
+impl HlSpotMetaApiServer for HlSpotMetaExt {
+    async fn reload_spot_metadata(&self) -> RpcResult<usize> {
+        trace!(target: "rpc::hl", "Serving hl_reloadSpotMetadata");
+        let (spot_meta, chain_id) = (self.spot_meta.clone(), self.chain_id);
+        // Fetching from the API is blocking
+        tokio::task::spawn_blocking(move || reload_spot_metadata(&spot_meta, chain_id))
+            .await
+            .map_err(|e| internal_rpc_err(e.to_string()))?
+            .map_err(|e| internal_rpc_err(e.to_string()))
+    }
+}
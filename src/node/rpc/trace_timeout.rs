@@ -0,0 +1,283 @@
+//! Timeouts of the `debug_trace*` methods replaying blocks.
+//!
+//! Tracing a block with thousands of transactions and large read precompile sets can hold one of
+//! the tracing permits of the [`BlockingTaskGuard`] for minutes, queueing every other trace
+//! behind it. A trace can be given a timeout with the `timeout` tracer option, e.g. `"5s"` as
+//! geth accepts it, or by default with `--rpc.trace-timeout`. Past its timeout, the request fails
+//! with [`HlErrorCode::TraceTimeout`] and releases its permit, and the replay running on the
+//! tracing pool stops before its next transaction.
+//!
+//! The deadline of a request is kept in a task-local while it is served, and carried over to the
+//! tracing pool by [`HlEthApi`]'s `spawn_tracing`, where [`check_trace_deadline`] reads it.
+//!
+//! [`BlockingTaskGuard`]: reth::tasks::pool::BlockingTaskGuard
+//! [`HlEthApi`]: super::HlEthApi
+
+use super::errors::{HlErrorCode, HlRpcError, TraceTimeoutData};
+use crate::addons::{
+    rpc_rate_limit::{RawParams, call_error},
+    sync_limits::forward_extensions,
+};
+use jsonrpsee::{
+    RpcModule,
+    core::server::{MethodCallback, Methods},
+};
+use jsonrpsee_types::{ErrorObject, error::INVALID_PARAMS_CODE};
+use serde_json::{Value, value::RawValue};
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+/// Methods replaying a block that can be given a timeout. Their tracer options are their second
+/// parameter.
+pub const TIMED_TRACE_METHODS: [&str; 4] = [
+    "debug_traceBlock",
+    "debug_traceBlockByHash",
+    "debug_traceBlockByNumber",
+    "debug_traceTransaction",
+];
+
+tokio::task_local! {
+    /// Deadline of the trace request being served.
+    static REQUEST_DEADLINE: TraceDeadline;
+}
+
+thread_local! {
+    /// Deadline of the trace replayed by this thread of the tracing pool.
+    static REPLAY_DEADLINE: Cell<Option<TraceDeadline>> = const { Cell::new(None) };
+}
+
+/// Parses a timeout in the format of Go durations, e.g. `100ms`, `5s` or `1m30s`.
+pub fn parse_timeout(s: &str) -> eyre::Result<Duration> {
+    let invalid = || eyre::eyre!("invalid timeout {s:?}, expected a duration such as 100ms or 5s");
+    let mut rest = s.trim();
+    let mut timeout = Duration::ZERO;
+    while !rest.is_empty() {
+        let unit_start =
+            rest.find(|c: char| !c.is_ascii_digit() && c != '.').ok_or_else(invalid)?;
+        let (value, tail) = rest.split_at(unit_start);
+        let unit_end = tail.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+        let seconds = match unit {
+            "ns" => 1e-9,
+            "us" | "µs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return Err(invalid()),
+        };
+        let value: f64 = value.parse().map_err(|_| invalid())?;
+        timeout += Duration::try_from_secs_f64(value * seconds).map_err(|_| invalid())?;
+        rest = tail;
+    }
+    Some(timeout).filter(|timeout| !timeout.is_zero()).ok_or_else(invalid)
+}
+
+/// When a trace request runs out of time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TraceDeadline {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl TraceDeadline {
+    fn new(timeout: Duration) -> Self {
+        Self { at: Instant::now() + timeout, timeout }
+    }
+
+    /// The deadline of the trace request being served by the current task, if any.
+    pub(crate) fn current() -> Option<Self> {
+        REQUEST_DEADLINE.try_with(|deadline| *deadline).ok()
+    }
+
+    /// Runs `f`, a replay on the tracing pool, under `deadline`.
+    pub(crate) fn enter<R>(deadline: Option<Self>, f: impl FnOnce() -> R) -> R {
+        struct Reset(Option<TraceDeadline>);
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                REPLAY_DEADLINE.set(self.0);
+            }
+        }
+
+        let _reset = Reset(REPLAY_DEADLINE.replace(deadline));
+        f()
+    }
+
+    fn error(&self) -> HlRpcError {
+        let data = TraceTimeoutData { timeout_ms: self.timeout.as_millis() as u64 };
+        let message = format!("trace exceeded its timeout of {:?}", self.timeout);
+        HlRpcError::new(HlErrorCode::TraceTimeout, message, data)
+    }
+}
+
+/// Fails once the deadline of the replay running on this thread has passed. Called between the
+/// transactions of a replay.
+pub(crate) fn check_trace_deadline() -> Result<(), HlRpcError> {
+    match REPLAY_DEADLINE.get() {
+        Some(deadline) if Instant::now() >= deadline.at => Err(deadline.error()),
+        _ => Ok(()),
+    }
+}
+
+/// The `timeout` tracer option of a request to one of the [`TIMED_TRACE_METHODS`].
+fn requested_timeout(params: Option<&str>) -> Result<Option<Duration>, ErrorObject<'static>> {
+    let Some(params) = params.and_then(|params| serde_json::from_str::<Vec<Value>>(params).ok())
+    else {
+        // Left to the method to reject
+        return Ok(None);
+    };
+    let Some(timeout) = params.get(1).and_then(|opts| opts.get("timeout")?.as_str()) else {
+        return Ok(None);
+    };
+    parse_timeout(timeout)
+        .map(Some)
+        .map_err(|err| ErrorObject::owned(INVALID_PARAMS_CODE, err.to_string(), None::<()>))
+}
+
+/// Enforces the timeouts of the [`TIMED_TRACE_METHODS`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceTimeouts {
+    /// Timeout of the traces without a `timeout` option, `None` if unlimited.
+    default: Option<Duration>,
+}
+
+impl TraceTimeouts {
+    pub fn new(default: Option<Duration>) -> Self {
+        Self { default }
+    }
+
+    /// Wraps the [`TIMED_TRACE_METHODS`] of `methods` in a module enforcing their timeouts, to
+    /// replace them.
+    pub fn wrap(&self, methods: Methods) -> RpcModule<Methods> {
+        let names: Vec<_> = methods
+            .method_names()
+            .filter(|name| TIMED_TRACE_METHODS.contains(name))
+            .filter(|name| {
+                matches!(
+                    methods.method(name),
+                    Some(MethodCallback::Sync(_) | MethodCallback::Async(_))
+                )
+            })
+            .collect();
+
+        let default = self.default;
+        let mut module = RpcModule::new(methods);
+        for name in names {
+            module
+                .register_async_method(name, move |params, methods, ext| async move {
+                    let params_str = params.as_str();
+                    let timeout = requested_timeout(params_str)?.or(default);
+                    let params = RawParams(params_str.map(str::to_owned));
+                    let call = methods.call::<_, Box<RawValue>>(name, params);
+                    let call = forward_extensions(&ext, call);
+                    let Some(timeout) = timeout else {
+                        return call.await.map_err(call_error);
+                    };
+
+                    let deadline = TraceDeadline::new(timeout);
+                    REQUEST_DEADLINE
+                        .scope(deadline, tokio::time::timeout(timeout, call))
+                        .await
+                        .map_err(|_| deadline.error())?
+                        .map_err(call_error)
+                })
+                .expect("method names of a module are unique");
+        }
+        module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::{core::server::MethodsError, rpc_params};
+    use reth::tasks::pool::BlockingTaskGuard;
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[test]
+    fn parses_go_durations() {
+        assert_eq!(parse_timeout("100ms").unwrap(), Duration::from_millis(100));
+        assert_eq!(parse_timeout("1m30s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_timeout("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_timeout("250µs").unwrap(), Duration::from_micros(250));
+        for invalid in ["", "10", "5 s", "1d", "0s", "ms"] {
+            assert!(parse_timeout(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    /// `debug_traceBlockByNumber` of a block with a million transactions taking 100µs each, as
+    /// served by reth: holding the only tracing permit while the block is replayed on a blocking
+    /// thread, which checks the deadline between transactions.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn timed_out_trace_releases_its_permit_and_stops() {
+        const TRANSACTIONS: usize = 1_000_000;
+        let guard = BlockingTaskGuard::new(1);
+        let replayed = Arc::new(AtomicUsize::new(0));
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let done_tx = Arc::new(Mutex::new(Some(done_tx)));
+
+        let mut module = RpcModule::new(());
+        let (permits, counter) = (guard.clone(), replayed.clone());
+        module
+            .register_async_method("debug_traceBlockByNumber", move |_, _, _| {
+                let (permits, counter, done_tx) =
+                    (permits.clone(), counter.clone(), done_tx.clone());
+                async move {
+                    let _permit = permits.acquire_owned().await.unwrap();
+                    let deadline = TraceDeadline::current();
+                    let replay = tokio::task::spawn_blocking(move || {
+                        let result = TraceDeadline::enter(deadline, || {
+                            for _ in 0..TRANSACTIONS {
+                                check_trace_deadline()?;
+                                std::thread::sleep(Duration::from_micros(100));
+                                counter.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Ok::<_, HlRpcError>(())
+                        });
+                        let _ = done_tx.lock().unwrap().take().unwrap().send(result.is_err());
+                        result
+                    });
+                    replay.await.unwrap().map(|()| Vec::<Value>::new()).map_err(ErrorObject::from)
+                }
+            })
+            .unwrap();
+        let module = TraceTimeouts::new(None).wrap(module.into());
+
+        let started = Instant::now();
+        let err = module
+            .call::<_, Vec<Value>>(
+                "debug_traceBlockByNumber",
+                rpc_params!["0x1", serde_json::json!({ "timeout": "100ms" })],
+            )
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        let MethodsError::JsonRpc(err) = err else { panic!("unexpected error {err:?}") };
+        assert_eq!(err.code(), HlErrorCode::TraceTimeout.code());
+        let data: TraceTimeoutData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert_eq!(data, TraceTimeoutData { timeout_ms: 100 });
+
+        // The permit is released with the request, and the replay stops before the next
+        // transaction
+        let permit = tokio::time::timeout(Duration::from_secs(1), guard.acquire_owned()).await;
+        assert!(permit.is_ok());
+        let stopped = tokio::time::timeout(Duration::from_secs(1), done_rx).await;
+        assert!(stopped.unwrap().unwrap(), "replay finished without timing out");
+        assert!(replayed.load(Ordering::Relaxed) < TRANSACTIONS);
+
+        let err = module
+            .call::<_, Vec<Value>>(
+                "debug_traceBlockByNumber",
+                rpc_params!["0x1", serde_json::json!({ "timeout": "soon" })],
+            )
+            .await
+            .unwrap_err();
+        let MethodsError::JsonRpc(err) = err else { panic!("unexpected error {err:?}") };
+        assert_eq!(err.code(), INVALID_PARAMS_CODE);
+    }
+}
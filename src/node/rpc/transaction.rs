@@ -1,11 +1,12 @@
 use std::time::Duration;
 
-use crate::node::rpc::{HlEthApi, HlRpcNodeCore};
+use crate::node::rpc::{HlEthApi, HlRpcNodeCore, archive::ArchiveData};
 use alloy_primitives::{B256, Bytes};
 use reth::rpc::server_types::eth::EthApiError;
+use reth_provider::TransactionsProvider;
 use reth_rpc_eth_api::{
-    RpcConvert,
-    helpers::{EthTransactions, LoadTransaction, spec::SignersForRpc},
+    RpcConvert, RpcNodeCore, RpcReceipt,
+    helpers::{EthTransactions, LoadReceipt, LoadTransaction, spec::SignersForRpc},
 };
 
 impl<N, Rpc> LoadTransaction for HlEthApi<N, Rpc>
@@ -31,4 +32,26 @@ where
     fn send_raw_transaction_sync_timeout(&self) -> Duration {
         self.inner.eth_api.send_raw_transaction_sync_timeout()
     }
+
+    /// Same as the default implementation, but rejects transactions of blocks whose receipts were
+    /// pruned by `--archive-window` instead of returning `null`.
+    async fn transaction_receipt(
+        &self,
+        hash: B256,
+    ) -> Result<Option<RpcReceipt<Self::NetworkTypes>>, Self::Error>
+    where
+        Self: LoadReceipt + 'static,
+    {
+        if self.inner.archive_window.is_some() &&
+            let Some((_, meta)) = self.provider().transaction_by_hash_with_meta(hash)?
+        {
+            self.check_archive_window(ArchiveData::Blocks, meta.block_number)?;
+        }
+        match self.load_transaction_and_receipt(hash).await? {
+            Some((tx, meta, receipt)) => {
+                self.build_transaction_receipt(tx, meta, receipt).await.map(Some)
+            }
+            None => Ok(None),
+        }
+    }
 }
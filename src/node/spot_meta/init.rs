@@ -1,7 +1,7 @@
 use crate::node::{
     spot_meta::{SpotId, erc20_contract_to_spot_token},
     storage::tables::{self, SPOT_METADATA_KEY},
-    types::reth_compat,
+    types::{SpotMetaContext, reth_compat},
 };
 use alloy_primitives::Address;
 use reth_db::{DatabaseEnv, cursor::DbCursorRO};
@@ -9,27 +9,37 @@ use reth_db_api::{Database, transaction::DbTx};
 use std::{collections::BTreeMap, sync::Arc};
 use tracing::info;
 
-/// Load spot metadata from database and initialize cache
-pub fn load_spot_metadata_cache(db: &Arc<DatabaseEnv>, chain_id: u64) {
-    // Try to read from database
-    let data = match db.view(|tx| -> Result<Option<Vec<u8>>, reth_db::DatabaseError> {
+/// Read the raw spot metadata entry from database
+fn read_spot_metadata(
+    db: &Arc<DatabaseEnv>,
+) -> Result<Result<Option<Vec<u8>>, reth_db::DatabaseError>, reth_db::DatabaseError> {
+    db.view(|tx| -> Result<Option<Vec<u8>>, reth_db::DatabaseError> {
         let mut cursor = tx.cursor_read::<tables::SpotMetadata>()?;
         Ok(cursor.seek_exact(SPOT_METADATA_KEY)?.map(|(_, data)| data.to_vec()))
-    }) {
+    })
+}
+
+/// Read and deserialize spot metadata from database, logging why it is unavailable otherwise
+pub(crate) fn load_spot_metadata(
+    db: &Arc<DatabaseEnv>,
+    chain_id: u64,
+) -> Option<BTreeMap<Address, SpotId>> {
+    // Try to read from database
+    let data = match read_spot_metadata(db) {
         Ok(Ok(data)) => data,
         Ok(Err(e)) => {
             info!(
                 "Failed to read spot metadata from database: {}. Will fetch on-demand from API.",
                 e
             );
-            return;
+            return None;
         }
         Err(e) => {
             info!(
                 "Database view error while loading spot metadata: {}. Will fetch on-demand from API.",
                 e
             );
-            return;
+            return None;
         }
     };
 
@@ -39,7 +49,7 @@ pub fn load_spot_metadata_cache(db: &Arc<DatabaseEnv>, chain_id: u64) {
             "No spot metadata found in database for chain {}. Run 'init-state' to populate, or it will be fetched on-demand from API.",
             chain_id
         );
-        return;
+        return None;
     };
 
     // Deserialize metadata
@@ -47,16 +57,42 @@ pub fn load_spot_metadata_cache(db: &Arc<DatabaseEnv>, chain_id: u64) {
         Ok(map) => map,
         Err(e) => {
             info!("Failed to deserialize spot metadata: {}. Will fetch on-demand from API.", e);
-            return;
+            return None;
         }
     };
 
-    // Convert and initialize cache
-    let metadata: BTreeMap<Address, SpotId> =
-        serializable_map.into_iter().map(|(addr, index)| (addr, SpotId { index })).collect();
+    // Convert to spot ids
+    Some(serializable_map.into_iter().map(|(addr, index)| (addr, SpotId { index })).collect())
+}
+
+/// Load spot metadata from database and initialize the cache of `spot_meta`
+pub fn load_spot_metadata_cache(spot_meta: &SpotMetaContext, db: &Arc<DatabaseEnv>, chain_id: u64) {
+    let Some(metadata) = load_spot_metadata(db, chain_id) else {
+        return;
+    };
 
     info!("Loaded spot metadata from database ({} entries)", metadata.len());
-    reth_compat::initialize_spot_metadata_cache(metadata);
+    spot_meta.initialize(metadata);
+}
+
+/// Reload spot metadata into `spot_meta` without restarting.
+///
+/// Re-runs the load path against the database, then refreshes from the API; the API result
+/// wins when it is reachable. Returns the new entry count.
+pub fn reload_spot_metadata(spot_meta: &SpotMetaContext, chain_id: u64) -> eyre::Result<usize> {
+    let stored = spot_meta.db().and_then(|db| load_spot_metadata(&db, chain_id));
+    let metadata = match (spot_meta.fetch_from_api(chain_id), stored) {
+        (Ok(metadata), _) => metadata,
+        (Err(e), Some(stored)) => {
+            info!("Failed to fetch spot metadata from API: {}. Reloading from database.", e);
+            stored
+        }
+        (Err(e), None) => return Err(e.wrap_err("failed to reload spot metadata")),
+    };
+
+    let count = spot_meta.reload(metadata);
+    info!("Reloaded spot metadata ({} entries)", count);
+    Ok(count)
 }
 
 /// Initialize spot metadata in database from API
@@ -95,3 +131,41 @@ pub fn init_spot_metadata(
     info!("Successfully fetched and stored spot metadata for chain {}", chain_id);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::storage::tables::Tables;
+    use reth_db::{ClientVersion, mdbx::DatabaseArguments};
+    use std::time::Duration;
+
+    fn open(path: &std::path::Path) -> Arc<DatabaseEnv> {
+        let args = DatabaseArguments::new(ClientVersion::default());
+        Arc::new(reth_db::mdbx::init_db_for::<_, Tables>(path, args).unwrap())
+    }
+
+    #[test]
+    fn persisted_spot_metadata_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = BTreeMap::from([
+            (Address::repeat_byte(0x20), SpotId { index: 1 }),
+            (Address::repeat_byte(0x21), SpotId { index: 2 }),
+        ]);
+
+        // The reload fails to persist, leaving the metadata for the shutdown to flush
+        let db = open(dir.path());
+        let spot_meta = SpotMetaContext::default()
+            .with_persist_retries(1, Duration::ZERO)
+            .with_store(|_, _| eyre::bail!("database is busy"));
+        spot_meta.set_db(db.clone());
+        assert_eq!(spot_meta.reload(metadata.clone()), 2);
+        assert_eq!(load_spot_metadata(&db, 999), None);
+        spot_meta.shutdown();
+        assert!(spot_meta.db().is_none());
+        drop(db);
+
+        // Simulated restart: reopen and read back
+        let db = open(dir.path());
+        assert_eq!(load_spot_metadata(&db, 999), Some(metadata));
+    }
+}
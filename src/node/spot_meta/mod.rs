@@ -37,6 +37,15 @@ impl SpotId {
         addr[24..32].copy_from_slice(self.index.to_be_bytes().as_ref());
         U256::from_be_bytes(addr)
     }
+
+    /// Inverse of [`Self::to_s`]: the spot token whose system address is encoded in `s`.
+    pub(crate) fn from_s(s: U256) -> Option<Self> {
+        let addr = s.to_be_bytes::<32>();
+        let is_system_address =
+            addr[..12].iter().chain(&addr[13..24]).all(|byte| *byte == 0) && addr[12] == 0x20;
+        is_system_address
+            .then(|| Self { index: u64::from_be_bytes(addr[24..32].try_into().unwrap()) })
+    }
 }
 
 fn fetch_spot_meta(chain_id: u64) -> Result<SpotMeta> {
@@ -0,0 +1,158 @@
+//! Periodic status line of the import pipeline and the block source it imports from.
+//!
+//! Summarizes what `hl_importStatus` and the block source metrics report, for operators who only
+//! look at the logs. Every value is read from the shared status cells once per interval, so the
+//! fetch and import paths never wait on the logger.
+
+use crate::{
+    addons::sync_server::SyncSourceStatus, node::network::block_import::status::EngineStatus,
+};
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+use tokio::time::MissedTickBehavior;
+use tracing::info;
+
+/// Default interval of the status line, in seconds.
+pub const DEFAULT_STATUS_LOG_INTERVAL: u64 = 30;
+
+/// Counters sampled at the end of an interval, to derive rates over the next one.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    head: Option<u64>,
+    cache_lookups: (u64, u64),
+}
+
+/// Logs the state of the import pipeline every `interval`.
+#[derive(Debug, Clone)]
+pub struct StatusLogger {
+    engine_status: EngineStatus,
+    source_status: SyncSourceStatus,
+    interval: Duration,
+}
+
+impl StatusLogger {
+    pub fn new(
+        engine_status: EngineStatus,
+        source_status: SyncSourceStatus,
+        interval: Duration,
+    ) -> Self {
+        Self { engine_status, source_status, interval }
+    }
+
+    fn sample(&self) -> Sample {
+        Sample {
+            at: Instant::now(),
+            head: self.engine_status.import().head,
+            cache_lookups: self.source_status.cache_lookups(),
+        }
+    }
+
+    /// Logs the status line for the interval since `previous`, returning the new sample.
+    fn report(&self, previous: Sample) -> Sample {
+        let current = self.sample();
+        let source_latest = self.source_status.latest();
+        let lag = current.head.zip(source_latest).map(|(head, latest)| latest.saturating_sub(head));
+
+        let elapsed = current.at.duration_since(previous.at).as_secs_f64();
+        let imported =
+            current.head.zip(previous.head).map_or(0, |(head, prev)| head.saturating_sub(prev));
+        let blocks_per_sec = if elapsed > 0.0 { imported as f64 / elapsed } else { 0.0 };
+
+        let hits = current.cache_lookups.0 - previous.cache_lookups.0;
+        let misses = current.cache_lookups.1 - previous.cache_lookups.1;
+        let cache_hit_rate = (hits + misses > 0)
+            .then(|| format!("{:.1}%", hits as f64 * 100.0 / (hits + misses) as f64));
+
+        info!(
+            target: "reth::cli",
+            head = %or_none(current.head),
+            source_latest = %or_none(source_latest),
+            lag = %or_none(lag),
+            blocks_per_sec = %format_args!("{blocks_per_sec:.2}"),
+            cache_hit_rate = %or_none(cache_hit_rate),
+            active_source = %or_none(self.source_status.active_source()),
+            "Import status"
+        );
+        current
+    }
+
+    /// Logs the status line every interval, starting one interval from now.
+    pub async fn run(self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        ticker.tick().await;
+        let mut previous = self.sample();
+        loop {
+            ticker.tick().await;
+            previous = self.report(previous);
+        }
+    }
+}
+
+fn or_none(value: Option<impl Display>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pseudo_peer::sources::ActiveSource;
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    /// Log output captured in memory.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_status_line() {
+        let (engine_status, source_status) = (EngineStatus::default(), SyncSourceStatus::default());
+        let logger = StatusLogger::new(
+            engine_status.clone(),
+            source_status.clone(),
+            Duration::from_secs(10),
+        );
+
+        engine_status.record_import(100);
+        source_status.record_cache_lookups(5, 5);
+        let previous = Sample { at: Instant::now() - Duration::from_secs(10), ..logger.sample() };
+
+        engine_status.record_import(150);
+        source_status.record(160);
+        source_status.record_cache_lookups(9, 1);
+        source_status.record_active_source(ActiveSource::Fallback);
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber =
+            tracing_subscriber::fmt().with_ansi(false).with_writer(move || writer.clone()).finish();
+        tracing::subscriber::with_default(subscriber, || logger.report(previous));
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("Import status"), "{output}");
+        assert!(output.contains("head=150 source_latest=160 lag=10"), "{output}");
+        // 50 blocks over a little more than 10 seconds
+        let rate = output.split("blocks_per_sec=").nth(1).unwrap().split(' ').next().unwrap();
+        let rate: f64 = rate.parse().unwrap();
+        assert!(rate > 4.5 && rate <= 5.0, "{output}");
+        assert!(output.contains("cache_hit_rate=90.0%"), "{output}");
+        assert!(output.contains("active_source=fallback"), "{output}");
+    }
+}
@@ -0,0 +1,284 @@
+//! Progress of the block sources, persisted independently of stage checkpoints.
+//!
+//! The pseudo peer derives its starting height from the `Finish` stage checkpoint, which a manual
+//! stage unwind moves without the block source knowing. Each configured block source has its own
+//! checkpoint, keyed by [`BlockSourceConfig::checkpoint_key`], recording the last height it served
+//! and the last height whose block was stored, so that the two can be reconciled on boot.
+//!
+//! The stored height is written by the storage in the same transaction as the blocks, and moved
+//! back with them on unwind. The served height is written by the [`BlockSourceCheckpointer`] of
+//! the pseudo peer, which owns the checkpoint of its source.
+//!
+//! [`BlockSourceConfig::checkpoint_key`]: crate::pseudo_peer::BlockSourceConfig::checkpoint_key
+
+use super::tables;
+use crate::HlPrimitives;
+use alloy_primitives::{B256, Bytes};
+use futures::StreamExt;
+use reth_db::{
+    DatabaseError,
+    cursor::DbCursorRO,
+    transaction::{DbTx, DbTxMut},
+};
+use reth_provider::{
+    CanonStateNotificationStream, DBProvider, DatabaseProviderFactory, ProviderResult,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use tracing::{info, warn};
+
+/// Persisted progress of a block source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSourceCheckpoint {
+    /// Last height the block source served to the node.
+    pub served: u64,
+    /// Last height whose block was stored.
+    pub acknowledged: u64,
+}
+
+/// Highest height served by a block source since startup.
+#[derive(Debug, Default)]
+pub struct BlockSourceProgress(AtomicU64);
+
+impl BlockSourceProgress {
+    pub fn record_served(&self, height: u64) {
+        self.0.fetch_max(height, Ordering::Relaxed);
+    }
+
+    pub fn served(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads the checkpoint of the block source `source`, if one was ever written. A checkpoint that
+/// doesn't decode is a [`DatabaseError::Decode`].
+pub fn read_block_source_checkpoint<Tx: DbTx>(
+    tx: &Tx,
+    source: B256,
+) -> ProviderResult<Option<BlockSourceCheckpoint>> {
+    tx.get::<tables::BlockSourceCheckpoints>(source)?.as_deref().map(decode_checkpoint).transpose()
+}
+
+fn decode_checkpoint(checkpoint: &[u8]) -> ProviderResult<BlockSourceCheckpoint> {
+    Ok(rmp_serde::from_slice(checkpoint).map_err(|_| DatabaseError::Decode)?)
+}
+
+fn write_block_source_checkpoint<Tx: DbTxMut>(
+    tx: &Tx,
+    source: B256,
+    checkpoint: BlockSourceCheckpoint,
+) -> ProviderResult<()> {
+    tx.put::<tables::BlockSourceCheckpoints>(
+        source,
+        Bytes::from(rmp_serde::to_vec(&checkpoint).expect("Failed to serialize checkpoint")),
+    )?;
+    Ok(())
+}
+
+/// Applies `update` to the checkpoint of every block source.
+fn update_block_source_checkpoints<Tx: DbTx + DbTxMut>(
+    tx: &Tx,
+    update: impl Fn(BlockSourceCheckpoint) -> BlockSourceCheckpoint,
+) -> ProviderResult<()> {
+    let checkpoints = tx
+        .cursor_read::<tables::BlockSourceCheckpoints>()?
+        .walk(None)?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (source, checkpoint) in checkpoints {
+        write_block_source_checkpoint(tx, source, update(decode_checkpoint(&checkpoint)?))?;
+    }
+    Ok(())
+}
+
+/// Records that the blocks up to `stored` were stored, in the checkpoint of every block source.
+pub(crate) fn record_stored_blocks<Tx: DbTx + DbTxMut>(tx: &Tx, stored: u64) -> ProviderResult<()> {
+    update_block_source_checkpoints(tx, |checkpoint| BlockSourceCheckpoint {
+        served: checkpoint.served.max(stored),
+        acknowledged: stored,
+    })
+}
+
+/// Moves the checkpoint of every block source back to `height` when the blocks above it are
+/// removed.
+pub(crate) fn unwind_block_source_checkpoints<Tx: DbTx + DbTxMut>(
+    tx: &Tx,
+    height: u64,
+) -> ProviderResult<()> {
+    update_block_source_checkpoints(tx, |checkpoint| BlockSourceCheckpoint {
+        served: checkpoint.served.min(height),
+        acknowledged: checkpoint.acknowledged.min(height),
+    })
+}
+
+/// Raises the height served by `source` to `served`. The checkpoint is left alone if it doesn't
+/// exist, as only [`BlockSourceCheckpointer::open`] knows the stored height to create it with.
+fn record_served<Tx: DbTx + DbTxMut>(tx: &Tx, source: B256, served: u64) -> ProviderResult<()> {
+    let Some(checkpoint) = read_block_source_checkpoint(tx, source)? else {
+        return Ok(());
+    };
+    if served > checkpoint.served {
+        write_block_source_checkpoint(tx, source, BlockSourceCheckpoint { served, ..checkpoint })?;
+    }
+    Ok(())
+}
+
+/// Returns the height the block source resumes after, given the `Finish` stage checkpoint.
+///
+/// Takes the lower of the stage checkpoint and the stored height, so that blocks are never
+/// skipped after either was moved back, and logs when they disagree.
+pub fn reconcile_start_height(stage: u64, checkpoint: Option<BlockSourceCheckpoint>) -> u64 {
+    let Some(checkpoint) = checkpoint else {
+        return stage;
+    };
+    if checkpoint.acknowledged != stage {
+        warn!(
+            stage,
+            acknowledged = checkpoint.acknowledged,
+            served = checkpoint.served,
+            "Block source checkpoint disagrees with the stage checkpoint, resuming from the lower"
+        );
+    } else {
+        info!(stage, served = checkpoint.served, "Resuming block source");
+    }
+    stage.min(checkpoint.acknowledged)
+}
+
+/// Owns the checkpoint of a block source, persisting the height it served as blocks are
+/// committed.
+#[derive(Debug, Clone)]
+pub struct BlockSourceCheckpointer<F> {
+    provider_factory: F,
+    source: B256,
+    progress: Arc<BlockSourceProgress>,
+}
+
+impl<F> BlockSourceCheckpointer<F>
+where
+    F: DatabaseProviderFactory<ProviderRW: DBProvider<Tx: DbTxMut>> + Clone + Send + Sync + 'static,
+{
+    /// Opens the checkpoint of `source`, returning it as it was left by the previous run. A
+    /// source without one starts from the `stored` height.
+    pub fn open(
+        provider_factory: F,
+        source: B256,
+        stored: u64,
+    ) -> ProviderResult<(Self, Option<BlockSourceCheckpoint>)> {
+        let provider = provider_factory.database_provider_rw()?;
+        let checkpoint = read_block_source_checkpoint(provider.tx_ref(), source)?;
+        if checkpoint.is_none() {
+            let checkpoint = BlockSourceCheckpoint { served: stored, acknowledged: stored };
+            write_block_source_checkpoint(provider.tx_ref(), source, checkpoint)?;
+            provider.commit()?;
+        }
+        let checkpointer = Self { provider_factory, source, progress: Default::default() };
+        Ok((checkpointer, checkpoint))
+    }
+
+    /// Progress of the source, for its pseudo peer to record the heights it serves.
+    pub fn progress(&self) -> Arc<BlockSourceProgress> {
+        self.progress.clone()
+    }
+
+    /// Persists the height served so far.
+    pub fn record(&self) -> ProviderResult<()> {
+        let provider = self.provider_factory.database_provider_rw()?;
+        record_served(provider.tx_ref(), self.source, self.progress.served())?;
+        provider.commit()?;
+        Ok(())
+    }
+
+    /// Persists the height served after each committed chain, off the async runtime.
+    pub async fn run(self, mut notifications: CanonStateNotificationStream<HlPrimitives>) {
+        while notifications.next().await.is_some() {
+            let checkpointer = self.clone();
+            match tokio::task::spawn_blocking(move || checkpointer.record()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => warn!(%err, "Failed to write the block source checkpoint"),
+                Err(err) => warn!(%err, "Block source checkpoint task failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::storage::tables::Tables;
+    use reth_db::{ClientVersion, Database, mdbx::DatabaseArguments};
+    use reth_provider::ProviderError;
+
+    const SOURCE: B256 = B256::repeat_byte(1);
+    const OTHER_SOURCE: B256 = B256::repeat_byte(2);
+
+    #[test]
+    fn reconciles_after_unwind_and_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let args = DatabaseArguments::new(ClientVersion::default());
+            let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+            assert_eq!(reconcile_start_height(0, read_checkpoint(&db, SOURCE)), 0);
+
+            let tx = db.tx_mut().unwrap();
+            let opened = BlockSourceCheckpoint { served: 0, acknowledged: 0 };
+            write_block_source_checkpoint(&tx, SOURCE, opened).unwrap();
+            let opened = BlockSourceCheckpoint { served: 50, acknowledged: 50 };
+            write_block_source_checkpoint(&tx, OTHER_SOURCE, opened).unwrap();
+            tx.commit().unwrap();
+
+            for stored in [100, 110] {
+                let tx = db.tx_mut().unwrap();
+                record_stored_blocks(&tx, stored).unwrap();
+                tx.commit().unwrap();
+            }
+            let tx = db.tx_mut().unwrap();
+            record_served(&tx, SOURCE, 120).unwrap();
+            // Served heights are never lowered, and only written to an opened checkpoint
+            record_served(&tx, OTHER_SOURCE, 10).unwrap();
+            record_served(&tx, B256::ZERO, 10).unwrap();
+            tx.commit().unwrap();
+            let checkpoint = read_checkpoint(&db, SOURCE).unwrap();
+            assert_eq!(checkpoint, BlockSourceCheckpoint { served: 120, acknowledged: 110 });
+            let checkpoint = read_checkpoint(&db, OTHER_SOURCE).unwrap();
+            assert_eq!(checkpoint, BlockSourceCheckpoint { served: 110, acknowledged: 110 });
+            assert_eq!(read_checkpoint(&db, B256::ZERO), None);
+
+            // Manual `stage unwind` to 90
+            let tx = db.tx_mut().unwrap();
+            unwind_block_source_checkpoints(&tx, 90).unwrap();
+            tx.commit().unwrap();
+        }
+
+        // Restart
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let unwound = BlockSourceCheckpoint { served: 90, acknowledged: 90 };
+        assert_eq!(read_checkpoint(&db, OTHER_SOURCE), Some(unwound));
+        let checkpoint = read_checkpoint(&db, SOURCE);
+        assert_eq!(checkpoint, Some(unwound));
+        assert_eq!(reconcile_start_height(90, checkpoint), 90);
+        // A stage checkpoint moved back without removing blocks, or one left ahead
+        assert_eq!(reconcile_start_height(80, checkpoint), 80);
+        assert_eq!(reconcile_start_height(110, checkpoint), 90);
+    }
+
+    #[test]
+    fn corrupt_checkpoint_is_a_decode_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let tx = db.tx_mut().unwrap();
+        tx.put::<tables::BlockSourceCheckpoints>(SOURCE, Bytes::from_static(&[0xc1])).unwrap();
+
+        let err = read_block_source_checkpoint(&tx, SOURCE).unwrap_err();
+        assert!(matches!(err, ProviderError::Database(DatabaseError::Decode)));
+        let err = record_stored_blocks(&tx, 1).unwrap_err();
+        assert!(matches!(err, ProviderError::Database(DatabaseError::Decode)));
+    }
+
+    fn read_checkpoint(db: &impl Database, source: B256) -> Option<BlockSourceCheckpoint> {
+        read_block_source_checkpoint(&db.tx().unwrap(), source).unwrap()
+    }
+}
@@ -2,11 +2,11 @@ use crate::{
     HlBlock, HlBlockBody, HlHeader, HlPrimitives,
     node::{primitives::TransactionSigned, types::HlExtras},
 };
-use alloy_consensus::BlockHeader;
-use alloy_primitives::Bytes;
+use alloy_consensus::{BlockHeader, transaction::TxHashRef};
+use alloy_primitives::{Bytes, TxHash};
 use reth_chainspec::EthereumHardforks;
 use reth_db::{
-    DbTxUnwindExt,
+    DatabaseError, DbTxUnwindExt,
     cursor::{DbCursorRO, DbCursorRW},
     transaction::{DbTx, DbTxMut},
 };
@@ -16,7 +16,10 @@ use reth_provider::{
     DBProvider, DatabaseProvider, EthStorage, ProviderResult, ReadBodyInput, StorageLocation,
     providers::{ChainStorage, NodeTypesForProvider},
 };
+use serde::{Deserialize, Serialize};
 
+pub mod checkpoint;
+pub mod prune;
 pub mod tables;
 
 #[derive(Debug, Clone, Default)]
@@ -61,11 +64,10 @@ impl HlStorage {
             provider.tx_ref().cursor_read::<tables::BlockReadPrecompileCalls>()?;
 
         for (header, _transactions) in inputs {
-            let precompile_calls = precompile_calls_cursor
-                .seek_exact(header.number())?
-                .map(|(_, calls)| calls)
-                .map(|calls| rmp_serde::from_slice(&calls).unwrap())
-                .unwrap_or_default();
+            let precompile_calls = match precompile_calls_cursor.seek_exact(header.number())? {
+                Some((_, calls)) => decode_extras(&calls)?,
+                None => HlExtras::default(),
+            };
             extras.push(precompile_calls);
         }
 
@@ -73,6 +75,62 @@ impl HlStorage {
     }
 }
 
+/// Reads the [`HlExtras`] of a single block, outside of the regular block body reader.
+pub(crate) fn read_block_extras<Tx: DbTx>(tx: &Tx, number: u64) -> ProviderResult<HlExtras> {
+    match tx.get::<tables::BlockReadPrecompileCalls>(number)? {
+        Some(calls) => decode_extras(&calls),
+        None => Ok(HlExtras::default()),
+    }
+}
+
+/// Decodes a [`tables::BlockReadPrecompileCalls`] row.
+fn decode_extras(calls: &[u8]) -> ProviderResult<HlExtras> {
+    Ok(rmp_serde::from_slice(calls).map_err(|_| DatabaseError::Decode)?)
+}
+
+/// Where a system transaction is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SystemTxLocation {
+    pub block_number: u64,
+    /// Index of the transaction in the block.
+    pub index: u64,
+}
+
+/// Indexes the system transactions of block `number` in [`tables::SystemTxHashNumbers`],
+/// returning how many were indexed.
+pub(crate) fn write_system_tx_hashes<Tx: DbTxMut>(
+    tx: &Tx,
+    number: u64,
+    transactions: &[TransactionSigned],
+) -> ProviderResult<usize> {
+    let mut indexed = 0;
+    for (index, transaction) in transactions.iter().enumerate() {
+        if !transaction.is_system_transaction() {
+            continue;
+        }
+        let location = SystemTxLocation { block_number: number, index: index as u64 };
+        tx.put::<tables::SystemTxHashNumbers>(
+            *transaction.tx_hash(),
+            Bytes::from(rmp_serde::to_vec(&location).expect("Failed to serialize tx location")),
+        )?;
+        indexed += 1;
+    }
+    Ok(indexed)
+}
+
+/// Reads where the system transaction `hash` is stored, if it is indexed. A location that doesn't
+/// decode is a [`DatabaseError::Decode`].
+pub(crate) fn read_system_tx_location<Tx: DbTx>(
+    tx: &Tx,
+    hash: TxHash,
+) -> ProviderResult<Option<SystemTxLocation>> {
+    let Some(location) = tx.get::<tables::SystemTxHashNumbers>(hash)? else {
+        return Ok(None);
+    };
+    let location = rmp_serde::from_slice(&location).map_err(|_| DatabaseError::Decode)?;
+    Ok(Some(location))
+}
+
 impl<Provider> BlockBodyWriter<Provider, HlBlockBody> for HlStorage
 where
     Provider: DBProvider<Tx: DbTxMut>,
@@ -85,8 +143,12 @@ where
     ) -> ProviderResult<()> {
         let mut eth_bodies = Vec::with_capacity(bodies.len());
         let mut read_precompile_calls = Vec::with_capacity(bodies.len());
+        let highest = bodies.iter().map(|(number, _)| *number).max();
 
         for (block_number, body) in bodies {
+            if let Some(body) = &body {
+                write_system_tx_hashes(provider.tx_ref(), block_number, &body.inner.transactions)?;
+            }
             let (inner_opt, extras) = match body {
                 Some(HlBlockBody {
                     inner,
@@ -102,6 +164,9 @@ where
 
         self.0.write_block_bodies(provider, eth_bodies, write_to)?;
         self.write_precompile_calls(provider, read_precompile_calls)?;
+        if let Some(highest) = highest {
+            checkpoint::record_stored_blocks(provider.tx_ref(), highest)?;
+        }
 
         Ok(())
     }
@@ -114,6 +179,9 @@ where
     ) -> ProviderResult<()> {
         self.0.remove_block_bodies_above(provider, block, remove_from)?;
         provider.tx_ref().unwind_table_by_num::<tables::BlockReadPrecompileCalls>(block)?;
+        checkpoint::unwind_block_source_checkpoints(provider.tx_ref(), block)?;
+        // `SystemTxHashNumbers` is keyed by hash and can't be unwound by number. Entries of
+        // removed blocks are checked against the block on lookup and overwritten on re-import.
 
         Ok(())
     }
@@ -170,3 +238,75 @@ impl ChainStorage<HlPrimitives> for HlStorage {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::storage::tables::Tables;
+    use alloy_consensus::{Signed, TxLegacy};
+    use alloy_primitives::{Signature, U256};
+    use reth_db::{ClientVersion, Database, mdbx::DatabaseArguments};
+    use reth_primitives::TransactionSigned as RethTxSigned;
+    use reth_provider::ProviderError;
+
+    fn transaction(gas_price: u128, nonce: u64) -> TransactionSigned {
+        let tx = TxLegacy { gas_price, nonce, ..Default::default() };
+        let signature = Signature::new(U256::ZERO, U256::from(nonce + 1), false);
+        TransactionSigned::Default(RethTxSigned::Legacy(Signed::new_unhashed(tx, signature)))
+    }
+
+    #[test]
+    fn indexes_system_transactions_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let system_txs = [transaction(0, 0), transaction(0, 1)];
+        let user_tx = transaction(1, 2);
+        let block = [system_txs[0].clone(), system_txs[1].clone(), user_tx.clone()];
+
+        // Backfilling a block is the same write as importing it, and can be repeated
+        for _ in 0..2 {
+            let tx = db.tx_mut().unwrap();
+            assert_eq!(write_system_tx_hashes(&tx, 7, &block).unwrap(), 2);
+            tx.commit().unwrap();
+        }
+
+        let tx = db.tx().unwrap();
+        for (index, system_tx) in system_txs.iter().enumerate() {
+            assert_eq!(
+                read_system_tx_location(&tx, *system_tx.tx_hash()).unwrap(),
+                Some(SystemTxLocation { block_number: 7, index: index as u64 })
+            );
+        }
+        assert_eq!(read_system_tx_location(&tx, *user_tx.tx_hash()).unwrap(), None);
+    }
+
+    #[test]
+    fn corrupt_location_is_a_decode_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let hash = *transaction(0, 0).tx_hash();
+        let tx = db.tx_mut().unwrap();
+        tx.put::<tables::SystemTxHashNumbers>(hash, Bytes::from_static(&[0xc1])).unwrap();
+        tx.commit().unwrap();
+
+        let err = read_system_tx_location(&db.tx().unwrap(), hash).unwrap_err();
+        assert!(matches!(err, ProviderError::Database(DatabaseError::Decode)), "{err:?}");
+    }
+
+    #[test]
+    fn corrupt_extras_are_a_decode_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap();
+        let tx = db.tx_mut().unwrap();
+        tx.put::<tables::BlockReadPrecompileCalls>(7, Bytes::from_static(&[0xc1])).unwrap();
+        tx.commit().unwrap();
+
+        let err = read_block_extras(&db.tx().unwrap(), 7).unwrap_err();
+        assert!(matches!(err, ProviderError::Database(DatabaseError::Decode)), "{err:?}");
+        let missing = read_block_extras(&db.tx().unwrap(), 8).unwrap();
+        assert!(missing.read_precompile_calls.is_none());
+    }
+}
@@ -0,0 +1,164 @@
+//! Coordinated pruning for `--archive-window`: only the latest blocks keep their full data.
+//!
+//! Receipts, and state history when enabled, are pruned by reth's pruner, configured from the
+//! window. Read precompile calls live in a table reth's pruner doesn't know about, so
+//! [`PrecompileCallsPruner`] prunes them as the chain grows.
+
+use super::tables;
+use crate::HlPrimitives;
+use alloy_consensus::BlockHeader;
+use futures::StreamExt;
+use reth_db::{cursor::DbCursorRW, transaction::DbTxMut};
+use reth_node_core::args::PruningArgs;
+use reth_provider::{
+    CanonStateNotificationStream, DBProvider, DatabaseProviderFactory, ProviderResult,
+};
+use tracing::{debug, warn};
+
+/// Smallest archive window: reth's minimum pruning distance, which keeps enough blocks to
+/// handle reorgs.
+pub const MINIMUM_ARCHIVE_WINDOW: u64 = 10_064;
+
+/// Maximum number of entries deleted in one database transaction.
+const PRUNE_BATCH_SIZE: usize = 10_000;
+
+/// The latest blocks whose full data is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveWindow {
+    /// Number of blocks kept, including the tip.
+    pub blocks: u64,
+    /// Whether state history is pruned too, in which case historical state is only available
+    /// within the window.
+    pub prune_state: bool,
+}
+
+impl ArchiveWindow {
+    /// Lowest block whose data is kept when `tip` is the latest block.
+    pub fn lowest_available(&self, tip: u64) -> u64 {
+        (tip + 1).saturating_sub(self.blocks)
+    }
+
+    /// Configures reth's pruner to prune receipts, and state history if enabled, below the
+    /// window.
+    pub fn apply_to(&self, pruning: &mut PruningArgs) {
+        pruning.receipts_distance = Some(self.blocks);
+        if self.prune_state {
+            pruning.account_history_distance = Some(self.blocks);
+            pruning.storage_history_distance = Some(self.blocks);
+        }
+    }
+}
+
+/// Deletes the read precompile calls of blocks below `below`, at most `limit` of them, returning
+/// how many were deleted.
+pub(crate) fn prune_precompile_calls<Tx: DbTxMut>(
+    tx: &Tx,
+    below: u64,
+    limit: usize,
+) -> ProviderResult<usize> {
+    let mut cursor = tx.cursor_write::<tables::BlockReadPrecompileCalls>()?;
+    let mut deleted = 0;
+    while deleted < limit &&
+        let Some((number, _)) = cursor.first()? &&
+        number < below
+    {
+        cursor.delete_current()?;
+        deleted += 1;
+    }
+    Ok(deleted)
+}
+
+/// Prunes read precompile calls below the archive window as blocks are committed.
+#[derive(Debug, Clone)]
+pub struct PrecompileCallsPruner<F> {
+    provider_factory: F,
+    window: ArchiveWindow,
+}
+
+impl<F> PrecompileCallsPruner<F>
+where
+    F: DatabaseProviderFactory<ProviderRW: DBProvider<Tx: DbTxMut>> + Clone + Send + Sync + 'static,
+{
+    pub fn new(provider_factory: F, window: ArchiveWindow) -> Self {
+        Self { provider_factory, window }
+    }
+
+    /// Prunes everything below the window of `tip`, committing in batches.
+    pub fn prune(&self, tip: u64) -> ProviderResult<usize> {
+        let below = self.window.lowest_available(tip);
+        let mut pruned = 0;
+        loop {
+            let provider = self.provider_factory.database_provider_rw()?;
+            let deleted = prune_precompile_calls(provider.tx_ref(), below, PRUNE_BATCH_SIZE)?;
+            provider.commit()?;
+            pruned += deleted;
+            if deleted < PRUNE_BATCH_SIZE {
+                return Ok(pruned);
+            }
+        }
+    }
+
+    /// Prunes after each committed chain, off the async runtime.
+    pub async fn run(self, mut notifications: CanonStateNotificationStream<HlPrimitives>) {
+        while let Some(notification) = notifications.next().await {
+            let tip = notification.tip().number();
+            let pruner = self.clone();
+            match tokio::task::spawn_blocking(move || pruner.prune(tip)).await {
+                Ok(Ok(0)) => {}
+                Ok(Ok(pruned)) => debug!(tip, pruned, "Pruned read precompile calls"),
+                Ok(Err(err)) => warn!(tip, %err, "Failed to prune read precompile calls"),
+                Err(err) => warn!(tip, %err, "Read precompile call pruning task failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::Bytes;
+    use reth_db::{
+        ClientVersion, Database, cursor::DbCursorRO, mdbx::DatabaseArguments, transaction::DbTx,
+    };
+
+    #[test]
+    fn prunes_outside_the_window_and_keeps_the_rest() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = reth_db::init_db(dir.path(), DatabaseArguments::new(ClientVersion::default()))?;
+        db.create_tables_for::<tables::Tables>()?;
+
+        let tx = db.tx_mut()?;
+        for number in 1..=100 {
+            tx.put::<tables::BlockReadPrecompileCalls>(number, Bytes::from_static(b"calls"))?;
+        }
+        let window = ArchiveWindow { blocks: 30, prune_state: false };
+        let below = window.lowest_available(100);
+        assert_eq!(below, 71);
+        // Batches stop at the limit, and the next one picks up where it stopped
+        assert_eq!(prune_precompile_calls(&tx, below, 25)?, 25);
+        assert_eq!(prune_precompile_calls(&tx, below, usize::MAX)?, 45);
+        assert_eq!(prune_precompile_calls(&tx, below, usize::MAX)?, 0);
+        tx.commit()?;
+
+        let tx = db.tx()?;
+        let kept = tx
+            .cursor_read::<tables::BlockReadPrecompileCalls>()?
+            .walk(None)?
+            .map(|entry| entry.map(|(number, _)| number))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(kept, (71..=100).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn window_configures_reth_pruning() {
+        let mut pruning = PruningArgs::default();
+        ArchiveWindow { blocks: 20_000, prune_state: false }.apply_to(&mut pruning);
+        assert_eq!(pruning.receipts_distance, Some(20_000));
+        assert_eq!(pruning.account_history_distance, None);
+
+        ArchiveWindow { blocks: 20_000, prune_state: true }.apply_to(&mut pruning);
+        assert_eq!(pruning.account_history_distance, Some(20_000));
+        assert_eq!(pruning.storage_history_distance, Some(20_000));
+    }
+}
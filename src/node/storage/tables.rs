@@ -1,4 +1,4 @@
-use alloy_primitives::{BlockNumber, Bytes};
+use alloy_primitives::{B256, BlockNumber, Bytes, TxHash};
 use reth_db::{TableSet, TableType, TableViewer, table::TableInfo, tables};
 use std::fmt;
 
@@ -19,4 +19,19 @@ tables! {
         type Key = u64;
         type Value = Bytes;
     }
+
+    /// Location of each system transaction (block number and index in the block), by hash.
+    /// Values are msgpack-encoded [`SystemTxLocation`](super::SystemTxLocation)s.
+    table SystemTxHashNumbers {
+        type Key = TxHash;
+        type Value = Bytes;
+    }
+
+    /// Progress of each configured block source, msgpack-encoded
+    /// [`BlockSourceCheckpoint`](super::checkpoint::BlockSourceCheckpoint)s, by the key of the
+    /// source.
+    table BlockSourceCheckpoints {
+        type Key = B256;
+        type Value = Bytes;
+    }
 }
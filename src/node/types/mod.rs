@@ -9,8 +9,12 @@ use bytes::BufMut;
 use reth_ethereum_primitives::EthereumReceipt;
 use reth_primitives_traits::InMemorySize;
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 
-use crate::HlBlock;
+use crate::{
+    HlBlock,
+    chainspec::{BASE_PRECOMPILE_ADDRESS, DEFAULT_HIGHEST_PRECOMPILE_ADDRESS},
+};
 
 pub type ReadPrecompileCall = (Address, Vec<(ReadPrecompileInput, ReadPrecompileResult)>);
 
@@ -18,9 +22,15 @@ pub type ReadPrecompileCall = (Address, Vec<(ReadPrecompileInput, ReadPrecompile
 pub struct ReadPrecompileCalls(pub Vec<ReadPrecompileCall>);
 
 pub(crate) mod reth_compat;
+pub mod system_tx_kind;
+#[cfg(test)]
+mod wire_format_tests;
 
 // Re-export spot metadata functions
-pub use reth_compat::{initialize_spot_metadata_cache, set_spot_metadata_db};
+pub use reth_compat::{
+    SPOT_META_PERSIST_ATTEMPTS, SPOT_META_PERSIST_BACKOFF, SpotMetaContext,
+};
+pub use system_tx_kind::SystemTxKind;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct HlExtras {
@@ -28,10 +38,31 @@ pub struct HlExtras {
     pub highest_precompile_address: Option<Address>,
 }
 
+impl HlExtras {
+    /// Read precompile addresses of the block: from the base address up to
+    /// `highest_precompile_address`, or the chain default for blocks that don't record it.
+    pub fn precompile_address_range(&self) -> RangeInclusive<Address> {
+        BASE_PRECOMPILE_ADDRESS..=
+            self.highest_precompile_address.unwrap_or(DEFAULT_HIGHEST_PRECOMPILE_ADDRESS)
+    }
+}
+
 impl InMemorySize for HlExtras {
     fn size(&self) -> usize {
-        self.read_precompile_calls.as_ref().map_or(0, |s| s.0.len()) +
-            self.highest_precompile_address.as_ref().map_or(0, |_| 20)
+        self.read_precompile_calls.as_ref().map_or(0, |calls| calls.size()) +
+            self.highest_precompile_address.as_ref().map_or(0, |_| size_of::<Address>())
+    }
+}
+
+impl InMemorySize for ReadPrecompileCalls {
+    fn size(&self) -> usize {
+        self.0
+            .iter()
+            .map(|(_, calls)| {
+                size_of::<ReadPrecompileCall>() +
+                    calls.iter().map(|(input, result)| input.size() + result.size()).sum::<usize>()
+            })
+            .sum()
     }
 }
 
@@ -44,13 +75,21 @@ impl Encodable for ReadPrecompileCalls {
 
 impl Decodable for ReadPrecompileCalls {
     fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
-        let bytes = Bytes::decode(buf)?;
-        let calls = rmp_serde::decode::from_slice(&bytes)
+        // Borrowed rather than copied out of the message, as the encoding is only read once
+        let bytes = alloy_rlp::Header::decode_bytes(buf, false)?;
+        let calls = rmp_serde::decode::from_slice(bytes)
             .map_err(|_| alloy_rlp::Error::Custom("Failed to decode ReadPrecompileCalls"))?;
         Ok(Self(calls))
     }
 }
 
+/// Version of the msgpack encoding of [`BlockAndReceipts`], as stored on S3, written by hl-node
+/// and served by the sync server. Changes to these types or their serde attributes that alter the
+/// encoding must bump it.
+///
+/// - 1: named maps, with `system_txs` and `read_precompile_calls` defaulting to empty.
+pub const BLOCK_FORMAT_VERSION: u64 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct BlockAndReceipts {
     pub block: EvmBlock,
@@ -63,24 +102,41 @@ pub struct BlockAndReceipts {
 }
 
 impl BlockAndReceipts {
-    pub fn to_reth_block(self, chain_id: u64) -> HlBlock {
+    /// Converts to a node block, deriving system transaction senders from `spot_meta`, which must
+    /// have the [`Self::spot_tokens`], see [`SpotMetaContext::resolve`]. Fails if the sender of a
+    /// system transaction can't be derived.
+    pub fn to_reth_block(self, spot_meta: &SpotMetaContext) -> eyre::Result<HlBlock> {
         let EvmBlock::Reth115(block) = self.block;
         block.to_reth_block(
             self.read_precompile_calls.clone(),
             self.highest_precompile_address,
             self.system_txs.clone(),
             self.receipts.clone(),
-            chain_id,
+            spot_meta,
         )
     }
 
+    /// Tokens of the system transactions whose senders are derived from the spot metadata.
+    pub fn spot_tokens(&self) -> impl Iterator<Item = Address> + '_ {
+        self.system_txs
+            .iter()
+            .filter(|tx| tx.receipt.is_some())
+            .filter_map(reth_compat::system_tx_token)
+    }
+
     /// Construct a `BlockAndReceipts` from database types (reverse of `to_reth_block`).
     ///
     /// Splits system transactions and receipts from regular ones using
     /// the `system_tx_count` stored in the header extras.
     pub fn from_db(block: HlBlock, receipts: Vec<EthereumReceipt>) -> Self {
-        let system_tx_count = block.header.extras.system_tx_count as usize;
         let hash = alloy_primitives::Sealable::hash_slow(&block.header);
+        Self::from_db_sealed(hash, block, receipts)
+    }
+
+    /// Like [`Self::from_db`], for a block whose hash is already known (e.g. read from static
+    /// files alongside its header).
+    pub fn from_db_sealed(hash: B256, block: HlBlock, receipts: Vec<EthereumReceipt>) -> Self {
+        let system_tx_count = block.header.extras.system_tx_count as usize;
         let all_txs = block.body.inner.transactions;
 
         // Split system txs from regular txs
@@ -151,6 +207,29 @@ impl BlockAndReceipts {
         let EvmBlock::Reth115(block) = &self.block;
         block.header.header.number
     }
+
+    pub fn parent_hash(&self) -> B256 {
+        let EvmBlock::Reth115(block) = &self.block;
+        block.header.header.parent_hash
+    }
+
+    /// Chain of the first transaction signed for one. System transactions don't count, and
+    /// blocks without user transactions don't tell their chain.
+    pub fn chain_id(&self) -> Option<u64> {
+        let EvmBlock::Reth115(block) = &self.block;
+        block.body.transactions.iter().find_map(|tx| tx.chain_id())
+    }
+}
+
+impl InMemorySize for BlockAndReceipts {
+    fn size(&self) -> usize {
+        let EvmBlock::Reth115(block) = &self.block;
+        block.size() +
+            self.receipts.iter().map(InMemorySize::size).sum::<usize>() +
+            self.system_txs.iter().map(InMemorySize::size).sum::<usize>() +
+            self.read_precompile_calls.size() +
+            self.highest_precompile_address.as_ref().map_or(0, |_| size_of::<Address>())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -166,6 +245,16 @@ pub struct LegacyReceipt {
     logs: Vec<Log>,
 }
 
+impl InMemorySize for LegacyReceipt {
+    fn size(&self) -> usize {
+        size_of::<Self>() +
+            self.logs
+                .iter()
+                .map(|log| size_of::<Log>() + log.topics().len() * 32 + log.data.data.len())
+                .sum::<usize>()
+    }
+}
+
 impl From<LegacyReceipt> for EthereumReceipt {
     fn from(r: LegacyReceipt) -> Self {
         EthereumReceipt {
@@ -215,6 +304,12 @@ pub struct SystemTx {
     pub receipt: Option<LegacyReceipt>,
 }
 
+impl InMemorySize for SystemTx {
+    fn size(&self) -> usize {
+        self.tx.size() + self.receipt.as_ref().map_or(0, InMemorySize::size)
+    }
+}
+
 impl SystemTx {
     pub fn gas_limit(&self) -> u64 {
         use reth_compat::Transaction;
@@ -246,6 +341,12 @@ pub struct ReadPrecompileInput {
     pub gas_limit: u64,
 }
 
+impl InMemorySize for ReadPrecompileInput {
+    fn size(&self) -> usize {
+        size_of::<Self>() + self.input.len()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum ReadPrecompileResult {
     Ok { gas_used: u64, bytes: Bytes },
@@ -253,3 +354,125 @@ pub enum ReadPrecompileResult {
     Error,
     UnexpectedError,
 }
+
+impl InMemorySize for ReadPrecompileResult {
+    fn size(&self) -> usize {
+        size_of::<Self>() +
+            match self {
+                Self::Ok { bytes, .. } => bytes.len(),
+                _ => 0,
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{node::spot_meta::SpotId, test_utils::empty_block};
+    use alloy_consensus::TxLegacy;
+    use alloy_primitives::{Sealable, TxKind, address};
+    use reth_primitives_traits::BlockBody as _;
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    #[test]
+    fn extras_size_counts_precompile_payloads() {
+        let input = |len| ReadPrecompileInput { input: Bytes::from(vec![0; len]), gas_limit: 0 };
+        let ok = |len| ReadPrecompileResult::Ok { gas_used: 0, bytes: Bytes::from(vec![0; len]) };
+        let extras = HlExtras {
+            read_precompile_calls: Some(ReadPrecompileCalls(vec![
+                (Address::repeat_byte(1), vec![(input(36), ok(64)), (input(4), ok(1024))]),
+                (Address::repeat_byte(2), vec![(input(68), ReadPrecompileResult::OutOfGas)]),
+            ])),
+            highest_precompile_address: Some(Address::repeat_byte(2)),
+        };
+
+        let input_size = size_of::<ReadPrecompileInput>();
+        let result_size = size_of::<ReadPrecompileResult>();
+        let expected = 2 * size_of::<ReadPrecompileCall>() +
+            3 * (input_size + result_size) +
+            (36 + 4 + 68) +
+            (64 + 1024) +
+            size_of::<Address>();
+        assert_eq!(extras.size(), expected);
+    }
+
+    #[test]
+    fn precompile_address_range_falls_back_to_chain_default() {
+        let highest = address!("0x0000000000000000000000000000000000000812");
+        let extras = HlExtras { highest_precompile_address: Some(highest), ..Default::default() };
+        assert_eq!(extras.precompile_address_range(), BASE_PRECOMPILE_ADDRESS..=highest);
+        assert_eq!(
+            HlExtras::default().precompile_address_range(),
+            BASE_PRECOMPILE_ADDRESS..=DEFAULT_HIGHEST_PRECOMPILE_ADDRESS
+        );
+    }
+
+    #[test]
+    fn imported_system_tx_senders_do_not_need_spot_metadata() {
+        let token = address!("0x2000000000000000000000000000000000000001");
+        let system_tx = SystemTx {
+            tx: reth_compat::Transaction::Legacy(TxLegacy {
+                to: TxKind::Call(token),
+                input: Bytes::from_static(&[0xa9, 0x05, 0x9c, 0xbb]),
+                ..Default::default()
+            }),
+            receipt: Some(EthereumReceipt::default().into()),
+        };
+        let block = BlockAndReceipts { system_txs: vec![system_tx], ..empty_block(0) };
+        let spot_meta = SpotMetaContext::new(BTreeMap::from([(token, SpotId { index: 3 })]));
+        // Converted again for peer requests, with the senders derived on the first conversion
+        let converted = block.clone().to_reth_block(&spot_meta).unwrap();
+        let block = block.to_reth_block(&spot_meta).unwrap();
+        assert_eq!(block, converted);
+        assert_eq!(spot_meta.lookups(), 1);
+
+        // Serving the block recovers senders from the stored pseudo signature only
+        spot_meta.initialize(BTreeMap::new());
+        let senders = block.body.recover_signers().unwrap();
+        assert_eq!(senders, vec![address!("0x2000000000000000000000000000000000000003")]);
+        let served = BlockAndReceipts::from_db(block, vec![EthereumReceipt::default()]);
+        assert_eq!(served.system_txs.len(), 1);
+        assert_eq!(spot_meta.lookups(), 1);
+    }
+
+    /// Recomputes the hash of real mainnet blocks, which pins the header encoding. Populate the
+    /// fixtures directory first, see `tests/fixtures/blocks/README.md`, then run with
+    /// `cargo test block_hashes_match_mainnet -- --ignored`.
+    #[test]
+    #[ignore = "requires mainnet block fixtures"]
+    fn block_hashes_match_mainnet() {
+        let dir = std::env::var("HL_BLOCK_FIXTURES").map(PathBuf::from).unwrap_or_else(|_| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/blocks")
+        });
+        let mut checked = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if !path.to_string_lossy().ends_with(".rmp.lz4") {
+                continue;
+            }
+            let file = std::fs::read(&path).unwrap();
+            let mut decoder = lz4_flex::frame::FrameDecoder::new(&file[..]);
+            let blocks: Vec<BlockAndReceipts> = rmp_serde::from_read(&mut decoder).unwrap();
+            for block in blocks {
+                let (number, expected) = (block.number(), block.hash());
+                // System tx senders don't affect the hash; any spot index avoids an API lookup
+                let spot_meta = SpotMetaContext::new(
+                    block
+                        .system_txs
+                        .iter()
+                        .filter_map(|tx| match &tx.tx {
+                            reth_compat::Transaction::Legacy(tx) => tx.to.to().copied(),
+                            _ => None,
+                        })
+                        .map(|to| (to, SpotId { index: 0 }))
+                        .collect::<BTreeMap<_, _>>(),
+                );
+                let block = block.to_reth_block(&spot_meta).unwrap();
+                assert_eq!(block.header.number, number, "{}", path.display());
+                assert_eq!(block.header.hash_slow(), expected, "block {number} hash changed");
+                checked += 1;
+            }
+        }
+        assert!(checked > 0, "no block fixtures found in {}", dir.display());
+    }
+}
@@ -1,22 +1,32 @@
 //! Copy of reth codebase to preserve serialization compatibility
 use crate::node::storage::tables::{SPOT_METADATA_KEY, SpotMetadata};
 use alloy_consensus::{Header, Signed, TxEip1559, TxEip2930, TxEip4844, TxEip7702, TxLegacy};
-use alloy_primitives::{Address, BlockHash, Bytes, Signature, TxKind, U256};
+use alloy_primitives::{Address, B256, BlockHash, Bytes, Signature, TxKind, U256};
 use reth_db::{DatabaseEnv, DatabaseError, cursor::DbCursorRW};
 use reth_db_api::{Database, transaction::DbTxMut};
+use reth_metrics::{
+    Metrics,
+    metrics::{Counter, Gauge},
+};
+use reth_network::cache::LruMap;
 use reth_primitives::TransactionSigned as RethTxSigned;
+use reth_primitives_traits::InMemorySize;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeMap,
-    sync::{Arc, LazyLock, Mutex, RwLock},
+    collections::{BTreeMap, HashMap},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
     HlBlock, HlBlockBody, HlHeader,
     node::{
         primitives::TransactionSigned as TxSigned,
-        spot_meta::{SpotId, erc20_contract_to_spot_token},
+        spot_meta::{SpotId, erc20_contract_to_spot_token, init::load_spot_metadata},
         types::{LegacyReceipt, ReadPrecompileCalls, SystemTx},
     },
 };
@@ -110,6 +120,37 @@ impl TransactionSigned {
     }
 }
 
+impl Transaction {
+    /// Chain the transaction is signed for, `None` for legacy transactions predating EIP-155.
+    pub fn chain_id(&self) -> Option<u64> {
+        match self {
+            Self::Legacy(tx) => tx.chain_id,
+            Self::Eip2930(tx) => Some(tx.chain_id),
+            Self::Eip1559(tx) => Some(tx.chain_id),
+            Self::Eip4844(tx) => Some(tx.chain_id),
+            Self::Eip7702(tx) => Some(tx.chain_id),
+        }
+    }
+}
+
+impl InMemorySize for Transaction {
+    fn size(&self) -> usize {
+        match self {
+            Self::Legacy(tx) => tx.size(),
+            Self::Eip2930(tx) => tx.size(),
+            Self::Eip1559(tx) => tx.size(),
+            Self::Eip4844(tx) => tx.size(),
+            Self::Eip7702(tx) => tx.size(),
+        }
+    }
+}
+
+impl InMemorySize for TransactionSigned {
+    fn size(&self) -> usize {
+        size_of::<Signature>() + self.transaction.size()
+    }
+}
+
 type BlockBody = alloy_consensus::BlockBody<TransactionSigned, Header>;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -126,24 +167,409 @@ pub struct SealedBlock {
     pub body: BlockBody,
 }
 
-static SPOT_EVM_MAP: LazyLock<Arc<RwLock<BTreeMap<Address, SpotId>>>> =
-    LazyLock::new(|| Arc::new(RwLock::new(BTreeMap::new())));
+impl InMemorySize for SealedBlock {
+    fn size(&self) -> usize {
+        size_of::<BlockHash>() + self.header.header.size() + self.body.size()
+    }
+}
 
-// Optional database handle for persisting on-demand fetches
-static DB_HANDLE: LazyLock<Mutex<Option<Arc<DatabaseEnv>>>> = LazyLock::new(|| Mutex::new(None));
+/// Spot metadata used to derive system transaction senders, along with where to persist it.
+///
+/// Clones share the same state. Each node owns a context, so that several nodes can run
+/// in-process.
+#[derive(Debug, Clone, Default)]
+pub struct SpotMetaContext {
+    map: Arc<RwLock<BTreeMap<Address, SpotId>>>,
+    /// Optional database handle for persisting on-demand fetches
+    db: Arc<Mutex<Option<Arc<DatabaseEnv>>>>,
+    /// Set when the in-memory spot metadata is newer than what is persisted to the database
+    dirty: Arc<AtomicBool>,
+    /// Number of system transaction senders derived from the spot metadata
+    lookups: Arc<AtomicU64>,
+    /// Senders of the system transactions of recently converted blocks
+    senders: SystemTxSenders,
+    /// Fetches the spot metadata on cache misses
+    fetch: SpotMetaFetch,
+    /// Held while the spot metadata is fetched, so that concurrent misses fetch it once
+    fetching: Arc<tokio::sync::Mutex<()>>,
+    /// Tokens still missing after the last fetch, and when it ended
+    unresolved: Arc<Mutex<HashMap<Address, Instant>>>,
+    /// Writes the spot metadata to the database
+    store: SpotMetaStore,
+    metrics: SpotMetaMetrics,
+}
 
-/// Set the database handle for persisting spot metadata
-pub fn set_spot_metadata_db(db: Arc<DatabaseEnv>) {
-    *DB_HANDLE.lock().unwrap() = Some(db);
+#[derive(Metrics, Clone)]
+#[metrics(scope = "spot_meta")]
+struct SpotMetaMetrics {
+    /// Number of tokens in the spot metadata cache
+    entries: Gauge,
+    /// How many system transaction tokens were not in the spot metadata cache
+    cache_misses: Counter,
+    /// How many times the spot metadata was fetched from the API on a cache miss, per attempt
+    api_fetches: Counter,
+    /// How many fetches of the spot metadata from the API failed
+    api_fetch_failures: Counter,
+    /// How many times the spot metadata was persisted to the database
+    persists: Counter,
+    /// How many times persisting the spot metadata to the database failed, per attempt
+    persist_failures: Counter,
+    /// How many times persisting the spot metadata was given up after exhausting the retries
+    persist_exhausted: Counter,
 }
 
-/// Initialize the spot metadata cache with data loaded from database.
-/// This should be called during node initialization.
-pub fn initialize_spot_metadata_cache(metadata: BTreeMap<Address, SpotId>) {
-    *SPOT_EVM_MAP.write().unwrap() = metadata;
+/// Number of blocks whose system transaction senders are cached.
+const SYSTEM_TX_SENDERS_CACHE_SIZE: u32 = 1024;
+
+/// Signature `s` values of the system transactions of recently converted blocks by block hash,
+/// which encode their senders. A block is converted again for every peer request for its header
+/// or body, which then derives its senders without the spot metadata.
+#[derive(Debug, Clone)]
+struct SystemTxSenders(Arc<Mutex<LruMap<BlockHash, Arc<[U256]>>>>);
+
+impl Default for SystemTxSenders {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(LruMap::new(SYSTEM_TX_SENDERS_CACHE_SIZE))))
+    }
+}
+
+/// Number of attempts at fetching the spot metadata on a cache miss.
+const SPOT_META_FETCH_ATTEMPTS: u32 = 4;
+/// Delay before the second attempt, doubled for each later one.
+const SPOT_META_FETCH_BACKOFF: Duration = Duration::from_millis(500);
+/// How long a token missing after a fetch isn't fetched again.
+const SPOT_META_MISS_TTL: Duration = Duration::from_secs(60);
+
+type SpotMetaFetchFn = dyn Fn(u64) -> eyre::Result<BTreeMap<Address, SpotId>> + Send + Sync;
+
+/// How [`SpotMetaContext`] fetches the spot metadata, and how long it backs off between attempts.
+#[derive(Clone)]
+struct SpotMetaFetch {
+    fetch: Arc<SpotMetaFetchFn>,
+    backoff: Duration,
+}
+
+impl Default for SpotMetaFetch {
+    fn default() -> Self {
+        Self { fetch: Arc::new(erc20_contract_to_spot_token), backoff: SPOT_META_FETCH_BACKOFF }
+    }
+}
+
+impl std::fmt::Debug for SpotMetaFetch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpotMetaFetch").field("backoff", &self.backoff).finish_non_exhaustive()
+    }
+}
+
+/// Default number of attempts at persisting the spot metadata to the database.
+pub const SPOT_META_PERSIST_ATTEMPTS: u32 = 3;
+/// Default delay before the second persist attempt, doubled for each later one.
+pub const SPOT_META_PERSIST_BACKOFF: Duration = Duration::from_millis(100);
+
+type SpotMetaStoreFn =
+    dyn Fn(&Arc<DatabaseEnv>, &BTreeMap<Address, SpotId>) -> eyre::Result<()> + Send + Sync;
+
+/// How [`SpotMetaContext`] writes the spot metadata to the database, and how it retries.
+#[derive(Clone)]
+struct SpotMetaStore {
+    store: Arc<SpotMetaStoreFn>,
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl Default for SpotMetaStore {
+    fn default() -> Self {
+        Self {
+            store: Arc::new(|db, metadata| Ok(store_spot_metadata(db, metadata)?)),
+            attempts: SPOT_META_PERSIST_ATTEMPTS,
+            backoff: SPOT_META_PERSIST_BACKOFF,
+        }
+    }
+}
+
+impl std::fmt::Debug for SpotMetaStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpotMetaStore")
+            .field("attempts", &self.attempts)
+            .field("backoff", &self.backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SpotMetaContext {
+    pub fn new(metadata: BTreeMap<Address, SpotId>) -> Self {
+        let context = Self::default();
+        context.initialize(metadata);
+        context
+    }
+
+    /// Replaces how the spot metadata is fetched on cache misses, e.g. to simulate API failures.
+    pub(crate) fn with_fetch(
+        mut self,
+        fetch: impl Fn(u64) -> eyre::Result<BTreeMap<Address, SpotId>> + Send + Sync + 'static,
+        backoff: Duration,
+    ) -> Self {
+        self.fetch = SpotMetaFetch { fetch: Arc::new(fetch), backoff };
+        self
+    }
+
+    /// Makes up to `attempts` attempts at persisting the spot metadata, backing off `backoff`
+    /// after the first failure and twice as long after each later one.
+    pub fn with_persist_retries(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.store.attempts = attempts.max(1);
+        self.store.backoff = backoff;
+        self
+    }
+
+    /// Replaces how the spot metadata is written to the database, e.g. to simulate write failures.
+    pub(crate) fn with_store(
+        mut self,
+        store: impl Fn(&Arc<DatabaseEnv>, &BTreeMap<Address, SpotId>) -> eyre::Result<()>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        self.store.store = Arc::new(store);
+        self
+    }
+
+    /// Set the database handle for persisting spot metadata
+    pub fn set_db(&self, db: Arc<DatabaseEnv>) {
+        *self.db.lock().unwrap() = Some(db);
+    }
+
+    /// Replace the spot metadata, e.g. with data loaded from database. Senders derived from the
+    /// previous metadata are dropped.
+    pub fn initialize(&self, metadata: BTreeMap<Address, SpotId>) {
+        self.metrics.entries.set(metadata.len() as f64);
+        *self.map.write().unwrap() = metadata;
+        self.senders.0.lock().unwrap().clear();
+    }
+
+    /// Swap in freshly loaded spot metadata and persist it. Returns the new entry count.
+    pub fn reload(&self, metadata: BTreeMap<Address, SpotId>) -> usize {
+        let count = metadata.len();
+        self.initialize(metadata.clone());
+        self.persist(&metadata);
+        count
+    }
+
+    /// The database handle used for persisting spot metadata, if set.
+    pub(crate) fn db(&self) -> Option<Arc<DatabaseEnv>> {
+        self.db.lock().unwrap().clone()
+    }
+
+    /// Flush spot metadata that failed to persist and release the database handle.
+    ///
+    /// A persist backing off meanwhile stops at its next attempt, leaving the flush to this.
+    /// Should be called on shutdown, before the database is closed.
+    pub fn shutdown(&self) {
+        let Some(db) = self.db.lock().unwrap().take() else {
+            return;
+        };
+        if self.dirty.swap(false, Ordering::SeqCst) {
+            let metadata = self.map.read().unwrap().clone();
+            match store_spot_metadata(&db, &metadata) {
+                Ok(_) => info!("Flushed spot metadata to database on shutdown"),
+                Err(e) => warn!("Failed to flush spot metadata to database on shutdown: {}", e),
+            }
+        }
+    }
+
+    /// Persist spot metadata to database if handle is available, retrying transient failures.
+    ///
+    /// `metadata` is a copy, so the spot metadata stays readable while the writes back off. The
+    /// handle lock is only held to clone the handle, so that neither [`Self::shutdown`] nor other
+    /// persists wait for the backoff. The metadata is marked dirty until written: when every
+    /// attempt fails, or the handle is released by a shutdown meanwhile, the shutdown flushes it.
+    fn persist(&self, metadata: &BTreeMap<Address, SpotId>) {
+        if self.db().is_none() {
+            return;
+        }
+        self.dirty.store(true, Ordering::SeqCst);
+        let mut backoff = self.store.backoff;
+        let mut attempt = 1;
+        // Released on shutdown, which flushes the dirty metadata itself
+        while let Some(db) = self.db() {
+            match (self.store.store)(&db, metadata) {
+                Ok(_) => {
+                    self.metrics.persists.increment(1);
+                    self.dirty.store(false, Ordering::SeqCst);
+                    info!(attempt, "Persisted spot metadata to database");
+                    return;
+                }
+                Err(err) if attempt < self.store.attempts => {
+                    self.metrics.persist_failures.increment(1);
+                    info!(
+                        %err,
+                        attempt,
+                        "Failed to persist spot metadata, retrying in {backoff:?}"
+                    );
+                    drop(db);
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    self.metrics.persist_failures.increment(1);
+                    self.metrics.persist_exhausted.increment(1);
+                    warn!(%err, attempts = attempt, "Gave up persisting spot metadata to database");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Number of system transaction senders derived so far.
+    ///
+    /// Senders are only derived when converting archive blocks, once per block while it is
+    /// cached. Imported blocks store the pseudo signature, from which senders are recovered
+    /// without the spot metadata.
+    pub fn lookups(&self) -> u64 {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    /// Returns the signature `s` values of `system_txs`, the system transactions of the block
+    /// `hash`, deriving them on the first conversion of the block.
+    fn system_tx_signatures(
+        &self,
+        hash: BlockHash,
+        system_txs: &[SystemTx],
+    ) -> eyre::Result<Arc<[U256]>> {
+        if let Some(signatures) = self.senders.0.lock().unwrap().get(&hash) &&
+            signatures.len() == system_txs.len()
+        {
+            return Ok(signatures.clone());
+        }
+        let signatures = system_txs
+            .iter()
+            .map(|tx| system_tx_signature(tx, self))
+            .collect::<eyre::Result<Arc<[U256]>>>()?;
+        self.senders.0.lock().unwrap().insert(hash, signatures.clone());
+        Ok(signatures)
+    }
+
+    /// Returns the signature `s` value of system transactions to the token at `to`, failing if
+    /// the spot metadata doesn't have it, as the sender of its system transactions is unknown.
+    ///
+    /// Nothing is fetched here, see [`Self::resolve`].
+    fn spot_s(&self, to: Address) -> eyre::Result<U256> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        match self.map.read().unwrap().get(&to) {
+            Some(spot) => Ok(spot.to_s()),
+            None => Err(eyre::eyre!(
+                "no spot metadata for system transaction token {to}; the spot metadata API is \
+                 unreachable or does not list it"
+            )),
+        }
+    }
+
+    /// Tokens of `tokens` the spot metadata doesn't have, leaving out those a fetch missed less
+    /// than [`SPOT_META_MISS_TTL`] ago.
+    fn missing(&self, tokens: &[Address]) -> Vec<Address> {
+        let map = self.map.read().unwrap();
+        let unresolved = self.unresolved.lock().unwrap();
+        let recently_missed = |token| {
+            unresolved.get(token).is_some_and(|at: &Instant| at.elapsed() < SPOT_META_MISS_TTL)
+        };
+        tokens
+            .iter()
+            .filter(|token| !map.contains_key(*token) && !recently_missed(*token))
+            .copied()
+            .collect()
+    }
+
+    /// Makes sure the spot metadata has `tokens`, the tokens of the system transactions of blocks
+    /// about to be converted.
+    ///
+    /// On a cache miss, the spot metadata is fetched from the API with retries, once for all the
+    /// callers missing tokens at the same time. If the API stays unreachable, the spot metadata
+    /// last persisted to the database is used instead. Tokens still missing aren't fetched again
+    /// for [`SPOT_META_MISS_TTL`]; converting their blocks fails meanwhile.
+    pub async fn resolve(&self, tokens: impl IntoIterator<Item = Address>, chain_id: u64) {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        if self.missing(&tokens).is_empty() {
+            return;
+        }
+        let _fetching = self.fetching.lock().await;
+        // Fetched meanwhile by the caller that held the lock
+        let missing = self.missing(&tokens);
+        if missing.is_empty() {
+            return;
+        }
+
+        self.metrics.cache_misses.increment(1);
+        info!(?missing, "Tokens not found in the spot metadata, fetching it from the API");
+        let this = self.clone();
+        let resolved = tokio::task::spawn_blocking(move || this.refresh(chain_id));
+        if let Err(err) = resolved.await {
+            warn!(%err, "Failed to fetch the spot metadata");
+        }
+
+        let map = self.map.read().unwrap();
+        let mut unresolved = self.unresolved.lock().unwrap();
+        for token in missing {
+            if map.contains_key(&token) {
+                unresolved.remove(&token);
+            } else {
+                unresolved.insert(token, Instant::now());
+            }
+        }
+    }
+
+    /// Replaces the spot metadata with the API's and persists it, or adds the persisted spot
+    /// metadata if the API is unreachable. Runs on a blocking thread.
+    fn refresh(&self, chain_id: u64) {
+        match self.fetch_with_retries(chain_id) {
+            Ok(metadata) => {
+                self.initialize(metadata.clone());
+                self.persist(&metadata);
+            }
+            Err(err) => {
+                warn!(%err, "Spot metadata API unreachable, using the persisted spot metadata");
+                if let Some(stored) = self.db().and_then(|db| load_spot_metadata(&db, chain_id)) {
+                    let mut map = self.map.write().unwrap();
+                    for (address, spot) in stored {
+                        map.entry(address).or_insert(spot);
+                    }
+                    self.metrics.entries.set(map.len() as f64);
+                }
+            }
+        }
+    }
+
+    /// Fetches the spot metadata from the API once. Blocking.
+    pub(crate) fn fetch_from_api(&self, chain_id: u64) -> eyre::Result<BTreeMap<Address, SpotId>> {
+        (self.fetch.fetch)(chain_id)
+    }
+
+    /// Fetches the spot metadata, backing off between attempts. Runs on a blocking thread.
+    fn fetch_with_retries(&self, chain_id: u64) -> eyre::Result<BTreeMap<Address, SpotId>> {
+        let mut backoff = self.fetch.backoff;
+        let mut attempt = 1;
+        loop {
+            self.metrics.api_fetches.increment(1);
+            let result = (self.fetch.fetch)(chain_id);
+            if result.is_err() {
+                self.metrics.api_fetch_failures.increment(1);
+            }
+            match result {
+                Ok(metadata) => return Ok(metadata),
+                Err(err) if attempt < SPOT_META_FETCH_ATTEMPTS => {
+                    warn!(%err, attempt, "Failed to fetch spot metadata, retrying in {backoff:?}");
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
 }
 
 /// Helper function to serialize and store spot metadata to database
+///
+/// The write is committed before returning, so it is durable under the database's sync mode.
 pub fn store_spot_metadata(
     db: &Arc<DatabaseEnv>,
     metadata: &BTreeMap<Address, SpotId>,
@@ -165,56 +591,59 @@ pub fn store_spot_metadata(
     })?
 }
 
-/// Persist spot metadata to database if handle is available
-fn persist_spot_metadata_to_db(metadata: &BTreeMap<Address, SpotId>) {
-    if let Some(db) = DB_HANDLE.lock().unwrap().as_ref() {
-        match store_spot_metadata(db, metadata) {
-            Ok(_) => info!("Persisted spot metadata to database"),
-            Err(e) => info!("Failed to persist spot metadata to database: {}", e),
-        }
+/// The token a system transaction needs the spot metadata of to derive its sender, if any.
+pub(crate) fn system_tx_token(transaction: &SystemTx) -> Option<Address> {
+    match &transaction.tx {
+        Transaction::Legacy(tx) if !tx.input.is_empty() => tx.to.to().copied(),
+        _ => None,
     }
 }
 
-fn system_tx_to_reth_transaction(transaction: &SystemTx, chain_id: u64) -> TxSigned {
+/// The signature `s` value of a system transaction, which encodes its sender.
+fn system_tx_signature(transaction: &SystemTx, spot_meta: &SpotMetaContext) -> eyre::Result<U256> {
     let Transaction::Legacy(tx) = &transaction.tx else {
         panic!("Unexpected transaction type");
     };
     let TxKind::Call(to) = tx.to else {
         panic!("Unexpected contract creation");
     };
-    let s = if tx.input.is_empty() {
-        U256::from(0x1)
-    } else {
-        loop {
-            if let Some(spot) = SPOT_EVM_MAP.read().unwrap().get(&to) {
-                break spot.to_s();
-            }
+    if tx.input.is_empty() { Ok(U256::from(0x1)) } else { spot_meta.spot_s(to) }
+}
 
-            // Cache miss - fetch from API, update cache, and persist to database
-            info!("Contract not found: {to:?} from spot mapping, fetching from API...");
-            let metadata = erc20_contract_to_spot_token(chain_id).unwrap();
-            *SPOT_EVM_MAP.write().unwrap() = metadata.clone();
-            persist_spot_metadata_to_db(&metadata);
-        }
+fn system_tx_to_reth_transaction(transaction: &SystemTx, s: U256) -> TxSigned {
+    let Transaction::Legacy(tx) = &transaction.tx else {
+        panic!("Unexpected transaction type");
     };
     let signature = Signature::new(U256::from(0x1), s, true);
     TxSigned::Default(RethTxSigned::Legacy(Signed::new_unhashed(tx.clone(), signature)))
 }
 
 impl SealedBlock {
+    /// Root of the transactions of the body, which excludes the system transactions the header
+    /// doesn't commit to either.
+    pub fn calculate_tx_root(&self) -> B256 {
+        let transactions: Vec<_> =
+            self.body.transactions.iter().map(TransactionSigned::to_reth_transaction).collect();
+        alloy_consensus::proofs::calculate_transaction_root(&transactions)
+    }
+
     pub fn to_reth_block(
         &self,
         read_precompile_calls: ReadPrecompileCalls,
         highest_precompile_address: Option<Address>,
         mut system_txs: Vec<super::SystemTx>,
         receipts: Vec<LegacyReceipt>,
-        chain_id: u64,
-    ) -> HlBlock {
+        spot_meta: &SpotMetaContext,
+    ) -> eyre::Result<HlBlock> {
         // NOTE: These types of transactions are tracked at #97.
         system_txs.retain(|tx| tx.receipt.is_some());
 
-        let mut merged_txs = vec![];
-        merged_txs.extend(system_txs.iter().map(|tx| system_tx_to_reth_transaction(tx, chain_id)));
+        let signatures = spot_meta.system_tx_signatures(self.header.hash, &system_txs)?;
+        let mut merged_txs = system_txs
+            .iter()
+            .zip(signatures.iter())
+            .map(|(tx, s)| system_tx_to_reth_transaction(tx, *s))
+            .collect::<Vec<_>>();
         merged_txs.extend(self.body.transactions.iter().map(|tx| tx.to_reth_transaction()));
 
         let mut merged_receipts = vec![];
@@ -233,13 +662,296 @@ impl SealedBlock {
         };
 
         let system_tx_count = system_txs.len() as u64;
-        HlBlock {
+        Ok(HlBlock {
             header: HlHeader::from_ethereum_header(
                 self.header.header.clone(),
                 &merged_receipts,
                 system_tx_count,
             ),
             body: block_body,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::{spot_meta::init::reload_spot_metadata, storage::tables::Tables};
+    use alloy_primitives::address;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use reth_db::{ClientVersion, mdbx::DatabaseArguments};
+    use reth_metrics::metrics;
+    use reth_primitives_traits::SignerRecoverable;
+
+    const TOKEN: Address = address!("0x2000000000000000000000000000000000000001");
+
+    /// A system transaction calling [`TOKEN`].
+    fn system_tx() -> SystemTx {
+        SystemTx {
+            tx: Transaction::Legacy(TxLegacy {
+                to: TxKind::Call(TOKEN),
+                input: Bytes::from_static(&[0xa9, 0x05, 0x9c, 0xbb]),
+                ..Default::default()
+            }),
+            receipt: None,
+        }
+    }
+
+    /// Recovers the sender of a system transaction to [`TOKEN`] under `spot_meta`.
+    fn system_tx_sender(spot_meta: &SpotMetaContext) -> Address {
+        let tx = system_tx();
+        let tx = system_tx_to_reth_transaction(&tx, system_tx_signature(&tx, spot_meta).unwrap());
+        tx.recover_signer().unwrap()
+    }
+
+    #[test]
+    fn contexts_derive_system_tx_senders_independently() {
+        let first = SpotMetaContext::new(BTreeMap::from([(TOKEN, SpotId { index: 1 })]));
+        let second = SpotMetaContext::new(BTreeMap::from([(TOKEN, SpotId { index: 2 })]));
+        assert_eq!(
+            system_tx_sender(&first),
+            address!("0x2000000000000000000000000000000000000001")
+        );
+        assert_eq!(
+            system_tx_sender(&second),
+            address!("0x2000000000000000000000000000000000000002")
+        );
+    }
+
+    #[test]
+    fn reload_updates_system_tx_sender_derivation() {
+        let reloaded = BTreeMap::from([
+            (TOKEN, SpotId { index: 7 }),
+            (Address::repeat_byte(0x20), SpotId { index: 8 }),
+        ]);
+        // What the API serves, `None` while it is unreachable
+        let api = Arc::new(Mutex::new(None));
+        let spot_meta = SpotMetaContext::new(BTreeMap::from([(TOKEN, SpotId { index: 1 })]))
+            .with_fetch(
+                {
+                    let api = api.clone();
+                    move |_| api.lock().unwrap().clone().ok_or_else(|| eyre::eyre!("unreachable"))
+                },
+                Duration::ZERO,
+            );
+        let reload = || reload_spot_metadata(&spot_meta, 999);
+
+        // Nothing to reload from
+        assert!(reload().is_err());
+        assert_eq!(
+            system_tx_sender(&spot_meta),
+            address!("0x2000000000000000000000000000000000000001")
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = Arc::new(reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap());
+        spot_meta.set_db(db.clone());
+        *api.lock().unwrap() = Some(reloaded.clone());
+        assert_eq!(reload().unwrap(), 2);
+        assert_eq!(
+            system_tx_sender(&spot_meta),
+            address!("0x2000000000000000000000000000000000000007")
+        );
+        assert_eq!(load_spot_metadata(&db, 999), Some(reloaded));
+
+        // Reloads what was persisted while the API is unreachable
+        *api.lock().unwrap() = None;
+        spot_meta.initialize(BTreeMap::from([(TOKEN, SpotId { index: 1 })]));
+        assert_eq!(reload().unwrap(), 2);
+        assert_eq!(
+            system_tx_sender(&spot_meta),
+            address!("0x2000000000000000000000000000000000000007")
+        );
+    }
+
+    fn metric(snapshotter: &Snapshotter, name: &str) -> Option<DebugValue> {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| (key.key().name() == name).then_some(value))
+    }
+
+    #[tokio::test]
+    async fn cache_miss_is_counted_as_api_fetch() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        // Handles are bound to the recorder they were registered with
+        let spot_meta = metrics::with_local_recorder(&recorder, || {
+            SpotMetaContext::default().with_fetch(
+                |_| {
+                    Ok(BTreeMap::from([
+                        (TOKEN, SpotId { index: 1 }),
+                        (Address::repeat_byte(0x20), SpotId { index: 2 }),
+                    ]))
+                },
+                Duration::ZERO,
+            )
+        });
+
+        spot_meta.resolve([TOKEN], 999).await;
+        system_tx_sender(&spot_meta);
+        assert_eq!(metric(&snapshotter, "spot_meta.cache_misses"), Some(DebugValue::Counter(1)));
+        assert_eq!(metric(&snapshotter, "spot_meta.api_fetches"), Some(DebugValue::Counter(1)));
+        assert_eq!(metric(&snapshotter, "spot_meta.entries"), Some(DebugValue::Gauge(2.0.into())));
+
+        // Hits don't fetch
+        spot_meta.resolve([TOKEN], 999).await;
+        system_tx_sender(&spot_meta);
+        assert_eq!(metric(&snapshotter, "spot_meta.api_fetches"), Some(DebugValue::Counter(1)));
+        assert_eq!(
+            metric(&snapshotter, "spot_meta.api_fetch_failures"),
+            Some(DebugValue::Counter(0))
+        );
+    }
+
+    /// A context whose fetches fail, counting them.
+    fn unreachable_api() -> (SpotMetaContext, Arc<AtomicU64>) {
+        let attempts = Arc::new(AtomicU64::new(0));
+        let spot_meta = SpotMetaContext::default().with_fetch(
+            {
+                let attempts = attempts.clone();
+                move |_| {
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    eyre::bail!("connection refused")
+                }
+            },
+            Duration::ZERO,
+        );
+        (spot_meta, attempts)
+    }
+
+    #[tokio::test]
+    async fn cache_miss_survives_unreachable_api() {
+        let (spot_meta, attempts) = unreachable_api();
+        spot_meta.resolve([TOKEN], 999).await;
+        let err = system_tx_signature(&system_tx(), &spot_meta).unwrap_err();
+        assert!(err.to_string().contains("no spot metadata for system transaction token"));
+        assert_eq!(attempts.load(Ordering::Relaxed), u64::from(SPOT_META_FETCH_ATTEMPTS));
+
+        // The missing token isn't fetched again right away
+        spot_meta.resolve([TOKEN], 999).await;
+        assert_eq!(attempts.load(Ordering::Relaxed), u64::from(SPOT_META_FETCH_ATTEMPTS));
+
+        // Falls back to the spot metadata last persisted to the database
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = Arc::new(reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap());
+        store_spot_metadata(&db, &BTreeMap::from([(TOKEN, SpotId { index: 4 })])).unwrap();
+        let (spot_meta, _) = unreachable_api();
+        spot_meta.set_db(db);
+        spot_meta.resolve([TOKEN], 999).await;
+        assert_eq!(
+            system_tx_sender(&spot_meta),
+            address!("0x2000000000000000000000000000000000000004")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_cache_misses_fetch_once() {
+        let fetches = Arc::new(AtomicU64::new(0));
+        let spot_meta = SpotMetaContext::default().with_fetch(
+            {
+                let fetches = fetches.clone();
+                move |_| {
+                    fetches.fetch_add(1, Ordering::Relaxed);
+                    std::thread::sleep(Duration::from_millis(50));
+                    Ok(BTreeMap::from([(TOKEN, SpotId { index: 5 })]))
+                }
+            },
+            Duration::ZERO,
+        );
+
+        let misses = (0..8).map(|_| {
+            let spot_meta = spot_meta.clone();
+            tokio::spawn(async move { spot_meta.resolve([TOKEN], 999).await })
+        });
+        for miss in misses.collect::<Vec<_>>() {
+            miss.await.unwrap();
         }
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            system_tx_sender(&spot_meta),
+            address!("0x2000000000000000000000000000000000000005")
+        );
+    }
+
+    #[tokio::test]
+    async fn transient_persist_failure_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = Arc::new(reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap());
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let writes = Arc::new(AtomicU64::new(0));
+        let spot_meta = metrics::with_local_recorder(&recorder, || {
+            SpotMetaContext::default()
+                .with_fetch(|_| Ok(BTreeMap::from([(TOKEN, SpotId { index: 3 })])), Duration::ZERO)
+                .with_persist_retries(3, Duration::ZERO)
+                .with_store({
+                    let writes = writes.clone();
+                    move |db, metadata| {
+                        // The first write hits a transient error
+                        if writes.fetch_add(1, Ordering::Relaxed) == 0 {
+                            eyre::bail!("database is busy");
+                        }
+                        Ok(store_spot_metadata(db, metadata)?)
+                    }
+                })
+        });
+        spot_meta.set_db(db.clone());
+
+        spot_meta.resolve([TOKEN], 999).await;
+        assert_eq!(writes.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            load_spot_metadata(&db, 999),
+            Some(BTreeMap::from([(TOKEN, SpotId { index: 3 })]))
+        );
+        assert!(!spot_meta.dirty.load(Ordering::SeqCst));
+        assert_eq!(
+            metric(&snapshotter, "spot_meta.persist_failures"),
+            Some(DebugValue::Counter(1))
+        );
+        assert_eq!(metric(&snapshotter, "spot_meta.persists"), Some(DebugValue::Counter(1)));
+        assert_eq!(
+            metric(&snapshotter, "spot_meta.persist_exhausted"),
+            Some(DebugValue::Counter(0))
+        );
+    }
+
+    #[test]
+    fn shutdown_does_not_wait_for_a_persist_backing_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let args = DatabaseArguments::new(ClientVersion::default());
+        let db = Arc::new(reth_db::mdbx::init_db_for::<_, Tables>(dir.path(), args).unwrap());
+        let metadata = BTreeMap::from([(TOKEN, SpotId { index: 3 })]);
+
+        let (failed_tx, failed_rx) = std::sync::mpsc::channel();
+        let spot_meta = SpotMetaContext::new(metadata.clone())
+            .with_persist_retries(2, Duration::from_secs(1))
+            .with_store(move |_, _| {
+                let _ = failed_tx.send(());
+                eyre::bail!("database is busy")
+            });
+        spot_meta.set_db(db.clone());
+
+        let persisting = std::thread::spawn({
+            let (spot_meta, metadata) = (spot_meta.clone(), metadata.clone());
+            move || spot_meta.persist(&metadata)
+        });
+        failed_rx.recv().unwrap();
+
+        // The persist sleeps without the handle, and the shutdown flushes its metadata
+        let started = Instant::now();
+        spot_meta.shutdown();
+        assert!(started.elapsed() < Duration::from_millis(500), "{:?}", started.elapsed());
+        assert_eq!(load_spot_metadata(&db, 999), Some(metadata));
+        assert!(!spot_meta.dirty.load(Ordering::SeqCst));
+
+        // Woken up without a handle, the persist gives up without another attempt
+        persisting.join().unwrap();
+        assert!(failed_rx.try_recv().is_err());
     }
 }
@@ -0,0 +1,119 @@
+//! Typed decoding of the HyperCore actions carried by system transactions.
+//!
+//! HyperCore -> HyperEVM transfers are the only system transactions so far:
+//! - native HYPE is sent as plain value from `0x2222...2222`, encoded with the `s = 1` sentinel;
+//! - spot tokens are moved by an ERC-20 `transfer` on the token's linked contract, sent from the
+//!   token's system address `0x20...<spot index>`, which `s` encodes.
+
+use alloy_consensus::Transaction as _;
+use alloy_primitives::{Address, Bytes, TxKind, U256};
+use alloy_sol_types::{SolCall, sol};
+use serde::{Deserialize, Serialize};
+
+use crate::node::{primitives::TransactionSigned, spot_meta::SpotId};
+
+sol! {
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+
+/// The HyperCore action a system transaction carries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SystemTxKind {
+    /// Native HYPE transferred from HyperCore.
+    #[serde(rename_all = "camelCase")]
+    NativeTransfer { recipient: Address, amount: U256 },
+    /// Spot token transferred from HyperCore. `spot_index` is unknown for transactions that were
+    /// not signed yet.
+    #[serde(rename_all = "camelCase")]
+    SpotTransfer { token: Address, spot_index: Option<u64>, recipient: Address, amount: U256 },
+    /// A system transaction that matches no known action.
+    #[serde(rename_all = "camelCase")]
+    Unknown { to: Option<Address>, value: U256, input: Bytes },
+}
+
+impl SystemTxKind {
+    /// Classifies a system transaction from its target, value, calldata and, once it is signed,
+    /// the pseudo signature's `s` value.
+    pub fn decode(to: TxKind, value: U256, input: &Bytes, s: Option<U256>) -> Self {
+        let unknown = || Self::Unknown { to: to.to().copied(), value, input: input.clone() };
+        let TxKind::Call(to) = to else {
+            return unknown();
+        };
+
+        if input.is_empty() && s.is_none_or(|s| s == U256::ONE) {
+            return Self::NativeTransfer { recipient: to, amount: value };
+        }
+        let spot_index = match s.map(SpotId::from_s) {
+            Some(None) => return unknown(),
+            spot => spot.flatten().map(|spot| spot.index),
+        };
+        match transferCall::abi_decode(input) {
+            Ok(transferCall { to: recipient, amount }) if value.is_zero() => {
+                Self::SpotTransfer { token: to, spot_index, recipient, amount }
+            }
+            _ => unknown(),
+        }
+    }
+
+    /// Classifies `tx`, or returns `None` if it is not a system transaction.
+    pub fn from_transaction(tx: &TransactionSigned) -> Option<Self> {
+        tx.is_system_transaction()
+            .then(|| Self::decode(tx.kind(), tx.value(), tx.input(), Some(tx.signature().s())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: Address = Address::repeat_byte(0x7e);
+    const RECIPIENT: Address = Address::repeat_byte(0xaa);
+
+    fn transfer(amount: u64) -> Bytes {
+        transferCall { to: RECIPIENT, amount: U256::from(amount) }.abi_encode().into()
+    }
+
+    #[test]
+    fn decodes_native_transfer() {
+        let value = U256::from(10u64.pow(18));
+        let expected = SystemTxKind::NativeTransfer { recipient: RECIPIENT, amount: value };
+        let to = TxKind::Call(RECIPIENT);
+        assert_eq!(SystemTxKind::decode(to, value, &Bytes::new(), Some(U256::ONE)), expected);
+        assert_eq!(SystemTxKind::decode(to, value, &Bytes::new(), None), expected);
+    }
+
+    #[test]
+    fn decodes_spot_transfer() {
+        let s = SpotId { index: 150 }.to_s();
+        let kind = SystemTxKind::decode(TxKind::Call(TOKEN), U256::ZERO, &transfer(42), Some(s));
+        assert_eq!(
+            kind,
+            SystemTxKind::SpotTransfer {
+                token: TOKEN,
+                spot_index: Some(150),
+                recipient: RECIPIENT,
+                amount: U256::from(42),
+            }
+        );
+
+        // Not signed yet: the spot index is not known
+        let kind = SystemTxKind::decode(TxKind::Call(TOKEN), U256::ZERO, &transfer(42), None);
+        assert!(matches!(kind, SystemTxKind::SpotTransfer { spot_index: None, .. }));
+    }
+
+    #[test]
+    fn keeps_unknown_payloads() {
+        let input = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]);
+        let s = SpotId { index: 150 }.to_s();
+        assert_eq!(
+            SystemTxKind::decode(TxKind::Call(TOKEN), U256::ZERO, &input, Some(s)),
+            SystemTxKind::Unknown { to: Some(TOKEN), value: U256::ZERO, input }
+        );
+
+        // A transfer whose `s` is not a spot token system address
+        let kind =
+            SystemTxKind::decode(TxKind::Call(TOKEN), U256::ZERO, &transfer(1), Some(U256::ONE));
+        assert!(matches!(kind, SystemTxKind::Unknown { .. }));
+    }
+}
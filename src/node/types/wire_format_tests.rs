@@ -0,0 +1,211 @@
+//! Golden tests of the msgpack encoding of [`BlockAndReceipts`], which the S3 bucket, hl-node's
+//! block files, the sync server and `RpcBlockSource` all depend on.
+//!
+//! Each fixture in `tests/fixtures/wire` must keep decoding to the block it was recorded from,
+//! and the sync server's encoding of the canonical block must keep producing `named.rmp` byte for
+//! byte. A missing fixture fails the tests; record the fixtures with `BLESS_WIRE_FIXTURES=1`, and
+//! re-record them only for a deliberate format change, along with a bump of
+//! [`BLOCK_FORMAT_VERSION`].
+
+use super::*;
+use crate::{addons::sync_server::encode_block, node::primitives::TransactionSigned as TxSigned};
+use alloy_consensus::{BlockBody, Header, Signed, TxEip1559, TxLegacy};
+use alloy_primitives::{B64, Bloom, LogData, Signature, TxKind, U256, address, b256};
+use reth_ethereum_primitives::TransactionSigned as RethTxSigned;
+use std::path::PathBuf;
+
+const TOKEN: Address = address!("0x2000000000000000000000000000000000000001");
+const RECIPIENT: Address = address!("0x00000000000000000000000000000000000000aa");
+const PRECOMPILE: Address = address!("0x0000000000000000000000000000000000000801");
+
+/// Returns the bytes of fixture `name`, recording `encoded` first if the fixtures are being
+/// blessed.
+fn fixture(name: &str, encoded: &[u8]) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/wire").join(name);
+    if std::env::var_os("BLESS_WIRE_FIXTURES").is_some() {
+        std::fs::write(&path, encoded).unwrap();
+    }
+    std::fs::read(&path).unwrap_or_else(|err| {
+        panic!(
+            "fixture {} can't be read ({err}), record it with BLESS_WIRE_FIXTURES=1",
+            path.display()
+        )
+    })
+}
+
+fn signed(transaction: impl Into<RethTxSigned>) -> reth_compat::TransactionSigned {
+    reth_compat::TransactionSigned::from_node_tx(TxSigned::Default(transaction.into()))
+}
+
+fn signature() -> Signature {
+    Signature::new(U256::from(0x1234), U256::from(0x5678), true)
+}
+
+fn user_tx() -> reth_compat::TransactionSigned {
+    let tx = TxEip1559 {
+        chain_id: 999,
+        nonce: 7,
+        gas_limit: 21_000,
+        max_fee_per_gas: 1_000_000_000,
+        max_priority_fee_per_gas: 1,
+        to: TxKind::Call(RECIPIENT),
+        value: U256::from(1_000),
+        input: Bytes::from_static(&[0xde, 0xad]),
+        ..Default::default()
+    };
+    signed(Signed::new_unhashed(tx, signature()))
+}
+
+/// A legacy transaction predating EIP-155, which has no chain id.
+fn pre_eip155_tx() -> reth_compat::TransactionSigned {
+    let tx = TxLegacy {
+        chain_id: None,
+        nonce: 1,
+        gas_price: 1_000_000_000,
+        gas_limit: 21_000,
+        to: TxKind::Call(RECIPIENT),
+        ..Default::default()
+    };
+    signed(Signed::new_unhashed(tx, Signature::new(U256::from(1), U256::from(2), false)))
+}
+
+fn receipt(cumulative_gas_used: u64, logs: Vec<Log>) -> LegacyReceipt {
+    LegacyReceipt { tx_type: LegacyTxType::Eip1559, success: true, cumulative_gas_used, logs }
+}
+
+fn transfer_log() -> Log {
+    Log {
+        address: TOKEN,
+        data: LogData::new_unchecked(
+            vec![b256!("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")],
+            Bytes::from_static(&[0; 32]),
+        ),
+    }
+}
+
+fn block(
+    transactions: Vec<reth_compat::TransactionSigned>,
+    receipts: Vec<LegacyReceipt>,
+) -> BlockAndReceipts {
+    let header = Header {
+        parent_hash: B256::repeat_byte(0x01),
+        beneficiary: Address::ZERO,
+        state_root: B256::repeat_byte(0x02),
+        transactions_root: B256::repeat_byte(0x03),
+        receipts_root: B256::repeat_byte(0x04),
+        logs_bloom: Bloom::ZERO,
+        number: 1_000_000,
+        gas_limit: 30_000_000,
+        gas_used: 42_000,
+        timestamp: 1_700_000_000,
+        extra_data: Bytes::new(),
+        mix_hash: B256::ZERO,
+        nonce: B64::ZERO,
+        base_fee_per_gas: Some(100_000_000),
+        ..Default::default()
+    };
+    BlockAndReceipts {
+        block: EvmBlock::Reth115(reth_compat::SealedBlock {
+            header: reth_compat::SealedHeader { hash: B256::repeat_byte(0x05), header },
+            body: BlockBody { transactions, ommers: vec![], withdrawals: None },
+        }),
+        receipts,
+        system_txs: vec![],
+        read_precompile_calls: ReadPrecompileCalls::default(),
+        highest_precompile_address: None,
+    }
+}
+
+/// A block with a user transaction, a system transaction and read precompile calls.
+fn canonical_block() -> BlockAndReceipts {
+    let mut block = block(vec![user_tx()], vec![receipt(21_000, vec![])]);
+    block.system_txs = vec![SystemTx {
+        tx: reth_compat::Transaction::Legacy(TxLegacy {
+            chain_id: Some(999),
+            gas_limit: 21_000,
+            to: TxKind::Call(TOKEN),
+            input: Bytes::from_static(&[0xa9, 0x05, 0x9c, 0xbb]),
+            ..Default::default()
+        }),
+        receipt: Some(LegacyReceipt {
+            tx_type: LegacyTxType::Legacy,
+            ..receipt(21_000, vec![transfer_log()])
+        }),
+    }];
+    block.read_precompile_calls = ReadPrecompileCalls(vec![(
+        PRECOMPILE,
+        vec![
+            (
+                ReadPrecompileInput { input: Bytes::from_static(&[0x01; 36]), gas_limit: 30_000 },
+                ReadPrecompileResult::Ok {
+                    gas_used: 2_000,
+                    bytes: Bytes::from_static(&[0x02; 64]),
+                },
+            ),
+            (
+                ReadPrecompileInput { input: Bytes::from_static(&[0x03; 4]), gas_limit: 10 },
+                ReadPrecompileResult::OutOfGas,
+            ),
+        ],
+    )]);
+    block.highest_precompile_address = Some(PRECOMPILE);
+    block
+}
+
+/// Layout of blocks recorded before system transactions and read precompile calls were added.
+#[derive(Serialize)]
+struct BlockWithoutSystemTxs {
+    block: EvmBlock,
+    receipts: Vec<LegacyReceipt>,
+    highest_precompile_address: Option<Address>,
+}
+
+#[test]
+fn named_encoding_is_stable() {
+    let encoded = encode_block(&canonical_block()).unwrap();
+    assert!(
+        encoded[..] == fixture("named.rmp", &encoded)[..],
+        "the msgpack encoding of BlockAndReceipts changed, which breaks syncing between \
+         versions; if this is deliberate, bump BLOCK_FORMAT_VERSION and re-record the fixtures \
+         with BLESS_WIRE_FIXTURES=1"
+    );
+}
+
+#[test]
+fn named_maps_decode() {
+    let block = canonical_block();
+    let bytes = fixture("named.rmp", &rmp_serde::to_vec_named(&block).unwrap());
+    assert_eq!(rmp_serde::from_slice::<BlockAndReceipts>(&bytes).unwrap(), block);
+}
+
+#[test]
+fn positional_encoding_decodes() {
+    // Structs, including the signature, as arrays rather than maps
+    let block = canonical_block();
+    let bytes = fixture("positional.rmp", &rmp_serde::to_vec(&block).unwrap());
+    assert_eq!(rmp_serde::from_slice::<BlockAndReceipts>(&bytes).unwrap(), block);
+}
+
+#[test]
+fn transaction_without_chain_id_decodes() {
+    let block = block(vec![pre_eip155_tx()], vec![receipt(21_000, vec![])]);
+    let bytes = fixture("legacy_without_chain_id.rmp", &encode_block(&block).unwrap());
+    let decoded = rmp_serde::from_slice::<BlockAndReceipts>(&bytes).unwrap();
+    assert_eq!(decoded.chain_id(), None);
+    assert_eq!(decoded, block);
+}
+
+#[test]
+fn blocks_without_system_txs_and_precompile_calls_decode() {
+    let block = block(vec![user_tx()], vec![receipt(21_000, vec![transfer_log()])]);
+    let old = BlockWithoutSystemTxs {
+        block: block.block.clone(),
+        receipts: block.receipts.clone(),
+        highest_precompile_address: None,
+    };
+    let bytes = fixture("without_system_txs.rmp", &rmp_serde::to_vec_named(&old).unwrap());
+    let decoded = rmp_serde::from_slice::<BlockAndReceipts>(&bytes).unwrap();
+    assert!(decoded.system_txs.is_empty());
+    assert_eq!(decoded.read_precompile_calls, ReadPrecompileCalls::default());
+    assert_eq!(decoded, block);
+}
@@ -0,0 +1,100 @@
+//! Failures that stop the pseudo peer for good, reported to the node so that it shuts down.
+//!
+//! A node whose block source is denied access or permanently lacks the next block can't import
+//! anything anymore. Rather than leaving it running without importing, or panicking the task,
+//! the pseudo peer reports the error over a channel the node's exit future listens on.
+
+use super::sources::BlockSourceError;
+use crate::chainspec::chain_name;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::error;
+
+/// Why the pseudo peer stopped.
+#[derive(Debug, thiserror::Error)]
+pub enum PseudoPeerError {
+    /// The block source failed in a way retrying won't fix.
+    #[error("block source failed permanently at block {height}: {source}")]
+    Source {
+        height: u64,
+        #[source]
+        source: BlockSourceError,
+    },
+    /// The block source serves blocks of another chain than the node's.
+    #[error(
+        "block source serves {}, but the node runs {}; check the block source configuration",
+        chain_name(*found),
+        chain_name(*expected)
+    )]
+    ChainMismatch { expected: u64, found: u64 },
+    /// The pseudo peer itself failed, e.g. its network couldn't be started.
+    #[error("pseudo peer stopped: {0}")]
+    Stopped(eyre::Report),
+}
+
+impl PseudoPeerError {
+    /// Process exit code for the error, following `sysexits.h`: `EX_UNAVAILABLE` for the block
+    /// source, `EX_CONFIG` for a source of another chain and `EX_SOFTWARE` for the pseudo peer
+    /// itself.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Source { .. } => 69,
+            Self::ChainMismatch { .. } => 78,
+            Self::Stopped(_) => 70,
+        }
+    }
+
+    /// Fails with [`Self::ChainMismatch`] unless `found` is the `expected` chain.
+    pub fn ensure_chain(expected: u64, found: u64) -> Result<(), Self> {
+        if expected == found { Ok(()) } else { Err(Self::ChainMismatch { expected, found }) }
+    }
+}
+
+/// Channel of the fatal errors of the pseudo peer, shared by the pseudo peer reporting them and
+/// the node waiting on them.
+#[derive(Debug, Clone)]
+pub struct FatalErrors {
+    tx: mpsc::UnboundedSender<PseudoPeerError>,
+    rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<PseudoPeerError>>>>,
+}
+
+impl Default for FatalErrors {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self { tx, rx: Arc::new(Mutex::new(Some(rx))) }
+    }
+}
+
+impl FatalErrors {
+    /// Logs `err` and reports it to the node.
+    pub fn report(&self, err: PseudoPeerError) {
+        error!(target: "reth::cli", %err, "Pseudo peer stopped, shutting down the node");
+        // Nobody listening means the node is shutting down already
+        let _ = self.tx.send(err);
+    }
+
+    /// Receiver of the reported errors, for the single task waiting on them.
+    pub fn take_receiver(&self) -> Option<mpsc::UnboundedReceiver<PseudoPeerError>> {
+        self.rx.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_errors_to_the_receiver() {
+        let errors = FatalErrors::default();
+        let mut rx = errors.take_receiver().unwrap();
+        assert!(errors.take_receiver().is_none());
+
+        errors.clone().report(PseudoPeerError::Source {
+            height: 7,
+            source: BlockSourceError::fatal("access denied"),
+        });
+        let err = rx.recv().await.unwrap();
+        assert_eq!(err.exit_code(), 69);
+        assert_eq!(err.to_string(), "block source failed permanently at block 7: access denied");
+    }
+}
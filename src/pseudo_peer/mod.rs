@@ -5,6 +5,7 @@
 
 pub mod cli;
 pub mod config;
+pub mod fatal;
 pub mod network;
 pub mod service;
 pub mod sources;
@@ -16,6 +17,7 @@ use tracing::{error, info};
 
 pub use cli::*;
 pub use config::*;
+pub use fatal::*;
 pub use network::*;
 pub use service::*;
 pub use sources::*;
@@ -38,6 +40,7 @@ pub async fn start_pseudo_peer(
     destination_peer: String,
     block_source: BlockSourceBoxed,
     debug_cutoff_height: Option<u64>,
+    context: PseudoPeerContext,
 ) -> eyre::Result<()> {
     let blockhash_cache = new_blockhash_cache();
 
@@ -48,6 +51,7 @@ pub async fn start_pseudo_peer(
         block_source.clone(),
         blockhash_cache.clone(),
         debug_cutoff_height,
+        context.clone(),
     )
     .await?;
 
@@ -60,9 +64,10 @@ pub async fn start_pseudo_peer(
 
     let network_handle = network.handle().clone();
     let mut network_events = network_handle.event_listener();
-    info!("Starting network manager...");
+    info!(enode = %network_handle.local_node_record(), "Starting the pseudo peer");
 
-    let mut service = PseudoPeer::new(chain_spec, block_source, blockhash_cache.clone());
+    let mut service =
+        PseudoPeer::new(chain_spec, block_source, blockhash_cache.clone(), context.spot_meta);
     tokio::spawn(network);
     let mut first = true;
 
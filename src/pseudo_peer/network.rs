@@ -1,5 +1,6 @@
-use super::service::{BlockHashCache, BlockPoller};
+use super::service::{BlockHashCache, BlockPoller, DEFAULT_PUSH_SIZE_LIMIT, PseudoPeerContext};
 use crate::{HlPrimitives, chainspec::HlChainSpec, node::network::HlNetworkPrimitives};
+use reth_cli_util::get_secret_key;
 use reth_network::{
     NetworkConfig, NetworkManager, PeersConfig,
     config::{SecretKey, rng_secret_key},
@@ -8,11 +9,24 @@ use reth_network_peers::TrustedPeer;
 use reth_provider::test_utils::NoopProvider;
 use std::{
     net::{Ipv4Addr, SocketAddr},
+    path::Path,
     str::FromStr,
     sync::Arc,
 };
 use tokio::sync::mpsc;
 
+/// File under the node's datadir holding the secret key of the pseudo peer, next to reth's own
+/// `discovery-secret`.
+pub const PSEUDO_PEER_KEY_FILE: &str = "pseudo-peer-secret";
+
+/// Reads the pseudo peer's secret key from `path`, generating and writing one if the file doesn't
+/// exist yet, so that the pseudo peer keeps its enode across restarts.
+pub fn load_pseudo_peer_key(path: &Path) -> eyre::Result<SecretKey> {
+    get_secret_key(path).map_err(|err| {
+        eyre::eyre!("failed to load the pseudo peer key at {}: {err}", path.display())
+    })
+}
+
 pub struct NetworkBuilder {
     secret: SecretKey,
     peer_config: PeersConfig,
@@ -21,6 +35,8 @@ pub struct NetworkBuilder {
     listener_port: u16,
     chain_spec: HlChainSpec,
     debug_cutoff_height: Option<u64>,
+    push_size_limit: usize,
+    context: PseudoPeerContext,
 }
 
 impl Default for NetworkBuilder {
@@ -33,11 +49,18 @@ impl Default for NetworkBuilder {
             listener_port: 0,
             chain_spec: HlChainSpec::default(),
             debug_cutoff_height: None,
+            push_size_limit: DEFAULT_PUSH_SIZE_LIMIT,
+            context: PseudoPeerContext::default(),
         }
     }
 }
 
 impl NetworkBuilder {
+    pub fn with_secret_key(mut self, secret: SecretKey) -> Self {
+        self.secret = secret;
+        self
+    }
+
     pub fn with_boot_nodes(mut self, boot_nodes: Vec<TrustedPeer>) -> Self {
         self.boot_nodes = boot_nodes;
         self
@@ -53,6 +76,16 @@ impl NetworkBuilder {
         self
     }
 
+    pub fn with_push_size_limit(mut self, push_size_limit: usize) -> Self {
+        self.push_size_limit = push_size_limit;
+        self
+    }
+
+    pub fn with_context(mut self, context: PseudoPeerContext) -> Self {
+        self.context = context;
+        self
+    }
+
     pub async fn build<BS>(
         self,
         block_source: Arc<Box<dyn super::sources::BlockSource>>,
@@ -70,7 +103,9 @@ impl NetworkBuilder {
             block_source,
             blockhash_cache,
             self.debug_cutoff_height,
+            self.context,
         );
+        let block_poller = block_poller.with_push_size_limit(self.push_size_limit);
         let config = builder.block_import(Box::new(block_poller)).build(Arc::new(NoopProvider::<
             HlChainSpec,
             HlPrimitives,
@@ -89,11 +124,42 @@ pub async fn create_network_manager<BS>(
     block_source: Arc<Box<dyn super::sources::BlockSource>>,
     blockhash_cache: BlockHashCache,
     debug_cutoff_height: Option<u64>,
+    context: PseudoPeerContext,
 ) -> eyre::Result<(NetworkManager<HlNetworkPrimitives>, mpsc::Sender<()>)> {
-    NetworkBuilder::default()
+    let mut builder = NetworkBuilder::default();
+    if let Some(secret_key) = context.secret_key {
+        builder = builder.with_secret_key(secret_key);
+    }
+    builder
         .with_boot_nodes(vec![TrustedPeer::from_str(&destination_peer).unwrap()])
         .with_chain_spec(chain_spec)
         .with_debug_cutoff_height(debug_cutoff_height)
+        .with_context(context)
         .build::<BS>(block_source, blockhash_cache)
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pseudo_peer::{BlockSourceBoxed, LocalBlockSource, new_blockhash_cache};
+
+    #[tokio::test]
+    async fn restarted_pseudo_peer_keeps_its_node_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_path = dir.path().join(PSEUDO_PEER_KEY_FILE);
+        let mut node_ids = Vec::new();
+        for _ in 0..2 {
+            let secret_key = load_pseudo_peer_key(&key_path).unwrap();
+            let source: BlockSourceBoxed = Arc::new(Box::new(LocalBlockSource::new(dir.path())));
+            let (network, _start_tx) = NetworkBuilder::default()
+                .with_secret_key(secret_key)
+                .build::<BlockSourceBoxed>(source, new_blockhash_cache())
+                .await
+                .unwrap();
+            node_ids.push(*network.peer_id());
+        }
+        assert!(key_path.exists());
+        assert_eq!(node_ids[0], node_ids[1]);
+    }
+}
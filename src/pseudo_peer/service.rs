@@ -1,20 +1,29 @@
-use super::{sources::BlockSource, utils::LruBiMap};
+use super::{
+    fatal::{FatalErrors, PseudoPeerError},
+    sources::{BlockSource, BlockSourceError, PollingConfig, PollingSchedule},
+    utils::LruBiMap,
+};
 use crate::{
+    HlBlock,
     chainspec::HlChainSpec,
     node::{
-        network::{HlNetworkPrimitives, HlNewBlock},
-        types::BlockAndReceipts,
+        network::{
+            HlNetworkPrimitives, HlNewBlock,
+            block_trace::{self, BlockSpans, TARGET as BLOCK_TRACE},
+        },
+        storage::checkpoint::BlockSourceProgress,
+        types::{BlockAndReceipts, SpotMetaContext},
     },
 };
 use alloy_eips::HashOrNumber;
-use alloy_primitives::{B256, U128};
+use alloy_primitives::B256;
+use alloy_rlp::Encodable;
 use alloy_rpc_types::Block;
 use parking_lot::RwLock;
 use rayon::prelude::*;
-use reth_eth_wire::{
-    BlockBodies, BlockHeaders, GetBlockBodies, GetBlockHeaders, HeadersDirection, NewBlock,
-};
+use reth_eth_wire::{BlockBodies, BlockHeaders, GetBlockBodies, GetBlockHeaders, HeadersDirection};
 use reth_network::{
+    config::SecretKey,
     eth_requests::IncomingEthRequest,
     import::{BlockImport, BlockImportEvent, BlockValidation, NewBlockEvent},
     message::NewBlockMessage,
@@ -25,25 +34,82 @@ use std::{
     pin::Pin,
     sync::{Arc, Mutex},
     task::{Context, Poll},
+    time::Duration,
 };
-use tokio::{sync::mpsc, task::JoinHandle};
-use tracing::{debug, info};
+use tokio::{sync::mpsc, task::JoinHandle, time::Instant};
+use tracing::{Instrument, Span, debug, error, info, trace_span, warn};
 
 /// A cache of block hashes to block numbers.
 pub type BlockHashCache = Arc<RwLock<LruBiMap<B256, u64>>>;
 const BLOCKHASH_CACHE_LIMIT: u32 = 1000000;
 
+/// Blocks whose `NewBlock` encoding exceeds this many bytes are announced by hash only, and
+/// fetched by reth through `GetBlockHeaders`/`GetBlockBodies`.
+pub const DEFAULT_PUSH_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// How long the poller waits before retrying a block that failed to convert.
+const CONVERSION_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long the poller waits before retrying a block the source failed to fetch, other than
+/// one that isn't available yet, which is polled for at the source's polling interval.
+const FETCH_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the source may keep failing to serve the next block, missing or corrupt, before the
+/// poller gives up on it, by default.
+pub const DEFAULT_MISSING_BLOCK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 pub fn new_blockhash_cache() -> BlockHashCache {
     Arc::new(RwLock::new(LruBiMap::new(BLOCKHASH_CACHE_LIMIT)))
 }
 
+/// State of the node the pseudo peer feeds, kept per node so that several nodes can run in one
+/// process.
+#[derive(Debug, Clone)]
+pub struct PseudoPeerContext {
+    /// Derives the senders of system transactions when converting blocks.
+    pub spot_meta: SpotMetaContext,
+    /// Records the heights served, for the block source checkpoint written with stored blocks.
+    pub progress: Arc<BlockSourceProgress>,
+    /// Reports the failures the poller can't recover from, to shut the node down.
+    pub fatal_errors: FatalErrors,
+    /// Jitter and idle backoff of the polls for the next block.
+    pub polling: PollingConfig,
+    /// Where the spans of the announced blocks are handed over to the import service of the node,
+    /// see [`block_trace`].
+    pub block_spans: BlockSpans,
+    /// devp2p identity of the pseudo peer, see [`load_pseudo_peer_key`]. A new one is generated
+    /// on every start when unset.
+    ///
+    /// [`load_pseudo_peer_key`]: super::network::load_pseudo_peer_key
+    pub secret_key: Option<SecretKey>,
+    /// How long the source may keep failing to serve the next block, missing or corrupt, before
+    /// the poller reports it to [`Self::fatal_errors`].
+    pub missing_block_timeout: Duration,
+}
+
+impl Default for PseudoPeerContext {
+    fn default() -> Self {
+        Self {
+            spot_meta: Default::default(),
+            progress: Default::default(),
+            fatal_errors: Default::default(),
+            polling: Default::default(),
+            block_spans: Default::default(),
+            secret_key: None,
+            missing_block_timeout: DEFAULT_MISSING_BLOCK_TIMEOUT,
+        }
+    }
+}
+
 /// A block poller that polls blocks from `BlockSource` and sends them to the `block_tx`
 #[derive(Debug)]
 pub struct BlockPoller {
-    chain_id: u64,
-    block_rx: mpsc::Receiver<(u64, BlockAndReceipts)>,
+    /// Blocks with their number and the span of their trace, see [`block_trace`].
+    block_rx: mpsc::Receiver<(u64, HlBlock, Span)>,
     task: JoinHandle<eyre::Result<()>>,
     blockhash_cache: BlockHashCache,
+    push_size_limit: usize,
+    block_spans: BlockSpans,
 }
 
 impl BlockPoller {
@@ -52,12 +118,34 @@ impl BlockPoller {
         block_source: BS,
         blockhash_cache: BlockHashCache,
         debug_cutoff_height: Option<u64>,
+        context: PseudoPeerContext,
     ) -> (Self, mpsc::Sender<()>) {
         let block_source = Arc::new(block_source);
         let (start_tx, start_rx) = mpsc::channel(1);
         let (block_tx, block_rx) = mpsc::channel(100);
-        let task = tokio::spawn(Self::task(start_rx, block_source, block_tx, debug_cutoff_height));
-        (Self { chain_id, block_rx, task, blockhash_cache: blockhash_cache.clone() }, start_tx)
+        let block_spans = context.block_spans.clone();
+        let task = tokio::spawn(Self::task(
+            chain_id,
+            start_rx,
+            block_source,
+            block_tx,
+            debug_cutoff_height,
+            context,
+        ));
+        let poller = Self {
+            block_rx,
+            task,
+            blockhash_cache: blockhash_cache.clone(),
+            push_size_limit: DEFAULT_PUSH_SIZE_LIMIT,
+            block_spans,
+        };
+        (poller, start_tx)
+    }
+
+    /// Sets the encoded size above which blocks are announced by hash instead of pushed.
+    pub fn with_push_size_limit(mut self, push_size_limit: usize) -> Self {
+        self.push_size_limit = push_size_limit;
+        self
     }
 
     #[allow(unused)]
@@ -66,19 +154,37 @@ impl BlockPoller {
     }
 
     async fn task<BS: BlockSource>(
+        chain_id: u64,
         mut start_rx: mpsc::Receiver<()>,
         block_source: Arc<BS>,
-        block_tx: mpsc::Sender<(u64, BlockAndReceipts)>,
+        block_tx: mpsc::Sender<(u64, HlBlock, Span)>,
         debug_cutoff_height: Option<u64>,
+        context: PseudoPeerContext,
     ) -> eyre::Result<()> {
         start_rx.recv().await.ok_or(eyre::eyre!("Failed to receive start signal"))?;
         info!("Starting block poller");
 
-        let polling_interval = block_source.polling_interval();
+        // Sources reporting their chain are checked before any block is fetched, the others
+        // once a block tells its chain
+        let mut chain_verified = false;
+        if let Some(found) = block_source.chain_id().await {
+            if let Err(err) = PseudoPeerError::ensure_chain(chain_id, found) {
+                context.fatal_errors.report(err);
+                return Ok(());
+            }
+            chain_verified = true;
+        }
+
+        let mut polling = PollingSchedule::new(block_source.polling_interval(), context.polling);
         let mut next_block_number = block_source
             .find_latest_block_number()
             .await
             .ok_or(eyre::eyre!("Failed to find latest block number"))?;
+        // When the source started failing to serve the next block, to give up on a gap that
+        // never fills or a block that stays corrupt
+        let mut missing_since = None;
+        // Span of the next block, kept across the polls for it
+        let mut block_span: Option<(u64, Span)> = None;
 
         loop {
             if let Some(debug_cutoff_height) = debug_cutoff_height &&
@@ -87,12 +193,76 @@ impl BlockPoller {
                 next_block_number = debug_cutoff_height;
             }
 
-            match block_source.collect_block(next_block_number).await {
+            let height = next_block_number;
+            let span = match block_span.take() {
+                Some((number, span)) if number == height => span,
+                _ => block_trace::block_span(height),
+            };
+            block_span = Some((height, span.clone()));
+            let fetch = trace_span!(target: BLOCK_TRACE, parent: &span, "fetch");
+            let block = match block_source.collect_block(height).instrument(fetch).await {
                 Ok(block) => {
-                    block_tx.send((next_block_number, block)).await?;
+                    missing_since = None;
+                    polling.hit();
+                    block
+                }
+                // The tip: poll until the block is produced
+                Err(BlockSourceError::NotFoundYet { .. }) => {
+                    polling.wait().await;
+                    continue;
+                }
+                Err(
+                    err @ (BlockSourceError::Missing { .. } | BlockSourceError::Corrupt { .. }),
+                ) => {
+                    let since = *missing_since.get_or_insert_with(Instant::now);
+                    if since.elapsed() >= context.missing_block_timeout {
+                        let err = PseudoPeerError::Source { height, source: err };
+                        context.fatal_errors.report(err);
+                        return Ok(());
+                    }
+                    warn!(height, %err, "Block source cannot serve the next block, retrying");
+                    tokio::time::sleep(FETCH_RETRY_INTERVAL).await;
+                    continue;
+                }
+                Err(err) if err.is_retryable() => {
+                    debug!(height, %err, "Failed to fetch block, retrying");
+                    tokio::time::sleep(FETCH_RETRY_INTERVAL).await;
+                    continue;
+                }
+                Err(err) => {
+                    context.fatal_errors.report(PseudoPeerError::Source { height, source: err });
+                    return Ok(());
+                }
+            };
+            if !chain_verified && let Some(found) = block.chain_id() {
+                if let Err(err) = PseudoPeerError::ensure_chain(chain_id, found) {
+                    context.fatal_errors.report(err);
+                    return Ok(());
+                }
+                chain_verified = true;
+            }
+            let spot_meta = context.spot_meta.clone();
+            spot_meta.resolve(block.spot_tokens(), chain_id).await;
+            let convert_span = trace_span!(target: BLOCK_TRACE, parent: &span, "convert");
+            let convert = move || {
+                let _span = convert_span.entered();
+                block.to_reth_block(&spot_meta)
+            };
+            match tokio::task::spawn_blocking(convert).await? {
+                Ok(block) => {
+                    let send = trace_span!(target: BLOCK_TRACE, parent: &span, "send");
+                    block_tx.send((next_block_number, block, span)).instrument(send).await?;
+                    context.progress.record_served(next_block_number);
                     next_block_number += 1;
                 }
-                Err(_) => tokio::time::sleep(polling_interval).await,
+                Err(err) => {
+                    error!(
+                        height = next_block_number,
+                        %err,
+                        "Failed to convert block, pausing import until it can be converted"
+                    );
+                    tokio::time::sleep(CONVERSION_RETRY_INTERVAL).await;
+                }
             }
         }
     }
@@ -102,18 +272,25 @@ impl BlockImport<HlNewBlock> for BlockPoller {
     fn poll(&mut self, _cx: &mut Context<'_>) -> Poll<BlockImportEvent<HlNewBlock>> {
         debug!("(receiver) Polling");
         match Pin::new(&mut self.block_rx).poll_recv(_cx) {
-            Poll::Ready(Some((number, block))) => {
+            Poll::Ready(Some((number, reth_block, span))) => {
+                let _span = trace_span!(target: BLOCK_TRACE, parent: &span, "announce").entered();
                 debug!("Polled block: {}", number);
-                let reth_block = block.to_reth_block(self.chain_id);
                 let hash = reth_block.header.hash_slow();
+                self.block_spans.hand_over(hash, &span);
                 self.blockhash_cache.write().insert(hash, number);
-                let td = U128::from(reth_block.header.difficulty);
-                Poll::Ready(BlockImportEvent::Announcement(BlockValidation::ValidHeader {
-                    block: NewBlockMessage {
-                        block: HlNewBlock(NewBlock { block: reth_block, td }).into(),
-                        hash,
-                    },
-                }))
+                let new_block = HlNewBlock::new(reth_block);
+                let size = new_block.length();
+                let block = NewBlockMessage { block: new_block.into(), hash };
+                // reth pushes the full block for `ValidHeader` announcements and only sends
+                // `NewBlockHashes` for `ValidBlock` ones; large blocks are then served from the
+                // block source on request instead of being copied onto the wire up front.
+                let validation = if size > self.push_size_limit {
+                    debug!(number, size, "Announcing block by hash");
+                    BlockValidation::ValidBlock { block }
+                } else {
+                    BlockValidation::ValidHeader { block }
+                };
+                Poll::Ready(BlockImportEvent::Announcement(validation))
             }
             Poll::Ready(None) | Poll::Pending => Poll::Pending,
         }
@@ -127,6 +304,7 @@ pub struct PseudoPeer<BS: BlockSource> {
     chain_spec: Arc<HlChainSpec>,
     block_source: BS,
     blockhash_cache: BlockHashCache,
+    spot_meta: SpotMetaContext,
     warm_cache_size: u64,
     if_hit_then_warm_around: Arc<Mutex<HashSet<u64>>>,
 
@@ -136,15 +314,19 @@ pub struct PseudoPeer<BS: BlockSource> {
 }
 
 impl<BS: BlockSource> PseudoPeer<BS> {
+    /// Creates a pseudo peer deriving system transaction senders from `spot_meta` when serving
+    /// blocks.
     pub fn new(
         chain_spec: Arc<HlChainSpec>,
         block_source: BS,
         blockhash_cache: BlockHashCache,
+        spot_meta: SpotMetaContext,
     ) -> Self {
         Self {
             chain_spec,
             block_source,
             blockhash_cache,
+            spot_meta,
             warm_cache_size: 1000, // reth default chunk size for GetBlockBodies
             if_hit_then_warm_around: Arc::new(Mutex::new(HashSet::new())),
             known_latest_block_number: 0,
@@ -156,7 +338,7 @@ impl<BS: BlockSource> PseudoPeer<BS> {
         block_numbers: impl IntoIterator<Item = u64>,
     ) -> eyre::Result<Vec<BlockAndReceipts>> {
         let block_numbers = block_numbers.into_iter().collect::<Vec<_>>();
-        self.block_source.collect_blocks(block_numbers).await
+        Ok(self.block_source.collect_blocks(block_numbers).await?)
     }
 
     pub async fn process_eth_request(
@@ -164,6 +346,7 @@ impl<BS: BlockSource> PseudoPeer<BS> {
         eth_req: IncomingEthRequest<HlNetworkPrimitives>,
     ) -> eyre::Result<()> {
         let chain_id = self.chain_spec.inner.chain().id();
+        let spot_meta = self.spot_meta.clone();
         match eth_req {
             IncomingEthRequest::GetBlockHeaders {
                 peer_id: _,
@@ -178,15 +361,19 @@ impl<BS: BlockSource> PseudoPeer<BS> {
                     HashOrNumber::Number(number) => number,
                 };
 
-                let block_headers = match direction {
+                let blocks = match direction {
                     HeadersDirection::Rising => self.collect_blocks(number..number + limit).await,
                     HeadersDirection::Falling => {
                         self.collect_blocks((number + 1 - limit..number + 1).rev()).await
                     }
-                }?
-                .into_par_iter()
-                .map(|block| block.to_reth_block(chain_id).header.clone())
-                .collect::<Vec<_>>();
+                }?;
+                spot_meta
+                    .resolve(blocks.iter().flat_map(|block| block.spot_tokens()), chain_id)
+                    .await;
+                let block_headers = blocks
+                    .into_par_iter()
+                    .map(|block| block.to_reth_block(&spot_meta).map(|block| block.header))
+                    .collect::<eyre::Result<Vec<_>>>()?;
 
                 let _ = response.send(Ok(BlockHeaders(block_headers)));
             }
@@ -199,12 +386,14 @@ impl<BS: BlockSource> PseudoPeer<BS> {
                     numbers.push(self.hash_to_block_number(hash).await);
                 }
 
-                let block_bodies = self
-                    .collect_blocks(numbers)
-                    .await?
+                let blocks = self.collect_blocks(numbers).await?;
+                spot_meta
+                    .resolve(blocks.iter().flat_map(|block| block.spot_tokens()), chain_id)
+                    .await;
+                let block_bodies = blocks
                     .into_iter()
-                    .map(|block| block.to_reth_block(chain_id).body)
-                    .collect::<Vec<_>>();
+                    .map(|block| block.to_reth_block(&spot_meta).map(|block| block.body))
+                    .collect::<eyre::Result<Vec<_>>>()?;
 
                 let _ = response.send(Ok(BlockBodies(block_bodies)));
             }
@@ -370,3 +559,237 @@ impl<BS: BlockSource> PseudoPeer<BS> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HlBlock,
+        node::{
+            primitives::TransactionSigned,
+            types::{EvmBlock, reth_compat},
+        },
+        pseudo_peer::sources::BlockSourceResult,
+        test_utils::header_block,
+    };
+    use alloy_consensus::{Header, Signed, TxLegacy};
+    use alloy_primitives::{Bytes, Signature};
+    use futures::{FutureExt, future::BoxFuture};
+    use std::{collections::BTreeMap, time::Duration};
+    use tokio::sync::oneshot;
+
+    const PUSH_SIZE_LIMIT: usize = 1024;
+
+    /// Serves blocks from memory, reporting the lowest one as the latest so that the poller
+    /// walks through all of them.
+    #[derive(Debug)]
+    struct MemoryBlockSource(BTreeMap<u64, BlockAndReceipts>);
+
+    impl BlockSource for MemoryBlockSource {
+        fn collect_block(
+            &self,
+            height: u64,
+        ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+            let block = self.0.get(&height).cloned();
+            async move { block.ok_or(BlockSourceError::NotFoundYet { height }) }.boxed()
+        }
+
+        fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+            let first = self.0.keys().next().copied();
+            async move { first }.boxed()
+        }
+
+        fn recommended_chunk_size(&self) -> u64 {
+            10
+        }
+    }
+
+    fn block(number: u64, extra_data_len: usize) -> BlockAndReceipts {
+        let extra_data = Bytes::from(vec![0; extra_data_len]);
+        header_block(Header { number, extra_data, ..Default::default() })
+    }
+
+    /// Block `number` with a transaction signed for `chain_id`.
+    fn block_of_chain(number: u64, chain_id: u64) -> BlockAndReceipts {
+        let tx = TxLegacy { chain_id: Some(chain_id), ..Default::default() };
+        let tx = Signed::new_unhashed(tx, Signature::test_signature());
+        let tx = TransactionSigned::Default(tx.into());
+        let tx = reth_compat::TransactionSigned::from_node_tx(tx);
+        let mut block = block(number, 0);
+        let EvmBlock::Reth115(sealed) = &mut block.block;
+        sealed.body.transactions.push(tx);
+        block
+    }
+
+    /// Serves block 1, then fails every fetch of a later block with its error.
+    #[derive(Debug)]
+    struct StuckBlockSource(fn(u64) -> BlockSourceError);
+
+    impl BlockSource for StuckBlockSource {
+        fn collect_block(
+            &self,
+            height: u64,
+        ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+            let result = if height == 1 { Ok(block(1, 0)) } else { Err((self.0)(height)) };
+            async move { result }.boxed()
+        }
+
+        fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+            async move { Some(1) }.boxed()
+        }
+
+        fn recommended_chunk_size(&self) -> u64 {
+            10
+        }
+    }
+
+    /// Block 1 fits in a push, block 2 is over the limit.
+    fn block_source() -> Arc<MemoryBlockSource> {
+        let blocks = [block(1, 0), block(2, 4 * PUSH_SIZE_LIMIT)];
+        Arc::new(MemoryBlockSource(blocks.into_iter().map(|b| (b.number(), b)).collect()))
+    }
+
+    async fn next_announcement(poller: &mut BlockPoller) -> BlockValidation<HlNewBlock> {
+        let event = std::future::poll_fn(|cx| poller.poll(cx));
+        match tokio::time::timeout(Duration::from_secs(5), event).await.unwrap() {
+            BlockImportEvent::Announcement(validation) => validation,
+            BlockImportEvent::Outcome(_) => panic!("expected an announcement"),
+        }
+    }
+
+    #[tokio::test]
+    async fn pushes_small_blocks_and_announces_large_ones_by_hash() {
+        let chain_spec = Arc::new(HlChainSpec::default());
+        let chain_id = chain_spec.inner.chain().id();
+        let blockhash_cache = new_blockhash_cache();
+        let block_source = block_source();
+        let (poller, start_tx) = BlockPoller::new_suspended(
+            chain_id,
+            block_source.clone(),
+            blockhash_cache.clone(),
+            None,
+            PseudoPeerContext::default(),
+        );
+        let mut poller = poller.with_push_size_limit(PUSH_SIZE_LIMIT);
+        start_tx.send(()).await.unwrap();
+
+        let BlockValidation::ValidHeader { block } = next_announcement(&mut poller).await else {
+            panic!("small block should be pushed");
+        };
+        assert_eq!(block.block.0.block.header.number, 1);
+
+        let BlockValidation::ValidBlock { block } = next_announcement(&mut poller).await else {
+            panic!("large block should be announced by hash");
+        };
+        assert_eq!(block.block.0.block.header.number, 2);
+
+        // reth then fetches the announced block from the pseudo peer
+        let mut peer =
+            PseudoPeer::new(chain_spec, block_source, blockhash_cache, SpotMetaContext::default());
+        let (response, headers) = oneshot::channel();
+        let request = GetBlockHeaders {
+            start_block: block.hash.into(),
+            limit: 1,
+            skip: 0,
+            direction: HeadersDirection::Rising,
+        };
+        let peer_id = PeerId::random();
+        peer.process_eth_request(IncomingEthRequest::GetBlockHeaders { peer_id, request, response })
+            .await
+            .unwrap();
+        let BlockHeaders(mut headers) = headers.await.unwrap().unwrap();
+
+        let (response, bodies) = oneshot::channel();
+        let request = GetBlockBodies(vec![block.hash]);
+        peer.process_eth_request(IncomingEthRequest::GetBlockBodies { peer_id, request, response })
+            .await
+            .unwrap();
+        let BlockBodies(mut bodies) = bodies.await.unwrap().unwrap();
+
+        let fetched = HlBlock { header: headers.pop().unwrap(), body: bodies.pop().unwrap() };
+        assert_eq!(fetched, block.block.0.block);
+    }
+
+    #[tokio::test]
+    async fn refuses_blocks_of_another_chain() {
+        let chain_id = HlChainSpec::default().inner.chain().id();
+        let start = |blocks: Vec<BlockAndReceipts>| {
+            let context = PseudoPeerContext::default();
+            let fatal_rx = context.fatal_errors.take_receiver().unwrap();
+            let source = MemoryBlockSource(blocks.into_iter().map(|b| (b.number(), b)).collect());
+            let (poller, start_tx) =
+                BlockPoller::new_suspended(chain_id, source, new_blockhash_cache(), None, context);
+            (poller, start_tx, fatal_rx)
+        };
+
+        let (mut poller, start_tx, mut fatal_rx) = start(vec![block_of_chain(1, chain_id)]);
+        start_tx.send(()).await.unwrap();
+        next_announcement(&mut poller).await;
+        assert!(fatal_rx.try_recv().is_err());
+
+        // A block without transactions doesn't tell its chain, the next one does
+        let (_poller, start_tx, mut fatal_rx) =
+            start(vec![block(1, 0), block_of_chain(2, chain_id + 1)]);
+        start_tx.send(()).await.unwrap();
+        let err =
+            tokio::time::timeout(Duration::from_secs(5), fatal_rx.recv()).await.unwrap().unwrap();
+        assert!(
+            matches!(err, PseudoPeerError::ChainMismatch { expected, found }
+                if expected == chain_id && found == chain_id + 1),
+            "{err}"
+        );
+        assert_eq!(err.exit_code(), 78);
+    }
+
+    /// Polls a [`StuckBlockSource`] failing with `error` under a timeout of a minute, returning
+    /// the error the poller reports and how long after its first failure.
+    async fn stuck_block_report(error: fn(u64) -> BlockSourceError) -> (PseudoPeerError, Duration) {
+        let context = PseudoPeerContext {
+            missing_block_timeout: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let mut fatal_rx = context.fatal_errors.take_receiver().unwrap();
+        let chain_id = HlChainSpec::default().inner.chain().id();
+        let (mut poller, start_tx) = BlockPoller::new_suspended(
+            chain_id,
+            StuckBlockSource(error),
+            new_blockhash_cache(),
+            None,
+            context,
+        );
+        start_tx.send(()).await.unwrap();
+        next_announcement(&mut poller).await;
+
+        let stuck_since = Instant::now();
+        let err = fatal_rx.recv().await.unwrap();
+        (err, stuck_since.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_on_a_missing_block_after_the_timeout() {
+        let (err, elapsed) =
+            stuck_block_report(|height| BlockSourceError::Missing { height }).await;
+        assert!(
+            matches!(
+                err,
+                PseudoPeerError::Source { height: 2, source: BlockSourceError::Missing { .. } }
+            ),
+            "{err}"
+        );
+        assert!((60..62).contains(&elapsed.as_secs()), "{elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_on_a_corrupt_block_after_the_timeout() {
+        let (err, elapsed) =
+            stuck_block_report(|height| BlockSourceError::corrupt(height, "truncated")).await;
+        assert!(
+            matches!(
+                err,
+                PseudoPeerError::Source { height: 2, source: BlockSourceError::Corrupt { .. } }
+            ),
+            "{err}"
+        );
+        assert!((60..62).contains(&elapsed.as_secs()), "{elapsed:?}");
+    }
+}
@@ -0,0 +1,268 @@
+use super::{
+    BlockSource, BlockSourceBoxed, BlockSourceError, BlockSourceMetrics, BlockSourceResult,
+    PartialBlocksError,
+};
+use crate::node::types::BlockAndReceipts;
+use futures::{FutureExt, future::BoxFuture};
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// Consecutive successful chunks before the chunk size grows.
+const GROW_AFTER: u32 = 4;
+/// Successful chunks after which a size that failed before is tried again.
+const CEILING_RESET_AFTER: u32 = 1024;
+/// A chunk whose per-block latency exceeds the baseline by this factor counts as a regression.
+const LATENCY_REGRESSION_FACTOR: u32 = 3;
+/// Chunks faster than this are never considered a latency regression, as timing noise dominates.
+const MIN_REGRESSION_LATENCY: Duration = Duration::from_millis(50);
+
+/// Additive-increase, multiplicative-decrease controller of the chunk size.
+///
+/// Grows by an eighth after [`GROW_AFTER`] successful chunks in a row, and halves on errors or
+/// when the per-block latency regresses. A size that failed caps growth until enough chunks
+/// succeed, so that the controller settles below the size at which the backend throttles.
+#[derive(Debug)]
+struct ChunkSizeController {
+    current: u64,
+    bounds: RangeInclusive<u64>,
+    successes: u32,
+    /// Smallest size that failed recently; growth stays below it.
+    ceiling: Option<u64>,
+    /// Moving average of the per-block latency of successful chunks.
+    baseline: Option<Duration>,
+}
+
+impl ChunkSizeController {
+    fn new(initial: u64, bounds: RangeInclusive<u64>) -> Self {
+        let current = initial.clamp(*bounds.start(), *bounds.end());
+        Self { current, bounds, successes: 0, ceiling: None, baseline: None }
+    }
+
+    fn on_success(&mut self, blocks: usize, elapsed: Duration) {
+        let per_block = elapsed / blocks.max(1) as u32;
+        if let Some(baseline) = self.baseline &&
+            elapsed >= MIN_REGRESSION_LATENCY &&
+            per_block > baseline * LATENCY_REGRESSION_FACTOR
+        {
+            debug!(?per_block, ?baseline, "Block source latency regressed");
+            // Reset, so that a slower backend becomes the new normal after shrinking
+            self.baseline = None;
+            self.shrink();
+            return;
+        }
+        self.baseline = Some(match self.baseline {
+            Some(baseline) => (baseline * 7 + per_block) / 8,
+            None => per_block,
+        });
+
+        self.successes += 1;
+        if self.successes % CEILING_RESET_AFTER == 0 {
+            self.ceiling = None;
+        }
+        if self.successes % GROW_AFTER == 0 {
+            let limit = self.ceiling.map_or(*self.bounds.end(), |ceiling| ceiling - 1);
+            self.current = (self.current + (self.current / 8).max(1))
+                .min(limit)
+                .clamp(*self.bounds.start(), *self.bounds.end());
+        }
+    }
+
+    fn on_error(&mut self) {
+        self.ceiling = Some(self.ceiling.map_or(self.current, |ceiling| ceiling.min(self.current)));
+        self.shrink();
+    }
+
+    fn shrink(&mut self) {
+        self.successes = 0;
+        self.current = (self.current / 2).max(*self.bounds.start());
+    }
+}
+
+/// Block source wrapper that adapts the chunk size to how the inner source copes with it.
+///
+/// Batches are fetched in chunks of the current size, starting from the inner source's
+/// recommendation, and the size is tuned within the configured bounds from the outcome and
+/// latency of each chunk. The current size is reported as the `chunk_size` gauge.
+#[derive(Debug, Clone)]
+pub struct AdaptiveBlockSource {
+    block_source: BlockSourceBoxed,
+    controller: Arc<Mutex<ChunkSizeController>>,
+    metrics: BlockSourceMetrics,
+}
+
+impl AdaptiveBlockSource {
+    pub fn new(block_source: BlockSourceBoxed, bounds: RangeInclusive<u64>) -> Self {
+        let controller =
+            ChunkSizeController::new(block_source.recommended_chunk_size(), bounds.clone());
+        let metrics = BlockSourceMetrics::for_kind("adaptive");
+        metrics.chunk_size.set(controller.current as f64);
+        Self { block_source, controller: Arc::new(Mutex::new(controller)), metrics }
+    }
+
+    fn record(&self, outcome: Result<(usize, Duration), ()>) {
+        let mut controller = self.controller.lock().unwrap();
+        match outcome {
+            Ok((blocks, elapsed)) => controller.on_success(blocks, elapsed),
+            Err(()) => controller.on_error(),
+        }
+        self.metrics.chunk_size.set(controller.current as f64);
+    }
+}
+
+impl BlockSource for AdaptiveBlockSource {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        self.block_source.collect_block(height)
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        self.block_source.find_latest_block_number()
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        self.controller.lock().unwrap().current
+    }
+
+    /// Fetches the heights in chunks of the current size, adjusting it after each chunk.
+    fn collect_blocks(
+        &self,
+        heights: Vec<u64>,
+    ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
+        let this = self.clone();
+        async move {
+            let mut blocks = Vec::with_capacity(heights.len());
+            let mut remaining = &heights[..];
+            while !remaining.is_empty() {
+                let size = (this.recommended_chunk_size() as usize).min(remaining.len());
+                let (chunk, rest) = remaining.split_at(size);
+                let started = Instant::now();
+                match this.block_source.collect_blocks(chunk.to_vec()).await {
+                    Ok(fetched) => {
+                        this.record(Ok((fetched.len(), started.elapsed())));
+                        blocks.extend(fetched);
+                        remaining = rest;
+                    }
+                    Err(err) => {
+                        // Only backend failures say something about the chunk size
+                        if matches!(err, BlockSourceError::Backend { .. }) {
+                            this.record(Err(()));
+                        }
+                        if blocks.is_empty() {
+                            return Err(err);
+                        }
+                        let partial = PartialBlocksError {
+                            blocks,
+                            failed_heights: remaining.to_vec(),
+                            errors: vec![err.to_string()],
+                        };
+                        return Err(BlockSourceError::Backend {
+                            retryable: err.is_retryable(),
+                            source: partial.into(),
+                        });
+                    }
+                }
+            }
+            Ok(blocks)
+        }
+        .boxed()
+    }
+
+    fn polling_interval(&self) -> Duration {
+        self.block_source.polling_interval()
+    }
+
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        self.block_source.chain_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_block;
+
+    /// Fails batches larger than `threshold`, like a backend throttling concurrent requests.
+    #[derive(Debug)]
+    struct ThrottledSource {
+        threshold: usize,
+    }
+
+    impl BlockSource for ThrottledSource {
+        fn collect_block(
+            &self,
+            height: u64,
+        ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+            async move { Ok(empty_block(height)) }.boxed()
+        }
+
+        fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+            async { None }.boxed()
+        }
+
+        fn recommended_chunk_size(&self) -> u64 {
+            200
+        }
+
+        fn collect_blocks(
+            &self,
+            heights: Vec<u64>,
+        ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
+            if heights.len() > self.threshold {
+                let len = heights.len();
+                let err = format!("throttled {len} concurrent requests");
+                return async move { Err(BlockSourceError::backend(err)) }.boxed();
+            }
+            let blocks: Vec<_> = heights.into_iter().map(|h| self.collect_block(h)).collect();
+            async move { futures::future::try_join_all(blocks).await }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn converges_below_failure_threshold() {
+        let source = AdaptiveBlockSource::new(
+            Arc::new(Box::new(ThrottledSource { threshold: 50 })),
+            1..=1000,
+        );
+        assert_eq!(source.recommended_chunk_size(), 200);
+
+        let mut start = 0;
+        let mut failures = vec![];
+        for _ in 0..40 {
+            let heights: Vec<_> = (start..start + 500).collect();
+            failures.push(source.collect_blocks(heights).await.is_err());
+            start += 500;
+        }
+
+        let chunk_size = source.recommended_chunk_size();
+        assert!((25..=50).contains(&chunk_size), "chunk size {chunk_size}");
+        // Settled: the last rounds fetch everything without hitting the threshold
+        assert!(failures.iter().rev().take(10).all(|failed| !failed));
+        let blocks = source.collect_blocks((0..500).collect()).await.unwrap();
+        assert_eq!(blocks.len(), 500);
+        assert_eq!(blocks[499].number(), 499);
+    }
+
+    #[test]
+    fn stays_within_bounds() {
+        let mut controller = ChunkSizeController::new(5000, 10..=100);
+        assert_eq!(controller.current, 100);
+        for _ in 0..10 {
+            controller.on_error();
+        }
+        assert_eq!(controller.current, 10);
+        for _ in 0..2000 {
+            controller.on_success(10, Duration::from_millis(10));
+        }
+        assert_eq!(controller.current, 100);
+
+        // A latency regression shrinks without an error
+        controller.on_success(100, Duration::from_secs(10));
+        assert_eq!(controller.current, 50);
+    }
+}
@@ -1,34 +1,286 @@
-use super::{BlockSource, BlockSourceBoxed};
-use crate::node::types::BlockAndReceipts;
+use super::{
+    BlockSource, BlockSourceBoxed, BlockSourceError, BlockSourceMetrics, BlockSourceResult,
+    utils::decode_rmp_lz4,
+};
+use crate::{
+    addons::sync_server::SyncSourceStatus,
+    node::types::{BlockAndReceipts, EvmBlock},
+};
 use futures::{FutureExt, future::BoxFuture};
+use lz4_flex::frame::{FrameEncoder, FrameInfo};
 use reth_network::cache::LruMap;
-use std::{collections::HashMap, sync::{Arc, RwLock}};
+use reth_primitives_traits::InMemorySize;
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tracing::{info, warn};
 
 /// Block source wrapper that caches blocks in memory
 #[derive(Debug, Clone)]
 pub struct CachedBlockSource {
     block_source: BlockSourceBoxed,
-    cache: Arc<RwLock<LruMap<u64, BlockAndReceipts>>>,
+    cache: Arc<RwLock<BlockCache>>,
+    lookups: Arc<CacheLookups>,
+}
+
+/// LRU of blocks, bounded by count and optionally by the total in-memory size of the blocks.
+#[derive(Debug)]
+struct BlockCache {
+    blocks: LruMap<u64, BlockAndReceipts>,
+    limit: usize,
+    /// Total [`InMemorySize`] of the cached blocks.
+    bytes: usize,
+    max_bytes: Option<usize>,
+}
+
+impl BlockCache {
+    fn new(limit: u32, max_bytes: Option<usize>) -> Self {
+        Self { blocks: LruMap::new(limit), limit: limit as usize, bytes: 0, max_bytes }
+    }
+
+    fn get(&mut self, height: u64) -> Option<&BlockAndReceipts> {
+        self.blocks.get(&height).map(|block| &*block)
+    }
+
+    /// Inserts `block`, then evicts the least recently used blocks until the cache is within
+    /// its byte budget.
+    fn insert(&mut self, height: u64, block: BlockAndReceipts) {
+        if let Some(previous) = self.blocks.remove(&height) {
+            self.bytes -= previous.size();
+        }
+        // Evict here rather than in the map, so that the evicted block is accounted for
+        if self.blocks.len() >= self.limit {
+            self.pop_oldest();
+        }
+        self.bytes += block.size();
+        self.blocks.insert(height, block);
+        if let Some(max_bytes) = self.max_bytes {
+            while self.bytes > max_bytes && self.pop_oldest() {}
+        }
+    }
+
+    fn pop_oldest(&mut self) -> bool {
+        let Some((_, block)) = self.blocks.pop_oldest() else {
+            return false;
+        };
+        self.bytes -= block.size();
+        true
+    }
+}
+
+/// Name of the block cache snapshot file under the datadir.
+pub const BLOCK_CACHE_SNAPSHOT_FILE: &str = "block-cache.rmp.lz4";
+
+/// Default bound of the block cache snapshot, in bytes of the blocks it holds.
+pub const DEFAULT_SNAPSHOT_MAX_BYTES: usize = 1 << 30;
+
+/// File the block cache is saved to on shutdown and restored from on startup, so that a
+/// restarted node serves the blocks it cached before without fetching them again.
+///
+/// Holds the most recently used blocks, up to `max_bytes` of them by [`InMemorySize`]. Clones
+/// share the cache to save.
+#[derive(Debug, Clone)]
+pub struct BlockCacheSnapshot {
+    path: PathBuf,
+    max_bytes: usize,
+    /// The cache the snapshot was restored into, saved back by [`Self::save`]
+    cache: Arc<Mutex<Option<Arc<RwLock<BlockCache>>>>>,
+}
+
+impl BlockCacheSnapshot {
+    pub fn new(path: impl Into<PathBuf>, max_bytes: usize) -> Self {
+        Self { path: path.into(), max_bytes, cache: Default::default() }
+    }
+
+    /// Path of the snapshot file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Restores the blocks of the snapshot file, if any, into `cache`, which [`Self::save`]
+    /// saves from then on. Blocks whose hash doesn't match their header, or whose transactions
+    /// don't match the transactions root of their header, are skipped. Returns the number of
+    /// blocks restored.
+    fn restore(&self, cache: &Arc<RwLock<BlockCache>>) -> eyre::Result<usize> {
+        *self.cache.lock().unwrap() = Some(cache.clone());
+        let mut file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err.into()),
+        };
+        // Compressed blocks take less than they do in memory, so a larger file isn't one we wrote
+        let len = file.metadata()?.len();
+        eyre::ensure!(
+            len <= self.max_bytes as u64,
+            "snapshot of {len} bytes exceeds the bound of {} bytes",
+            self.max_bytes
+        );
+        let mut bytes = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut bytes)?;
+
+        // Blocks are stored least recently used first, so that they are used in the same order
+        let mut cache = cache.write().unwrap();
+        let (mut restored, mut invalid, mut size) = (0, 0, 0);
+        for block in decode_rmp_lz4(&bytes)? {
+            let EvmBlock::Reth115(sealed) = &block.block;
+            let header = &sealed.header;
+            if header.header.hash_slow() != header.hash ||
+                sealed.calculate_tx_root() != header.header.transactions_root
+            {
+                invalid += 1;
+                continue;
+            }
+            size += block.size();
+            if size > self.max_bytes {
+                break;
+            }
+            cache.insert(block.number(), block);
+            restored += 1;
+        }
+        if invalid > 0 {
+            let path = self.path.display();
+            warn!(invalid, %path, "Skipped blocks that don't match their header");
+        }
+        Ok(restored)
+    }
+
+    /// Writes the most recently used blocks of the cache to the snapshot file, replacing it.
+    /// Returns the number of blocks written.
+    pub fn save(&self) -> eyre::Result<usize> {
+        let Some(cache) = self.cache.lock().unwrap().clone() else {
+            return Ok(0);
+        };
+        let mut blocks = {
+            let cache = cache.read().unwrap();
+            let mut size = 0;
+            cache
+                .blocks
+                .iter()
+                .map(|(_, block)| block)
+                .take_while(|block| {
+                    size += block.size();
+                    size <= self.max_bytes
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        blocks.reverse();
+
+        let mut encoder =
+            FrameEncoder::with_frame_info(FrameInfo::new().content_checksum(true), Vec::new());
+        rmp_serde::encode::write(&mut encoder, &blocks)?;
+        // Written next to the snapshot and moved over it, so that a crash leaves the old one
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, encoder.finish()?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(blocks.len())
+    }
+}
+
+/// Running cache hit and miss counts, from which the hit ratio gauge is derived.
+#[derive(Debug)]
+struct CacheLookups {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    metrics: BlockSourceMetrics,
+    /// Also counts the lookups for the status log, if set.
+    status: Option<SyncSourceStatus>,
+}
+
+impl CacheLookups {
+    fn new(status: Option<SyncSourceStatus>) -> Self {
+        Self {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            metrics: BlockSourceMetrics::for_kind("cached"),
+            status,
+        }
+    }
+
+    fn record(&self, hits: u64, misses: u64) {
+        let metrics = &self.metrics;
+        metrics.polling_attempt.increment(hits + misses);
+        metrics.cache_hits.increment(hits);
+        metrics.cache_misses.increment(misses);
+        if let Some(status) = &self.status {
+            status.record_cache_lookups(hits, misses);
+        }
+        let hits = self.hits.fetch_add(hits, Ordering::Relaxed) + hits;
+        let misses = self.misses.fetch_add(misses, Ordering::Relaxed) + misses;
+        if hits + misses > 0 {
+            metrics.cache_hit_ratio.set(hits as f64 / (hits + misses) as f64);
+        }
+    }
 }
 
 impl CachedBlockSource {
     const CACHE_LIMIT: u32 = 100000;
 
     pub fn new(block_source: BlockSourceBoxed) -> Self {
-        Self { block_source, cache: Arc::new(RwLock::new(LruMap::new(Self::CACHE_LIMIT))) }
+        Self {
+            block_source,
+            cache: Arc::new(RwLock::new(BlockCache::new(Self::CACHE_LIMIT, None))),
+            lookups: Arc::new(CacheLookups::new(None)),
+        }
+    }
+
+    /// Caps the total in-memory size of the cached blocks, on top of the entry count limit.
+    pub fn with_max_bytes(self, max_bytes: Option<usize>) -> Self {
+        *self.cache.write().unwrap() = BlockCache::new(Self::CACHE_LIMIT, max_bytes);
+        self
+    }
+
+    /// Counts the cache lookups in `status` as well.
+    pub fn with_source_status(mut self, status: Option<SyncSourceStatus>) -> Self {
+        self.lookups = Arc::new(CacheLookups::new(status));
+        self
+    }
+
+    /// Warms the cache with the blocks of `snapshot`, which then saves this cache. Should be
+    /// called after [`Self::with_max_bytes`], which replaces the cache.
+    ///
+    /// The snapshot is read and decoded on a blocking thread, as it can hold up to a gigabyte of
+    /// blocks.
+    pub async fn with_snapshot(self, snapshot: Option<BlockCacheSnapshot>) -> Self {
+        let Some(snapshot) = snapshot else {
+            return self;
+        };
+        let cache = self.cache.clone();
+        let restoring = snapshot.clone();
+        let restored = tokio::task::spawn_blocking(move || restoring.restore(&cache)).await;
+        let path = snapshot.path().display();
+        match restored.map_err(eyre::Report::from).and_then(|restored| restored) {
+            Ok(restored) => info!(restored, %path, "Restored the block cache snapshot"),
+            Err(err) => warn!(%err, %path, "Failed to restore the block cache snapshot"),
+        }
+        self
     }
 }
 
 impl BlockSource for CachedBlockSource {
-    fn collect_block(&self, height: u64) -> BoxFuture<'static, eyre::Result<BlockAndReceipts>> {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
         let block_source = self.block_source.clone();
         let cache = self.cache.clone();
+        let lookups = self.lookups.clone();
         async move {
-            if let Some(block) = cache.write().unwrap().get(&height) {
+            if let Some(block) = cache.write().unwrap().get(height) {
+                lookups.record(1, 0);
+                lookups.metrics.fetched.increment(1);
                 return Ok(block.clone());
             }
+            lookups.record(0, 1);
             let block = block_source.collect_block(height).await?;
             cache.write().unwrap().insert(height, block.clone());
+            lookups.metrics.fetched.increment(1);
             Ok(block)
         }
         .boxed()
@@ -45,9 +297,10 @@ impl BlockSource for CachedBlockSource {
     fn collect_blocks(
         &self,
         heights: Vec<u64>,
-    ) -> BoxFuture<'static, eyre::Result<Vec<BlockAndReceipts>>> {
+    ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
         let block_source = self.block_source.clone();
         let cache = self.cache.clone();
+        let lookups = self.lookups.clone();
         async move {
             // Split into cached and uncached
             let mut cached: HashMap<u64, BlockAndReceipts> = HashMap::new();
@@ -55,17 +308,30 @@ impl BlockSource for CachedBlockSource {
             {
                 let mut c = cache.write().unwrap();
                 for &h in &heights {
-                    if let Some(block) = c.get(&h) {
+                    if let Some(block) = c.get(h) {
                         cached.insert(h, block.clone());
                     } else {
                         uncached_heights.push(h);
                     }
                 }
             }
+            lookups.record(cached.len() as u64, uncached_heights.len() as u64);
 
             // Batch fetch uncached blocks from inner source
             if !uncached_heights.is_empty() {
-                let fetched = block_source.collect_blocks(uncached_heights).await?;
+                let fetched = match block_source.collect_blocks(uncached_heights).await {
+                    Ok(fetched) => fetched,
+                    Err(err) => {
+                        // Keep what did arrive, so that the retry only refetches the failed heights
+                        if let Some(partial) = err.partial_blocks() {
+                            let mut c = cache.write().unwrap();
+                            for block in &partial.blocks {
+                                c.insert(block.number(), block.clone());
+                            }
+                        }
+                        return Err(err);
+                    }
+                };
                 let mut c = cache.write().unwrap();
                 for block in fetched {
                     let h = block.number();
@@ -74,14 +340,12 @@ impl BlockSource for CachedBlockSource {
                 }
             }
 
+            lookups.metrics.fetched.increment(cached.len() as u64);
+
             // Return in original order
             heights
                 .iter()
-                .map(|h| {
-                    cached
-                        .remove(h)
-                        .ok_or_else(|| eyre::eyre!("Block {h} not found"))
-                })
+                .map(|&height| cached.remove(&height).ok_or(BlockSourceError::Missing { height }))
                 .collect()
         }
         .boxed()
@@ -90,4 +354,122 @@ impl BlockSource for CachedBlockSource {
     fn polling_interval(&self) -> std::time::Duration {
         self.block_source.polling_interval()
     }
+
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        self.block_source.chain_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        node::{
+            primitives::TransactionSigned as TxSigned,
+            types::{
+                ReadPrecompileCalls, ReadPrecompileInput, ReadPrecompileResult,
+                reth_compat::TransactionSigned,
+            },
+        },
+        test_utils::{CountingSource, empty_block},
+    };
+    use alloy_consensus::{Signed, TxLegacy};
+    use alloy_primitives::{Address, Bytes, Signature, U256};
+
+    /// A block whose read precompile calls carry `payload` bytes.
+    fn block(number: u64, payload: usize) -> BlockAndReceipts {
+        let input = ReadPrecompileInput { input: Bytes::from(vec![0; payload]), gas_limit: 0 };
+        let calls = vec![(Address::ZERO, vec![(input, ReadPrecompileResult::OutOfGas)])];
+        BlockAndReceipts {
+            read_precompile_calls: ReadPrecompileCalls(calls),
+            ..empty_block(number)
+        }
+    }
+
+    #[test]
+    fn respects_byte_budget() {
+        let (small, large) = (block(0, 100).size(), block(0, 100_000).size());
+        let max_bytes = large + 2 * small;
+        let mut cache = BlockCache::new(100, Some(max_bytes));
+
+        for number in 0..10 {
+            cache.insert(number, block(number, 100));
+        }
+        assert_eq!(cache.blocks.len(), 10);
+
+        // The large block only fits with two small blocks; the oldest ones are evicted
+        cache.insert(10, block(10, 100_000));
+        assert!(cache.bytes <= max_bytes);
+        assert_eq!(cache.bytes, large + 2 * small);
+        assert!(cache.get(7).is_none());
+        assert!(cache.get(8).is_some() && cache.get(9).is_some() && cache.get(10).is_some());
+
+        // Small blocks push the large block out once it is the least recently used
+        for number in 11..14 {
+            cache.insert(number, block(number, 100));
+        }
+        assert!(cache.get(10).is_none());
+        assert!(cache.bytes <= max_bytes);
+    }
+
+    #[tokio::test]
+    async fn restored_snapshot_serves_cache_hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(BLOCK_CACHE_SNAPSHOT_FILE);
+        let source = Arc::new(CountingSource { heights: vec![1, 2, 3], ..Default::default() });
+
+        let snapshot = BlockCacheSnapshot::new(&path, DEFAULT_SNAPSHOT_MAX_BYTES);
+        let cached = CachedBlockSource::new(Arc::new(Box::new(source.clone())))
+            .with_snapshot(Some(snapshot.clone()))
+            .await;
+        for height in [1, 2, 3] {
+            cached.collect_block(height).await.unwrap();
+        }
+        assert_eq!(snapshot.save().unwrap(), 3);
+
+        // A restarted node fetches nothing it had cached
+        let restarted = Arc::new(CountingSource::default());
+        let snapshot = BlockCacheSnapshot::new(&path, DEFAULT_SNAPSHOT_MAX_BYTES);
+        let cached = CachedBlockSource::new(Arc::new(Box::new(restarted.clone())))
+            .with_snapshot(Some(snapshot))
+            .await;
+        for height in [1, 2, 3] {
+            assert_eq!(cached.collect_block(height).await.unwrap(), empty_block(height));
+        }
+        assert_eq!(restarted.fetches.load(Ordering::Relaxed), 0);
+        assert!(cached.collect_block(4).await.is_err());
+        assert_eq!(restarted.fetches.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn snapshot_skips_blocks_that_do_not_match_their_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(BLOCK_CACHE_SNAPSHOT_FILE);
+        let snapshot = BlockCacheSnapshot::new(&path, DEFAULT_SNAPSHOT_MAX_BYTES);
+        let cache = Arc::new(RwLock::new(BlockCache::new(100, None)));
+        snapshot.restore(&cache).unwrap();
+        let mut tampered = block(2, 10);
+        let EvmBlock::Reth115(sealed) = &mut tampered.block;
+        sealed.header.header.gas_used = 1;
+        // A transaction the header doesn't commit to, under an untouched header
+        let mut smuggled = block(3, 10);
+        let EvmBlock::Reth115(sealed) = &mut smuggled.block;
+        let signature = Signature::new(U256::ONE, U256::ONE, false);
+        let tx = TxSigned::Default(Signed::new_unhashed(TxLegacy::default(), signature).into());
+        sealed.body.transactions.push(TransactionSigned::from_node_tx(tx));
+        cache.write().unwrap().insert(1, block(1, 10));
+        cache.write().unwrap().insert(2, tampered);
+        cache.write().unwrap().insert(3, smuggled);
+        assert_eq!(snapshot.save().unwrap(), 3);
+
+        let restored = Arc::new(RwLock::new(BlockCache::new(100, None)));
+        assert_eq!(snapshot.restore(&restored).unwrap(), 1);
+        let mut restored = restored.write().unwrap();
+        assert!(restored.get(1).is_some() && restored.get(2).is_none());
+        assert!(restored.get(3).is_none());
+
+        // A snapshot beyond the bound is refused
+        let snapshot = BlockCacheSnapshot::new(&path, 16);
+        assert!(snapshot.restore(&Arc::new(RwLock::new(BlockCache::new(100, None)))).is_err());
+    }
 }
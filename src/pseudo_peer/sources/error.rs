@@ -0,0 +1,87 @@
+use super::PartialBlocksError;
+use std::fmt::Display;
+
+/// Boxed error of a block source backend.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Result of a [`BlockSource`](super::BlockSource) fetch.
+pub type BlockSourceResult<T> = Result<T, BlockSourceError>;
+
+/// Why a block source failed to return a block.
+///
+/// Callers branch on the variant: a block that isn't available yet is simply polled for again,
+/// a missing or corrupt block is worth the operator's attention, and a backend failure is only
+/// retried if it is transient.
+#[derive(Debug, thiserror::Error)]
+pub enum BlockSourceError {
+    /// The block is past what the source has, e.g. not written or produced yet.
+    #[error("block {height} is not available yet")]
+    NotFoundYet { height: u64 },
+    /// The source should have the block but doesn't, e.g. a gap in its files.
+    #[error("block {height} is missing from the block source")]
+    Missing { height: u64 },
+    /// The block was fetched but its payload is unusable.
+    #[error("block {height} is corrupt: {reason}")]
+    Corrupt { height: u64, reason: String },
+    /// The backend failed, e.g. on an I/O or transport error.
+    #[error("{source}")]
+    Backend {
+        /// Whether the same request may succeed later.
+        retryable: bool,
+        source: BoxError,
+    },
+}
+
+impl BlockSourceError {
+    /// A backend failure that may succeed when retried.
+    pub fn backend(err: impl Into<BoxError>) -> Self {
+        Self::Backend { retryable: true, source: err.into() }
+    }
+
+    /// A backend failure that retrying won't fix, e.g. a misconfiguration.
+    pub fn fatal(err: impl Into<BoxError>) -> Self {
+        Self::Backend { retryable: false, source: err.into() }
+    }
+
+    pub fn corrupt(height: u64, reason: impl Display) -> Self {
+        Self::Corrupt { height, reason: reason.to_string() }
+    }
+
+    /// Whether the same request may succeed later. Only backend failures can be permanent:
+    /// files are written in place and servers catch up, so even a corrupt block can be fixed.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Self::Backend { retryable: false, .. })
+    }
+
+    /// The blocks that did arrive if a batch only partially failed.
+    pub fn partial_blocks(&self) -> Option<&PartialBlocksError> {
+        match self {
+            Self::Backend { source, .. } => source.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Compatibility with block sources written against the `eyre` based trait: any error is
+/// treated as a transient backend failure.
+impl From<eyre::Report> for BlockSourceError {
+    fn from(err: eyre::Report) -> Self {
+        Self::backend(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_blocks_are_reachable() {
+        let partial =
+            PartialBlocksError { blocks: vec![], failed_heights: vec![1], errors: vec![] };
+        let err = BlockSourceError::fatal(partial);
+        assert!(!err.is_retryable());
+        assert_eq!(err.partial_blocks().unwrap().failed_heights, vec![1]);
+        assert!(BlockSourceError::from(eyre::eyre!("timeout")).is_retryable());
+        assert!(BlockSourceError::corrupt(1, "bad checksum").is_retryable());
+    }
+}
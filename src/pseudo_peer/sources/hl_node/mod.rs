@@ -1,6 +1,7 @@
 mod cache;
 mod file_ops;
 mod scan;
+mod selection;
 #[cfg(test)]
 mod tests;
 mod time_utils;
@@ -8,17 +9,26 @@ mod time_utils;
 use self::{
     cache::LocalBlocksCache,
     file_ops::FileOperations,
-    scan::{LineStream, ScanOptions, Scanner},
+    scan::{LineStream, ParseFailureLog, ScanOptions},
+    selection::SourceSelection,
     time_utils::TimeUtils,
 };
-use super::{BlockSource, BlockSourceBoxed};
-use crate::node::types::BlockAndReceipts;
+pub use scan::LocalBlockAndReceipts;
+pub(crate) use scan::Scanner;
+pub use selection::ActiveSource;
+use super::{
+    BlockSource, BlockSourceBoxed, BlockSourceError, BlockSourceMetrics, BlockSourceResult,
+};
+use crate::{addons::sync_server::SyncSourceStatus, node::types::BlockAndReceipts};
 use futures::future::BoxFuture;
-use reth_metrics::{Metrics, metrics, metrics::Counter};
+use reth_metrics::{
+    Metrics, metrics,
+    metrics::{Counter, Gauge},
+};
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use time::OffsetDateTime;
 use tokio::sync::Mutex;
@@ -27,12 +37,22 @@ use tracing::{info, warn};
 const HOURLY_SUBDIR: &str = "hourly";
 const CACHE_SIZE: u32 = 8000; // 3660 blocks per hour
 const ONE_HOUR: Duration = Duration::from_secs(60 * 60);
-const TAIL_INTERVAL: Duration = Duration::from_millis(25);
+/// How long the fallback gets to report its latest block before switching to it.
+const FALLBACK_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often a fallback that is behind is asked for its latest block again.
+const FALLBACK_PROBE_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone)]
 pub struct HlNodeBlockSourceArgs {
     pub root: PathBuf,
     pub fallback_threshold: Duration,
+    /// How often the ingest loop checks the hourly file for new blocks.
+    pub polling_interval: Duration,
+    /// Maximum number of lines read from the hourly file before releasing the block cache.
+    pub scan_batch: usize,
+    /// Number of blocks in a row the hourly files must serve before they take over from the
+    /// fallback again.
+    pub switch_back_polls: u64,
 }
 
 /// Block source that monitors the local ingest directory for the HL node.
@@ -41,8 +61,16 @@ pub struct HlNodeBlockSource {
     pub fallback: BlockSourceBoxed,
     pub local_blocks_cache: Arc<Mutex<LocalBlocksCache>>,
     pub last_local_fetch: Arc<Mutex<Option<(u64, OffsetDateTime)>>>,
+    selection: Arc<Mutex<SourceSelection>>,
+    /// Latest block of the fallback, as last probed before switching to it.
+    fallback_probe: Arc<Mutex<FallbackProbe>>,
     pub args: HlNodeBlockSourceArgs,
     pub metrics: HlNodeBlockSourceMetrics,
+    pub source_metrics: BlockSourceMetrics,
+    /// Reports the active source to the status log, if set.
+    source_status: Option<SyncSourceStatus>,
+    /// Parse failures of the hourly files, logged without flooding the logs.
+    parse_failures: Arc<ParseFailureLog>,
 }
 
 #[derive(Metrics, Clone)]
@@ -54,37 +82,76 @@ pub struct HlNodeBlockSourceMetrics {
     pub fetched_from_fallback: Counter,
     /// How many times `try_collect_local_block` was faster than ingest loop
     pub file_read_triggered: Counter,
+    /// Seconds since the ingest loop last scanned a new block from the hourly files
+    pub seconds_since_local_block: Gauge,
+    /// Source serving blocks: 0 for the hl-node files, 1 for the fallback
+    pub active_source: Gauge,
 }
 
 impl BlockSource for HlNodeBlockSource {
-    fn collect_block(&self, height: u64) -> BoxFuture<'static, eyre::Result<BlockAndReceipts>> {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
         let fallback = self.fallback.clone();
         let args = self.args.clone();
         let local_blocks_cache = self.local_blocks_cache.clone();
         let last_local_fetch = self.last_local_fetch.clone();
+        let selection = self.selection.clone();
+        let fallback_probe = self.fallback_probe.clone();
         let metrics = self.metrics.clone();
+        let source_metrics = self.source_metrics.clone();
+        let source_status = self.source_status.clone();
+        let parse_failures = self.parse_failures.clone();
         Box::pin(async move {
             let now = OffsetDateTime::now_utc();
+            source_metrics.polling_attempt.increment(1);
 
+            let started = Instant::now();
             if let Some(block) =
-                Self::try_collect_local_block(&metrics, local_blocks_cache, height).await
+                Self::try_collect_local_block(&metrics, local_blocks_cache, &parse_failures, height)
+                    .await
             {
+                source_metrics.fetch_latency.record(started.elapsed().as_secs_f64());
+                source_metrics.fetched.increment(1);
                 Self::update_last_fetch(last_local_fetch, height, now).await;
                 metrics.fetched_from_hl_node.increment(1);
+                let mut selection = selection.lock().await;
+                selection.on_local_hit(height);
+                Self::record_active_source(&metrics, &source_status, selection.active());
                 return Ok(block);
             }
 
-            if let Some((last_height, last_poll_time)) = *last_local_fetch.lock().await {
-                let more_recent = last_height < height;
-                let too_soon = now - last_poll_time < args.fallback_threshold;
-                if more_recent && too_soon {
-                    return Err(eyre::eyre!(
-                        "Not found locally; limiting polling rate before fallback so that hl-node has chance to catch up"
-                    ));
+            let lagging =
+                last_local_fetch.lock().await.is_none_or(|(last_height, last_poll_time)| {
+                    last_height >= height || now - last_poll_time >= args.fallback_threshold
+                });
+            // Switching only helps if the fallback is ahead of the hl-node files
+            let switching = lagging && selection.lock().await.active() == ActiveSource::Local;
+            let fallback_behind = if switching {
+                fallback_probe.lock().await.latest_below(&fallback, height).await
+            } else {
+                None
+            };
+            let active = {
+                let mut selection = selection.lock().await;
+                match fallback_behind {
+                    Some(latest) => selection.on_fallback_behind(height, latest),
+                    None => selection.on_local_miss(height, lagging),
                 }
+                selection.active()
+            };
+            Self::record_active_source(&metrics, &source_status, active);
+            // Once on the fallback, blocks missing locally are fetched without waiting; until
+            // then, hl-node gets the chance to catch up
+            if active == ActiveSource::Local {
+                source_metrics.errors_not_found.increment(1);
+                return Err(BlockSourceError::NotFoundYet { height });
             }
 
+            // The fallback records its own fetch metrics under its own kind
             let block = fallback.collect_block(height).await?;
+            source_metrics.fetched.increment(1);
             metrics.fetched_from_fallback.increment(1);
             Self::update_last_fetch(last_local_fetch, height, now).await;
             Ok(block)
@@ -122,6 +189,14 @@ impl BlockSource for HlNodeBlockSource {
     fn recommended_chunk_size(&self) -> u64 {
         self.fallback.recommended_chunk_size()
     }
+
+    fn polling_interval(&self) -> Duration {
+        self.args.polling_interval
+    }
+
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        self.fallback.chain_id()
+    }
 }
 
 struct CurrentFile {
@@ -146,12 +221,42 @@ impl CurrentFile {
     }
 }
 
+/// Latest block of the fallback, probed at most once per [`FALLBACK_PROBE_INTERVAL`] so that a
+/// lagging hl-node doesn't cost a probe, e.g. an S3 listing, per poll.
+#[derive(Debug, Default)]
+struct FallbackProbe {
+    latest: Option<u64>,
+    probed_at: Option<tokio::time::Instant>,
+}
+
+impl FallbackProbe {
+    /// Returns the latest block of the fallback if it is below `height`. A fallback that doesn't
+    /// report its latest block in time is assumed to have it.
+    async fn latest_below(&mut self, fallback: &BlockSourceBoxed, height: u64) -> Option<u64> {
+        // The latest block of the fallback only grows, so it has `height` for good
+        if self.latest.is_some_and(|latest| latest >= height) {
+            return None;
+        }
+        if self.probed_at.is_none_or(|probed_at| probed_at.elapsed() >= FALLBACK_PROBE_INTERVAL) {
+            self.probed_at = Some(tokio::time::Instant::now());
+            let latest =
+                tokio::time::timeout(FALLBACK_PROBE_TIMEOUT, fallback.find_latest_block_number())
+                    .await
+                    .ok()
+                    .flatten()?;
+            self.latest = self.latest.max(Some(latest));
+        }
+        self.latest.filter(|latest| *latest < height)
+    }
+}
+
 /// Checks if a file has any blocks (i.e., hl-node is actively writing to it).
-fn file_has_blocks(path: &Path) -> bool {
+fn file_has_blocks(path: &Path, parse_failures: &ParseFailureLog) -> bool {
     LineStream::from_path(path).is_ok_and(|mut stream| {
         !Scanner::scan_hour_file(
             &mut stream,
-            ScanOptions { start_height: 0, only_load_ranges: true },
+            ScanOptions { start_height: 0, only_load_ranges: true, batch_size: usize::MAX },
+            parse_failures,
         )
         .new_block_ranges
         .is_empty()
@@ -170,9 +275,35 @@ impl HlNodeBlockSource {
         }
     }
 
+    /// Source currently serving blocks.
+    pub async fn active_source(&self) -> ActiveSource {
+        self.selection.lock().await.active()
+    }
+
+    fn record_active_source(
+        metrics: &HlNodeBlockSourceMetrics,
+        source_status: &Option<SyncSourceStatus>,
+        active: ActiveSource,
+    ) {
+        metrics.active_source.set(active.gauge_value());
+        if let Some(status) = source_status {
+            status.record_active_source(active);
+        }
+    }
+
+    /// Reports the source serving blocks in `status` as well.
+    pub fn with_source_status(mut self, status: Option<SyncSourceStatus>) -> Self {
+        if let Some(status) = &status {
+            status.record_active_source(ActiveSource::Local);
+        }
+        self.source_status = status;
+        self
+    }
+
     async fn try_collect_local_block(
         metrics: &HlNodeBlockSourceMetrics,
         local_blocks_cache: Arc<Mutex<LocalBlocksCache>>,
+        parse_failures: &ParseFailureLog,
         height: u64,
     ) -> Option<BlockAndReceipts> {
         let mut u_cache = local_blocks_cache.lock().await;
@@ -185,7 +316,8 @@ impl HlNodeBlockSource {
         let mut line_stream = LineStream::from_path(&path).ok()?;
         let scan_result = Scanner::scan_hour_file(
             &mut line_stream,
-            ScanOptions { start_height: 0, only_load_ranges: false },
+            ScanOptions { start_height: 0, only_load_ranges: false, batch_size: usize::MAX },
+            parse_failures,
         );
         u_cache.load_scan_result(scan_result);
         u_cache.get_block(height)
@@ -194,6 +326,7 @@ impl HlNodeBlockSource {
     async fn try_backfill_local_blocks(
         root: &Path,
         cache: &Arc<Mutex<LocalBlocksCache>>,
+        parse_failures: &ParseFailureLog,
         cutoff_height: u64,
     ) -> eyre::Result<()> {
         let mut u_cache = cache.lock().await;
@@ -209,7 +342,12 @@ impl HlNodeBlockSource {
                 LineStream::from_path(&subfile).expect("Failed to open line stream");
             let mut scan_result = Scanner::scan_hour_file(
                 &mut line_stream,
-                ScanOptions { start_height: cutoff_height, only_load_ranges: true },
+                ScanOptions {
+                    start_height: cutoff_height,
+                    only_load_ranges: true,
+                    batch_size: usize::MAX,
+                },
+                parse_failures,
             );
             scan_result.new_blocks.clear(); // Only store ranges, load data lazily
             u_cache.load_scan_result(scan_result);
@@ -220,14 +358,18 @@ impl HlNodeBlockSource {
 
     async fn start_local_ingest_loop(&self, current_head: u64) {
         let root = self.args.root.to_owned();
+        let (polling_interval, scan_batch) = (self.args.polling_interval, self.args.scan_batch);
         let cache = self.local_blocks_cache.clone();
+        let metrics = self.metrics.clone();
+        let parse_failures = self.parse_failures.clone();
         tokio::spawn(async move {
             let mut next_height = current_head;
+            let mut last_local_block = Instant::now();
             let mut dt = loop {
                 if let Some(f) = FileOperations::find_latest_hourly_file(&root) {
                     break TimeUtils::datetime_from_path(&f).unwrap();
                 }
-                tokio::time::sleep(TAIL_INTERVAL).await;
+                tokio::time::sleep(polling_interval).await;
             };
             let mut current_file = CurrentFile::from_datetime(dt, &root);
             info!("Starting local ingest loop from height: {}", current_head);
@@ -236,22 +378,41 @@ impl HlNodeBlockSource {
                 if let Some(line_stream) = &mut current_file.line_stream {
                     let scan_result = Scanner::scan_hour_file(
                         line_stream,
-                        ScanOptions { start_height: next_height, only_load_ranges: false },
+                        ScanOptions {
+                            start_height: next_height,
+                            only_load_ranges: false,
+                            batch_size: scan_batch,
+                        },
+                        &parse_failures,
                     );
                     next_height = scan_result.next_expected_height;
+                    let batch_full = scan_result.batch_full;
+                    if !scan_result.new_blocks.is_empty() {
+                        last_local_block = Instant::now();
+                    }
                     cache.lock().await.load_scan_result(scan_result);
+                    if batch_full {
+                        // More lines are ready; keep reading without waiting
+                        continue;
+                    }
                 }
+                metrics.seconds_since_local_block.set(last_local_block.elapsed().as_secs_f64());
                 // Check if we should switch to the next hourly file
                 let now = OffsetDateTime::now_utc();
                 let next_dt = dt + ONE_HOUR;
                 if next_dt < now {
                     let next_file = CurrentFile::from_datetime(next_dt, &root);
-                    if file_has_blocks(&next_file.path) {
+                    if file_has_blocks(&next_file.path, &parse_failures) {
                         // Final scan of current file to catch any late-written blocks
                         if let Some(line_stream) = &mut current_file.line_stream {
                             let scan_result = Scanner::scan_hour_file(
                                 line_stream,
-                                ScanOptions { start_height: next_height, only_load_ranges: false },
+                                ScanOptions {
+                                    start_height: next_height,
+                                    only_load_ranges: false,
+                                    batch_size: usize::MAX,
+                                },
+                                &parse_failures,
                             );
                             next_height = scan_result.next_expected_height;
                             cache.lock().await.load_scan_result(scan_result);
@@ -262,7 +423,7 @@ impl HlNodeBlockSource {
                         continue; // Start reading new file immediately
                     }
                 }
-                tokio::time::sleep(TAIL_INTERVAL).await;
+                tokio::time::sleep(polling_interval).await;
             }
         });
     }
@@ -271,6 +432,7 @@ impl HlNodeBlockSource {
         let _ = Self::try_backfill_local_blocks(
             &self.args.root,
             &self.local_blocks_cache,
+            &self.parse_failures,
             next_block_number,
         )
         .await;
@@ -285,10 +447,15 @@ impl HlNodeBlockSource {
     ) -> Self {
         let block_source = Self {
             fallback,
+            selection: Arc::new(Mutex::new(SourceSelection::new(args.switch_back_polls))),
+            fallback_probe: Default::default(),
             args,
             local_blocks_cache: Arc::new(Mutex::new(LocalBlocksCache::new(CACHE_SIZE))),
             last_local_fetch: Arc::new(Mutex::new(None)),
             metrics: HlNodeBlockSourceMetrics::default(),
+            source_metrics: BlockSourceMetrics::for_kind("hl_node"),
+            source_status: None,
+            parse_failures: Default::default(),
         };
         block_source.run(next_block_number).await.unwrap();
         block_source
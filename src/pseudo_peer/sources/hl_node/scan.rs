@@ -1,12 +1,23 @@
 use crate::node::types::{BlockAndReceipts, EvmBlock};
+use reth_metrics::{Metrics, metrics, metrics::Counter};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashSet,
     fs::File,
     io::{BufRead, BufReader, Seek, SeekFrom},
     ops::RangeInclusive,
     path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
 };
-use tracing::warn;
+use tracing::{error, warn};
+
+/// Interval at which suppressed parse failures are summarized.
+const PARSE_FAILURE_SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A scan of at least this many lines that all fail to parse is reported as an unreadable file
+/// rather than as bad lines.
+const UNREADABLE_FILE_MIN_LINES: usize = 100;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LocalBlockAndReceipts(pub String, pub BlockAndReceipts);
@@ -16,11 +27,97 @@ pub struct ScanResult {
     pub next_expected_height: u64,
     pub new_blocks: Vec<BlockAndReceipts>,
     pub new_block_ranges: Vec<RangeInclusive<u64>>,
+    /// Whether the scan stopped at `batch_size` lines, i.e. more lines may be readable already.
+    pub batch_full: bool,
+    /// Number of lines that failed to parse.
+    pub parse_failures: usize,
+}
+
+#[derive(Metrics, Clone)]
+#[metrics(scope = "block_source.hl_node")]
+struct ScannerMetrics {
+    /// Lines of hl-node files that failed to parse
+    parse_failures: Counter,
+}
+
+#[derive(Debug)]
+struct ParseFailureState {
+    /// Files whose first parse failure was logged.
+    logged_files: HashSet<PathBuf>,
+    /// Files reported as unreadable.
+    unreadable_files: HashSet<PathBuf>,
+    suppressed: u64,
+    last_summary: Instant,
+}
+
+/// Logs parse failures of hl-node files without flooding the logs when a file can't be read at
+/// all, e.g. after a schema change of a newer hl-node: the first failure of each file is logged,
+/// later ones are counted and summarized every `interval`.
+#[derive(Debug)]
+pub struct ParseFailureLog {
+    interval: Duration,
+    metrics: ScannerMetrics,
+    state: Mutex<ParseFailureState>,
+}
+
+impl ParseFailureLog {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            metrics: ScannerMetrics::default(),
+            state: Mutex::new(ParseFailureState {
+                logged_files: HashSet::new(),
+                unreadable_files: HashSet::new(),
+                suppressed: 0,
+                last_summary: Instant::now(),
+            }),
+        }
+    }
+
+    fn record(&self, path: &Path, line: &str, err: &serde_json::Error) {
+        self.metrics.parse_failures.increment(1);
+        let mut state = self.state.lock().unwrap();
+        if !state.logged_files.contains(path) {
+            state.logged_files.insert(path.to_path_buf());
+            let line = line.get(0..50).unwrap_or(line);
+            warn!(?path, %err, "Failed to parse line: {line}...");
+            return;
+        }
+        state.suppressed += 1;
+        self.summarize(&mut state);
+    }
+
+    /// Reports a scan whose lines all failed to parse, once per file.
+    fn record_unreadable(&self, path: &Path, lines: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.unreadable_files.insert(path.to_path_buf()) {
+            error!(
+                ?path,
+                lines, "No line of the hl-node file could be parsed, its format may have changed"
+            );
+        }
+    }
+
+    fn summarize(&self, state: &mut ParseFailureState) {
+        if state.suppressed > 0 && state.last_summary.elapsed() >= self.interval {
+            warn!(failures = state.suppressed, "More lines of hl-node files failed to parse");
+            state.suppressed = 0;
+            state.last_summary = Instant::now();
+        }
+    }
+}
+
+impl Default for ParseFailureLog {
+    fn default() -> Self {
+        Self::new(PARSE_FAILURE_SUMMARY_INTERVAL)
+    }
 }
 
 pub struct ScanOptions {
     pub start_height: u64,
     pub only_load_ranges: bool,
+    /// Maximum number of lines read by a single scan.
+    pub batch_size: usize,
 }
 
 pub struct Scanner;
@@ -86,13 +183,24 @@ impl Scanner {
         Ok((parsed_block, height))
     }
 
-    pub fn scan_hour_file(line_stream: &mut LineStream, options: ScanOptions) -> ScanResult {
+    /// Scans the lines of an hourly file from where `line_stream` stopped, reporting parse
+    /// failures to `failure_log`.
+    pub fn scan_hour_file(
+        line_stream: &mut LineStream,
+        options: ScanOptions,
+        failure_log: &ParseFailureLog,
+    ) -> ScanResult {
         let mut new_blocks = Vec::new();
         let mut last_height = options.start_height;
         let mut block_ranges = Vec::new();
         let mut current_range: Option<(u64, u64)> = None;
+        let mut lines_read = 0;
+        let mut parse_failures = 0;
 
-        while let Some(line) = line_stream.next() {
+        while lines_read < options.batch_size &&
+            let Some(line) = line_stream.next()
+        {
+            lines_read += 1;
             match Self::line_to_evm_block(&line) {
                 Ok((parsed_block, height)) => {
                     if height >= options.start_height {
@@ -114,9 +222,15 @@ impl Scanner {
                         }
                     }
                 }
-                Err(_) => warn!("Failed to parse line: {}...", line.get(0..50).unwrap_or(&line)),
+                Err(err) => {
+                    parse_failures += 1;
+                    failure_log.record(&line_stream.path, &line, &err);
+                }
             }
         }
+        if parse_failures >= UNREADABLE_FILE_MIN_LINES && parse_failures == lines_read {
+            failure_log.record_unreadable(&line_stream.path, lines_read);
+        }
 
         if let Some((start, end)) = current_range {
             block_ranges.push(start..=end);
@@ -127,6 +241,8 @@ impl Scanner {
             next_expected_height: last_height + current_range.is_some() as u64,
             new_blocks,
             new_block_ranges: block_ranges,
+            batch_full: lines_read == options.batch_size,
+            parse_failures,
         }
     }
 }
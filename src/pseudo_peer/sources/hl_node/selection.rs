@@ -0,0 +1,96 @@
+use std::fmt;
+use tracing::{info, warn};
+
+/// The source serving blocks of the hl-node block source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveSource {
+    /// The hl-node hourly files.
+    Local,
+    /// The fallback source, used while the hourly files lag.
+    Fallback,
+}
+
+impl ActiveSource {
+    /// Value of the `active_source` gauge.
+    pub fn gauge_value(self) -> f64 {
+        match self {
+            Self::Local => 0.0,
+            Self::Fallback => 1.0,
+        }
+    }
+}
+
+impl fmt::Display for ActiveSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Local => "hl-node files",
+            Self::Fallback => "fallback",
+        })
+    }
+}
+
+/// Tracks which source serves blocks, with hysteresis on the way back.
+///
+/// The hl-node files lose priority as soon as they lag behind the fallback threshold, but only
+/// reclaim it once they have served `switch_back_polls` requested blocks in a row, so a writer
+/// that keeps stalling doesn't make the source flap between the two.
+#[derive(Debug)]
+pub struct SourceSelection {
+    active: ActiveSource,
+    fresh_polls: u64,
+    switch_back_polls: u64,
+    /// Block the hl-node files are waited for because the fallback doesn't have it either.
+    waiting_for: Option<u64>,
+}
+
+impl SourceSelection {
+    pub fn new(switch_back_polls: u64) -> Self {
+        Self { active: ActiveSource::Local, fresh_polls: 0, switch_back_polls, waiting_for: None }
+    }
+
+    pub fn active(&self) -> ActiveSource {
+        self.active
+    }
+
+    /// Records that block `height` was found in the hl-node files.
+    pub fn on_local_hit(&mut self, height: u64) {
+        if self.active == ActiveSource::Local {
+            return;
+        }
+        self.fresh_polls += 1;
+        if self.fresh_polls >= self.switch_back_polls {
+            info!(
+                height,
+                polls = self.fresh_polls,
+                "hl-node files caught up; switching back from the fallback"
+            );
+            self.active = ActiveSource::Local;
+            self.fresh_polls = 0;
+        }
+    }
+
+    /// Records that block `height` was missing from the hl-node files, `lagging` telling whether
+    /// they lag behind the fallback threshold.
+    pub fn on_local_miss(&mut self, height: u64, lagging: bool) {
+        self.fresh_polls = 0;
+        if self.active == ActiveSource::Local && lagging {
+            warn!(height, "hl-node files are lagging; switching to the fallback");
+            self.active = ActiveSource::Fallback;
+        }
+    }
+
+    /// Records that block `height` was missing from the lagging hl-node files, but the fallback
+    /// only has blocks up to `fallback_latest`, so the files keep serving. Logged once per block.
+    pub fn on_fallback_behind(&mut self, height: u64, fallback_latest: u64) {
+        self.fresh_polls = 0;
+        if self.waiting_for != Some(height) {
+            info!(
+                height,
+                fallback_latest,
+                "hl-node files are lagging, but the fallback doesn't have the block either; \
+                 waiting for the hl-node files"
+            );
+            self.waiting_for = Some(height);
+        }
+    }
+}
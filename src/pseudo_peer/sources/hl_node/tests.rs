@@ -8,6 +8,9 @@ use alloy_primitives::{Address, B64, B256, Bloom, Bytes, U256};
 use std::{io::Write, time::Duration};
 
 const DEFAULT_FALLBACK_THRESHOLD_FOR_TEST: Duration = Duration::from_millis(5000);
+const DEFAULT_POLLING_INTERVAL_FOR_TEST: Duration = Duration::from_millis(25);
+const DEFAULT_SCAN_BATCH_FOR_TEST: usize = 10000;
+const DEFAULT_SWITCH_BACK_POLLS_FOR_TEST: u64 = 10;
 
 #[test]
 fn test_datetime_from_path() {
@@ -24,7 +27,10 @@ async fn test_backfill() {
     }
 
     let cache = Arc::new(Mutex::new(LocalBlocksCache::new(CACHE_SIZE)));
-    HlNodeBlockSource::try_backfill_local_blocks(test_path, &cache, 1000000).await.unwrap();
+    let parse_failures = scan::ParseFailureLog::default();
+    HlNodeBlockSource::try_backfill_local_blocks(test_path, &cache, &parse_failures, 1000000)
+        .await
+        .unwrap();
 
     let u_cache = cache.lock().await;
     assert_eq!(
@@ -43,6 +49,8 @@ fn scan_result_from_single_block(block: BlockAndReceipts) -> scan::ScanResult {
         next_expected_height: height + 1,
         new_blocks: vec![block],
         new_block_ranges: vec![height..=height],
+        batch_full: false,
+        parse_failures: 0,
     }
 }
 
@@ -116,6 +124,9 @@ async fn setup_block_source_hierarchy() -> eyre::Result<BlockSourceHierarchy> {
         HlNodeBlockSourceArgs {
             root: { PathBuf::from("/nonexistent") },
             fallback_threshold: DEFAULT_FALLBACK_THRESHOLD_FOR_TEST,
+            polling_interval: DEFAULT_POLLING_INTERVAL_FOR_TEST,
+            scan_batch: DEFAULT_SCAN_BATCH_FOR_TEST,
+            switch_back_polls: DEFAULT_SWITCH_BACK_POLLS_FOR_TEST,
         },
         1000000,
     )
@@ -132,6 +143,9 @@ async fn setup_block_source_hierarchy() -> eyre::Result<BlockSourceHierarchy> {
         HlNodeBlockSourceArgs {
             root: temp_dir1.path().to_path_buf(),
             fallback_threshold: DEFAULT_FALLBACK_THRESHOLD_FOR_TEST,
+            polling_interval: DEFAULT_POLLING_INTERVAL_FOR_TEST,
+            scan_batch: DEFAULT_SCAN_BATCH_FOR_TEST,
+            switch_back_polls: DEFAULT_SWITCH_BACK_POLLS_FOR_TEST,
         },
         1000000,
     )
@@ -164,7 +178,7 @@ async fn test_update_last_fetch_no_fallback() -> eyre::Result<()> {
     assert_eq!(block, current_block.1);
 
     let block = block_source.collect_block(1000001).await;
-    assert!(block.is_err());
+    assert!(matches!(block, Err(BlockSourceError::NotFoundYet { height: 1000001 })));
 
     writeln!(&mut file1, "{}", serde_json::to_string(&future_block_hl_node)?)?;
     tokio::time::sleep(Duration::from_millis(100)).await;
@@ -194,6 +208,164 @@ async fn test_update_last_fetch_fallback() -> eyre::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_switches_to_fallback_and_back() -> eyre::Result<()> {
+    let fallback_threshold = Duration::from_millis(200);
+    let block_source_fallback = HlNodeBlockSource::new(
+        BlockSourceBoxed::new(Box::new(LocalBlockSource::new("/nonexistent"))),
+        HlNodeBlockSourceArgs {
+            root: PathBuf::from("/nonexistent"),
+            fallback_threshold,
+            polling_interval: DEFAULT_POLLING_INTERVAL_FOR_TEST,
+            scan_batch: DEFAULT_SCAN_BATCH_FOR_TEST,
+            switch_back_polls: DEFAULT_SWITCH_BACK_POLLS_FOR_TEST,
+        },
+        1000000,
+    )
+    .await;
+    for number in 1000001..=1000004 {
+        let block = empty_block(number, 1722633600, b"fallback");
+        let mut cache = block_source_fallback.local_blocks_cache.lock().await;
+        cache.load_scan_result(scan_result_from_single_block(block.1));
+    }
+
+    let (temp_dir, mut file) = setup_temp_dir_and_file()?;
+    let local_block = |number| empty_block(number, 1722633600, b"hl-node");
+    writeln!(&mut file, "{}", serde_json::to_string(&local_block(1000000))?)?;
+    let block_source = HlNodeBlockSource::new(
+        BlockSourceBoxed::new(Box::new(block_source_fallback)),
+        HlNodeBlockSourceArgs {
+            root: temp_dir.path().to_path_buf(),
+            fallback_threshold,
+            polling_interval: DEFAULT_POLLING_INTERVAL_FOR_TEST,
+            scan_batch: DEFAULT_SCAN_BATCH_FOR_TEST,
+            switch_back_polls: 2,
+        },
+        1000000,
+    )
+    .await;
+    assert_eq!(block_source.collect_block(1000000).await?, local_block(1000000).1);
+    assert_eq!(block_source.active_source().await, ActiveSource::Local);
+
+    // The local writer stalls: the fallback takes over past the threshold
+    assert!(block_source.collect_block(1000001).await.is_err());
+    tokio::time::sleep(fallback_threshold).await;
+    let block = block_source.collect_block(1000001).await?;
+    assert_eq!(block, empty_block(1000001, 1722633600, b"fallback").1);
+    assert_eq!(block_source.active_source().await, ActiveSource::Fallback);
+
+    // The writer resumes: the files reclaim priority after two blocks in a row
+    for number in 1000002..=1000003 {
+        writeln!(&mut file, "{}", serde_json::to_string(&local_block(number))?)?;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(block_source.collect_block(1000002).await?, local_block(1000002).1);
+    assert_eq!(block_source.active_source().await, ActiveSource::Fallback);
+    assert_eq!(block_source.collect_block(1000003).await?, local_block(1000003).1);
+    assert_eq!(block_source.active_source().await, ActiveSource::Local);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_waits_for_local_when_fallback_lacks_the_block() -> eyre::Result<()> {
+    let fallback_threshold = Duration::from_millis(200);
+    let args = |root| HlNodeBlockSourceArgs {
+        root,
+        fallback_threshold,
+        polling_interval: DEFAULT_POLLING_INTERVAL_FOR_TEST,
+        scan_batch: DEFAULT_SCAN_BATCH_FOR_TEST,
+        switch_back_polls: DEFAULT_SWITCH_BACK_POLLS_FOR_TEST,
+    };
+    // The fallback is no further than the hl-node files
+    let (fallback_dir, mut fallback_file) = setup_temp_dir_and_file()?;
+    let fallback_block = empty_block(1000000, 1722633600, b"fallback");
+    writeln!(&mut fallback_file, "{}", serde_json::to_string(&fallback_block)?)?;
+    let block_source_fallback = HlNodeBlockSource::new(
+        BlockSourceBoxed::new(Box::new(LocalBlockSource::new("/nonexistent"))),
+        args(fallback_dir.path().to_path_buf()),
+        1000000,
+    )
+    .await;
+
+    let (temp_dir, mut file) = setup_temp_dir_and_file()?;
+    let local_block = |number| empty_block(number, 1722633600, b"hl-node");
+    writeln!(&mut file, "{}", serde_json::to_string(&local_block(1000000))?)?;
+    let block_source = HlNodeBlockSource::new(
+        BlockSourceBoxed::new(Box::new(block_source_fallback)),
+        args(temp_dir.path().to_path_buf()),
+        1000000,
+    )
+    .await;
+    assert_eq!(block_source.collect_block(1000000).await?, local_block(1000000).1);
+
+    // The local writer stalls past the threshold, but switching wouldn't get the block
+    tokio::time::sleep(fallback_threshold).await;
+    let block = block_source.collect_block(1000001).await;
+    assert!(matches!(block, Err(BlockSourceError::NotFoundYet { height: 1000001 })));
+    assert_eq!(block_source.active_source().await, ActiveSource::Local);
+
+    writeln!(&mut file, "{}", serde_json::to_string(&local_block(1000001))?)?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(block_source.collect_block(1000001).await?, local_block(1000001).1);
+    assert_eq!(block_source.active_source().await, ActiveSource::Local);
+
+    Ok(())
+}
+
+/// Fallback at a fixed latest block, counting how often it is asked for it.
+#[derive(Debug, Default)]
+struct ProbedFallback {
+    latest: std::sync::atomic::AtomicU64,
+    probes: std::sync::atomic::AtomicU64,
+}
+
+impl BlockSource for Arc<ProbedFallback> {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        Box::pin(async move { Err(BlockSourceError::NotFoundYet { height }) })
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        use std::sync::atomic::Ordering;
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        let latest = self.latest.load(Ordering::Relaxed);
+        Box::pin(async move { Some(latest) })
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        1
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_fallback_probe_is_rate_limited() {
+    use std::sync::atomic::Ordering;
+    let source = Arc::new(ProbedFallback::default());
+    source.latest.store(100, Ordering::Relaxed);
+    let fallback = BlockSourceBoxed::new(Box::new(source.clone()));
+    let mut probe = FallbackProbe::default();
+
+    // A fallback behind is asked again only once the interval has passed
+    for _ in 0..10 {
+        assert_eq!(probe.latest_below(&fallback, 101).await, Some(100));
+    }
+    assert_eq!(source.probes.load(Ordering::Relaxed), 1);
+    source.latest.store(101, Ordering::Relaxed);
+    assert_eq!(probe.latest_below(&fallback, 101).await, Some(100));
+    tokio::time::advance(FALLBACK_PROBE_INTERVAL).await;
+    assert_eq!(probe.latest_below(&fallback, 101).await, None);
+    assert_eq!(source.probes.load(Ordering::Relaxed), 2);
+
+    // Once the fallback has a height, it isn't asked for it again
+    for height in [50, 100, 101] {
+        assert_eq!(probe.latest_below(&fallback, height).await, None);
+    }
+    assert_eq!(source.probes.load(Ordering::Relaxed), 2);
+}
+
 #[test]
 fn test_hourly_files_sort() -> eyre::Result<()> {
     let temp_dir = tempfile::tempdir()?;
@@ -212,3 +384,98 @@ fn test_hourly_files_sort() -> eyre::Result<()> {
     assert_eq!(file_names, ["9", "14"]);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_polling_interval_is_configurable() {
+    let polling_interval = Duration::from_millis(250);
+    let block_source = HlNodeBlockSource::new(
+        BlockSourceBoxed::new(Box::new(LocalBlockSource::new("/nonexistent"))),
+        HlNodeBlockSourceArgs {
+            root: PathBuf::from("/nonexistent"),
+            fallback_threshold: DEFAULT_FALLBACK_THRESHOLD_FOR_TEST,
+            polling_interval,
+            scan_batch: DEFAULT_SCAN_BATCH_FOR_TEST,
+            switch_back_polls: DEFAULT_SWITCH_BACK_POLLS_FOR_TEST,
+        },
+        1000000,
+    )
+    .await;
+    assert_eq!(block_source.polling_interval(), polling_interval);
+}
+
+#[test]
+fn test_scan_respects_batch_size() -> eyre::Result<()> {
+    let (temp_dir, mut file) = setup_temp_dir_and_file()?;
+    for number in 1000000..1000003 {
+        writeln!(&mut file, "{}", serde_json::to_string(&empty_block(number, 1722633600, b""))?)?;
+    }
+    let path = FileOperations::find_latest_hourly_file(temp_dir.path()).unwrap();
+    let mut line_stream = LineStream::from_path(&path)?;
+    let options =
+        |start_height| ScanOptions { start_height, only_load_ranges: false, batch_size: 2 };
+
+    let failure_log = scan::ParseFailureLog::default();
+    let first = Scanner::scan_hour_file(&mut line_stream, options(1000000), &failure_log);
+    assert!(first.batch_full);
+    assert_eq!(first.new_blocks.len(), 2);
+    assert_eq!(first.next_expected_height, 1000002);
+
+    let second = Scanner::scan_hour_file(
+        &mut line_stream,
+        options(first.next_expected_height),
+        &failure_log,
+    );
+    assert!(!second.batch_full);
+    assert_eq!(second.new_blocks.len(), 1);
+    assert_eq!(second.next_expected_height, 1000003);
+    Ok(())
+}
+
+#[test]
+fn test_scan_bounds_parse_failure_logs() -> eyre::Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tracing_subscriber::{Layer, layer::SubscriberExt};
+
+    /// Counts the events logged at `WARN` level or above.
+    struct CountingLayer(Arc<AtomicUsize>);
+
+    impl<S: tracing::Subscriber> Layer<S> for CountingLayer {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if *event.metadata().level() <= tracing::Level::WARN {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    let (temp_dir, mut file) = setup_temp_dir_and_file()?;
+    writeln!(&mut file, "{}", serde_json::to_string(&empty_block(1000000, 1722633600, b""))?)?;
+    for _ in 0..10_000 {
+        writeln!(&mut file, r#"["1722633600",{{"block":{{"Reth200":{{}}}}}}]"#)?;
+    }
+    let path = FileOperations::find_latest_hourly_file(temp_dir.path()).unwrap();
+    let options = || ScanOptions { start_height: 0, only_load_ranges: false, batch_size: 1000 };
+
+    let events = Arc::new(AtomicUsize::new(0));
+    let subscriber = tracing_subscriber::registry().with(CountingLayer(events.clone()));
+    let failure_log = scan::ParseFailureLog::new(Duration::from_secs(3600));
+    let failures = tracing::subscriber::with_default(subscriber, || {
+        let mut line_stream = LineStream::from_path(&path).unwrap();
+        let mut failures = 0;
+        loop {
+            let result = Scanner::scan_hour_file(&mut line_stream, options(), &failure_log);
+            failures += result.parse_failures;
+            if !result.batch_full {
+                break failures;
+            }
+        }
+    });
+
+    assert_eq!(failures, 10_000);
+    // The first bad line, and the file reported as unreadable once
+    assert_eq!(events.load(Ordering::Relaxed), 2);
+    Ok(())
+}
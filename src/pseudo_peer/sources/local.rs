@@ -1,34 +1,218 @@
-use super::{BlockSource, utils};
+use super::{
+    BlockSource, BlockSourceError, BlockSourceMetrics, BlockSourceResult, DEFAULT_POLLING_INTERVAL,
+    utils,
+};
 use crate::node::types::BlockAndReceipts;
-use eyre::Context;
 use futures::{FutureExt, future::BoxFuture};
-use reth_metrics::{Metrics, metrics, metrics::Counter};
-use std::path::PathBuf;
-use tracing::info;
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+/// How long a block file that ends early must go unmodified before it is taken as corrupt
+/// rather than still being written.
+const WRITE_SETTLE_TIME: Duration = Duration::from_secs(10);
+
+/// How blocks are laid out in the files of a local block directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlockFileLayout {
+    /// One file per block, at `<millions>/<thousands>/<height>.rmp.lz4`.
+    #[default]
+    PerBlock,
+    /// Files of consecutive blocks, e.g. one per thousand blocks or per hour, each named and
+    /// placed like the file of the first block it holds.
+    Aggregated,
+}
 
 /// Block source that reads blocks from local filesystem (--ingest-dir)
 #[derive(Debug, Clone)]
 pub struct LocalBlockSource {
     dir: PathBuf,
-    metrics: LocalBlockSourceMetrics,
-}
-
-#[derive(Metrics, Clone)]
-#[metrics(scope = "block_source.local")]
-pub struct LocalBlockSourceMetrics {
-    /// How many times the local block source is polling for a block
-    pub polling_attempt: Counter,
-    /// How many times the local block source is fetched from the local filesystem
-    pub fetched: Counter,
+    chunk_size: u64,
+    polling_interval: Duration,
+    mmap: bool,
+    layout: BlockFileLayout,
+    aggregated: Arc<Mutex<AggregatedFiles>>,
+    metrics: BlockSourceMetrics,
 }
 
 impl LocalBlockSource {
+    const DEFAULT_CHUNK_SIZE: u64 = 1000;
+
     pub fn new(dir: impl Into<PathBuf>) -> Self {
-        Self { dir: dir.into(), metrics: LocalBlockSourceMetrics::default() }
+        Self {
+            dir: dir.into(),
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            polling_interval: DEFAULT_POLLING_INTERVAL,
+            mmap: false,
+            layout: BlockFileLayout::default(),
+            aggregated: Default::default(),
+            metrics: BlockSourceMetrics::for_kind("local"),
+        }
+    }
+
+    /// Overrides the recommended chunk size.
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets the interval at which the tip is polled for new block files.
+    pub fn with_polling_interval(mut self, polling_interval: Duration) -> Self {
+        self.polling_interval = polling_interval;
+        self
+    }
+
+    /// Memory-maps block files instead of reading them into memory, which saves an allocation
+    /// and a copy per file on heavy random reads. Files modified within the last
+    /// [`WRITE_SETTLE_TIME`] may still be written, or rewritten after an interrupted write, and
+    /// are read as usual. A settled file truncated while it is mapped still makes the node crash
+    /// with `SIGBUS` rather than fail the read, so only enable it for directories whose files
+    /// are not rewritten once complete.
+    pub fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Sets how blocks are laid out in the directory's files.
+    pub fn with_layout(mut self, layout: BlockFileLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    async fn read_file(path: PathBuf, mmap: bool) -> std::io::Result<FileContents> {
+        if !mmap {
+            return Ok(FileContents::Read(tokio::fs::read(&path).await?));
+        }
+        tokio::task::spawn_blocking(move || map_file(&path)).await.map_err(std::io::Error::other)?
+    }
+
+    /// Reads and decodes the blocks of the file at `path`, which should hold block `height`.
+    async fn read_blocks(
+        &self,
+        path: &Path,
+        height: u64,
+    ) -> BlockSourceResult<Vec<BlockAndReceipts>> {
+        let metrics = &self.metrics;
+        let started = Instant::now();
+        let file = match Self::read_file(path.to_path_buf(), self.mmap).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                metrics.errors_not_found.increment(1);
+                return Err(BlockSourceError::NotFoundYet { height });
+            }
+            Err(err) => {
+                metrics.errors_transport.increment(1);
+                let err =
+                    eyre::Report::new(err).wrap_err(format!("Failed to read block from {path:?}"));
+                return Err(BlockSourceError::backend(err));
+            }
+        };
+        metrics.fetch_latency.record(started.elapsed().as_secs_f64());
+        metrics.bytes_fetched.increment(file.len() as u64);
+
+        let started = Instant::now();
+        let blocks = match utils::decode_rmp_lz4(&file) {
+            // An interrupted writer leaves a partial file it rewrites moments later
+            Err(err) if utils::is_truncated(&err) && !is_settled(path).await => {
+                debug!(height, %err, "Block file is incomplete, waiting for its writer");
+                metrics.errors_not_found.increment(1);
+                return Err(BlockSourceError::NotFoundYet { height });
+            }
+            decoded => decoded.map_err(|err| {
+                metrics.errors_decode.increment(1);
+                BlockSourceError::corrupt(height, err)
+            })?,
+        };
+        metrics.decode_latency.record(started.elapsed().as_secs_f64());
+        Ok(blocks)
+    }
+
+    async fn collect_per_block(&self, height: u64) -> BlockSourceResult<BlockAndReceipts> {
+        let blocks = self.read_blocks(&self.dir.join(utils::rmp_path(height)), height).await?;
+        blocks.into_iter().next().ok_or_else(|| {
+            self.metrics.errors_decode.increment(1);
+            BlockSourceError::corrupt(height, "block file holds no block")
+        })
+    }
+
+    async fn collect_aggregated(&self, height: u64) -> BlockSourceResult<BlockAndReceipts> {
+        let scanned = {
+            let files = self.aggregated.lock().await;
+            if let Some(block) = files.cached(height) {
+                return Ok(block);
+            }
+            files.index.range(height..).next().is_some()
+        };
+        // The block may be in a file written since the last scan
+        if !scanned {
+            self.scan_aggregated().await;
+        }
+
+        // Held while decoding, so that the heights of a chunk wait for one decode of their file
+        let mut files = self.aggregated.lock().await;
+        let Some((&first, path)) = files.index.range(..=height).next_back() else {
+            self.metrics.errors_not_found.increment(1);
+            return Err(BlockSourceError::NotFoundYet { height });
+        };
+        let path = path.clone();
+        // Decoded files are complete up to where they were written, as partially written ones
+        // fail to decode, but the writer may have appended blocks to them since
+        let stale = match &files.decoded {
+            Some(decoded) if decoded.first == first => {
+                files.cached(height).is_none() && decoded.stamp != FileStamp::read(&path).await
+            }
+            _ => true,
+        };
+        if stale {
+            let stamp = FileStamp::read(&path).await;
+            let blocks = self.read_blocks(&path, height).await?;
+            files.decoded = Some(DecodedFile { first, stamp, blocks: Arc::new(blocks) });
+        }
+        if let Some(block) = files.cached(height) {
+            return Ok(block);
+        }
+        self.metrics.errors_not_found.increment(1);
+        if files.index.range(height..).next().is_some() {
+            // A later file exists, so the block should have been in this one
+            Err(BlockSourceError::Missing { height })
+        } else {
+            Err(BlockSourceError::NotFoundYet { height })
+        }
+    }
+
+    async fn latest_aggregated(&self) -> Option<u64> {
+        self.scan_aggregated().await;
+        let mut files = self.aggregated.lock().await;
+        let (&first, path) = files.index.last_key_value()?;
+        let path = path.clone();
+        let stamp = FileStamp::read(&path).await;
+        let blocks = self.read_blocks(&path, first).await.ok()?;
+        let latest = blocks.last()?.number();
+        info!("Latest block number: {} in {}", latest, path.display());
+        files.decoded = Some(DecodedFile { first, stamp, blocks: Arc::new(blocks) });
+        Some(latest)
+    }
+
+    /// Indexes the files written since the last scan, listing the directories on a blocking
+    /// thread without holding the index.
+    async fn scan_aggregated(&self) {
+        let from = self.aggregated.lock().await.scan_start();
+        let dir = self.dir.clone();
+        match tokio::task::spawn_blocking(move || scan_aggregated_dir(&dir, from)).await {
+            Ok(found) => self.aggregated.lock().await.index.extend(found),
+            Err(err) => debug!(%err, "Failed to scan the block directory"),
+        }
     }
 
     async fn pick_path_with_highest_number(dir: PathBuf, is_dir: bool) -> Option<(u64, String)> {
-        let files = std::fs::read_dir(&dir).unwrap().collect::<Vec<_>>();
+        let files = std::fs::read_dir(&dir).ok()?.collect::<Vec<_>>();
         let files = files
             .into_iter()
             .filter(|path| path.as_ref().unwrap().path().is_dir() == is_dir)
@@ -40,27 +224,30 @@ impl LocalBlockSource {
 }
 
 impl BlockSource for LocalBlockSource {
-    fn collect_block(&self, height: u64) -> BoxFuture<'static, eyre::Result<BlockAndReceipts>> {
-        let dir = self.dir.clone();
-        let metrics = self.metrics.clone();
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        let source = self.clone();
         async move {
-            let path = dir.join(utils::rmp_path(height));
-            metrics.polling_attempt.increment(1);
-
-            let file = tokio::fs::read(&path)
-                .await
-                .wrap_err_with(|| format!("Failed to read block from {path:?}"))?;
-            let mut decoder = lz4_flex::frame::FrameDecoder::new(&file[..]);
-            let blocks: Vec<BlockAndReceipts> = rmp_serde::from_read(&mut decoder)?;
-            metrics.fetched.increment(1);
-            Ok(blocks[0].clone())
+            source.metrics.polling_attempt.increment(1);
+            let block = match source.layout {
+                BlockFileLayout::PerBlock => source.collect_per_block(height).await?,
+                BlockFileLayout::Aggregated => source.collect_aggregated(height).await?,
+            };
+            source.metrics.fetched.increment(1);
+            Ok(block)
         }
         .boxed()
     }
 
     fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
-        let dir = self.dir.clone();
+        let source = self.clone();
         async move {
+            if source.layout == BlockFileLayout::Aggregated {
+                return source.latest_aggregated().await;
+            }
+            let dir = source.dir;
             let (_, first_level) = Self::pick_path_with_highest_number(dir.clone(), true).await?;
             let (_, second_level) =
                 Self::pick_path_with_highest_number(dir.join(first_level), true).await?;
@@ -74,6 +261,321 @@ impl BlockSource for LocalBlockSource {
     }
 
     fn recommended_chunk_size(&self) -> u64 {
-        1000
+        self.chunk_size
+    }
+
+    fn polling_interval(&self) -> Duration {
+        self.polling_interval
+    }
+}
+
+/// Files of an aggregated layout found so far, and the last one decoded.
+#[derive(Debug, Default)]
+struct AggregatedFiles {
+    /// Paths of the files by the first block they hold.
+    index: BTreeMap<u64, PathBuf>,
+    decoded: Option<DecodedFile>,
+}
+
+/// Blocks of a decoded file, kept so that the neighboring heights don't decode it again.
+#[derive(Debug)]
+struct DecodedFile {
+    first: u64,
+    /// The file as it was before it was read, to tell when it was written to since.
+    stamp: Option<FileStamp>,
+    blocks: Arc<Vec<BlockAndReceipts>>,
+}
+
+/// Size and modification time of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    async fn read(path: &Path) -> Option<Self> {
+        let meta = tokio::fs::metadata(path).await.ok()?;
+        Some(Self { len: meta.len(), modified: meta.modified().ok() })
+    }
+}
+
+impl AggregatedFiles {
+    fn cached(&self, height: u64) -> Option<BlockAndReceipts> {
+        let decoded = self.decoded.as_ref()?;
+        let block = decoded.blocks.get(height.checked_sub(decoded.first)? as usize)?;
+        // Files hold consecutive blocks, but don't trust the offset blindly
+        if block.number() == height {
+            Some(block.clone())
+        } else {
+            decoded.blocks.iter().find(|block| block.number() == height).cloned()
+        }
+    }
+
+    /// Height the next scan starts from: the directory of the highest indexed file, as earlier
+    /// directories aren't written to anymore.
+    fn scan_start(&self) -> u64 {
+        self.index.last_key_value().map_or(0, |(first, _)| first.saturating_sub(1))
+    }
+}
+
+/// Files of the aggregated layout in `dir`, from the directories of height `from` on.
+fn scan_aggregated_dir(dir: &Path, from: u64) -> Vec<(u64, PathBuf)> {
+    // Directories are named as in `utils::rmp_path`
+    let (from_millions, from_thousands) = (from / 1_000_000 * 1_000_000, from / 1_000 * 1_000);
+    let mut found = vec![];
+    for (millions, millions_dir) in numbered_entries(dir, true) {
+        if millions < from_millions {
+            continue;
+        }
+        for (thousands, thousands_dir) in numbered_entries(&millions_dir, true) {
+            if thousands < from_thousands {
+                continue;
+            }
+            found.extend(numbered_entries(&thousands_dir, false));
+        }
+    }
+    found
+}
+
+/// Entries of `dir` named by a number, directories or `.rmp.lz4` files, with their number.
+fn numbered_entries(dir: &Path, is_dir: bool) -> Vec<(u64, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.is_dir() != is_dir {
+                return None;
+            }
+            let name = path.file_name()?.to_str()?;
+            let stem = if is_dir { name } else { name.strip_suffix(".rmp.lz4")? };
+            Some((stem.parse().ok()?, path))
+        })
+        .collect()
+}
+
+/// Contents of a block file, either read into memory or memory-mapped.
+enum FileContents {
+    Read(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for FileContents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Read(bytes) => bytes,
+            Self::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+/// Whether the file at `path` has gone unmodified for [`WRITE_SETTLE_TIME`].
+async fn is_settled(path: &Path) -> bool {
+    let Ok(modified) = tokio::fs::metadata(path).await.and_then(|meta| meta.modified()) else {
+        return false;
+    };
+    modified.elapsed().is_ok_and(|elapsed| elapsed >= WRITE_SETTLE_TIME)
+}
+
+/// Memory-maps the file at `path` if it has settled, and reads it otherwise.
+fn map_file(path: &Path) -> std::io::Result<FileContents> {
+    let mut file = std::fs::File::open(path)?;
+    let modified = file.metadata()?.modified()?;
+    if !modified.elapsed().is_ok_and(|elapsed| elapsed >= WRITE_SETTLE_TIME) {
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut bytes)?;
+        return Ok(FileContents::Read(bytes));
+    }
+    // SAFETY: the file has gone unmodified for `WRITE_SETTLE_TIME`, so its writer is done with
+    // it. Nothing prevents another process from truncating it afterwards, which raises `SIGBUS`
+    // on the next access to the mapping; `LocalBlockSource::with_mmap` leaves that to the
+    // operator.
+    unsafe { memmap2::Mmap::map(&file) }.map(FileContents::Mapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_block;
+    use std::{io::Write, ops::RangeInclusive};
+
+    fn write_block(dir: &Path, height: u64) {
+        write_blocks(dir, height..=height);
+    }
+
+    /// Writes `heights` into one file, named after the first of them.
+    fn write_blocks(dir: &Path, heights: RangeInclusive<u64>) {
+        let path = dir.join(utils::rmp_path(*heights.start()));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, encode_blocks(heights)).unwrap();
+    }
+
+    fn encode_block(height: u64) -> Vec<u8> {
+        encode_blocks(height..=height)
+    }
+
+    fn encode_blocks(heights: RangeInclusive<u64>) -> Vec<u8> {
+        let blocks: Vec<_> = heights.map(empty_block).collect();
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        encoder.write_all(&rmp_serde::to_vec(&blocks).unwrap()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Marks the file at `path` as last modified [`WRITE_SETTLE_TIME`] ago.
+    fn settle(path: &Path) {
+        let modified = SystemTime::now() - WRITE_SETTLE_TIME;
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mmap_reads_the_same_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        for height in [1, 1001, 2_000_001] {
+            write_block(dir.path(), height);
+        }
+        // The file of block 2_000_001 is still being written, and read rather than mapped
+        for height in [1, 1001] {
+            settle(&dir.path().join(utils::rmp_path(height)));
+        }
+        let read = LocalBlockSource::new(dir.path());
+        let mapped = LocalBlockSource::new(dir.path()).with_mmap(true);
+
+        for height in [1, 1001, 2_000_001] {
+            let expected = read.collect_block(height).await.unwrap();
+            assert_eq!(expected.number(), height);
+            let path = dir.path().join(utils::rmp_path(height));
+            let file = LocalBlockSource::read_file(path, true).await.unwrap();
+            assert_eq!(matches!(file, FileContents::Mapped(_)), height != 2_000_001);
+            assert_eq!(mapped.collect_block(height).await.unwrap(), expected);
+        }
+        assert!(mapped.collect_block(2).await.is_err());
+    }
+
+    /// Checks that memory-mapping a large aggregated file is no slower than reading it. Run with
+    /// `cargo test --release bench_mmap -- --ignored`.
+    #[tokio::test]
+    #[ignore = "benchmark"]
+    async fn bench_mmap() {
+        const ROUNDS: u32 = 20;
+        let dir = tempfile::tempdir().unwrap();
+        write_blocks(dir.path(), 1..=100_000);
+        let path = dir.path().join(utils::rmp_path(1));
+        settle(&path);
+
+        let mut elapsed = [Duration::ZERO; 2];
+        for (mmap, elapsed) in [false, true].into_iter().zip(&mut elapsed) {
+            for _ in 0..ROUNDS {
+                let started = Instant::now();
+                let file = LocalBlockSource::read_file(path.clone(), mmap).await.unwrap();
+                let blocks = utils::decode_rmp_lz4(&file).unwrap();
+                *elapsed += started.elapsed();
+                assert_eq!(blocks.len(), 100_000);
+            }
+        }
+        let [read, mapped] = elapsed.map(|elapsed| elapsed / ROUNDS);
+        assert!(mapped <= read, "read: {read:?}, mmap: {mapped:?}");
+    }
+
+    #[tokio::test]
+    async fn maps_file_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = LocalBlockSource::new(dir.path());
+        let err = source.collect_block(1).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 1 }), "{err}");
+
+        let path = dir.path().join(utils::rmp_path(1));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, b"not lz4").unwrap();
+        let err = source.collect_block(1).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::Corrupt { height: 1, .. }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn waits_for_truncated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = LocalBlockSource::new(dir.path());
+        let path = dir.path().join(utils::rmp_path(1));
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        // A writer interrupted halfway through the file
+        let bytes = encode_block(1);
+        for len in [0, 4, bytes.len() / 2] {
+            std::fs::write(&path, &bytes[..len]).unwrap();
+            let err = source.collect_block(1).await.unwrap_err();
+            assert!(matches!(err, BlockSourceError::NotFoundYet { height: 1 }), "{len}: {err}");
+        }
+
+        // The writer completes the file
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(source.collect_block(1).await.unwrap().number(), 1);
+
+        // A partial file nobody rewrites is corrupt once it has settled
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        settle(&path);
+        let err = source.collect_block(1).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::Corrupt { height: 1, .. }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn reads_both_layouts() {
+        let per_block_dir = tempfile::tempdir().unwrap();
+        let heights = [1, 999, 1000, 1001, 1500, 1502];
+        for height in heights {
+            write_block(per_block_dir.path(), height);
+        }
+        let aggregated_dir = tempfile::tempdir().unwrap();
+        // A file per thousand blocks, then hour-like files spanning directories
+        write_blocks(aggregated_dir.path(), 1..=1000);
+        write_blocks(aggregated_dir.path(), 1001..=2400);
+        write_blocks(aggregated_dir.path(), 2401..=2402);
+        let per_block = LocalBlockSource::new(per_block_dir.path());
+        let aggregated =
+            LocalBlockSource::new(aggregated_dir.path()).with_layout(BlockFileLayout::Aggregated);
+
+        assert_eq!(per_block.find_latest_block_number().await, Some(1502));
+        assert_eq!(aggregated.find_latest_block_number().await, Some(2402));
+        for height in heights {
+            let expected = per_block.collect_block(height).await.unwrap();
+            assert_eq!(aggregated.collect_block(height).await.unwrap(), expected);
+        }
+        // Neighboring heights are served from the decoded file
+        assert_eq!(aggregated.aggregated.lock().await.decoded.as_ref().unwrap().first, 1001);
+        assert_eq!(aggregated.collect_block(2400).await.unwrap().number(), 2400);
+
+        // Files written after the last scan are found
+        let err = aggregated.collect_block(2403).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 2403 }), "{err}");
+        write_blocks(aggregated_dir.path(), 2403..=3000);
+        assert_eq!(aggregated.collect_block(2403).await.unwrap().number(), 2403);
+        assert_eq!(aggregated.collect_block(3000).await.unwrap().number(), 3000);
+
+        // A block a later file skips is missing rather than not written yet
+        write_blocks(aggregated_dir.path(), 3002..=3002);
+        let err = aggregated.collect_block(3001).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::Missing { height: 3001 }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn rereads_aggregated_files_the_writer_appends_to() {
+        let dir = tempfile::tempdir().unwrap();
+        write_blocks(dir.path(), 1..=10);
+        let source = LocalBlockSource::new(dir.path()).with_layout(BlockFileLayout::Aggregated);
+        assert_eq!(source.find_latest_block_number().await, Some(10));
+        let err = source.collect_block(11).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 11 }), "{err}");
+
+        // The newest file grows
+        write_blocks(dir.path(), 1..=12);
+        assert_eq!(source.collect_block(11).await.unwrap().number(), 11);
+
+        // It grows again before the writer starts the next file
+        write_blocks(dir.path(), 1..=15);
+        write_blocks(dir.path(), 16..=20);
+        assert_eq!(source.collect_block(13).await.unwrap().number(), 13);
+        assert_eq!(source.collect_block(16).await.unwrap().number(), 16);
     }
 }
@@ -0,0 +1,82 @@
+use reth_metrics::{
+    Metrics,
+    metrics::{self, Counter, Gauge, Histogram},
+};
+
+/// Metrics shared by all block sources, labeled with the source `kind` (`s3`, `local`, `rpc`,
+/// `hl_node`, `stdin`, `cached` or `adaptive`) so that sources can be compared in one dashboard.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "block_source")]
+pub struct BlockSourceMetrics {
+    /// How many times the block source is polling for a block
+    pub polling_attempt: Counter,
+    /// How many blocks the block source has fetched
+    pub fetched: Counter,
+    /// Time spent fetching block payloads, in seconds
+    pub fetch_latency: Histogram,
+    /// Time spent decoding block payloads, in seconds
+    pub decode_latency: Histogram,
+    /// Bytes of block payloads fetched, as transferred
+    pub bytes_fetched: Counter,
+    /// Fetches that failed because the block is not available (yet)
+    pub errors_not_found: Counter,
+    /// Fetches that failed on an I/O or transport error
+    pub errors_transport: Counter,
+    /// Payloads that failed to decode
+    pub errors_decode: Counter,
+    /// Blocks served from the in-memory cache
+    pub cache_hits: Counter,
+    /// Blocks that were not cached and were fetched from the wrapped source
+    pub cache_misses: Counter,
+    /// Share of blocks served from the in-memory cache since startup
+    pub cache_hit_ratio: Gauge,
+    /// Chunk size currently chosen by the adaptive chunk size controller
+    pub chunk_size: Gauge,
+}
+
+impl BlockSourceMetrics {
+    /// Creates the metrics of a block source of the given `kind`.
+    pub fn for_kind(kind: &'static str) -> Self {
+        Self::new_with_labels(&[("kind", kind)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pseudo_peer::sources::{BlockSource, CachedBlockSource, LocalBlockSource};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use std::sync::Arc;
+
+    fn counter(snapshotter: &Snapshotter, name: &str, kind: &str) -> Option<u64> {
+        snapshotter.snapshot().into_vec().into_iter().find_map(|(key, _, _, value)| {
+            let key = key.key();
+            let labeled = key.labels().any(|label| label.key() == "kind" && label.value() == kind);
+            match value {
+                DebugValue::Counter(value) if key.name() == name && labeled => Some(value),
+                _ => None,
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn sources_report_under_their_kind() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let dir = tempfile::tempdir().unwrap();
+        // Handles are bound to the recorder they were registered with
+        let source = metrics::with_local_recorder(&recorder, || {
+            let local = LocalBlockSource::new(dir.path());
+            CachedBlockSource::new(Arc::new(Box::new(local)))
+        });
+
+        assert!(source.collect_block(1).await.is_err());
+        assert!(source.collect_block(1).await.is_err());
+
+        assert_eq!(counter(&snapshotter, "block_source.polling_attempt", "local"), Some(2));
+        assert_eq!(counter(&snapshotter, "block_source.errors_not_found", "local"), Some(2));
+        assert_eq!(counter(&snapshotter, "block_source.cache_misses", "cached"), Some(2));
+        assert_eq!(counter(&snapshotter, "block_source.cache_hits", "cached"), Some(0));
+        assert_eq!(counter(&snapshotter, "block_source.polling_attempt", "cached"), Some(2));
+    }
+}
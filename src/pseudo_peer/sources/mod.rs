@@ -4,27 +4,46 @@ use futures::{FutureExt, StreamExt, future::BoxFuture};
 use std::{sync::Arc, time::Duration};
 
 // Module declarations
+mod adaptive;
 mod cached;
+mod error;
 mod hl_node;
 mod local;
+mod metrics;
+mod polling;
+mod routed;
 mod rpc;
 mod s3;
+mod stdin;
+mod tracked;
 mod utils;
 
 // Public exports
-pub use cached::CachedBlockSource;
-pub use hl_node::{HlNodeBlockSource, HlNodeBlockSourceArgs};
-pub use local::LocalBlockSource;
-pub use rpc::RpcBlockSource;
+pub use adaptive::AdaptiveBlockSource;
+pub use cached::{
+    BLOCK_CACHE_SNAPSHOT_FILE, BlockCacheSnapshot, CachedBlockSource, DEFAULT_SNAPSHOT_MAX_BYTES,
+};
+pub use error::{BlockSourceError, BlockSourceResult, BoxError};
+pub(crate) use hl_node::Scanner;
+pub use hl_node::{ActiveSource, HlNodeBlockSource, HlNodeBlockSourceArgs, LocalBlockAndReceipts};
+pub use local::{BlockFileLayout, LocalBlockSource};
+pub use metrics::BlockSourceMetrics;
+pub use polling::{DEFAULT_POLLING_JITTER, PollingConfig, PollingSchedule};
+pub use routed::{HeightRoute, RoutedBlockSource};
+pub use rpc::{PartialBlocksError, RpcBatchConfig, RpcBlockSource, RpcTransport};
 pub use s3::S3BlockSource;
+pub use stdin::StdinBlockSource;
+pub use tracked::TrackedBlockSource;
+pub use utils::decode_rmp_lz4;
 
-const DEFAULT_POLLING_INTERVAL: Duration = Duration::from_millis(25);
+/// Polling interval of sources that don't set their own.
+pub const DEFAULT_POLLING_INTERVAL: Duration = Duration::from_millis(25);
 
 /// Trait for block sources that can retrieve blocks from various sources
 #[auto_impl(&, &mut, Box, Arc)]
 pub trait BlockSource: Send + Sync + std::fmt::Debug + Unpin + 'static {
     /// Retrieves a block at the specified height
-    fn collect_block(&self, height: u64) -> BoxFuture<'static, eyre::Result<BlockAndReceipts>>;
+    fn collect_block(&self, height: u64) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>>;
 
     /// Finds the latest block number available from this source
     fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>>;
@@ -38,7 +57,7 @@ pub trait BlockSource: Send + Sync + std::fmt::Debug + Unpin + 'static {
     fn collect_blocks(
         &self,
         heights: Vec<u64>,
-    ) -> BoxFuture<'static, eyre::Result<Vec<BlockAndReceipts>>> {
+    ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
         let chunk_size = self.recommended_chunk_size() as usize;
         let futs: Vec<_> = heights.into_iter().map(|h| self.collect_block(h)).collect();
         async move {
@@ -56,6 +75,11 @@ pub trait BlockSource: Send + Sync + std::fmt::Debug + Unpin + 'static {
     fn polling_interval(&self) -> Duration {
         DEFAULT_POLLING_INTERVAL
     }
+
+    /// Chain the source serves blocks of, for sources that report it without fetching a block.
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        async { None }.boxed()
+    }
 }
 
 /// Type alias for a boxed block source
@@ -0,0 +1,129 @@
+//! How often the block poller polls a block source for the next block at the tip.
+//!
+//! Polls are spread by a random jitter, so that followers of the same source don't poll it in
+//! lockstep, and back off while the source has nothing new: each poll that finds no block doubles
+//! the interval up to a maximum, and the next block brings it back to the source's interval.
+
+use reth_metrics::{Metrics, metrics::Gauge};
+use std::time::Duration;
+
+/// Default jitter of the polling interval set from the command line, as a fraction of it.
+pub const DEFAULT_POLLING_JITTER: f64 = 0.1;
+
+/// Jitter and idle backoff of the polls for the next block.
+///
+/// The default polls at the source's interval exactly.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PollingConfig {
+    /// Largest deviation from the interval, as a fraction of it, e.g. `0.1` for ±10%.
+    pub jitter: f64,
+    /// Interval the backoff grows to while polls find nothing new. Without it, the interval
+    /// doesn't grow.
+    pub max_interval: Option<Duration>,
+}
+
+#[derive(Metrics, Clone)]
+#[metrics(scope = "block_poller")]
+struct PollingMetrics {
+    /// Interval between polls for the next block, before jitter, in seconds
+    polling_interval: Gauge,
+}
+
+/// Intervals between the polls for a block that isn't available yet.
+#[derive(Debug)]
+pub struct PollingSchedule {
+    base: Duration,
+    current: Duration,
+    max: Duration,
+    jitter: f64,
+    metrics: PollingMetrics,
+}
+
+impl PollingSchedule {
+    /// Polls at `interval`, spread and backed off as `config` says.
+    pub fn new(interval: Duration, config: PollingConfig) -> Self {
+        let schedule = Self {
+            base: interval,
+            current: interval,
+            max: config.max_interval.map_or(interval, |max| max.max(interval)),
+            jitter: config.jitter.clamp(0.0, 1.0),
+            metrics: PollingMetrics::default(),
+        };
+        schedule.metrics.polling_interval.set(interval.as_secs_f64());
+        schedule
+    }
+
+    /// Interval before the next poll, before jitter.
+    pub fn interval(&self) -> Duration {
+        self.current
+    }
+
+    /// Records a poll that found a block, bringing the interval back to the source's.
+    pub fn hit(&mut self) {
+        if self.current != self.base {
+            self.current = self.base;
+            self.metrics.polling_interval.set(self.current.as_secs_f64());
+        }
+    }
+
+    /// Records a poll that found nothing new, returning how long to wait before the next one.
+    pub fn miss(&mut self) -> Duration {
+        let delay = if self.jitter > 0.0 {
+            let factor = rand::random_range(1.0 - self.jitter..=1.0 + self.jitter);
+            self.current.mul_f64(factor)
+        } else {
+            self.current
+        };
+        if self.current < self.max {
+            self.current = (self.current * 2).min(self.max);
+            self.metrics.polling_interval.set(self.current.as_secs_f64());
+        }
+        delay
+    }
+
+    /// Waits after a poll that found nothing new, see [`Self::miss`].
+    pub async fn wait(&mut self) {
+        tokio::time::sleep(self.miss()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+
+    const INTERVAL: Duration = Duration::from_millis(25);
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_polls_back_off_until_a_block_is_found() {
+        let config = PollingConfig { jitter: 0.0, max_interval: Some(Duration::from_millis(150)) };
+        let mut schedule = PollingSchedule::new(INTERVAL, config);
+
+        let mut waits = Vec::new();
+        for _ in 0..5 {
+            let start = Instant::now();
+            schedule.wait().await;
+            waits.push(start.elapsed().as_millis());
+        }
+        assert_eq!(waits, [25, 50, 100, 150, 150]);
+
+        schedule.hit();
+        assert_eq!(schedule.interval(), INTERVAL);
+        let start = Instant::now();
+        schedule.wait().await;
+        assert_eq!(start.elapsed(), INTERVAL);
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let mut schedule =
+            PollingSchedule::new(INTERVAL, PollingConfig { jitter: 0.2, max_interval: None });
+        let delays: Vec<_> = (0..100).map(|_| schedule.miss()).collect();
+        let bounds = Duration::from_micros(19_999)..=Duration::from_micros(30_001);
+        assert!(delays.iter().all(|delay| bounds.contains(delay)), "{delays:?}");
+        // Followers don't poll in lockstep
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+        // Without a maximum, the interval doesn't grow
+        assert_eq!(schedule.interval(), INTERVAL);
+    }
+}
@@ -0,0 +1,252 @@
+use super::{BlockSource, BlockSourceBoxed, BlockSourceError, BlockSourceResult};
+use crate::node::types::BlockAndReceipts;
+use futures::{FutureExt, future::BoxFuture};
+use std::{
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tracing::debug;
+
+/// Heights a route serves, relative to the latest block known to any route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeightRoute {
+    /// Every height.
+    Any,
+    /// Heights at most this many blocks below the tip, e.g. `tip:1000`.
+    Tip(u64),
+    /// Heights more than this many blocks below the tip, e.g. `historical:1000`.
+    Historical(u64),
+}
+
+impl HeightRoute {
+    /// Whether `height` is routed here. Every route matches until the tip is known.
+    pub fn matches(&self, height: u64, tip: Option<u64>) -> bool {
+        let Some(tip) = tip else {
+            return true;
+        };
+        match *self {
+            Self::Any => true,
+            Self::Tip(depth) => height.saturating_add(depth) >= tip,
+            Self::Historical(depth) => height.saturating_add(depth) < tip,
+        }
+    }
+}
+
+impl FromStr for HeightRoute {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, depth) = s.split_once(':').unwrap_or((s, "0"));
+        let depth = depth.parse().map_err(|_| eyre::eyre!("invalid route depth: {s}"))?;
+        match kind {
+            "any" => Ok(Self::Any),
+            "tip" => Ok(Self::Tip(depth)),
+            "historical" => Ok(Self::Historical(depth)),
+            _ => eyre::bail!("unknown route {s:?}, expected any, tip:<n> or historical:<n>"),
+        }
+    }
+}
+
+/// Block source that routes each height to the first of several sources serving it, falling
+/// back to the next matching source when one fails. Routes are tried in the order given, so the
+/// order expresses the operator's preference.
+#[derive(Debug, Clone)]
+pub struct RoutedBlockSource {
+    routes: Arc<Vec<(BlockSourceBoxed, HeightRoute)>>,
+    /// Latest block reported by any route, 0 until known.
+    tip: Arc<AtomicU64>,
+}
+
+impl RoutedBlockSource {
+    pub fn new(routes: Vec<(BlockSourceBoxed, HeightRoute)>) -> Self {
+        assert!(!routes.is_empty(), "routed block source needs at least one route");
+        Self { routes: Arc::new(routes), tip: Arc::new(AtomicU64::new(0)) }
+    }
+
+    fn tip(&self) -> Option<u64> {
+        Some(self.tip.load(Ordering::Relaxed)).filter(|tip| *tip > 0)
+    }
+
+    fn sources_for(&self, height: u64) -> Vec<BlockSourceBoxed> {
+        let tip = self.tip();
+        self.routes
+            .iter()
+            .filter(|(_, route)| route.matches(height, tip))
+            .map(|(source, _)| source.clone())
+            .collect()
+    }
+}
+
+impl BlockSource for RoutedBlockSource {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        let sources = self.sources_for(height);
+        async move {
+            let mut last_error = None;
+            for source in sources {
+                match source.collect_block(height).await {
+                    Ok(block) => return Ok(block),
+                    Err(err) => {
+                        debug!(height, ?source, %err, "Routed block source failed, trying next");
+                        last_error = Some(err);
+                    }
+                }
+            }
+            // No route serves the height, e.g. between a tip and a historical route
+            Err(last_error.unwrap_or(BlockSourceError::Missing { height }))
+        }
+        .boxed()
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        let latest: Vec<_> =
+            self.routes.iter().map(|(source, _)| source.find_latest_block_number()).collect();
+        let tip = self.tip.clone();
+        async move {
+            let latest = futures::future::join_all(latest).await.into_iter().flatten().max()?;
+            tip.fetch_max(latest, Ordering::Relaxed);
+            Some(latest)
+        }
+        .boxed()
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        self.routes[0].0.recommended_chunk_size()
+    }
+
+    /// Sends the whole batch to the source preferred for its first height, so batch endpoints
+    /// are used, and falls back to routing heights one by one if that fails.
+    fn collect_blocks(
+        &self,
+        heights: Vec<u64>,
+    ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
+        let Some(first) = heights.first() else {
+            return async { Ok(vec![]) }.boxed();
+        };
+        let tip = self.tip();
+        let first_route =
+            |height: &u64| self.routes.iter().position(|(_, r)| r.matches(*height, tip));
+        let route = first_route(first);
+        let preferred = route
+            .filter(|_| heights.iter().all(|height| first_route(height) == route))
+            .map(|index| self.routes[index].0.clone());
+        let this = self.clone();
+        async move {
+            if let Some(source) = preferred {
+                match source.collect_blocks(heights.clone()).await {
+                    Ok(blocks) => return Ok(blocks),
+                    Err(err) => debug!(?source, %err, "Routed batch failed, routing blocks singly"),
+                }
+            }
+            let blocks = heights.into_iter().map(|height| this.collect_block(height));
+            futures::future::try_join_all(blocks).await
+        }
+        .boxed()
+    }
+
+    fn polling_interval(&self) -> Duration {
+        self.routes.iter().map(|(source, _)| source.polling_interval()).min().unwrap_or_default()
+    }
+
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        let chain_ids: Vec<_> = self.routes.iter().map(|(source, _)| source.chain_id()).collect();
+        async move { futures::future::join_all(chain_ids).await.into_iter().flatten().next() }
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::empty_block;
+    use std::sync::Mutex;
+
+    /// Serves every height up to `latest`, recording which heights it was asked for.
+    #[derive(Debug)]
+    struct RecordingSource {
+        latest: u64,
+        served: Mutex<Vec<u64>>,
+    }
+
+    impl BlockSource for RecordingSource {
+        fn collect_block(
+            &self,
+            height: u64,
+        ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+            self.served.lock().unwrap().push(height);
+            let found = height <= self.latest;
+            async move {
+                if !found {
+                    return Err(BlockSourceError::NotFoundYet { height });
+                }
+                Ok(empty_block(height))
+            }
+            .boxed()
+        }
+
+        fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+            let latest = self.latest;
+            async move { Some(latest) }.boxed()
+        }
+
+        fn recommended_chunk_size(&self) -> u64 {
+            10
+        }
+    }
+
+    fn source(latest: u64) -> Arc<RecordingSource> {
+        Arc::new(RecordingSource { latest, served: Mutex::new(vec![]) })
+    }
+
+    #[tokio::test]
+    async fn routes_by_height_relative_to_tip() {
+        let (local, s3) = (source(10_000), source(9_990));
+        let routed = RoutedBlockSource::new(vec![
+            (Arc::new(Box::new(local.clone())), "tip:100".parse().unwrap()),
+            (Arc::new(Box::new(s3.clone())), "historical:100".parse().unwrap()),
+        ]);
+        assert_eq!(routed.find_latest_block_number().await, Some(10_000));
+
+        assert_eq!(routed.collect_block(5_000).await.unwrap().number(), 5_000);
+        assert_eq!(routed.collect_block(9_950).await.unwrap().number(), 9_950);
+        assert_eq!(*s3.served.lock().unwrap(), vec![5_000]);
+        assert_eq!(*local.served.lock().unwrap(), vec![9_950]);
+
+        let blocks = routed.collect_blocks(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(*s3.served.lock().unwrap(), vec![5_000, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn falls_back_in_preference_order() {
+        let (local, rpc, s3) = (source(100), source(200), source(300));
+        let routed = RoutedBlockSource::new(vec![
+            (Arc::new(Box::new(local.clone())), HeightRoute::Any),
+            (Arc::new(Box::new(rpc.clone())), HeightRoute::Any),
+            (Arc::new(Box::new(s3.clone())), HeightRoute::Any),
+        ]);
+
+        assert!(routed.collect_block(150).await.is_ok());
+        assert_eq!(*local.served.lock().unwrap(), vec![150]);
+        assert_eq!(*rpc.served.lock().unwrap(), vec![150]);
+        assert!(s3.served.lock().unwrap().is_empty());
+        let err = routed.collect_block(301).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 301 }), "{err}");
+    }
+
+    #[test]
+    fn parses_routes() {
+        assert_eq!("any".parse::<HeightRoute>().unwrap(), HeightRoute::Any);
+        assert_eq!("tip:64".parse::<HeightRoute>().unwrap(), HeightRoute::Tip(64));
+        assert_eq!("historical:64".parse::<HeightRoute>().unwrap(), HeightRoute::Historical(64));
+        assert!("tip:x".parse::<HeightRoute>().is_err());
+        assert!("recent:1".parse::<HeightRoute>().is_err());
+        assert!(HeightRoute::Historical(100).matches(5, None));
+    }
+}
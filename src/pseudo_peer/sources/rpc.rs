@@ -1,66 +1,788 @@
-use super::BlockSource;
-use crate::node::types::BlockAndReceipts;
-use alloy_primitives::Bytes;
+use super::{BlockSource, BlockSourceError, BlockSourceMetrics, BlockSourceResult, utils};
+use crate::{
+    addons::{
+        sync_limits::{RateLimitedData, SYNC_RATE_LIMITED_CODE},
+        sync_server::{SyncBlockReader, SyncBlocksResponse, SyncLatestBlockResponse},
+    },
+    node::{rpc::errors::HlErrorCode, types::BlockAndReceipts},
+};
+use alloy_primitives::{B256, Bytes};
 use futures::{FutureExt, StreamExt, future::BoxFuture};
-use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
-use jsonrpsee_core::client::ClientT;
+use jsonrpsee::{
+    http_client::{HttpClient, HttpClientBuilder},
+    ws_client::{WsClient, WsClientBuilder},
+};
+use jsonrpsee_core::{
+    client::{
+        Client as UdsClient, ClientT, Error as ClientError, Subscription, SubscriptionClientT,
+    },
+    rpc_params,
+    traits::ToRpcParams,
+};
+use jsonrpsee_types::error::{INVALID_PARAMS_CODE, METHOD_NOT_FOUND_CODE};
+use reth_ipc::client::IpcClientBuilder;
 use reth_metrics::{Metrics, metrics, metrics::Counter};
-use std::{sync::Arc, time::Duration};
-use tracing::info;
+use reth_network::cache::LruMap;
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::OnceCell;
+use tracing::{debug, info, warn};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// How many times a rate-limited request is retried before giving up.
+const MAX_RATE_LIMITED_RETRIES: usize = 10;
+/// Backoff used when the sync server doesn't say how long to wait.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+/// How many times a response that fails to decode is requested again before giving up.
+const MAX_CORRUPT_RETRIES: usize = 3;
+/// Backoff between attempts to subscribe to pushed blocks again, doubling up to the maximum.
+const MIN_RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How [`RpcBlockSource::collect_blocks`] splits requests into `hl_syncGetBlocks` batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcBatchConfig {
+    /// Heights per request; the sync server caps this at 500.
+    pub batch_size: usize,
+    /// Requests in flight at once.
+    pub max_concurrent_batches: usize,
+}
+
+impl Default for RpcBatchConfig {
+    fn default() -> Self {
+        Self { batch_size: 500, max_concurrent_batches: 20 }
+    }
+}
+
+/// Error of [`RpcBlockSource::collect_blocks`] when some batches failed, carrying the blocks of
+/// the batches that succeeded so that only the failed heights need to be fetched again.
+#[derive(Debug, thiserror::Error)]
+#[error("failed to fetch {} of the requested blocks: {}", failed_heights.len(), errors.join("; "))]
+pub struct PartialBlocksError {
+    /// Blocks that were fetched, in request order.
+    pub blocks: Vec<BlockAndReceipts>,
+    /// Heights of the failed batches, in request order.
+    pub failed_heights: Vec<u64>,
+    /// One error per failed batch.
+    pub errors: Vec<String>,
+}
 
 /// Block source that fetches blocks from a remote nanoreth node via RPC.
 ///
 /// Connects to another nanoreth node running with `--enable-sync-server`
 /// and fetches blocks through the `hl_sync` RPC namespace.
+///
+/// With a `ws://` or `wss://` URL, the source also subscribes to `hl_subscribeBlocks` and serves
+/// tip blocks from what the server pushes, falling back to request/response for anything else
+/// (historical ranges, or servers without the subscription). The same goes for a `unix://` URL,
+/// which reaches a sync server on the same host over its Unix domain socket. Either connection is
+/// established again once it drops, and the subscription with it.
+///
+/// With a local block store, blocks that are already stored locally are only confirmed by hash
+/// by servers speaking sync protocol version 2, instead of being downloaded again.
+///
+/// Consecutive blocks of a batch must be linked by their parent hashes. A block that doesn't
+/// match its child's parent hash, e.g. one served from a stale fork, is requested again through
+/// `hl_syncGetBlockByHash` before the batch fails. Pushed blocks are only served if they link to
+/// each other and to the last block served, and are fetched otherwise.
 #[derive(Debug, Clone)]
 pub struct RpcBlockSource {
-    client: Arc<HttpClient>,
+    client: RpcClient,
+    /// Blocks pushed through the WebSocket subscription, keyed by height.
+    pushed: Arc<Mutex<LruMap<u64, BlockAndReceipts>>>,
+    /// Height and hash of the highest block served, which pushed blocks must link to.
+    last_served: Arc<Mutex<Option<(u64, B256)>>>,
+    /// Blocks this node already has, advertised to the server as known.
+    local_blocks: Option<Arc<dyn SyncBlockReader>>,
+    /// Sync protocol version of the server, negotiated on first use.
+    protocol_version: Arc<OnceCell<u64>>,
+    batching: RpcBatchConfig,
     polling_interval: Duration,
+    chunk_size: u64,
     metrics: RpcBlockSourceMetrics,
+    source_metrics: BlockSourceMetrics,
 }
 
+/// Metrics specific to the RPC block source, next to the shared [`BlockSourceMetrics`].
 #[derive(Metrics, Clone)]
 #[metrics(scope = "block_source.rpc")]
 pub struct RpcBlockSourceMetrics {
-    /// How many times the RPC block source is polling for a block
-    pub polling_attempt: Counter,
-    /// How many times the RPC block source has fetched a block
-    pub fetched: Counter,
+    /// How many blocks were received through the block subscription
+    pub pushed: Counter,
+    /// How many times the sync server asked the RPC block source to back off
+    pub rate_limited: Counter,
+    /// How many blocks were confirmed by hash and read locally instead of downloaded
+    pub confirmed: Counter,
+    /// How many blocks were requested again by hash after not matching their child's parent hash
+    pub resolved_by_hash: Counter,
+    /// How many pushed blocks were fetched instead for not matching their parent's hash
+    pub pushed_unlinked: Counter,
+}
+
+/// Transport used to reach the remote sync server, derived from the URL scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcTransport {
+    Http,
+    Ws,
+    /// Unix domain socket, for a sync server on the same host.
+    Uds,
+}
+
+impl RpcTransport {
+    pub fn from_url(url: &str) -> Self {
+        if url.starts_with("ws://") || url.starts_with("wss://") {
+            Self::Ws
+        } else if url.starts_with("unix://") {
+            Self::Uds
+        } else {
+            Self::Http
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum RpcClient {
+    Http(Arc<HttpClient>),
+    Ws(Arc<Reconnecting<WsClient>>),
+    Uds(Arc<Reconnecting<UdsClient>>),
+}
+
+/// Connects a client to a URL.
+type Connect<C> = fn(String) -> BoxFuture<'static, Result<C, ClientError>>;
+
+fn connect_ws(url: String) -> BoxFuture<'static, Result<WsClient, ClientError>> {
+    async move { WsClientBuilder::default().request_timeout(REQUEST_TIMEOUT).build(&url).await }
+        .boxed()
+}
+
+fn connect_uds(url: String) -> BoxFuture<'static, Result<UdsClient, ClientError>> {
+    async move {
+        IpcClientBuilder::default()
+            .request_timeout(REQUEST_TIMEOUT)
+            .build(url.trim_start_matches("unix://"))
+            .await
+            .map_err(|err| ClientError::Transport(err.into()))
+    }
+    .boxed()
+}
+
+/// A client over a persistent connection, connected again once the connection drops.
+struct Reconnecting<C> {
+    url: String,
+    connect: Connect<C>,
+    client: Mutex<Arc<C>>,
+    /// Held while connecting again, so that callers noticing the same drop connect only once.
+    reconnecting: tokio::sync::Mutex<()>,
+}
+
+impl<C> std::fmt::Debug for Reconnecting<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reconnecting").field("url", &self.url).finish_non_exhaustive()
+    }
+}
+
+impl<C> Reconnecting<C> {
+    /// Connects to `url`.
+    async fn connect(url: String, connect: Connect<C>) -> Result<Self, ClientError> {
+        let client = connect(url.clone()).await?;
+        Ok(Self {
+            url,
+            connect,
+            client: Mutex::new(Arc::new(client)),
+            reconnecting: Default::default(),
+        })
+    }
+
+    fn current(&self) -> Arc<C> {
+        self.client.lock().unwrap().clone()
+    }
+
+    /// Replaces `stale`, a client whose connection dropped, unless that was done already.
+    async fn reconnect(&self, stale: &Arc<C>) -> Result<Arc<C>, ClientError> {
+        let _reconnecting = self.reconnecting.lock().await;
+        let current = self.current();
+        if !Arc::ptr_eq(&current, stale) {
+            return Ok(current);
+        }
+        let client = Arc::new((self.connect)(self.url.clone()).await?);
+        *self.client.lock().unwrap() = client.clone();
+        info!(url = %self.url, "Reconnected to the sync server");
+        Ok(client)
+    }
+}
+
+impl<C: ClientT> Reconnecting<C> {
+    /// Sends a request, connecting again for the next one if the connection dropped.
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        let client = self.current();
+        let result = client.request(method, params).await;
+        if let Err(ClientError::RestartNeeded(_)) = &result &&
+            let Err(err) = self.reconnect(&client).await
+        {
+            warn!(url = %self.url, %err, "Failed to reconnect to the sync server");
+        }
+        result
+    }
+}
+
+impl RpcClient {
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        match self {
+            Self::Http(client) => client.request(method, params).await,
+            Self::Ws(client) => client.request(method, params).await,
+            Self::Uds(client) => client.request(method, params).await,
+        }
+    }
+
+    /// Sends a request, backing off and retrying while the sync server is rate limiting us.
+    async fn request_with_backoff<R, Params>(
+        &self,
+        method: &str,
+        params: Params,
+        metrics: &RpcBlockSourceMetrics,
+    ) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Clone + Send,
+    {
+        let mut retries = 0;
+        loop {
+            match self.request(method, params.clone()).await {
+                Err(ClientError::Call(err))
+                    if err.code() == SYNC_RATE_LIMITED_CODE && retries < MAX_RATE_LIMITED_RETRIES =>
+                {
+                    retries += 1;
+                    let retry_after = err
+                        .data()
+                        .and_then(|data| serde_json::from_str::<RateLimitedData>(data.get()).ok())
+                        .map_or(DEFAULT_RETRY_AFTER, |data| {
+                            Duration::from_millis(data.retry_after_ms)
+                        })
+                        .min(MAX_RETRY_AFTER);
+                    metrics.rate_limited.increment(1);
+                    debug!(method, ?retry_after, retries, "Sync server is rate limiting us");
+                    tokio::time::sleep(retry_after).await;
+                }
+                result => return result,
+            }
+        }
+    }
 }
 
 impl RpcBlockSource {
-    pub fn new(url: String, polling_interval: Duration) -> Self {
+    /// Number of pushed blocks kept around until they are collected.
+    const PUSHED_CACHE_LIMIT: u32 = 1024;
+    const DEFAULT_CHUNK_SIZE: u64 = 200;
+
+    pub fn new(url: String, polling_interval: Duration) -> eyre::Result<Self> {
         let client = HttpClientBuilder::default()
-            .request_timeout(Duration::from_secs(120))
+            .request_timeout(REQUEST_TIMEOUT)
             .build(&url)
-            .unwrap_or_else(|e| panic!("Failed to build RPC client for {url}: {e}"));
+            .map_err(|e| eyre::eyre!("Failed to build RPC client for {url}: {e}"))?;
         info!("RPC block source connected to {url}");
-        Self { client: Arc::new(client), polling_interval, metrics: RpcBlockSourceMetrics::default() }
+        Ok(Self::with_client(RpcClient::Http(Arc::new(client)), polling_interval))
+    }
+
+    /// Creates a block source for `url`, using a WebSocket client for `ws://` and `wss://` URLs
+    /// and a Unix domain socket for `unix://` URLs.
+    pub async fn connect(url: String, polling_interval: Duration) -> eyre::Result<Self> {
+        match RpcTransport::from_url(&url) {
+            RpcTransport::Http => Self::new(url, polling_interval),
+            RpcTransport::Ws => {
+                let client = Reconnecting::connect(url.clone(), connect_ws).await.map_err(|e| {
+                    eyre::eyre!("Failed to connect to the sync server at {url}: {e}")
+                })?;
+                info!("RPC block source connected to {url} over WebSocket");
+                let client = Arc::new(client);
+                let source = Self::with_client(RpcClient::Ws(client.clone()), polling_interval);
+                source.spawn_block_subscription(client);
+                Ok(source)
+            }
+            RpcTransport::Uds => {
+                let client =
+                    Reconnecting::connect(url.clone(), connect_uds).await.map_err(|e| {
+                        eyre::eyre!("Failed to connect to the sync server at {url}: {e}")
+                    })?;
+                info!("RPC block source connected to {url} over a Unix domain socket");
+                let client = Arc::new(client);
+                let source = Self::with_client(RpcClient::Uds(client.clone()), polling_interval);
+                source.spawn_block_subscription(client);
+                Ok(source)
+            }
+        }
+    }
+
+    /// Asks the sync server at `url` for its latest block once, without setting up a source.
+    pub async fn probe(url: &str) -> Result<Option<u64>, String> {
+        let client = match RpcTransport::from_url(url) {
+            RpcTransport::Http => HttpClientBuilder::default()
+                .request_timeout(REQUEST_TIMEOUT)
+                .build(url)
+                .map(|client| RpcClient::Http(Arc::new(client)))
+                .map_err(|err| format!("invalid URL {url}: {err}"))?,
+            RpcTransport::Ws => Reconnecting::connect(url.to_owned(), connect_ws)
+                .await
+                .map(|client| RpcClient::Ws(Arc::new(client)))
+                .map_err(|err| format!("{url} is unreachable: {err}"))?,
+            RpcTransport::Uds => Reconnecting::connect(url.to_owned(), connect_uds)
+                .await
+                .map(|client| RpcClient::Uds(Arc::new(client)))
+                .map_err(|err| format!("{url} is unreachable: {err}"))?,
+        };
+        let response: SyncLatestBlockResponse = client
+            .request("hl_syncLatestBlockNumber", Vec::<u64>::new())
+            .await
+            .map_err(|err| format!("{url} is unreachable: {err}"))?;
+        Ok(response.latest())
+    }
+
+    fn with_client(client: RpcClient, polling_interval: Duration) -> Self {
+        Self {
+            client,
+            pushed: Arc::new(Mutex::new(LruMap::new(Self::PUSHED_CACHE_LIMIT))),
+            last_served: Arc::new(Mutex::new(None)),
+            local_blocks: None,
+            protocol_version: Arc::new(OnceCell::new()),
+            batching: RpcBatchConfig::default(),
+            polling_interval,
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            metrics: RpcBlockSourceMetrics::default(),
+            source_metrics: BlockSourceMetrics::for_kind("rpc"),
+        }
+    }
+
+    /// Keeps `client` subscribed to pushed blocks, subscribing again whenever the subscription
+    /// ends. Blocks are requested meanwhile, so a subscription that fails only delays the tip.
+    fn spawn_block_subscription<C>(&self, client: Arc<Reconnecting<C>>)
+    where
+        C: SubscriptionClientT + Send + Sync + 'static,
+    {
+        let pushed = self.pushed.clone();
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            let mut backoff = MIN_RESUBSCRIBE_BACKOFF;
+            loop {
+                let current = client.current();
+                match current
+                    .subscribe::<Bytes, _>(
+                        "hl_subscribeBlocks",
+                        rpc_params![],
+                        "hl_unsubscribeBlocks",
+                    )
+                    .await
+                {
+                    Ok(subscription) => {
+                        backoff = MIN_RESUBSCRIBE_BACKOFF;
+                        receive_pushed_blocks(subscription, &pushed, &metrics).await;
+                        warn!("Block subscription closed, polling until subscribed again");
+                    }
+                    Err(ClientError::Call(err)) if err.code() == METHOD_NOT_FOUND_CODE => {
+                        info!("Block subscription unavailable, polling instead");
+                        return;
+                    }
+                    Err(ClientError::RestartNeeded(_)) => match client.reconnect(&current).await {
+                        Ok(_) => continue,
+                        Err(err) => debug!(%err, "Failed to reconnect for the block subscription"),
+                    },
+                    Err(err) => debug!(%err, "Failed to subscribe to blocks"),
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RESUBSCRIBE_BACKOFF);
+            }
+        });
+    }
+
+    /// Sets the local block store used to skip downloading blocks this node already has.
+    pub fn with_local_blocks(mut self, local_blocks: Option<Arc<dyn SyncBlockReader>>) -> Self {
+        self.local_blocks = local_blocks;
+        self
+    }
+
+    /// Sets how `collect_blocks` batches its requests.
+    pub fn with_batching(mut self, batching: RpcBatchConfig) -> Self {
+        self.batching = batching;
+        self
+    }
+
+    /// Overrides the recommended chunk size.
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    fn take_pushed(&self, height: u64) -> Option<BlockAndReceipts> {
+        self.pushed.lock().unwrap().remove(&height)
+    }
+
+    /// Takes the blocks pushed at `heights` if all of them were pushed, each linked by its parent
+    /// hash to the block below it, the lowest one to the last block served. The pushed blocks
+    /// are dropped otherwise, to be fetched instead.
+    fn take_pushed_chain(&self, heights: &[u64]) -> Option<Vec<BlockAndReceipts>> {
+        let blocks: Vec<_> =
+            heights.iter().filter_map(|height| self.take_pushed(*height)).collect();
+        if blocks.is_empty() || blocks.len() != heights.len() {
+            return None;
+        }
+        let mut parent = *self.last_served.lock().unwrap();
+        for block in &blocks {
+            if let Some((height, hash)) = parent &&
+                height + 1 == block.number() &&
+                hash != block.parent_hash()
+            {
+                warn!(
+                    height = block.number(),
+                    "Pushed block does not match its parent's hash, fetching it instead"
+                );
+                self.metrics.pushed_unlinked.increment(1);
+                return None;
+            }
+            parent = Some((block.number(), block.hash()));
+        }
+        Some(blocks)
+    }
+}
+
+/// Records `blocks` as served, so that pushed blocks are checked against the highest of them.
+fn record_served(last_served: &Mutex<Option<(u64, B256)>>, blocks: &[BlockAndReceipts]) {
+    let Some(highest) = blocks.iter().max_by_key(|block| block.number()) else { return };
+    let mut last_served = last_served.lock().unwrap();
+    if last_served.is_none_or(|(height, _)| height <= highest.number()) {
+        *last_served = Some((highest.number(), highest.hash()));
+    }
+}
+
+/// Returns the local block store if the server can confirm known blocks.
+async fn negotiate_local_blocks(
+    client: &RpcClient,
+    protocol_version: &OnceCell<u64>,
+    local_blocks: Option<Arc<dyn SyncBlockReader>>,
+) -> Option<Arc<dyn SyncBlockReader>> {
+    let local_blocks = local_blocks?;
+    let version = protocol_version
+        .get_or_try_init(|| async {
+            match client.request::<u64, _>("hl_syncProtocolVersion", rpc_params![]).await {
+                Ok(version) => Ok(version),
+                // Servers predating version negotiation
+                Err(ClientError::Call(err)) if err.code() == METHOD_NOT_FOUND_CODE => Ok(1),
+                Err(err) => Err(err),
+            }
+        })
+        .await
+        .copied()
+        .unwrap_or(1);
+    (version >= 2).then_some(local_blocks)
+}
+
+/// Returns the `(height, hash)` pairs of the requested blocks that are stored locally.
+fn known_blocks(local_blocks: &dyn SyncBlockReader, heights: &[u64]) -> Vec<(u64, B256)> {
+    heights
+        .iter()
+        .filter_map(|&height| Some((height, local_blocks.block_hash(height).ok()??)))
+        .collect()
+}
+
+/// Maps a failed request for `height`, the first height of a batch, counting it under its error
+/// class: a block above the server's tip isn't available yet, a server without the sync methods
+/// or rejecting our requests won't serve them however often they are retried, and anything else,
+/// including running out of rate limit retries, is a transport error.
+fn request_error(metrics: &BlockSourceMetrics, height: u64, err: ClientError) -> BlockSourceError {
+    match err {
+        ClientError::Call(err) if err.code() == HlErrorCode::BlockNotServed.code() => {
+            metrics.errors_not_found.increment(1);
+            BlockSourceError::NotFoundYet { height }
+        }
+        ClientError::Call(err)
+            if matches!(err.code(), METHOD_NOT_FOUND_CODE | INVALID_PARAMS_CODE) =>
+        {
+            metrics.errors_transport.increment(1);
+            BlockSourceError::fatal(ClientError::Call(err))
+        }
+        err => {
+            metrics.errors_transport.increment(1);
+            BlockSourceError::backend(err)
+        }
+    }
+}
+
+fn decode(bytes: &[u8]) -> eyre::Result<Vec<BlockAndReceipts>> {
+    Ok(utils::decode_rmp_lz4(bytes)?)
+}
+
+/// Keeps the blocks pushed through `subscription` until it ends. A push that fails to decode is
+/// skipped, its block being requested instead.
+async fn receive_pushed_blocks(
+    mut subscription: Subscription<Bytes>,
+    pushed: &Mutex<LruMap<u64, BlockAndReceipts>>,
+    metrics: &RpcBlockSourceMetrics,
+) {
+    while let Some(item) = subscription.next().await {
+        let blocks = match item.map_err(eyre::Error::from).and_then(|bytes| decode(&bytes)) {
+            Ok(blocks) => blocks,
+            Err(err) => {
+                warn!("Skipping a pushed block that failed to decode: {err}");
+                continue;
+            }
+        };
+        metrics.pushed.increment(blocks.len() as u64);
+        let mut pushed = pushed.lock().unwrap();
+        for block in blocks {
+            pushed.insert(block.number(), block);
+        }
     }
 }
 
+/// Requests `heights` through `hl_syncGetBlocks`, requesting again while the response fails to
+/// decode, e.g. when its checksum doesn't match.
+async fn request_blocks(
+    client: &RpcClient,
+    heights: &[u64],
+    known: Vec<(u64, B256)>,
+    metrics: &RpcBlockSourceMetrics,
+    source_metrics: &BlockSourceMetrics,
+) -> BlockSourceResult<(SyncBlocksResponse, Vec<BlockAndReceipts>, Option<u64>)> {
+    let mut retries = 0;
+    loop {
+        let started = Instant::now();
+        let response: Result<SyncBlocksResponse, _> = if known.is_empty() {
+            client.request_with_backoff("hl_syncGetBlocks", (heights.to_vec(),), metrics).await
+        } else {
+            let params = (heights.to_vec(), known.clone());
+            client.request_with_backoff("hl_syncGetBlocks", params, metrics).await
+        };
+        let response = response.map_err(|err| request_error(source_metrics, heights[0], err))?;
+        source_metrics.fetch_latency.record(started.elapsed().as_secs_f64());
+        source_metrics.bytes_fetched.increment(response.encoded_len() as u64);
+
+        let started = Instant::now();
+        let decoded = response.decode();
+        source_metrics.decode_latency.record(started.elapsed().as_secs_f64());
+        match decoded {
+            Ok((blocks, truncated_at)) => return Ok((response, blocks, truncated_at)),
+            Err(err) if retries < MAX_CORRUPT_RETRIES => {
+                retries += 1;
+                source_metrics.errors_decode.increment(1);
+                warn!(first = heights[0], retries, %err, "Corrupt sync response, requesting again");
+            }
+            Err(err) => {
+                source_metrics.errors_decode.increment(1);
+                return Err(BlockSourceError::corrupt(heights[0], format!("{err:#}")));
+            }
+        }
+    }
+}
+
+/// Fetches a batch through `hl_syncGetBlocks`, re-requesting the remainder whenever the server
+/// truncates its response.
+async fn fetch_batch(
+    client: &RpcClient,
+    mut heights: Vec<u64>,
+    metrics: &RpcBlockSourceMetrics,
+    source_metrics: &BlockSourceMetrics,
+    local_blocks: Option<&dyn SyncBlockReader>,
+) -> BlockSourceResult<Vec<BlockAndReceipts>> {
+    let mut blocks = Vec::with_capacity(heights.len());
+    loop {
+        let known = local_blocks.map(|local| known_blocks(local, &heights)).unwrap_or_default();
+        let (response, fetched, truncated_at) =
+            request_blocks(client, &heights, known, metrics, source_metrics).await?;
+
+        if response.confirmed().is_empty() {
+            blocks.extend(fetched);
+        } else {
+            let local_blocks = local_blocks.ok_or_else(|| {
+                BlockSourceError::backend("Server confirmed blocks that were not requested")
+            })?;
+            let mut served: HashMap<u64, BlockAndReceipts> =
+                fetched.into_iter().map(|block| (block.number(), block)).collect();
+            for &(height, hash) in response.confirmed() {
+                let block = local_blocks.read_block_and_receipts(height)?;
+                if block.hash() != hash {
+                    let err = format!("Local block {height} does not match {hash}");
+                    return Err(BlockSourceError::backend(err));
+                }
+                served.insert(height, block);
+            }
+            metrics.confirmed.increment(response.confirmed().len() as u64);
+            blocks.extend(heights.iter().filter_map(|height| served.remove(height)));
+        }
+
+        let Some(truncated_at) = truncated_at else {
+            resolve_parent_links(client, &mut blocks, metrics, source_metrics).await?;
+            return Ok(blocks);
+        };
+        let position = heights
+            .iter()
+            .position(|height| *height == truncated_at)
+            .filter(|position| *position > 0)
+            .ok_or_else(|| {
+                let reason = format!("invalid truncation height {truncated_at} in response");
+                BlockSourceError::corrupt(heights[0], reason)
+            })?;
+        heights = heights.split_off(position);
+    }
+}
+
+/// Requests the block at `height` with `hash` through `hl_syncGetBlockByHash`.
+async fn request_block_by_hash(
+    client: &RpcClient,
+    height: u64,
+    hash: B256,
+    metrics: &RpcBlockSourceMetrics,
+    source_metrics: &BlockSourceMetrics,
+) -> BlockSourceResult<Option<BlockAndReceipts>> {
+    let started = Instant::now();
+    let bytes: Option<Bytes> = client
+        .request_with_backoff("hl_syncGetBlockByHash", (hash,), metrics)
+        .await
+        .map_err(|err| request_error(source_metrics, height, err))?;
+    source_metrics.fetch_latency.record(started.elapsed().as_secs_f64());
+    let Some(bytes) = bytes else { return Ok(None) };
+    source_metrics.bytes_fetched.increment(bytes.len() as u64);
+    let block = decode(&bytes)
+        .map_err(|err| {
+            source_metrics.errors_decode.increment(1);
+            BlockSourceError::corrupt(height, format!("{err:#}"))
+        })?
+        .into_iter()
+        .next();
+    Ok(block)
+}
+
+/// Checks that consecutive heights of `blocks` are linked by their parent hashes.
+///
+/// Walking down from the highest block, a block whose hash doesn't match the parent hash of the
+/// block above it is replaced by the block with that hash, which is then checked against the
+/// block below it in turn. Fails if the server doesn't have the expected block.
+async fn resolve_parent_links(
+    client: &RpcClient,
+    blocks: &mut [BlockAndReceipts],
+    metrics: &RpcBlockSourceMetrics,
+    source_metrics: &BlockSourceMetrics,
+) -> BlockSourceResult<()> {
+    for index in (0..blocks.len().saturating_sub(1)).rev() {
+        let (block, child) = (&blocks[index], &blocks[index + 1]);
+        let height = block.number();
+        let expected = child.parent_hash();
+        if height + 1 != child.number() || block.hash() == expected {
+            continue;
+        }
+
+        warn!(
+            height,
+            served = %block.hash(),
+            %expected,
+            "Block does not match its child's parent hash, requesting it by hash"
+        );
+        let resolved = request_block_by_hash(client, height, expected, metrics, source_metrics)
+            .await?
+            .ok_or_else(|| {
+                // Retryable, as the server may still switch to the canonical fork
+                BlockSourceError::backend(format!(
+                    "Block {height} does not match the parent hash {expected} of block {}, \
+                     which the server does not have",
+                    height + 1
+                ))
+            })?;
+        if resolved.number() != height || resolved.hash() != expected {
+            let reason = format!(
+                "requested by hash {expected}, got block {} {}",
+                resolved.number(),
+                resolved.hash()
+            );
+            return Err(BlockSourceError::corrupt(height, reason));
+        }
+        metrics.resolved_by_hash.increment(1);
+        blocks[index] = resolved;
+    }
+    Ok(())
+}
+
 impl BlockSource for RpcBlockSource {
-    fn collect_block(&self, height: u64) -> BoxFuture<'static, eyre::Result<BlockAndReceipts>> {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
         let client = self.client.clone();
         let metrics = self.metrics.clone();
+        let source_metrics = self.source_metrics.clone();
+        let last_served = self.last_served.clone();
+        let pushed = self.take_pushed_chain(&[height]).and_then(|mut blocks| blocks.pop());
         async move {
-            metrics.polling_attempt.increment(1);
-            let bytes: Bytes = client.request("hl_syncGetBlock", (height,)).await?;
-            let mut decoder = lz4_flex::frame::FrameDecoder::new(&bytes[..]);
-            let blocks: Vec<BlockAndReceipts> = rmp_serde::from_read(&mut decoder)?;
-            metrics.fetched.increment(1);
-            Ok(blocks[0].clone())
+            source_metrics.polling_attempt.increment(1);
+            if let Some(block) = pushed {
+                source_metrics.fetched.increment(1);
+                record_served(&last_served, std::slice::from_ref(&block));
+                return Ok(block);
+            }
+            let mut retries = 0;
+            let blocks = loop {
+                let started = Instant::now();
+                let bytes: Bytes = client
+                    .request_with_backoff("hl_syncGetBlock", (height,), &metrics)
+                    .await
+                    .map_err(|err| request_error(&source_metrics, height, err))?;
+                source_metrics.fetch_latency.record(started.elapsed().as_secs_f64());
+                source_metrics.bytes_fetched.increment(bytes.len() as u64);
+
+                let started = Instant::now();
+                let decoded = decode(&bytes);
+                source_metrics.decode_latency.record(started.elapsed().as_secs_f64());
+                match decoded {
+                    Ok(blocks) => break blocks,
+                    Err(err) if retries < MAX_CORRUPT_RETRIES => {
+                        retries += 1;
+                        source_metrics.errors_decode.increment(1);
+                        warn!(height, retries, %err, "Corrupt sync response, requesting again");
+                    }
+                    Err(err) => {
+                        source_metrics.errors_decode.increment(1);
+                        return Err(BlockSourceError::corrupt(height, format!("{err:#}")));
+                    }
+                }
+            };
+            let block = blocks
+                .into_iter()
+                .next()
+                .ok_or_else(|| BlockSourceError::corrupt(height, "response holds no block"))?;
+            source_metrics.fetched.increment(1);
+            record_served(&last_served, std::slice::from_ref(&block));
+            Ok(block)
         }
         .boxed()
     }
 
     fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
         let client = self.client.clone();
+        let polling_interval = self.polling_interval;
         async move {
-            let result: Option<u64> =
-                client.request("hl_syncLatestBlockNumber", Vec::<u64>::new()).await.ok()?;
-            info!("Latest block number from remote: {:?}", result);
-            result
+            let mut held_back = false;
+            loop {
+                let response: SyncLatestBlockResponse =
+                    client.request("hl_syncLatestBlockNumber", Vec::<u64>::new()).await.ok()?;
+                // A server that is far behind its own source would only make us chase it
+                if response.ready() {
+                    info!("Latest block number from remote: {:?}", response.latest());
+                    return response.latest();
+                }
+                if !held_back {
+                    info!("Remote is catching up with its own source, holding back: {response:?}");
+                    held_back = true;
+                }
+                tokio::time::sleep(polling_interval).await;
+            }
         }
         .boxed()
     }
@@ -68,51 +790,634 @@ impl BlockSource for RpcBlockSource {
     fn collect_blocks(
         &self,
         heights: Vec<u64>,
-    ) -> BoxFuture<'static, eyre::Result<Vec<BlockAndReceipts>>> {
+    ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
+        // Serve the tip from the subscription only if it covers the whole request
+        if let Some(blocks) = self.take_pushed_chain(&heights) {
+            self.source_metrics.polling_attempt.increment(blocks.len() as u64);
+            self.source_metrics.fetched.increment(blocks.len() as u64);
+            record_served(&self.last_served, &blocks);
+            return async move { Ok(blocks) }.boxed();
+        }
+
         let client = self.client.clone();
         let metrics = self.metrics.clone();
+        let source_metrics = self.source_metrics.clone();
+        let protocol_version = self.protocol_version.clone();
+        let local_blocks = self.local_blocks.clone();
+        let last_served = self.last_served.clone();
+        let RpcBatchConfig { batch_size, max_concurrent_batches } = self.batching;
         async move {
-            const BATCH_SIZE: usize = 500;
-            const MAX_CONCURRENT_BATCHES: usize = 20;
+            let local_blocks =
+                negotiate_local_blocks(&client, &protocol_version, local_blocks).await;
 
             let batches: Vec<Vec<u64>> =
-                heights.chunks(BATCH_SIZE).map(|c| c.to_vec()).collect();
+                heights.chunks(batch_size.max(1)).map(|c| c.to_vec()).collect();
 
-            let results: Vec<eyre::Result<Vec<BlockAndReceipts>>> =
+            let results: Vec<(Vec<u64>, BlockSourceResult<Vec<BlockAndReceipts>>)> =
                 futures::stream::iter(batches)
                     .map(|batch| {
                         let client = client.clone();
                         let metrics = metrics.clone();
+                        let source_metrics = source_metrics.clone();
+                        let local_blocks = local_blocks.clone();
                         async move {
-                            metrics.polling_attempt.increment(batch.len() as u64);
-                            let bytes: Bytes =
-                                client.request("hl_syncGetBlocks", (batch,)).await?;
-                            let mut decoder =
-                                lz4_flex::frame::FrameDecoder::new(&bytes[..]);
-                            let blocks: Vec<BlockAndReceipts> =
-                                rmp_serde::from_read(&mut decoder)?;
-                            metrics.fetched.increment(blocks.len() as u64);
-                            Ok(blocks)
+                            source_metrics.polling_attempt.increment(batch.len() as u64);
+                            let result = fetch_batch(
+                                &client,
+                                batch.clone(),
+                                &metrics,
+                                &source_metrics,
+                                local_blocks.as_deref(),
+                            )
+                            .await;
+                            (batch, result)
                         }
                     })
-                    .buffered(MAX_CONCURRENT_BATCHES)
+                    .buffered(max_concurrent_batches.max(1))
                     .collect()
                     .await;
 
-            let mut all_blocks = Vec::with_capacity(heights.len());
-            for result in results {
-                all_blocks.extend(result?);
+            // Keep going past failed batches, so that the caller only retries what is missing
+            let mut blocks = Vec::with_capacity(heights.len());
+            let mut failed_heights = Vec::new();
+            let mut errors = Vec::new();
+            let mut retryable = true;
+            for (batch, result) in results {
+                match result {
+                    Ok(fetched) => {
+                        source_metrics.fetched.increment(fetched.len() as u64);
+                        blocks.extend(fetched);
+                    }
+                    Err(err) => {
+                        warn!(first = batch[0], count = batch.len(), %err, "Failed to fetch batch");
+                        failed_heights.extend(batch);
+                        errors.push(err.to_string());
+                        retryable &= err.is_retryable();
+                    }
+                }
+            }
+            if failed_heights.is_empty() {
+                record_served(&last_served, &blocks);
+                Ok(blocks)
+            } else {
+                let partial = PartialBlocksError { blocks, failed_heights, errors };
+                Err(BlockSourceError::Backend { retryable, source: partial.into() })
             }
-            Ok(all_blocks)
         }
         .boxed()
     }
 
     fn recommended_chunk_size(&self) -> u64 {
-        200
+        self.chunk_size
     }
 
     fn polling_interval(&self) -> Duration {
         self.polling_interval
     }
+
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        let client = self.client.clone();
+        async move {
+            let response: SyncLatestBlockResponse =
+                client.request("hl_syncLatestBlockNumber", Vec::<u64>::new()).await.ok()?;
+            response.chain_id()
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        addons::{
+            sync_limits::SyncServerLimits,
+            sync_server::{
+                DEFAULT_MAX_RESPONSE_BYTES, DEFAULT_PAYLOAD_CACHE_SIZE, HlSyncApiServer,
+                HlSyncServer, SYNC_PROTOCOL_VERSION, SyncSourceStatus, serve_over_tcp,
+                serve_over_uds,
+            },
+        },
+        pseudo_peer::{BlockSourceArgs, BlockSourceType, TrackedBlockSource},
+        test_utils::{CountingReader, empty_block, header_block},
+    };
+    use alloy_consensus::Header;
+    use clap::Parser;
+    use jsonrpsee::{
+        RpcModule,
+        server::{Server, ServerHandle},
+    };
+    use jsonrpsee_types::{ErrorObject, error::INTERNAL_ERROR_CODE};
+    use std::{collections::BTreeMap, time::Instant};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: BlockSourceArgs,
+    }
+
+    async fn rpc_url(block_source: &str) -> String {
+        let cli = Cli::parse_from(["reth-hl", "--block-source", block_source]);
+        match cli.args.parse().await.unwrap().unwrap().source_type {
+            BlockSourceType::Rpc { url, .. } => url,
+            other => panic!("expected an RPC block source, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ws_url_selects_ws_transport() {
+        for source in ["ws://localhost:8546", "rpc://ws://localhost:8546"] {
+            let url = rpc_url(source).await;
+            assert_eq!(url, "ws://localhost:8546");
+            assert_eq!(RpcTransport::from_url(&url), RpcTransport::Ws);
+        }
+        assert_eq!(RpcTransport::from_url(&rpc_url("wss://node:443").await), RpcTransport::Ws);
+    }
+
+    #[tokio::test]
+    async fn http_url_selects_http_transport() {
+        let url = rpc_url("rpc://localhost:8545").await;
+        assert_eq!(url, "http://localhost:8545");
+        assert_eq!(RpcTransport::from_url(&url), RpcTransport::Http);
+    }
+
+    #[derive(Debug)]
+    struct EmptyBlockReader;
+
+    impl SyncBlockReader for EmptyBlockReader {
+        fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+            Ok(empty_block(number))
+        }
+
+        fn best_block_number(&self) -> eyre::Result<u64> {
+            Ok(u64::MAX)
+        }
+    }
+
+    fn sync_server(reader: Arc<dyn SyncBlockReader>, limits: &SyncServerLimits) -> HlSyncServer {
+        HlSyncServer::new(reader, DEFAULT_MAX_RESPONSE_BYTES, DEFAULT_PAYLOAD_CACHE_SIZE, limits)
+    }
+
+    /// Starts `sync_server` over WebSocket.
+    async fn serve(sync_server: HlSyncServer) -> (String, ServerHandle) {
+        serve_at("127.0.0.1:0", sync_server).await
+    }
+
+    /// Starts `sync_server` over WebSocket at `addr`.
+    async fn serve_at(addr: &str, sync_server: HlSyncServer) -> (String, ServerHandle) {
+        let server = Server::builder().build(addr).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.start(sync_server.into_rpc());
+        (format!("ws://{addr}"), handle)
+    }
+
+    /// Starts a sync server serving [`EmptyBlockReader`] blocks.
+    async fn start_sync_server(limits: SyncServerLimits) -> (String, ServerHandle) {
+        serve(sync_server(Arc::new(EmptyBlockReader), &limits)).await
+    }
+
+    #[tokio::test]
+    async fn blocks_round_trip_over_a_unix_domain_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sync.ipc");
+        let server = sync_server(Arc::new(EmptyBlockReader), &SyncServerLimits::default());
+        let handle = serve_over_uds(server.into_rpc(), &path).await.unwrap();
+
+        let url = rpc_url(&format!("unix://{}", path.display())).await;
+        assert_eq!(RpcTransport::from_url(&url), RpcTransport::Uds);
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+        let blocks = source.collect_blocks(vec![1, 2, 3]).await.unwrap();
+        let expected = [1, 2, 3].map(|number| EmptyBlockReader.read_block_and_receipts(number));
+        assert_eq!(
+            blocks.iter().map(|b| b.hash()).collect::<Vec<_>>(),
+            expected.map(|block| block.unwrap().hash())
+        );
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn locally_stored_blocks_are_confirmed_instead_of_downloaded() {
+        let (url, handle) = start_sync_server(SyncServerLimits::default()).await;
+        let local_blocks = CountingReader::new(2);
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10))
+            .await
+            .unwrap()
+            .with_local_blocks(Some(local_blocks.clone() as Arc<dyn SyncBlockReader>));
+
+        let blocks = source.collect_blocks(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(blocks.iter().map(|b| b.number()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(local_blocks.reads(), 2);
+        assert_eq!(source.protocol_version.get(), Some(&SYNC_PROTOCOL_VERSION));
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn rate_limited_clients_of_one_ip_back_off() {
+        let limits = SyncServerLimits { max_blocks_per_second: Some(4), ..Default::default() };
+        let server = sync_server(Arc::new(EmptyBlockReader), &limits);
+        let (addr, handle) =
+            serve_over_tcp(server.into_rpc(), ([127, 0, 0, 1], 0).into()).await.unwrap();
+        // Two connections from the same IP share its budget
+        let url = format!("ws://{addr}");
+        let first = RpcBlockSource::connect(url.clone(), Duration::from_millis(10)).await.unwrap();
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+
+        // The first request takes twice the budget, leaving a second worth of blocks to wait for
+        let blocks = first.collect_blocks((1..=8).collect()).await.unwrap();
+        assert_eq!(blocks.len(), 8);
+
+        let err = source
+            .client
+            .request::<SyncBlocksResponse, _>("hl_syncGetBlocks", (vec![9u64],))
+            .await
+            .unwrap_err();
+        let ClientError::Call(err) = err else { panic!("expected a call error, got {err:?}") };
+        assert_eq!(err.code(), SYNC_RATE_LIMITED_CODE);
+        let data: RateLimitedData = serde_json::from_str(err.data().unwrap().get()).unwrap();
+        assert!(data.retry_after_ms > 0);
+
+        // The block source waits for the budget to refill instead of failing
+        let started = Instant::now();
+        let blocks = source.collect_blocks(vec![9, 10]).await.unwrap();
+        assert_eq!(blocks.iter().map(|b| b.number()).collect::<Vec<_>>(), vec![9, 10]);
+        assert!(started.elapsed() >= Duration::from_millis(500));
+
+        handle.stop().unwrap();
+    }
+
+    /// Serves [`EmptyBlockReader`] blocks, except for one height that fails to read.
+    #[derive(Debug)]
+    struct FailingBlockReader {
+        failing: u64,
+    }
+
+    impl SyncBlockReader for FailingBlockReader {
+        fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+            eyre::ensure!(number != self.failing, "Block {number} is unreadable");
+            EmptyBlockReader.read_block_and_receipts(number)
+        }
+
+        fn best_block_number(&self) -> eyre::Result<u64> {
+            Ok(u64::MAX)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_batch_keeps_other_batches() {
+        let reader = Arc::new(FailingBlockReader { failing: 8 });
+        let (url, handle) = serve(sync_server(reader, &SyncServerLimits::default())).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10))
+            .await
+            .unwrap()
+            .with_batching(RpcBatchConfig { batch_size: 3, max_concurrent_batches: 2 });
+
+        let err = source.collect_blocks((1..=12).collect()).await.unwrap_err();
+        let partial = err.partial_blocks().unwrap();
+        assert_eq!(partial.failed_heights, vec![7, 8, 9]);
+        assert_eq!(partial.errors.len(), 1);
+        let numbers = partial.blocks.iter().map(|b| b.number()).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![1, 2, 3, 4, 5, 6, 10, 11, 12]);
+
+        handle.stop().unwrap();
+    }
+
+    /// Serves a chain of linked blocks, except for a block from a stale fork at one height.
+    /// Blocks of either fork are served by hash.
+    #[derive(Debug)]
+    struct ForkedReader {
+        canonical: Vec<BlockAndReceipts>,
+        stale: BlockAndReceipts,
+    }
+
+    impl ForkedReader {
+        fn new(length: u64, stale_height: u64) -> Self {
+            let mut parent_hash = B256::ZERO;
+            let canonical: Vec<_> = (0..=length)
+                .map(|number| {
+                    let block = header_block(Header { number, parent_hash, ..Default::default() });
+                    parent_hash = block.hash();
+                    block
+                })
+                .collect();
+            let stale = header_block(Header {
+                number: stale_height,
+                parent_hash: canonical[stale_height as usize - 1].hash(),
+                extra_data: Bytes::from_static(b"stale"),
+                ..Default::default()
+            });
+            Self { canonical, stale }
+        }
+    }
+
+    impl SyncBlockReader for ForkedReader {
+        fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+            if number == self.stale.number() {
+                return Ok(self.stale.clone());
+            }
+            Ok(self.canonical[number as usize].clone())
+        }
+
+        fn best_block_number(&self) -> eyre::Result<u64> {
+            Ok(self.canonical.len() as u64 - 1)
+        }
+
+        fn read_block_by_hash(&self, hash: B256) -> eyre::Result<Option<BlockAndReceipts>> {
+            Ok(self.canonical.iter().chain([&self.stale]).find(|b| b.hash() == hash).cloned())
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_block_is_resolved_by_hash() {
+        let reader = Arc::new(ForkedReader::new(10, 5));
+        let canonical = reader.canonical.clone();
+        let (url, handle) = serve(sync_server(reader, &SyncServerLimits::default())).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+
+        // Block 5 by height is from the stale fork, so block 6 doesn't link to it
+        let stale = source.collect_block(5).await.unwrap();
+        assert_ne!(stale.hash(), canonical[5].hash());
+
+        let blocks = source.collect_blocks((1..=10).collect()).await.unwrap();
+        let hashes: Vec<_> = blocks.iter().map(|b| b.hash()).collect();
+        let expected: Vec<_> = canonical[1..].iter().map(|b| b.hash()).collect();
+        assert_eq!(hashes, expected);
+
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn pushed_blocks_that_do_not_link_are_fetched() {
+        let reader = Arc::new(ForkedReader::new(10, 10));
+        let canonical = reader.canonical.clone();
+        let (url, handle) = serve(sync_server(reader, &SyncServerLimits::default())).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+        let fork_block = |number| {
+            let parent_hash = B256::repeat_byte(1);
+            header_block(Header { number, parent_hash, ..Default::default() })
+        };
+        let push = |block: BlockAndReceipts| {
+            source.pushed.lock().unwrap().insert(block.number(), block);
+        };
+
+        // Block 4 of another fork doesn't link to the served block 3, so the push is fetched
+        source.collect_block(3).await.unwrap();
+        push(fork_block(4));
+        push(canonical[5].clone());
+        push(canonical[6].clone());
+        let blocks = source.collect_blocks(vec![4, 5, 6]).await.unwrap();
+        let hashes: Vec<_> = blocks.iter().map(|b| b.hash()).collect();
+        let expected: Vec<_> = canonical[4..=6].iter().map(|b| b.hash()).collect();
+        assert_eq!(hashes, expected);
+        assert!((4..=6).all(|height| source.take_pushed(height).is_none()));
+
+        // Linked pushes are served without asking the server
+        handle.stop().unwrap();
+        handle.stopped().await;
+        push(canonical[7].clone());
+        assert_eq!(source.collect_block(7).await.unwrap().hash(), canonical[7].hash());
+        push(fork_block(8));
+        let err = source.collect_block(8).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::Backend { retryable: true, .. }), "{err}");
+        assert!(source.take_pushed(8).is_none());
+    }
+
+    #[tokio::test]
+    async fn unresolvable_parent_link_fails_the_batch() {
+        // Serves the stale block by height, but doesn't know the canonical one by hash
+        #[derive(Debug)]
+        struct StaleOnly(ForkedReader);
+
+        impl SyncBlockReader for StaleOnly {
+            fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+                self.0.read_block_and_receipts(number)
+            }
+
+            fn best_block_number(&self) -> eyre::Result<u64> {
+                self.0.best_block_number()
+            }
+        }
+
+        let reader = Arc::new(StaleOnly(ForkedReader::new(10, 5)));
+        let (url, handle) = serve(sync_server(reader, &SyncServerLimits::default())).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+
+        let err = source.collect_blocks((1..=10).collect()).await.unwrap_err();
+        let partial = err.partial_blocks().unwrap();
+        assert_eq!(partial.failed_heights, (1..=10).collect::<Vec<_>>());
+        assert!(partial.errors[0].contains("does not match the parent hash"), "{err}");
+
+        handle.stop().unwrap();
+    }
+
+    /// Blocks a follower has imported, served up to the highest one.
+    #[derive(Debug, Default)]
+    struct ImportedBlocks {
+        blocks: Mutex<BTreeMap<u64, BlockAndReceipts>>,
+    }
+
+    impl ImportedBlocks {
+        fn import(&self, blocks: Vec<BlockAndReceipts>) {
+            let mut imported = self.blocks.lock().unwrap();
+            imported.extend(blocks.into_iter().map(|block| (block.number(), block)));
+        }
+    }
+
+    impl SyncBlockReader for ImportedBlocks {
+        fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+            self.blocks
+                .lock()
+                .unwrap()
+                .get(&number)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("Block {number} not imported"))
+        }
+
+        fn best_block_number(&self) -> eyre::Result<u64> {
+            Ok(self.blocks.lock().unwrap().last_key_value().map_or(0, |(number, _)| *number))
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_the_chain_of_the_server() {
+        let server = sync_server(Arc::new(EmptyBlockReader), &SyncServerLimits::default());
+        let (url, handle) = serve(server.with_chain_id(998)).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(source.chain_id().await, Some(998));
+        handle.stop().unwrap();
+
+        // Servers reporting plain heights leave the check to the blocks
+        let server = sync_server(Arc::new(EmptyBlockReader), &SyncServerLimits::default());
+        let (url, handle) =
+            serve(server.with_chain_id(998).with_legacy_latest_block_number(true)).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(source.chain_id().await, None);
+        handle.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn response_without_blocks_is_corrupt() {
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        rmp::encode::write_array_len(&mut encoder, 0).unwrap();
+        let empty = Bytes::from(encoder.finish().unwrap());
+        let mut module = RpcModule::new(());
+        module.register_method("hl_syncGetBlock", move |_, _, _| empty.clone()).unwrap();
+        let (url, handle) = serve_module(module).await;
+
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+        let err = source.collect_block(1).await.unwrap_err();
+        assert!(err.to_string().contains("response holds no block"), "{err}");
+        handle.stop().unwrap();
+    }
+
+    /// Starts `module` over WebSocket.
+    async fn serve_module(module: RpcModule<()>) -> (String, ServerHandle) {
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", server.local_addr().unwrap());
+        (url, server.start(module))
+    }
+
+    /// The error of fetching a block from a server answering `hl_syncGetBlock` with `code`.
+    async fn collect_block_error(code: i32) -> BlockSourceError {
+        let mut module = RpcModule::new(());
+        module
+            .register_method("hl_syncGetBlock", move |_, _, _| {
+                Err::<Bytes, _>(ErrorObject::owned(code, "refused", None::<()>))
+            })
+            .unwrap();
+        let (url, handle) = serve_module(module).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+        let err = source.collect_block(1).await.unwrap_err();
+        handle.stop().unwrap();
+        err
+    }
+
+    #[tokio::test]
+    async fn only_blocks_above_the_tip_are_not_found_yet() {
+        let err = collect_block_error(HlErrorCode::BlockNotServed.code()).await;
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 1 }), "{err}");
+
+        // A server without the sync methods, or refusing our requests, never serves them
+        for code in [METHOD_NOT_FOUND_CODE, INVALID_PARAMS_CODE] {
+            let err = collect_block_error(code).await;
+            assert!(matches!(err, BlockSourceError::Backend { retryable: false, .. }), "{err}");
+        }
+
+        // A server failing to read the block may succeed later
+        let err = collect_block_error(INTERNAL_ERROR_CODE).await;
+        assert!(matches!(err, BlockSourceError::Backend { retryable: true, .. }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn unreachable_sync_server_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = format!("unix://{}", dir.path().join("missing.sock").display());
+        for url in ["ws://127.0.0.1:1".to_string(), socket, "not a url".to_string()] {
+            let source = RpcBlockSource::connect(url.clone(), Duration::from_millis(10)).await;
+            assert!(source.is_err(), "{url}");
+        }
+    }
+
+    /// Imports one block after another into `imported` until one of them is pushed to `source`.
+    async fn wait_for_pushed_block(source: &RpcBlockSource, imported: &ImportedBlocks) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let height = imported.best_block_number().unwrap() + 1;
+                imported.import(vec![EmptyBlockReader.read_block_and_receipts(height).unwrap()]);
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                if (1..=height).any(|height| source.take_pushed(height).is_some()) {
+                    return;
+                }
+            }
+        })
+        .await
+        .expect("no block was pushed");
+    }
+
+    #[tokio::test]
+    async fn pushed_blocks_resume_after_the_server_restarts() {
+        let imported = Arc::new(ImportedBlocks::default());
+        imported.import(vec![EmptyBlockReader.read_block_and_receipts(1).unwrap()]);
+        let server = sync_server(imported.clone(), &SyncServerLimits::default());
+        let (url, handle) = serve(server).await;
+        let source = RpcBlockSource::connect(url.clone(), Duration::from_millis(10)).await.unwrap();
+        wait_for_pushed_block(&source, &imported).await;
+
+        // The connection drops with the server, and is established again once it is back
+        handle.stop().unwrap();
+        handle.stopped().await;
+        let server = sync_server(imported.clone(), &SyncServerLimits::default());
+        let (_, handle) = serve_at(url.trim_start_matches("ws://"), server).await;
+        wait_for_pushed_block(&source, &imported).await;
+        let best = imported.best_block_number().unwrap();
+        assert_eq!(source.collect_block(best - 1).await.unwrap().number(), best - 1);
+
+        handle.stop().unwrap();
+    }
+
+    /// A serves its fully synced database, B follows A and C follows B.
+    #[tokio::test]
+    async fn chained_followers_hold_back_until_ready() {
+        const MAX_READY_LAG: u64 = 10;
+        let polling_interval = Duration::from_millis(10);
+
+        let node_a = CountingReader::new(100);
+        let (url_a, handle_a) = serve(sync_server(node_a, &SyncServerLimits::default())).await;
+
+        let status_b = SyncSourceStatus::default();
+        let source_b = TrackedBlockSource::new(
+            Arc::new(Box::new(RpcBlockSource::connect(url_a, polling_interval).await.unwrap())),
+            status_b.clone(),
+        );
+        let node_b = Arc::new(ImportedBlocks::default());
+        let (url_b, handle_b) = serve(
+            sync_server(node_b.clone(), &SyncServerLimits::default())
+                .with_source_status(status_b, MAX_READY_LAG),
+        )
+        .await;
+        let source_c = RpcBlockSource::connect(url_b, polling_interval).await.unwrap();
+
+        // B knows A's tip but hasn't imported anything yet, so C holds back
+        assert_eq!(source_b.find_latest_block_number().await, Some(100));
+        let latest_c = tokio::spawn({
+            let source_c = source_c.clone();
+            async move { source_c.find_latest_block_number().await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!latest_c.is_finished());
+
+        // Still too far behind, and heights B hasn't imported are refused
+        node_b.import(source_b.collect_blocks((1..=50).collect()).await.unwrap());
+        assert!(source_c.collect_blocks(vec![60]).await.is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!latest_c.is_finished());
+
+        // Within the allowed lag, C follows B's tip
+        node_b.import(source_b.collect_blocks((51..=95).collect()).await.unwrap());
+        let latest = tokio::time::timeout(Duration::from_secs(5), latest_c).await.unwrap().unwrap();
+        assert_eq!(latest, Some(95));
+        let blocks = source_c.collect_blocks((90..=95).collect()).await.unwrap();
+        let numbers = blocks.iter().map(|b| b.number()).collect::<Vec<_>>();
+        assert_eq!(numbers, (90..=95).collect::<Vec<_>>());
+
+        handle_a.stop().unwrap();
+        handle_b.stop().unwrap();
+    }
+
+    #[tokio::test]
+    async fn maps_refused_and_failed_requests() {
+        let reader = Arc::new(ImportedBlocks::default());
+        let (url, handle) = serve(sync_server(reader, &SyncServerLimits::default())).await;
+        let source = RpcBlockSource::connect(url, Duration::from_millis(10)).await.unwrap();
+
+        // The server refuses heights above its tip
+        let err = source.collect_block(1).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 1 }), "{err}");
+
+        handle.stop().unwrap();
+        handle.stopped().await;
+        let err = source.collect_block(1).await.unwrap_err();
+        assert!(matches!(err, BlockSourceError::Backend { retryable: true, .. }), "{err}");
+    }
 }
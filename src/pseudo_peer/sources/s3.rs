@@ -1,9 +1,18 @@
-use super::{BlockSource, utils};
+use super::{BlockSource, BlockSourceError, BlockSourceMetrics, BlockSourceResult, utils};
 use crate::node::types::BlockAndReceipts;
-use aws_sdk_s3::types::RequestPayer;
+use aws_sdk_s3::{
+    error::{ProvideErrorMetadata, SdkError},
+    operation::get_object::GetObjectError,
+    types::RequestPayer,
+};
 use futures::{FutureExt, future::BoxFuture};
-use reth_metrics::{Metrics, metrics, metrics::Counter};
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
 use tracing::info;
 
 /// Block source that reads blocks from S3 (--s3)
@@ -12,28 +21,32 @@ pub struct S3BlockSource {
     client: Arc<aws_sdk_s3::Client>,
     bucket: String,
     polling_interval: Duration,
-    metrics: S3BlockSourceMetrics,
-}
-
-#[derive(Metrics, Clone)]
-#[metrics(scope = "block_source.s3")]
-pub struct S3BlockSourceMetrics {
-    /// How many times the S3 block source is polling for a block
-    pub polling_attempt: Counter,
-    /// How many times the S3 block source has polled a block
-    pub fetched: Counter,
+    chunk_size: u64,
+    /// Highest block number known to be in the bucket, fetched or listed.
+    latest: Arc<AtomicU64>,
+    metrics: BlockSourceMetrics,
 }
 
 impl S3BlockSource {
+    const DEFAULT_CHUNK_SIZE: u64 = 1000;
+
     pub fn new(client: aws_sdk_s3::Client, bucket: String, polling_interval: Duration) -> Self {
         Self {
             client: client.into(),
             bucket,
             polling_interval,
-            metrics: S3BlockSourceMetrics::default(),
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            latest: Default::default(),
+            metrics: BlockSourceMetrics::for_kind("s3"),
         }
     }
 
+    /// Overrides the recommended chunk size.
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
     async fn pick_path_with_highest_number(
         client: &aws_sdk_s3::Client,
         bucket: &str,
@@ -64,10 +77,54 @@ impl S3BlockSource {
     }
 }
 
+/// Error codes of requests S3 rejects for the credentials, which won't pass when retried.
+const ACCESS_ERROR_CODES: [&str; 5] = [
+    "AccessDenied",
+    "InvalidAccessKeyId",
+    "SignatureDoesNotMatch",
+    "ExpiredToken",
+    "AllAccessDisabled",
+];
+
+/// Maps a failed `GetObject` request: a missing key is a block that isn't uploaded yet, unless a
+/// later block is known to be in the bucket, and a request that can't even be built or is denied
+/// access won't succeed when retried.
+fn get_object_error<R>(
+    height: u64,
+    latest: u64,
+    err: SdkError<GetObjectError, R>,
+) -> BlockSourceError
+where
+    R: std::fmt::Debug + Send + Sync + 'static,
+{
+    match err {
+        err if err.as_service_error().is_some_and(|err| err.is_no_such_key()) => {
+            if height < latest {
+                BlockSourceError::Missing { height }
+            } else {
+                BlockSourceError::NotFoundYet { height }
+            }
+        }
+        err @ SdkError::ConstructionFailure(_) => BlockSourceError::fatal(err),
+        err if err
+            .as_service_error()
+            .and_then(|err| err.code())
+            .is_some_and(|code| ACCESS_ERROR_CODES.contains(&code)) =>
+        {
+            BlockSourceError::fatal(err)
+        }
+        err => BlockSourceError::backend(err),
+    }
+}
+
 impl BlockSource for S3BlockSource {
-    fn collect_block(&self, height: u64) -> BoxFuture<'static, eyre::Result<BlockAndReceipts>> {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
         let client = self.client.clone();
         let bucket = self.bucket.clone();
+        let latest = self.latest.clone();
         let metrics = self.metrics.clone();
         async move {
             let path = utils::rmp_path(height);
@@ -78,12 +135,36 @@ impl BlockSource for S3BlockSource {
                 .request_payer(RequestPayer::Requester)
                 .bucket(&bucket)
                 .key(path);
-            let response = request.send().await?;
+            let started = Instant::now();
+            let response = request.send().await.map_err(|err| {
+                let err = get_object_error(height, latest.load(Ordering::Relaxed), err);
+                match err {
+                    BlockSourceError::NotFoundYet { .. } | BlockSourceError::Missing { .. } => {
+                        metrics.errors_not_found.increment(1)
+                    }
+                    _ => metrics.errors_transport.increment(1),
+                }
+                err
+            })?;
+            let bytes = response
+                .body
+                .collect()
+                .await
+                .map_err(|err| {
+                    metrics.errors_transport.increment(1);
+                    BlockSourceError::backend(err)
+                })?
+                .into_bytes();
+            metrics.fetch_latency.record(started.elapsed().as_secs_f64());
+            metrics.bytes_fetched.increment(bytes.len() as u64);
+
+            let started = Instant::now();
+            let block = utils::decode_block(height, &bytes)
+                .inspect_err(|_| metrics.errors_decode.increment(1))?;
+            metrics.decode_latency.record(started.elapsed().as_secs_f64());
             metrics.fetched.increment(1);
-            let bytes = response.body.collect().await?.into_bytes();
-            let mut decoder = lz4_flex::frame::FrameDecoder::new(&bytes[..]);
-            let blocks: Vec<BlockAndReceipts> = rmp_serde::from_read(&mut decoder)?;
-            Ok(blocks[0].clone())
+            latest.fetch_max(height, Ordering::Relaxed);
+            Ok(block)
         }
         .boxed()
     }
@@ -91,6 +172,7 @@ impl BlockSource for S3BlockSource {
     fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
         let client = self.client.clone();
         let bucket = self.bucket.clone();
+        let latest = self.latest.clone();
         async move {
             let (_, first_level) =
                 Self::pick_path_with_highest_number(&client, &bucket, "", true).await?;
@@ -100,16 +182,42 @@ impl BlockSource for S3BlockSource {
                 Self::pick_path_with_highest_number(&client, &bucket, &second_level, false).await?;
 
             info!("Latest block number: {} with path {}", block_number, third_level);
+            latest.fetch_max(block_number, Ordering::Relaxed);
             Some(block_number)
         }
         .boxed()
     }
 
     fn recommended_chunk_size(&self) -> u64 {
-        1000
+        self.chunk_size
     }
 
     fn polling_interval(&self) -> Duration {
         self.polling_interval
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::{error::ErrorMetadata, types::error::NoSuchKey};
+
+    #[test]
+    fn maps_s3_errors() {
+        let missing = || GetObjectError::NoSuchKey(NoSuchKey::builder().build());
+        let err = get_object_error(5, 5, SdkError::service_error(missing(), ()));
+        assert!(matches!(err, BlockSourceError::NotFoundYet { height: 5 }));
+        // A gap below a block known to be uploaded won't be filled by polling
+        let err = get_object_error(5, 6, SdkError::service_error(missing(), ()));
+        assert!(matches!(err, BlockSourceError::Missing { height: 5 }));
+
+        let err = get_object_error::<()>(5, 6, SdkError::timeout_error("timed out"));
+        assert!(matches!(err, BlockSourceError::Backend { retryable: true, .. }));
+        let err = get_object_error::<()>(5, 6, SdkError::construction_failure("no region"));
+        assert!(matches!(err, BlockSourceError::Backend { retryable: false, .. }));
+
+        let denied = GetObjectError::generic(ErrorMetadata::builder().code("AccessDenied").build());
+        let err = get_object_error(5, 6, SdkError::service_error(denied, ()));
+        assert!(!err.is_retryable());
+    }
+}
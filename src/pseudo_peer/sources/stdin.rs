@@ -0,0 +1,206 @@
+//! Block source reading hl-node block file lines from stdin, for one-off imports such as
+//! `reth-hl stream-blocks | reth-hl import-stdin`.
+
+use super::{BlockSource, BlockSourceError, BlockSourceMetrics, BlockSourceResult, Scanner};
+use crate::node::types::BlockAndReceipts;
+use futures::{FutureExt, future::BoxFuture};
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::watch,
+};
+use tracing::debug;
+
+/// Blocks read ahead of the heights requested so far, beyond which reading pauses.
+const MAX_BUFFERED_BLOCKS: usize = 10_000;
+
+/// Blocks kept below the highest height requested, for requests arriving out of order.
+const KEEP_BEHIND: u64 = 1_000;
+
+/// What has been read from the input so far.
+#[derive(Debug, Default)]
+struct Input {
+    blocks: BTreeMap<u64, BlockAndReceipts>,
+    /// Highest height read.
+    latest: Option<u64>,
+    /// Highest height requested.
+    requested: u64,
+    /// Set when the input ends, with the error that ended it early.
+    end: Option<Result<(), String>>,
+}
+
+/// Block source serving the blocks of newline-delimited JSON lines in the hl-node file format,
+/// `[block_time, block]`, read from stdin or any other reader.
+///
+/// Lines are read as the node imports them, so the input may be larger than memory. A line that
+/// doesn't parse ends the input, since the blocks after it could never be imported in order.
+#[derive(Debug, Clone)]
+pub struct StdinBlockSource {
+    input: Arc<watch::Sender<Input>>,
+    metrics: BlockSourceMetrics,
+}
+
+impl StdinBlockSource {
+    /// Reads blocks from stdin. Must be called within a tokio runtime.
+    pub fn new() -> Self {
+        Self::from_reader(tokio::io::stdin())
+    }
+
+    /// Reads blocks from `reader`. Must be called within a tokio runtime.
+    pub fn from_reader(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        let input = Arc::new(watch::Sender::new(Input::default()));
+        let metrics = BlockSourceMetrics::for_kind("stdin");
+        tokio::spawn(read_lines(input.clone(), reader, metrics.clone()));
+        Self { input, metrics }
+    }
+
+    /// Waits for the input to end, returning the highest height read, or the error that ended
+    /// the input early.
+    pub async fn finished(&self) -> eyre::Result<Option<u64>> {
+        let mut input = self.input.subscribe();
+        let input = input.wait_for(|input| input.end.is_some()).await?;
+        match &input.end {
+            Some(Err(err)) => Err(eyre::eyre!("{err}")),
+            _ => Ok(input.latest),
+        }
+    }
+}
+
+impl Default for StdinBlockSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn read_lines(
+    input: Arc<watch::Sender<Input>>,
+    reader: impl AsyncRead + Unpin,
+    metrics: BlockSourceMetrics,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    let mut room = input.subscribe();
+    let mut line_number = 0u64;
+    let end = loop {
+        if room.wait_for(|input| input.blocks.len() < MAX_BUFFERED_BLOCKS).await.is_err() {
+            return;
+        }
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break Ok(()),
+            Err(err) => {
+                metrics.errors_transport.increment(1);
+                break Err(format!("failed to read the input: {err}"));
+            }
+        };
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Scanner::line_to_evm_block(&line) {
+            Ok((block, height)) => {
+                metrics.bytes_fetched.increment(line.len() as u64);
+                input.send_modify(|input| {
+                    input.latest = input.latest.max(Some(height));
+                    input.blocks.insert(height, block);
+                });
+            }
+            Err(err) => {
+                metrics.errors_decode.increment(1);
+                break Err(format!("line {line_number} is not an hl-node block: {err}"));
+            }
+        }
+    };
+    debug!(lines = line_number, ?end, "Input of the stdin block source ended");
+    input.send_modify(|input| input.end = Some(end));
+}
+
+impl BlockSource for StdinBlockSource {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        let mut block = None;
+        let mut past_end = false;
+        // Only wakes the reader when blocks were dropped to make room
+        self.input.send_if_modified(|input| {
+            block = input.blocks.get(&height).cloned();
+            past_end = input.end.is_some() && input.latest.is_some_and(|latest| height <= latest);
+            input.requested = input.requested.max(height);
+            let floor = input.requested.saturating_sub(KEEP_BEHIND);
+            let before = input.blocks.len();
+            input.blocks = input.blocks.split_off(&floor);
+            input.blocks.len() < before
+        });
+        let result = match block {
+            Some(block) => {
+                self.metrics.fetched.increment(1);
+                Ok(block)
+            }
+            None => {
+                self.metrics.errors_not_found.increment(1);
+                Err(if past_end {
+                    BlockSourceError::Missing { height }
+                } else {
+                    BlockSourceError::NotFoundYet { height }
+                })
+            }
+        };
+        async move { result }.boxed()
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        let mut input = self.input.subscribe();
+        async move {
+            // Waits for the first block, so that a slow writer isn't taken for an empty input
+            let input =
+                input.wait_for(|input| input.latest.is_some() || input.end.is_some()).await.ok()?;
+            input.latest
+        }
+        .boxed()
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        1000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{node::commands::stream_blocks::write_block_line, test_utils::empty_block};
+
+    fn lines(numbers: impl IntoIterator<Item = u64>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for number in numbers {
+            write_block_line(&mut out, empty_block(number)).unwrap();
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn serves_the_blocks_piped_in() {
+        let source = StdinBlockSource::from_reader(std::io::Cursor::new(lines(1..=3)));
+        assert_eq!(source.finished().await.unwrap(), Some(3));
+        assert_eq!(source.find_latest_block_number().await, Some(3));
+        for number in 1..=3 {
+            let served = source.collect_block(number).await.unwrap();
+            assert_eq!(served.hash(), empty_block(number).hash());
+        }
+        assert!(matches!(
+            source.collect_block(4).await,
+            Err(BlockSourceError::NotFoundYet { height: 4 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn unparsable_line_ends_the_input() {
+        let mut input = lines([1]);
+        input.extend_from_slice(b"\nnot a block\n");
+        input.extend(lines([2]));
+        let source = StdinBlockSource::from_reader(std::io::Cursor::new(input));
+        let err = source.finished().await.unwrap_err();
+        assert!(err.to_string().starts_with("line 3 is not an hl-node block"), "{err}");
+        assert!(source.collect_block(1).await.is_ok());
+        assert!(source.collect_block(2).await.is_err());
+    }
+}
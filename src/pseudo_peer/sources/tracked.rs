@@ -0,0 +1,73 @@
+use super::{BlockSource, BlockSourceBoxed, BlockSourceResult};
+use crate::{addons::sync_server::SyncSourceStatus, node::types::BlockAndReceipts};
+use futures::{FutureExt, future::BoxFuture};
+use std::time::Duration;
+
+/// Block source wrapper that records the latest block seen from the inner source, so that the
+/// sync server can report how far this node trails it.
+#[derive(Debug, Clone)]
+pub struct TrackedBlockSource {
+    block_source: BlockSourceBoxed,
+    status: SyncSourceStatus,
+}
+
+impl TrackedBlockSource {
+    pub fn new(block_source: BlockSourceBoxed, status: SyncSourceStatus) -> Self {
+        Self { block_source, status }
+    }
+}
+
+impl BlockSource for TrackedBlockSource {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        let status = self.status.clone();
+        let block = self.block_source.collect_block(height);
+        async move {
+            let block = block.await?;
+            status.record(height);
+            Ok(block)
+        }
+        .boxed()
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        let status = self.status.clone();
+        let latest = self.block_source.find_latest_block_number();
+        async move {
+            let latest = latest.await?;
+            status.record(latest);
+            Some(latest)
+        }
+        .boxed()
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        self.block_source.recommended_chunk_size()
+    }
+
+    fn collect_blocks(
+        &self,
+        heights: Vec<u64>,
+    ) -> BoxFuture<'static, BlockSourceResult<Vec<BlockAndReceipts>>> {
+        let status = self.status.clone();
+        let blocks = self.block_source.collect_blocks(heights);
+        async move {
+            let blocks = blocks.await?;
+            if let Some(highest) = blocks.iter().map(BlockAndReceipts::number).max() {
+                status.record(highest);
+            }
+            Ok(blocks)
+        }
+        .boxed()
+    }
+
+    fn polling_interval(&self) -> Duration {
+        self.block_source.polling_interval()
+    }
+
+    fn chain_id(&self) -> BoxFuture<'static, Option<u64>> {
+        self.block_source.chain_id()
+    }
+}
@@ -1,5 +1,9 @@
 //! Shared utilities for block sources
 
+use super::{BlockSourceError, BlockSourceResult};
+use crate::node::types::BlockAndReceipts;
+use std::io::ErrorKind;
+
 /// Finds the file/directory with the largest number in its name from a list of files
 pub fn name_with_largest_number(files: &[String], is_dir: bool) -> Option<(u64, String)> {
     let mut files = files
@@ -24,3 +28,36 @@ pub fn rmp_path(height: u64) -> String {
     let s = ((height - 1) / 1_000) * 1_000;
     format!("{f}/{s}/{height}.rmp.lz4")
 }
+
+/// Decodes the contents of a `.rmp.lz4` block file.
+pub fn decode_rmp_lz4(bytes: &[u8]) -> Result<Vec<BlockAndReceipts>, rmp_serde::decode::Error> {
+    let mut decoder = lz4_flex::frame::FrameDecoder::new(bytes);
+    rmp_serde::from_read(&mut decoder)
+}
+
+/// Decodes the block at `height` from the contents of its `.rmp.lz4` file.
+pub fn decode_block(height: u64, bytes: &[u8]) -> BlockSourceResult<BlockAndReceipts> {
+    first_block(height, decode_rmp_lz4(bytes))
+}
+
+/// The block at `height` from the decoded contents of its `.rmp.lz4` file.
+pub fn first_block(
+    height: u64,
+    decoded: Result<Vec<BlockAndReceipts>, rmp_serde::decode::Error>,
+) -> BlockSourceResult<BlockAndReceipts> {
+    decoded
+        .map_err(|err| BlockSourceError::corrupt(height, err))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| BlockSourceError::corrupt(height, "block file holds no block"))
+}
+
+/// Whether decoding failed because the payload ends early, as a file still being written does,
+/// rather than on data that can't be decoded.
+pub fn is_truncated(err: &rmp_serde::decode::Error) -> bool {
+    match err {
+        rmp_serde::decode::Error::InvalidMarkerRead(err) |
+        rmp_serde::decode::Error::InvalidDataRead(err) => err.kind() == ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
@@ -0,0 +1,106 @@
+//! Blocks, block sources and sync readers shared by the unit tests.
+
+use crate::{
+    addons::sync_server::SyncBlockReader,
+    node::types::{BlockAndReceipts, EvmBlock, ReadPrecompileCalls, reth_compat},
+    pseudo_peer::{BlockSource, BlockSourceError, BlockSourceResult},
+};
+use alloy_consensus::{BlockBody, Header};
+use alloy_primitives::B256;
+use futures::{FutureExt, future::BoxFuture};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// Timestamp of block 0 of [`empty_block`]s, 2025-06-30T12:00:00Z. Each block is a second later.
+pub(crate) const FIRST_BLOCK_TIMESTAMP: u64 = 1_751_284_800;
+
+/// A block of `header` without transactions, sealed with `hash`.
+pub(crate) fn sealed_block(header: Header, hash: B256) -> BlockAndReceipts {
+    BlockAndReceipts {
+        block: EvmBlock::Reth115(reth_compat::SealedBlock {
+            header: reth_compat::SealedHeader { header, hash },
+            body: BlockBody { transactions: vec![], ommers: vec![], withdrawals: None },
+        }),
+        receipts: vec![],
+        system_txs: vec![],
+        read_precompile_calls: ReadPrecompileCalls::default(),
+        highest_precompile_address: None,
+    }
+}
+
+/// A block of `header` without transactions, sealed with its hash.
+pub(crate) fn header_block(header: Header) -> BlockAndReceipts {
+    let hash = header.hash_slow();
+    sealed_block(header, hash)
+}
+
+/// The block at `number` of a chain of empty blocks, sealed with its hash.
+pub(crate) fn empty_block(number: u64) -> BlockAndReceipts {
+    header_block(Header { number, timestamp: FIRST_BLOCK_TIMESTAMP + number, ..Default::default() })
+}
+
+/// Block source serving the [`empty_block`]s of `heights`, counting how many it was asked for.
+#[derive(Debug, Default)]
+pub(crate) struct CountingSource {
+    pub(crate) heights: Vec<u64>,
+    pub(crate) fetches: AtomicU64,
+}
+
+impl BlockSource for CountingSource {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        let found = self.heights.contains(&height);
+        async move {
+            if found { Ok(empty_block(height)) } else { Err(BlockSourceError::Missing { height }) }
+        }
+        .boxed()
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        let latest = self.heights.iter().max().copied();
+        async move { latest }.boxed()
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        1
+    }
+}
+
+/// Sync reader serving the [`empty_block`]s up to `height`, counting the block reads.
+#[derive(Debug)]
+pub(crate) struct CountingReader {
+    height: u64,
+    reads: AtomicUsize,
+}
+
+impl CountingReader {
+    pub(crate) fn new(height: u64) -> Arc<Self> {
+        Arc::new(Self { height, reads: AtomicUsize::new(0) })
+    }
+
+    /// Block reads since the last call.
+    pub(crate) fn reads(&self) -> usize {
+        self.reads.swap(0, Ordering::SeqCst)
+    }
+}
+
+impl SyncBlockReader for CountingReader {
+    fn read_block_and_receipts(&self, number: u64) -> eyre::Result<BlockAndReceipts> {
+        eyre::ensure!(number <= self.height, "block {number} missing");
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        Ok(empty_block(number))
+    }
+
+    fn best_block_number(&self) -> eyre::Result<u64> {
+        Ok(self.height)
+    }
+
+    fn block_hash(&self, number: u64) -> eyre::Result<Option<B256>> {
+        Ok((number <= self.height).then(|| empty_block(number).hash()))
+    }
+}
@@ -0,0 +1,295 @@
+//! Fixture chains on top of the mainnet genesis, served by [`MockBlockSource`].
+//!
+//! [`ChainBuilder`] applies the transfers and read precompile calls of its blocks itself, so that
+//! every block carries the state root, receipts root and gas used the node computes on import.
+
+use alloy_consensus::{
+    EMPTY_OMMER_ROOT_HASH, EMPTY_ROOT_HASH, Header, SignableTransaction, Signed, TxLegacy, TxType,
+    proofs::calculate_transaction_root,
+};
+use alloy_genesis::GenesisAccount;
+use alloy_primitives::{Address, B256, Bloom, Bytes, Signature, TxKind, U256, address};
+use alloy_signer::SignerSync;
+use alloy_signer_local::PrivateKeySigner;
+use futures::{FutureExt, future::BoxFuture};
+use reth_chainspec::EthChainSpec;
+use reth_hl::{
+    HlBlock, HlBlockBody, HlHeader,
+    chainspec::HlChainSpec,
+    node::{
+        primitives::{BlockBody, TransactionSigned, header::HlHeaderExtras},
+        types::{BlockAndReceipts, ReadPrecompileCalls, ReadPrecompileInput, ReadPrecompileResult},
+    },
+    pseudo_peer::{BlockSource, BlockSourceError, BlockSourceResult},
+};
+use reth_primitives::Receipt;
+use reth_trie_common::root::state_root_ref_unhashed;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Sender of the HYPE sent from HyperCore, as recovered from native transfer system transactions.
+pub const SYSTEM_ADDRESS: Address = address!("0x2222222222222222222222222222222222222222");
+/// Gas price of user transactions. System transactions are the ones with a gas price of 0.
+pub const GAS_PRICE: u128 = 1_000_000_000;
+/// Gas limit of the user calls into read precompiles.
+pub const PRECOMPILE_CALL_GAS_LIMIT: u64 = 100_000;
+/// Gas of a plain transfer.
+const TRANSFER_GAS: u64 = 21_000;
+
+/// Builds `count` empty blocks following the genesis of `chain_spec`.
+///
+/// Nothing is executed in empty blocks, so every block keeps the genesis state root.
+pub fn empty_chain(chain_spec: &HlChainSpec, count: u64) -> Vec<BlockAndReceipts> {
+    ChainBuilder::new(chain_spec).empty_blocks(count).build()
+}
+
+/// The user account `index`, with a well-known key. Fund it with a [`FixtureTx::NativeTransfer`]
+/// before it sends transactions.
+pub fn user(index: u8) -> PrivateKeySigner {
+    PrivateKeySigner::from_bytes(&B256::with_last_byte(index)).expect("valid secret key")
+}
+
+/// Gas used by a transaction with `input` before it runs any code.
+pub fn intrinsic_gas(input: &[u8]) -> u64 {
+    TRANSFER_GAS + input.iter().map(|byte| if *byte == 0 { 4 } else { 16 }).sum::<u64>()
+}
+
+/// A transaction of a [`ChainBuilder`] block.
+#[derive(Debug, Clone)]
+pub enum FixtureTx {
+    /// HYPE sent from HyperCore: a system transaction from [`SYSTEM_ADDRESS`].
+    NativeTransfer { to: Address, value: U256 },
+    /// A transfer between users.
+    Transfer { from: PrivateKeySigner, to: Address, value: U256 },
+    /// A user call into the read precompile `precompile`, answered with the recorded `result`.
+    PrecompileCall {
+        from: PrivateKeySigner,
+        precompile: Address,
+        input: Bytes,
+        result: ReadPrecompileResult,
+    },
+}
+
+/// Builds a chain on top of the genesis of a chain spec, keeping track of the state its blocks
+/// leave.
+#[derive(Debug)]
+pub struct ChainBuilder {
+    chain_id: u64,
+    accounts: BTreeMap<Address, GenesisAccount>,
+    parent_hash: B256,
+    blocks: Vec<BlockAndReceipts>,
+}
+
+impl ChainBuilder {
+    pub fn new(chain_spec: &HlChainSpec) -> Self {
+        Self {
+            chain_id: chain_spec.chain_id(),
+            accounts: chain_spec.inner.genesis.alloc.clone(),
+            parent_hash: chain_spec.genesis_hash(),
+            blocks: vec![],
+        }
+    }
+
+    /// Appends `count` empty blocks.
+    pub fn empty_blocks(mut self, count: u64) -> Self {
+        for _ in 0..count {
+            self = self.block([]);
+        }
+        self
+    }
+
+    /// Appends a block of `txs`, whose system transactions must come first as in HL blocks.
+    pub fn block(mut self, txs: impl IntoIterator<Item = FixtureTx>) -> Self {
+        let number = self.blocks.len() as u64 + 1;
+        let mut transactions = vec![];
+        let mut receipts = vec![];
+        let mut calls = ReadPrecompileCalls::default();
+        let (mut gas_used, mut system_tx_count) = (0, 0);
+        for tx in txs {
+            let (transaction, success, tx_gas_used) = match tx {
+                FixtureTx::NativeTransfer { to, value } => {
+                    assert_eq!(system_tx_count, transactions.len(), "system transactions go first");
+                    system_tx_count += 1;
+                    let tx = self.legacy_tx(SYSTEM_ADDRESS, TRANSFER_GAS, to, value, Bytes::new());
+                    self.apply(SYSTEM_ADDRESS, to, value, 0);
+                    let signature = Signature::new(U256::ONE, U256::ONE, true);
+                    let tx = TransactionSigned::Default(Signed::new_unhashed(tx, signature).into());
+                    // System transactions don't count towards the gas used of the block
+                    (tx, true, 0)
+                }
+                FixtureTx::Transfer { from, to, value } => {
+                    let sender = from.address();
+                    let tx = self.legacy_tx(sender, TRANSFER_GAS, to, value, Bytes::new());
+                    self.apply(sender, to, value, TRANSFER_GAS);
+                    (sign(tx, &from), true, TRANSFER_GAS)
+                }
+                FixtureTx::PrecompileCall { from, precompile, input, result } => {
+                    let sender = from.address();
+                    let intrinsic_gas = intrinsic_gas(&input);
+                    let (success, tx_gas_used) = match &result {
+                        ReadPrecompileResult::Ok { gas_used, .. } => {
+                            (true, intrinsic_gas + gas_used)
+                        }
+                        // A failed read precompile consumes all the gas it is given
+                        _ => (false, PRECOMPILE_CALL_GAS_LIMIT),
+                    };
+                    let gas_limit = PRECOMPILE_CALL_GAS_LIMIT;
+                    let tx =
+                        self.legacy_tx(sender, gas_limit, precompile, U256::ZERO, input.clone());
+                    self.apply(sender, precompile, U256::ZERO, tx_gas_used);
+                    let call = (
+                        ReadPrecompileInput {
+                            input,
+                            gas_limit: PRECOMPILE_CALL_GAS_LIMIT - intrinsic_gas,
+                        },
+                        result,
+                    );
+                    match calls.0.iter_mut().find(|(address, _)| *address == precompile) {
+                        Some((_, recorded)) => recorded.push(call),
+                        None => calls.0.push((precompile, vec![call])),
+                    }
+                    (sign(tx, &from), success, tx_gas_used)
+                }
+            };
+            gas_used += tx_gas_used;
+            receipts.push(Receipt {
+                tx_type: TxType::Legacy,
+                success,
+                cumulative_gas_used: gas_used,
+                logs: vec![],
+            });
+            transactions.push(transaction);
+        }
+
+        let header = Header {
+            parent_hash: self.parent_hash,
+            number,
+            state_root: state_root_ref_unhashed(&self.accounts),
+            ommers_hash: EMPTY_OMMER_ROOT_HASH,
+            transactions_root: calculate_transaction_root(&transactions[system_tx_count..]),
+            receipts_root: Receipt::calculate_receipt_root_no_memo(&receipts[system_tx_count..]),
+            withdrawals_root: Some(EMPTY_ROOT_HASH),
+            timestamp: 1_700_000_000 + number,
+            gas_limit: 0x1c9c380,
+            gas_used,
+            base_fee_per_gas: Some(0),
+            blob_gas_used: Some(0),
+            excess_blob_gas: Some(0),
+            parent_beacon_block_root: Some(B256::ZERO),
+            ..Default::default()
+        };
+        self.parent_hash = header.hash_slow();
+        let extras = HlHeaderExtras {
+            logs_bloom_with_system_txs: Bloom::ZERO,
+            system_tx_count: system_tx_count as u64,
+        };
+        let body =
+            BlockBody { transactions, ommers: vec![], withdrawals: Some(Default::default()) };
+        let block = HlBlock {
+            header: HlHeader { inner: header, extras },
+            body: HlBlockBody {
+                inner: body,
+                sidecars: None,
+                read_precompile_calls: (!calls.0.is_empty()).then_some(calls),
+                highest_precompile_address: None,
+            },
+        };
+        self.blocks.push(BlockAndReceipts::from_db(block, receipts));
+        self
+    }
+
+    pub fn build(self) -> Vec<BlockAndReceipts> {
+        self.blocks
+    }
+
+    /// An unsigned transaction of `sender`, with its next nonce.
+    fn legacy_tx(
+        &self,
+        sender: Address,
+        gas_limit: u64,
+        to: Address,
+        value: U256,
+        input: Bytes,
+    ) -> TxLegacy {
+        let nonce = self.accounts.get(&sender).and_then(|account| account.nonce).unwrap_or(0);
+        TxLegacy {
+            chain_id: Some(self.chain_id),
+            nonce,
+            gas_price: gas_price(sender),
+            gas_limit,
+            to: TxKind::Call(to),
+            value,
+            input,
+        }
+    }
+
+    /// Applies a transaction of `sender` sending `value` to `to` and using `gas_used`, whose fee
+    /// goes to the zero address, the beneficiary of fixture blocks.
+    fn apply(&mut self, sender: Address, to: Address, value: U256, gas_used: u64) {
+        let fee = U256::from(gas_used) * U256::from(gas_price(sender));
+        let account = self.accounts.entry(sender).or_default();
+        account.balance = account.balance.checked_sub(value + fee).expect("sender can pay");
+        account.nonce = Some(account.nonce.unwrap_or(0) + 1);
+        self.credit(to, value);
+        self.credit(Address::ZERO, fee);
+    }
+
+    /// Adds `amount` to the balance of `address`. Accounts left empty don't exist.
+    fn credit(&mut self, address: Address, amount: U256) {
+        if !amount.is_zero() {
+            self.accounts.entry(address).or_default().balance += amount;
+        }
+    }
+}
+
+/// Gas price of the transactions of `sender`.
+fn gas_price(sender: Address) -> u128 {
+    if sender == SYSTEM_ADDRESS { 0 } else { GAS_PRICE }
+}
+
+/// Signs a user transaction.
+fn sign(tx: TxLegacy, signer: &PrivateKeySigner) -> TransactionSigned {
+    let signature = signer.sign_hash_sync(&tx.signature_hash()).expect("signable transaction");
+    TransactionSigned::Default(Signed::new_unhashed(tx, signature).into())
+}
+
+/// Block source serving a fixed set of blocks.
+#[derive(Debug, Clone)]
+pub struct MockBlockSource {
+    blocks: Arc<BTreeMap<u64, BlockAndReceipts>>,
+    fatal_error_at: Option<u64>,
+}
+
+impl MockBlockSource {
+    pub fn new(blocks: Vec<BlockAndReceipts>) -> Self {
+        let blocks = blocks.into_iter().map(|block| (block.number(), block)).collect();
+        Self { blocks: Arc::new(blocks), fatal_error_at: None }
+    }
+
+    /// Fails permanently at `height`, like a source that is denied access.
+    pub fn with_fatal_error_at(mut self, height: u64) -> Self {
+        self.fatal_error_at = Some(height);
+        self
+    }
+}
+
+impl BlockSource for MockBlockSource {
+    fn collect_block(
+        &self,
+        height: u64,
+    ) -> BoxFuture<'static, BlockSourceResult<BlockAndReceipts>> {
+        if self.fatal_error_at == Some(height) {
+            return async { Err(BlockSourceError::fatal("access denied")) }.boxed();
+        }
+        let block = self.blocks.get(&height).cloned();
+        async move { block.ok_or(BlockSourceError::NotFoundYet { height }) }.boxed()
+    }
+
+    fn find_latest_block_number(&self) -> BoxFuture<'static, Option<u64>> {
+        let latest = self.blocks.keys().next_back().copied();
+        async move { latest }.boxed()
+    }
+
+    fn recommended_chunk_size(&self) -> u64 {
+        10
+    }
+}
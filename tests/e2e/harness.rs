@@ -0,0 +1,288 @@
+//! Launches full nodes in-process, each with its own datadir, block source and upstream RPC.
+//!
+//! Nodes share nothing but the process, so several can run at once. Each node runs on its own
+//! task manager, which [`TestNode::shutdown`] shuts down gracefully before the datadir is removed.
+
+use crate::fixtures::MockBlockSource;
+use alloy_primitives::B256;
+use clap::Parser;
+use futures::future::BoxFuture;
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{HttpClient, HttpClientBuilder},
+    rpc_params,
+    ws_client::{WsClient, WsClientBuilder},
+};
+use reth::{
+    api::NodeTypesWithDBAdapter,
+    args::{DatadirArgs, RpcServerArgs},
+    builder::{NodeBuilder, NodeConfig},
+    rpc::builder::{RpcModuleSelection, RpcServerHandle},
+    tasks::TaskManager,
+};
+use reth_chainspec::EthChainSpec;
+use reth_db::{ClientVersion, DatabaseEnv, mdbx::DatabaseArguments};
+use reth_hl::{
+    chainspec::parser::chain_value_parser,
+    node::{
+        HlNode, cli::HlNodeArgs, commands::import_stdin::commit_in_batches,
+        launch::launch_hl_node_with, types::BlockAndReceipts,
+    },
+    pseudo_peer::BlockSourceConfig,
+};
+use reth_provider::{
+    BlockHashReader, HeaderProvider, ProviderFactory, StageCheckpointReader,
+    StaticFileProviderFactory, StaticFileSegment, providers::StaticFileProvider,
+};
+use reth_stages_types::StageId;
+use std::{sync::Arc, time::Duration};
+use tempfile::TempDir;
+
+/// How long a node gets to import the fixture chain.
+const IMPORT_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long a node gets to finish its tasks on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Parser)]
+struct NodeArgs {
+    #[command(flatten)]
+    args: HlNodeArgs,
+}
+
+/// Configures a [`TestNode`].
+#[derive(Debug)]
+pub struct TestNodeBuilder {
+    blocks: Vec<BlockAndReceipts>,
+    upstream_url: String,
+    args: Vec<String>,
+    fatal_error_at: Option<u64>,
+    block_source: Option<BlockSourceConfig>,
+    import_batch_size: Option<u64>,
+    datadir: Option<TempDir>,
+    http_api: Option<RpcModuleSelection>,
+}
+
+impl TestNodeBuilder {
+    /// A node importing `blocks` and forwarding transactions to `upstream_url`.
+    pub fn new(blocks: Vec<BlockAndReceipts>, upstream_url: &str) -> Self {
+        Self {
+            blocks,
+            upstream_url: upstream_url.to_string(),
+            args: vec![],
+            fatal_error_at: None,
+            block_source: None,
+            import_batch_size: None,
+            datadir: None,
+            http_api: None,
+        }
+    }
+
+    /// Adds a `reth-hl` node argument, e.g. `--hl-node-compliant`.
+    pub fn with_arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Serves the RPC `modules` over HTTP, e.g. `eth,trace`, instead of reth's defaults.
+    pub fn with_http_api(mut self, modules: &str) -> Self {
+        self.http_api = Some(modules.parse().expect("valid RPC modules"));
+        self
+    }
+
+    /// Makes the block source fail permanently at `height` instead of serving the block.
+    pub fn with_fatal_error_at(mut self, height: u64) -> Self {
+        self.fatal_error_at = Some(height);
+        self
+    }
+
+    /// Makes the engine commit imported blocks `batch_size` at a time, as `import-stdin
+    /// --import.batch-size` does.
+    pub fn with_import_batch_size(mut self, batch_size: u64) -> Self {
+        self.import_batch_size = Some(batch_size);
+        self
+    }
+
+    /// Runs on `datadir`, e.g. one kept by [`TestNode::persist_and_shutdown`], instead of a new
+    /// one.
+    pub fn with_datadir(mut self, datadir: TempDir) -> Self {
+        self.datadir = Some(datadir);
+        self
+    }
+
+    /// Imports from `block_source` instead of the fixture blocks.
+    pub fn with_block_source(mut self, block_source: BlockSourceConfig) -> Self {
+        self.block_source = Some(block_source);
+        self
+    }
+
+    pub async fn launch(self) -> eyre::Result<TestNode> {
+        let ext = NodeArgs::try_parse_from(
+            ["reth-hl", "--upstream-rpc-url", &self.upstream_url]
+                .into_iter()
+                .map(String::from)
+                .chain(self.args),
+        )?
+        .args;
+
+        let datadir = match self.datadir {
+            Some(datadir) => datadir,
+            None => tempfile::tempdir()?,
+        };
+        let mut rpc = RpcServerArgs::default().with_http().with_ws().with_unused_ports();
+        rpc.ipcdisable = true;
+        rpc.http_api = self.http_api;
+        let mut config = NodeConfig::new(chain_value_parser("mainnet")?)
+            .with_datadir_args(DatadirArgs {
+                datadir: datadir.path().to_path_buf().into(),
+                ..Default::default()
+            })
+            .with_rpc(rpc)
+            .with_unused_ports();
+        if let Some(batch_size) = self.import_batch_size {
+            commit_in_batches(&mut config.engine, batch_size);
+        }
+
+        let db_args = DatabaseArguments::new(ClientVersion::default());
+        let db = Arc::new(reth_db::init_db(config.datadir().db(), db_args)?);
+
+        let task_manager = TaskManager::current();
+        let builder = NodeBuilder::new(config)
+            .with_database(db)
+            .with_launch_context(task_manager.executor());
+        let mut block_source = MockBlockSource::new(self.blocks);
+        if let Some(height) = self.fatal_error_at {
+            block_source = block_source.with_fatal_error_at(height);
+        }
+        let block_source = self
+            .block_source
+            .unwrap_or_else(|| BlockSourceConfig::custom(Arc::new(Box::new(block_source))));
+        let handle = launch_hl_node_with(builder, ext, Some(block_source)).await?;
+
+        Ok(TestNode {
+            rpc: handle.rpc,
+            exit: handle.exit,
+            persist: handle.persist,
+            task_manager,
+            datadir,
+        })
+    }
+}
+
+/// A running node.
+pub struct TestNode {
+    rpc: RpcServerHandle,
+    exit: BoxFuture<'static, eyre::Result<()>>,
+    persist: BoxFuture<'static, eyre::Result<()>>,
+    task_manager: TaskManager,
+    datadir: TempDir,
+}
+
+impl TestNode {
+    pub fn http(&self) -> HttpClient {
+        let url = self.rpc.http_url().expect("HTTP RPC is enabled");
+        HttpClientBuilder::default().build(url).expect("valid HTTP URL")
+    }
+
+    pub fn ws_url(&self) -> String {
+        self.rpc.ws_url().expect("WS RPC is enabled")
+    }
+
+    pub async fn ws(&self) -> eyre::Result<WsClient> {
+        Ok(WsClientBuilder::default().build(self.ws_url()).await?)
+    }
+
+    /// Waits until the block at `number` is imported.
+    pub async fn wait_for_block(&self, number: u64) -> eyre::Result<()> {
+        let client = self.http();
+        tokio::time::timeout(IMPORT_TIMEOUT, async {
+            loop {
+                let latest: alloy_primitives::U64 =
+                    client.request("eth_blockNumber", rpc_params![]).await?;
+                if latest.to::<u64>() >= number {
+                    return eyre::Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .map_err(|_| eyre::eyre!("block {number} was not imported within {IMPORT_TIMEOUT:?}"))?
+    }
+
+    /// Waits until the node exits on its own, returning what it exited with.
+    pub async fn wait_for_exit(&mut self) -> eyre::Result<eyre::Result<()>> {
+        tokio::time::timeout(IMPORT_TIMEOUT, &mut self.exit)
+            .await
+            .map_err(|_| eyre::eyre!("node did not exit within {IMPORT_TIMEOUT:?}"))
+    }
+
+    /// Shuts the node down, waiting for its tasks to finish before the datadir is removed.
+    pub async fn shutdown(self) -> eyre::Result<()> {
+        self.stop().await?;
+        Ok(())
+    }
+
+    /// Writes the blocks the engine holds in memory to the datadir and shuts the node down,
+    /// keeping the datadir to be read or run again.
+    pub async fn persist_and_shutdown(mut self) -> eyre::Result<TempDir> {
+        tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut self.persist)
+            .await
+            .map_err(|_| eyre::eyre!("blocks were not persisted within {SHUTDOWN_TIMEOUT:?}"))??;
+        self.stop().await
+    }
+
+    async fn stop(self) -> eyre::Result<TempDir> {
+        let Self { rpc, task_manager, datadir, .. } = self;
+        rpc.stop()?;
+        let finished = tokio::task::spawn_blocking(move || {
+            task_manager.graceful_shutdown_with_timeout(SHUTDOWN_TIMEOUT)
+        })
+        .await?;
+        eyre::ensure!(finished, "node tasks did not finish within {SHUTDOWN_TIMEOUT:?}");
+        Ok(datadir)
+    }
+}
+
+/// The chain a stopped node's datadir holds, read from its database and static files.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PersistedChain {
+    /// Block of the `Finish` stage checkpoint.
+    pub finished: Option<u64>,
+    /// Highest block of the headers, transactions and receipts static files.
+    pub static_files: Vec<Option<u64>>,
+    /// Canonical hashes of the blocks after genesis, from the database.
+    pub canonical_hashes: Vec<B256>,
+    /// Hashes of the headers of the blocks after genesis, from the static files.
+    pub header_hashes: Vec<B256>,
+}
+
+impl PersistedChain {
+    pub fn read(datadir: &TempDir) -> eyre::Result<Self> {
+        let chain_spec = chain_value_parser("mainnet")?;
+        let datadir =
+            DatadirArgs { datadir: datadir.path().to_path_buf().into(), ..Default::default() }
+                .resolve_datadir(chain_spec.chain());
+        let db_args = DatabaseArguments::new(ClientVersion::default());
+        let db = Arc::new(reth_db::open_db_read_only(&datadir.db(), db_args)?);
+        let static_files = StaticFileProvider::read_only(datadir.static_files(), false)?;
+        let factory = ProviderFactory::<NodeTypesWithDBAdapter<HlNode, Arc<DatabaseEnv>>>::new(
+            db,
+            chain_spec,
+            static_files,
+        );
+
+        let static_files = [
+            StaticFileSegment::Headers,
+            StaticFileSegment::Transactions,
+            StaticFileSegment::Receipts,
+        ]
+        .map(|segment| factory.static_file_provider().get_highest_static_file_block(segment))
+        .to_vec();
+        let provider = factory.provider()?;
+        let finished = provider.get_stage_checkpoint(StageId::Finish)?.map(|c| c.block_number);
+        let last = finished.unwrap_or_default();
+        let canonical_hashes = provider.canonical_hashes_range(1, last + 1)?;
+        let header_hashes =
+            provider.sealed_headers_range(1..=last)?.iter().map(|header| header.hash()).collect();
+        Ok(Self { finished, static_files, canonical_hashes, header_hashes })
+    }
+}
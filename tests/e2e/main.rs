@@ -0,0 +1,956 @@
+//! End-to-end tests running full nodes in-process against a fixture chain and a mock upstream.
+
+mod fixtures;
+mod harness;
+mod upstream;
+
+use alloy_consensus::{Signed, TxLegacy};
+use alloy_eips::Encodable2718;
+use alloy_primitives::{Address, B256, Bytes, U64, U256, address, keccak256};
+use alloy_rpc_types::{Block, EthCallResponse};
+use alloy_signer::Signature;
+use fixtures::{ChainBuilder, FixtureTx, empty_chain, user};
+use harness::{PersistedChain, TestNodeBuilder};
+use futures::StreamExt;
+use jsonrpsee::{
+    core::client::{ClientT, Error as ClientError, SubscriptionClientT},
+    rpc_params,
+};
+use reth_hl::{
+    chainspec::{MAINNET_CHAIN_ID, TESTNET_CHAIN_ID, parser::chain_value_parser},
+    node::{
+        commands::stream_blocks::write_block_line,
+        network::block_trace::TARGET as BLOCK_TRACE,
+        primitives::TransactionSigned,
+        rpc::errors::{HlErrorCode, TraceTimeoutData},
+        types::{BlockAndReceipts, ReadPrecompileResult},
+    },
+    pseudo_peer::{
+        BlockSourceConfig, PseudoPeerError, RpcBatchConfig, StdinBlockSource, decode_rmp_lz4,
+    },
+};
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{
+    Level, Subscriber,
+    span::{Attributes, Id},
+};
+use tracing_subscriber::{
+    Layer,
+    filter::Targets,
+    layer::{self, SubscriberExt},
+    registry::LookupSpan,
+};
+use upstream::{MockUpstream, UPSTREAM_CALL_RESULT};
+
+const CHAIN_LENGTH: u64 = 5;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn imports_fixture_chain_and_serves_it() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks.clone(), &upstream_url).launch().await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+
+    let (http, ws) = (node.http(), node.ws().await?);
+    for expected in &blocks {
+        let number = U256::from(expected.number());
+        let block: Block = http.request("eth_getBlockByNumber", rpc_params![number, false]).await?;
+        assert_eq!(block.header.hash, expected.hash());
+        assert!(block.transactions.is_empty());
+
+        let by_hash: Block =
+            ws.request("eth_getBlockByHash", rpc_params![expected.hash(), false]).await?;
+        assert_eq!(by_hash.header.number, expected.number());
+    }
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn imports_blocks_piped_as_hl_node_lines() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let mut lines = Vec::new();
+    for block in blocks.clone() {
+        write_block_line(&mut lines, block)?;
+    }
+    let source = StdinBlockSource::from_reader(std::io::Cursor::new(lines));
+
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(vec![], &upstream_url)
+        .with_block_source(BlockSourceConfig::custom(Arc::new(Box::new(source.clone()))))
+        .launch()
+        .await?;
+    assert_eq!(source.finished().await?, Some(CHAIN_LENGTH));
+    node.wait_for_block(CHAIN_LENGTH).await?;
+
+    let last = blocks.last().unwrap();
+    let block: Block =
+        node.http().request("eth_getBlockByNumber", rpc_params!["latest", false]).await?;
+    assert_eq!(block.header.hash, last.hash());
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batched_commits_persist_the_same_chain() -> eyre::Result<()> {
+    const BLOCKS: u64 = 100;
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, BLOCKS);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+
+    let mut persisted = vec![];
+    for batch_size in [1, 10] {
+        let node = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+            .with_import_batch_size(batch_size)
+            .launch()
+            .await?;
+        node.wait_for_block(BLOCKS).await?;
+        let datadir = node.persist_and_shutdown().await?;
+        persisted.push(PersistedChain::read(&datadir)?);
+
+        // Restarted with nothing to import, the node serves the chain from the datadir
+        let node =
+            TestNodeBuilder::new(vec![], &upstream_url).with_datadir(datadir).launch().await?;
+        let head: Block =
+            node.http().request("eth_getBlockByNumber", rpc_params!["latest", false]).await?;
+        assert_eq!(head.header.hash, blocks.last().unwrap().hash());
+        node.shutdown().await?;
+    }
+
+    let hashes: Vec<_> = blocks.iter().map(|block| block.hash()).collect();
+    assert_eq!(persisted[0].finished, Some(BLOCKS));
+    assert_eq!(persisted[0].canonical_hashes, hashes);
+    assert_eq!(persisted[0].header_hashes, hashes);
+    assert_eq!(persisted[0].static_files[0], Some(BLOCKS));
+    assert_eq!(persisted[0], persisted[1]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn safe_and_finalized_tags_trail_the_head() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_arg("--forkchoice.safe-depth=1")
+        .with_arg("--forkchoice.finalized-depth=3")
+        .with_arg("--forkchoice.interval=1")
+        .launch()
+        .await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+
+    // The tags move with the forkchoice update of the head, which may land after the head shows
+    let http = node.http();
+    let tags = [("safe", CHAIN_LENGTH - 1), ("finalized", CHAIN_LENGTH - 3)];
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let mut resolved = Vec::new();
+            for (tag, _) in tags {
+                let block: Option<Block> =
+                    http.request("eth_getBlockByNumber", rpc_params![tag, false]).await?;
+                resolved.push(block.map(|block| (block.header.number, block.header.hash)));
+            }
+            let expected: Vec<_> = tags
+                .iter()
+                .map(|(_, number)| Some((*number, blocks[*number as usize - 1].hash())))
+                .collect();
+            if resolved == expected {
+                return eyre::Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .map_err(|_| eyre::eyre!("safe and finalized tags did not trail the head"))??;
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn forwards_raw_transactions_to_upstream() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, 1);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks, &upstream_url).launch().await?;
+    let http = node.http();
+
+    let tx = Bytes::from_static(b"\x02raw transaction");
+    let hash: B256 = http.request("eth_sendRawTransaction", rpc_params![tx.clone()]).await?;
+    assert_eq!(hash, keccak256(&tx));
+    assert_eq!(upstream.received(), vec![tx.clone()]);
+    // Only the upstream knows the transaction, it never enters the local pool
+    let local: Option<Value> = http.request("eth_getTransactionByHash", rpc_params![hash]).await?;
+    assert!(local.is_none());
+
+    // Upstream errors reach the caller unchanged
+    upstream.reject_with(-32000, "nonce too low");
+    let err = http
+        .request::<B256, _>("eth_sendRawTransaction", rpc_params![tx])
+        .await
+        .expect_err("upstream rejected the transaction");
+    assert!(err.to_string().contains("nonce too low"), "{err}");
+    assert_eq!(upstream.received().len(), 1);
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn forwards_methods_by_policy() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, 1);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks, &upstream_url)
+        .with_arg("--forward.allow=eth_call")
+        .with_arg("--forward.deny=eth_sendRawTransaction")
+        .launch()
+        .await?;
+    node.wait_for_block(1).await?;
+    let http = node.http();
+
+    // Allowed: answered by the upstream
+    let call = json!({ "to": Address::ZERO });
+    let result: Bytes = http.request("eth_call", rpc_params![call, "latest"]).await?;
+    assert_eq!(result, UPSTREAM_CALL_RESULT);
+
+    // Denied: the node decodes the transaction itself, and the upstream never sees it
+    let tx = Bytes::from_static(b"\x02raw transaction");
+    http.request::<B256, _>("eth_sendRawTransaction", rpc_params![tx])
+        .await
+        .expect_err("the node rejects the malformed transaction");
+    assert!(upstream.received().is_empty());
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn call_many_matches_individual_calls() -> eyre::Result<()> {
+    let output = Bytes::from(U256::from(42).to_be_bytes::<32>());
+    let blocks = precompile_call_chain(&output)?;
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks, &upstream_url).launch().await?;
+    node.wait_for_block(2).await?;
+    let http = node.http();
+
+    let block = U256::from(2);
+    let identity = address!("0x0000000000000000000000000000000000000004");
+    let sha256 = address!("0x0000000000000000000000000000000000000002");
+    let calls = [
+        json!({ "to": identity, "input": "0xdeadbeef" }),
+        json!({ "to": sha256, "input": "0x01" }),
+        json!({ "to": Address::ZERO }),
+        // The sender can't pay the value
+        json!({ "from": Address::repeat_byte(0x11), "to": Address::ZERO, "value": "0x1" }),
+        // The call of block 2, replayed from its recorded result
+        json!({
+            "to": PRECOMPILE,
+            "input": Bytes::from_static(&[0x01; 32]),
+            "gas": U256::from(fixtures::PRECOMPILE_CALL_GAS_LIMIT),
+        }),
+    ];
+    let batch: Vec<EthCallResponse> =
+        http.request("hl_callMany", rpc_params![&calls, block]).await?;
+    assert_eq!(batch.len(), calls.len());
+    for (call, result) in calls.iter().zip(&batch) {
+        match http.request::<Bytes, _>("eth_call", rpc_params![call, block]).await {
+            Ok(output) => assert_eq!((&result.value, &result.error), (&Some(output), &None)),
+            Err(ClientError::Call(err)) => {
+                assert_eq!((&result.value, result.error.as_deref()), (&None, Some(err.message())))
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    assert_eq!(batch[0].value, Some(Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef])));
+    assert!(batch[3].error.is_some());
+    assert_eq!(batch[4].value, Some(output));
+
+    // Requests are bounded to a number of calls
+    let too_many = vec![json!({ "to": identity }); 101];
+    let err = http
+        .request::<Vec<EthCallResponse>, _>("hl_callMany", rpc_params![too_many, block])
+        .await
+        .expect_err("too many calls");
+    assert!(err.to_string().contains("too many calls"), "{err}");
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn call_honors_base_fee_override() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks, &upstream_url).launch().await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+    let http = node.http();
+
+    // Returns BASEFEE and GASPRICE, installed and funded through state overrides
+    let contract = Address::repeat_byte(0xcc);
+    let sender = Address::repeat_byte(0x11);
+    let state = json!({
+        contract.to_string(): { "code": "0x485f523a60205260405ff3" },
+        sender.to_string(): { "balance": "0xde0b6b3a7640000" },
+    });
+    let eip1559 = json!({
+        "from": sender,
+        "to": contract,
+        "maxFeePerGas": "0x64",
+        "maxPriorityFeePerGas": "0x1",
+    });
+    let params = |request: &Value, block_overrides: Value| {
+        rpc_params![request, U256::from(2), &state, block_overrides]
+    };
+    let fees = |output: Bytes| {
+        (U256::from_be_slice(&output[..32]), U256::from_be_slice(&output[32..]))
+    };
+
+    // The fixture blocks have no base fee, so the sender pays its priority fee only
+    let output = http.request("eth_call", params(&eip1559, json!({}))).await?;
+    assert_eq!(fees(output), (U256::ZERO, U256::from(1)));
+    let output = http.request("eth_call", params(&eip1559, json!({ "baseFee": "0xa" }))).await?;
+    assert_eq!(fees(output), (U256::from(10), U256::from(11)));
+
+    // Calls without a gas price keep the overridden base fee
+    let free = json!({ "to": contract });
+    let output = http.request("eth_call", params(&free, json!({ "baseFee": "0xa" }))).await?;
+    assert_eq!(fees(output), (U256::from(10), U256::ZERO));
+
+    node.shutdown().await
+}
+
+/// A legacy transaction signed for `chain_id`, with a signature the upstream never checks.
+fn raw_tx_for_chain(chain_id: u64) -> Bytes {
+    let tx = TxLegacy { chain_id: Some(chain_id), gas_limit: 21_000, ..Default::default() };
+    let tx = Signed::new_unhashed(tx, Signature::test_signature());
+    TransactionSigned::Default(tx.into()).encoded_2718().into()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejects_transactions_of_another_chain_before_forwarding() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, 1);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks.clone(), &upstream_url).launch().await?;
+    let http = node.http();
+
+    let tx = raw_tx_for_chain(TESTNET_CHAIN_ID);
+    let err = http
+        .request::<B256, _>("eth_sendRawTransaction", rpc_params![tx])
+        .await
+        .expect_err("the node rejects the transaction");
+    assert!(err.to_string().contains("HyperEVM testnet (998)"), "{err}");
+    assert!(upstream.received().is_empty());
+
+    let tx = raw_tx_for_chain(MAINNET_CHAIN_ID);
+    let hash: B256 = http.request("eth_sendRawTransaction", rpc_params![tx.clone()]).await?;
+    assert_eq!(hash, keccak256(&tx));
+    assert_eq!(upstream.received(), vec![tx]);
+    node.shutdown().await?;
+
+    // Forwarded with a warning
+    let node = TestNodeBuilder::new(blocks, &upstream_url)
+        .with_arg("--forward.chain-id-mismatch=warn")
+        .launch()
+        .await?;
+    let tx = raw_tx_for_chain(TESTNET_CHAIN_ID);
+    node.http().request::<B256, _>("eth_sendRawTransaction", rpc_params![tx.clone()]).await?;
+    assert_eq!(upstream.received().last(), Some(&tx));
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn preconnects_to_upstream_before_the_first_request() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, 1);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    // Without the preflight checks, only the forwarder's client talks to the upstream
+    let node = TestNodeBuilder::new(blocks, &upstream_url)
+        .with_arg("--skip-preflight")
+        .with_arg("--forward-preconnect")
+        .launch()
+        .await?;
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while upstream.chain_id_requests() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .map_err(|_| eyre::eyre!("the forwarder did not connect to the upstream"))?;
+    assert!(upstream.received().is_empty());
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serves_block_state_diffs_when_enabled() -> eyre::Result<()> {
+    let chain_spec = chain_value_parser("mainnet")?;
+    let value = U256::from(1_000);
+    // Block 2 sends HYPE from HyperCore to user 2, then user 1 pays user 3
+    let blocks = ChainBuilder::new(&chain_spec)
+        .block([FixtureTx::NativeTransfer { to: user(1).address(), value: ONE_HYPE }])
+        .block([
+            FixtureTx::NativeTransfer { to: user(2).address(), value: ONE_HYPE },
+            FixtureTx::Transfer { from: user(1), to: user(3).address(), value },
+        ])
+        .empty_blocks(CHAIN_LENGTH - 2)
+        .build();
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_arg("--enable-state-diff-rpc")
+        .launch()
+        .await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+
+    // The system address sent HYPE once in block 1 already, and fees go to the zero address
+    let genesis = |address: Address| {
+        chain_spec.inner.genesis.alloc.get(&address).cloned().unwrap_or_default()
+    };
+    let system = genesis(fixtures::SYSTEM_ADDRESS);
+    let (system_balance, system_nonce) =
+        (system.balance - ONE_HYPE, system.nonce.unwrap_or_default() + 1);
+    let fee = U256::from(fixtures::intrinsic_gas(&[])) * U256::from(fixtures::GAS_PRICE);
+    let zero_balance = genesis(Address::ZERO).balance;
+    let change = |from: U256, to: U256| json!({ "from": from, "to": to });
+    let nonce_change = |from: u64| json!({ "from": U64::from(from), "to": U64::from(from + 1) });
+    let system_diff = BTreeMap::from([
+        (
+            fixtures::SYSTEM_ADDRESS,
+            json!({
+                "balance": change(system_balance, system_balance - ONE_HYPE),
+                "nonce": nonce_change(system_nonce),
+            }),
+        ),
+        (user(2).address(), json!({ "balance": change(U256::ZERO, ONE_HYPE) })),
+    ]);
+    let user_diff = BTreeMap::from([
+        (
+            user(1).address(),
+            json!({
+                "balance": change(ONE_HYPE, ONE_HYPE - value - fee),
+                "nonce": nonce_change(0),
+            }),
+        ),
+        (user(3).address(), json!({ "balance": change(U256::ZERO, value) })),
+        (Address::ZERO, json!({ "balance": change(zero_balance, zero_balance + fee) })),
+    ]);
+
+    let http = node.http();
+    let diffs =
+        [(&blocks[1], json!(system_diff), json!(user_diff)), (&blocks[2], json!({}), json!({}))];
+    for (expected, system_diff, user_diff) in diffs {
+        for block in [json!(U256::from(expected.number())), json!(expected.hash())] {
+            // The second request of each block is served from the cache
+            for _ in 0..2 {
+                let diff: Value = http.request("hl_getBlockStateDiff", rpc_params![&block]).await?;
+                assert_eq!(
+                    diff,
+                    json!({
+                        "blockHash": expected.hash(),
+                        "blockNumber": U256::from(expected.number()),
+                        "system": system_diff,
+                        "user": user_diff,
+                    })
+                );
+            }
+        }
+    }
+    let unknown: Option<Value> =
+        http.request("hl_getBlockStateDiff", rpc_params![U256::from(CHAIN_LENGTH + 1)]).await?;
+    assert_eq!(unknown, None);
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serves_raw_blocks_in_the_sync_format() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks.clone(), &upstream_url).launch().await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+
+    let http = node.http();
+    let expected = &blocks[2];
+    let raw: Option<Bytes> = http
+        .request("hl_getRawBlockAndReceipts", rpc_params![U256::from(expected.number())])
+        .await?;
+    let decoded = decode_rmp_lz4(&raw.expect("the block is stored"))?;
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].hash(), expected.hash());
+    assert_eq!(decoded[0].number(), expected.number());
+
+    let unknown: Option<Bytes> = http
+        .request("hl_getRawBlockAndReceipts", rpc_params![U256::from(CHAIN_LENGTH + 1)])
+        .await?;
+    assert_eq!(unknown, None);
+
+    node.shutdown().await
+}
+
+/// The serving node imports one block after another, pushing each to its subscribers, among them
+/// a follower syncing from it over WebSocket.
+#[tokio::test(flavor = "multi_thread")]
+async fn follower_syncs_from_blocks_pushed_over_websocket() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let (input, mut lines) = tokio::io::duplex(1 << 20);
+    let source = StdinBlockSource::from_reader(input);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let server = TestNodeBuilder::new(vec![], &upstream_url)
+        .with_arg("--enable-sync-server")
+        .with_import_batch_size(1)
+        .with_block_source(BlockSourceConfig::custom(Arc::new(Box::new(source))))
+        .launch()
+        .await?;
+    let write_block = |block: &BlockAndReceipts| {
+        let mut line = Vec::new();
+        write_block_line(&mut line, block.clone()).map(|()| line)
+    };
+    lines.write_all(&write_block(&blocks[0])?).await?;
+    server.wait_for_block(1).await?;
+
+    let ws = server.ws().await?;
+    let mut pushed = ws
+        .subscribe::<Bytes, _>("hl_subscribeBlocks", rpc_params![], "hl_unsubscribeBlocks")
+        .await?;
+    let follower = TestNodeBuilder::new(vec![], &upstream_url)
+        .with_block_source(BlockSourceConfig::rpc(
+            server.ws_url(),
+            Duration::from_millis(100),
+            RpcBatchConfig::default(),
+        ))
+        .launch()
+        .await?;
+    follower.wait_for_block(1).await?;
+
+    for block in &blocks[1..] {
+        lines.write_all(&write_block(block)?).await?;
+        let next = tokio::time::timeout(Duration::from_secs(30), pushed.next()).await?;
+        let decoded = decode_rmp_lz4(&next.expect("subscription is open")?)?;
+        assert_eq!(decoded.iter().map(|block| block.hash()).collect::<Vec<_>>(), [block.hash()]);
+    }
+    follower.wait_for_block(CHAIN_LENGTH).await?;
+    let head: Block =
+        follower.http().request("eth_getBlockByNumber", rpc_params!["latest", false]).await?;
+    assert_eq!(head.header.hash, blocks.last().unwrap().hash());
+
+    follower.shutdown().await?;
+    server.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compliant_node_serves_hl_node_block_shape() -> eyre::Result<()> {
+    // The last block has two system transactions and a user transaction
+    let blocks = ChainBuilder::new(&chain_value_parser("mainnet")?)
+        .empty_blocks(CHAIN_LENGTH - 2)
+        .block([FixtureTx::NativeTransfer { to: user(1).address(), value: ONE_HYPE }])
+        .block([
+            FixtureTx::NativeTransfer { to: user(2).address(), value: ONE_HYPE },
+            FixtureTx::NativeTransfer { to: user(3).address(), value: ONE_HYPE },
+            FixtureTx::Transfer { from: user(1), to: user(2).address(), value: U256::from(1) },
+        ])
+        .build();
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    // Two nodes in one process, fed the same chain
+    let compliant = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_arg("--hl-node-compliant")
+        .launch();
+    let regular = TestNodeBuilder::new(blocks.clone(), &upstream_url).launch();
+    let (compliant, regular) = tokio::try_join!(compliant, regular)?;
+    tokio::try_join!(compliant.wait_for_block(CHAIN_LENGTH), regular.wait_for_block(CHAIN_LENGTH))?;
+
+    let number = U256::from(CHAIN_LENGTH);
+    let (compliant_http, regular_http) = (compliant.http(), regular.http());
+    let compliant_block: Block =
+        compliant_http.request("eth_getBlockByNumber", rpc_params![number, true]).await?;
+    let regular_block: Block =
+        regular_http.request("eth_getBlockByNumber", rpc_params![number, true]).await?;
+    assert_eq!(compliant_block.header.hash, regular_block.header.hash);
+    assert_eq!(compliant_block.header.hash, blocks.last().unwrap().hash());
+    assert_eq!((compliant_block.transactions.len(), regular_block.transactions.len()), (1, 3));
+    // Counts agree with the transactions each mode returns
+    for (http, block) in [(&compliant_http, &compliant_block), (&regular_http, &regular_block)] {
+        let count: U256 =
+            http.request("eth_getBlockTransactionCountByNumber", rpc_params![number]).await?;
+        assert_eq!(count, U256::from(block.transactions.len()));
+        let count: U256 = http
+            .request("eth_getBlockTransactionCountByHash", rpc_params![block.header.hash])
+            .await?;
+        assert_eq!(count, U256::from(block.transactions.len()));
+        let uncles: U256 =
+            http.request("eth_getUncleCountByBlockNumber", rpc_params![number]).await?;
+        assert_eq!(uncles, U256::ZERO);
+    }
+
+    // The hl-node system transaction endpoints only exist in compliant mode
+    let system_txs: Option<Vec<Value>> =
+        compliant_http.request("eth_getEvmSystemTxsByBlockNumber", rpc_params![number]).await?;
+    assert_eq!(system_txs.map(|txs| txs.len()), Some(2));
+    let receipts: Value =
+        compliant_http.request("eth_getBlockReceiptsWithSystemTx", rpc_params![number]).await?;
+    assert_eq!(receipts["receipts"].as_array().map(Vec::len), Some(1), "{receipts}");
+    assert_eq!(receipts["systemTxReceipts"].as_array().map(Vec::len), Some(2), "{receipts}");
+    assert!(
+        regular_http
+            .request::<Value, _>("eth_getEvmSystemTxsByBlockNumber", rpc_params![number])
+            .await
+            .is_err()
+    );
+
+    tokio::try_join!(compliant.shutdown(), regular.shutdown())?;
+    Ok(())
+}
+
+/// The read precompile called by [`precompile_call_chain`].
+const PRECOMPILE: Address = address!("0x0000000000000000000000000000000000000801");
+const ONE_HYPE: U256 = U256::from_limbs([1_000_000_000_000_000_000, 0, 0, 0]);
+
+/// Block 1 funds [`user`] 1 from HyperCore. Block 2 sends HYPE from HyperCore to user 2, then
+/// user 1 calls [`PRECOMPILE`], which answers `output` as recorded.
+fn precompile_call_chain(output: &Bytes) -> eyre::Result<Vec<BlockAndReceipts>> {
+    Ok(ChainBuilder::new(&chain_value_parser("mainnet")?)
+        .block([FixtureTx::NativeTransfer { to: user(1).address(), value: ONE_HYPE }])
+        .block([
+            FixtureTx::NativeTransfer { to: user(2).address(), value: ONE_HYPE },
+            FixtureTx::PrecompileCall {
+                from: user(1),
+                precompile: PRECOMPILE,
+                input: Bytes::from_static(&[0x01; 32]),
+                result: ReadPrecompileResult::Ok { gas_used: 100, bytes: output.clone() },
+            },
+        ])
+        .build())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn traces_system_txs_and_read_precompile_calls() -> eyre::Result<()> {
+    let output = Bytes::from(U256::from(42).to_be_bytes::<32>());
+    let blocks = precompile_call_chain(&output)?;
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let compliant = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_http_api("eth,trace")
+        .with_arg("--hl-node-compliant")
+        .launch();
+    let regular = TestNodeBuilder::new(blocks, &upstream_url).with_http_api("eth,trace").launch();
+    let (compliant, regular) = tokio::try_join!(compliant, regular)?;
+    tokio::try_join!(compliant.wait_for_block(2), regular.wait_for_block(2))?;
+
+    for (node, system_tx_count) in [(&regular, 1), (&compliant, 0)] {
+        let traces: Vec<Value> = node.http().request("trace_block", rpc_params!["0x2"]).await?;
+        let (system, calls): (Vec<_>, Vec<_>) =
+            traces.iter().partition(|trace| trace["systemTx"] == json!(true));
+        // Flagged in regular mode, left out in compliant mode
+        assert_eq!(system.len(), system_tx_count, "{traces:?}");
+        for trace in system {
+            assert_eq!(trace["action"]["from"], json!(fixtures::SYSTEM_ADDRESS));
+            assert_eq!(trace["action"]["to"], json!(user(2).address()));
+            assert_eq!(trace["transactionPosition"], json!(0));
+        }
+        // Replayed against the recorded result, at its user-only position in compliant mode
+        let [call] = calls.as_slice() else { panic!("one precompile call trace: {traces:?}") };
+        assert_eq!(call["action"]["to"], json!(PRECOMPILE));
+        assert_eq!(call["result"]["output"], json!(output));
+        assert_eq!(call.get("error"), None);
+        assert_eq!(call["transactionPosition"], json!(system_tx_count));
+    }
+
+    tokio::try_join!(compliant.shutdown(), regular.shutdown())?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn methods_agree_on_system_txs_in_both_modes() -> eyre::Result<()> {
+    let output = Bytes::from(U256::from(42).to_be_bytes::<32>());
+    let blocks = precompile_call_chain(&output)?;
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let hidden = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_arg("--hl-node-compliant")
+        .launch();
+    let exposed = TestNodeBuilder::new(blocks, &upstream_url)
+        .with_arg("--hl-node-compliant")
+        .with_arg("--expose-system-txs")
+        .launch();
+    let (hidden, exposed) = tokio::try_join!(hidden, exposed)?;
+    tokio::try_join!(hidden.wait_for_block(2), exposed.wait_for_block(2))?;
+
+    // Block 2 has one system transaction before the precompile call
+    for (node, system_tx_count) in [(&hidden, 0), (&exposed, 1)] {
+        let http = node.http();
+        let block: Value = http.request("eth_getBlockByNumber", rpc_params!["0x2", true]).await?;
+        let transactions = block["transactions"].as_array().expect("full transactions");
+        assert_eq!(transactions.len(), system_tx_count + 1, "{block}");
+        let receipts: Vec<Value> =
+            http.request("eth_getBlockReceipts", rpc_params!["0x2"]).await?;
+        assert_eq!(receipts.len(), transactions.len(), "{receipts:?}");
+        for (index, (tx, receipt)) in transactions.iter().zip(&receipts).enumerate() {
+            let index = U256::from(index);
+            assert_eq!(tx["transactionIndex"], json!(index));
+            assert_eq!(receipt["transactionHash"], tx["hash"]);
+            assert_eq!(receipt["transactionIndex"], json!(index));
+            let by_number: Value = http
+                .request("eth_getTransactionByBlockNumberAndIndex", rpc_params!["0x2", index])
+                .await?;
+            let params = rpc_params![&block["hash"], index];
+            let by_hash: Value =
+                http.request("eth_getTransactionByBlockHashAndIndex", params).await?;
+            for found in [&by_number, &by_hash] {
+                assert_eq!(found["hash"], tx["hash"]);
+                assert_eq!(found["transactionIndex"], json!(index));
+            }
+            let receipt: Value =
+                http.request("eth_getTransactionReceipt", rpc_params![&tx["hash"]]).await?;
+            assert_eq!(receipt["transactionIndex"], json!(index));
+        }
+        let past_the_end: Value = http
+            .request(
+                "eth_getTransactionByBlockNumberAndIndex",
+                rpc_params!["0x2", U256::from(transactions.len())],
+            )
+            .await?;
+        assert_eq!(past_the_end, Value::Null);
+    }
+
+    tokio::try_join!(hidden.shutdown(), exposed.shutdown())?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn receipts_carry_the_gas_of_read_precompile_calls() -> eyre::Result<()> {
+    let output = Bytes::from(U256::from(42).to_be_bytes::<32>());
+    let blocks = precompile_call_chain(&output)?;
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let hidden = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_arg("--hl-node-compliant")
+        .launch();
+    let exposed = TestNodeBuilder::new(blocks, &upstream_url).launch();
+    let (hidden, exposed) = tokio::try_join!(hidden, exposed)?;
+    tokio::try_join!(hidden.wait_for_block(2), exposed.wait_for_block(2))?;
+
+    let block: Value =
+        exposed.http().request("eth_getBlockByNumber", rpc_params!["0x2", true]).await?;
+    let [system_tx, call] = block["transactions"].as_array().expect("full transactions").as_slice()
+    else {
+        panic!("a system transaction and a precompile call: {block}");
+    };
+
+    for (node, system_tx_count) in [(&hidden, 0), (&exposed, 1)] {
+        let http = node.http();
+        let mut receipt: Value =
+            http.request("hl_getTransactionReceipt", rpc_params![&call["hash"]]).await?;
+        assert_eq!(receipt["precompileGasUsed"], json!("0x64"), "{receipt}");
+        assert_eq!(receipt["transactionIndex"], json!(U256::from(system_tx_count)));
+        // The rest is the receipt of `eth_getTransactionReceipt`
+        receipt.as_object_mut().unwrap().remove("precompileGasUsed");
+        let eth_receipt: Value =
+            http.request("eth_getTransactionReceipt", rpc_params![&call["hash"]]).await?;
+        assert_eq!(receipt, eth_receipt);
+
+        // System transactions call no read precompile, and have no receipt when hidden
+        let receipt: Value =
+            http.request("hl_getTransactionReceipt", rpc_params![&system_tx["hash"]]).await?;
+        if system_tx_count == 0 {
+            assert_eq!(receipt, Value::Null);
+        } else {
+            assert_eq!(receipt["precompileGasUsed"], json!("0x0"), "{receipt}");
+        }
+    }
+
+    tokio::try_join!(hidden.shutdown(), exposed.shutdown())?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn traces_system_txs_alone_where_debug_is_enabled() -> eyre::Result<()> {
+    // Block 2 sends HYPE from HyperCore to users 2 and 3, then user 1 pays user 4
+    let blocks = ChainBuilder::new(&chain_value_parser("mainnet")?)
+        .block([FixtureTx::NativeTransfer { to: user(1).address(), value: ONE_HYPE }])
+        .block([
+            FixtureTx::NativeTransfer { to: user(2).address(), value: ONE_HYPE },
+            FixtureTx::NativeTransfer { to: user(3).address(), value: U256::from(1_000) },
+            FixtureTx::Transfer { from: user(1), to: user(4).address(), value: U256::from(1) },
+        ])
+        .build();
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let debug = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .with_http_api("eth,debug")
+        .launch();
+    let regular = TestNodeBuilder::new(blocks, &upstream_url).with_http_api("eth").launch();
+    let (debug, regular) = tokio::try_join!(debug, regular)?;
+    tokio::try_join!(debug.wait_for_block(2), regular.wait_for_block(2))?;
+
+    let opts = json!({ "tracer": "callTracer" });
+    let trace: Value =
+        debug.http().request("hl_traceSystemBlockExecution", rpc_params!["0x2", &opts]).await?;
+    // The system transactions, in block order, and not the user transfer after them
+    let traces = trace["traces"].as_array().expect("traces");
+    let calls = traces
+        .iter()
+        .map(|trace| (trace["result"]["from"].clone(), trace["result"]["to"].clone()))
+        .collect::<Vec<_>>();
+    let system = json!(fixtures::SYSTEM_ADDRESS);
+    assert_eq!(
+        calls,
+        [(system.clone(), json!(user(2).address())), (system, json!(user(3).address()))],
+        "{trace}"
+    );
+    let state_diff: BTreeMap<Address, Value> = serde_json::from_value(trace["stateDiff"].clone())?;
+    assert_eq!(state_diff[&user(2).address()]["balance"]["to"], json!(ONE_HYPE), "{trace}");
+    assert!(!state_diff.contains_key(&user(4).address()), "{trace}");
+
+    let err = regular
+        .http()
+        .request::<Value, _>("hl_traceSystemBlockExecution", rpc_params!["0x2", &opts])
+        .await
+        .expect_err("served only with the debug namespace");
+    assert!(matches!(err, ClientError::Call(err) if err.code() == -32601), "{err}");
+
+    tokio::try_join!(debug.shutdown(), regular.shutdown())?;
+    Ok(())
+}
+
+/// `debug_traceBlockByNumber` of a block of 50,000 system transactions, whose replay runs on the
+/// node's tracing pool and checks the deadline of the request between transactions.
+#[tokio::test(flavor = "multi_thread")]
+async fn timed_out_trace_of_a_large_block_fails_and_frees_the_tracer() -> eyre::Result<()> {
+    const SYSTEM_TXS: usize = 50_000;
+    let transfer = FixtureTx::NativeTransfer { to: user(1).address(), value: U256::from(1) };
+    let blocks = ChainBuilder::new(&chain_value_parser("mainnet")?)
+        .block(std::iter::repeat_n(transfer, SYSTEM_TXS))
+        .empty_blocks(1)
+        .build();
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks, &upstream_url)
+        .with_http_api("eth,debug")
+        .with_arg("--network.max-block-transactions=100000")
+        .with_arg("--rpc.max-tracing-requests=1")
+        .launch()
+        .await?;
+    node.wait_for_block(2).await?;
+    let http = node.http();
+
+    let started = Instant::now();
+    let opts = json!({ "tracer": "callTracer", "timeout": "100ms" });
+    let err = http
+        .request::<Vec<Value>, _>("debug_traceBlockByNumber", rpc_params!["0x1", opts])
+        .await
+        .expect_err("the trace times out");
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    let ClientError::Call(err) = err else { panic!("unexpected error {err}") };
+    assert_eq!(err.code(), HlErrorCode::TraceTimeout.code());
+    let data: TraceTimeoutData = serde_json::from_str(err.data().expect("timeout data").get())?;
+    assert_eq!(data, TraceTimeoutData { timeout_ms: 100 });
+
+    // The only tracing permit was released with the request
+    let opts = json!({ "tracer": "callTracer", "timeout": "1s" });
+    let traces: Vec<Value> =
+        http.request("debug_traceBlockByNumber", rpc_params!["0x2", opts]).await?;
+    assert!(traces.is_empty());
+
+    // Traced in full, the block takes longer than the timeout
+    let started = Instant::now();
+    let opts = json!({ "tracer": "callTracer" });
+    let traces: Vec<Value> =
+        http.request("debug_traceBlockByNumber", rpc_params!["0x1", opts]).await?;
+    assert_eq!(traces.len(), SYSTEM_TXS);
+    assert!(started.elapsed() > Duration::from_millis(100), "{:?}", started.elapsed());
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fatal_source_error_exits_the_node() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let mut node =
+        TestNodeBuilder::new(blocks, &upstream_url).with_fatal_error_at(3).launch().await?;
+    node.wait_for_block(2).await?;
+
+    let err = node.wait_for_exit().await?.expect_err("node exits with the source error");
+    let err = err.downcast_ref::<PseudoPeerError>().expect("pseudo peer error");
+    assert!(matches!(err, PseudoPeerError::Source { height: 3, .. }), "{err}");
+    assert_eq!(err.exit_code(), 69);
+
+    node.shutdown().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn preflight_rejects_upstream_of_another_chain() -> eyre::Result<()> {
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default().with_chain_id(TESTNET_CHAIN_ID);
+    let (upstream_url, _upstream) = upstream.start().await?;
+
+    let err = TestNodeBuilder::new(blocks.clone(), &upstream_url)
+        .launch()
+        .await
+        .err()
+        .expect("launch fails preflight");
+    let err = err.to_string();
+    assert!(err.contains("preflight checks failed"), "{err}");
+    assert!(err.contains(&format!("serves chain id {TESTNET_CHAIN_ID}")), "{err}");
+
+    // Skipping the checks starts the node anyway
+    let node =
+        TestNodeBuilder::new(blocks, &upstream_url).with_arg("--skip-preflight").launch().await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+    node.shutdown().await
+}
+
+/// Records the id, name and parent id of the spans opened.
+#[derive(Clone, Default)]
+struct SpanTree(Arc<Mutex<Vec<(u64, &'static str, Option<u64>)>>>);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanTree {
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: layer::Context<'_, S>) {
+        let span = ctx.span(id).expect("new span");
+        let parent = span.parent().map(|parent| parent.id().into_u64());
+        self.0.lock().unwrap().push((id.into_u64(), span.name(), parent));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn traces_blocks_from_the_source_to_their_import() -> eyre::Result<()> {
+    // The only test installing a subscriber, which records the block spans of the nodes of the
+    // other tests too
+    let spans = SpanTree::default();
+    let filter = Targets::new().with_target(BLOCK_TRACE, Level::TRACE);
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry().with(spans.clone().with_filter(filter)),
+    )?;
+
+    let blocks = empty_chain(&chain_value_parser("mainnet")?, CHAIN_LENGTH);
+    let upstream = MockUpstream::default();
+    let (upstream_url, _upstream) = upstream.start().await?;
+    let node = TestNodeBuilder::new(blocks, &upstream_url).launch().await?;
+    node.wait_for_block(CHAIN_LENGTH).await?;
+
+    // The import of the blocks announced by the pseudo peer is traced under their block span,
+    // after the steps the block went through before crossing devp2p
+    let spans = spans.0.lock().unwrap().clone();
+    let traced = spans.iter().filter(|(_, name, _)| *name == "block").any(|&(block, _, _)| {
+        let mut children = spans.iter().filter(|(_, _, parent)| *parent == Some(block));
+        ["fetch", "convert", "send", "announce", "import"]
+            .iter()
+            .all(|step| children.any(|(_, name, _)| name == step))
+    });
+    assert!(traced, "no block traced from its source to its import: {spans:?}");
+
+    node.shutdown().await
+}
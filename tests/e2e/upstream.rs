@@ -0,0 +1,111 @@
+//! A programmable stand-in for the upstream RPC that transactions are forwarded to.
+
+use alloy_primitives::{B256, Bytes, U64, keccak256};
+use jsonrpsee::{
+    core::{RpcResult, async_trait},
+    proc_macros::rpc,
+    server::{Server, ServerHandle},
+    types::ErrorObject,
+};
+use reth_hl::chainspec::MAINNET_CHAIN_ID;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// Result of every `eth_call` answered by the upstream.
+pub const UPSTREAM_CALL_RESULT: Bytes = Bytes::from_static(b"upstream");
+
+#[rpc(server, namespace = "eth")]
+pub trait MockUpstreamApi {
+    #[method(name = "chainId")]
+    async fn chain_id(&self) -> RpcResult<U64>;
+
+    #[method(name = "sendRawTransaction")]
+    async fn send_raw_transaction(&self, tx: Bytes) -> RpcResult<B256>;
+
+    #[method(name = "call")]
+    async fn call(
+        &self,
+        request: Value,
+        block_id: Option<Value>,
+        state_overrides: Option<Value>,
+        block_overrides: Option<Value>,
+    ) -> RpcResult<Bytes>;
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Chain id reported instead of mainnet's.
+    chain_id: Option<u64>,
+    /// Number of `eth_chainId` requests answered.
+    chain_id_requests: usize,
+    /// Raw transactions received, in order.
+    received: Vec<Bytes>,
+    /// Error returned for the next transactions instead of accepting them.
+    rejection: Option<(i32, String)>,
+}
+
+/// Upstream RPC server of mainnet accepting raw transactions, or rejecting them once told to.
+///
+/// Accepted transactions are answered with the hash of their encoding, as a real node would.
+#[derive(Debug, Clone, Default)]
+pub struct MockUpstream {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockUpstream {
+    /// Starts the server, returning its URL and a handle that stops it when dropped.
+    pub async fn start(&self) -> eyre::Result<(String, ServerHandle)> {
+        let server = Server::builder().build("127.0.0.1:0").await?;
+        let url = format!("http://{}", server.local_addr()?);
+        Ok((url, server.start(self.clone().into_rpc())))
+    }
+
+    /// Number of `eth_chainId` requests answered so far, e.g. preflight checks and pings.
+    pub fn chain_id_requests(&self) -> usize {
+        self.state.lock().unwrap().chain_id_requests
+    }
+
+    /// Raw transactions received so far.
+    pub fn received(&self) -> Vec<Bytes> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Reports `chain_id` as the chain it serves.
+    pub fn with_chain_id(self, chain_id: u64) -> Self {
+        self.state.lock().unwrap().chain_id = Some(chain_id);
+        self
+    }
+
+    /// Rejects the following transactions with the given JSON-RPC error.
+    pub fn reject_with(&self, code: i32, message: &str) {
+        self.state.lock().unwrap().rejection = Some((code, message.to_string()));
+    }
+}
+
+#[async_trait]
+impl MockUpstreamApiServer for MockUpstream {
+    async fn chain_id(&self) -> RpcResult<U64> {
+        let mut state = self.state.lock().unwrap();
+        state.chain_id_requests += 1;
+        Ok(U64::from(state.chain_id.unwrap_or(MAINNET_CHAIN_ID)))
+    }
+
+    async fn send_raw_transaction(&self, tx: Bytes) -> RpcResult<B256> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((code, message)) = &state.rejection {
+            return Err(ErrorObject::owned(*code, message.clone(), None::<()>));
+        }
+        let hash = keccak256(&tx);
+        state.received.push(tx);
+        Ok(hash)
+    }
+    async fn call(
+        &self,
+        _request: Value,
+        _block_id: Option<Value>,
+        _state_overrides: Option<Value>,
+        _block_overrides: Option<Value>,
+    ) -> RpcResult<Bytes> {
+        Ok(UPSTREAM_CALL_RESULT)
+    }
+}